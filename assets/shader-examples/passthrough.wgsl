@@ -9,7 +9,9 @@
 struct PostProcessUniform {
     resolution: vec2<f32>,
     time: f32,
-    _padding: f32,
+    // From 0.0 to 1.0 while a visual bell whose target is
+    // "PostProcess" is ringing
+    bell_intensity: f32,
 };
 
 @group(0) @binding(0) var<uniform> uniforms: PostProcessUniform;
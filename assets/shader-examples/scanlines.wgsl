@@ -8,7 +8,7 @@
 struct PostProcessUniform {
     resolution: vec2<f32>,
     time: f32,
-    _padding: f32,
+    bell_intensity: f32,
 };
 
 @group(0) @binding(0) var<uniform> uniforms: PostProcessUniform;
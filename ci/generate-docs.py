@@ -463,6 +463,7 @@ TOC = [
         "cli/general.md",
         children=[
             Gen("wezterm cli", "cli/cli"),
+            Page("wezterm config-schema", "cli/config-schema.md"),
             Page("wezterm connect", "cli/connect.md"),
             Page("wezterm imgcat", "cli/imgcat.md"),
             Page("wezterm ls-fonts", "cli/ls-fonts.md"),
@@ -473,6 +474,7 @@ TOC = [
             Page("wezterm show-keys", "cli/show-keys.md"),
             Page("wezterm ssh", "cli/ssh.md"),
             Page("wezterm start", "cli/start.md"),
+            Page("wezterm test-hyperlink-rules", "cli/test-hyperlink-rules.md"),
         ],
     ),
     Page(
@@ -13,6 +13,7 @@
 
 use anyhow::{bail, Context as _, Error};
 use config::keyassignment::{PaneDirection, ScrollbackEraseMode};
+use mux::capture::CapturedRegion;
 use mux::client::{ClientId, ClientInfo};
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
@@ -374,6 +375,9 @@ macro_rules! pdu {
                 }
             }
 
+            /// The names of every type of Pdu, excluding Invalid
+            pub const PDU_NAMES: &'static [&'static str] = &[$(stringify!($name),)*];
+
             pub fn pdu_name(&self) -> &'static str {
                 match self {
                     Pdu::Invalid{..} => "Invalid",
@@ -441,7 +445,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 45;
+pub const CODEC_VERSION: usize = 50;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -502,9 +506,97 @@ pdu! {
     GetPaneDirection: 60,
     GetPaneDirectionResponse: 61,
     AdjustPaneSize: 62,
-}
+    PermissionDenied: 63,
+    CapturePaneRegion: 64,
+    CapturePaneRegionResponse: 65,
+}
+
+/// Classifies what a Pdu does when it is received by the server,
+/// so that the server can reject the requests that read-only
+/// clients are not permitted to make
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PduAccess {
+    /// Queries state without changing anything
+    Read,
+    /// Changes the state of the mux, or sends input to a pane
+    Write,
+    /// A response or notification that is only sent by the server
+    Response,
+}
+
+/// The access classification of each type of Pdu, by name.
+/// Every Pdu must have an entry here; unclassified Pdus are
+/// treated as `Write`.
+pub const PDU_ACCESS: &[(&str, PduAccess)] = &[
+    ("ErrorResponse", PduAccess::Response),
+    ("Ping", PduAccess::Read),
+    ("Pong", PduAccess::Response),
+    ("ListPanes", PduAccess::Read),
+    ("ListPanesResponse", PduAccess::Response),
+    ("SpawnResponse", PduAccess::Response),
+    ("WriteToPane", PduAccess::Write),
+    ("UnitResponse", PduAccess::Response),
+    ("SendKeyDown", PduAccess::Write),
+    ("SendMouseEvent", PduAccess::Write),
+    ("SendPaste", PduAccess::Write),
+    ("Resize", PduAccess::Write),
+    ("SetClipboard", PduAccess::Response),
+    ("GetLines", PduAccess::Read),
+    ("GetLinesResponse", PduAccess::Response),
+    ("GetPaneRenderChanges", PduAccess::Read),
+    ("GetPaneRenderChangesResponse", PduAccess::Response),
+    ("GetCodecVersion", PduAccess::Read),
+    ("GetCodecVersionResponse", PduAccess::Response),
+    ("GetTlsCreds", PduAccess::Read),
+    ("GetTlsCredsResponse", PduAccess::Response),
+    ("LivenessResponse", PduAccess::Response),
+    ("SearchScrollbackRequest", PduAccess::Read),
+    ("SearchScrollbackResponse", PduAccess::Response),
+    ("SetPaneZoomed", PduAccess::Write),
+    ("SplitPane", PduAccess::Write),
+    ("KillPane", PduAccess::Write),
+    ("SpawnV2", PduAccess::Write),
+    ("PaneRemoved", PduAccess::Response),
+    ("SetPalette", PduAccess::Write),
+    ("NotifyAlert", PduAccess::Response),
+    ("SetClientId", PduAccess::Read),
+    ("GetClientList", PduAccess::Read),
+    ("GetClientListResponse", PduAccess::Response),
+    ("SetWindowWorkspace", PduAccess::Write),
+    ("WindowWorkspaceChanged", PduAccess::Response),
+    ("SetFocusedPane", PduAccess::Write),
+    ("GetImageCell", PduAccess::Read),
+    ("GetImageCellResponse", PduAccess::Response),
+    ("MovePaneToNewTab", PduAccess::Write),
+    ("MovePaneToNewTabResponse", PduAccess::Response),
+    ("ActivatePaneDirection", PduAccess::Write),
+    ("GetPaneRenderableDimensions", PduAccess::Read),
+    ("GetPaneRenderableDimensionsResponse", PduAccess::Response),
+    ("PaneFocused", PduAccess::Response),
+    ("TabResized", PduAccess::Response),
+    ("TabAddedToWindow", PduAccess::Response),
+    ("TabTitleChanged", PduAccess::Write),
+    ("WindowTitleChanged", PduAccess::Write),
+    ("RenameWorkspace", PduAccess::Write),
+    ("EraseScrollbackRequest", PduAccess::Write),
+    ("GetPaneDirection", PduAccess::Read),
+    ("GetPaneDirectionResponse", PduAccess::Response),
+    ("AdjustPaneSize", PduAccess::Write),
+    ("PermissionDenied", PduAccess::Response),
+    ("CapturePaneRegion", PduAccess::Read),
+    ("CapturePaneRegionResponse", PduAccess::Response),
+];
 
 impl Pdu {
+    pub fn access(&self) -> PduAccess {
+        let name = self.pdu_name();
+        PDU_ACCESS
+            .iter()
+            .find(|(pdu_name, _)| *pdu_name == name)
+            .map(|(_, access)| *access)
+            .unwrap_or(PduAccess::Write)
+    }
+
     /// Returns true if this type of Pdu represents action taken
     /// directly by a user, rather than background traffic on
     /// a live connection
@@ -609,6 +701,14 @@ pub struct ErrorResponse {
     pub reason: String,
 }
 
+/// Sent in place of the response to a request that a read-only
+/// client is not permitted to make
+#[derive(Error, Deserialize, Serialize, PartialEq, Debug)]
+#[error("{pdu_name} is not permitted for a read-only client")]
+pub struct PermissionDenied {
+    pub pdu_name: String,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetCodecVersion {}
 
@@ -834,6 +934,7 @@ pub struct WindowWorkspaceChanged {
 pub struct SetClientId {
     pub client_id: ClientId,
     pub is_proxy: bool,
+    pub read_only: bool,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -1109,6 +1210,19 @@ pub struct GetLinesResponse {
     pub lines: SerializedLines,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CapturePaneRegion {
+    pub pane_id: PaneId,
+    pub range: Range<StableRowIndex>,
+    pub include_styles: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CapturePaneRegionResponse {
+    pub pane_id: PaneId,
+    pub region: CapturedRegion,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct EraseScrollbackRequest {
     pub pane_id: PaneId,
@@ -1250,6 +1364,41 @@ mod test {
         );
     }
 
+    #[test]
+    fn every_pdu_has_an_access_classification() {
+        for name in Pdu::PDU_NAMES {
+            assert!(
+                PDU_ACCESS.iter().any(|(pdu_name, _)| pdu_name == name),
+                "{name} must be classified in PDU_ACCESS"
+            );
+        }
+        for (name, _) in PDU_ACCESS {
+            assert!(
+                Pdu::PDU_NAMES.contains(name),
+                "PDU_ACCESS has an entry for unknown pdu {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_only_classification() {
+        assert_eq!(Pdu::Ping(Ping {}).access(), PduAccess::Read);
+        assert_eq!(Pdu::Pong(Pong {}).access(), PduAccess::Response);
+        assert_eq!(
+            Pdu::KillPane(KillPane { pane_id: 0 }).access(),
+            PduAccess::Write
+        );
+        assert_eq!(
+            Pdu::WriteToPane(WriteToPane {
+                pane_id: 0,
+                data: vec![]
+            })
+            .access(),
+            PduAccess::Write
+        );
+        assert_eq!(Pdu::Invalid { ident: 0 }.access(), PduAccess::Write);
+    }
+
     #[test]
     fn test_bogus_pdu() {
         let mut encoded = Vec::new();
@@ -899,6 +899,7 @@ impl FromStr for SrgbaTuple {
 
 /// A pixel value encoded as linear RGBA values in f32 format (range: 0.0-1.0)
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct LinearRgba(pub f32, pub f32, pub f32, pub f32);
 
 impl Eq for LinearRgba {}
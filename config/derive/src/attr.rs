@@ -98,6 +98,12 @@ pub struct FieldInfo<'a> {
     pub validate: Option<Path>,
     pub doc: String,
     pub container_type: ContainerType,
+    /// The type held by the container, or the field type if
+    /// it is not in a container
+    pub inner_type: Type,
+    /// Set by `#[config(nested)]`: the field type is itself a
+    /// section of the configuration that implements ConfigMeta
+    pub nested: bool,
 }
 
 #[derive(Debug)]
@@ -115,9 +121,28 @@ impl<'a> FieldInfo<'a> {
         let type_name = &self.type_name;
         let container_type = Ident::new(&format!("{:?}", self.container_type), Span::call_site());
         let get_default = match self.compute_default() {
-            Some(def) => quote!(Some(|| #def.to_dynamic())),
+            Some(def) => quote!(Some(|| phaedra_dynamic::ToDynamic::to_dynamic(&#def))),
             None => quote!(None),
         };
+        let deprecated = match &self.deprecated {
+            Some(reason) => quote!(Some(#reason)),
+            None => quote!(None),
+        };
+        // Fields that convert via another type accept whatever that
+        // type accepts
+        let possible_values = match &self.try_from {
+            Some(try_from) => quote!(crate::meta::variant_names::<#try_from>),
+            None => {
+                let inner_type = &self.inner_type;
+                quote!(crate::meta::variant_names::<#inner_type>)
+            }
+        };
+        let fields = if self.nested {
+            let ty = &self.field.ty;
+            quote!(<#ty as crate::meta::ConfigMeta>::OPTIONS)
+        } else {
+            quote!(&[])
+        };
         quote!(
             crate::meta::ConfigOption {
                 name: #name,
@@ -126,8 +151,9 @@ impl<'a> FieldInfo<'a> {
                 container: crate::meta::ConfigContainer::#container_type,
                 type_name: #type_name,
                 default_value: #get_default,
-                possible_values: &[],
-                fields: &[],
+                deprecated: #deprecated,
+                possible_values: #possible_values,
+                fields: #fields,
             }
         )
     }
@@ -157,6 +183,8 @@ pub fn field_info(field: &Field) -> Result<FieldInfo<'_>> {
     let mut deprecated = None;
     let mut doc = String::new();
     let mut container_type = ContainerType::None;
+    let mut inner_type = field.ty.clone();
+    let mut nested = false;
 
     let type_name = match &field.ty {
         Type::Path(p) => {
@@ -166,12 +194,13 @@ pub fn field_info(field: &Field) -> Result<FieldInfo<'_>> {
                 PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
                     let arg = args.args.first().unwrap();
                     match arg {
-                        GenericArgument::Type(Type::Path(t)) => {
+                        GenericArgument::Type(ty @ Type::Path(t)) => {
                             container_type = match last_seg.ident.to_string().as_str() {
                                 "Option" => ContainerType::Option,
                                 "Vec" => ContainerType::Vec,
                                 _ => panic!("unhandled type for {name}: {:#?}", field.ty),
                             };
+                            inner_type = ty.clone();
                             t.path.segments.last().unwrap().ident.to_string()
                         }
                         _ => panic!("unhandled type for {name}: {:#?}", field.ty),
@@ -180,11 +209,12 @@ pub fn field_info(field: &Field) -> Result<FieldInfo<'_>> {
                 PathArguments::AngleBracketed(args) if args.args.len() == 2 => {
                     let arg = args.args.last().unwrap();
                     match arg {
-                        GenericArgument::Type(Type::Path(t)) => {
+                        GenericArgument::Type(ty @ Type::Path(t)) => {
                             container_type = match last_seg.ident.to_string().as_str() {
                                 "HashMap" => ContainerType::Map,
                                 _ => panic!("unhandled type for {name}: {:#?}", field.ty),
                             };
+                            inner_type = ty.clone();
                             t.path.segments.last().unwrap().ident.to_string()
                         }
                         _ => panic!("unhandled type for {name}: {:#?}", field.ty),
@@ -216,6 +246,17 @@ pub fn field_info(field: &Field) -> Result<FieldInfo<'_>> {
             continue;
         }
 
+        if attr.path().is_ident("config") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("nested") {
+                    nested = true;
+                    return Ok(());
+                }
+                Err(meta.error("unsupported attribute"))
+            })?;
+            continue;
+        }
+
         if !attr.path().is_ident("dynamic") {
             continue;
         }
@@ -304,5 +345,7 @@ pub fn field_info(field: &Field) -> Result<FieldInfo<'_>> {
         validate,
         doc,
         container_type,
+        inner_type,
+        nested,
     })
 }
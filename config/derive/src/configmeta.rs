@@ -31,6 +31,16 @@ fn derive_struct(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStrea
         .map(attr::field_info)
         .collect::<Result<Vec<_>>>()?;
 
+    let sections = options
+        .iter()
+        .filter(|f| !f.skip)
+        .map(|f| {
+            let field = f.field.ident.as_ref().unwrap();
+            let name = field.to_string();
+            quote!((#name, crate::meta::hash_config_section(&self.#field)))
+        })
+        .collect::<Vec<_>>();
+
     let options = options
         .into_iter()
         .filter_map(|f| if f.skip { None } else { Some(f.to_option()) })
@@ -41,10 +51,14 @@ fn derive_struct(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStrea
 
     let tokens = quote! {
         impl #impl_generics crate::meta::ConfigMeta for #ident #ty_generics #bounded_where_clause {
-            fn get_config_options(&self) -> &'static [crate::meta::ConfigOption]
+            const OPTIONS: &'static [crate::meta::ConfigOption] = &[
+                #( #options, )*
+            ];
+
+            fn section_hashes(&self) -> Vec<(&'static str, u64)>
             {
-                &[
-                    #( #options, )*
+                vec![
+                    #( #sections, )*
                 ]
             }
         }
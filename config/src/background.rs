@@ -222,23 +222,103 @@ impl Default for BackgroundRepeat {
     }
 }
 
-#[derive(Debug, Copy, Clone, FromDynamic, ToDynamic)]
+/// Controls how a background layer moves as the viewport scrolls
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum BackgroundAttachment {
+    /// The layer does not move as the viewport scrolls
     Fixed,
-    Scroll,
-    Parallax(f32),
+    /// The layer moves with the terminal content; a speed of 1.0
+    /// keeps it locked to the text
+    Scroll { speed: f32 },
+    /// The layer moves by a fraction of the scrolled distance,
+    /// which makes it appear to be further behind the text
+    Parallax { factor: f32 },
 }
 
 impl BackgroundAttachment {
+    const VARIANTS: &'static [&'static str] = &["Fixed", "Scroll", "Parallax"];
+
     pub fn scroll_factor(&self) -> Option<f32> {
         match self {
             Self::Fixed => None,
-            Self::Scroll => Some(1.0),
-            Self::Parallax(f) => Some(*f),
+            Self::Scroll { speed } => Some(*speed),
+            Self::Parallax { factor } => Some(*factor),
         }
     }
 }
 
+impl FromDynamic for BackgroundAttachment {
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, phaedra_dynamic::Error> {
+        /// Accepts either a bare number, which is how `{Parallax=0.1}`
+        /// has always been written, or a table with the named field.
+        fn multiplier(
+            value: &Value,
+            field: &str,
+            options: FromDynamicOptions,
+        ) -> Result<f32, phaedra_dynamic::Error> {
+            match value {
+                Value::Object(obj) => match obj.get_by_str(field) {
+                    Some(v) => f32::from_dynamic(v, options),
+                    None => Ok(1.0),
+                },
+                other => f32::from_dynamic(other, options),
+            }
+        }
+
+        let invalid = |name: &str| phaedra_dynamic::Error::InvalidVariantForType {
+            variant_name: name.to_string(),
+            type_name: "BackgroundAttachment",
+            possible: Self::VARIANTS,
+        };
+
+        match value {
+            Value::String(s) => match s.as_str() {
+                "Fixed" => Ok(Self::Fixed),
+                "Scroll" => Ok(Self::Scroll { speed: 1.0 }),
+                _ => Err(invalid(s)),
+            },
+            Value::Object(obj) if obj.len() == 1 => {
+                let (name, value) = obj.iter().next().unwrap();
+                match name {
+                    Value::String(name) => match name.as_str() {
+                        "Fixed" => Ok(Self::Fixed),
+                        "Scroll" => Ok(Self::Scroll {
+                            speed: multiplier(value, "speed", options)?,
+                        }),
+                        "Parallax" => Ok(Self::Parallax {
+                            factor: multiplier(value, "factor", options)?,
+                        }),
+                        _ => Err(invalid(name)),
+                    },
+                    other => Err(invalid(other.variant_name())),
+                }
+            }
+            other => Err(phaedra_dynamic::Error::NoConversion {
+                source_type: other.variant_name().to_string(),
+                dest_type: "BackgroundAttachment",
+            }),
+        }
+    }
+}
+
+impl ToDynamic for BackgroundAttachment {
+    fn to_dynamic(&self) -> Value {
+        let (name, field, value) = match self {
+            Self::Fixed => return Value::String("Fixed".to_string()),
+            Self::Scroll { speed } => ("Scroll", "speed", *speed),
+            Self::Parallax { factor } => ("Parallax", "factor", *factor),
+        };
+        let mut fields = std::collections::BTreeMap::new();
+        fields.insert(Value::String(field.to_string()), value.to_dynamic());
+        let mut variant = std::collections::BTreeMap::new();
+        variant.insert(Value::String(name.to_string()), Value::Object(fields.into()));
+        Value::Object(variant.into())
+    }
+}
+
 impl Default for BackgroundAttachment {
     fn default() -> Self {
         Self::Fixed
@@ -1,3 +1,4 @@
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
 /// <https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function>
@@ -62,6 +63,9 @@ pub struct VisualBell {
 pub enum VisualBellTarget {
     BackgroundColor,
     CursorColor,
+    /// Runs the whole window through a post-processing pass: the
+    /// configured `webgpu_shader`, or a built-in flash if there is none
+    PostProcess,
 }
 
 impl Default for VisualBellTarget {
@@ -82,12 +86,16 @@ impl Default for AudibleBell {
     }
 }
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct BellConfig {
     #[dynamic(default)]
     pub visual_bell: VisualBell,
     #[dynamic(default)]
     pub audible_bell: AudibleBell,
+    /// Whether a bell in a window that isn't focused asks the desktop
+    /// environment to draw attention to that window
+    #[dynamic(default = "default_true")]
+    pub set_urgency_hint: bool,
 }
 
 impl Default for BellConfig {
@@ -95,6 +103,11 @@ impl Default for BellConfig {
         Self {
             visual_bell: VisualBell::default(),
             audible_bell: AudibleBell::default(),
+            set_urgency_hint: default_true(),
         }
     }
 }
+
+fn default_true() -> bool {
+    true
+}
@@ -1,6 +1,7 @@
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct CacheConfig {
     #[dynamic(default = "default_shape_cache_size")]
     pub shape_cache_size: usize,
@@ -12,6 +13,14 @@ pub struct CacheConfig {
     pub line_to_ele_shape_cache_size: usize,
     #[dynamic(default = "default_glyph_cache_image_cache_size")]
     pub glyph_cache_image_cache_size: usize,
+    /// An approximate upper bound, in bytes, for the combined size of
+    /// the caches above.  When exceeded, each cache that is using more
+    /// than its share of the budget is trimmed.  None disables this.
+    #[dynamic(default)]
+    pub soft_budget_bytes: Option<usize>,
+    /// How many frames to paint between checks against soft_budget_bytes
+    #[dynamic(default = "default_rebalance_interval_frames")]
+    pub rebalance_interval_frames: u64,
 }
 
 impl Default for CacheConfig {
@@ -22,6 +31,8 @@ impl Default for CacheConfig {
             line_quad_cache_size: default_line_quad_cache_size(),
             line_to_ele_shape_cache_size: default_line_to_ele_shape_cache_size(),
             glyph_cache_image_cache_size: default_glyph_cache_image_cache_size(),
+            soft_budget_bytes: None,
+            rebalance_interval_frames: default_rebalance_interval_frames(),
         }
     }
 }
@@ -45,3 +56,7 @@ fn default_line_quad_cache_size() -> usize {
 fn default_line_to_ele_shape_cache_size() -> usize {
     1024
 }
+
+fn default_rebalance_interval_frames() -> u64 {
+    120
+}
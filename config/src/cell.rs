@@ -20,3 +20,28 @@ impl CellWidth {
         Some(map.into())
     }
 }
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, FromDynamic, ToDynamic)]
+pub enum EmojiPresentation {
+    Text,
+    Emoji,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, FromDynamic, ToDynamic)]
+pub struct EmojiPresentationOverride {
+    pub first: u32,
+    pub last: u32,
+    pub presentation: EmojiPresentation,
+}
+
+impl EmojiPresentationOverride {
+    pub fn compile_to_map(overrides: &[Self]) -> HashMap<u32, EmojiPresentation> {
+        let mut map = HashMap::new();
+        for entry in overrides {
+            for i in entry.first..=entry.last {
+                map.insert(i, entry.presentation);
+            }
+        }
+        map
+    }
+}
@@ -28,31 +28,73 @@ impl Default for HsbTransform {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, FromDynamic, ToDynamic)]
-#[dynamic(try_from = "String", into = "String")]
+/// A color that may be specified either directly, as a string, or as a
+/// `DerivedColor` that is computed from another color in the palette.
+/// Derived colors are resolved by `Palette::resolve_derived_colors`;
+/// until then, the color value of a derived color is a placeholder.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RgbaColor {
-    #[dynamic(flatten)]
     color: SrgbaTuple,
+    derived: Option<DerivedColor>,
+}
+
+impl RgbaColor {
+    /// Returns the derivation that will be used to compute this color,
+    /// if it has not yet been resolved
+    pub fn derived(&self) -> Option<DerivedColor> {
+        self.derived
+    }
+}
+
+impl FromDynamic for RgbaColor {
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, phaedra_dynamic::Error> {
+        match value {
+            Value::Object(_) => Ok(Self {
+                color: SrgbaTuple::default(),
+                derived: Some(DerivedColor::from_dynamic(value, options)?),
+            }),
+            _ => {
+                let s = String::from_dynamic(value, options)?;
+                Ok(Self::try_from(s).map_err(|err| format!("{:#}", err))?)
+            }
+        }
+    }
+}
+
+impl ToDynamic for RgbaColor {
+    fn to_dynamic(&self) -> Value {
+        match &self.derived {
+            Some(derived) => derived.to_dynamic(),
+            None => self.color.to_string().to_dynamic(),
+        }
+    }
 }
 
 impl From<RgbColor> for RgbaColor {
     fn from(color: RgbColor) -> Self {
         Self {
             color: color.into(),
+            derived: None,
         }
     }
 }
 
 impl From<SrgbaTuple> for RgbaColor {
     fn from(color: SrgbaTuple) -> Self {
-        Self { color }
+        Self {
+            color,
+            derived: None,
+        }
     }
 }
 
 impl From<(u8, u8, u8)> for RgbaColor {
     fn from((r, g, b): (u8, u8, u8)) -> Self {
         let color: SrgbaTuple = (r, g, b).into();
-        Self { color }
+        color.into()
     }
 }
 
@@ -87,6 +129,146 @@ impl TryFrom<String> for RgbaColor {
         Ok(RgbaColor {
             color: SrgbaTuple::from_str(&s)
                 .map_err(|_| anyhow::anyhow!("failed to parse {} as RgbaColor", &s))?,
+            derived: None,
+        })
+    }
+}
+
+macro_rules! palette_color_names {
+    ($($variant:ident => $field:ident,)*) => {
+        /// Names one of the colors in the palette, so that a `DerivedColor`
+        /// can refer to it.  The names match the fields of `Palette`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromDynamic, ToDynamic)]
+        #[dynamic(try_from = "String", into = "String")]
+        pub enum PaletteColorName {
+            $($variant,)*
+        }
+
+        impl PaletteColorName {
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($field),)*
+                }
+            }
+
+            fn get(self, palette: &Palette) -> Option<RgbaColor> {
+                match self {
+                    $(Self::$variant => palette.$field,)*
+                }
+            }
+
+            fn get_mut(self, palette: &mut Palette) -> &mut Option<RgbaColor> {
+                match self {
+                    $(Self::$variant => &mut palette.$field,)*
+                }
+            }
+
+            const ALL: &'static [Self] = &[$(Self::$variant,)*];
+        }
+
+        impl TryFrom<String> for PaletteColorName {
+            type Error = anyhow::Error;
+            fn try_from(s: String) -> anyhow::Result<Self> {
+                match s.as_str() {
+                    $(stringify!($field) => Ok(Self::$variant),)*
+                    _ => anyhow::bail!(
+                        "`{}` is not a palette color name. Possible names are {}",
+                        s,
+                        [$(stringify!($field),)*].join(", ")
+                    ),
+                }
+            }
+        }
+    };
+}
+
+palette_color_names! {
+    Foreground => foreground,
+    Background => background,
+    CursorFg => cursor_fg,
+    CursorBg => cursor_bg,
+    CursorBorder => cursor_border,
+    SelectionFg => selection_fg,
+    SelectionBg => selection_bg,
+    ScrollbarThumb => scrollbar_thumb,
+    Split => split,
+    VisualBell => visual_bell,
+    ComposeCursor => compose_cursor,
+    PaneHighlightBg => pane_highlight_bg,
+}
+
+impl From<&PaletteColorName> for String {
+    fn from(val: &PaletteColorName) -> Self {
+        val.as_str().to_string()
+    }
+}
+
+impl From<PaletteColorName> for String {
+    fn from(val: PaletteColorName) -> Self {
+        val.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for PaletteColorName {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+/// A color computed from other colors in the palette, after the
+/// color scheme and the `colors` overrides have been applied
+#[derive(Debug, Clone, Copy, PartialEq, FromDynamic, ToDynamic)]
+pub enum DerivedColor {
+    /// Scale the lightness of `from` towards white by `amount`,
+    /// a value ranging from 0.0 to 1.0
+    Lighten { from: PaletteColorName, amount: f32 },
+    /// Scale the lightness of `from` towards black by `amount`,
+    /// a value ranging from 0.0 to 1.0
+    Darken { from: PaletteColorName, amount: f32 },
+    /// Blend `over` on top of `under`, where `alpha` is the opacity
+    /// of `over`, ranging from 0.0 to 1.0
+    AlphaBlend {
+        over: PaletteColorName,
+        under: PaletteColorName,
+        alpha: f32,
+    },
+    /// The complement of the named color; its hue rotated by 180 degrees
+    Complement(PaletteColorName),
+}
+
+impl Eq for DerivedColor {}
+
+impl std::hash::Hash for DerivedColor {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::Lighten { from, amount } | Self::Darken { from, amount } => {
+                from.hash(state);
+                amount.to_bits().hash(state);
+            }
+            Self::AlphaBlend { over, under, alpha } => {
+                over.hash(state);
+                under.hash(state);
+                alpha.to_bits().hash(state);
+            }
+            Self::Complement(name) => name.hash(state),
+        }
+    }
+}
+
+impl DerivedColor {
+    fn compute(
+        &self,
+        mut lookup: impl FnMut(PaletteColorName) -> anyhow::Result<SrgbaTuple>,
+    ) -> anyhow::Result<SrgbaTuple> {
+        Ok(match self {
+            Self::Lighten { from, amount } => lookup(*from)?.lighten(*amount as f64),
+            Self::Darken { from, amount } => lookup(*from)?.lighten(-*amount as f64),
+            Self::AlphaBlend { over, under, alpha } => {
+                let over = lookup(*over)?;
+                lookup(*under)?.interpolate(over, *alpha as f64)
+            }
+            Self::Complement(name) => lookup(*name)?.complement(),
         })
     }
 }
@@ -108,7 +290,7 @@ impl From<ColorSpec> for ColorAttribute {
     fn from(val: ColorSpec) -> Self {
         match val {
             ColorSpec::AnsiColor(c) => ColorAttribute::PaletteIndex(c.into()),
-            ColorSpec::Color(RgbaColor { color }) => {
+            ColorSpec::Color(RgbaColor { color, .. }) => {
                 ColorAttribute::TrueColorWithDefaultFallback(color)
             }
             ColorSpec::Default => ColorAttribute::Default,
@@ -120,7 +302,7 @@ impl From<ColorSpec> for TWColorSpec {
     fn from(val: ColorSpec) -> Self {
         match val {
             ColorSpec::AnsiColor(c) => c.into(),
-            ColorSpec::Color(RgbaColor { color }) => TWColorSpec::TrueColor(color),
+            ColorSpec::Color(RgbaColor { color, .. }) => TWColorSpec::TrueColor(color),
             ColorSpec::Default => TWColorSpec::Default,
         }
     }
@@ -160,6 +342,10 @@ pub struct Palette {
     pub visual_bell: Option<RgbaColor>,
     /// The color to use for the cursor when a dead key or leader state is active
     pub compose_cursor: Option<RgbaColor>,
+    /// The color drawn behind the matches of the pattern set by
+    /// `SetPaneHighlightPattern`.  It is blended over the background
+    /// of the matching cells, so it is usually translucent.
+    pub pane_highlight_bg: Option<RgbaColor>,
 
     pub copy_mode_active_highlight_fg: Option<ColorSpec>,
     pub copy_mode_active_highlight_bg: Option<ColorSpec>,
@@ -217,6 +403,7 @@ impl Palette {
             split: overlay!(split),
             visual_bell: overlay!(visual_bell),
             compose_cursor: overlay!(compose_cursor),
+            pane_highlight_bg: overlay!(pane_highlight_bg),
             copy_mode_active_highlight_fg: overlay!(copy_mode_active_highlight_fg),
             copy_mode_active_highlight_bg: overlay!(copy_mode_active_highlight_bg),
             copy_mode_inactive_highlight_fg: overlay!(copy_mode_inactive_highlight_fg),
@@ -231,6 +418,111 @@ impl Palette {
             launcher_label_bg: overlay!(launcher_label_bg),
         }
     }
+
+    /// Compute the values of any `DerivedColor`s in the palette.
+    /// Colors that are not set in the palette are taken from the
+    /// default terminal palette.  Returns an error if a derivation
+    /// refers to a color that has no value, or if derivations form
+    /// a cycle.
+    pub fn resolve_derived_colors(&self) -> anyhow::Result<Self> {
+        let defaults: Palette = ColorPalette::default().into();
+        let mut resolved = self.clone();
+        let mut cache = HashMap::new();
+
+        fn resolve_name(
+            name: PaletteColorName,
+            palette: &Palette,
+            defaults: &Palette,
+            cache: &mut HashMap<PaletteColorName, SrgbaTuple>,
+            stack: &mut Vec<PaletteColorName>,
+        ) -> anyhow::Result<SrgbaTuple> {
+            if let Some(color) = cache.get(&name) {
+                return Ok(*color);
+            }
+            if stack.contains(&name) {
+                let cycle: Vec<&str> = stack
+                    .iter()
+                    .skip_while(|&&n| n != name)
+                    .chain(std::iter::once(&name))
+                    .map(|n| n.as_str())
+                    .collect();
+                anyhow::bail!("derived colors form a cycle: {}", cycle.join(" -> "));
+            }
+            let color = name
+                .get(palette)
+                .or_else(|| name.get(defaults))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("derived color refers to `{}`, which is not set", name)
+                })?;
+            let value = match color.derived {
+                None => color.color,
+                Some(derived) => {
+                    stack.push(name);
+                    let value = derived
+                        .compute(|n| resolve_name(n, palette, defaults, cache, stack))
+                        .with_context(|| format!("resolving derived color `{}`", name));
+                    stack.pop();
+                    value?
+                }
+            };
+            cache.insert(name, value);
+            Ok(value)
+        }
+
+        for &name in PaletteColorName::ALL {
+            if let Some(color) = name.get_mut(&mut resolved) {
+                if color.derived.is_some() {
+                    *color = resolve_name(name, self, &defaults, &mut cache, &mut vec![])?.into();
+                }
+            }
+        }
+
+        // The remaining colors cannot be referenced by name, so they
+        // cannot participate in a cycle
+        let mut resolve = |color: &mut RgbaColor| -> anyhow::Result<()> {
+            if let Some(derived) = color.derived.take() {
+                color.color = derived
+                    .compute(|n| resolve_name(n, self, &defaults, &mut cache, &mut vec![]))?;
+            }
+            Ok(())
+        };
+        for color in resolved
+            .ansi
+            .iter_mut()
+            .chain(resolved.brights.iter_mut())
+            .flatten()
+            .chain(resolved.indexed.values_mut())
+        {
+            resolve(color)?;
+        }
+        if let Some(tab_bar) = &mut resolved.tab_bar {
+            for color in [
+                &mut tab_bar.background,
+                &mut tab_bar.inactive_tab_edge,
+                &mut tab_bar.inactive_tab_edge_hover,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                resolve(color)?;
+            }
+            for tab in [
+                &mut tab_bar.active_tab,
+                &mut tab_bar.inactive_tab,
+                &mut tab_bar.inactive_tab_hover,
+                &mut tab_bar.new_tab,
+                &mut tab_bar.new_tab_hover,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                resolve(&mut tab.bg_color)?;
+                resolve(&mut tab.fg_color)?;
+            }
+        }
+
+        Ok(resolved)
+    }
 }
 
 impl From<ColorPalette> for Palette {
@@ -804,3 +1096,103 @@ brights = [ "#8ca6a6" ,"#e5164a" ,"#00b368" ,"#b3694d" ,"#0094f0" ,"#ff5792" ,"#
         Some(&RgbColor::new_8bpc(0xfb, 0xda, 0xda).into())
     );
 }
+
+#[cfg(test)]
+fn derived_palette(s: &str) -> anyhow::Result<Palette> {
+    let value: toml::Value = toml::from_str(s)?;
+    let palette = Palette::from_dynamic(&crate::toml_to_dynamic(&value), Default::default())
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    palette.resolve_derived_colors()
+}
+
+#[cfg(test)]
+fn assert_color_near(color: Option<RgbaColor>, expect: (f32, f32, f32, f32)) {
+    let SrgbaTuple(r, g, b, a) = *color.expect("color to be set");
+    let actual = (r, g, b, a);
+    for (c, e) in [(r, expect.0), (g, expect.1), (b, expect.2), (a, expect.3)] {
+        assert!((c - e).abs() < 0.001, "{actual:?} != {expect:?}");
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_lighten_darken() {
+    let palette = derived_palette(
+        r##"
+background = "#000000"
+foreground = "#ffffff"
+cursor_bg = { Lighten = { from = "background", amount = 0.2 } }
+cursor_fg = { Darken = { from = "foreground", amount = 0.5 } }
+"##,
+    )
+    .unwrap();
+    assert_color_near(palette.cursor_bg, (0.2, 0.2, 0.2, 1.0));
+    assert_color_near(palette.cursor_fg, (0.5, 0.5, 0.5, 1.0));
+    assert_eq!(palette.cursor_bg.unwrap().derived(), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_alpha_blend() {
+    let palette = derived_palette(
+        r##"
+background = "#000000"
+foreground = "#ffffff"
+selection_bg = { AlphaBlend = { over = "foreground", under = "background", alpha = 0.25 } }
+"##,
+    )
+    .unwrap();
+    assert_color_near(palette.selection_bg, (0.25, 0.25, 0.25, 1.0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_complement_chain() {
+    // cursor_border depends upon cursor_bg, which is itself derived,
+    // and which is declared later in the palette
+    let palette = derived_palette(
+        r##"
+background = "#ff0000"
+cursor_border = { Complement = "cursor_bg" }
+cursor_bg = { Lighten = { from = "background", amount = 0.0 } }
+"##,
+    )
+    .unwrap();
+    assert_color_near(palette.cursor_bg, (1.0, 0.0, 0.0, 1.0));
+    assert_color_near(palette.cursor_border, (0.0, 1.0, 1.0, 1.0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_uses_default_palette() {
+    // background is not set, so the default terminal background is used
+    let palette = derived_palette(r##"split = { Complement = "background" }"##).unwrap();
+    let default = ColorPalette::default().background.complement();
+    assert_color_near(palette.split, default.into());
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_cycle() {
+    let err = derived_palette(
+        r##"
+cursor_fg = { Darken = { from = "cursor_bg", amount = 0.1 } }
+cursor_bg = { Lighten = { from = "selection_bg", amount = 0.1 } }
+selection_bg = { Complement = "cursor_fg" }
+"##,
+    )
+    .unwrap_err();
+    let message = format!("{:#}", err);
+    assert!(message.contains("form a cycle"), "{message}");
+    assert!(
+        message.contains("cursor_fg -> cursor_bg -> selection_bg -> cursor_fg"),
+        "{message}"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_derived_unknown_name() {
+    let err = derived_palette(r##"split = { Complement = "bogus" }"##).unwrap_err();
+    assert!(format!("{:#}", err).contains("not a palette color name"));
+}
@@ -1,11 +1,13 @@
 use crate::background::BackgroundLayer;
+use crate::bell::EasingFunction;
 use crate::color::{HsbTransform, Palette, RgbaColor, SrgbaTuple};
 use crate::config::BoldBrightening;
-use phaedra_dynamic::{FromDynamic, ToDynamic};
+use phaedra_config_derive::ConfigMeta;
+use phaedra_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct ColorConfig {
     #[dynamic(default)]
     pub color_scheme_dirs: Vec<PathBuf>,
@@ -14,7 +16,11 @@ pub struct ColorConfig {
     pub colors: Option<Palette>,
     #[dynamic(default)]
     pub resolved_palette: Palette,
-    pub color_scheme: Option<String>,
+    pub color_scheme: Option<ColorSchemeSelection>,
+    /// A file whose first line names the color scheme, taking
+    /// precedence over `color_scheme`
+    #[dynamic(default)]
+    pub color_scheme_file: Option<PathBuf>,
     #[dynamic(default)]
     pub color_schemes: HashMap<String, Palette>,
     #[dynamic(default)]
@@ -22,6 +28,10 @@ pub struct ColorConfig {
     #[dynamic(default = "default_inactive_pane_hsb")]
     pub inactive_pane_hsb: HsbTransform,
     #[dynamic(default)]
+    pub inactive_pane_fade_duration_ms: u64,
+    #[dynamic(default)]
+    pub inactive_pane_fade_function: EasingFunction,
+    #[dynamic(default)]
     pub background: Vec<BackgroundLayer>,
     #[dynamic(default = "default_char_select_fg_color")]
     pub char_select_fg_color: RgbaColor,
@@ -45,9 +55,12 @@ impl Default for ColorConfig {
             colors: None,
             resolved_palette: Palette::default(),
             color_scheme: None,
+            color_scheme_file: None,
             color_schemes: HashMap::new(),
             foreground_text_hsb: HsbTransform::default(),
             inactive_pane_hsb: default_inactive_pane_hsb(),
+            inactive_pane_fade_duration_ms: 0,
+            inactive_pane_fade_function: EasingFunction::default(),
             background: Vec::new(),
             char_select_fg_color: default_char_select_fg_color(),
             char_select_bg_color: default_char_select_bg_color(),
@@ -59,6 +72,122 @@ impl Default for ColorConfig {
     }
 }
 
+impl ColorConfig {
+    /// Returns the path of `color_scheme_file`, with a leading `~`
+    /// expanded to the home directory
+    pub fn color_scheme_file(&self) -> Option<PathBuf> {
+        let path = self.color_scheme_file.as_ref()?;
+        Some(match path.strip_prefix("~") {
+            Ok(rest) => crate::HOME_DIR.join(rest),
+            Err(_) => path.clone(),
+        })
+    }
+
+    /// Returns true if the color scheme that is used depends upon
+    /// the appearance of the windowing environment
+    pub fn scheme_depends_on_appearance(&self) -> bool {
+        matches!(
+            self.color_scheme,
+            Some(ColorSchemeSelection::ByAppearance { .. })
+        )
+    }
+}
+
+/// The appearance of the windowing environment, as reported by the GUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SystemAppearance {
+    /// Assumed until the GUI reports otherwise
+    #[default]
+    Light,
+    Dark,
+    LightHighContrast,
+    DarkHighContrast,
+}
+
+impl SystemAppearance {
+    pub fn is_dark(self) -> bool {
+        matches!(self, Self::Dark | Self::DarkHighContrast)
+    }
+}
+
+/// Chooses the color scheme, either by name, or according to whether
+/// the windowing environment has a dark or a light appearance
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ColorSchemeSelection {
+    Named(String),
+    ByAppearance { dark: String, light: String },
+}
+
+impl ColorSchemeSelection {
+    /// Returns the name of the scheme to use with `appearance`
+    pub fn scheme_name(&self, appearance: SystemAppearance) -> &str {
+        match self {
+            Self::Named(name) => name,
+            Self::ByAppearance { dark, .. } if appearance.is_dark() => dark,
+            Self::ByAppearance { light, .. } => light,
+        }
+    }
+}
+
+#[derive(FromDynamic, ToDynamic)]
+struct SchemeByAppearance {
+    dark: String,
+    light: String,
+}
+
+impl FromDynamic for ColorSchemeSelection {
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, phaedra_dynamic::Error> {
+        match value {
+            Value::String(name) => Ok(Self::Named(name.to_string())),
+            Value::Object(_) => {
+                let SchemeByAppearance { dark, light } =
+                    SchemeByAppearance::from_dynamic(value, options)?;
+                Ok(Self::ByAppearance { dark, light })
+            }
+            _ => Err(phaedra_dynamic::Error::Message(format!(
+                "expected either the name of a color scheme, or a table \
+                 of the form {{dark=\"name\", light=\"name\"}}, but got {}",
+                value.variant_name()
+            ))),
+        }
+    }
+}
+
+impl ToDynamic for ColorSchemeSelection {
+    fn to_dynamic(&self) -> Value {
+        match self {
+            Self::Named(name) => name.to_dynamic(),
+            Self::ByAppearance { dark, light } => SchemeByAppearance {
+                dark: dark.clone(),
+                light: light.clone(),
+            }
+            .to_dynamic(),
+        }
+    }
+}
+
+/// Returns the name of the color scheme to use.  The first line of
+/// `file_contents`, the contents of `color_scheme_file`, wins if it
+/// names a scheme, otherwise `selection` chooses according to
+/// `appearance`.
+pub fn resolve_color_scheme_name(
+    selection: Option<&ColorSchemeSelection>,
+    appearance: SystemAppearance,
+    file_contents: Option<&str>,
+) -> Option<String> {
+    let from_file = file_contents
+        .and_then(|contents| contents.lines().next())
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    match from_file {
+        Some(name) => Some(name.to_string()),
+        None => selection.map(|selection| selection.scheme_name(appearance).to_string()),
+    }
+}
+
 fn default_inactive_pane_hsb() -> HsbTransform {
     HsbTransform {
         brightness: 0.8,
@@ -90,3 +219,92 @@ fn default_pane_select_fg_color() -> RgbaColor {
 fn default_pane_select_bg_color() -> RgbaColor {
     SrgbaTuple(0., 0., 0., 0.5).into()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn by_appearance() -> ColorSchemeSelection {
+        ColorSchemeSelection::ByAppearance {
+            dark: "Tokyo Night".to_string(),
+            light: "Catppuccin Latte".to_string(),
+        }
+    }
+
+    #[test]
+    fn scheme_follows_appearance() {
+        let named = ColorSchemeSelection::Named("Builtin Dark".to_string());
+        let selection = by_appearance();
+        let resolve = |selection: Option<&ColorSchemeSelection>, appearance| {
+            resolve_color_scheme_name(selection, appearance, None)
+        };
+
+        assert_eq!(
+            resolve(Some(&named), SystemAppearance::Light).as_deref(),
+            Some("Builtin Dark")
+        );
+        assert_eq!(
+            resolve(Some(&selection), SystemAppearance::Dark).as_deref(),
+            Some("Tokyo Night")
+        );
+        assert_eq!(
+            resolve(Some(&selection), SystemAppearance::DarkHighContrast).as_deref(),
+            Some("Tokyo Night")
+        );
+        assert_eq!(
+            resolve(Some(&selection), SystemAppearance::LightHighContrast).as_deref(),
+            Some("Catppuccin Latte")
+        );
+        assert_eq!(resolve(None, SystemAppearance::Dark), None);
+    }
+
+    #[test]
+    fn scheme_file_takes_precedence() {
+        let selection = by_appearance();
+        assert_eq!(
+            resolve_color_scheme_name(
+                Some(&selection),
+                SystemAppearance::Dark,
+                Some("  Gruvbox Dark\nignored\n")
+            )
+            .as_deref(),
+            Some("Gruvbox Dark")
+        );
+        assert_eq!(
+            resolve_color_scheme_name(None, SystemAppearance::Dark, Some("Gruvbox Dark\n"))
+                .as_deref(),
+            Some("Gruvbox Dark")
+        );
+
+        // An empty file leaves the choice to color_scheme
+        assert_eq!(
+            resolve_color_scheme_name(Some(&selection), SystemAppearance::Light, Some("\n"))
+                .as_deref(),
+            Some("Catppuccin Latte")
+        );
+    }
+
+    #[test]
+    fn selection_accepts_a_name_or_a_table() {
+        let lua = mlua::Lua::new();
+        let parse = |expr: &str| -> Result<ColorSchemeSelection, String> {
+            let value: mlua::Value = lua.load(expr).eval().unwrap();
+            luahelper::from_lua(value).map_err(|err| err.to_string())
+        };
+
+        assert_eq!(
+            parse("'Builtin Dark'").unwrap(),
+            ColorSchemeSelection::Named("Builtin Dark".to_string())
+        );
+        let selection = parse("{ dark = 'Tokyo Night', light = 'Catppuccin Latte' }").unwrap();
+        assert_eq!(selection, by_appearance());
+        assert_eq!(
+            ColorSchemeSelection::from_dynamic(&selection.to_dynamic(), Default::default())
+                .unwrap(),
+            selection
+        );
+
+        assert!(parse("{ dark = 'Tokyo Night' }").is_err());
+        assert!(parse("42").is_err());
+    }
+}
@@ -1,20 +1,26 @@
 use crate::bell::{AudibleBell, BellConfig, EasingFunction, VisualBell};
 use crate::cache_config::CacheConfig;
 use crate::color::{ColorSchemeFile, Palette, TabBarStyle};
-use crate::color_config::ColorConfig;
+use crate::color_config::{resolve_color_scheme_name, ColorConfig, SystemAppearance};
+use crate::config_overrides::{json_overrides_object, ConfigOverride};
 use crate::cursor::CursorConfig;
 use crate::domain_config::DomainConfig;
 use crate::font::StyleRule;
 use crate::font_config::FontConfig;
 use crate::gpu_config::GpuConfig;
 use crate::key_input_config::KeyInputConfig;
-use crate::keyassignment::{KeyAssignment, KeyTable, KeyTableEntry, KeyTables, MouseEventTrigger};
+use crate::keyassignment::{
+    add_key_table_entry, KeyAssignment, KeyTable, KeyTableEntry, KeyTables, MouseEventTrigger,
+    WindowPaddingOverride,
+};
 use crate::launch_config::LaunchConfig;
-use crate::lua::make_lua_context;
+use crate::lua::{eval_config_with_timeout, make_lua_context};
 use crate::mux_config::MuxConfig;
 use crate::mouse_config::MouseConfig;
 use crate::runtime_config::RuntimeConfig;
-use crate::scroll::ScrollConfig;
+use crate::scheme_formats::ColorSchemeFormat;
+use crate::scroll::{AlternateBufferWheelBehavior, ScrollConfig};
+use crate::spawn_template::SpawnContext;
 use crate::ssh::SshDomain;
 use crate::tab_bar::TabBarConfig;
 use crate::terminal_feature_config::TerminalFeatureConfig;
@@ -23,8 +29,9 @@ use crate::units::Dimension;
 use crate::update_check::UpdateConfig;
 use crate::window_config::WindowConfig;
 use crate::{
-    default_config_with_overrides_applied, LoadedConfig, MouseEventTriggerMods, CONFIG_DIRS,
-    CONFIG_FILE_OVERRIDE, CONFIG_OVERRIDES, CONFIG_SKIP, HOME_DIR,
+    config_eval_timeout, default_config_with_overrides_applied, LoadedConfig,
+    MouseEventTriggerMods, MouseRegion, CONFIG_DIRS, CONFIG_FILE_OVERRIDE, CONFIG_OVERRIDES,
+    CONFIG_SKIP, HOME_DIR,
 };
 use anyhow::Context;
 use luahelper::impl_lua_conversion_dynamic;
@@ -35,7 +42,7 @@ use std::ffi::OsStr;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use termwiz::surface::CursorShape;
 use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
@@ -45,57 +52,75 @@ use phaedra_term::TerminalSize;
 #[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct Config {
     #[dynamic(default)]
+    #[config(nested)]
     pub font_config: FontConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub text: TextConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub cursor: CursorConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub window_config: WindowConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub gpu: GpuConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub color_config: ColorConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub tab_bar: TabBarConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub scroll: ScrollConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub launch: LaunchConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub terminal_features: TerminalFeatureConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub domain: DomainConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub mux: MuxConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub key_input: KeyInputConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub mouse: MouseConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub runtime: RuntimeConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub update_check: UpdateConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub cache: CacheConfig,
 
     #[dynamic(default)]
+    #[config(nested)]
     pub bell: BellConfig,
 }
 impl_lua_conversion_dynamic!(Config);
@@ -239,6 +264,7 @@ impl Config {
                         file_name: Some(path_item.path.clone()),
                         lua: None,
                         warnings: vec![],
+                        eval_duration: None,
                     }
                 }
                 Ok(None) => continue,
@@ -258,6 +284,7 @@ impl Config {
                 file_name: None,
                 lua: None,
                 warnings: vec![],
+                eval_duration: None,
             },
             Ok(cfg) => cfg,
         }
@@ -274,6 +301,7 @@ impl Config {
             file_name: None,
             lua: Some(make_lua_context(Path::new(""))?),
             warnings,
+            eval_duration: None,
         })
     }
 
@@ -294,19 +322,24 @@ impl Config {
         let mut s = String::new();
         file.read_to_string(&mut s)?;
         let lua = make_lua_context(p)?;
+        let mut eval_duration = Duration::ZERO;
 
         let (config, warnings) =
             phaedra_dynamic::Error::capture_warnings(|| -> anyhow::Result<Config> {
                 let cfg: Config;
 
-                let config: mlua::Value = smol::block_on(
+                let started = Instant::now();
+                let config = eval_config_with_timeout(
+                    &lua,
                     // Skip a potential BOM that Windows software may have placed in the
-                    // file. Note that we can't catch this happening for files that are
-                    // imported via the lua require function.
-                    lua.load(s.trim_start_matches('\u{FEFF}'))
-                        .set_name(p.to_string_lossy())
-                        .eval_async(),
+                    // file. Files that are imported via the lua require function are
+                    // handled by the searcher that make_lua_context installs.
+                    s.trim_start_matches('\u{FEFF}'),
+                    p,
+                    config_eval_timeout(),
                 )?;
+                eval_duration = started.elapsed();
+                log::debug!("evaluated {} in {:?}", p.display(), eval_duration);
                 let config = Config::apply_overrides_to(&lua, config)?;
                 let config = Config::apply_overrides_obj_to(&lua, config, overrides)?;
                 cfg = Config::from_lua(config, &lua).with_context(|| {
@@ -325,15 +358,18 @@ impl Config {
                 if let Some(dir) = p.parent() {
                     std::env::set_var("PHAEDRA_CONFIG_DIR", dir);
                 }
-                Ok(cfg)
+                let resolved = cfg.compute_extra_defaults(Some(p));
+                crate::font_validation::validate_fonts(&resolved, &cfg.font_config, p.parent());
+                Ok(resolved)
             });
         let cfg = config?;
 
         Ok(Some(LoadedConfig {
-            config: Ok(cfg.compute_extra_defaults(Some(p))),
+            config: Ok(cfg),
             file_name: Some(p.to_path_buf()),
             lua: Some(lua),
             warnings,
+            eval_duration: Some(eval_duration),
         }))
     }
 
@@ -356,11 +392,35 @@ impl Config {
             )
             .eval()?;
 
+        let getter: mlua::Function = lua
+            .load(
+                r#"
+                    return function(config, key)
+                        return config[key];
+                    end
+                    "#,
+            )
+            .eval()?;
+
         match overrides {
             phaedra_dynamic::Value::Object(obj) => {
                 for (key, value) in obj {
                     let key = luahelper::dynamic_to_lua_value(lua, key.clone())?;
-                    let value = luahelper::dynamic_to_lua_value(lua, value.clone())?;
+                    // Merge objects into an existing table, such as one
+                    // of the config sections, rather than replacing it,
+                    // so that only the fields that are named are changed
+                    let existing: mlua::Value = match value {
+                        phaedra_dynamic::Value::Object(_) => {
+                            getter.call((config.clone(), key.clone()))?
+                        }
+                        _ => mlua::Value::Nil,
+                    };
+                    let value = match existing {
+                        mlua::Value::Table(_) => {
+                            Self::apply_overrides_obj_to(lua, existing, value)?
+                        }
+                        _ => luahelper::dynamic_to_lua_value(lua, value.clone())?,
+                    };
                     config = setter.call((config, key, value))?;
                 }
                 Ok(config)
@@ -374,7 +434,11 @@ impl Config {
         mut config: mlua::Value<'l>,
     ) -> anyhow::Result<mlua::Value<'l>> {
         let overrides = CONFIG_OVERRIDES.lock().unwrap();
-        for (key, value) in &*overrides {
+        for item in &*overrides {
+            let (key, value) = match item {
+                ConfigOverride::Lua { key, value } => (key, value),
+                ConfigOverride::Json { .. } => continue,
+            };
             if value == "nil" {
                 // Literal nil as the value is the same as not specifying the value.
                 // We special case this here as we want to explicitly check for
@@ -402,15 +466,63 @@ impl Config {
             log::debug!("Apply {}={} to config", key, value);
             config = chunk.eval()?;
         }
-        Ok(config)
+
+        // The JSON overrides are merged in without evaluating any lua
+        let json_overrides = json_overrides_object(&overrides)?;
+        Self::apply_overrides_obj_to(lua, config, &json_overrides)
     }
 
     /// Check for logical conflicts in the config
     pub fn check_consistency(&self) -> anyhow::Result<()> {
         self.check_domain_consistency()?;
+        self.check_activate_tab_indices();
         Ok(())
     }
 
+    /// Warn about ActivateTab assignments whose index no window is
+    /// likely to have, as they otherwise silently do nothing
+    fn check_activate_tab_indices(&self) {
+        fn check(action: &KeyAssignment, struct_name: &'static str, field: String) {
+            action.visit(&mut |action| {
+                if let KeyAssignment::ActivateTab(index) = action {
+                    if index.is_implausible() {
+                        phaedra_dynamic::Error::warn(phaedra_dynamic::Warning::InvalidValue {
+                            struct_name,
+                            field: field.clone(),
+                            message: format!(
+                                "ActivateTab({}) refers to a tab that is unlikely to exist, \
+                                 so it will do nothing; use out_of_range = 'Clamp' if \
+                                 that is intended",
+                                index.position.lua_index(),
+                            ),
+                            location: None,
+                        });
+                    }
+                }
+            });
+        }
+
+        for (idx, key) in self.key_input.keys.iter().enumerate() {
+            check(&key.action, "KeyInputConfig", format!("keys[{}]", idx + 1));
+        }
+        for (name, keys) in &self.key_input.key_tables {
+            for (idx, key) in keys.iter().enumerate() {
+                check(
+                    &key.action,
+                    "KeyInputConfig",
+                    format!("key_tables.{name}[{}]", idx + 1),
+                );
+            }
+        }
+        for (idx, mouse) in self.mouse.mouse_bindings.iter().enumerate() {
+            check(
+                &mouse.action,
+                "MouseConfig",
+                format!("mouse_bindings[{}]", idx + 1),
+            );
+        }
+    }
+
     fn check_domain_consistency(&self) -> anyhow::Result<()> {
         let mut domains = HashMap::new();
 
@@ -455,10 +567,11 @@ impl Config {
                 .key
                 .resolve(self.key_input.key_map_preference)
                 .normalize_shift(k.key.mods);
-            tables.default.insert(
-                (key, mods),
+            add_key_table_entry(
+                tables.default.entry((key, mods)).or_default(),
                 KeyTableEntry {
                     action: k.action.clone(),
+                    when: k.when.clone(),
                 },
             );
         }
@@ -471,10 +584,11 @@ impl Config {
                     .key
                     .resolve(self.key_input.key_map_preference)
                     .normalize_shift(k.key.mods);
-                table.insert(
-                    (key, mods),
+                add_key_table_entry(
+                    table.entry((key, mods)).or_default(),
                     KeyTableEntry {
                         action: k.action.clone(),
+                        when: k.when.clone(),
                     },
                 );
             }
@@ -484,13 +598,20 @@ impl Config {
         tables
     }
 
+    /// Returns the configured mouse bindings, keyed first by the region
+    /// they are restricted to; region-less bindings are keyed by `None`.
     pub fn mouse_bindings(
         &self,
-    ) -> HashMap<(MouseEventTrigger, MouseEventTriggerMods), KeyAssignment> {
-        let mut map = HashMap::new();
+    ) -> HashMap<
+        Option<MouseRegion>,
+        HashMap<(MouseEventTrigger, MouseEventTriggerMods), KeyAssignment>,
+    > {
+        let mut map: HashMap<Option<MouseRegion>, HashMap<_, _>> = HashMap::new();
 
         for m in &self.mouse.mouse_bindings {
-            map.insert((m.event.clone(), m.mods), m.action.clone());
+            map.entry(m.region)
+                .or_default()
+                .insert((m.event.clone(), m.mods), m.action.clone());
         }
 
         map
@@ -532,6 +653,10 @@ impl Config {
         self.scroll.alternate_buffer_wheel_scroll_speed
     }
 
+    pub fn alternate_buffer_wheel_behavior(&self) -> AlternateBufferWheelBehavior {
+        self.scroll.alternate_buffer_wheel_behavior()
+    }
+
     pub fn cursor_thickness(&self) -> Option<Dimension> {
         self.cursor.cursor_thickness
     }
@@ -687,8 +812,16 @@ impl Config {
                     cfg.gpu.webgpu_shader.replace(config_dir.join(path));
                 }
             }
+
+            for path in cfg.gpu.webgpu_shader_effects.values_mut() {
+                if !path.is_absolute() {
+                    *path = config_dir.join(&path);
+                }
+            }
         }
 
+        cfg.validate_postprocess_shaders();
+
         // Add some reasonable default font rules
         let reduced = self.font_config.font.reduce_first_font_to_family();
 
@@ -739,26 +872,48 @@ impl Config {
         cfg.load_color_schemes(&cfg.compute_color_scheme_dirs())
             .ok();
 
-        if let Some(scheme) = cfg.color_config.color_scheme.as_ref() {
-            match cfg.resolve_color_scheme() {
-                None => {
-                    log::error!(
-                        "Your configuration specifies color_scheme=\"{}\" \
-                        but that scheme was not found",
-                        scheme
-                    );
-                }
-                Some(p) => {
-                    cfg.color_config.resolved_palette = p.clone();
-                }
-            }
+        cfg.resolve_palette(crate::system_appearance());
+
+        cfg
+    }
+
+    /// Check the post-processing shaders and drop any that cannot be
+    /// used, warning about the problem, so that the GUI doesn't attempt
+    /// to build a pipeline from them
+    fn validate_postprocess_shaders(&mut self) {
+        fn warn(field: String, err: anyhow::Error, consequence: &str) {
+            phaedra_dynamic::Error::warn(phaedra_dynamic::Warning::InvalidValue {
+                struct_name: "GpuConfig",
+                field,
+                message: format!("{:#}; {}", err, consequence),
+                location: None,
+            });
         }
 
-        if let Some(colors) = &cfg.color_config.colors {
-            cfg.color_config.resolved_palette = cfg.color_config.resolved_palette.overlay_with(colors);
+        if let Some(path) = &self.gpu.webgpu_shader {
+            if let Err(err) = crate::shader_validation::validate_postprocess_shader(path) {
+                warn(
+                    "webgpu_shader".to_string(),
+                    err,
+                    "post-processing will not be enabled",
+                );
+                self.gpu.webgpu_shader.take();
+            }
         }
 
-        cfg
+        self.gpu.webgpu_shader_effects.retain(|name, path| {
+            match crate::shader_validation::validate_postprocess_shader(path) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn(
+                        format!("webgpu_shader_effects.{}", name),
+                        err,
+                        "the effect will not be available",
+                    );
+                    false
+                }
+            }
+        });
     }
 
     fn compute_color_scheme_dirs(&self) -> Vec<PathBuf> {
@@ -779,18 +934,15 @@ impl Config {
 
     fn load_color_schemes(&mut self, paths: &[PathBuf]) -> anyhow::Result<()> {
         fn extract_scheme_name(name: &str) -> Option<&str> {
-            if name.ends_with(".toml") {
-                let len = name.len();
-                Some(&name[..len - 5])
-            } else {
-                None
-            }
+            [".toml", ".yaml", ".yml"]
+                .iter()
+                .find_map(|ext| name.strip_suffix(ext))
         }
 
-        fn load_scheme(path: &Path) -> anyhow::Result<ColorSchemeFile> {
-            let s = std::fs::read_to_string(path)?;
-            ColorSchemeFile::from_toml_str(&s).context("parsing TOML")
-        }
+        // Tracks the schemes loaded from files by this function, so that
+        // a native scheme can take precedence over a foreign scheme of the
+        // same name, regardless of the order in which they are found.
+        let mut loaded: HashMap<String, (ColorSchemeFormat, PathBuf)> = HashMap::new();
 
         for colors_dir in paths {
             if let Ok(dir) = std::fs::read_dir(colors_dir) {
@@ -798,24 +950,59 @@ impl Config {
                     if let Ok(entry) = entry {
                         if let Some(name) = entry.file_name().to_str() {
                             if let Some(scheme_name) = extract_scheme_name(name) {
-                                if self.color_config.color_schemes.contains_key(scheme_name) {
+                                if self.color_config.color_schemes.contains_key(scheme_name)
+                                    && !loaded.contains_key(scheme_name)
+                                {
                                     // This scheme has already been defined
                                     continue;
                                 }
 
                                 let path = entry.path();
-                                match load_scheme(&path) {
-                                    Ok(scheme) => {
+                                match ColorSchemeFile::load_file(&path) {
+                                    Ok((scheme, format)) => {
                                         let name = scheme
                                             .metadata
                                             .name
                                             .unwrap_or_else(|| scheme_name.to_string());
+
+                                        if let Some((prior_format, prior_path)) = loaded.get(&name)
+                                        {
+                                            let replace = format == ColorSchemeFormat::Native
+                                                && *prior_format != ColorSchemeFormat::Native;
+                                            let (winner, loser) = if replace {
+                                                (&path, prior_path)
+                                            } else {
+                                                (prior_path, &path)
+                                            };
+                                            log::info!(
+                                                "Color scheme `{}` from {} takes precedence \
+                                                 over the one from {}",
+                                                name,
+                                                winner.display(),
+                                                loser.display(),
+                                            );
+                                            if !replace {
+                                                continue;
+                                            }
+                                        } else if self
+                                            .color_config
+                                            .color_schemes
+                                            .contains_key(&name)
+                                        {
+                                            // This scheme has already been defined
+                                            continue;
+                                        }
+
                                         log::trace!(
-                                            "Loaded color scheme `{}` from {}",
+                                            "Loaded {:?} color scheme `{}` from {}",
+                                            format,
                                             name,
                                             path.display()
                                         );
-                                        self.color_config.color_schemes.insert(name, scheme.colors);
+                                        self.color_config
+                                            .color_schemes
+                                            .insert(name.clone(), scheme.colors);
+                                        loaded.insert(name, (format, path));
                                     }
                                     Err(err) => {
                                         log::error!(
@@ -835,9 +1022,69 @@ impl Config {
         Ok(())
     }
 
-    pub fn resolve_color_scheme(&self) -> Option<&Palette> {
-        let scheme_name = self.color_config.color_scheme.as_ref()?;
+    /// Resolve the color scheme that is chosen for `appearance`,
+    /// overlaid with `colors`, into `resolved_palette`.
+    /// If the scheme cannot be found, `colors` is overlaid on the
+    /// palette as it was.
+    pub fn resolve_palette(&mut self, appearance: SystemAppearance) {
+        if let Some(scheme) = self.color_scheme_name(appearance) {
+            match self.lookup_color_scheme(&scheme) {
+                None => {
+                    log::error!(
+                        "Your configuration specifies color_scheme=\"{}\" \
+                        but that scheme was not found",
+                        scheme
+                    );
+                }
+                Some(p) => {
+                    self.color_config.resolved_palette = p.clone();
+                }
+            }
+        }
 
+        let scheme_palette = self.color_config.resolved_palette.clone();
+        let palette = match &self.color_config.colors {
+            Some(colors) => scheme_palette.overlay_with(colors),
+            None => scheme_palette.clone(),
+        };
+        self.color_config.resolved_palette = match palette.resolve_derived_colors() {
+            Ok(palette) => palette,
+            Err(err) => {
+                phaedra_dynamic::Error::warn(phaedra_dynamic::Warning::InvalidValue {
+                    struct_name: "ColorConfig",
+                    field: "colors".to_string(),
+                    message: format!("{:#}; the overrides will be ignored", err),
+                    location: None,
+                });
+                scheme_palette.resolve_derived_colors().unwrap_or_default()
+            }
+        };
+    }
+
+    /// Returns the name of the color scheme chosen for `appearance`,
+    /// taking `color_scheme_file` into account
+    fn color_scheme_name(&self, appearance: SystemAppearance) -> Option<String> {
+        let file_contents = self.color_config.color_scheme_file().and_then(|path| {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => Some(contents),
+                Err(err) => {
+                    log::warn!(
+                        "Unable to read color_scheme_file {}: {:#}",
+                        path.display(),
+                        err
+                    );
+                    None
+                }
+            }
+        });
+        resolve_color_scheme_name(
+            self.color_config.color_scheme.as_ref(),
+            appearance,
+            file_contents.as_deref(),
+        )
+    }
+
+    fn lookup_color_scheme(&self, scheme_name: &str) -> Option<&Palette> {
         if let Some(palette) = self.color_config.color_schemes.get(scheme_name) {
             Some(palette)
         } else {
@@ -845,6 +1092,11 @@ impl Config {
         }
     }
 
+    pub fn resolve_color_scheme(&self) -> Option<&Palette> {
+        let scheme_name = self.color_scheme_name(crate::system_appearance())?;
+        self.lookup_color_scheme(&scheme_name)
+    }
+
     pub fn initial_size(&self, dpi: u32, cell_pixel_dims: Option<(usize, usize)>) -> TerminalSize {
         // If we aren't passed the actual values, guess at a plausible
         // default set of pixel dimensions.
@@ -867,11 +1119,14 @@ impl Config {
         }
     }
 
+    /// When `context` is set, `default_prog` and `default_cwd` are
+    /// templates whose `${variable}` references are expanded using it
     pub fn build_prog(
         &self,
         prog: Option<Vec<&OsStr>>,
         default_prog: Option<&Vec<String>>,
         default_cwd: Option<&PathBuf>,
+        context: Option<&SpawnContext>,
     ) -> anyhow::Result<CommandBuilder> {
         let mut cmd = match prog {
             Some(args) => {
@@ -882,6 +1137,7 @@ impl Config {
             }
             None => {
                 if let Some(prog) = default_prog {
+                    let prog = expand_prog(prog, context)?;
                     let mut args = prog.iter();
                     let mut cmd = CommandBuilder::new(args.next().expect("executable name"));
                     cmd.args(args);
@@ -892,7 +1148,7 @@ impl Config {
             }
         };
 
-        self.apply_cmd_defaults(&mut cmd, None, default_cwd);
+        self.apply_cmd_defaults(&mut cmd, None, default_cwd, context)?;
 
         Ok(cmd)
     }
@@ -902,16 +1158,25 @@ impl Config {
         cmd: &mut CommandBuilder,
         default_prog: Option<&Vec<String>>,
         default_cwd: Option<&PathBuf>,
-    ) {
+        context: Option<&SpawnContext>,
+    ) -> anyhow::Result<()> {
         // Apply `default_cwd` only if `cwd` is not already set, allows `--cwd`
         // option to take precedence
         if let (None, Some(cwd)) = (cmd.get_cwd(), default_cwd) {
-            cmd.cwd(cwd);
+            match context {
+                Some(context) => {
+                    let cwd = cwd
+                        .to_str()
+                        .ok_or_else(|| anyhow::anyhow!("default_cwd {cwd:?} is not UTF-8"))?;
+                    cmd.cwd(context.expand(cwd)?);
+                }
+                None => cmd.cwd(cwd),
+            }
         }
 
         if let Some(default_prog) = default_prog {
             if cmd.is_default_prog() {
-                cmd.replace_default_prog(default_prog);
+                cmd.replace_default_prog(&expand_prog(default_prog, context)?);
             }
         }
 
@@ -955,6 +1220,14 @@ impl Config {
         // de-facto standard for identifying the terminal.
         cmd.env("TERM_PROGRAM", "Phaedra");
         cmd.env("TERM_PROGRAM_VERSION", crate::phaedra_version());
+        Ok(())
+    }
+}
+
+fn expand_prog(prog: &[String], context: Option<&SpawnContext>) -> anyhow::Result<Vec<String>> {
+    match context {
+        Some(context) => prog.iter().map(|arg| context.expand(arg)).collect(),
+        None => Ok(prog.to_vec()),
     }
 }
 
@@ -1060,6 +1333,19 @@ impl Default for WindowPadding {
     }
 }
 
+impl WindowPadding {
+    /// Returns this padding with the sides that are specified by
+    /// `padding` replaced
+    pub fn with_override(&self, padding: &WindowPaddingOverride) -> Self {
+        Self {
+            left: padding.left.unwrap_or(self.left),
+            top: padding.top.unwrap_or(self.top),
+            right: padding.right.unwrap_or(self.right),
+            bottom: padding.bottom.unwrap_or(self.bottom),
+        }
+    }
+}
+
 #[derive(FromDynamic, ToDynamic, Clone, Copy, Debug, Default)]
 pub struct WindowContentAlignment {
     pub horizontal: HorizontalWindowContentAlignment,
@@ -1207,9 +1493,11 @@ impl FromDynamic for BoldBrightening {
                 "No" => Ok(Self::No),
                 "BrightAndBold" => Ok(Self::BrightAndBold),
                 "BrightOnly" => Ok(Self::BrightOnly),
-                s => Err(phaedra_dynamic::Error::Message(format!(
-                    "`{s}` is not valid, use one of `No`, `BrightAndBold` or `BrightOnly`"
-                ))),
+                s => Err(phaedra_dynamic::Error::InvalidVariantForType {
+                    variant_name: s.to_string(),
+                    type_name: "BoldBrightening",
+                    possible: &["No", "BrightAndBold", "BrightOnly"],
+                }),
             },
             Err(err) => match bool::from_dynamic(value, options) {
                 Ok(true) => Ok(Self::BrightAndBold),
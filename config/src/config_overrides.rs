@@ -0,0 +1,214 @@
+//! Overrides of configuration values that are given on the command
+//! line, either as lua with `--config key=value`, or as JSON with
+//! `--config-json key=value`.
+use crate::json_to_dynamic;
+use anyhow::Context;
+use phaedra_dynamic::Value;
+use std::collections::BTreeMap;
+
+/// An override of a configuration value from the command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigOverride {
+    /// From `--config key=value`: the value is lua, which is evaluated
+    /// when the configuration is loaded
+    Lua { key: String, value: String },
+    /// From `--config-json key=value`: the value was parsed from JSON,
+    /// and is merged into the configuration without evaluating any lua
+    Json { key: String, value: Value },
+}
+
+impl ConfigOverride {
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Lua { key, .. } | Self::Json { key, .. } => key,
+        }
+    }
+}
+
+/// Parses the `--config` and `--config-json` overrides.  Fails if a
+/// `--config-json` value isn't valid JSON, or if the same key is given
+/// to both options.
+pub fn parse_config_overrides(
+    lua: &[(String, String)],
+    json: &[(String, String)],
+) -> anyhow::Result<Vec<ConfigOverride>> {
+    let mut overrides: Vec<ConfigOverride> = lua
+        .iter()
+        .map(|(key, value)| ConfigOverride::Lua {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect();
+
+    for (key, value) in json {
+        if let Some((_, lua_value)) = lua.iter().find(|(lua_key, _)| lua_key == key) {
+            anyhow::bail!(
+                "`{key}` is overridden by both `--config {key}={lua_value}` \
+                 and `--config-json {key}={value}`"
+            );
+        }
+        let parsed: serde_json::Value = serde_json::from_str(value)
+            .with_context(|| format!("`--config-json {key}={value}` is not valid JSON"))?;
+        overrides.push(ConfigOverride::Json {
+            key: key.clone(),
+            value: json_to_dynamic(&parsed),
+        });
+    }
+
+    Ok(overrides)
+}
+
+/// Builds the object that holds each of the `--config-json` overrides
+/// at its key, which is a dotted path such as `font_config.font_size`.
+/// The objects along the path are created as needed, so that merging
+/// the result into the configuration only changes the named fields.
+/// When the same key is given more than once, the last value is used.
+pub(crate) fn json_overrides_object(overrides: &[ConfigOverride]) -> anyhow::Result<Value> {
+    let mut root = BTreeMap::new();
+    for (idx, item) in overrides.iter().enumerate() {
+        if let ConfigOverride::Json { key, value } = item {
+            let replaced_later = overrides[idx + 1..]
+                .iter()
+                .any(|later| matches!(later, ConfigOverride::Json { .. }) && later.key() == key);
+            if !replaced_later {
+                insert_at_path(&mut root, key, value.clone())?;
+            }
+        }
+    }
+    Ok(Value::Object(root.into()))
+}
+
+fn insert_at_path(
+    root: &mut BTreeMap<Value, Value>,
+    path: &str,
+    value: Value,
+) -> anyhow::Result<()> {
+    let components: Vec<&str> = path.split('.').collect();
+    if components.iter().any(|component| component.is_empty()) {
+        anyhow::bail!("`--config-json {path}` has an empty key");
+    }
+    let (last, parents) = components
+        .split_last()
+        .expect("split always yields at least one component");
+
+    let mut object = root;
+    for (idx, component) in parents.iter().enumerate() {
+        let entry = object
+            .entry(Value::String(component.to_string()))
+            .or_insert_with(|| Value::Object(BTreeMap::new().into()));
+        object = match entry {
+            Value::Object(child) => &mut **child,
+            _ => anyhow::bail!(
+                "`--config-json {path}` conflicts with `--config-json {}`",
+                components[..=idx].join(".")
+            ),
+        };
+    }
+
+    let key = Value::String(last.to_string());
+    if object.contains_key(&key) {
+        anyhow::bail!("`--config-json {path}` conflicts with the overrides of its fields");
+    }
+    object.insert(key, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Config;
+    use phaedra_dynamic::FromDynamic;
+
+    fn json(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dotted_paths_create_intermediate_objects() {
+        let overrides = parse_config_overrides(
+            &[],
+            &json(&[
+                ("font_config.font_size", "14"),
+                ("font_config.harfbuzz_features", r#"["calt=0"]"#),
+                ("window_config.initial_rows", "30"),
+                ("window_config.initial_rows", "40"),
+            ]),
+        )
+        .unwrap();
+        let object = json_overrides_object(&overrides).unwrap();
+
+        let expected: serde_json::Value = serde_json::from_str(
+            r#"{
+                "font_config": {"font_size": 14, "harfbuzz_features": ["calt=0"]},
+                "window_config": {"initial_rows": 40}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(object, json_to_dynamic(&expected));
+    }
+
+    #[test]
+    fn malformed_overrides_are_errors() {
+        let err = parse_config_overrides(&[], &json(&[("font_config", "{size: 1}")])).unwrap_err();
+        assert!(err.to_string().contains("not valid JSON"), "{err:#}");
+
+        let err = parse_config_overrides(
+            &json(&[("font_config", "{font_size = 10}")]),
+            &json(&[("font_config", r#"{"font_size": 12}"#)]),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.contains("--config font_config={font_size = 10}"),
+            "{err}"
+        );
+        assert!(
+            err.contains(r#"--config-json font_config={"font_size": 12}"#),
+            "{err}"
+        );
+
+        let overrides = parse_config_overrides(
+            &[],
+            &json(&[("window_config", "1"), ("window_config.initial_rows", "2")]),
+        )
+        .unwrap();
+        assert!(json_overrides_object(&overrides).is_err());
+
+        let overrides =
+            parse_config_overrides(&[], &json(&[("font_config..font_size", "1")])).unwrap();
+        assert!(json_overrides_object(&overrides).is_err());
+
+        // A value of the wrong type is reported when the configuration
+        // is converted, as it would be for the config file
+        let overrides =
+            parse_config_overrides(&[], &json(&[("window_config.initial_rows", r#""many""#)]))
+                .unwrap();
+        let object = json_overrides_object(&overrides).unwrap();
+        assert!(Config::from_dynamic(&object, Default::default()).is_err());
+    }
+
+    #[test]
+    fn overrides_are_merged_over_the_config_file() {
+        let lua = mlua::Lua::new();
+        let file_config: mlua::Value = lua
+            .load("return {font_config = {font_size = 10, dpi = 96}}")
+            .eval()
+            .unwrap();
+        let overrides =
+            parse_config_overrides(&[], &json(&[("font_config.font_size", "14")])).unwrap();
+        let config = Config::apply_overrides_obj_to(
+            &lua,
+            file_config,
+            &json_overrides_object(&overrides).unwrap(),
+        )
+        .unwrap();
+
+        let config = luahelper::lua_value_to_dynamic(config).unwrap();
+        let config = Config::from_dynamic(&config, Default::default()).unwrap();
+        assert_eq!(config.font_config.font_size, 14.0);
+        assert_eq!(config.font_config.dpi, Some(96.0));
+    }
+}
@@ -1,9 +1,10 @@
 use crate::bell::EasingFunction;
 use crate::config::DefaultCursorStyle;
 use crate::units::Dimension;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct CursorConfig {
     #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
     pub cursor_thickness: Option<Dimension>,
@@ -23,6 +24,23 @@ pub struct CursorConfig {
     pub xcursor_theme: Option<String>,
     #[dynamic(default)]
     pub xcursor_size: Option<u32>,
+    /// How the cursor is drawn when the window doesn't have the focus,
+    /// or the pane isn't the active pane
+    #[dynamic(default)]
+    pub unfocused_cursor_style: UnfocusedCursorStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, FromDynamic, ToDynamic)]
+pub enum UnfocusedCursorStyle {
+    /// An outline of the cell, whatever the shape of the cursor
+    #[default]
+    Hollow,
+    /// The cursor keeps its shape, but its color is made more
+    /// transparent by scaling its alpha by `alpha`, a value ranging
+    /// from 0.0 to 1.0
+    Dimmed { alpha: f32 },
+    /// The cursor is drawn just as it is when focused
+    Unchanged,
 }
 
 impl Default for CursorConfig {
@@ -37,6 +55,7 @@ impl Default for CursorConfig {
             reverse_video_cursor_min_contrast: default_reverse_video_cursor_min_contrast(),
             xcursor_theme: None,
             xcursor_size: None,
+            unfocused_cursor_style: UnfocusedCursorStyle::default(),
         }
     }
 }
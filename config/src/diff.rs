@@ -0,0 +1,305 @@
+//! Comparing two configurations, so that a reload can report what it
+//! changed.  The comparison is made between the ToDynamic
+//! representations, so that the paths match the names used in lua.
+use crate::config::Config;
+use luahelper::impl_lua_conversion_dynamic;
+use phaedra_dynamic::{ToDynamic, Value};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Values whose rendering is longer than this are summarized
+/// rather than shown in full
+const MAX_VALUE_LEN: usize = 80;
+
+/// A single field that differs between two configurations
+#[derive(Debug, Clone, PartialEq, Eq, ToDynamic)]
+pub struct ConfigDelta {
+    /// The dotted path to the field, eg: `font_config.font_size`.
+    /// Array elements are numbered from 1, as they are in lua.
+    pub path: String,
+    /// The prior value, or None if the field was unset or the array
+    /// element was inserted
+    pub old: Option<String>,
+    /// The new value, or None if the field was unset or the array
+    /// element was removed
+    pub new: Option<String>,
+}
+impl_lua_conversion_dynamic!(ConfigDelta);
+
+impl std::fmt::Display for ConfigDelta {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{}: {} -> {}",
+            self.path,
+            self.old.as_deref().unwrap_or("nil"),
+            self.new.as_deref().unwrap_or("nil")
+        )
+    }
+}
+
+impl Config {
+    /// Returns the fields that differ between this configuration and
+    /// `other`, in path order
+    pub fn diff(&self, other: &Config) -> Vec<ConfigDelta> {
+        let mut deltas = vec![];
+        diff_values(
+            String::new(),
+            Some(&self.to_dynamic()),
+            Some(&other.to_dynamic()),
+            &mut deltas,
+        );
+        deltas
+    }
+}
+
+/// Compares `old` and `new` at `path`, where None means that there is
+/// no such field or element.  An unset optional field is Null, which
+/// is treated as being absent.
+fn diff_values(
+    path: String,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    deltas: &mut Vec<ConfigDelta>,
+) {
+    let old = old.filter(|value| **value != Value::Null);
+    let new = new.filter(|value| **value != Value::Null);
+    match (old, new) {
+        (Some(old), Some(new)) if old == new => {}
+        (None, None) => {}
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys: BTreeSet<&Value> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                diff_values(field_path(&path, key), old.get(key), new.get(key), deltas);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => diff_arrays(&path, old, new, deltas),
+        (old, new) => deltas.push(ConfigDelta {
+            path,
+            old: old.map(format_value),
+            new: new.map(format_value),
+        }),
+    }
+}
+
+/// Arrays are compared element by element, except that when the only
+/// difference is a run of elements inserted or removed at one place,
+/// just those elements are reported, rather than every element that
+/// follows them.
+fn diff_arrays(path: &str, old: &[Value], new: &[Value], deltas: &mut Vec<ConfigDelta>) {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    if prefix + suffix == old.len().min(new.len()) {
+        for idx in prefix..old.len() - suffix {
+            diff_values(element_path(path, idx), Some(&old[idx]), None, deltas);
+        }
+        for idx in prefix..new.len() - suffix {
+            diff_values(element_path(path, idx), None, Some(&new[idx]), deltas);
+        }
+        return;
+    }
+
+    for idx in 0..old.len().max(new.len()) {
+        diff_values(element_path(path, idx), old.get(idx), new.get(idx), deltas);
+    }
+}
+
+fn field_path(path: &str, key: &Value) -> String {
+    match key {
+        Value::String(key) if path.is_empty() => key.to_string(),
+        Value::String(key) => format!("{path}.{key}"),
+        key => format!("{path}[{}]", format_value(key)),
+    }
+}
+
+fn element_path(path: &str, idx: usize) -> String {
+    format!("{path}[{}]", idx + 1)
+}
+
+/// Renders `value` in lua syntax, or as a summary if that would be
+/// too long to be useful, as it is for a whole color scheme
+fn format_value(value: &Value) -> String {
+    let mut result = String::new();
+    if write_value(value, &mut result) {
+        return result;
+    }
+    match value {
+        Value::Array(array) => format!("<array of {} elements>", array.len()),
+        Value::Object(object) => format!("<table with {} fields>", object.len()),
+        Value::String(s) => format!("<string of {} bytes>", s.len()),
+        _ => result,
+    }
+}
+
+/// Appends `value` to `out`, returning false as soon as `out` becomes
+/// longer than MAX_VALUE_LEN
+fn write_value(value: &Value, out: &mut String) -> bool {
+    match value {
+        Value::Null => out.push_str("nil"),
+        Value::Bool(b) => write!(out, "{b}").unwrap(),
+        Value::String(s) => write!(out, "{s:?}").unwrap(),
+        Value::U64(n) => write!(out, "{n}").unwrap(),
+        Value::I64(n) => write!(out, "{n}").unwrap(),
+        Value::F64(n) => write!(out, "{n}").unwrap(),
+        Value::Array(array) => {
+            out.push('{');
+            for (idx, element) in array.iter().enumerate() {
+                out.push_str(if idx == 0 { " " } else { ", " });
+                if !write_value(element, out) {
+                    return false;
+                }
+            }
+            out.push_str(" }");
+        }
+        Value::Object(object) => {
+            out.push('{');
+            for (idx, (key, element)) in object.iter().enumerate() {
+                out.push_str(if idx == 0 { " " } else { ", " });
+                match key {
+                    Value::String(key) => out.push_str(key),
+                    key => {
+                        out.push('[');
+                        if !write_value(key, out) {
+                            return false;
+                        }
+                        out.push(']');
+                    }
+                }
+                out.push_str(" = ");
+                if !write_value(element, out) {
+                    return false;
+                }
+            }
+            out.push_str(" }");
+        }
+    }
+    out.len() <= MAX_VALUE_LEN
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RecordFramesConfig;
+    use std::path::PathBuf;
+
+    fn delta(path: &str, old: Option<&str>, new: Option<&str>) -> ConfigDelta {
+        ConfigDelta {
+            path: path.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    fn with_font_dirs(dirs: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.font_config.font_dirs = dirs.iter().map(PathBuf::from).collect();
+        config
+    }
+
+    #[test]
+    fn identical_configs() {
+        assert_eq!(Config::default().diff(&Config::default()), vec![]);
+    }
+
+    #[test]
+    fn nested_fields() {
+        let old = Config::default();
+        let mut new = Config::default();
+        new.font_config.font_size = 13.;
+        new.tab_bar.enable_tab_bar = !old.tab_bar.enable_tab_bar;
+
+        let deltas = old.diff(&new);
+        assert_eq!(
+            deltas,
+            vec![
+                delta("font_config.font_size", Some("12"), Some("13")),
+                delta(
+                    "tab_bar.enable_tab_bar",
+                    Some(&old.tab_bar.enable_tab_bar.to_string()),
+                    Some(&new.tab_bar.enable_tab_bar.to_string())
+                ),
+            ]
+        );
+        assert_eq!(deltas[0].to_string(), "font_config.font_size: 12 -> 13");
+    }
+
+    #[test]
+    fn array_elements() {
+        let abc = with_font_dirs(&["/a", "/b", "/c"]);
+
+        // A changed element is reported on its own
+        assert_eq!(
+            abc.diff(&with_font_dirs(&["/a", "/x", "/c"])),
+            vec![delta(
+                "font_config.font_dirs[2]",
+                Some("\"/b\""),
+                Some("\"/x\"")
+            )]
+        );
+
+        // An insertion or removal doesn't shift the elements after it
+        assert_eq!(
+            with_font_dirs(&["/a", "/c"]).diff(&abc),
+            vec![delta("font_config.font_dirs[2]", None, Some("\"/b\""))]
+        );
+        assert_eq!(
+            abc.diff(&with_font_dirs(&["/b", "/c"])),
+            vec![delta("font_config.font_dirs[1]", Some("\"/a\""), None)]
+        );
+
+        // Anything else is compared element by element
+        assert_eq!(
+            abc.diff(&with_font_dirs(&["/c", "/b"])),
+            vec![
+                delta("font_config.font_dirs[1]", Some("\"/a\""), Some("\"/c\"")),
+                delta("font_config.font_dirs[3]", Some("\"/c\""), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_optional_field() {
+        let mut old = Config::default();
+        old.runtime.record_frames = Some(RecordFramesConfig {
+            path: PathBuf::from("/tmp/frames"),
+            max_frames: 10,
+        });
+        let new = Config::default();
+
+        assert_eq!(
+            old.diff(&new),
+            vec![delta(
+                "runtime.record_frames",
+                Some("{ max_frames = 10, path = \"/tmp/frames\" }"),
+                None
+            )]
+        );
+        assert_eq!(
+            new.diff(&old)[0].to_string(),
+            "runtime.record_frames: nil -> { max_frames = 10, path = \"/tmp/frames\" }"
+        );
+    }
+
+    #[test]
+    fn large_values_are_summarized() {
+        let array = Value::Array((0..100u64).map(Value::U64).collect());
+        assert_eq!(format_value(&array), "<array of 100 elements>");
+
+        let object = Value::Object(
+            (0..30u64)
+                .map(|n| (Value::String(format!("field{n}")), Value::U64(n)))
+                .collect(),
+        );
+        assert_eq!(format_value(&object), "<table with 30 fields>");
+    }
+}
@@ -2,9 +2,10 @@ use crate::exec_domain::ExecDomain;
 use crate::ssh::{SshBackend, SshDomain};
 use crate::tls::{TlsDomainClient, TlsDomainServer};
 use crate::unix::UnixDomain;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct DomainConfig {
     #[dynamic(default)]
     pub exec_domains: Vec<ExecDomain>,
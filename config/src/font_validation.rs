@@ -0,0 +1,262 @@
+//! Checks the fonts that the configuration refers to while it is loaded,
+//! so that a `font_dirs` entry that holds no fonts, or a font that is
+//! named but can't be found, is reported as a configuration warning
+//! rather than silently showing a fallback font.
+use crate::{Config, FontAttributes, FontConfig};
+use anyhow::anyhow;
+use std::path::Path;
+
+/// The extensions of the files that are taken to be fonts when
+/// looking for them in a `font_dirs` entry
+const FONT_EXTENSIONS: &[&str] = &[
+    "ttf", "ttc", "otf", "otc", "woff", "woff2", "dfont", "pfa", "pfb", "pcf", "bdf",
+];
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| {
+            FONT_EXTENSIONS
+                .iter()
+                .any(|font_ext| ext.eq_ignore_ascii_case(font_ext))
+        })
+}
+
+/// Returns true if there is a font file in `dir` or in any of the
+/// directories beneath it
+fn contains_font_file(dir: &Path) -> bool {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if contains_font_file(&path) {
+                return true;
+            }
+        } else if is_font_file(&path) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Check that `dir` is a directory with at least one font file in it
+pub fn check_font_dir(dir: &Path) -> anyhow::Result<()> {
+    if !dir.exists() {
+        return Err(anyhow!("{} does not exist", dir.display()));
+    }
+    if !dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", dir.display()));
+    }
+    if !contains_font_file(dir) {
+        return Err(anyhow!(
+            "{} contains no font files (looked for {})",
+            dir.display(),
+            FONT_EXTENSIONS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn warn(field: String, message: String) {
+    phaedra_dynamic::Error::warn(phaedra_dynamic::Warning::InvalidValue {
+        struct_name: "FontConfig",
+        field,
+        message,
+        location: None,
+    });
+}
+
+/// Warn about each of the `font_dirs` of `config` that can't provide
+/// any fonts, and each font named by `font` and `font_rules` that can't
+/// be found.  `configured` is the font configuration as it was written,
+/// before relative `font_dirs` were resolved against `config_dir` and
+/// the default `font_rules` were added.
+pub fn validate_fonts(config: &Config, configured: &FontConfig, config_dir: Option<&Path>) {
+    for (idx, (dir, as_written)) in config
+        .font_config
+        .font_dirs
+        .iter()
+        .zip(configured.font_dirs.iter())
+        .enumerate()
+    {
+        if let Err(err) = check_font_dir(dir) {
+            let resolved = match config_dir {
+                Some(config_dir) if !as_written.is_absolute() => format!(
+                    " ({} was resolved relative to the config directory {})",
+                    as_written.display(),
+                    config_dir.display()
+                ),
+                _ => String::new(),
+            };
+            warn(
+                format!("font_dirs[{}]", idx + 1),
+                format!("{:#}{}; no fonts will be loaded from it", err, resolved),
+            );
+        }
+    }
+
+    let mut named: Vec<(String, &FontAttributes)> = vec![];
+    for attr in &configured.font.font {
+        named.push(("font".to_string(), attr));
+    }
+    for (idx, rule) in configured.font_rules.iter().enumerate() {
+        for attr in &rule.font.font {
+            named.push((format!("font_rules[{}]", idx + 1), attr));
+        }
+    }
+    named.retain(|(_, attr)| !attr.is_fallback);
+
+    let fonts: Vec<FontAttributes> = named.iter().map(|(_, attr)| (*attr).clone()).collect();
+    let Some(unresolved) = crate::unresolved_fonts(config, &fonts) else {
+        return;
+    };
+    for (field, attr) in named {
+        if unresolved.contains(attr) {
+            warn(
+                field,
+                format!(
+                    "{} was not found in font_dirs, by the font locator, or among \
+                     the built-in fonts; a fallback font will be used instead",
+                    attr
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TextStyle;
+    use std::path::PathBuf;
+
+    /// A directory holding an empty directory, a directory with a font
+    /// file nested a level down, and a directory with only a text file
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "phaedra-font-validation-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(root.join("empty")).unwrap();
+            std::fs::create_dir_all(root.join("fonts/nested")).unwrap();
+            std::fs::write(root.join("fonts/nested/Example-Regular.TTF"), b"").unwrap();
+            std::fs::create_dir_all(root.join("text")).unwrap();
+            std::fs::write(root.join("text/README.txt"), b"").unwrap();
+            Self { root }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.root).ok();
+        }
+    }
+
+    #[test]
+    fn font_dirs() {
+        let fixture = Fixture::new("dirs");
+        let root = &fixture.root;
+
+        assert!(check_font_dir(&root.join("fonts")).is_ok());
+
+        let err = check_font_dir(&root.join("empty")).unwrap_err().to_string();
+        assert!(err.contains("contains no font files"), "{err}");
+        let err = check_font_dir(&root.join("text")).unwrap_err().to_string();
+        assert!(err.contains("contains no font files"), "{err}");
+
+        let err = check_font_dir(&root.join("missing"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with("does not exist"), "{err}");
+
+        let err = check_font_dir(&root.join("text/README.txt"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.ends_with("is not a directory"), "{err}");
+    }
+
+    fn unresolved_unless_available(
+        _config: &Config,
+        fonts: &[FontAttributes],
+    ) -> Vec<FontAttributes> {
+        fonts
+            .iter()
+            .filter(|attr| attr.family != "Available")
+            .cloned()
+            .collect()
+    }
+
+    fn style(families: &[&str]) -> TextStyle {
+        TextStyle {
+            font: families
+                .iter()
+                .map(|family| FontAttributes::new(family))
+                .collect(),
+            foreground: None,
+        }
+    }
+
+    #[test]
+    fn warnings() {
+        let fixture = Fixture::new("warnings");
+        crate::assign_font_resolver(unresolved_unless_available);
+
+        let mut configured = FontConfig::default();
+        configured.font_dirs = vec![
+            PathBuf::from("fonts"),
+            PathBuf::from("empty"),
+            fixture.root.join("missing"),
+        ];
+        configured.font = style(&["Available", "Missing"]);
+        configured.font_rules = vec![
+            crate::StyleRule {
+                font: style(&["Available"]),
+                ..Default::default()
+            },
+            crate::StyleRule {
+                font: style(&["Also Missing"]),
+                ..Default::default()
+            },
+        ];
+
+        let mut config = Config::default();
+        config.font_config = configured.clone();
+        config.font_config.font_dirs = vec![
+            fixture.root.join("fonts"),
+            fixture.root.join("empty"),
+            fixture.root.join("missing"),
+        ];
+
+        let ((), warnings) = phaedra_dynamic::Error::capture_warnings(|| {
+            validate_fonts(&config, &configured, Some(&fixture.root))
+        });
+        let warnings: Vec<(String, String)> = warnings
+            .into_iter()
+            .map(|warning| match warning {
+                phaedra_dynamic::Warning::InvalidValue { field, message, .. } => (field, message),
+                warning => panic!("unexpected {warning:?}"),
+            })
+            .collect();
+        let fields: Vec<&str> = warnings.iter().map(|(field, _)| field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["font_dirs[2]", "font_dirs[3]", "font", "font_rules[2]"]
+        );
+
+        assert!(warnings[0]
+            .1
+            .contains("empty was resolved relative to the config directory"));
+        assert!(!warnings[1].1.contains("resolved relative"));
+        assert!(warnings[2].1.contains("Missing"));
+        assert!(warnings[3].1.contains("Also Missing"));
+    }
+}
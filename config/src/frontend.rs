@@ -17,9 +17,44 @@ pub struct GpuInfo {
     pub driver_info: Option<String>,
     pub vendor: Option<u32>,
     pub device: Option<u32>,
+    /// Whether this adapter appears to be a software rasterizer
+    #[dynamic(default)]
+    pub is_software: bool,
+    /// Whether the adapter can present to our window surface.
+    /// None if it was enumerated without a surface.
+    #[dynamic(default)]
+    pub surface_compatible: Option<bool>,
 }
 impl_lua_conversion_dynamic!(GpuInfo);
 
+impl GpuInfo {
+    /// Heuristic to identify software renderers such as llvmpipe,
+    /// WARP and SwiftShader, which work but are typically much
+    /// slower than a real GPU
+    pub fn detect_software(name: &str, driver: Option<&str>, device_type: &str) -> bool {
+        const SOFTWARE_NAMES: &[&str] = &[
+            "llvmpipe",
+            "softpipe",
+            "lavapipe",
+            "swiftshader",
+            "microsoft basic render driver",
+        ];
+
+        if device_type == "Cpu" {
+            return true;
+        }
+
+        let matches = |s: &str| {
+            let s = s.to_lowercase();
+            SOFTWARE_NAMES.iter().any(|candidate| s.contains(candidate))
+                // Too short to safely match as a substring
+                || s.split(|c: char| !c.is_alphanumeric()).any(|word| word == "warp")
+        };
+
+        matches(name) || driver.map(matches).unwrap_or(false)
+    }
+}
+
 impl ToString for GpuInfo {
     fn to_string(&self) -> String {
         let mut result = format!(
@@ -38,6 +73,14 @@ impl ToString for GpuInfo {
         if let Some(device) = &self.device {
             result.push_str(&format!(", device={device}"));
         }
+        if self.is_software {
+            result.push_str(", software=yes");
+        }
+        match self.surface_compatible {
+            Some(true) => result.push_str(", compatible=yes"),
+            Some(false) => result.push_str(", compatible=NO"),
+            None => result.push_str(", compatible=unknown"),
+        }
         result
     }
 }
@@ -53,3 +96,98 @@ impl Default for WebGpuPowerPreference {
         Self::LowPower
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gpu(name: &str, device_type: &str, driver: Option<&str>) -> GpuInfo {
+        GpuInfo {
+            name: name.to_string(),
+            device_type: device_type.to_string(),
+            backend: "Vulkan".to_string(),
+            driver: driver.map(|s| s.to_string()),
+            driver_info: None,
+            vendor: Some(0x10005),
+            device: Some(0),
+            is_software: GpuInfo::detect_software(name, driver, device_type),
+            surface_compatible: None,
+        }
+    }
+
+    #[test]
+    fn detects_software_renderers() {
+        assert!(GpuInfo::detect_software(
+            "llvmpipe (LLVM 15.0.7, 256 bits)",
+            Some("llvmpipe"),
+            "Cpu"
+        ));
+        assert!(GpuInfo::detect_software(
+            "llvmpipe (LLVM 15.0.7, 256 bits)",
+            None,
+            "Other"
+        ));
+        assert!(GpuInfo::detect_software(
+            "Microsoft Basic Render Driver",
+            None,
+            "IntegratedGpu"
+        ));
+        assert!(GpuInfo::detect_software("WARP", None, "Other"));
+        assert!(GpuInfo::detect_software(
+            "SwiftShader Device (Subzero)",
+            Some("SwiftShader driver"),
+            "Other"
+        ));
+        assert!(GpuInfo::detect_software("Unknown", None, "Cpu"));
+    }
+
+    #[test]
+    fn real_gpus_are_not_software() {
+        assert!(!GpuInfo::detect_software(
+            "NVIDIA GeForce RTX 3080",
+            Some("NVIDIA"),
+            "DiscreteGpu"
+        ));
+        assert!(!GpuInfo::detect_software(
+            "AMD Radeon Graphics (RADV RENOIR)",
+            Some("radv"),
+            "IntegratedGpu"
+        ));
+        assert!(!GpuInfo::detect_software("Warpspeed 9000", None, "DiscreteGpu"));
+    }
+
+    #[test]
+    fn lua_conversion_roundtrip() {
+        use mlua::{FromLua, IntoLua};
+
+        let lua = mlua::Lua::new();
+        let info = gpu("llvmpipe (LLVM 15.0.7, 256 bits)", "Cpu", Some("llvmpipe"));
+        assert!(info.is_software);
+
+        let value = info.clone().into_lua(&lua).unwrap();
+        let table = match &value {
+            mlua::Value::Table(t) => t.clone(),
+            other => panic!("expected table, got {other:?}"),
+        };
+        assert_eq!(table.get::<_, bool>("is_software").unwrap(), true);
+        assert_eq!(table.get::<_, String>("device_type").unwrap(), "Cpu");
+        assert!(table.get::<_, Option<bool>>("surface_compatible").unwrap().is_none());
+
+        let back = GpuInfo::from_lua(value, &lua).unwrap();
+        assert_eq!(back.to_string(), info.to_string());
+        assert_eq!(back.is_software, info.is_software);
+        assert_eq!(back.surface_compatible, None);
+    }
+
+    #[test]
+    fn preferred_adapter_without_new_fields_still_parses() {
+        let lua = mlua::Lua::new();
+        let table = lua.create_table().unwrap();
+        table.set("name", "NVIDIA GeForce RTX 3080").unwrap();
+        table.set("device_type", "DiscreteGpu").unwrap();
+        table.set("backend", "Vulkan").unwrap();
+        let info: GpuInfo = mlua::FromLua::from_lua(mlua::Value::Table(table), &lua).unwrap();
+        assert!(!info.is_software);
+        assert_eq!(info.surface_compatible, None);
+    }
+}
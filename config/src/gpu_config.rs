@@ -1,9 +1,11 @@
 use crate::frontend::FrontEndSelection;
 use crate::{GpuInfo, WebGpuPowerPreference};
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct GpuConfig {
     #[dynamic(default)]
     pub front_end: FrontEndSelection,
@@ -15,10 +17,26 @@ pub struct GpuConfig {
     pub webgpu_preferred_adapter: Option<GpuInfo>,
     #[dynamic(default)]
     pub webgpu_shader: Option<PathBuf>,
+    /// Named post-processing shaders that can be applied to part of
+    /// the frame, such as the area behind a modal
+    #[dynamic(default)]
+    pub webgpu_shader_effects: HashMap<String, PathBuf>,
+    /// The name of an entry in `webgpu_shader_effects` to apply to
+    /// the area covered by a modal overlay
+    #[dynamic(default)]
+    pub webgpu_modal_effect: Option<String>,
     #[dynamic(default = "default_webgpu_shader_fps")]
     pub webgpu_shader_fps: u8,
     #[dynamic(default = "default_max_fps")]
     pub max_fps: u64,
+    /// Draw split lines and rounded tab corners as lines and rounded
+    /// rects, rather than from cell-sized sprites
+    #[dynamic(default)]
+    pub experimental_vector_chrome: bool,
+    /// Draw the sections of the frame grouped by scissor rect where
+    /// that doesn't change the result, rather than in describe order
+    #[dynamic(default)]
+    pub experimental_batch_render_sections: bool,
 }
 
 impl Default for GpuConfig {
@@ -29,8 +47,12 @@ impl Default for GpuConfig {
             webgpu_force_fallback_adapter: false,
             webgpu_preferred_adapter: None,
             webgpu_shader: None,
+            webgpu_shader_effects: HashMap::new(),
+            webgpu_modal_effect: None,
             webgpu_shader_fps: default_webgpu_shader_fps(),
             max_fps: default_max_fps(),
+            experimental_vector_chrome: false,
+            experimental_batch_render_sections: false,
         }
     }
 }
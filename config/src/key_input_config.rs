@@ -1,10 +1,11 @@
 use crate::config::ImePreeditRendering;
 use crate::keys::{Key, KeyMapPreference, LeaderKey};
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 use phaedra_input_types::{Modifiers, UIKeyCapRendering};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct KeyInputConfig {
     #[dynamic(default)]
     pub keys: Vec<Key>,
@@ -41,6 +42,8 @@ pub struct KeyInputConfig {
     pub ui_key_cap_rendering: UIKeyCapRendering,
     #[dynamic(default = "default_num_alphabet")]
     pub launcher_alphabet: String,
+    #[dynamic(default)]
+    pub show_active_key_table: bool,
 }
 
 impl Default for KeyInputConfig {
@@ -64,6 +67,7 @@ impl Default for KeyInputConfig {
             key_map_preference: KeyMapPreference::default(),
             ui_key_cap_rendering: UIKeyCapRendering::default(),
             launcher_alphabet: default_num_alphabet(),
+            show_active_key_table: false,
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::default_true;
-use crate::keys::KeyNoAction;
+use crate::keys::{KeyCondition, KeyConditionSubject, KeyNoAction};
+use crate::units::Dimension;
 use crate::window::WindowLevel;
 use luahelper::impl_lua_conversion_dynamic;
 use ordered_float::NotNan;
@@ -276,6 +277,31 @@ pub enum PaneDirection {
     Prev,
 }
 
+/// The panes that input is broadcast to by TogglePaneBroadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic, Default)]
+pub enum BroadcastScope {
+    /// The panes of the current tab
+    #[default]
+    Tab,
+    /// The panes of all of the tabs of the window
+    Window,
+    /// The panes of the window that have been selected with
+    /// SetPaneBroadcastParticipation
+    Selection,
+}
+
+/// How a pane takes part in broadcasting input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic, Default)]
+pub enum PaneBroadcastParticipation {
+    /// Receives input when it is in the scope of a Tab or Window broadcast
+    #[default]
+    Default,
+    /// Also receives input from a Selection broadcast
+    Selected,
+    /// Never receives broadcast input
+    OptOut,
+}
+
 impl PaneDirection {
     pub fn direction_from_str(arg: &str) -> Result<PaneDirection, String> {
         for candidate in PaneDirection::variants() {
@@ -458,6 +484,10 @@ pub struct QuickSelectArguments {
     /// How many lines before and how many lines after the viewport to
     /// search to produce the quickselect results
     pub scope_lines: Option<usize>,
+    /// Only match text within the output of commands, as marked
+    /// by OSC 133
+    #[dynamic(default)]
+    pub only_command_output: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
@@ -545,13 +575,17 @@ pub enum KeyAssignment {
         destination: ClipboardCopyDestination,
     },
     PasteFrom(ClipboardPasteSource),
+    PasteAsOsc52(ClipboardPasteSource),
     ActivateTabRelative(isize),
     ActivateTabRelativeNoWrap(isize),
     IncreaseFontSize,
     DecreaseFontSize,
     ResetFontSize,
     ResetFontAndWindowSize,
-    ActivateTab(isize),
+    IncreasePaneFontSize,
+    DecreasePaneFontSize,
+    ResetPaneFontSize,
+    ActivateTab(TabIndex),
     ActivateLastTab,
     SendString(String),
     SendKey(KeyNoAction),
@@ -569,6 +603,8 @@ pub enum KeyAssignment {
     ScrollByLine(isize),
     ScrollByCurrentEventWheelDelta,
     ScrollToPrompt(isize),
+    ScrollToLastCommandStart,
+    SelectLastCommandOutput,
     ScrollToTop,
     ScrollToBottom,
     ShowTabNavigator,
@@ -594,10 +630,22 @@ pub enum KeyAssignment {
     StartWindowDrag,
 
     AdjustPaneSize(PaneDirection, usize),
+    SetSplitSize(PaneDirection, SplitSize),
     ActivatePaneDirection(PaneDirection),
     ActivatePaneByIndex(usize),
     TogglePaneZoomState,
     SetPaneZoomState(bool),
+    SetPaneRenderFrozen(bool),
+    SetPaneHighlightPattern(Option<Pattern>),
+    TogglePaneBroadcast {
+        #[dynamic(default)]
+        scope: BroadcastScope,
+    },
+    SetPaneBroadcastParticipation(PaneBroadcastParticipation),
+    TogglePaneEscapeLog {
+        #[dynamic(default)]
+        path: Option<PathBuf>,
+    },
     CloseCurrentPane {
         confirm: bool,
     },
@@ -646,9 +694,272 @@ pub enum KeyAssignment {
     PromptInputLine(PromptInputLine),
     InputSelector(InputSelector),
     Confirmation(Confirmation),
+    MovePaneToNewTab {
+        #[dynamic(default)]
+        keep_focus: bool,
+    },
+    MovePaneToWindow(MoveTarget),
+    SetWindowPadding(WindowPaddingOverride),
+    ResetWindowPadding,
+    AdjustWindowOpacity(NotNan<f64>),
+    SetWindowBackgroundBlur {
+        enabled: bool,
+        #[dynamic(default)]
+        radius: Option<u32>,
+    },
+    ResetWindowBackground,
 }
 impl_lua_conversion_dynamic!(KeyAssignment);
 
+impl KeyAssignment {
+    /// Calls `f` with this assignment and with each of the assignments
+    /// nested within it, such as the steps of a Multiple
+    pub fn visit(&self, f: &mut dyn FnMut(&KeyAssignment)) {
+        f(self);
+        match self {
+            Self::Multiple(actions) => {
+                for action in actions {
+                    action.visit(f);
+                }
+            }
+            Self::QuickSelectArgs(QuickSelectArguments {
+                action: Some(action),
+                ..
+            })
+            | Self::PromptInputLine(PromptInputLine { action, .. })
+            | Self::InputSelector(InputSelector { action, .. }) => action.visit(f),
+            Self::Confirmation(Confirmation { action, cancel, .. }) => {
+                action.visit(f);
+                if let Some(cancel) = cancel {
+                    cancel.visit(f);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The tab that ActivateTab activates, counted either from the first
+/// tab or back from the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabPosition {
+    /// 0 is the first tab
+    Absolute(usize),
+    /// 1 is the last tab, 2 the one before it, and so on
+    FromEnd(usize),
+}
+
+/// What ActivateTab does when its position is beyond the tabs that
+/// the window has
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum TabIndexOutOfRange {
+    /// Leave the active tab unchanged
+    #[default]
+    Ignore,
+    /// Activate the first or last tab, whichever is nearest
+    Clamp,
+    /// Keep counting around the tabs, as if they repeated
+    Wrap,
+}
+
+/// The argument to ActivateTab.  In lua this is either a number, where
+/// negative numbers count back from the last tab (-1 is the last tab),
+/// the string "LastButOne", or a table of the form
+/// `{ index = 7, out_of_range = 'Clamp' }` whose `index` is either of
+/// those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabIndex {
+    pub position: TabPosition,
+    pub out_of_range: TabIndexOutOfRange,
+}
+
+impl TabIndex {
+    /// Indices at or beyond this are almost certainly a mistake
+    pub const IMPLAUSIBLE: usize = 100;
+
+    pub fn absolute(index: usize) -> Self {
+        Self {
+            position: TabPosition::Absolute(index),
+            out_of_range: TabIndexOutOfRange::Ignore,
+        }
+    }
+
+    /// The `n`th tab from the end, where 1 is the last tab
+    pub fn from_end(n: usize) -> Self {
+        Self {
+            position: TabPosition::FromEnd(n),
+            out_of_range: TabIndexOutOfRange::Ignore,
+        }
+    }
+
+    pub fn last() -> Self {
+        Self::from_end(1)
+    }
+
+    pub fn last_but_one() -> Self {
+        Self::from_end(2)
+    }
+
+    pub fn with_out_of_range(self, out_of_range: TabIndexOutOfRange) -> Self {
+        Self {
+            out_of_range,
+            ..self
+        }
+    }
+
+    /// Returns the zero based index of the tab to activate in a window
+    /// that has `tab_count` tabs, or None if there is no such tab
+    pub fn resolve(&self, tab_count: usize) -> Option<usize> {
+        if tab_count == 0 {
+            return None;
+        }
+        let last = tab_count - 1;
+        match (self.position, self.out_of_range) {
+            (TabPosition::Absolute(index), TabIndexOutOfRange::Ignore) => {
+                (index < tab_count).then_some(index)
+            }
+            (TabPosition::Absolute(index), TabIndexOutOfRange::Clamp) => Some(index.min(last)),
+            (TabPosition::Absolute(index), TabIndexOutOfRange::Wrap) => Some(index % tab_count),
+            (TabPosition::FromEnd(0), _) => None,
+            (TabPosition::FromEnd(n), TabIndexOutOfRange::Ignore) => tab_count.checked_sub(n),
+            (TabPosition::FromEnd(n), TabIndexOutOfRange::Clamp) => {
+                Some(tab_count.saturating_sub(n))
+            }
+            (TabPosition::FromEnd(n), TabIndexOutOfRange::Wrap) => Some(last - (n - 1) % tab_count),
+        }
+    }
+
+    /// Returns true if this refers to a tab that no real window is
+    /// likely to have, such as the 200th tab, and so will never
+    /// activate anything
+    pub fn is_implausible(&self) -> bool {
+        if self.out_of_range != TabIndexOutOfRange::Ignore {
+            return false;
+        }
+        match self.position {
+            TabPosition::Absolute(index) => index >= Self::IMPLAUSIBLE,
+            TabPosition::FromEnd(n) => n == 0 || n > Self::IMPLAUSIBLE,
+        }
+    }
+}
+
+impl From<isize> for TabIndex {
+    fn from(index: isize) -> Self {
+        if index < 0 {
+            Self::from_end(index.unsigned_abs())
+        } else {
+            Self::absolute(index as usize)
+        }
+    }
+}
+
+impl TabPosition {
+    fn from_dynamic_impl(value: &Value) -> Result<Self, phaedra_dynamic::Error> {
+        match value {
+            Value::I64(index) => Ok(TabIndex::from(*index as isize).position),
+            Value::U64(index) => Ok(Self::Absolute(*index as usize)),
+            Value::String(s) if s == "LastButOne" => Ok(TabIndex::last_but_one().position),
+            other => Err(phaedra_dynamic::Error::Message(format!(
+                "expected a tab index number or \"LastButOne\", but got {}",
+                other.variant_name()
+            ))),
+        }
+    }
+
+    /// The index as it is written in lua
+    pub fn lua_index(&self) -> i64 {
+        match self {
+            Self::Absolute(index) => *index as i64,
+            Self::FromEnd(n) => -(*n as i64),
+        }
+    }
+
+    fn to_dynamic_impl(&self) -> Value {
+        Value::I64(self.lua_index())
+    }
+}
+
+impl FromDynamic for TabIndex {
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, phaedra_dynamic::Error> {
+        match value {
+            Value::Object(obj) => {
+                let index = obj
+                    .get_by_str("index")
+                    .ok_or_else(|| {
+                        phaedra_dynamic::Error::Message(
+                            "ActivateTab requires an index field".to_string(),
+                        )
+                    })
+                    .and_then(TabPosition::from_dynamic_impl)?;
+                let out_of_range = match obj.get_by_str("out_of_range") {
+                    Some(value) => TabIndexOutOfRange::from_dynamic(value, options)?,
+                    None => TabIndexOutOfRange::default(),
+                };
+                Ok(Self {
+                    position: index,
+                    out_of_range,
+                })
+            }
+            value => Ok(Self {
+                position: TabPosition::from_dynamic_impl(value)?,
+                out_of_range: TabIndexOutOfRange::default(),
+            }),
+        }
+    }
+}
+
+impl ToDynamic for TabIndex {
+    fn to_dynamic(&self) -> Value {
+        if self.out_of_range == TabIndexOutOfRange::default() {
+            return self.position.to_dynamic_impl();
+        }
+        Value::Object(
+            [
+                (
+                    Value::String("index".to_string()),
+                    self.position.to_dynamic_impl(),
+                ),
+                (
+                    Value::String("out_of_range".to_string()),
+                    self.out_of_range.to_dynamic(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+}
+
+/// Where MovePaneToWindow should place the pane
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum MoveTarget {
+    /// A new window in the current workspace
+    NewWindow,
+    /// The window with this mux window id
+    Window(usize),
+    /// The first window in the named workspace, or a new window
+    /// in that workspace if it has none
+    Workspace(String),
+}
+
+/// Padding for some of the sides of a window, replacing the padding
+/// that those sides currently have.  The sides that are None keep
+/// their current padding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, FromDynamic, ToDynamic)]
+pub struct WindowPaddingOverride {
+    #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
+    pub left: Option<Dimension>,
+    #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
+    pub right: Option<Dimension>,
+    #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
+    pub top: Option<Dimension>,
+    #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
+    pub bottom: Option<Dimension>,
+}
+
 #[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
 pub struct SplitPane {
     pub direction: PaneDirection,
@@ -722,7 +1033,9 @@ pub enum CopyModeAssignment {
     JumpReverse,
 }
 
-pub type KeyTable = HashMap<(KeyCode, Modifiers), KeyTableEntry>;
+/// The bindings for each chord, in the order that they are considered;
+/// see `add_key_table_entry`
+pub type KeyTable = HashMap<(KeyCode, Modifiers), Vec<KeyTableEntry>>;
 
 #[derive(Debug, Clone, Default)]
 pub struct KeyTables {
@@ -733,4 +1046,163 @@ pub struct KeyTables {
 #[derive(Debug, Clone, PartialEq)]
 pub struct KeyTableEntry {
     pub action: KeyAssignment,
+    /// When set, the entry only applies to panes that match it
+    pub when: Option<KeyCondition>,
+}
+
+impl KeyTableEntry {
+    pub fn new(action: KeyAssignment) -> Self {
+        Self { action, when: None }
+    }
+
+    pub fn matches(&self, subject: &dyn KeyConditionSubject) -> bool {
+        self.when
+            .as_ref()
+            .map(|when| when.matches(subject))
+            .unwrap_or(true)
+    }
+}
+
+/// Adds `entry` to the bindings for a chord.  Conditional entries are
+/// considered in the order that they were added, followed by the single
+/// unconditional entry, so that a later unconditional binding replaces
+/// an earlier one just as it did before conditions were introduced.
+pub fn add_key_table_entry(entries: &mut Vec<KeyTableEntry>, entry: KeyTableEntry) {
+    if entry.when.is_none() {
+        entries.retain(|existing| existing.when.is_some());
+        entries.push(entry);
+    } else {
+        let pos = entries
+            .iter()
+            .position(|existing| existing.when.is_none())
+            .unwrap_or(entries.len());
+        entries.insert(pos, entry);
+    }
+}
+
+/// Returns the unconditional entry among `entries`, if any
+pub fn unconditional_key_table_entry(entries: &[KeyTableEntry]) -> Option<&KeyTableEntry> {
+    entries.iter().find(|entry| entry.when.is_none())
+}
+
+/// Returns the first of `entries` that applies to `subject`
+pub fn select_key_table_entry<'a>(
+    entries: &'a [KeyTableEntry],
+    subject: &dyn KeyConditionSubject,
+) -> Option<&'a KeyTableEntry> {
+    entries.iter().find(|entry| entry.matches(subject))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_tab_index(lua_expr: &str) -> Result<TabIndex, phaedra_dynamic::Error> {
+        let lua = mlua::Lua::new();
+        let value: mlua::Value = lua.load(lua_expr).eval().unwrap();
+        let value = luahelper::lua_value_to_dynamic(value).unwrap();
+        TabIndex::from_dynamic(&value, FromDynamicOptions::default())
+    }
+
+    fn resolve_all(index: TabIndex) -> Vec<Option<usize>> {
+        (1..=5).map(|count| index.resolve(count)).collect()
+    }
+
+    #[test]
+    fn resolve_absolute() {
+        use TabIndexOutOfRange::*;
+        let index = TabIndex::absolute(2);
+        assert_eq!(
+            resolve_all(index),
+            vec![None, None, Some(2), Some(2), Some(2)]
+        );
+        assert_eq!(
+            resolve_all(index.with_out_of_range(Clamp)),
+            vec![Some(0), Some(1), Some(2), Some(2), Some(2)]
+        );
+        assert_eq!(
+            resolve_all(index.with_out_of_range(Wrap)),
+            vec![Some(0), Some(0), Some(2), Some(2), Some(2)]
+        );
+        assert_eq!(TabIndex::absolute(0).resolve(0), None);
+    }
+
+    #[test]
+    fn resolve_from_end() {
+        use TabIndexOutOfRange::*;
+        assert_eq!(
+            resolve_all(TabIndex::last()),
+            vec![Some(0), Some(1), Some(2), Some(3), Some(4)]
+        );
+        let index = TabIndex::from_end(3);
+        assert_eq!(
+            resolve_all(index),
+            vec![None, None, Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            resolve_all(index.with_out_of_range(Clamp)),
+            vec![Some(0), Some(0), Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            resolve_all(index.with_out_of_range(Wrap)),
+            vec![Some(0), Some(1), Some(0), Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn resolve_last_but_one() {
+        assert_eq!(
+            resolve_all(TabIndex::last_but_one()),
+            vec![None, Some(0), Some(1), Some(2), Some(3)]
+        );
+        assert_eq!(
+            resolve_all(TabIndex::last_but_one().with_out_of_range(TabIndexOutOfRange::Clamp)),
+            vec![Some(0), Some(0), Some(1), Some(2), Some(3)]
+        );
+    }
+
+    #[test]
+    fn parse_tab_indices() {
+        assert_eq!(parse_tab_index("3").unwrap(), TabIndex::absolute(3));
+        assert_eq!(parse_tab_index("-1").unwrap(), TabIndex::last());
+        assert_eq!(
+            parse_tab_index("'LastButOne'").unwrap(),
+            TabIndex::last_but_one()
+        );
+        assert_eq!(
+            parse_tab_index("{ index = -2, out_of_range = 'Wrap' }").unwrap(),
+            TabIndex::from_end(2).with_out_of_range(TabIndexOutOfRange::Wrap)
+        );
+        assert_eq!(
+            parse_tab_index("{ index = 7 }").unwrap(),
+            TabIndex::absolute(7)
+        );
+        assert!(parse_tab_index("'First'").is_err());
+        assert!(parse_tab_index("{ out_of_range = 'Clamp' }").is_err());
+    }
+
+    #[test]
+    fn tab_index_round_trips() {
+        for index in [
+            TabIndex::absolute(4),
+            TabIndex::last(),
+            TabIndex::from_end(2).with_out_of_range(TabIndexOutOfRange::Clamp),
+        ] {
+            let value = index.to_dynamic();
+            assert_eq!(
+                TabIndex::from_dynamic(&value, FromDynamicOptions::default()).unwrap(),
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn implausible_indices() {
+        assert!(!TabIndex::absolute(8).is_implausible());
+        assert!(TabIndex::absolute(150).is_implausible());
+        assert!(!TabIndex::absolute(150)
+            .with_out_of_range(TabIndexOutOfRange::Clamp)
+            .is_implausible());
+        assert!(TabIndex::from_end(0).is_implausible());
+    }
 }
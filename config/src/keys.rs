@@ -1,4 +1,5 @@
 use crate::keyassignment::{KeyAssignment, MouseEventTrigger};
+use regex::Regex;
 use std::convert::TryFrom;
 use phaedra_dynamic::{Error as DynError, FromDynamic, FromDynamicOptions, ToDynamic, Value};
 use phaedra_input_types::{KeyCode, Modifiers, PhysKeyCode};
@@ -123,6 +124,96 @@ pub struct Key {
     #[dynamic(flatten)]
     pub key: KeyNoAction,
     pub action: KeyAssignment,
+    /// When set, the binding only applies to panes that match it;
+    /// otherwise the key falls through to the next binding for the
+    /// same key, or is sent to the pane
+    #[dynamic(default)]
+    pub when: Option<KeyCondition>,
+}
+
+/// Restricts a key binding to the panes that match all of the
+/// criteria that are specified
+#[derive(Debug, Clone, Default, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct KeyCondition {
+    /// A glob, in which `*` matches any run of characters and `?` any
+    /// one character, that is matched against the path of the
+    /// foreground process.  A glob without a `/` is matched against
+    /// just the last component of the path.
+    #[dynamic(default)]
+    pub foreground_process_name: Option<String>,
+    /// A regex that is matched against the title of the pane
+    #[dynamic(default, validate = "validate_pane_title")]
+    pub pane_title: Option<String>,
+    /// Matches panes that belong to the domain with this name
+    #[dynamic(default)]
+    pub domain_name: Option<String>,
+}
+
+/// The properties of a pane that a `KeyCondition` can match.
+/// They are queried as the condition is evaluated, so that a key
+/// bound without one doesn't pay for looking up the foreground process.
+pub trait KeyConditionSubject {
+    fn foreground_process_name(&self) -> Option<String>;
+    fn title(&self) -> String;
+    fn domain_name(&self) -> Option<String>;
+}
+
+fn validate_pane_title(title: &Option<String>) -> Result<(), String> {
+    match title {
+        Some(title) => Regex::new(title)
+            .map(|_| ())
+            .map_err(|err| format!("key binding pane_title {title:?}: {err}")),
+        None => Ok(()),
+    }
+}
+
+/// Returns true if `text` matches `glob` in its entirety
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern)
+        .map(|re| re.is_match(text))
+        .unwrap_or(false)
+}
+
+impl KeyCondition {
+    pub fn matches(&self, subject: &dyn KeyConditionSubject) -> bool {
+        if let Some(domain) = &self.domain_name {
+            if subject.domain_name().as_ref() != Some(domain) {
+                return false;
+            }
+        }
+        if let Some(title) = &self.pane_title {
+            match Regex::new(title) {
+                Ok(re) if re.is_match(&subject.title()) => {}
+                _ => return false,
+            }
+        }
+        if let Some(glob) = &self.foreground_process_name {
+            let name = match subject.foreground_process_name() {
+                Some(name) => name,
+                None => return false,
+            };
+            let name = if glob.contains('/') {
+                name.as_str()
+            } else {
+                name.rsplit(|c| c == '/' || c == '\\')
+                    .next()
+                    .unwrap_or(&name)
+            };
+            if !glob_matches(glob, name) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 #[derive(Debug, Clone, FromDynamic, ToDynamic)]
@@ -142,9 +233,29 @@ pub struct Mouse {
     pub event: MouseEventTrigger,
     #[dynamic(flatten)]
     pub mods: MouseEventTriggerMods,
+    /// When set, the binding only applies to events over this
+    /// part of the window
+    #[dynamic(default)]
+    pub region: Option<MouseRegion>,
     pub action: KeyAssignment,
 }
 
+/// The part of the window that a mouse event is over, as determined
+/// by hit testing the UI elements before looking up a mouse binding
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash, FromDynamic, ToDynamic)]
+pub enum MouseRegion {
+    /// The terminal cells of a pane
+    Text,
+    /// The scroll bar, including its thumb
+    ScrollBar,
+    /// The tab bar, including its buttons and status areas
+    TabBar,
+    /// The divider between split panes
+    SplitDivider,
+    /// The window padding and border surrounding the panes
+    Border,
+}
+
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum MouseEventAltScreen {
     True,
@@ -192,3 +303,177 @@ pub struct MouseEventTriggerMods {
     #[dynamic(default)]
     pub alt_screen: MouseEventAltScreen,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_mouse(lua_expr: &str) -> Mouse {
+        let lua = mlua::Lua::new();
+        let value: mlua::Value = lua.load(lua_expr).eval().unwrap();
+        luahelper::from_lua(value).unwrap()
+    }
+
+    #[test]
+    fn mouse_binding_region_defaults_to_none() {
+        let mouse = parse_mouse(
+            "{ event = { Down = { streak = 1, button = 'Left' } }, mods = 'CTRL', action = 'Nop' }",
+        );
+        assert_eq!(mouse.region, None);
+        assert_eq!(mouse.mods.mods, Modifiers::CTRL);
+    }
+
+    #[test]
+    fn mouse_binding_with_region_and_triple_modifier_chord() {
+        let mouse = parse_mouse(
+            "{ event = { Up = { streak = 2, button = 'Right' } }, \
+               mods = 'CTRL|SHIFT|ALT', region = 'ScrollBar', action = 'Nop' }",
+        );
+        assert_eq!(mouse.region, Some(MouseRegion::ScrollBar));
+        assert_eq!(
+            mouse.mods.mods,
+            Modifiers::CTRL | Modifiers::SHIFT | Modifiers::ALT
+        );
+
+        let round_trip: Mouse =
+            Mouse::from_dynamic(&mouse.to_dynamic(), Default::default()).unwrap();
+        assert_eq!(round_trip.region, Some(MouseRegion::ScrollBar));
+    }
+
+    struct FakePane {
+        process: Option<&'static str>,
+        title: &'static str,
+        domain: &'static str,
+    }
+
+    impl KeyConditionSubject for FakePane {
+        fn foreground_process_name(&self) -> Option<String> {
+            self.process.map(|p| p.to_string())
+        }
+
+        fn title(&self) -> String {
+            self.title.to_string()
+        }
+
+        fn domain_name(&self) -> Option<String> {
+            Some(self.domain.to_string())
+        }
+    }
+
+    fn nvim() -> FakePane {
+        FakePane {
+            process: Some("/usr/bin/nvim"),
+            title: "nvim keys.rs",
+            domain: "local",
+        }
+    }
+
+    fn shell() -> FakePane {
+        FakePane {
+            process: Some("/bin/zsh"),
+            title: "~/src",
+            domain: "local",
+        }
+    }
+
+    fn process(glob: &str) -> KeyCondition {
+        KeyCondition {
+            foreground_process_name: Some(glob.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn key_condition_matches_process_name() {
+        assert!(process("nvim").matches(&nvim()));
+        assert!(process("*vim").matches(&nvim()));
+        assert!(process("n?im").matches(&nvim()));
+        assert!(process("/usr/bin/*").matches(&nvim()));
+        assert!(!process("vim").matches(&nvim()));
+        assert!(!process("*vim").matches(&shell()));
+        assert!(!process("/bin/*vim").matches(&nvim()));
+        // A pane without a known foreground process can't match
+        assert!(!process("*").matches(&FakePane {
+            process: None,
+            ..shell()
+        }));
+
+        let title = KeyCondition {
+            pane_title: Some("^nvim ".to_string()),
+            ..Default::default()
+        };
+        assert!(title.matches(&nvim()));
+        assert!(!title.matches(&shell()));
+
+        // All of the criteria must match
+        let both = KeyCondition {
+            domain_name: Some("SSH:host".to_string()),
+            ..process("nvim")
+        };
+        assert!(!both.matches(&nvim()));
+        assert!(KeyCondition::default().matches(&shell()));
+    }
+
+    #[test]
+    fn key_table_entries_fall_through_in_order() {
+        use crate::keyassignment::{add_key_table_entry, select_key_table_entry, KeyTableEntry};
+
+        let entry = |action, when| KeyTableEntry { action, when };
+        let mut entries = vec![];
+        add_key_table_entry(&mut entries, KeyTableEntry::new(KeyAssignment::Nop));
+        add_key_table_entry(
+            &mut entries,
+            entry(KeyAssignment::ScrollToTop, Some(process("*vim"))),
+        );
+        add_key_table_entry(
+            &mut entries,
+            entry(KeyAssignment::ScrollToBottom, Some(process("nvim"))),
+        );
+        // A later unconditional binding replaces the earlier one
+        add_key_table_entry(&mut entries, KeyTableEntry::new(KeyAssignment::PopKeyTable));
+
+        let actions: Vec<_> = entries.iter().map(|e| e.action.clone()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                KeyAssignment::ScrollToTop,
+                KeyAssignment::ScrollToBottom,
+                KeyAssignment::PopKeyTable,
+            ]
+        );
+
+        // The first matching conditional entry wins
+        assert_eq!(
+            select_key_table_entry(&entries, &nvim()).map(|e| &e.action),
+            Some(&KeyAssignment::ScrollToTop)
+        );
+        // Otherwise, the unconditional entry applies
+        assert_eq!(
+            select_key_table_entry(&entries, &shell()).map(|e| &e.action),
+            Some(&KeyAssignment::PopKeyTable)
+        );
+        // Without one, nothing does, leaving the key for the pane
+        entries.pop();
+        assert_eq!(select_key_table_entry(&entries, &shell()), None);
+    }
+
+    #[test]
+    fn key_binding_with_when_clause() {
+        let lua = mlua::Lua::new();
+        let value: mlua::Value = lua
+            .load(
+                "{ key = 'h', mods = 'CTRL', action = 'Nop', \
+                   when = { foreground_process_name = '*vim', domain_name = 'local' } }",
+            )
+            .eval()
+            .unwrap();
+        let key: Key = luahelper::from_lua(value).unwrap();
+        assert_eq!(
+            key.when,
+            Some(KeyCondition {
+                domain_name: Some("local".to_string()),
+                ..process("*vim")
+            })
+        );
+    }
+}
@@ -1,10 +1,11 @@
 use crate::config::{ExitBehavior, ExitBehaviorMessaging};
 use crate::keyassignment::SpawnCommand;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct LaunchConfig {
     pub default_prog: Option<Vec<String>>,
     #[dynamic(default = "default_gui_startup_args")]
@@ -19,6 +20,8 @@ pub struct LaunchConfig {
     #[dynamic(default = "default_clean_exits")]
     pub clean_exit_codes: Vec<u32>,
     #[dynamic(default)]
+    pub exit_behavior_template: Option<String>,
+    #[dynamic(default)]
     pub set_environment_variables: HashMap<String, String>,
     #[dynamic(default)]
     pub prefer_to_spawn_tabs: bool,
@@ -41,6 +44,7 @@ impl Default for LaunchConfig {
             exit_behavior: ExitBehavior::default(),
             exit_behavior_messaging: ExitBehaviorMessaging::default(),
             clean_exit_codes: default_clean_exits(),
+            exit_behavior_template: None,
             set_environment_variables: HashMap::new(),
             prefer_to_spawn_tabs: false,
             term: default_term(),
@@ -18,7 +18,10 @@ use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use phaedra_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, UnknownFieldAction, Value};
+use phaedra_dynamic::{
+    FromDynamic, FromDynamicOptions, ToDynamic, UnknownFieldAction, Value, Warning,
+};
+use phaedra_term::color::ColorPalette;
 use phaedra_term::{TerminalSize, UnicodeVersion};
 
 mod background;
@@ -27,12 +30,15 @@ pub mod cache_config;
 mod cell;
 mod color;
 mod config;
+mod config_overrides;
 pub mod domain_config;
 pub mod cursor;
 mod daemon;
+mod diff;
 mod exec_domain;
 mod font;
 pub mod font_config;
+pub mod font_validation;
 mod frontend;
 pub mod gpu_config;
 pub mod keyassignment;
@@ -44,9 +50,14 @@ pub mod meta;
 pub mod mouse_config;
 pub mod mux_config;
 pub mod observers;
+pub mod reload_watch;
 pub mod color_config;
 mod scheme_data;
+mod scheme_formats;
+pub mod schema;
 pub mod scroll;
+pub mod shader_validation;
+pub mod spawn_template;
 pub mod runtime_config;
 mod ssh;
 pub mod tab_bar;
@@ -60,17 +71,22 @@ mod unix;
 mod version;
 pub mod window;
 pub mod window_config;
+pub mod window_overrides;
 
 pub use crate::config::*;
 pub use background::*;
 pub use bell::*;
 pub use cache_config::CacheConfig;
 pub use cell::*;
-pub use color_config::ColorConfig;
+pub use color_config::{
+    resolve_color_scheme_name, ColorConfig, ColorSchemeSelection, SystemAppearance,
+};
 pub use color::*;
+pub use config_overrides::{parse_config_overrides, ConfigOverride};
 pub use daemon::*;
+pub use diff::ConfigDelta;
 pub use domain_config::DomainConfig;
-pub use cursor::CursorConfig;
+pub use cursor::{CursorConfig, UnfocusedCursorStyle};
 pub use exec_domain::*;
 pub use font::*;
 pub use font_config::FontConfig;
@@ -82,8 +98,13 @@ pub use launch_config::LaunchConfig;
 pub use mouse_config::MouseConfig;
 pub use mux_config::MuxConfig;
 pub use observers::*;
-pub use runtime_config::RuntimeConfig;
-pub use scroll::ScrollConfig;
+pub use reload_watch::subscribe_to_reloads;
+pub use runtime_config::{
+    CommandPaletteConfig, PaletteFuzzyAlgorithm, RecordFramesConfig, RuntimeConfig,
+};
+pub use scheme_formats::ColorSchemeFormat;
+pub use scroll::{AlternateBufferWheelBehavior, ScrollConfig};
+pub use spawn_template::SpawnContext;
 pub use ssh::*;
 pub use tab_bar::TabBarConfig;
 pub use terminal::*;
@@ -94,9 +115,16 @@ pub use units::*;
 pub use update_check::UpdateConfig;
 pub use unix::*;
 pub use version::*;
-pub use window_config::WindowConfig;
+pub use window_config::{resolve_monitor_dpi, AnimationEnabled, DpiRounding, WindowConfig};
+pub use window_overrides::{WindowOverride, WindowProperties};
+
+/// How long the config file may take to evaluate before loading it
+/// is abandoned; see `set_config_eval_timeout`
+pub const DEFAULT_CONFIG_EVAL_TIMEOUT: Duration = Duration::from_secs(5);
 
 type ErrorCallback = fn(&str);
+type WarningCallback = fn(&[Warning]);
+type FontResolverCallback = fn(&Config, &[FontAttributes]) -> Vec<FontAttributes>;
 
 lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
@@ -107,9 +135,13 @@ lazy_static! {
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_SKIP: AtomicBool = AtomicBool::new(false);
-    static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
+    static ref CONFIG_OVERRIDES: Mutex<Vec<ConfigOverride>> = Mutex::new(vec![]);
+    static ref SYSTEM_APPEARANCE: Mutex<SystemAppearance> = Mutex::new(SystemAppearance::default());
+    static ref CONFIG_EVAL_TIMEOUT: Mutex<Duration> = Mutex::new(DEFAULT_CONFIG_EVAL_TIMEOUT);
     static ref SHOW_ERROR: Mutex<Option<ErrorCallback>> =
         Mutex::new(Some(|e| log::error!("{}", e)));
+    static ref SHOW_WARNINGS: Mutex<Option<WarningCallback>> = Mutex::new(None);
+    static ref FONT_RESOLVER: Mutex<Option<FontResolverCallback>> = Mutex::new(None);
     static ref LUA_PIPE: LuaPipe = LuaPipe::new();
     pub static ref COLOR_SCHEMES: HashMap<String, Palette> = build_default_schemes();
 }
@@ -280,6 +312,20 @@ where
     ConfigSubscription(CONFIG.subscribe(subscriber))
 }
 
+/// Subscribe to reloads that change any of the specified `sections`.
+/// The subscriber is passed the full set of sections that changed.
+/// Like `subscribe_to_config_reload`, returning false from the
+/// subscriber cancels the subscription.
+pub fn subscribe_to_config_sections<F>(
+    sections: ConfigSectionSet,
+    subscriber: F,
+) -> ConfigSubscription
+where
+    F: Fn(ConfigSectionSet) -> bool + 'static + Send,
+{
+    ConfigSubscription(CONFIG.subscribe_sections(sections, subscriber))
+}
+
 /// Spawn a future that will run with an optional Lua state from the most
 /// recently loaded lua configuration.
 /// The `func` argument is passed the lua state and must return a Future.
@@ -377,6 +423,7 @@ fn default_config_with_overrides_applied() -> anyhow::Result<Config> {
 pub fn common_init(
     config_file: Option<&OsString>,
     overrides: &[(String, String)],
+    json_overrides: &[(String, String)],
     skip_config: bool,
 ) -> anyhow::Result<()> {
     if let Some(config_file) = config_file {
@@ -385,7 +432,8 @@ pub fn common_init(
         CONFIG_SKIP.store(true, Ordering::Relaxed);
     }
 
-    set_config_overrides(overrides).context("common_init: set_config_overrides")?;
+    let overrides = parse_config_overrides(overrides, json_overrides)?;
+    set_config_overrides(&overrides).context("common_init: set_config_overrides")?;
     reload();
     Ok(())
 }
@@ -402,6 +450,35 @@ pub fn show_error(err: &str) {
     }
 }
 
+/// Assign a callback that is used to present configuration warnings
+/// that were newly encountered when the configuration was reloaded
+pub fn assign_warning_callback(cb: WarningCallback) {
+    let mut factory = SHOW_WARNINGS.lock().unwrap();
+    factory.replace(cb);
+}
+
+fn show_warnings(warnings: &[Warning]) {
+    let factory = SHOW_WARNINGS.lock().unwrap();
+    if let Some(cb) = factory.as_ref() {
+        cb(warnings)
+    }
+}
+
+/// Assign a callback that returns those of the fonts that it is given
+/// that can't be found, so that the fonts named by the configuration
+/// can be checked as it is loaded
+pub fn assign_font_resolver(cb: FontResolverCallback) {
+    let mut factory = FONT_RESOLVER.lock().unwrap();
+    factory.replace(cb);
+}
+
+/// Returns those of `fonts` that can't be found, or None if no font
+/// resolver has been assigned
+fn unresolved_fonts(config: &Config, fonts: &[FontAttributes]) -> Option<Vec<FontAttributes>> {
+    let factory = FONT_RESOLVER.lock().unwrap();
+    factory.as_ref().map(|cb| cb(config, fonts))
+}
+
 pub fn create_user_owned_dirs(p: &Path) -> anyhow::Result<()> {
     let mut builder = DirBuilder::new();
     builder.recursive(true);
@@ -441,13 +518,24 @@ pub fn set_config_file_override(path: &Path) {
         .replace(path.to_path_buf());
 }
 
-pub fn set_config_overrides(items: &[(String, String)]) -> anyhow::Result<()> {
+pub fn set_config_overrides(items: &[ConfigOverride]) -> anyhow::Result<()> {
     *CONFIG_OVERRIDES.lock().unwrap() = items.to_vec();
 
     let _ = default_config_with_overrides_applied()?;
     Ok(())
 }
 
+/// Sets how long the config file may take to evaluate.  A config
+/// that runs for longer than this fails to load with an error that
+/// says so, rather than blocking the loading thread indefinitely.
+pub fn set_config_eval_timeout(timeout: Duration) {
+    *CONFIG_EVAL_TIMEOUT.lock().unwrap() = timeout;
+}
+
+pub(crate) fn config_eval_timeout() -> Duration {
+    *CONFIG_EVAL_TIMEOUT.lock().unwrap()
+}
+
 pub fn is_config_overridden() -> bool {
     CONFIG_SKIP.load(Ordering::Relaxed)
         || !CONFIG_OVERRIDES.lock().unwrap().is_empty()
@@ -481,6 +569,21 @@ pub fn overridden_config(overrides: &phaedra_dynamic::Value) -> Result<ConfigHan
     CONFIG.overridden(overrides)
 }
 
+/// Returns the appearance of the windowing environment, as last
+/// reported by `set_system_appearance`
+pub fn system_appearance() -> SystemAppearance {
+    *SYSTEM_APPEARANCE.lock().unwrap()
+}
+
+/// Record the appearance of the windowing environment.  If the color
+/// scheme is chosen according to the appearance, the palette is
+/// resolved again and swapped without reloading the configuration.
+/// Returns true if the lua configuration consulted the appearance
+/// when it was evaluated, in which case the caller should reload it.
+pub fn set_system_appearance(appearance: SystemAppearance) -> bool {
+    CONFIG.set_system_appearance(appearance)
+}
+
 pub fn reload() {
     CONFIG.reload();
 }
@@ -500,24 +603,57 @@ pub fn configuration_warnings_and_errors() -> Vec<String> {
     CONFIG.get_warnings_and_errors()
 }
 
+/// Returns the warnings encountered while loading the preferred
+/// configuration
+pub fn configuration_warnings() -> Vec<Warning> {
+    CONFIG.get_warnings()
+}
+
+/// Returns the fields that the most recent reload changed.  This is
+/// empty if that reload failed, or if the configuration has not been
+/// reloaded since it was first loaded.
+pub fn configuration_deltas() -> Arc<Vec<ConfigDelta>> {
+    CONFIG.get_deltas()
+}
+
+type SectionSubscriber = Box<dyn Fn(ConfigSectionSet) -> bool + Send>;
+
+static SUB_ID: AtomicUsize = AtomicUsize::new(0);
+
 struct ConfigInner {
     config: Arc<Config>,
+    sections: Arc<ConfigSectionHashes>,
+    palette: Arc<ColorPalette>,
+    /// The generation at which `palette` last changed
+    palette_generation: usize,
     error: Option<String>,
-    warnings: Vec<String>,
+    warnings: Vec<Warning>,
+    deltas: Arc<Vec<ConfigDelta>>,
     generation: usize,
+    /// Whether evaluating the lua configuration consulted the
+    /// appearance of the windowing environment
+    consulted_appearance: bool,
     watcher: Option<notify::RecommendedWatcher>,
     subscribers: HashMap<usize, Box<dyn Fn() -> bool + Send>>,
+    section_subscribers: HashMap<usize, (ConfigSectionSet, SectionSubscriber)>,
 }
 
 impl ConfigInner {
     fn new() -> Self {
+        let config = Config::default_config();
         Self {
-            config: Arc::new(Config::default_config()),
+            sections: Arc::new(ConfigSectionHashes::compute(&config)),
+            palette: terminal_palette(&config),
+            palette_generation: 0,
+            config: Arc::new(config),
             error: None,
             warnings: vec![],
+            deltas: Arc::new(vec![]),
             generation: 0,
+            consulted_appearance: false,
             watcher: None,
             subscribers: HashMap::new(),
+            section_subscribers: HashMap::new(),
         }
     }
 
@@ -525,55 +661,75 @@ impl ConfigInner {
     where
         F: Fn() -> bool + 'static + Send,
     {
-        static SUB_ID: AtomicUsize = AtomicUsize::new(0);
         let sub_id = SUB_ID.fetch_add(1, Ordering::Relaxed);
         self.subscribers.insert(sub_id, Box::new(subscriber));
         sub_id
     }
 
+    fn subscribe_sections<F>(&mut self, sections: ConfigSectionSet, subscriber: F) -> usize
+    where
+        F: Fn(ConfigSectionSet) -> bool + 'static + Send,
+    {
+        let sub_id = SUB_ID.fetch_add(1, Ordering::Relaxed);
+        self.section_subscribers
+            .insert(sub_id, (sections, Box::new(subscriber)));
+        sub_id
+    }
+
     fn unsub(&mut self, sub_id: usize) {
         self.subscribers.remove(&sub_id);
+        self.section_subscribers.remove(&sub_id);
     }
 
-    fn notify(&mut self) {
+    fn notify(&mut self, changed: ConfigSectionSet) {
         self.subscribers.retain(|_, notify| notify());
+        self.section_subscribers
+            .retain(|_, (sections, notify)| !changed.intersects(sections) || notify(changed));
+    }
+
+    /// Replace the current configuration, bumping the generation,
+    /// and return the set of sections that differ from the prior one
+    fn replace_config(&mut self, config: Config) -> ConfigSectionSet {
+        let sections = ConfigSectionHashes::compute(&config);
+        let changed = sections.changed_since(&self.sections);
+        let palette = terminal_palette(&config);
+        self.config = Arc::new(config);
+        self.sections = Arc::new(sections);
+        self.error.take();
+        self.generation += 1;
+        if palette != self.palette {
+            self.palette = palette;
+            self.palette_generation = self.generation;
+        }
+        changed
+    }
+
+    fn handle(&self) -> ConfigHandle {
+        ConfigHandle {
+            config: Arc::clone(&self.config),
+            sections: Arc::clone(&self.sections),
+            palette: Arc::clone(&self.palette),
+            palette_generation: self.palette_generation,
+            generation: self.generation,
+        }
     }
 
     fn watch_path(&mut self, path: PathBuf) {
         if self.watcher.is_none() {
             let (tx, rx) = std::sync::mpsc::channel();
-            const DELAY: Duration = Duration::from_millis(200);
+            const DELAY: Duration = reload_watch::RELOAD_DEBOUNCE;
             let watcher = notify::recommended_watcher(tx).unwrap();
-            let path = path.clone();
 
             std::thread::spawn(move || {
                 // block until we get an event
-                use notify::EventKind;
-
-                fn extract_path(event: notify::Event) -> Vec<PathBuf> {
-                    match event.kind {
-                        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => {
-                            event.paths
-                        }
-                        _ => vec![],
-                    }
-                }
-
                 while let Ok(event) = rx.recv() {
                     log::debug!("event:{:?}", event);
                     match event {
                         Ok(event) => {
-                            let mut paths = extract_path(event);
+                            let paths = reload_watch::changed_paths(event);
                             if !paths.is_empty() {
-                                // Grace period to allow events to settle
-                                std::thread::sleep(DELAY);
-                                // Drain any other immediately ready events
-                                while let Ok(Ok(event)) = rx.try_recv() {
-                                    paths.append(&mut extract_path(event));
-                                }
-                                paths.sort();
-                                paths.dedup();
-                                log::debug!("paths {:?} changed, reload config", path);
+                                let paths = reload_watch::settle(&rx, paths, DELAY);
+                                log::debug!("paths {:?} changed, reload config", paths);
                                 reload();
                             }
                         }
@@ -593,57 +749,60 @@ impl ConfigInner {
         }
     }
 
-    fn accumulate_watch_paths(lua: &Lua, watch_paths: &mut Vec<PathBuf>) {
-        if let Ok(mlua::Value::Table(tbl)) = lua.named_registry_value("phaedra-watch-paths") {
-            for path in tbl.sequence_values::<String>() {
-                if let Ok(path) = path {
-                    watch_paths.push(PathBuf::from(path));
-                }
-            }
-        }
-    }
-
     /// Attempt to load the user's configuration.
     /// On success, clear any error and replace the current
     /// configuration.
     /// On failure, retain the existing configuration but
     /// replace any captured error message.
     fn reload(&mut self) {
+        let loaded = Config::load();
+        // Before we process the success/failure, extract and update
+        // any paths that we should be watching
+        let watch_paths = reload_watch::loaded_watch_paths(&loaded);
         let LoadedConfig {
             config,
-            file_name,
+            file_name: _,
             lua,
             warnings,
-        } = Config::load();
+            eval_duration: _,
+        } = loaded;
 
+        // Only log warnings that we haven't already logged, so that
+        // reloading doesn't keep repeating the same warnings.
+        let new_warnings: Vec<Warning> = warnings
+            .iter()
+            .filter(|warning| warning.log_once())
+            .cloned()
+            .collect();
+        if self.generation > 0 && !new_warnings.is_empty() {
+            show_warnings(&new_warnings);
+        }
         self.warnings = warnings;
 
-        // Before we process the success/failure, extract and update
-        // any paths that we should be watching
-        let mut watch_paths = vec![];
-        if let Some(path) = file_name {
-            // Let's also watch the parent directory for folks that do
-            // things with symlinks:
-            if let Some(parent) = path.parent() {
-                // But avoid watching the home dir itself, so that we
-                // don't keep reloading every time something in the
-                // home dir changes!
-                // <https://github.com/PaleRoses/phaedra/issues/1895>
-                if parent != &*HOME_DIR {
-                    watch_paths.push(parent.to_path_buf());
-                }
-            }
-            watch_paths.push(path);
-        }
-        if let Some(lua) = &lua {
-            ConfigInner::accumulate_watch_paths(lua, &mut watch_paths);
-        }
+        self.consulted_appearance = lua.as_ref().map_or(false, |lua| {
+            lua.named_registry_value::<bool>("phaedra-consulted-appearance")
+                .unwrap_or(false)
+        });
 
-        match config {
+        let changed = match config {
             Ok(config) => {
-                self.config = Arc::new(config);
-                self.error.take();
-                self.generation += 1;
+                // Only report the changes made by an actual reload,
+                // rather than how the initial config differs from
+                // the defaults
+                let deltas = if self.generation > 0 {
+                    self.config.diff(&config)
+                } else {
+                    vec![]
+                };
+                if !deltas.is_empty() {
+                    log::info!("Reloaded configuration changed {} fields:", deltas.len());
+                    for delta in &deltas {
+                        log::info!("  {}", delta);
+                    }
+                }
+                self.deltas = Arc::new(deltas);
+
+                let changed = self.replace_config(config);
 
                 // If we loaded a user config, publish this latest version of
                 // the lua state to the LUA_PIPE.  This allows a subsequent
@@ -653,7 +812,12 @@ impl ConfigInner {
                 if let Some(lua) = lua {
                     LUA_PIPE.sender.try_send(lua).ok();
                 }
-                log::debug!("Reloaded configuration! generation={}", self.generation);
+                log::debug!(
+                    "Reloaded configuration! generation={} changed={:?}",
+                    self.generation,
+                    changed.iter().collect::<Vec<_>>()
+                );
+                changed
             }
             Err(err) => {
                 let err = format!("{:#}", err);
@@ -662,38 +826,63 @@ impl ConfigInner {
                     show_error(&err);
                 }
                 self.error.replace(err);
+                self.deltas = Arc::new(vec![]);
+                ConfigSectionSet::empty()
             }
-        }
+        };
 
-        self.notify();
-        if ConfigHandle::from_arc(Arc::clone(&self.config), self.generation)
-            .runtime()
-            .automatically_reload_config
-        {
+        self.notify(changed);
+        if self.handle().runtime().automatically_reload_config {
             for path in watch_paths {
                 self.watch_path(path);
             }
         }
     }
 
+    fn set_system_appearance(&mut self, appearance: SystemAppearance) -> bool {
+        {
+            let mut current = SYSTEM_APPEARANCE.lock().unwrap();
+            if *current == appearance {
+                return false;
+            }
+            *current = appearance;
+        }
+
+        if self.config.color_config.scheme_depends_on_appearance() {
+            let mut config = (*self.config).clone();
+            config.resolve_palette(appearance);
+            let changed = self.replace_config(config);
+            log::debug!(
+                "Resolved color scheme for {:?} appearance; generation={}",
+                appearance,
+                self.generation
+            );
+            self.notify(changed);
+        }
+
+        self.consulted_appearance
+    }
+
     /// Discard the current configuration and any recorded
     /// error message; replace them with the default
     /// configuration
     fn use_defaults(&mut self) {
-        self.config = Arc::new(Config::default_config());
-        self.error.take();
-        self.generation += 1;
+        self.replace_config(Config::default_config());
     }
 
     fn use_this_config(&mut self, cfg: Config) {
-        self.config = Arc::new(cfg);
-        self.error.take();
-        self.generation += 1;
+        self.replace_config(cfg);
     }
 
     fn overridden(&mut self, overrides: &phaedra_dynamic::Value) -> Result<ConfigHandle, Error> {
         let config = Config::load_with_overrides(overrides);
         let config = Arc::new(config.config?);
+        if *overrides != phaedra_dynamic::Value::Null {
+            // The result differs from the shared config, so give it a
+            // generation of its own, so that anything derived from a
+            // previous config for the same window is invalidated
+            self.generation += 1;
+        }
         Ok(ConfigHandle::from_arc(config, self.generation))
     }
 
@@ -717,9 +906,7 @@ impl ConfigInner {
         // that we have consistent values regardless of the
         // operating system that we're running tests on
         config.font_config.dpi.replace(96.0);
-        self.config = Arc::new(config);
-        self.error.take();
-        self.generation += 1;
+        self.replace_config(config);
     }
 }
 
@@ -736,8 +923,7 @@ impl Configuration {
 
     /// Returns the effective configuration.
     pub fn get(&self) -> ConfigHandle {
-        let inner = self.inner.lock().unwrap();
-        ConfigHandle::from_arc(Arc::clone(&inner.config), inner.generation)
+        self.inner.lock().unwrap().handle()
     }
 
     /// Subscribe to config reload events
@@ -749,6 +935,14 @@ impl Configuration {
         inner.subscribe(subscriber)
     }
 
+    fn subscribe_sections<F>(&self, sections: ConfigSectionSet, subscriber: F) -> usize
+    where
+        F: Fn(ConfigSectionSet) -> bool + 'static + Send,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        inner.subscribe_sections(sections, subscriber)
+    }
+
     fn unsub(&self, sub_id: usize) {
         let mut inner = self.inner.lock().unwrap();
         inner.unsub(sub_id);
@@ -777,6 +971,11 @@ impl Configuration {
         inner.use_test();
     }
 
+    fn set_system_appearance(&self, appearance: SystemAppearance) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.set_system_appearance(appearance)
+    }
+
     /// Reload the configuration
     pub fn reload(&self) {
         let mut inner = self.inner.lock().unwrap();
@@ -797,11 +996,21 @@ impl Configuration {
             result.push(error.clone());
         }
         for warning in &inner.warnings {
-            result.push(warning.clone());
+            result.push(warning.to_string());
         }
         result
     }
 
+    pub fn get_warnings(&self) -> Vec<Warning> {
+        let inner = self.inner.lock().unwrap();
+        inner.warnings.clone()
+    }
+
+    pub fn get_deltas(&self) -> Arc<Vec<ConfigDelta>> {
+        let inner = self.inner.lock().unwrap();
+        Arc::clone(&inner.deltas)
+    }
+
     /// Returns any captured error message, and clears
     /// it from the config state.
     #[allow(dead_code)]
@@ -811,15 +1020,44 @@ impl Configuration {
     }
 }
 
+/// Returns the resolved palette of `config` in the form used by the
+/// terminal, shared with any equal palette that is already in use
+fn terminal_palette(config: &Config) -> Arc<ColorPalette> {
+    ColorPalette::from(config.color_config.resolved_palette.clone()).intern()
+}
+
 #[derive(Clone, Debug)]
 pub struct ConfigHandle {
     config: Arc<Config>,
+    sections: Arc<ConfigSectionHashes>,
+    palette: Arc<ColorPalette>,
+    palette_generation: usize,
     generation: usize,
 }
 
 impl ConfigHandle {
     fn from_arc(config: Arc<Config>, generation: usize) -> Self {
-        Self { config, generation }
+        Self {
+            sections: Arc::new(ConfigSectionHashes::compute(&config)),
+            palette: terminal_palette(&config),
+            palette_generation: generation,
+            config,
+            generation,
+        }
+    }
+
+    /// Returns the per-section content hashes for this configuration
+    pub fn section_hashes(&self) -> &ConfigSectionHashes {
+        &self.sections
+    }
+
+    /// Returns the set of sections that differ between this
+    /// configuration and `prior`
+    pub fn changed_sections(&self, prior: &ConfigHandle) -> ConfigSectionSet {
+        if Arc::ptr_eq(&self.config, &prior.config) {
+            return ConfigSectionSet::empty();
+        }
+        self.sections.changed_since(&prior.sections)
     }
 
     /// Returns the generation number for the configuration,
@@ -830,10 +1068,31 @@ impl ConfigHandle {
         self.generation
     }
 
+    /// Returns the resolved palette of the configuration, as used by
+    /// the terminal.  It is shared rather than copied, so this is cheap
+    /// enough to call for each pane in each frame.
+    pub fn color_palette(&self) -> Arc<ColorPalette> {
+        Arc::clone(&self.palette)
+    }
+
+    /// Returns the generation at which the palette last changed, which
+    /// is at most `generation`.  Reloading a configuration without
+    /// changing its colors keeps the same palette generation, so that
+    /// anything derived from the palette need not be recomputed.
+    pub fn palette_generation(&self) -> usize {
+        self.palette_generation
+    }
+
     pub fn default_config() -> Self {
         Self::from_arc(Arc::new(Config::default_config()), 0)
     }
 
+    /// Returns the fields of the configuration that differ from their
+    /// default values
+    pub fn diff_from_default(&self) -> Vec<ConfigDelta> {
+        Config::default_config().diff(&self.config)
+    }
+
     pub fn unicode_version(&self) -> UnicodeVersion {
         let text = self.text();
         UnicodeVersion {
@@ -864,9 +1123,10 @@ impl ConfigHandle {
         cmd: &mut portable_pty::CommandBuilder,
         default_prog: Option<&Vec<String>>,
         default_cwd: Option<&PathBuf>,
-    ) {
+        context: Option<&SpawnContext>,
+    ) -> anyhow::Result<()> {
         self.config
-            .apply_cmd_defaults(cmd, default_prog, default_cwd)
+            .apply_cmd_defaults(cmd, default_prog, default_cwd, context)
     }
 
     pub fn initial_size(&self, dpi: u32, cell_pixel_dims: Option<(usize, usize)>) -> TerminalSize {
@@ -878,8 +1138,10 @@ impl ConfigHandle {
         prog: Option<Vec<&std::ffi::OsStr>>,
         default_prog: Option<&Vec<String>>,
         default_cwd: Option<&PathBuf>,
+        context: Option<&SpawnContext>,
     ) -> anyhow::Result<portable_pty::CommandBuilder> {
-        self.config.build_prog(prog, default_prog, default_cwd)
+        self.config
+            .build_prog(prog, default_prog, default_cwd, context)
     }
 }
 
@@ -997,7 +1259,9 @@ pub struct LoadedConfig {
     pub config: anyhow::Result<Config>,
     pub file_name: Option<PathBuf>,
     pub lua: Option<mlua::Lua>,
-    pub warnings: Vec<String>,
+    pub warnings: Vec<Warning>,
+    /// How long the config file took to evaluate, when one was loaded
+    pub eval_duration: Option<Duration>,
 }
 
 fn default_one_point_oh_f64() -> f64 {
@@ -1011,3 +1275,77 @@ fn default_one_point_oh() -> f32 {
 fn default_true() -> bool {
     true
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use phaedra_term::color::SrgbaTuple;
+
+    #[test]
+    fn palette_generation_follows_color_changes() {
+        let mut inner = ConfigInner::new();
+        let initial = inner.handle().color_palette();
+
+        // Reloading without changing the colors keeps the palette
+        inner.replace_config(Config::default_config());
+        let handle = inner.handle();
+        assert_eq!(handle.generation(), 1);
+        assert_eq!(handle.palette_generation(), 0);
+        assert!(Arc::ptr_eq(&handle.color_palette(), &initial));
+
+        let mut config = Config::default_config();
+        config.color_config.resolved_palette.background = Some((0x20, 0x10, 0x30).into());
+        inner.replace_config(config);
+        let handle = inner.handle();
+        assert_eq!(handle.palette_generation(), 2);
+        assert_eq!(
+            handle.color_palette().background,
+            SrgbaTuple::from((0x20, 0x10, 0x30))
+        );
+
+        // Going back to the original colors shares the original palette
+        inner.replace_config(Config::default_config());
+        let handle = inner.handle();
+        assert_eq!(handle.palette_generation(), 3);
+        assert!(Arc::ptr_eq(&handle.color_palette(), &initial));
+    }
+
+    #[test]
+    fn appearance_change_swaps_the_palette() {
+        use phaedra_term::TerminalConfiguration;
+
+        let mut inner = ConfigInner::new();
+        let mut config = Config::default_config();
+        config.color_config.color_scheme = Some(ColorSchemeSelection::ByAppearance {
+            dark: "Builtin Dark".to_string(),
+            light: "Builtin Light".to_string(),
+        });
+        config.resolve_palette(SystemAppearance::Light);
+        inner.replace_config(config);
+        let light = inner.handle();
+        assert_eq!(
+            light.color_palette().background,
+            SrgbaTuple::from((0xff, 0xff, 0xff))
+        );
+
+        // The lua config didn't consult the appearance, so it needn't
+        // be reloaded
+        assert!(!inner.set_system_appearance(SystemAppearance::Dark));
+        let dark = inner.handle();
+        assert_eq!(dark.color_palette().background, SrgbaTuple::from((0, 0, 0)));
+        assert!(dark.generation() > light.generation());
+        // The new palette generation is what causes the panes of a
+        // window to be described again
+        assert_ne!(dark.palette_generation(), light.palette_generation());
+        assert_eq!(
+            TermConfig::with_config(dark.clone()).color_palette(),
+            dark.color_palette()
+        );
+
+        // Being told the same appearance again changes nothing
+        assert!(!inner.set_system_appearance(SystemAppearance::Dark));
+        assert_eq!(inner.handle().generation(), dark.generation());
+
+        inner.set_system_appearance(SystemAppearance::default());
+    }
+}
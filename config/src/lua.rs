@@ -12,8 +12,10 @@ use portable_pty::CommandBuilder;
 use std::convert::TryFrom;
 use std::path::Path;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use phaedra_dynamic::{
-    FromDynamic, FromDynamicOptions, ToDynamic, UnknownFieldAction, Value as DynValue,
+    FromDynamic, FromDynamicOptions, SourceLocation, ToDynamic, UnknownFieldAction,
+    Value as DynValue, Warning,
 };
 
 pub use mlua;
@@ -30,6 +32,66 @@ pub fn add_context_setup_func(func: SetupFunc) {
     SETUP_FUNCS.lock().unwrap().push(func);
 }
 
+/// Evaluates `source`, the contents of the config file `path`, and
+/// returns the value that it produces.  If that takes longer than
+/// `timeout`, the evaluation is abandoned with an error.  A script that
+/// is busy running Lua code is interrupted by a hook that checks the
+/// deadline every so many instructions, while one that is waiting on
+/// async work, such as a child process, is dropped when a timer fires.
+pub fn eval_config_with_timeout<'lua>(
+    lua: &'lua Lua,
+    source: &str,
+    path: &Path,
+    timeout: Duration,
+) -> anyhow::Result<Value<'lua>> {
+    let deadline = Instant::now() + timeout;
+    lua.set_hook(
+        mlua::HookTriggers::new().every_nth_instruction(10_000),
+        move |_lua, _debug| {
+            if Instant::now() >= deadline {
+                Err(mlua::Error::RuntimeError(
+                    "config evaluation timed out".to_string(),
+                ))
+            } else {
+                Ok(())
+            }
+        },
+    );
+
+    let eval = async {
+        lua.load(source)
+            .set_name(path.to_string_lossy())
+            .eval_async::<Value>()
+            .await
+            .map(Some)
+    };
+    let timer = async {
+        smol::Timer::at(deadline).await;
+        Ok(None)
+    };
+    let result = smol::block_on(smol::future::or(eval, timer));
+    lua.remove_hook();
+
+    match result {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Err(eval_timed_out(path, timeout)),
+        Err(_) if Instant::now() >= deadline => Err(eval_timed_out(path, timeout)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn eval_timed_out(path: &Path, timeout: Duration) -> anyhow::Error {
+    anyhow!(
+        "Evaluating {} timed out after {:?}. \
+         Slow work, such as network requests, should be done in \
+         an event callback, such as one registered with \
+         phaedra.on('gui-startup', ...), rather than while \
+         the config is being evaluated.",
+        path.display(),
+        timeout
+    )
+}
+
 pub fn get_or_create_module<'lua>(lua: &'lua Lua, name: &str) -> anyhow::Result<mlua::Table<'lua>> {
     let globals = lua.globals();
     let package: Table = globals.get("package")?;
@@ -143,6 +205,9 @@ fn config_builder_new_index<'lua>(
                     // a mistake. This path is taken when they are not in strict
                     // mode, and we want to print some more context after the from_dynamic
                     // impl has logged a warning and suggested alternative field names.
+                    // The innermost frame with a line number is recorded as the
+                    // location of the structured warning.
+                    let mut location = None;
                     let mut message =
                         format!("Attempted to set invalid config option `{key}` at:\n");
                     // Start at frame 1, our caller, as the frame for invoking this
@@ -165,11 +230,25 @@ fn config_builder_new_index<'lua>(
 
                             let line = debug.curr_line();
                             message.push_str(&format!("    [{i}] {source}:{line} {func_name}\n"));
+                            if location.is_none() && line > 0 {
+                                location.replace(SourceLocation {
+                                    file: source
+                                        .trim_start_matches(|c| c == '@' || c == '=')
+                                        .to_string(),
+                                    line: line as usize,
+                                });
+                            }
                         } else {
                             break;
                         }
                     }
-                    phaedra_dynamic::Error::warn(message);
+                    log::debug!("{message}");
+                    phaedra_dynamic::Error::warn(Warning::UnknownField {
+                        struct_name: "Config",
+                        field: key,
+                        alternatives: String::new(),
+                        location,
+                    });
                 }
                 Some(_dvalue) => {
                     myself.raw_set(key, value)?;
@@ -259,24 +338,52 @@ pub fn make_lua_context(config_file: &Path) -> anyhow::Result<Lua> {
         // Hook into loader and arrange to watch all require'd files.
         // <https://www.lua.org/manual/5.3/manual.html#pdf-package.searchers>
         // says that the second searcher function is the one that is responsible
-        // for loading lua files, so we shim around that and speculatively
-        // add the name of the file that it would find (as returned from
-        // package.searchpath) to the watch list, then we just call the
-        // original implementation.
+        // for loading lua files, so we replace it with one that adds the
+        // file that it finds (as returned from package.searchpath) to the
+        // watch list, and then reads it with read_lua_module so that a BOM
+        // is skipped and UTF-16 is reported clearly.  The modules that are
+        // being loaded are tracked so that an error loading a module can
+        // say which modules required it.
         lua.load(
             r#"
-local orig = package.searchers[2]
+local read_module = ...
+local chain = {}
+
+local function required_by()
+  local parents = {}
+  for i = #chain, 1, -1 do
+    table.insert(parents, '\n\trequired by ' .. chain[i])
+  end
+  return table.concat(parents)
+end
+
 package.searchers[2] = function(module)
-  local name, err = package.searchpath(module, package.path)
-  if name then
-    package.loaded.wezterm.add_to_config_reload_watch_list(name)
+  local name, not_found = package.searchpath(module, package.path)
+  if not name then
+    return not_found
+  end
+  package.loaded.wezterm.add_to_config_reload_watch_list(name)
+
+  local chunk
+  local source, err = read_module(name)
+  if source then
+    chunk, err = load(source, '@' .. name)
+  end
+  if not chunk then
+    error(string.format("error loading module '%s' from file '%s':\n\t%s%s",
+      module, name, err, required_by()), 0)
   end
-  return orig(module)
+
+  return function(...)
+    table.insert(chain, string.format("'%s' (%s)", module, name))
+    local _ <close> = setmetatable({}, {__close = function() table.remove(chain) end})
+    return chunk(...)
+  end, name
 end
         "#,
         )
         .set_name("=searcher")
-        .eval::<()>()
+        .call::<_, ()>(lua.create_function(read_lua_module)?)
         .context("replace package.searchers")?;
 
         phaedra_mod.set(
@@ -360,6 +467,10 @@ end
                 Ok(to_lua(lua, rules))
             })?,
         )?;
+        phaedra_mod.set(
+            "test_hyperlink_rules",
+            lua.create_function(test_hyperlink_rules)?,
+        )?;
 
         // Define our own os.getenv function that knows how to resolve current
         // environment values from eg: the registry on Windows, or for
@@ -677,6 +788,26 @@ fn exec_domain<'lua>(
     })
 }
 
+/// This implements `phaedra.test_hyperlink_rules`, which reports the
+/// matches that a set of hyperlink rules make in `text`.  The rule
+/// indices and the spans are 1-based, so that `text:sub(m.start, m['end'])`
+/// yields the matched text.
+fn test_hyperlink_rules<'lua>(
+    lua: &'lua Lua,
+    (text, rules): (String, Value<'lua>),
+) -> mlua::Result<Value<'lua>> {
+    let rules: Vec<termwiz::hyperlink::Rule> = luahelper::from_lua(rules)?;
+    let matches: Vec<_> = termwiz::hyperlink::Rule::test_hyperlinks(&text, &rules)
+        .into_iter()
+        .map(|mut m| {
+            m.rule_index += 1;
+            m.start += 1;
+            m
+        })
+        .collect();
+    to_lua(lua, matches)
+}
+
 fn split_by_newlines<'lua>(_: &'lua Lua, text: String) -> mlua::Result<Vec<String>> {
     Ok(text
         .lines()
@@ -843,6 +974,42 @@ fn utf16_to_utf8<'lua>(_: &'lua Lua, text: mlua::String) -> mlua::Result<String>
     String::from_utf16(wide).map_err(mlua::Error::external)
 }
 
+/// Returns the source of a lua module that was read from `path`,
+/// without the byte order mark that some Windows software places at the
+/// start of UTF-8 files.  Lua can only read UTF-8 source, so a UTF-16
+/// file is an error, rather than a syntax error on its first byte.
+fn lua_module_source<'a>(path: &Path, bytes: &'a [u8]) -> anyhow::Result<&'a [u8]> {
+    let utf16_bom = bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]);
+    // UTF-16 without a BOM has a zero byte in every ASCII character,
+    // which can't appear at the start of lua source
+    let utf16_ascii = bytes.len() >= 2 && (bytes[0] == 0 || bytes[1] == 0);
+    if utf16_bom || utf16_ascii {
+        anyhow::bail!(
+            "{} is encoded as UTF-16, which lua cannot read; re-save it as UTF-8",
+            path.display()
+        );
+    }
+    Ok(bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes))
+}
+
+/// Reads the lua module at `path` for the searcher installed by
+/// `make_lua_context`.  Returns its source, or nil and an error message,
+/// as lua's own loaders do.
+fn read_lua_module<'lua>(
+    lua: &'lua Lua,
+    path: String,
+) -> mlua::Result<(Option<mlua::String<'lua>>, Option<String>)> {
+    let path = Path::new(&path);
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return Ok((None, Some(format!("cannot read {}: {err}", path.display())))),
+    };
+    match lua_module_source(path, &bytes) {
+        Ok(source) => Ok((Some(lua.create_string(source)?), None)),
+        Err(err) => Ok((None, Some(format!("{err:#}")))),
+    }
+}
+
 pub fn add_to_config_reload_watch_list<'lua>(
     lua: &'lua Lua,
     args: Variadic<String>,
@@ -858,6 +1025,112 @@ mod test {
     use super::*;
     use std::sync::{Arc, Mutex};
 
+    #[test]
+    fn config_eval_times_out() -> anyhow::Result<()> {
+        let path = Path::new("busy.lua");
+        let lua = make_lua_context(path)?;
+        let started = Instant::now();
+        let err =
+            eval_config_with_timeout(&lua, "while true do end", path, Duration::from_millis(100))
+                .unwrap_err()
+                .to_string();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err.contains("busy.lua"), "{}", err);
+        assert!(err.contains("timed out"), "{}", err);
+
+        // The deadline has passed, so this would be interrupted if
+        // the hook were left in place
+        let value: i64 = lua
+            .load("local n = 0; for i = 1, 1000000 do n = n + 1 end; return n")
+            .eval()?;
+        assert_eq!(value, 1000000);
+        drop(lua);
+
+        let lua = make_lua_context(path)?;
+        let value = eval_config_with_timeout(&lua, "return 42", path, Duration::from_millis(100))?;
+        assert!(matches!(value, Value::Integer(42)));
+
+        let err = eval_config_with_timeout(&lua, "error('oops')", path, Duration::from_secs(5))
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains("timed out"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn required_modules_skip_bom_and_reject_utf16() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("phaedra-lua-modules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("bom.lua"), "\u{FEFF}return 'bom'")?;
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "return 'utf16'".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        std::fs::write(dir.join("utf16.lua"), utf16)?;
+        std::fs::write(dir.join("outer.lua"), "return require 'middle'")?;
+        std::fs::write(dir.join("middle.lua"), "return require 'broken'")?;
+        std::fs::write(dir.join("broken.lua"), "return (")?;
+
+        let lua = make_lua_context(Path::new("modules.lua"))?;
+        let package: Table = lua.globals().get("package")?;
+        package.set("path", format!("{}/?.lua", dir.display()))?;
+
+        let value: String = lua.load("return require 'bom'").eval()?;
+        assert_eq!(value, "bom");
+
+        let err = lua
+            .load("return require 'utf16'")
+            .eval::<Value>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("utf16.lua is encoded as UTF-16"), "{err}");
+        assert!(err.contains("re-save it as UTF-8"), "{err}");
+
+        let err = lua
+            .load("return require 'outer'")
+            .eval::<Value>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("error loading module 'broken'"), "{err}");
+        assert!(err.contains("broken.lua:1:"), "{err}");
+        let middle = err.find("required by 'middle'").expect(&err);
+        let outer = err.find("required by 'outer'").expect(&err);
+        assert!(middle < outer, "{err}");
+
+        // The chain is unwound once the failed require returns
+        std::fs::write(dir.join("other.lua"), "return (")?;
+        let err = lua
+            .load("return require 'other'")
+            .eval::<Value>()
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains("required by"), "{err}");
+
+        // A module that isn't found is reported as lua would
+        let err = lua
+            .load("return require 'missing'")
+            .eval::<Value>()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("module 'missing' not found"), "{err}");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn lua_module_source_strips_bom() {
+        let path = Path::new("module.lua");
+        assert_eq!(
+            lua_module_source(path, b"\xEF\xBB\xBFreturn 1").unwrap(),
+            b"return 1"
+        );
+        assert_eq!(lua_module_source(path, b"return 1").unwrap(), b"return 1");
+        assert_eq!(lua_module_source(path, b"").unwrap(), b"");
+        assert!(lua_module_source(path, b"\xFE\xFF\x00r").is_err());
+        assert!(lua_module_source(path, b"r\x00e\x00").is_err());
+    }
+
     #[test]
     fn can_register_and_emit_multiple_events() -> anyhow::Result<()> {
         let _ = env_logger::Builder::new()
@@ -942,4 +1215,27 @@ assert(wezterm.emit('bar', 42, 'woot') == true)
 
         Ok(())
     }
+
+    #[test]
+    fn test_hyperlink_rules_reports_one_based_matches() -> anyhow::Result<()> {
+        let lua = make_lua_context(Path::new("testing"))?;
+        let (rule_index, text, url): (i64, String, String) = lua
+            .load(
+                r#"
+local phaedra = require 'phaedra';
+local rules = {
+  { regex = [[\bhttps://\S+]], format = '$0' },
+  { regex = [[\bT(\d+)\b]], format = 'https://example.com/task/$1' },
+}
+local line = 'fixes T42'
+local m = phaedra.test_hyperlink_rules(line, rules)[1]
+return m.rule_index, line:sub(m.start, m['end']), m.url
+"#,
+            )
+            .eval()?;
+        assert_eq!(rule_index, 2);
+        assert_eq!(text, "T42");
+        assert_eq!(url, "https://example.com/task/42");
+        Ok(())
+    }
 }
@@ -1,8 +1,27 @@
-use phaedra_dynamic::Value;
+use phaedra_dynamic::{Error, FromDynamic, ToDynamic, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
 /// Trait for returning metadata about config options
 pub trait ConfigMeta {
-    fn get_config_options(&self) -> &'static [ConfigOption];
+    /// The options held by this struct, in declaration order
+    const OPTIONS: &'static [ConfigOption];
+
+    fn get_config_options(&self) -> &'static [ConfigOption] {
+        Self::OPTIONS
+    }
+
+    /// Returns a content hash for each field, keyed by field name.
+    /// Comparing these across a reload tells us which sections
+    /// of the configuration actually changed.
+    fn section_hashes(&self) -> Vec<(&'static str, u64)>;
+}
+
+/// Hashes the dynamic representation of a config field
+pub fn hash_config_section<T: ToDynamic>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.to_dynamic().hash(&mut hasher);
+    hasher.finish()
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -26,8 +45,24 @@ pub struct ConfigOption {
     pub type_name: &'static str,
     /// call this to get the default value
     pub default_value: Option<fn() -> Value>,
-    /// TODO: For enum types, the set of possible values
-    pub possible_values: &'static [&'static Value],
-    /// TODO: For struct types, the fields in the child struct
+    /// If set, the option is deprecated and this explains what
+    /// to use instead
+    pub deprecated: Option<&'static str>,
+    /// call this to get the variant names of an enum type; it returns
+    /// an empty list for other types
+    pub possible_values: fn() -> &'static [&'static str],
+    /// For nested sections of the configuration, the fields in the
+    /// child struct
     pub fields: &'static [ConfigOption],
 }
+
+/// Returns the names of the variants of an enum that converts from a
+/// string, or an empty list for other types.
+/// This relies on the FromDynamic conversion rejecting an unknown
+/// variant name with a list of the names that it would have accepted.
+pub fn variant_names<T: FromDynamic>() -> &'static [&'static str] {
+    match T::from_dynamic(&Value::String(" ".to_string()), Default::default()) {
+        Err(Error::InvalidVariantForType { possible, .. }) => possible,
+        _ => &[],
+    }
+}
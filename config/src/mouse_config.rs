@@ -1,9 +1,10 @@
 use crate::config::DroppedFileQuoting;
 use crate::keys::Mouse;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 use phaedra_input_types::Modifiers;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct MouseConfig {
     #[dynamic(default)]
     pub mouse_bindings: Vec<Mouse>,
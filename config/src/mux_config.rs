@@ -1,7 +1,8 @@
 use crate::daemon::DaemonOptions;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct MuxConfig {
     #[dynamic(default = "default_ratelimit_line_prefetches_per_second")]
     pub ratelimit_mux_line_prefetches_per_second: u32,
@@ -11,6 +12,10 @@ pub struct MuxConfig {
     pub mux_output_parser_coalesce_delay_ms: u64,
     #[dynamic(default)]
     pub daemon_options: DaemonOptions,
+    /// Save the layout of the windows, tabs and panes on exit and
+    /// periodically, so that it can be rebuilt by `phaedra start --resurrect`
+    #[dynamic(default)]
+    pub save_session_layout: bool,
 }
 
 impl Default for MuxConfig {
@@ -20,6 +25,7 @@ impl Default for MuxConfig {
             mux_output_parser_buffer_size: default_mux_output_parser_buffer_size(),
             mux_output_parser_coalesce_delay_ms: default_mux_output_parser_coalesce_delay_ms(),
             daemon_options: DaemonOptions::default(),
+            save_session_layout: false,
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::meta::ConfigMeta;
 use crate::*;
 
 pub trait BellObserver {
@@ -93,3 +94,224 @@ pub trait FullConfigObserver:
     + RuntimeObserver
 {
 }
+
+/// Identifies one of the top level sections of `Config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfigSection {
+    FontConfig,
+    Text,
+    Cursor,
+    WindowConfig,
+    Gpu,
+    ColorConfig,
+    TabBar,
+    Scroll,
+    Launch,
+    TerminalFeatures,
+    Domain,
+    Mux,
+    KeyInput,
+    Mouse,
+    Runtime,
+    UpdateCheck,
+    Cache,
+    Bell,
+}
+
+impl ConfigSection {
+    pub const ALL: &'static [ConfigSection] = &[
+        Self::FontConfig,
+        Self::Text,
+        Self::Cursor,
+        Self::WindowConfig,
+        Self::Gpu,
+        Self::ColorConfig,
+        Self::TabBar,
+        Self::Scroll,
+        Self::Launch,
+        Self::TerminalFeatures,
+        Self::Domain,
+        Self::Mux,
+        Self::KeyInput,
+        Self::Mouse,
+        Self::Runtime,
+        Self::UpdateCheck,
+        Self::Cache,
+        Self::Bell,
+    ];
+
+    /// The name of the corresponding field in `Config`
+    pub fn field_name(self) -> &'static str {
+        match self {
+            Self::FontConfig => "font_config",
+            Self::Text => "text",
+            Self::Cursor => "cursor",
+            Self::WindowConfig => "window_config",
+            Self::Gpu => "gpu",
+            Self::ColorConfig => "color_config",
+            Self::TabBar => "tab_bar",
+            Self::Scroll => "scroll",
+            Self::Launch => "launch",
+            Self::TerminalFeatures => "terminal_features",
+            Self::Domain => "domain",
+            Self::Mux => "mux",
+            Self::KeyInput => "key_input",
+            Self::Mouse => "mouse",
+            Self::Runtime => "runtime",
+            Self::UpdateCheck => "update_check",
+            Self::Cache => "cache",
+            Self::Bell => "bell",
+        }
+    }
+
+    pub fn from_field_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|s| s.field_name() == name)
+    }
+
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+/// A set of config sections, typically the sections that differ
+/// between two generations of the configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct ConfigSectionSet(u32);
+
+impl ConfigSectionSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn all() -> Self {
+        Self::from_sections(ConfigSection::ALL)
+    }
+
+    pub fn from_sections(sections: &[ConfigSection]) -> Self {
+        let mut set = Self::empty();
+        for &section in sections {
+            set.insert(section);
+        }
+        set
+    }
+
+    pub fn insert(&mut self, section: ConfigSection) {
+        self.0 |= section.bit();
+    }
+
+    pub fn contains(&self, section: ConfigSection) -> bool {
+        self.0 & section.bit() != 0
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Returns true if any of `sections` are in this set
+    pub fn any_of(&self, sections: &[ConfigSection]) -> bool {
+        self.intersects(&Self::from_sections(sections))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ConfigSection> + '_ {
+        ConfigSection::ALL
+            .iter()
+            .copied()
+            .filter(move |s| self.contains(*s))
+    }
+}
+
+impl From<ConfigSection> for ConfigSectionSet {
+    fn from(section: ConfigSection) -> Self {
+        Self(section.bit())
+    }
+}
+
+/// Per-section content hashes for a given configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSectionHashes {
+    hashes: Vec<(ConfigSection, u64)>,
+}
+
+impl ConfigSectionHashes {
+    pub fn compute(config: &Config) -> Self {
+        let hashes = config
+            .section_hashes()
+            .into_iter()
+            .filter_map(|(name, hash)| {
+                let section = ConfigSection::from_field_name(name);
+                if section.is_none() {
+                    log::warn!("Config field {name} has no corresponding ConfigSection");
+                }
+                section.map(|s| (s, hash))
+            })
+            .collect();
+        Self { hashes }
+    }
+
+    pub fn get(&self, section: ConfigSection) -> Option<u64> {
+        self.hashes
+            .iter()
+            .find(|(s, _)| *s == section)
+            .map(|(_, hash)| *hash)
+    }
+
+    /// Returns the set of sections whose content differs from `prior`
+    pub fn changed_since(&self, prior: &Self) -> ConfigSectionSet {
+        let mut changed = ConfigSectionSet::empty();
+        for &section in ConfigSection::ALL {
+            if self.get(section) != prior.get(section) {
+                changed.insert(section);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_config_field_has_a_section() {
+        let config = Config::default_config();
+        let names: Vec<_> = config.section_hashes().into_iter().map(|(n, _)| n).collect();
+        for name in &names {
+            assert!(
+                ConfigSection::from_field_name(name).is_some(),
+                "{name} has no ConfigSection"
+            );
+        }
+        assert_eq!(names.len(), ConfigSection::ALL.len());
+    }
+
+    #[test]
+    fn unchanged_config_has_no_changed_sections() {
+        let config = Config::default_config();
+        let a = ConfigSectionHashes::compute(&config);
+        let b = ConfigSectionHashes::compute(&config.clone());
+        assert!(b.changed_since(&a).is_empty());
+    }
+
+    #[test]
+    fn changing_colors_only_notifies_color_section() {
+        let config = Config::default_config();
+        let before = ConfigSectionHashes::compute(&config);
+
+        let mut changed = config.clone();
+        changed.color_config.color_scheme = Some(crate::ColorSchemeSelection::Named(
+            "Builtin Dark".to_string(),
+        ));
+        let after = ConfigSectionHashes::compute(&changed);
+
+        let sections = after.changed_since(&before);
+        assert_eq!(sections, ConfigSectionSet::from(ConfigSection::ColorConfig));
+        assert_eq!(
+            sections.iter().collect::<Vec<_>>(),
+            vec![ConfigSection::ColorConfig]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Watching the configuration files for changes.  This is shared by the
+//! automatic reloading of the global configuration and by
+//! `subscribe_to_reloads`, which lets programs that embed this crate be
+//! told about a freshly loaded configuration rather than polling
+//! `Config::load`.
+use crate::{Config, LoadedConfig, HOME_DIR};
+use mlua::Lua;
+use notify::{EventKind, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// How long the configuration files must go without being changed
+/// before they are reloaded, so that a burst of writes, such as an
+/// editor saving via a temporary file, results in a single reload
+pub const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+pub(crate) type EventReceiver = Receiver<notify::Result<notify::Event>>;
+
+/// Returns the paths that `event` reports as having been changed
+pub(crate) fn changed_paths(event: notify::Event) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => event.paths,
+        _ => vec![],
+    }
+}
+
+/// Having received an event that changed `paths`, wait until no more
+/// events have arrived for `delay`, and return all of the paths that
+/// changed in the meantime
+pub(crate) fn settle(rx: &EventReceiver, mut paths: Vec<PathBuf>, delay: Duration) -> Vec<PathBuf> {
+    loop {
+        match rx.recv_timeout(delay) {
+            Ok(Ok(event)) => paths.append(&mut changed_paths(event)),
+            Ok(Err(_)) => {}
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Returns the paths that should be watched for changes to the
+/// configuration that was loaded from `file_name`: the file itself, its
+/// directory for the sake of those that symlink their config, the
+/// files that were added to the watch list by the lua package loader
+/// when they were `require`d, and the `color_scheme_file`, if any.
+pub(crate) fn reload_watch_paths(
+    file_name: Option<&Path>,
+    lua: Option<&Lua>,
+    color_scheme_file: Option<&Path>,
+) -> Vec<PathBuf> {
+    let mut watch_paths = vec![];
+    if let Some(path) = file_name {
+        // Let's also watch the parent directory for folks that do
+        // things with symlinks:
+        if let Some(parent) = path.parent() {
+            // But avoid watching the home dir itself, so that we
+            // don't keep reloading every time something in the
+            // home dir changes!
+            // <https://github.com/PaleRoses/phaedra/issues/1895>
+            if parent != &*HOME_DIR {
+                watch_paths.push(parent.to_path_buf());
+            }
+        }
+        watch_paths.push(path.to_path_buf());
+    }
+    if let Some(lua) = lua {
+        if let Ok(mlua::Value::Table(tbl)) = lua.named_registry_value("phaedra-watch-paths") {
+            for path in tbl.sequence_values::<String>() {
+                if let Ok(path) = path {
+                    watch_paths.push(PathBuf::from(path));
+                }
+            }
+        }
+    }
+    if let Some(path) = color_scheme_file {
+        watch_paths.push(path.to_path_buf());
+    }
+    watch_paths
+}
+
+/// Returns the paths that should be watched for changes to `loaded`
+pub(crate) fn loaded_watch_paths(loaded: &LoadedConfig) -> Vec<PathBuf> {
+    let color_scheme_file = loaded
+        .config
+        .as_ref()
+        .ok()
+        .and_then(|config| config.color_config.color_scheme_file());
+    reload_watch_paths(
+        loaded.file_name.as_deref(),
+        loaded.lua.as_ref(),
+        color_scheme_file.as_deref(),
+    )
+}
+
+/// Watch `paths` from a background thread, calling `reload` once the
+/// changes to them have settled for `delay`.  `reload` returns the paths
+/// to watch from then on, which are added to those already watched, or
+/// `None` to stop watching.
+pub(crate) fn watch_for_reloads<F>(
+    paths: Vec<PathBuf>,
+    delay: Duration,
+    mut reload: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut() -> Option<Vec<PathBuf>> + Send + 'static,
+{
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for path in &paths {
+        watcher
+            .watch(path, notify::RecursiveMode::NonRecursive)
+            .ok();
+    }
+
+    std::thread::Builder::new()
+        .name("config-reload-watcher".to_string())
+        .spawn(move || {
+            while let Ok(event) = rx.recv() {
+                let paths = match event {
+                    Ok(event) => changed_paths(event),
+                    Err(_) => vec![],
+                };
+                if paths.is_empty() {
+                    continue;
+                }
+                let paths = settle(&rx, paths, delay);
+                log::debug!("paths {:?} changed, reload config", paths);
+                match reload() {
+                    Some(paths) => {
+                        for path in &paths {
+                            watcher
+                                .watch(path, notify::RecursiveMode::NonRecursive)
+                                .ok();
+                        }
+                    }
+                    None => break,
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Returns a channel that receives a freshly loaded configuration,
+/// along with any warnings that were raised while loading it, each
+/// time the configuration file, or a file that it `require`s, changes.
+/// Rapid successive changes are coalesced into a single reload.
+/// The files are watched until the receiver has been dropped and they
+/// next change.
+pub fn subscribe_to_reloads() -> anyhow::Result<smol::channel::Receiver<LoadedConfig>> {
+    let (tx, rx) = smol::channel::unbounded();
+    let paths = loaded_watch_paths(&Config::load());
+
+    watch_for_reloads(paths, RELOAD_DEBOUNCE, move || {
+        let loaded = Config::load();
+        let paths = loaded_watch_paths(&loaded);
+        tx.try_send(loaded).ok().map(|()| paths)
+    })?;
+    Ok(rx)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn watch_paths_include_required_files() {
+        let lua = Lua::new();
+        lua.set_named_registry_value(
+            "phaedra-watch-paths",
+            vec!["/cfg/lib/colors.lua".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(
+            reload_watch_paths(
+                Some(Path::new("/cfg/phaedra.lua")),
+                Some(&lua),
+                Some(Path::new("/home/user/.theme"))
+            ),
+            vec![
+                PathBuf::from("/cfg"),
+                PathBuf::from("/cfg/phaedra.lua"),
+                PathBuf::from("/cfg/lib/colors.lua"),
+                PathBuf::from("/home/user/.theme"),
+            ]
+        );
+        assert!(reload_watch_paths(None, None, None).is_empty());
+    }
+
+    #[test]
+    fn rapid_writes_are_coalesced() {
+        let dir = std::env::temp_dir().join(format!("phaedra-reload-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("phaedra.lua");
+        std::fs::write(&path, "return {font_size = 10}").unwrap();
+
+        let (tx, rx) = smol::channel::unbounded();
+        let watched = path.clone();
+        watch_for_reloads(vec![path.clone()], RELOAD_DEBOUNCE, move || {
+            let content = std::fs::read_to_string(&watched).unwrap();
+            tx.try_send(content).ok().map(|()| vec![watched.clone()])
+        })
+        .unwrap();
+
+        std::fs::write(&path, "return {font_size = 11}").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(&path, "return {font_size = 12}").unwrap();
+
+        // Wait for the changes to settle and then for long enough for
+        // any further reload to have been made
+        let deadline = Instant::now() + RELOAD_DEBOUNCE * 10;
+        let mut reloads = vec![];
+        while Instant::now() < deadline {
+            while let Ok(content) = rx.try_recv() {
+                reloads.push(content);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(!reloads.is_empty() && reloads.len() <= 2, "{reloads:?}");
+        assert_eq!(reloads.last().unwrap(), "return {font_size = 12}");
+    }
+}
@@ -1,6 +1,8 @@
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct RuntimeConfig {
     #[dynamic(default)]
     pub log_unknown_escape_sequences: bool,
@@ -18,6 +20,31 @@ pub struct RuntimeConfig {
     pub ulimit_nproc: u64,
     #[dynamic(default = "default_one")]
     pub palette_max_key_assigments_for_action: usize,
+    #[dynamic(default)]
+    pub command_palette: CommandPaletteConfig,
+    /// Record a structured trace of the decisions made while rendering
+    /// each frame to a file in the runtime dir
+    #[dynamic(default)]
+    pub render_diagnostics: bool,
+    /// Record the render commands of each painted frame, so that they
+    /// can be replayed with `phaedra-gui replay-frames`
+    #[dynamic(default)]
+    pub record_frames: Option<RecordFramesConfig>,
+    /// Log a warning, naming the slowest stage of the frame, when
+    /// painting a frame takes longer than this many milliseconds.
+    /// 0 disables the warning.
+    #[dynamic(default = "default_frame_time_warning_ms")]
+    pub frame_time_warning_ms: u64,
+    /// Output from a pane within this many milliseconds of the last
+    /// time that it repainted the window is painted together at the
+    /// end of that interval.  0 paints each output as it arrives.
+    #[dynamic(default)]
+    pub output_coalesce_ms: u64,
+    /// Limits how many times a second output from each pane may
+    /// repaint the window; output beyond that is painted once the
+    /// limit allows.  0 means no limit.
+    #[dynamic(default)]
+    pub max_render_updates_per_second_per_pane: u32,
 }
 
 impl Default for RuntimeConfig {
@@ -31,10 +58,55 @@ impl Default for RuntimeConfig {
             ulimit_nofile: default_ulimit_nofile(),
             ulimit_nproc: default_ulimit_nproc(),
             palette_max_key_assigments_for_action: default_one(),
+            command_palette: CommandPaletteConfig::default(),
+            render_diagnostics: false,
+            record_frames: None,
+            frame_time_warning_ms: default_frame_time_warning_ms(),
+            output_coalesce_ms: 0,
+            max_render_updates_per_second_per_pane: 0,
         }
     }
 }
 
+/// How the command palette matches the typed text against its entries
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum PaletteFuzzyAlgorithm {
+    /// Fuzzy matching where the characters need only appear in order
+    #[default]
+    SkimV2,
+    /// The typed text must appear somewhere in the entry, ignoring case
+    Substring,
+    /// The typed text must begin the entry, or one of its words, ignoring case
+    Prefix,
+}
+
+#[derive(Debug, Default, Clone, FromDynamic, ToDynamic)]
+pub struct CommandPaletteConfig {
+    #[dynamic(default)]
+    pub fuzzy_algorithm: PaletteFuzzyAlgorithm,
+    /// Limits the number of matching entries; all matches are
+    /// shown when this is not set
+    #[dynamic(default)]
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct RecordFramesConfig {
+    /// The recording to write; it is replaced when recording starts
+    pub path: PathBuf,
+    /// Recording stops after this many frames
+    #[dynamic(default = "default_max_recorded_frames")]
+    pub max_frames: usize,
+}
+
+fn default_max_recorded_frames() -> usize {
+    1_000
+}
+
+fn default_frame_time_warning_ms() -> u64 {
+    100
+}
+
 fn default_true() -> bool {
     true
 }
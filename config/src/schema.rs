@@ -0,0 +1,317 @@
+//! Describes the configuration in forms that editors understand, so that
+//! they can offer completion and validation while editing phaedra.lua.
+//! Both descriptions are derived from the `ConfigMeta` of `Config` and
+//! its sections, so they follow the structs without further upkeep.
+use crate::config::Config;
+use crate::meta::{ConfigContainer, ConfigMeta, ConfigOption};
+use phaedra_dynamic::Value;
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+impl Config {
+    /// Returns a JSON Schema describing the configuration
+    pub fn json_schema() -> JsonValue {
+        let mut schema = object_schema("Config", Self::OPTIONS);
+        if let Some(obj) = schema.as_object_mut() {
+            obj.insert(
+                "$schema".to_string(),
+                "https://json-schema.org/draft/2020-12/schema".into(),
+            );
+        }
+        schema
+    }
+
+    /// Returns `---@class` annotations describing the configuration,
+    /// for use with lua-language-server
+    pub fn lua_type_definitions() -> String {
+        let mut defs = LuaDefinitions::default();
+        defs.add_class("Config", Self::OPTIONS);
+        defs.finish()
+    }
+}
+
+fn object_schema(title: &str, options: &[ConfigOption]) -> JsonValue {
+    let properties: Map<String, JsonValue> = options
+        .iter()
+        .map(|option| (option.name.to_string(), option_schema(option)))
+        .collect();
+    json!({
+        "title": title,
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+fn option_schema(option: &ConfigOption) -> JsonValue {
+    let mut schema = match option.container {
+        ConfigContainer::None | ConfigContainer::Option => type_schema(option),
+        ConfigContainer::Vec => json!({
+            "type": "array",
+            "items": type_schema(option),
+        }),
+        ConfigContainer::Map => json!({
+            "type": "object",
+            "additionalProperties": type_schema(option),
+        }),
+    };
+
+    let obj = match schema.as_object_mut() {
+        Some(obj) => obj,
+        None => return schema,
+    };
+    let mut description = doc_text(option.doc);
+    // The default of a section is the whole section; the defaults of
+    // its fields are reported on the fields themselves
+    if option.fields.is_empty() {
+        if let Some(default) = option.default_value {
+            let default = dynamic_to_json(&default());
+            if !default.is_null() {
+                obj.insert("default".to_string(), default);
+            }
+        }
+    }
+    if let Some(reason) = option.deprecated {
+        obj.insert("deprecated".to_string(), true.into());
+        if !description.is_empty() {
+            description.push_str("\n\n");
+        }
+        description.push_str("Deprecated: ");
+        description.push_str(reason);
+    }
+    if !description.is_empty() {
+        obj.insert("description".to_string(), description.into());
+    }
+    schema
+}
+
+/// The schema for a single value of the option's type, ignoring
+/// any container that holds it
+fn type_schema(option: &ConfigOption) -> JsonValue {
+    if !option.fields.is_empty() {
+        return object_schema(option.type_name, option.fields);
+    }
+    let variants = (option.possible_values)();
+    if !variants.is_empty() {
+        return json!({
+            "title": option.type_name,
+            "type": "string",
+            "enum": variants,
+        });
+    }
+    match scalar_type(option.type_name) {
+        Some(Scalar::Boolean) => json!({"type": "boolean"}),
+        Some(Scalar::Integer) => json!({"type": "integer"}),
+        Some(Scalar::Number) => json!({"type": "number"}),
+        Some(Scalar::String) => json!({"type": "string"}),
+        // Anything else has a conversion of its own that we can't
+        // describe here, so only name the type
+        None => json!({"title": option.type_name}),
+    }
+}
+
+enum Scalar {
+    Boolean,
+    Integer,
+    Number,
+    String,
+}
+
+fn scalar_type(type_name: &str) -> Option<Scalar> {
+    match type_name {
+        "bool" => Some(Scalar::Boolean),
+        "u8" | "u16" | "u32" | "u64" | "usize" | "i8" | "i16" | "i32" | "i64" | "isize" => {
+            Some(Scalar::Integer)
+        }
+        "f32" | "f64" => Some(Scalar::Number),
+        "String" | "PathBuf" => Some(Scalar::String),
+        _ => None,
+    }
+}
+
+/// Joins the lines of a doc comment, which arrive with the leading
+/// space that follows `///`
+fn doc_text(doc: &str) -> String {
+    doc.lines()
+        .map(|line| line.trim())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+fn dynamic_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => (*b).into(),
+        Value::String(s) => s.as_str().into(),
+        Value::Array(a) => a.iter().map(dynamic_to_json).collect(),
+        Value::Object(o) => JsonValue::Object(
+            o.iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Value::String(s) => s.to_string(),
+                        other => dynamic_to_json(other).to_string(),
+                    };
+                    (key, dynamic_to_json(v))
+                })
+                .collect(),
+        ),
+        Value::U64(u) => (*u).into(),
+        Value::I64(i) => (*i).into(),
+        Value::F64(f) => f.into_inner().into(),
+    }
+}
+
+#[derive(Default)]
+struct LuaDefinitions {
+    classes: String,
+    aliases: BTreeMap<&'static str, &'static [&'static str]>,
+}
+
+impl LuaDefinitions {
+    fn add_class(&mut self, name: &str, options: &'static [ConfigOption]) {
+        let mut nested = vec![];
+        let mut class = format!("---@class {name}\n");
+        for option in options {
+            let mut ty = self.lua_type(option);
+            if !option.fields.is_empty() {
+                nested.push(option);
+            }
+            match option.container {
+                ConfigContainer::None | ConfigContainer::Option => {}
+                ConfigContainer::Vec => ty.push_str("[]"),
+                ConfigContainer::Map => ty = format!("table<string, {ty}>"),
+            }
+            write!(class, "---@field {}? {}", option.name, ty).ok();
+            // lua-language-server shows a single line of description
+            let doc = doc_text(option.doc);
+            let mut summary = doc.lines().next().unwrap_or("").to_string();
+            if let Some(reason) = option.deprecated {
+                if !summary.is_empty() {
+                    summary.push(' ');
+                }
+                write!(summary, "(deprecated: {reason})").ok();
+            }
+            if !summary.is_empty() {
+                write!(class, " {summary}").ok();
+            }
+            class.push('\n');
+        }
+        class.push('\n');
+        self.classes.push_str(&class);
+
+        for option in nested {
+            self.add_class(option.type_name, option.fields);
+        }
+    }
+
+    fn lua_type(&mut self, option: &ConfigOption) -> String {
+        if !option.fields.is_empty() {
+            return option.type_name.to_string();
+        }
+        let variants = (option.possible_values)();
+        if !variants.is_empty() {
+            self.aliases.insert(option.type_name, variants);
+            return option.type_name.to_string();
+        }
+        match scalar_type(option.type_name) {
+            Some(Scalar::Boolean) => "boolean",
+            Some(Scalar::Integer) => "integer",
+            Some(Scalar::Number) => "number",
+            Some(Scalar::String) => "string",
+            None => "any",
+        }
+        .to_string()
+    }
+
+    fn finish(self) -> String {
+        let mut result = "---@meta\n\n".to_string();
+        result.push_str(&self.classes);
+        for (name, variants) in self.aliases {
+            let variants: Vec<String> = variants.iter().map(|v| format!("\"{v}\"")).collect();
+            writeln!(result, "---@alias {} {}", name, variants.join("|")).ok();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use phaedra_config_derive::ConfigMeta;
+    use phaedra_dynamic::ToDynamic;
+
+    #[allow(dead_code)]
+    #[derive(ToDynamic, ConfigMeta)]
+    struct WithDeprecated {
+        /// Still supported
+        #[dynamic(default)]
+        current: bool,
+        #[dynamic(deprecated = "use `current` instead")]
+        old: bool,
+    }
+
+    #[test]
+    fn well_known_fields() {
+        let schema = Config::json_schema();
+        let font_size = &schema["properties"]["font_config"]["properties"]["font_size"];
+        assert_eq!(font_size["type"], "number");
+        assert_eq!(font_size["default"], 12.0);
+
+        let scrollback = &schema["properties"]["scroll"]["properties"]["scrollback_lines"];
+        assert_eq!(scrollback["type"], "integer");
+        assert_eq!(scrollback["default"], 3500);
+    }
+
+    #[test]
+    fn enums_list_their_variants() {
+        let schema = Config::json_schema();
+        let cursor_style = &schema["properties"]["cursor"]["properties"]["default_cursor_style"];
+        assert_eq!(cursor_style["type"], "string");
+        assert_eq!(cursor_style["default"], "SteadyBlock");
+        assert!(cursor_style["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&"BlinkingBar".into()));
+
+        let exit_behavior = &schema["properties"]["launch"]["properties"]["exit_behavior"];
+        assert_eq!(
+            exit_behavior["enum"],
+            json!(["Close", "CloseOnCleanExit", "Hold"])
+        );
+
+        let bold =
+            &schema["properties"]["color_config"]["properties"]["bold_brightens_ansi_colors"];
+        assert_eq!(bold["enum"], json!(["No", "BrightAndBold", "BrightOnly"]));
+    }
+
+    #[test]
+    fn deprecated_fields_are_marked() {
+        let schema = object_schema("WithDeprecated", WithDeprecated::OPTIONS);
+        let current = &schema["properties"]["current"];
+        assert_eq!(current["description"], "Still supported");
+        assert!(current.get("deprecated").is_none());
+
+        let old = &schema["properties"]["old"];
+        assert_eq!(old["deprecated"], true);
+        assert_eq!(old["description"], "Deprecated: use `current` instead");
+
+        let mut defs = LuaDefinitions::default();
+        defs.add_class("WithDeprecated", WithDeprecated::OPTIONS);
+        assert!(defs
+            .finish()
+            .contains("---@field old? boolean (deprecated: use `current` instead)\n"));
+    }
+
+    #[test]
+    fn lua_definitions() {
+        let defs = Config::lua_type_definitions();
+        assert!(defs.contains("---@class Config\n"));
+        assert!(defs.contains("---@field font_config? FontConfig\n"));
+        assert!(defs.contains("---@class FontConfig\n"));
+        assert!(defs.contains("---@field font_size? number"));
+        assert!(defs.contains("---@alias ExitBehavior \"Close\"|\"CloseOnCleanExit\"|\"Hold\"\n"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Conversion of color schemes written for other tools into the
+//! native `ColorSchemeFile` representation.
+use crate::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// The format that a color scheme file was written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSchemeFormat {
+    /// Our own TOML layout
+    Native,
+    /// A base16 YAML scheme
+    Base16,
+    /// An Alacritty TOML theme
+    Alacritty,
+}
+
+/// Parse a color value from a foreign scheme format.
+/// base16 omits the leading `#` and older Alacritty themes
+/// use a `0x` prefix, so both are normalized here.
+fn parse_scheme_color(s: &str) -> anyhow::Result<RgbaColor> {
+    let s = s.trim();
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix('#'))
+        .unwrap_or(s);
+    if matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        RgbaColor::try_from(format!("#{hex}"))
+    } else {
+        RgbaColor::try_from(s.to_string())
+    }
+}
+
+const BASE16_KEYS: [&str; 16] = [
+    "base00", "base01", "base02", "base03", "base04", "base05", "base06", "base07", "base08",
+    "base09", "base0A", "base0B", "base0C", "base0D", "base0E", "base0F",
+];
+
+const ALACRITTY_ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl ColorSchemeFile {
+    /// Convert a base16 YAML scheme.  Both the classic layout, where the
+    /// `base00`..`base0F` keys are at the top level, and the newer layout
+    /// that nests them under `palette` are accepted.
+    pub fn from_base16_yaml_str(s: &str) -> anyhow::Result<Self> {
+        let value: serde_yaml::Value = serde_yaml::from_str(s)?;
+        let palette = value.get("palette").unwrap_or(&value);
+
+        let mut base = vec![];
+        for key in BASE16_KEYS {
+            let color = match palette.get(key) {
+                Some(serde_yaml::Value::String(s)) => parse_scheme_color(s),
+                // An unquoted value such as 282828 is parsed as a number
+                Some(serde_yaml::Value::Number(n)) if n.is_u64() => {
+                    parse_scheme_color(&format!("{:06}", n.as_u64().unwrap_or(0)))
+                }
+                _ => anyhow::bail!("base16 scheme is missing {key}"),
+            }
+            .with_context(|| format!("parsing {key}"))?;
+            base.push(color);
+        }
+
+        let string_field = |key: &str| {
+            value
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        };
+
+        let name = string_field("scheme")
+            .or_else(|| string_field("name"))
+            .ok_or_else(|| anyhow!("base16 scheme has no scheme name"))?;
+
+        let mut indexed = HashMap::new();
+        indexed.insert(16, base[0x9]);
+        indexed.insert(17, base[0xf]);
+        indexed.insert(18, base[0x1]);
+        indexed.insert(19, base[0x2]);
+        indexed.insert(20, base[0x4]);
+        indexed.insert(21, base[0x6]);
+
+        Ok(Self {
+            colors: Palette {
+                foreground: Some(base[0x5]),
+                background: Some(base[0x0]),
+                cursor_fg: Some(base[0x0]),
+                cursor_bg: Some(base[0x5]),
+                cursor_border: Some(base[0x5]),
+                selection_bg: Some(base[0x5]),
+                selection_fg: Some(base[0x0]),
+                ansi: Some([
+                    base[0x0], base[0x8], base[0xb], base[0xa], base[0xd], base[0xe], base[0xc],
+                    base[0x5],
+                ]),
+                brights: Some([
+                    base[0x3], base[0x8], base[0xb], base[0xa], base[0xd], base[0xe], base[0xc],
+                    base[0x7],
+                ]),
+                indexed,
+                ..Default::default()
+            },
+            metadata: ColorSchemeMetaData {
+                name: Some(name),
+                author: string_field("author"),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Convert an Alacritty theme, which defines its colors in the
+    /// `colors.primary`, `colors.normal`, `colors.bright`, `colors.cursor`
+    /// and `colors.selection` tables.  Alacritty themes don't carry a name,
+    /// so the caller is expected to derive one from the file name.
+    pub fn from_alacritty_toml_value(value: &toml::Value) -> anyhow::Result<Self> {
+        let colors = value
+            .get("colors")
+            .ok_or_else(|| anyhow!("alacritty theme has no [colors] table"))?;
+
+        let color = |table: &str, key: &str| -> anyhow::Result<Option<RgbaColor>> {
+            match colors.get(table).and_then(|t| t.get(key)) {
+                None => Ok(None),
+                Some(toml::Value::String(s)) => match s.as_str() {
+                    // These refer to the colors of the cell under the
+                    // cursor or selection, which is our default behavior
+                    "CellForeground" | "CellBackground" => Ok(None),
+                    s => parse_scheme_color(s)
+                        .map(Some)
+                        .with_context(|| format!("parsing colors.{table}.{key}")),
+                },
+                Some(other) => bail!("colors.{table}.{key}: expected a string, got {other}"),
+            }
+        };
+
+        let ansi_table = |table: &str| -> anyhow::Result<Option<[RgbaColor; 8]>> {
+            if colors.get(table).is_none() {
+                return Ok(None);
+            }
+            let mut result = [RgbaColor::default(); 8];
+            for (idx, name) in ALACRITTY_ANSI_NAMES.iter().enumerate() {
+                result[idx] = color(table, name)?
+                    .ok_or_else(|| anyhow!("alacritty theme is missing colors.{table}.{name}"))?;
+            }
+            Ok(Some(result))
+        };
+
+        let mut indexed = HashMap::new();
+        if let Some(entries) = colors.get("indexed_colors").and_then(|v| v.as_array()) {
+            for entry in entries {
+                let index = entry
+                    .get("index")
+                    .and_then(|v| v.as_integer())
+                    .and_then(|i| u8::try_from(i).ok())
+                    .ok_or_else(|| anyhow!("invalid index in colors.indexed_colors"))?;
+                let color = entry
+                    .get("color")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| anyhow!("missing color in colors.indexed_colors"))?;
+                indexed.insert(index, parse_scheme_color(color)?);
+            }
+        }
+
+        let ansi = ansi_table("normal")?;
+        anyhow::ensure!(ansi.is_some(), "alacritty theme is missing colors.normal");
+
+        Ok(Self {
+            colors: Palette {
+                foreground: color("primary", "foreground")?,
+                background: color("primary", "background")?,
+                cursor_fg: color("cursor", "text")?,
+                cursor_bg: color("cursor", "cursor")?,
+                cursor_border: color("cursor", "cursor")?,
+                selection_fg: color("selection", "text")?,
+                selection_bg: color("selection", "background")?,
+                ansi,
+                brights: ansi_table("bright")?,
+                indexed,
+                ..Default::default()
+            },
+            metadata: ColorSchemeMetaData::default(),
+        })
+    }
+
+    /// Load a scheme from a file in any of the supported formats.
+    /// `.yaml` and `.yml` files are treated as base16 schemes.
+    /// `.toml` files are treated as native schemes, unless they lack
+    /// `colors.ansi` and have the `colors.normal` table that is
+    /// characteristic of Alacritty themes.
+    pub fn load_file(path: &Path) -> anyhow::Result<(Self, ColorSchemeFormat)> {
+        let s = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => Ok((
+                Self::from_base16_yaml_str(&s).context("parsing base16 YAML")?,
+                ColorSchemeFormat::Base16,
+            )),
+            _ => {
+                let value: toml::Value = toml::from_str(&s).context("parsing TOML")?;
+                let colors = value.get("colors");
+                let is_alacritty = colors.map_or(false, |c| {
+                    c.get("ansi").is_none() && c.get("normal").is_some()
+                });
+                if is_alacritty {
+                    Ok((
+                        Self::from_alacritty_toml_value(&value)
+                            .context("parsing Alacritty theme")?,
+                        ColorSchemeFormat::Alacritty,
+                    ))
+                } else {
+                    Ok((
+                        Self::from_toml_value(&value).context("parsing TOML")?,
+                        ColorSchemeFormat::Native,
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> RgbaColor {
+        (r, g, b).into()
+    }
+
+    #[test]
+    fn base16_tomorrow_night() {
+        let scheme =
+            ColorSchemeFile::from_base16_yaml_str(include_str!("../test-data/tomorrow-night.yaml"))
+                .unwrap();
+        assert_eq!(scheme.metadata.name.as_deref(), Some("Tomorrow Night"));
+        assert_eq!(
+            scheme.metadata.author.as_deref(),
+            Some("Chris Kempson (http://chriskempson.com)")
+        );
+
+        let colors = &scheme.colors;
+        assert_eq!(colors.background, Some(rgb(0x1d, 0x1f, 0x21)));
+        assert_eq!(colors.foreground, Some(rgb(0xc5, 0xc8, 0xc6)));
+
+        let ansi = colors.ansi.unwrap();
+        assert_eq!(ansi[0], rgb(0x1d, 0x1f, 0x21));
+        assert_eq!(ansi[1], rgb(0xcc, 0x66, 0x66));
+        assert_eq!(ansi[3], rgb(0xf0, 0xc6, 0x74));
+        assert_eq!(ansi[4], rgb(0x81, 0xa2, 0xbe));
+
+        let brights = colors.brights.unwrap();
+        assert_eq!(brights[0], rgb(0x96, 0x98, 0x96));
+        assert_eq!(brights[7], rgb(0xff, 0xff, 0xff));
+
+        assert_eq!(colors.indexed.get(&16), Some(&rgb(0xde, 0x93, 0x5f)));
+        assert_eq!(colors.indexed.get(&17), Some(&rgb(0xa3, 0x68, 0x5a)));
+    }
+
+    #[test]
+    fn base16_nested_palette() {
+        let mut yaml = "system: base16\nname: Nested\npalette:\n".to_string();
+        for (idx, key) in BASE16_KEYS.iter().enumerate() {
+            yaml.push_str(&format!("  {key}: '#0000{idx:02x}'\n"));
+        }
+        let scheme = ColorSchemeFile::from_base16_yaml_str(&yaml).unwrap();
+        assert_eq!(scheme.metadata.name.as_deref(), Some("Nested"));
+        assert_eq!(scheme.colors.foreground, Some(rgb(0, 0, 5)));
+        assert_eq!(scheme.colors.ansi.unwrap()[1], rgb(0, 0, 8));
+    }
+
+    #[test]
+    fn alacritty_dracula() {
+        let value: toml::Value =
+            toml::from_str(include_str!("../test-data/dracula-alacritty.toml")).unwrap();
+        let scheme = ColorSchemeFile::from_alacritty_toml_value(&value).unwrap();
+        assert_eq!(scheme.metadata.name, None);
+
+        let colors = &scheme.colors;
+        assert_eq!(colors.background, Some(rgb(0x28, 0x2a, 0x36)));
+        assert_eq!(colors.foreground, Some(rgb(0xf8, 0xf8, 0xf2)));
+        assert_eq!(colors.cursor_bg, Some(rgb(0xf8, 0xf8, 0xf2)));
+        assert_eq!(colors.cursor_fg, Some(rgb(0x28, 0x2a, 0x36)));
+        assert_eq!(colors.selection_bg, Some(rgb(0x44, 0x47, 0x5a)));
+        // "CellForeground" defers to our default behavior
+        assert_eq!(colors.selection_fg, None);
+
+        let ansi = colors.ansi.unwrap();
+        assert_eq!(ansi[0], rgb(0x21, 0x22, 0x2c));
+        assert_eq!(ansi[1], rgb(0xff, 0x55, 0x55));
+        assert_eq!(ansi[5], rgb(0xff, 0x79, 0xc6));
+
+        let brights = colors.brights.unwrap();
+        assert_eq!(brights[0], rgb(0x62, 0x72, 0xa4));
+        assert_eq!(brights[7], rgb(0xff, 0xff, 0xff));
+
+        assert_eq!(colors.indexed.get(&16), Some(&rgb(0xff, 0xb8, 0x6c)));
+    }
+
+    #[test]
+    fn legacy_hex_prefix() {
+        assert_eq!(
+            parse_scheme_color("0x282a36").unwrap(),
+            rgb(0x28, 0x2a, 0x36)
+        );
+        assert_eq!(parse_scheme_color("282a36").unwrap(), rgb(0x28, 0x2a, 0x36));
+        assert_eq!(
+            parse_scheme_color("#282a36").unwrap(),
+            rgb(0x28, 0x2a, 0x36)
+        );
+    }
+}
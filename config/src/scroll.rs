@@ -1,7 +1,8 @@
 use crate::units::Dimension;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct ScrollConfig {
     #[dynamic(
         default = "default_scrollback_lines",
@@ -16,6 +17,32 @@ pub struct ScrollConfig {
     pub scroll_to_bottom_on_input: bool,
     #[dynamic(default = "default_alternate_buffer_wheel_scroll_speed")]
     pub alternate_buffer_wheel_scroll_speed: u8,
+    /// When unset, the wheel sends `alternate_buffer_wheel_scroll_speed`
+    /// arrow keys
+    #[dynamic(default)]
+    pub alternate_buffer_wheel_behavior: Option<AlternateBufferWheelBehavior>,
+    /// Whether the wheel can scroll a few rows past the top of the
+    /// scrollback, from where the viewport eases back
+    #[dynamic(default)]
+    pub overscroll: bool,
+}
+
+/// What the mouse wheel does while the alternate screen is active and
+/// the application hasn't enabled mouse reporting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum AlternateBufferWheelBehavior {
+    SendArrows { speed: u8 },
+    ScrollHistory,
+    PassThroughMouse,
+}
+
+impl ScrollConfig {
+    pub fn alternate_buffer_wheel_behavior(&self) -> AlternateBufferWheelBehavior {
+        self.alternate_buffer_wheel_behavior
+            .unwrap_or(AlternateBufferWheelBehavior::SendArrows {
+                speed: self.alternate_buffer_wheel_scroll_speed,
+            })
+    }
 }
 
 impl Default for ScrollConfig {
@@ -26,6 +53,8 @@ impl Default for ScrollConfig {
             min_scroll_bar_height: default_half_cell(),
             scroll_to_bottom_on_input: default_true(),
             alternate_buffer_wheel_scroll_speed: default_alternate_buffer_wheel_scroll_speed(),
+            alternate_buffer_wheel_behavior: None,
+            overscroll: false,
         }
     }
 }
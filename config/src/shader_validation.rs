@@ -0,0 +1,180 @@
+//! Checks the post-processing shaders named by `gpu.webgpu_shader` and
+//! `gpu.webgpu_shader_effects` while the configuration is loaded, so that
+//! problems are reported as configuration warnings with the place in the
+//! WGSL source that caused them, rather than when the GUI builds the
+//! render pipeline.
+use anyhow::anyhow;
+use naga::{
+    AddressSpace, ImageClass, ImageDimension, Module, ResourceBinding, ScalarKind, ShaderStage,
+    SourceLocation, Span, TypeInner,
+};
+use std::path::Path;
+
+/// The entry points that the post-processing pipeline is built from
+const ENTRY_POINTS: [(&str, ShaderStage); 2] = [
+    ("vs_main", ShaderStage::Vertex),
+    ("fs_main", ShaderStage::Fragment),
+];
+
+/// Read and check the post-processing shader at `path`
+pub fn validate_postprocess_shader(path: &Path) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("failed to read {}: {}", path.display(), err))?;
+    validate_postprocess_shader_source(&source, path)
+}
+
+/// Check that `source` is valid WGSL that can be used with the
+/// post-processing bind group layout.  `path` is used to describe
+/// the location of any problem.
+pub fn validate_postprocess_shader_source(source: &str, path: &Path) -> anyhow::Result<()> {
+    let at = |location: Option<SourceLocation>| match location {
+        Some(loc) => format!(
+            "{}:{}:{}",
+            path.display(),
+            loc.line_number,
+            loc.line_position
+        ),
+        None => path.display().to_string(),
+    };
+
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|err| anyhow!("{}: {}", at(err.location(source)), err.message()))?;
+
+    let mut validator = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    );
+    if let Err(err) = validator.validate(&module) {
+        let mut message = err.as_inner().to_string();
+        let mut cause = std::error::Error::source(err.as_inner());
+        while let Some(err) = cause {
+            message.push_str(&format!(": {}", err));
+            cause = std::error::Error::source(err);
+        }
+        return Err(anyhow!("{}: {}", at(err.location(source)), message));
+    }
+
+    for (name, stage) in ENTRY_POINTS {
+        let found = module
+            .entry_points
+            .iter()
+            .any(|entry| entry.name == name && entry.stage == stage);
+        if !found {
+            return Err(anyhow!(
+                "{}: the shader must define a {:?} entry point named `{}`",
+                at(None),
+                stage,
+                name
+            ));
+        }
+    }
+
+    for (handle, var) in module.global_variables.iter() {
+        let binding = match &var.binding {
+            Some(binding) => binding,
+            None => continue,
+        };
+        if let Err(message) = check_binding(&module, binding, var.space, var.ty) {
+            let span = module.global_variables.get_span(handle);
+            let location = if span == Span::default() {
+                None
+            } else {
+                Some(span.location(source))
+            };
+            return Err(anyhow!(
+                "{}: `{}` @group({}) @binding({}) {}",
+                at(location),
+                var.name.as_deref().unwrap_or("?"),
+                binding.group,
+                binding.binding,
+                message
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a resource binding against the post-processing bind group
+/// layout: a uniform buffer at binding 0, a filterable 2D float texture
+/// at binding 1 and a filtering sampler at binding 2, all in group 0
+fn check_binding(
+    module: &Module,
+    binding: &ResourceBinding,
+    space: AddressSpace,
+    ty: naga::Handle<naga::Type>,
+) -> Result<(), &'static str> {
+    if binding.group != 0 {
+        return Err("is not part of the post-processing layout, which only has group 0");
+    }
+    let inner = &module.types[ty].inner;
+    match binding.binding {
+        0 => match space {
+            AddressSpace::Uniform => Ok(()),
+            _ => Err("must be a var<uniform>"),
+        },
+        1 => match inner {
+            TypeInner::Image {
+                dim: ImageDimension::D2,
+                arrayed: false,
+                class:
+                    ImageClass::Sampled {
+                        kind: ScalarKind::Float,
+                        multi: false,
+                    },
+            } => Ok(()),
+            _ => Err("must be a texture_2d<f32>"),
+        },
+        2 => match inner {
+            TypeInner::Sampler { comparison: false } => Ok(()),
+            _ => Err("must be a sampler"),
+        },
+        _ => Err("is not part of the post-processing layout, which only has bindings 0 to 2"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(source: &str) -> String {
+        match validate_postprocess_shader_source(source, Path::new("test.wgsl")) {
+            Ok(()) => String::new(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_shader() {
+        assert_eq!(
+            check(include_str!("../test-data/shaders/valid.wgsl")),
+            String::new()
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let err = check(include_str!("../test-data/shaders/syntax-error.wgsl"));
+        assert!(err.starts_with("test.wgsl:19:"), "{}", err);
+    }
+
+    #[test]
+    fn wrong_entry_point() {
+        let err = check(include_str!("../test-data/shaders/wrong-entry-point.wgsl"));
+        assert_eq!(
+            err,
+            "test.wgsl: the shader must define a Fragment entry point named `fs_main`"
+        );
+    }
+
+    #[test]
+    fn wrong_binding_type() {
+        let err = check(include_str!("../test-data/shaders/wrong-binding-type.wgsl"));
+        assert!(err.starts_with("test.wgsl:8:"), "{}", err);
+        assert!(
+            err.ends_with("`input_texture` @group(0) @binding(1) must be a texture_2d<f32>"),
+            "{}",
+            err
+        );
+    }
+}
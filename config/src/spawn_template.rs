@@ -0,0 +1,214 @@
+//! Expansion of `${variable}` references in the `default_prog` and
+//! `default_cwd` of a domain.  The templates are expanded at spawn time,
+//! so that they can refer to the pane that the spawn was requested from.
+use anyhow::{anyhow, bail, Context};
+
+/// The values that a template can refer to, other than environment
+/// variables
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpawnContext {
+    /// The current working directory of the pane that requested
+    /// the spawn, if there was one and its cwd is known
+    pub pane_cwd: Option<String>,
+    /// The workspace that the new pane will belong to
+    pub workspace: Option<String>,
+    /// The name of the domain that we are spawning into
+    pub domain_name: String,
+}
+
+impl SpawnContext {
+    /// Expands the references in `template`.
+    /// `${pane_cwd}`, `${workspace}` and `${domain_name}` expand to the
+    /// corresponding field; any other name is looked up in the
+    /// environment of the phaedra process.
+    pub fn expand(&self, template: &str) -> anyhow::Result<String> {
+        expand_template(template, |name| self.lookup(name))
+            .with_context(|| format!("expanding `{template}` for domain {}", self.domain_name))
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<String> {
+        match name {
+            "pane_cwd" => self.pane_cwd.clone().ok_or_else(|| {
+                anyhow!(
+                    "${{pane_cwd}} is not available: there is no current pane, \
+                     or its cwd is unknown"
+                )
+            }),
+            "workspace" => self
+                .workspace
+                .clone()
+                .ok_or_else(|| anyhow!("${{workspace}} is not available")),
+            "domain_name" => Ok(self.domain_name.clone()),
+            name => std::env::var(name).map_err(|_| {
+                anyhow!(
+                    "${{{name}}} is not known. Use one of ${{pane_cwd}}, \
+                     ${{workspace}}, ${{domain_name}} or the name of an \
+                     environment variable"
+                )
+            }),
+        }
+    }
+}
+
+/// Replaces each `${name}` in `template` with the result of `lookup(name)`.
+/// `$$` produces a literal `$`; a `$` that isn't followed by `{` or `$`
+/// is left as it is.
+pub fn expand_template(
+    template: &str,
+    lookup: impl Fn(&str) -> anyhow::Result<String>,
+) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(template.len());
+    let mut chars = template.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some((_, '$')) => {
+                chars.next();
+                result.push('$');
+            }
+            Some(&(start, '{')) => {
+                chars.next();
+                let name_start = start + 1;
+                let end = loop {
+                    match chars.next() {
+                        Some((idx, '}')) => break idx,
+                        Some((_, c)) if c.is_ascii_alphanumeric() || c == '_' => {}
+                        Some((_, c)) => bail!(
+                            "invalid character {c:?} in the variable name that starts at `{}`",
+                            &template[start - 1..]
+                        ),
+                        None => bail!("missing `}}` after `{}`", &template[start - 1..]),
+                    }
+                };
+                let name = &template[name_start..end];
+                if name.is_empty() {
+                    bail!("empty variable name `${{}}`");
+                }
+                result.push_str(&lookup(name)?);
+            }
+            _ => result.push('$'),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+    use portable_pty::CommandBuilder;
+    use std::path::PathBuf;
+
+    fn context() -> SpawnContext {
+        SpawnContext {
+            pane_cwd: Some("/home/user/project".to_string()),
+            workspace: Some("work".to_string()),
+            domain_name: "devhost".to_string(),
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        let ctx = context();
+        assert_eq!(
+            ctx.expand("${pane_cwd}/src").unwrap(),
+            "/home/user/project/src"
+        );
+        assert_eq!(
+            ctx.expand("tmux new -As ${workspace}-${domain_name}")
+                .unwrap(),
+            "tmux new -As work-devhost"
+        );
+        assert_eq!(ctx.expand("no variables").unwrap(), "no variables");
+    }
+
+    #[test]
+    fn expands_environment() {
+        let lookup = |name: &str| match name {
+            "HOME" => Ok("/home/user".to_string()),
+            _ => bail!("unknown {name}"),
+        };
+        assert_eq!(
+            expand_template("${HOME}/.local", lookup).unwrap(),
+            "/home/user/.local"
+        );
+        std::env::set_var("PHAEDRA_SPAWN_TEMPLATE_TEST", "value");
+        assert_eq!(
+            context().expand("${PHAEDRA_SPAWN_TEMPLATE_TEST}").unwrap(),
+            "value"
+        );
+    }
+
+    #[test]
+    fn escapes_dollar() {
+        let ctx = context();
+        assert_eq!(ctx.expand("cost: $$5").unwrap(), "cost: $5");
+        assert_eq!(ctx.expand("$${workspace}").unwrap(), "${workspace}");
+        assert_eq!(ctx.expand("echo $HOME $").unwrap(), "echo $HOME $");
+    }
+
+    #[test]
+    fn unknown_variables_are_errors() {
+        let ctx = context();
+        let err = ctx.expand("${PHAEDRA_NO_SUCH_VARIABLE}").unwrap_err();
+        assert!(format!("{err:#}").contains("${PHAEDRA_NO_SUCH_VARIABLE} is not known"));
+
+        let ctx = SpawnContext {
+            pane_cwd: None,
+            ..context()
+        };
+        let err = ctx.expand("${pane_cwd}").unwrap_err();
+        assert!(format!("{err:#}").contains("${pane_cwd} is not available"));
+    }
+
+    #[test]
+    fn build_prog_expands_domain_defaults() {
+        let config = Config::default_config();
+        let ctx = context();
+        let prog = vec![
+            "tmux".to_string(),
+            "new".to_string(),
+            "-As".to_string(),
+            "${workspace}".to_string(),
+        ];
+        let cwd = PathBuf::from("${pane_cwd}/src");
+
+        let cmd = config
+            .build_prog(None, Some(&prog), Some(&cwd), Some(&ctx))
+            .unwrap();
+        assert_eq!(cmd.get_argv(), &["tmux", "new", "-As", "work"]);
+        assert_eq!(cmd.get_cwd().unwrap(), "/home/user/project/src");
+
+        // An explicit command and cwd are left alone
+        let mut cmd = CommandBuilder::new("${workspace}");
+        cmd.cwd("${pane_cwd}");
+        config
+            .apply_cmd_defaults(&mut cmd, Some(&prog), Some(&cwd), Some(&ctx))
+            .unwrap();
+        assert_eq!(cmd.get_argv(), &["${workspace}"]);
+        assert_eq!(cmd.get_cwd().unwrap(), "${pane_cwd}");
+
+        // Without a context, the defaults are used as they are
+        let cmd = config.build_prog(None, Some(&prog), None, None).unwrap();
+        assert_eq!(cmd.get_argv(), &["tmux", "new", "-As", "${workspace}"]);
+
+        let no_pane = SpawnContext {
+            pane_cwd: None,
+            ..context()
+        };
+        assert!(config
+            .build_prog(None, Some(&prog), Some(&cwd), Some(&no_pane))
+            .is_err());
+    }
+
+    #[test]
+    fn malformed_references_are_errors() {
+        let ctx = context();
+        assert!(ctx.expand("${workspace").is_err());
+        assert!(ctx.expand("${}").is_err());
+        assert!(ctx.expand("${a b}").is_err());
+    }
+}
@@ -30,6 +30,24 @@ impl Default for SshMultiplexing {
     }
 }
 
+/// Controls whether the panes spawned into a plain ssh domain share
+/// a single ssh connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum SshConnectionMultiplex {
+    /// Each pane gets its own ssh connection
+    NewConnectionPerPane,
+    /// Panes are opened as additional channels on an existing
+    /// connection; a new connection is made when the existing
+    /// connections cannot accept any more channels
+    SharedConnection,
+}
+
+impl Default for SshConnectionMultiplex {
+    fn default() -> Self {
+        Self::SharedConnection
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum Shell {
     /// Unknown command shell: no assumptions can be made
@@ -100,10 +118,32 @@ pub struct SshDomain {
     #[dynamic(default)]
     pub ssh_option: HashMap<String, String>,
 
+    /// The program to run in new panes, rather than the login shell.
+    /// Each argument may refer to `${pane_cwd}`, `${workspace}`,
+    /// `${domain_name}` and environment variables; see `SpawnContext`.
     pub default_prog: Option<Vec<String>>,
 
+    /// The directory on the remote host in which to start new panes,
+    /// when the spawn doesn't specify one.  Expanded in the same way
+    /// as `default_prog`.
+    pub default_cwd: Option<String>,
+
     #[dynamic(default)]
     pub assume_shell: Shell,
+
+    /// How long to wait for the TCP connection to the remote host
+    /// to be established.  Equivalent to the ssh_config `ConnectTimeout`
+    /// option.
+    pub connection_timeout: Option<Duration>,
+
+    /// How often to send keepalive messages to the remote host.
+    /// Equivalent to the ssh_config `ServerAliveInterval` option.
+    pub keepalive_interval: Option<Duration>,
+
+    /// Whether panes share an ssh connection.  Only applies when
+    /// `multiplexing = "None"`.
+    #[dynamic(default)]
+    pub multiplex: SshConnectionMultiplex,
 }
 impl_lua_conversion_dynamic!(SshDomain);
 
@@ -183,3 +223,34 @@ impl FromStr for SshParameters {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_domain(lua_expr: &str) -> SshDomain {
+        let lua = mlua::Lua::new();
+        let value: mlua::Value = lua.load(lua_expr).eval().unwrap();
+        luahelper::from_lua(value).unwrap()
+    }
+
+    #[test]
+    fn connection_options_default() {
+        let dom = parse_domain("{ name = 'my.server', remote_address = '192.168.1.1' }");
+        assert_eq!(dom.connection_timeout, None);
+        assert_eq!(dom.keepalive_interval, None);
+        assert_eq!(dom.multiplex, SshConnectionMultiplex::SharedConnection);
+    }
+
+    #[test]
+    fn connection_options() {
+        let dom = parse_domain(
+            "{ name = 'my.server', remote_address = '192.168.1.1', \
+               connection_timeout = 5, keepalive_interval = 0.5, \
+               multiplex = 'NewConnectionPerPane' }",
+        );
+        assert_eq!(dom.connection_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(dom.keepalive_interval, Some(Duration::from_millis(500)));
+        assert_eq!(dom.multiplex, SshConnectionMultiplex::NewConnectionPerPane);
+    }
+}
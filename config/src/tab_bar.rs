@@ -1,7 +1,9 @@
+use crate::bell::EasingFunction;
 use crate::color::TabBarStyle;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct TabBarConfig {
     #[dynamic(default)]
     pub tab_bar_style: TabBarStyle,
@@ -29,6 +31,16 @@ pub struct TabBarConfig {
     pub hide_tab_bar_if_only_one_tab: bool,
     #[dynamic(default)]
     pub switch_to_last_active_tab_when_closing_tab: bool,
+    #[dynamic(default)]
+    pub tooltip_format: Option<String>,
+    #[dynamic(default)]
+    pub hover_fade_duration_ms: u64,
+    #[dynamic(default)]
+    pub hover_fade_function: EasingFunction,
+    /// Shown before the title of a tab whose active pane is using the
+    /// alternate screen; empty to show nothing
+    #[dynamic(default)]
+    pub alt_screen_indicator: String,
 }
 
 impl Default for TabBarConfig {
@@ -47,6 +59,10 @@ impl Default for TabBarConfig {
             tab_max_width: default_tab_max_width(),
             hide_tab_bar_if_only_one_tab: false,
             switch_to_last_active_tab_when_closing_tab: false,
+            tooltip_format: None,
+            hover_fade_duration_ms: 0,
+            hover_fade_function: EasingFunction::default(),
+            alt_screen_indicator: String::new(),
         }
     }
 }
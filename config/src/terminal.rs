@@ -1,8 +1,8 @@
 //! Bridge our gui config into the terminal crate configuration
 
-use crate::{configuration, ConfigHandle, NewlineCanon};
+use crate::{configuration, AlternateBufferWheelBehavior, ConfigHandle, NewlineCanon};
 use crate::observers::*;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use termwiz::cell::UnicodeVersion;
 use phaedra_term::color::ColorPalette;
 use phaedra_term::config::BidiMode;
@@ -10,7 +10,7 @@ use phaedra_term::config::BidiMode;
 #[derive(Debug)]
 pub struct TermConfig {
     config: Mutex<Option<ConfigHandle>>,
-    client_palette: Mutex<Option<ColorPalette>>,
+    client_palette: Mutex<Option<Arc<ColorPalette>>>,
 }
 
 impl TermConfig {
@@ -33,7 +33,10 @@ impl TermConfig {
     }
 
     pub fn set_client_palette(&self, palette: ColorPalette) {
-        self.client_palette.lock().unwrap().replace(palette);
+        self.client_palette
+            .lock()
+            .unwrap()
+            .replace(palette.intern());
     }
 
     fn configuration(&self) -> ConfigHandle {
@@ -57,20 +60,35 @@ impl phaedra_term::TerminalConfiguration for TermConfig {
         self.configuration().key_input().enable_csi_u_key_encoding
     }
 
-    fn color_palette(&self) -> ColorPalette {
+    fn color_palette(&self) -> Arc<ColorPalette> {
         let client_palette = self.client_palette.lock().unwrap();
         if let Some(p) = client_palette.as_ref().cloned() {
             return p;
         }
-        let config = self.configuration();
-
-        config.color_config().resolved_palette.clone().into()
+        self.configuration().color_palette()
     }
 
     fn alternate_buffer_wheel_scroll_speed(&self) -> u8 {
         self.configuration().scroll().alternate_buffer_wheel_scroll_speed
     }
 
+    fn alternate_buffer_wheel_behavior(
+        &self,
+    ) -> phaedra_term::config::AlternateBufferWheelBehavior {
+        let behavior = self.configuration().scroll().alternate_buffer_wheel_behavior();
+        match behavior {
+            AlternateBufferWheelBehavior::SendArrows { speed } => {
+                phaedra_term::config::AlternateBufferWheelBehavior::SendArrows { speed }
+            }
+            AlternateBufferWheelBehavior::ScrollHistory => {
+                phaedra_term::config::AlternateBufferWheelBehavior::ScrollHistory
+            }
+            AlternateBufferWheelBehavior::PassThroughMouse => {
+                phaedra_term::config::AlternateBufferWheelBehavior::PassThroughMouse
+            }
+        }
+    }
+
     fn enq_answerback(&self) -> String {
         configuration().terminal_features().enq_answerback.clone()
     }
@@ -1,8 +1,9 @@
 use crate::config::NotificationHandling;
-use phaedra_dynamic::{FromDynamic, ToDynamic};
+use phaedra_config_derive::ConfigMeta;
+use phaedra_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, Value};
 use termwiz::hyperlink;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct TerminalFeatureConfig {
     #[dynamic(default = "default_true")]
     pub enable_kitty_graphics: bool,
@@ -20,8 +21,18 @@ pub struct TerminalFeatureConfig {
     pub enq_answerback: String,
     #[dynamic(default)]
     pub notification_handling: NotificationHandling,
-    #[dynamic(default = "default_hyperlink_rules")]
+    /// A rule that can't be used, such as one whose regex is invalid,
+    /// is skipped with a warning rather than failing the configuration
+    #[dynamic(default = "default_hyperlink_rules", try_from = "HyperlinkRules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
+    /// PasteAsOsc52 refuses to send a clipboard that holds more than
+    /// this many bytes
+    #[dynamic(default = "default_osc52_paste_max_bytes")]
+    pub osc52_paste_max_bytes: usize,
+    /// When set, PasteAsOsc52 splits its payload across as many escape
+    /// sequences as are needed for none to be longer than this
+    #[dynamic(default)]
+    pub osc52_paste_max_sequence_length: Option<usize>,
 }
 
 impl Default for TerminalFeatureConfig {
@@ -36,7 +47,44 @@ impl Default for TerminalFeatureConfig {
             enq_answerback: default_enq_answerback(),
             notification_handling: NotificationHandling::default(),
             hyperlink_rules: default_hyperlink_rules(),
+            osc52_paste_max_bytes: default_osc52_paste_max_bytes(),
+            osc52_paste_max_sequence_length: None,
+        }
+    }
+}
+
+/// The hyperlink rules as they are read from the configuration
+struct HyperlinkRules(Vec<hyperlink::Rule>);
+
+impl FromDynamic for HyperlinkRules {
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, phaedra_dynamic::Error> {
+        let mut rules = vec![];
+        for (idx, value) in Vec::<Value>::from_dynamic(value, options)?
+            .iter()
+            .enumerate()
+        {
+            match hyperlink::Rule::from_dynamic(value, options) {
+                Ok(rule) => rules.push(rule),
+                Err(err) => {
+                    phaedra_dynamic::Error::warn(phaedra_dynamic::Warning::InvalidValue {
+                        struct_name: "TerminalFeatureConfig",
+                        field: format!("hyperlink_rules[{}]", idx + 1),
+                        message: format!("{:#}; the rule will be ignored", err),
+                        location: None,
+                    });
+                }
+            }
         }
+        Ok(Self(rules))
+    }
+}
+
+impl From<HyperlinkRules> for Vec<hyperlink::Rule> {
+    fn from(rules: HyperlinkRules) -> Self {
+        rules.0
     }
 }
 
@@ -44,6 +92,10 @@ fn default_true() -> bool {
     true
 }
 
+fn default_osc52_paste_max_bytes() -> usize {
+    1024 * 1024
+}
+
 fn default_enq_answerback() -> String {
     String::new()
 }
@@ -58,3 +110,37 @@ pub(crate) fn default_hyperlink_rules() -> Vec<hyperlink::Rule> {
         hyperlink::Rule::new(r"\b\w+@[\w-]+(\.[\w-]+)+\b", "mailto:$0").unwrap(),
     ]
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn invalid_hyperlink_rules_are_skipped_with_a_warning() {
+        let value = vec![
+            [("regex", r"\bT\d+\b"), ("format", "https://example.com/$0")],
+            [("regex", r"(unclosed"), ("format", "$0")],
+        ]
+        .into_iter()
+        .map(|rule| {
+            rule.into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<std::collections::BTreeMap<String, String>>()
+        })
+        .collect::<Vec<_>>()
+        .to_dynamic();
+
+        let (rules, warnings) = phaedra_dynamic::Error::capture_warnings(|| {
+            HyperlinkRules::from_dynamic(&value, FromDynamicOptions::default())
+        });
+        let rules: Vec<hyperlink::Rule> = rules.unwrap().into();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].format, "https://example.com/$0");
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].to_string().contains("hyperlink_rules[2]"),
+            "{}",
+            warnings[0]
+        );
+    }
+}
@@ -1,13 +1,14 @@
 use crate::bell::EasingFunction;
-use crate::cell::CellWidth;
+use crate::cell::{CellWidth, EmojiPresentationOverride};
 use crate::config::NewlineCanon;
 use crate::default_one_point_oh;
 use crate::default_one_point_oh_f64;
 use crate::units::Dimension;
 use phaedra_bidi::ParagraphDirectionHint;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct TextConfig {
     #[dynamic(
         default = "default_one_point_oh_f64",
@@ -22,6 +23,8 @@ pub struct TextConfig {
     pub underline_position: Option<Dimension>,
     #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
     pub strikethrough_position: Option<Dimension>,
+    #[dynamic(try_from = "crate::units::OptPixelUnit", default)]
+    pub overline_position: Option<Dimension>,
     #[dynamic(default = "default_true")]
     pub custom_block_glyphs: bool,
     #[dynamic(default = "default_true")]
@@ -62,6 +65,8 @@ pub struct TextConfig {
     pub treat_east_asian_ambiguous_width_as_wide: bool,
     #[dynamic(default)]
     pub cell_widths: Option<Vec<CellWidth>>,
+    #[dynamic(default)]
+    pub emoji_presentation_overrides: Vec<EmojiPresentationOverride>,
 }
 
 impl Default for TextConfig {
@@ -72,6 +77,7 @@ impl Default for TextConfig {
             underline_thickness: None,
             underline_position: None,
             strikethrough_position: None,
+            overline_position: None,
             custom_block_glyphs: default_true(),
             anti_alias_custom_block_glyphs: default_true(),
             text_background_opacity: default_one_point_oh(),
@@ -92,6 +98,7 @@ impl Default for TextConfig {
             unicode_version: default_unicode_version(),
             treat_east_asian_ambiguous_width_as_wide: false,
             cell_widths: None,
+            emoji_presentation_overrides: vec![],
         }
     }
 }
@@ -299,6 +299,62 @@ mod test {
         );
     }
 
+    fn parse(value: Value) -> Result<Dimension, String> {
+        DefaultUnit::Pixels.from_dynamic_impl(&value)
+    }
+
+    fn parse_str(s: &str) -> Result<Dimension, String> {
+        parse(Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_str("5%"), Ok(Dimension::Percent(0.05)));
+        assert_eq!(parse_str("12.5 %"), Ok(Dimension::Percent(0.125)));
+        assert_eq!(parse_str("150%"), Ok(Dimension::Percent(1.5)));
+        assert_eq!(parse_str("10px"), Ok(Dimension::Pixels(10.)));
+        assert_eq!(parse_str("72pt"), Ok(Dimension::Points(72.)));
+        assert_eq!(parse_str("0.5cell"), Ok(Dimension::Cells(0.5)));
+        assert_eq!(parse_str("3"), Ok(Dimension::Pixels(3.)));
+        assert_eq!(parse(Value::I64(4)), Ok(Dimension::Pixels(4.)));
+        assert!(parse_str("5em").is_err());
+        assert!(parse_str("%").is_err());
+    }
+
+    #[test]
+    fn percent_of_window() {
+        // The same window at increasing scale factors: percentages and
+        // points scale with it, pixels do not
+        for scale in [1., 1.5, 2.] {
+            let h_context = DimensionContext {
+                dpi: 96. * scale,
+                pixel_max: 1000. * scale,
+                pixel_cell: 8. * scale,
+            };
+            let v_context = DimensionContext {
+                dpi: 96. * scale,
+                pixel_max: 600. * scale,
+                pixel_cell: 16. * scale,
+            };
+            let five_percent = Dimension::Percent(0.05);
+            assert_eq!(five_percent.evaluate_as_pixels(h_context), 50. * scale);
+            assert_eq!(five_percent.evaluate_as_pixels(v_context), 30. * scale);
+            assert_eq!(
+                Dimension::Points(72.).evaluate_as_pixels(h_context),
+                96. * scale
+            );
+            assert_eq!(Dimension::Pixels(10.).evaluate_as_pixels(h_context), 10.);
+        }
+
+        // Fractional pixels are rounded down
+        let context = DimensionContext {
+            dpi: 96.,
+            pixel_max: 1001.,
+            pixel_cell: 8.,
+        };
+        assert_eq!(Dimension::Percent(0.05).evaluate_as_pixels(context), 50.);
+    }
+
     #[test]
     fn main() {
         assert_eq!(
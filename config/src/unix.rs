@@ -62,6 +62,12 @@ pub struct UnixDomain {
     /// instead.
     #[dynamic(default)]
     pub overlay_lag_indicator: bool,
+
+    /// If true, attach to the server as a read-only client: pane
+    /// output and layout changes are received, but input and
+    /// requests that would change the mux are rejected by the server
+    #[dynamic(default)]
+    pub read_only: bool,
 }
 
 impl Default for UnixDomain {
@@ -78,6 +84,7 @@ impl Default for UnixDomain {
             local_echo_threshold_ms: None,
             proxy_command: None,
             overlay_lag_indicator: false,
+            read_only: false,
         }
     }
 }
@@ -1,6 +1,7 @@
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct UpdateConfig {
     #[dynamic(default = "default_check_for_updates")]
     pub check_for_updates: bool,
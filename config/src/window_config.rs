@@ -2,13 +2,17 @@ use crate::background::SystemBackdrop;
 use crate::color::{IntegratedTitleButtonColor, RgbaColor, WindowFrameConfig};
 use crate::config::{WindowCloseConfirmation, WindowContentAlignment, WindowPadding};
 use crate::default_win32_acrylic_accent_color;
+use crate::font_config::FontConfig;
+use crate::window_overrides::WindowOverride;
+use phaedra_config_derive::ConfigMeta;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 use phaedra_input_types::{
     IntegratedTitleButton, IntegratedTitleButtonAlignment, IntegratedTitleButtonStyle,
     WindowDecorations,
 };
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, FromDynamic, ToDynamic, ConfigMeta)]
 pub struct WindowConfig {
     #[dynamic(default)]
     pub window_decorations: WindowDecorations,
@@ -50,10 +54,100 @@ pub struct WindowConfig {
     pub quit_when_all_windows_are_closed: bool,
     #[dynamic(default)]
     pub enable_zwlr_output_manager: bool,
+    #[dynamic(default = "default_true")]
+    pub frozen_pane_indicator: bool,
     #[dynamic(default)]
     pub win32_system_backdrop: SystemBackdrop,
     #[dynamic(default = "default_win32_acrylic_accent_color")]
     pub win32_acrylic_accent_color: RgbaColor,
+    #[dynamic(default)]
+    pub window_overrides: Vec<WindowOverride>,
+    #[dynamic(default)]
+    pub pane_tooltip_format: Option<String>,
+    #[dynamic(default = "default_tooltip_delay_ms")]
+    pub tooltip_delay_ms: u64,
+    #[dynamic(default)]
+    pub dpi_by_monitor: HashMap<String, f32>,
+    #[dynamic(default)]
+    pub dpi_rounding: DpiRounding,
+    #[dynamic(default)]
+    pub animation_enabled: AnimationEnabled,
+}
+
+/// Whether the GUI animates the blinking cursor and text, the visual
+/// bell, color transitions, animated shaders and the return from an
+/// overscroll.  Without animation, each of these shows its final state
+/// at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, FromDynamic, ToDynamic)]
+pub enum AnimationEnabled {
+    /// Animate unless the system asks for reduced motion
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl AnimationEnabled {
+    /// Returns true if animations should play.  `prefers_reduced_motion`
+    /// queries the system preference, and is only called for Auto.
+    pub fn resolve(self, prefers_reduced_motion: impl FnOnce() -> bool) -> bool {
+        match self {
+            Self::Auto => !prefers_reduced_motion(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// How the scale factor of a monitor, relative to a standard density
+/// display, is rounded when computing the DPI of a window on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, FromDynamic, ToDynamic)]
+pub enum DpiRounding {
+    /// Round to the nearest whole scale factor
+    Nearest,
+    /// Round down to a whole scale factor
+    Floor,
+    /// Use the scale factor as it is
+    #[default]
+    None,
+}
+
+impl DpiRounding {
+    /// Applies the policy to `dpi`, the DPI of a monitor, where
+    /// `base_dpi` is the DPI of a standard density display
+    pub fn apply(self, dpi: f64, base_dpi: f64) -> f64 {
+        let scale = dpi / base_dpi;
+        let scale = match self {
+            Self::Nearest => scale.round(),
+            Self::Floor => scale.floor(),
+            Self::None => return dpi,
+        };
+        // Never round a high density display down to nothing
+        scale.max(1.0) * base_dpi
+    }
+}
+
+/// Resolves the DPI of a window on the monitor named `monitor`, whose
+/// own DPI is `monitor_dpi`.  An override for the monitor in
+/// `dpi_by_monitor` or `dpi_by_screen` takes precedence over `dpi`;
+/// only the DPI of the monitor itself is subject to `dpi_rounding`.
+pub fn resolve_monitor_dpi(
+    window_config: &WindowConfig,
+    font_config: &FontConfig,
+    monitor: &str,
+    monitor_dpi: f64,
+    base_dpi: f64,
+) -> f64 {
+    if let Some(dpi) = window_config.dpi_by_monitor.get(monitor) {
+        return *dpi as f64;
+    }
+    if let Some(dpi) = font_config.dpi_by_screen.get(monitor) {
+        return *dpi;
+    }
+    if let Some(dpi) = font_config.dpi {
+        return dpi;
+    }
+    window_config.dpi_rounding.apply(monitor_dpi, base_dpi)
 }
 
 impl Default for WindowConfig {
@@ -79,8 +173,15 @@ impl Default for WindowConfig {
             unzoom_on_switch_pane: default_true(),
             quit_when_all_windows_are_closed: default_true(),
             enable_zwlr_output_manager: false,
+            frozen_pane_indicator: default_true(),
             win32_system_backdrop: SystemBackdrop::default(),
             win32_acrylic_accent_color: default_win32_acrylic_accent_color(),
+            window_overrides: vec![],
+            pane_tooltip_format: None,
+            tooltip_delay_ms: default_tooltip_delay_ms(),
+            dpi_by_monitor: HashMap::new(),
+            dpi_rounding: DpiRounding::default(),
+            animation_enabled: AnimationEnabled::default(),
         }
     }
 }
@@ -113,6 +214,10 @@ fn default_tiling_desktop_environments() -> Vec<String> {
     .collect()
 }
 
+fn default_tooltip_delay_ms() -> u64 {
+    500
+}
+
 fn default_true() -> bool {
     true
 }
@@ -124,3 +229,52 @@ fn validate_row_or_col(value: &u16) -> Result<(), String> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dpi_rounding_rounds_the_scale_factor() {
+        assert_eq!(DpiRounding::None.apply(144.0, 96.0), 144.0);
+        assert_eq!(DpiRounding::Nearest.apply(144.0, 96.0), 192.0);
+        assert_eq!(DpiRounding::Nearest.apply(132.0, 96.0), 96.0);
+        assert_eq!(DpiRounding::Floor.apply(180.0, 96.0), 96.0);
+        assert_eq!(DpiRounding::Floor.apply(192.0, 96.0), 192.0);
+        // A display that is less dense than standard isn't rounded to zero
+        assert_eq!(DpiRounding::Floor.apply(72.0, 96.0), 96.0);
+    }
+
+    #[test]
+    fn auto_animation_follows_the_system_preference() {
+        assert!(AnimationEnabled::Auto.resolve(|| false));
+        assert!(!AnimationEnabled::Auto.resolve(|| true));
+        assert!(AnimationEnabled::Always.resolve(|| true));
+        // The system is only asked when it matters
+        assert!(!AnimationEnabled::Never.resolve(|| panic!("queried the system")));
+    }
+
+    #[test]
+    fn monitor_overrides_take_precedence() {
+        let mut window_config = WindowConfig::default();
+        window_config.dpi_rounding = DpiRounding::Nearest;
+        window_config
+            .dpi_by_monitor
+            .insert("DP-1".to_string(), 110.0);
+        let mut font_config = FontConfig::default();
+        font_config
+            .dpi_by_screen
+            .insert("HDMI-1".to_string(), 120.0);
+
+        let resolve = |font_config: &FontConfig, monitor| {
+            resolve_monitor_dpi(&window_config, font_config, monitor, 144.0, 96.0)
+        };
+        assert_eq!(resolve(&font_config, "DP-1"), 110.0);
+        assert_eq!(resolve(&font_config, "HDMI-1"), 120.0);
+        assert_eq!(resolve(&font_config, "eDP-1"), 192.0);
+
+        font_config.dpi = Some(100.0);
+        assert_eq!(resolve(&font_config, "DP-1"), 110.0);
+        assert_eq!(resolve(&font_config, "eDP-1"), 100.0);
+    }
+}
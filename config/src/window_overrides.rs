@@ -0,0 +1,244 @@
+//! Per-window config overrides, selected by matching properties of a
+//! window against the entries in `window_config.window_overrides`.
+use phaedra_dynamic::{FromDynamic, ToDynamic, Value};
+use regex::Regex;
+
+/// Applies `config` to the windows that match all of the criteria
+/// that are specified.  An entry that specifies no criteria applies
+/// to every window.
+#[derive(Debug, Clone, Default, PartialEq, FromDynamic, ToDynamic)]
+pub struct WindowOverride {
+    /// Matches windows in this workspace
+    #[dynamic(default)]
+    pub workspace: Option<String>,
+    /// A regex that is matched against the title of the mux window,
+    /// as assigned by `window:set_title()`
+    #[dynamic(default, validate = "validate_title")]
+    pub title: Option<String>,
+    /// Matches windows whose class, as set by `--class`, is this
+    #[dynamic(default)]
+    pub window_class: Option<String>,
+    /// Matches the mux window with this id
+    #[dynamic(default)]
+    pub window_id: Option<u64>,
+    /// Config fields to set, in the same form as accepted by
+    /// `window:set_config_overrides()`
+    #[dynamic(default)]
+    pub config: Value,
+}
+
+/// The properties of a window that a `WindowOverride` can match
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowProperties {
+    pub window_id: u64,
+    pub workspace: String,
+    pub title: String,
+    pub window_class: String,
+}
+
+fn validate_title(title: &Option<String>) -> Result<(), String> {
+    match title {
+        Some(title) => Regex::new(title)
+            .map(|_| ())
+            .map_err(|err| format!("window_overrides title {title:?}: {err}")),
+        None => Ok(()),
+    }
+}
+
+impl WindowOverride {
+    pub fn matches(&self, props: &WindowProperties) -> bool {
+        if let Some(workspace) = &self.workspace {
+            if *workspace != props.workspace {
+                return false;
+            }
+        }
+        if let Some(class) = &self.window_class {
+            if *class != props.window_class {
+                return false;
+            }
+        }
+        if let Some(id) = self.window_id {
+            if id != props.window_id {
+                return false;
+            }
+        }
+        if let Some(title) = &self.title {
+            match Regex::new(title) {
+                Ok(re) if re.is_match(&props.title) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Recursively merge `overlay` into `base`.  Objects are merged key
+/// by key; any other value in `overlay` replaces the one in `base`.
+fn merge_into(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_into(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+/// Compute the overrides that apply to the window described by `props`.
+/// The `config` of each matching entry is layered on in list order, so
+/// that later entries take precedence over earlier ones, and then
+/// `explicit`, the overrides assigned by `window:set_config_overrides()`,
+/// is layered on top of those.
+pub fn resolve_window_overrides(
+    entries: &[WindowOverride],
+    props: &WindowProperties,
+    explicit: &Value,
+) -> Value {
+    let mut result = Value::Null;
+    for entry in entries.iter().filter(|entry| entry.matches(props)) {
+        merge_into(&mut result, &entry.config);
+    }
+    merge_into(&mut result, explicit);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use phaedra_dynamic::Object;
+
+    fn obj(fields: &[(&str, Value)]) -> Value {
+        Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (Value::String(k.to_string()), v.clone()))
+                .collect::<Object>(),
+        )
+    }
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn props() -> WindowProperties {
+        WindowProperties {
+            window_id: 3,
+            workspace: "work".to_string(),
+            title: "work: editor".to_string(),
+            window_class: "org.phaedra.term".to_string(),
+        }
+    }
+
+    #[test]
+    fn matchers() {
+        let all = WindowOverride::default();
+        assert!(all.matches(&props()));
+
+        let workspace = WindowOverride {
+            workspace: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert!(workspace.matches(&props()));
+        assert!(!workspace.matches(&WindowProperties {
+            workspace: "personal".to_string(),
+            ..props()
+        }));
+
+        let title = WindowOverride {
+            title: Some("^work:".to_string()),
+            ..Default::default()
+        };
+        assert!(title.matches(&props()));
+        assert!(!title.matches(&WindowProperties {
+            title: "personal: work".to_string(),
+            ..props()
+        }));
+
+        let id = WindowOverride {
+            window_id: Some(4),
+            ..Default::default()
+        };
+        assert!(!id.matches(&props()));
+
+        // Every specified criterion must match
+        let both = WindowOverride {
+            workspace: Some("work".to_string()),
+            window_class: Some("other".to_string()),
+            ..Default::default()
+        };
+        assert!(!both.matches(&props()));
+
+        let invalid = WindowOverride {
+            title: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(!invalid.matches(&props()));
+        assert!(validate_title(&invalid.title).is_err());
+    }
+
+    #[test]
+    fn later_entries_and_explicit_overrides_take_precedence() {
+        let entries = vec![
+            WindowOverride {
+                config: obj(&[
+                    ("color_config", obj(&[("color_scheme", string("Everyone"))])),
+                    ("font_size", Value::U64(12)),
+                ]),
+                ..Default::default()
+            },
+            WindowOverride {
+                workspace: Some("personal".to_string()),
+                config: obj(&[("font_size", Value::U64(20))]),
+                ..Default::default()
+            },
+            WindowOverride {
+                workspace: Some("work".to_string()),
+                config: obj(&[
+                    ("color_config", obj(&[("color_scheme", string("Work"))])),
+                    ("window_config", obj(&[("initial_rows", Value::U64(40))])),
+                ]),
+                ..Default::default()
+            },
+            WindowOverride {
+                window_id: Some(3),
+                config: obj(&[("window_config", obj(&[("initial_cols", Value::U64(100))]))]),
+                ..Default::default()
+            },
+        ];
+
+        let resolved = resolve_window_overrides(&entries, &props(), &Value::Null);
+        assert_eq!(
+            resolved,
+            obj(&[
+                ("color_config", obj(&[("color_scheme", string("Work"))])),
+                ("font_size", Value::U64(12)),
+                (
+                    "window_config",
+                    obj(&[
+                        ("initial_cols", Value::U64(100)),
+                        ("initial_rows", Value::U64(40)),
+                    ])
+                ),
+            ])
+        );
+
+        let explicit = obj(&[("color_config", obj(&[("color_scheme", string("Mine"))]))]);
+        let resolved = resolve_window_overrides(&entries, &props(), &explicit);
+        let color_config = match &resolved {
+            Value::Object(resolved) => resolved.get_by_str("color_config").cloned(),
+            _ => None,
+        };
+        assert_eq!(color_config, Some(obj(&[("color_scheme", string("Mine"))])));
+
+        // Nothing matches and nothing is explicitly set
+        let resolved = resolve_window_overrides(&entries[1..2], &props(), &Value::Null);
+        assert_eq!(resolved, Value::Null);
+    }
+}
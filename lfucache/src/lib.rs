@@ -125,6 +125,19 @@ impl<K: Hash + Eq + Clone + Debug, V, S: Default + BuildHasher> LfuCache<K, V, S
         self.len
     }
 
+    /// The maximum number of entries, as determined by the config
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Evict the least frequently used entries until at most `len`
+    /// remain.  The capacity is unchanged, so the cache may grow again.
+    pub fn trim_to(&mut self, len: usize) {
+        while self.len > len {
+            self.evict_one();
+        }
+    }
+
     /// Grow the hash buckets in the pursuit of reducing potential
     /// key collisions in any given bucket
     fn grow_hash(&mut self) {
@@ -728,6 +741,27 @@ mod test {
         );
     }
 
+    #[test]
+    fn trim_keeps_most_frequent() {
+        let mut cache = LfuCacheU64::with_capacity(8);
+        for i in 0..8 {
+            cache.put(i, i);
+            for _ in 0..i {
+                cache.get(&i);
+            }
+        }
+
+        cache.trim_to(3);
+        k9::assert_equal!(cache.len(), 3);
+        k9::assert_equal!(cache.capacity(), 8);
+        let mut keys: Vec<u64> = frequency_order(&cache).iter().map(|e| *e.key).collect();
+        keys.sort();
+        k9::assert_equal!(keys, vec![5, 6, 7]);
+
+        cache.trim_to(10);
+        k9::assert_equal!(cache.len(), 3);
+    }
+
     #[test]
     fn basic() {
         let mut cache = LfuCacheU64::<&'static str>::with_capacity(8);
@@ -5,6 +5,7 @@ use luahelper::impl_lua_conversion_dynamic;
 use mlua::UserDataRef;
 use mux::domain::{DomainId, SplitSource};
 use mux::pane::{Pane, PaneId};
+use mux::session::{restore_saved_session_layout, session_layout_path, SessionLayout};
 use mux::tab::{SplitDirection, SplitRequest, SplitSize, Tab, TabId};
 use mux::window::{Window, WindowId};
 use mux::Mux;
@@ -158,6 +159,30 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    mux_mod.set(
+        "save_session_layout",
+        lua.create_function(|_, _: ()| {
+            let mux = get_mux()?;
+            let path = session_layout_path();
+            SessionLayout::capture(&mux)
+                .save(&path)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        })?,
+    )?;
+
+    mux_mod.set(
+        "restore_session_layout",
+        lua.create_async_function(|_, _: ()| async move {
+            let window_ids = restore_saved_session_layout()
+                .await
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+            Ok(window_ids
+                .into_iter()
+                .map(MuxWindow)
+                .collect::<Vec<MuxWindow>>())
+        })?,
+    )?;
+
     mux_mod.set(
         "set_default_domain",
         lua.create_function(|_, domain: UserDataRef<MuxDomain>| {
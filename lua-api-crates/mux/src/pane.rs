@@ -320,6 +320,18 @@ impl UserData for MuxPane {
             Ok(zones)
         });
 
+        methods.add_method("get_command_zones", |lua, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+
+            let commands = pane
+                .get_command_zones()
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+
+            let commands = to_lua(lua, commands)?;
+            Ok(commands)
+        });
+
         methods.add_method(
             "get_semantic_zone_at",
             |lua, this, (x, y): (usize, StableRowIndex)| {
@@ -433,6 +445,60 @@ impl UserData for MuxPane {
             let pane = this.resolve(&mux)?;
             Ok(pane.tty_name())
         });
+
+        methods.add_method("capture", |lua, this, opts: Option<CaptureOptions>| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let opts = opts.unwrap_or_default();
+            let region = pane.capture_region(opts.range(&pane), opts.include_styles);
+            to_lua(lua, region)
+        });
+    }
+}
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct CaptureOptions {
+    /// The first stable row to capture
+    #[dynamic(default)]
+    start_row: Option<StableRowIndex>,
+    /// One past the last stable row to capture
+    #[dynamic(default)]
+    end_row: Option<StableRowIndex>,
+    /// When start_row is not specified, how many lines above
+    /// end_row to capture.  Defaults to the viewport height.
+    #[dynamic(default)]
+    nlines: Option<usize>,
+    #[dynamic(default = "default_include_styles")]
+    include_styles: bool,
+}
+impl_lua_conversion_dynamic!(CaptureOptions);
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            start_row: None,
+            end_row: None,
+            nlines: None,
+            include_styles: default_include_styles(),
+        }
+    }
+}
+
+fn default_include_styles() -> bool {
+    true
+}
+
+impl CaptureOptions {
+    fn range(&self, pane: &Arc<dyn Pane>) -> std::ops::Range<StableRowIndex> {
+        let dims = pane.get_dimensions();
+        let end_row = self
+            .end_row
+            .unwrap_or(dims.physical_top + dims.viewport_rows as StableRowIndex);
+        let start_row = self.start_row.unwrap_or_else(|| {
+            let nlines = self.nlines.unwrap_or(dims.viewport_rows);
+            end_row.saturating_sub(nlines as StableRowIndex)
+        });
+        start_row..end_row
     }
 }
 
@@ -3,6 +3,7 @@ use config::keyassignment::PaneDirection;
 use super::*;
 use luahelper::mlua::Value;
 use luahelper::{from_lua, to_lua};
+use phaedra_term::color::RgbColor;
 use std::sync::Arc;
 
 #[derive(Clone, Copy, Debug)]
@@ -46,6 +47,25 @@ impl UserData for MuxTab {
             let tab = this.resolve(&mux)?;
             Ok(tab.set_title(&title))
         });
+        methods.add_method("get_color", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let tab = this.resolve(&mux)?;
+            Ok(tab.get_color().map(|color| color.to_rgb_string()))
+        });
+        methods.add_method("set_color", |_, this, color: Option<String>| {
+            let mux = get_mux()?;
+            let tab = this.resolve(&mux)?;
+            let color = match color {
+                Some(color) => {
+                    Some(RgbColor::from_named_or_rgb_string(&color).ok_or_else(|| {
+                        mlua::Error::external(format!("{color} is not a valid color"))
+                    })?)
+                }
+                None => None,
+            };
+            tab.set_color(color);
+            Ok(())
+        });
         methods.add_method("active_pane", |_, this, _: ()| {
             let mux = get_mux()?;
             let tab = this.resolve(&mux)?;
@@ -91,7 +91,10 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
 
     window_mod.set(
         "get_appearance",
-        lua.create_function(|_, _: ()| {
+        lua.create_function(|lua, _: ()| {
+            // Let the config know that it depends on the appearance,
+            // so that it is reloaded when the appearance changes
+            lua.set_named_registry_value("phaedra-consulted-appearance", true)?;
             Ok(match Connection::get() {
                 Some(conn) => conn.get_appearance().to_string(),
                 None => {
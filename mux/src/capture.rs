@@ -0,0 +1,209 @@
+//! Serializable snapshots of pane content, including styling, for use
+//! by external tooling such as session loggers.
+use serde::{Deserialize, Serialize};
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use termwiz::surface::Line;
+use phaedra_term::color::ColorAttribute;
+use phaedra_term::StableRowIndex;
+
+/// A color as it was specified by the application, before it was
+/// resolved against the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CapturedColor {
+    Default,
+    PaletteIndex(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl From<ColorAttribute> for CapturedColor {
+    fn from(color: ColorAttribute) -> Self {
+        match color {
+            ColorAttribute::TrueColorWithPaletteFallback(c, _)
+            | ColorAttribute::TrueColorWithDefaultFallback(c) => {
+                let (r, g, b, _) = c.to_srgb_u8();
+                Self::Rgb(r, g, b)
+            }
+            ColorAttribute::PaletteIndex(idx) => Self::PaletteIndex(idx),
+            ColorAttribute::Default => Self::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedStyle {
+    pub foreground: CapturedColor,
+    pub background: CapturedColor,
+    pub intensity: Intensity,
+    pub underline: Underline,
+    pub italic: bool,
+    pub reverse: bool,
+    pub strikethrough: bool,
+    pub hyperlink: Option<String>,
+}
+
+impl From<&CellAttributes> for CapturedStyle {
+    fn from(attrs: &CellAttributes) -> Self {
+        Self {
+            foreground: attrs.foreground().into(),
+            background: attrs.background().into(),
+            intensity: attrs.intensity(),
+            underline: attrs.underline(),
+            italic: attrs.italic(),
+            reverse: attrs.reverse(),
+            strikethrough: attrs.strikethrough(),
+            hyperlink: attrs.hyperlink().map(|link| link.uri().to_string()),
+        }
+    }
+}
+
+/// A run of adjacent cells that share the same style
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedSpan {
+    pub text: String,
+    /// None when the capture was made without styles
+    pub style: Option<CapturedStyle>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedLine {
+    pub row: StableRowIndex,
+    pub spans: Vec<CapturedSpan>,
+    /// true if the line continues onto the next row
+    pub wrapped: bool,
+}
+
+impl CapturedLine {
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedRegion {
+    pub lines: Vec<CapturedLine>,
+}
+
+impl CapturedRegion {
+    /// Build a capture from the lines returned by `Pane::get_lines`.
+    /// Trailing blank cells are omitted from each line, unless they
+    /// are styled with a non-default background and styles are being
+    /// captured.
+    pub fn from_lines(first_row: StableRowIndex, lines: &[Line], include_styles: bool) -> Self {
+        let lines = lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| CapturedLine {
+                row: first_row + idx as StableRowIndex,
+                spans: capture_spans(line, include_styles),
+                wrapped: line.last_cell_was_wrapped(),
+            })
+            .collect();
+        Self { lines }
+    }
+}
+
+fn capture_spans(line: &Line, include_styles: bool) -> Vec<CapturedSpan> {
+    let cells: Vec<_> = line.visible_cells().collect();
+    let is_blank = |cell: &termwiz::surface::line::CellRef| {
+        cell.str().trim().is_empty()
+            && (!include_styles || cell.attrs().background() == ColorAttribute::Default)
+    };
+    let len = cells
+        .iter()
+        .rposition(|cell| !is_blank(cell))
+        .map_or(0, |idx| idx + 1);
+
+    let mut spans: Vec<CapturedSpan> = vec![];
+    for cell in &cells[..len] {
+        let style = if include_styles {
+            Some(CapturedStyle::from(cell.attrs()))
+        } else {
+            None
+        };
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(cell.str()),
+            _ => spans.push(CapturedSpan {
+                text: cell.str().to_string(),
+                style,
+            }),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use termwiz::color::SrgbaTuple;
+    use termwiz::hyperlink::Hyperlink;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn round_trip(region: &CapturedRegion) -> CapturedRegion {
+        let mut encoded = vec![];
+        region
+            .serialize(&mut varbincode::Serializer::new(&mut encoded))
+            .unwrap();
+        CapturedRegion::deserialize(&mut varbincode::Deserializer::new(&mut encoded.as_slice()))
+            .unwrap()
+    }
+
+    fn synthetic_screen() -> Vec<Line> {
+        let plain = CellAttributes::default();
+        let mut bold_red = CellAttributes::default();
+        bold_red
+            .set_intensity(Intensity::Bold)
+            .set_foreground(ColorAttribute::PaletteIndex(1));
+        let mut link = CellAttributes::default();
+        link.set_underline(Underline::Curly)
+            .set_background(ColorAttribute::TrueColorWithDefaultFallback(SrgbaTuple(
+                1., 0., 0., 1.,
+            )))
+            .set_hyperlink(Some(Arc::new(Hyperlink::new("https://example.com"))));
+
+        let mut line = Line::from_text("hello ", &plain, SEQ_ZERO, None);
+        line.append_line(Line::from_text("bold", &bold_red, SEQ_ZERO, None), SEQ_ZERO);
+        line.append_line(Line::from_text(" link", &link, SEQ_ZERO, None), SEQ_ZERO);
+        line.append_line(Line::from_text("    ", &plain, SEQ_ZERO, None), SEQ_ZERO);
+
+        vec![line, Line::from_text("second   ", &plain, SEQ_ZERO, None)]
+    }
+
+    #[test]
+    fn capture_with_styles() {
+        let region = CapturedRegion::from_lines(10, &synthetic_screen(), true);
+        assert_eq!(region.lines.len(), 2);
+
+        let first = &region.lines[0];
+        assert_eq!(first.row, 10);
+        assert_eq!(first.text(), "hello bold link");
+        let texts: Vec<&str> = first.spans.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["hello ", "bold", " link"]);
+
+        let bold = first.spans[1].style.as_ref().unwrap();
+        assert_eq!(bold.intensity, Intensity::Bold);
+        assert_eq!(bold.foreground, CapturedColor::PaletteIndex(1));
+        assert_eq!(bold.hyperlink, None);
+
+        let link = first.spans[2].style.as_ref().unwrap();
+        assert_eq!(link.underline, Underline::Curly);
+        assert_eq!(link.background, CapturedColor::Rgb(255, 0, 0));
+        assert_eq!(link.hyperlink.as_deref(), Some("https://example.com"));
+
+        assert_eq!(region.lines[1].row, 11);
+        assert_eq!(region.lines[1].text(), "second");
+
+        assert_eq!(round_trip(&region), region);
+    }
+
+    #[test]
+    fn capture_without_styles() {
+        let region = CapturedRegion::from_lines(0, &synthetic_screen(), false);
+        let first = &region.lines[0];
+        assert_eq!(first.spans.len(), 1);
+        assert_eq!(first.spans[0].style, None);
+        assert_eq!(first.text(), "hello bold link");
+
+        assert_eq!(round_trip(&region), region);
+    }
+}
@@ -395,10 +395,20 @@ impl LocalDomain {
 
         let mut cmd = match command {
             Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd, default_prog, config.launch().default_cwd.as_ref());
+                config.apply_cmd_defaults(
+                    &mut cmd,
+                    default_prog,
+                    config.launch().default_cwd.as_ref(),
+                    None,
+                )?;
                 cmd
             }
-            None => config.build_prog(None, default_prog, config.launch().default_cwd.as_ref())?,
+            None => config.build_prog(
+                None,
+                default_prog,
+                config.launch().default_cwd.as_ref(),
+                None,
+            )?,
         };
         if let Some(dir) = command_dir {
             cmd.cwd(dir);
@@ -479,6 +489,16 @@ impl portable_pty::MasterPty for FailedSpawnPty {
     }
 }
 
+/// Returns the argv of `command`, or None if it runs the default program
+fn spawn_argv(command: Option<&CommandBuilder>) -> Option<Vec<String>> {
+    command.filter(|cmd| !cmd.is_default_prog()).map(|cmd| {
+        cmd.get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    })
+}
+
 /// A fake child process for the case where the spawn attempt
 /// failed. It reports as immediately terminated.
 #[derive(Debug)]
@@ -516,6 +536,7 @@ impl Domain for LocalDomain {
         command_dir: Option<String>,
     ) -> anyhow::Result<Arc<dyn Pane>> {
         let pane_id = alloc_pane_id();
+        let argv = spawn_argv(command.as_ref());
         let cmd = self
             .build_command(command, command_dir, pane_id)
             .await
@@ -528,15 +549,12 @@ impl Domain for LocalDomain {
         let command_line = cmd
             .as_unix_command_line()
             .unwrap_or_else(|err| format!("error rendering command line: {:?}", err));
-        let command_description = format!(
-            "\"{}\" in domain \"{}\"",
-            if command_line.is_empty() {
-                cmd.get_shell()
-            } else {
-                command_line
-            },
-            self.name
-        );
+        let command_line = if command_line.is_empty() {
+            cmd.get_shell()
+        } else {
+            command_line
+        };
+        let command_description = format!("\"{}\" in domain \"{}\"", command_line, self.name);
         let child_result = pair.slave.spawn_command(cmd);
         let mut writer = WriterWrapper::new(pair.master.take_writer()?);
 
@@ -552,31 +570,39 @@ impl Domain for LocalDomain {
         }
 
         let pane: Arc<dyn Pane> = match child_result {
-            Ok(child) => Arc::new(LocalPane::new(
-                pane_id,
-                terminal,
-                child,
-                pair.master,
-                Box::new(writer),
-                self.id,
-                command_description,
-            )),
-            Err(err) => {
-                // Show the error to the user in the new pane
-                write!(writer, "{err:#}").ok();
-
-                // and return a dummy pane that has exited
-                Arc::new(LocalPane::new(
+            Ok(child) => Arc::new(
+                LocalPane::new(
                     pane_id,
                     terminal,
-                    Box::new(FailedProcessSpawn {}),
-                    Box::new(FailedSpawnPty {
-                        inner: Mutex::new(pair.master),
-                    }),
+                    child,
+                    pair.master,
                     Box::new(writer),
                     self.id,
                     command_description,
-                ))
+                )
+                .with_command_line(command_line)
+                .with_spawn_argv(argv),
+            ),
+            Err(err) => {
+                // Show the error to the user in the new pane
+                write!(writer, "{err:#}").ok();
+
+                // and return a dummy pane that has exited
+                Arc::new(
+                    LocalPane::new(
+                        pane_id,
+                        terminal,
+                        Box::new(FailedProcessSpawn {}),
+                        Box::new(FailedSpawnPty {
+                            inner: Mutex::new(pair.master),
+                        }),
+                        Box::new(writer),
+                        self.id,
+                        command_description,
+                    )
+                    .with_command_line(command_line)
+                    .with_spawn_argv(argv),
+                )
             }
         };
 
@@ -0,0 +1,421 @@
+//! Per-pane logs of the output of the program in a pane, for debugging
+//! programs that misbehave in the terminal.
+//!
+//! While a pane is being logged, each chunk of bytes read from its pty
+//! is written to the log, framed separately from the actions that the
+//! escape sequence parser made of it, and both are stamped with the
+//! time since logging started.  The log is a text file:
+//!
+//! ```text
+//! phaedra-escape-log 1 rows=24 cols=80
+//! 1052 raw 1b5b316d68656c6c6f
+//! 1052 action CSI(Sgr(Intensity(Bold)))
+//! 1052 action Print('h')
+//! ```
+//!
+//! Logging stops by itself when the log reaches `MAX_LOG_BYTES`.
+//! `EscapeLog::replay` feeds the raw bytes of a log into a fresh terminal of the
+//! logged size and captures the resulting screen; it doesn't know
+//! about any resizes that happened while the pane was being logged.
+use crate::capture::CapturedRegion;
+use crate::pane::PaneId;
+use anyhow::{anyhow, Context};
+use parking_lot::Mutex;
+use phaedra_term::{Terminal, TerminalSize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::escape::Action;
+
+const HEADER: &str = "phaedra-escape-log 1";
+
+/// The size at which a log stops by itself
+pub const MAX_LOG_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Writes the framed log of a single pane
+pub struct EscapeLogWriter<W: Write> {
+    out: W,
+    started: Instant,
+    written: u64,
+    limit: u64,
+}
+
+impl<W: Write> EscapeLogWriter<W> {
+    /// Starts a log of a pane that is `rows` by `cols` cells, which
+    /// will stop when it reaches `limit` bytes
+    pub fn new(mut out: W, rows: usize, cols: usize, limit: u64) -> std::io::Result<Self> {
+        let header = format!("{HEADER} rows={rows} cols={cols}\n");
+        out.write_all(header.as_bytes())?;
+        Ok(Self {
+            out,
+            started: Instant::now(),
+            written: header.len() as u64,
+            limit,
+        })
+    }
+
+    /// Logs a chunk of output and the actions that were parsed from
+    /// it.  Returns false, having noted that in the log, if the chunk
+    /// would take the log past its limit, in which case nothing more
+    /// should be recorded.
+    pub fn record(&mut self, bytes: &[u8], actions: &[Action]) -> std::io::Result<bool> {
+        let elapsed = self.started.elapsed().as_micros();
+
+        let mut frame = format!("{elapsed} raw ");
+        for b in bytes {
+            frame.push_str(&format!("{b:02x}"));
+        }
+        frame.push('\n');
+        for action in actions {
+            frame.push_str(&format!("{elapsed} action {action:?}\n"));
+        }
+
+        if self.written + frame.len() as u64 > self.limit {
+            self.out
+                .write_all(format!("{elapsed} stopped size limit reached\n").as_bytes())?;
+            self.out.flush()?;
+            return Ok(false);
+        }
+
+        self.out.write_all(frame.as_bytes())?;
+        self.written += frame.len() as u64;
+        Ok(true)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+struct ActiveLog {
+    path: PathBuf,
+    writer: EscapeLogWriter<BufWriter<std::fs::File>>,
+}
+
+lazy_static::lazy_static! {
+    static ref LOGS: Mutex<HashMap<PaneId, ActiveLog>> = Mutex::new(HashMap::new());
+}
+
+/// The number of panes being logged, so that the reader threads of
+/// the panes that aren't can skip the registry
+static NUM_ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if any pane is being logged
+pub fn any_active() -> bool {
+    NUM_ACTIVE.load(Ordering::Relaxed) > 0
+}
+
+pub fn is_logging(pane_id: PaneId) -> bool {
+    any_active() && LOGS.lock().contains_key(&pane_id)
+}
+
+/// Returns the path that the log of `pane_id` is written to when no
+/// path is given
+pub fn default_path(pane_id: PaneId) -> PathBuf {
+    config::RUNTIME_DIR.join(format!(
+        "escape-log-{}-pane-{pane_id}.log",
+        std::process::id()
+    ))
+}
+
+/// Starts logging the output of `pane_id`, which is `rows` by `cols`
+/// cells, to `path`, replacing any log already in progress for it
+pub fn start(pane_id: PaneId, path: &Path, rows: usize, cols: usize) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("creating {}", parent.display()))?;
+    }
+    let file =
+        std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let writer = EscapeLogWriter::new(BufWriter::new(file), rows, cols, MAX_LOG_BYTES)
+        .with_context(|| format!("writing {}", path.display()))?;
+
+    let previous = LOGS.lock().insert(
+        pane_id,
+        ActiveLog {
+            path: path.to_path_buf(),
+            writer,
+        },
+    );
+    match previous {
+        Some(mut previous) => {
+            previous.writer.flush().ok();
+        }
+        None => {
+            NUM_ACTIVE.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    log::info!("logging the output of pane {pane_id} to {}", path.display());
+    Ok(())
+}
+
+/// Stops logging the output of `pane_id`, returning the path of the
+/// log if it was being logged
+pub fn stop(pane_id: PaneId) -> Option<PathBuf> {
+    if !any_active() {
+        return None;
+    }
+    let mut log = LOGS.lock().remove(&pane_id)?;
+    NUM_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    if let Err(err) = log.writer.flush() {
+        log::error!("writing {}: {err:#}", log.path.display());
+    }
+    log::info!(
+        "stopped logging the output of pane {pane_id} to {}",
+        log.path.display()
+    );
+    Some(log.path)
+}
+
+/// Stops logging `pane_id` if it is being logged, or else starts
+/// logging it to `path`, or to `default_path`.  Returns the path of
+/// the log that was started or stopped, and whether it was started.
+pub fn toggle(
+    pane_id: PaneId,
+    path: Option<PathBuf>,
+    rows: usize,
+    cols: usize,
+) -> anyhow::Result<(PathBuf, bool)> {
+    if let Some(path) = stop(pane_id) {
+        return Ok((path, false));
+    }
+    let path = path.unwrap_or_else(|| default_path(pane_id));
+    start(pane_id, &path, rows, cols)?;
+    Ok((path, true))
+}
+
+/// Records a chunk of the output of `pane_id`, and the actions that
+/// were parsed from it, if the pane is being logged
+pub fn record(pane_id: PaneId, bytes: &[u8], actions: &[Action]) {
+    if !any_active() {
+        return;
+    }
+    let mut logs = LOGS.lock();
+    let log = match logs.get_mut(&pane_id) {
+        Some(log) => log,
+        None => return,
+    };
+    let keep_going = match log.writer.record(bytes, actions) {
+        Ok(keep_going) => keep_going,
+        Err(err) => {
+            log::error!("writing {}: {err:#}", log.path.display());
+            false
+        }
+    };
+    if !keep_going {
+        let log = logs.remove(&pane_id).expect("log was just found");
+        NUM_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+        log::warn!(
+            "stopped logging the output of pane {pane_id} to {}, which reached its size limit",
+            log.path.display()
+        );
+    }
+}
+
+/// A chunk of output read back from a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoggedChunk {
+    /// The time since logging started
+    pub elapsed: Duration,
+    pub bytes: Vec<u8>,
+    /// The actions that were parsed from the chunk, as they were
+    /// written to the log
+    pub actions: Vec<String>,
+}
+
+/// A log read back from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapeLog {
+    pub rows: usize,
+    pub cols: usize,
+    pub chunks: Vec<LoggedChunk>,
+}
+
+fn parse_header(line: &str) -> anyhow::Result<(usize, usize)> {
+    let fields = line
+        .strip_prefix(HEADER)
+        .ok_or_else(|| anyhow!("not a phaedra escape log"))?;
+    let mut rows = None;
+    let mut cols = None;
+    for field in fields.split_whitespace() {
+        match field.split_once('=') {
+            Some(("rows", value)) => rows = Some(value.parse()?),
+            Some(("cols", value)) => cols = Some(value.parse()?),
+            _ => {}
+        }
+    }
+    match (rows, cols) {
+        (Some(rows), Some(cols)) => Ok((rows, cols)),
+        _ => anyhow::bail!("the log header has no size: {line}"),
+    }
+}
+
+fn parse_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        anyhow::bail!("odd number of hex digits");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| {
+            hex.get(idx..idx + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| anyhow!("invalid hex digits at {idx}"))
+        })
+        .collect()
+}
+
+/// Reads a log written by `EscapeLogWriter`
+pub fn read_log<R: BufRead>(reader: R) -> anyhow::Result<EscapeLog> {
+    let mut lines = reader.lines();
+    let header = lines.next().ok_or_else(|| anyhow!("the log is empty"))??;
+    let (rows, cols) = parse_header(&header)?;
+
+    let mut chunks: Vec<LoggedChunk> = vec![];
+    for (idx, line) in lines.enumerate() {
+        let line = line?;
+        let line_number = idx + 2;
+        let mut fields = line.splitn(3, ' ');
+        let (elapsed, kind, rest) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(elapsed), Some(kind), rest) => (elapsed, kind, rest.unwrap_or("")),
+            _ => anyhow::bail!("line {line_number} is malformed"),
+        };
+        let elapsed = Duration::from_micros(
+            elapsed
+                .parse()
+                .with_context(|| format!("line {line_number} has no timestamp"))?,
+        );
+        match kind {
+            "raw" => chunks.push(LoggedChunk {
+                elapsed,
+                bytes: parse_hex(rest).with_context(|| format!("line {line_number}"))?,
+                actions: vec![],
+            }),
+            "action" => chunks
+                .last_mut()
+                .ok_or_else(|| anyhow!("line {line_number} has an action before any output"))?
+                .actions
+                .push(rest.to_string()),
+            _ => {}
+        }
+    }
+
+    Ok(EscapeLog { rows, cols, chunks })
+}
+
+/// Returns a capture of the visible screen of `terminal`
+pub fn capture_screen(terminal: &Terminal) -> CapturedRegion {
+    let screen = terminal.screen();
+    let first = screen.phys_row(0);
+    let lines = screen.lines_in_phys_range(first..first + screen.physical_rows);
+    CapturedRegion::from_lines(screen.visible_row_to_stable_row(0), &lines, true)
+}
+
+impl EscapeLog {
+    /// Feeds the logged output into a fresh terminal of the logged
+    /// size, and returns a capture of its screen
+    pub fn replay(&self) -> CapturedRegion {
+        let mut terminal = Terminal::new(
+            TerminalSize {
+                rows: self.rows,
+                cols: self.cols,
+                ..TerminalSize::default()
+            },
+            Arc::new(config::TermConfig::new()),
+            "phaedra",
+            config::phaedra_version(),
+            Box::new(Vec::new()),
+        );
+        for chunk in &self.chunks {
+            terminal.advance_bytes(&chunk.bytes);
+        }
+        capture_screen(&terminal)
+    }
+}
+
+/// Replays the log in the file at `path`; see `EscapeLog::replay`
+pub fn replay_file(path: &Path) -> anyhow::Result<CapturedRegion> {
+    let file = std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let log =
+        read_log(BufReader::new(file)).with_context(|| format!("reading {}", path.display()))?;
+    Ok(log.replay())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::escape::parser::Parser;
+
+    const OUTPUT: &[&[u8]] = &[
+        b"\x1b[1mhello\x1b[0m\r\n",
+        b"\x1b[31mred\x1b[",
+        b"0m\r\n\x1b[2;5Hmoved",
+        b"\x1b]0;title\x07 \xe2\x9c\x94",
+    ];
+
+    fn write_log(limit: u64) -> Vec<u8> {
+        let mut parser = Parser::new();
+        let mut writer = EscapeLogWriter::new(vec![], 5, 20, limit).unwrap();
+        for chunk in OUTPUT {
+            let actions = parser.parse_as_vec(chunk);
+            if !writer.record(chunk, &actions).unwrap() {
+                break;
+            }
+        }
+        writer.out
+    }
+
+    #[test]
+    fn replay_matches_direct_parsing() {
+        let log = read_log(write_log(MAX_LOG_BYTES).as_slice()).unwrap();
+        assert_eq!((log.rows, log.cols), (5, 20));
+        assert_eq!(log.chunks.len(), OUTPUT.len());
+        for (chunk, output) in log.chunks.iter().zip(OUTPUT) {
+            assert_eq!(chunk.bytes, *output);
+        }
+        // The escape split across chunks is parsed with the second one
+        assert_eq!(log.chunks[0].actions.len(), 8);
+        assert_eq!(log.chunks[1].actions.len(), 4);
+        assert_eq!(log.chunks[1].actions[1], "Print('r')");
+        assert_eq!(log.chunks[2].actions[0], "CSI(Sgr(Reset))");
+
+        let mut terminal = Terminal::new(
+            TerminalSize {
+                rows: 5,
+                cols: 20,
+                ..TerminalSize::default()
+            },
+            Arc::new(config::TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        terminal.advance_bytes(OUTPUT.concat());
+        let direct = capture_screen(&terminal);
+
+        let replayed = log.replay();
+        assert_eq!(replayed, direct);
+        let text: Vec<String> = replayed.lines.iter().map(|line| line.text()).collect();
+        assert_eq!(text, vec!["hello", "red moved ✔", "", "", ""]);
+    }
+
+    #[test]
+    fn log_stops_at_its_limit() {
+        let full = write_log(MAX_LOG_BYTES);
+        let limited = write_log(full.len() as u64 - 1);
+        let log = read_log(limited.as_slice()).unwrap();
+        assert_eq!(log.chunks.len(), OUTPUT.len() - 1);
+        assert!(String::from_utf8(limited)
+            .unwrap()
+            .ends_with(" stopped size limit reached\n"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(read_log("hello\n".as_bytes()).is_err());
+        assert!(read_log(format!("{HEADER} rows=5\n").as_bytes()).is_err());
+        assert!(read_log(format!("{HEADER} rows=5 cols=20\n1 raw 1b5\n").as_bytes()).is_err());
+    }
+}
@@ -6,7 +6,7 @@ use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Context, Error};
 use config::keyassignment::SpawnTabDomain;
 use config::observers::*;
-use config::{configuration, ExitBehavior, GuiPosition};
+use config::{configuration, ExitBehavior, GuiPosition, SpawnContext};
 use domain::{Domain, DomainId, DomainState, SplitSource};
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 #[cfg(unix)]
@@ -30,17 +30,23 @@ use std::time::{Duration, Instant};
 use termwiz::escape::csi::{DecPrivateMode, DecPrivateModeCode, Device, Mode};
 use termwiz::escape::{Action, CSI};
 use thiserror::*;
-use phaedra_term::{Clipboard, ClipboardSelection, DownloadHandler, TerminalSize};
+use phaedra_term::{
+    Clipboard, ClipboardSelection, DownloadHandler, TerminalSize, SYNCHRONIZED_OUTPUT_TIMEOUT,
+};
 #[cfg(windows)]
 use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 
 pub mod activity;
+pub mod capture;
 pub mod client;
 pub mod connui;
 pub mod domain;
+pub mod escape_log;
 pub mod localpane;
 pub mod pane;
 pub mod renderable;
+pub mod search;
+pub mod session;
 pub mod ssh;
 pub mod ssh_agent;
 pub mod tab;
@@ -142,13 +148,39 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
     let mut buf = vec![0; configuration().mux_config().mux_output_parser_buffer_size];
     let mut parser = termwiz::escape::parser::Parser::new();
     let mut actions = vec![];
-    let mut hold = false;
+    let pane_id = pane.upgrade().map(|pane| pane.pane_id());
+    // The terminal holds back rendering during a synchronized update;
+    // this tracks when that times out, so that the pane can be
+    // repainted then even if no further output arrives
+    let mut sync_deadline: Option<Instant> = None;
     let mut action_size = 0;
     let mut delay =
         Duration::from_millis(configuration().mux_config().mux_output_parser_coalesce_delay_ms);
     let mut deadline = None;
 
     loop {
+        if let Some(target) = sync_deadline {
+            let mut pfd = [pollfd {
+                fd: rx.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            }];
+            let timeout = target.saturating_duration_since(Instant::now());
+            if !matches!(poll(&mut pfd, Some(timeout)), Ok(1)) {
+                sync_deadline = None;
+                match pane.upgrade() {
+                    Some(pane) => {
+                        Mux::notify_from_any_thread(MuxNotification::PaneOutput(pane.pane_id()));
+                    }
+                    None => {
+                        dead.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+                continue;
+            }
+        }
+
         match rx.read(&mut buf) {
             Ok(size) if size == 0 => {
                 dead.store(true, Ordering::Relaxed);
@@ -159,13 +191,24 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                 break;
             }
             Ok(size) => {
+                // The actions are only kept for the escape log while
+                // the pane is being logged
+                let mut logged = match pane_id {
+                    Some(pane_id) if escape_log::is_logging(pane_id) => Some(vec![]),
+                    _ => None,
+                };
                 parser.parse(&buf[0..size], |action| {
+                    if let Some(logged) = logged.as_mut() {
+                        logged.push(action.clone());
+                    }
                     let mut flush = false;
                     match &action {
                         Action::CSI(CSI::Mode(Mode::SetDecPrivateMode(DecPrivateMode::Code(
                             DecPrivateModeCode::SynchronizedOutput,
                         )))) => {
-                            hold = true;
+                            if sync_deadline.is_none() {
+                                sync_deadline.replace(Instant::now() + SYNCHRONIZED_OUTPUT_TIMEOUT);
+                            }
 
                             // Flush prior actions
                             if !actions.is_empty() {
@@ -176,11 +219,11 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                         Action::CSI(CSI::Mode(Mode::ResetDecPrivateMode(
                             DecPrivateMode::Code(DecPrivateModeCode::SynchronizedOutput),
                         ))) => {
-                            hold = false;
+                            sync_deadline = None;
                             flush = true;
                         }
                         Action::CSI(CSI::Device(dev)) if matches!(**dev, Device::SoftReset) => {
-                            hold = false;
+                            sync_deadline = None;
                             flush = true;
                         }
                         _ => {}
@@ -192,13 +235,17 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                         action_size = 0;
                     }
                 });
+                if let (Some(pane_id), Some(logged)) = (pane_id, logged) {
+                    escape_log::record(pane_id, &buf[0..size], &logged);
+                }
                 action_size += size;
-                if !actions.is_empty() && !hold {
+                if !actions.is_empty() {
                     // If we haven't accumulated too much data,
                     // pause for a short while to increase the chances
                     // that we coalesce a full "frame" from an unoptimized
-                    // TUI program
-                    if action_size < buf.len() {
+                    // TUI program.  That isn't needed during a synchronized
+                    // update, which won't be rendered until it is complete.
+                    if sync_deadline.is_none() && action_size < buf.len() {
                         let poll_delay = match deadline {
                             None => {
                                 deadline.replace(Instant::now() + delay);
@@ -242,6 +289,10 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
     if !actions.is_empty() {
         send_actions_to_mux(&pane, &dead, std::mem::take(&mut actions));
     }
+
+    if let Some(pane_id) = pane_id {
+        escape_log::stop(pane_id);
+    }
 }
 
 fn set_socket_buffer(fd: &mut FileDescriptor, option: i32, size: usize) -> anyhow::Result<()> {
@@ -1019,6 +1070,57 @@ impl Mux {
         None
     }
 
+    /// Moves a tab out of the window that contains it and into
+    /// `window_id` at `index`, or at the end if that is None, and
+    /// makes it the active tab there.  The tab takes on the size of
+    /// the tabs already in that window.  A window that is left
+    /// without tabs is removed.
+    pub fn move_tab_to_window(
+        &self,
+        tab_id: TabId,
+        window_id: WindowId,
+        index: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("move_tab_to_window: no such tab_id {}", tab_id))?;
+        let src_window_id = self
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("move_tab_to_window: tab {} is not in a window", tab_id))?;
+
+        let size = {
+            let window = self
+                .get_window(window_id)
+                .ok_or_else(|| anyhow!("move_tab_to_window: no such window_id {}", window_id))?;
+            window
+                .iter()
+                .find(|t| t.tab_id() != tab_id)
+                .map(|t| t.get_size())
+        };
+
+        if let Some(mut window) = self.get_window_mut(src_window_id) {
+            window.remove_by_id(tab_id);
+        }
+        if let Some(size) = size {
+            tab.resize(size);
+        }
+        {
+            let mut window = self
+                .get_window_mut(window_id)
+                .ok_or_else(|| anyhow!("move_tab_to_window: no such window_id {}", window_id))?;
+            let index = index.unwrap_or(window.len()).min(window.len());
+            window.insert(index, &tab);
+            window.save_and_then_set_active(index);
+        }
+
+        self.recompute_pane_count();
+        if src_window_id != window_id {
+            self.notify(MuxNotification::TabAddedToWindow { tab_id, window_id });
+            self.prune_dead_windows();
+        }
+        Ok(())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.panes.read().is_empty()
     }
@@ -1165,28 +1267,41 @@ impl Mux {
             match pane {
                 Some(pane) if pane.domain_id() == target_domain => pane
                     .get_current_working_dir(policy)
-                    .and_then(|url| {
-                        percent_decode_str(url.path())
-                            .decode_utf8()
-                            .ok()
-                            .map(|path| path.into_owned())
-                    })
-                    .map(|path| {
-                        // On Windows the file URI can produce a path like:
-                        // `/C:\Users` which is valid in a file URI, but the leading slash
-                        // is not liked by the windows file APIs, so we strip it off here.
-                        let bytes = path.as_bytes();
-                        if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
-                            path[1..].to_owned()
-                        } else {
-                            path
-                        }
-                    }),
+                    .and_then(|url| working_dir_to_command_dir(&url)),
                 _ => None,
             }
         })
     }
 
+    /// Gives the domain that we are spawning into the chance to expand
+    /// the `${variable}` references in its `default_prog` and
+    /// `default_cwd`, using the pane that requested the spawn
+    fn apply_spawn_templates(
+        &self,
+        domain: &Arc<dyn Domain>,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        current_pane: Option<&Arc<dyn Pane>>,
+        workspace: Option<String>,
+    ) -> anyhow::Result<(Option<CommandBuilder>, Option<String>)> {
+        let ssh = match domain.downcast_ref::<crate::ssh::RemoteSshDomain>() {
+            Some(ssh) => ssh,
+            None => return Ok((command, command_dir)),
+        };
+        // Unlike the inherited cwd, this is taken from the current pane
+        // even when it belongs to a different domain: the template says
+        // how to use it
+        let pane_cwd = current_pane
+            .and_then(|pane| pane.get_current_working_dir(CachePolicy::FetchImmediate))
+            .and_then(|url| working_dir_to_command_dir(&url));
+        let context = SpawnContext {
+            pane_cwd,
+            workspace,
+            domain_name: domain.domain_name().to_string(),
+        };
+        ssh.apply_spawn_templates(command, command_dir, &context)
+    }
+
     pub async fn split_pane(
         &self,
         // TODO: disambiguate with TabId
@@ -1216,15 +1331,27 @@ impl Mux {
             SplitSource::Spawn {
                 command,
                 command_dir,
-            } => SplitSource::Spawn {
-                command,
-                command_dir: self.resolve_cwd(
+            } => {
+                let workspace = self
+                    .get_window(window_id)
+                    .map(|window| window.get_workspace().to_string());
+                let (command, command_dir) = self.apply_spawn_templates(
+                    &domain,
+                    command,
                     command_dir,
-                    Some(Arc::clone(&current_pane)),
-                    domain.domain_id(),
-                    CachePolicy::FetchImmediate,
-                ),
-            },
+                    Some(&current_pane),
+                    workspace,
+                )?;
+                SplitSource::Spawn {
+                    command,
+                    command_dir: self.resolve_cwd(
+                        command_dir,
+                        Some(Arc::clone(&current_pane)),
+                        domain.domain_id(),
+                        CachePolicy::FetchImmediate,
+                    ),
+                }
+            }
             other => other,
         };
 
@@ -1322,6 +1449,13 @@ impl Mux {
             .resolve_spawn_tab_domain(current_pane_id, &domain)
             .context("resolve_spawn_tab_domain")?;
 
+        let workspace = match window_id {
+            Some(window_id) => self
+                .get_window(window_id)
+                .map(|window| window.get_workspace().to_string()),
+            None => Some(workspace_for_new_window.clone()),
+        };
+
         let window_builder;
         let term_config;
 
@@ -1350,6 +1484,15 @@ impl Mux {
             domain.attach(Some(window_id)).await?;
         }
 
+        let current_pane = current_pane_id.and_then(|id| self.get_pane(id));
+        let (command, command_dir) = self.apply_spawn_templates(
+            &domain,
+            command,
+            command_dir,
+            current_pane.as_ref(),
+            workspace,
+        )?;
+
         let cwd = self.resolve_cwd(
             command_dir,
             match current_pane_id {
@@ -1425,6 +1568,21 @@ pub enum SessionTerminated {
     WindowClosed,
 }
 
+/// Convert the working directory of a pane into a path that can be
+/// used as the `command_dir` of a spawn request
+pub(crate) fn working_dir_to_command_dir(url: &url::Url) -> Option<String> {
+    let path = percent_decode_str(url.path()).decode_utf8().ok()?.into_owned();
+    // On Windows the file URI can produce a path like:
+    // `/C:\Users` which is valid in a file URI, but the leading slash
+    // is not liked by the windows file APIs, so we strip it off here.
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+        Some(path[1..].to_owned())
+    } else {
+        Some(path)
+    }
+}
+
 pub(crate) fn terminal_size_to_pty_size(size: TerminalSize) -> anyhow::Result<PtySize> {
     Ok(PtySize {
         rows: size.rows.try_into()?,
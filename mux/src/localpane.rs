@@ -1,9 +1,10 @@
 use crate::domain::DomainId;
 use crate::pane::{
-    CachePolicy, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId,
+    CachePolicy, CloseReason, ForEachPaneLogicalLine, HeldSnapshot, LogicalLine, Pane, PaneId,
     PaneRenderSnapshot, Pattern, SearchResult, WithPaneLines,
 };
 use crate::renderable::*;
+use crate::search::{CompiledPattern, LineSearch};
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
 use anyhow::Error;
@@ -11,13 +12,11 @@ use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
 use config::observers::*;
 use config::{configuration, ExitBehavior, ExitBehaviorMessaging};
-use fancy_regex::Regex;
 use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
 use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
 use procinfo::LocalProcessInfo;
 use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
-use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{Result as IoResult, Write};
@@ -32,8 +31,9 @@ use url::Url;
 use phaedra_dynamic::Value;
 use phaedra_term::color::ColorPalette;
 use phaedra_term::{
-    Alert, AlertHandler, Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration, TerminalSize,
+    Alert, AlertHandler, Clipboard, CommandZone, DownloadHandler, KeyCode, KeyModifiers,
+    MouseEvent, Progress, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
+    TerminalSize,
 };
 
 const PROC_INFO_CACHE_TTL: Duration = Duration::from_millis(300);
@@ -134,6 +134,14 @@ pub struct LocalPane {
     #[cfg(unix)]
     leader: Arc<Mutex<Option<CachedLeaderInfo>>>,
     command_description: String,
+    /// The command line of the spawned program, if known; used
+    /// to expand `{command}` in `exit_behavior_template`
+    command_line: Option<String>,
+    /// The argv that the pane was spawned with, or None if it runs
+    /// the default program
+    spawn_argv: Option<Vec<String>>,
+    /// Shown in place of the terminal content during synchronized output
+    held_snapshot: HeldSnapshot,
 }
 
 #[async_trait(?Send)]
@@ -161,6 +169,12 @@ impl Pane for LocalPane {
             );
         }
 
+        insert_terminal_metadata(
+            &mut map,
+            &self.terminal.lock(),
+            self.tmux_domain.lock().is_some(),
+        );
+
         Value::Object(map.into())
     }
 
@@ -223,41 +237,46 @@ impl Pane for LocalPane {
     fn snapshot_for_render(&self, viewport: Option<StableRowIndex>) -> PaneRenderSnapshot {
         let tmux_mode = self.tmux_domain.lock().is_some();
         let mut terminal = self.terminal.lock();
-        let mut cursor = terminal_get_cursor_position(&mut terminal);
-        if tmux_mode {
-            cursor.visibility = termwiz::surface::CursorVisibility::Hidden;
-        }
-        let dims = terminal_get_dimensions(&mut terminal);
-        let stable_range = match viewport {
-            Some(top) => top..top + dims.viewport_rows as StableRowIndex,
-            None => dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
-        };
-        let seqno = terminal.current_seqno();
-        let title = terminal.get_title().to_string();
-        let is_mouse_grabbed = if tmux_mode {
-            false
-        } else {
-            terminal.is_mouse_grabbed()
-        };
-        let is_alt_screen_active = if tmux_mode {
-            false
-        } else {
-            terminal.is_alt_screen_active()
-        };
-        let palette = terminal.palette();
-        let (first_row, lines) = terminal_get_lines(&mut terminal, stable_range);
-
-        PaneRenderSnapshot::new(
-            lines,
-            first_row,
-            cursor,
-            dims,
-            seqno,
-            title,
-            is_mouse_grabbed,
-            is_alt_screen_active,
-            palette,
-        )
+        let held = terminal.is_output_held();
+        self.held_snapshot.resolve(viewport, held, || {
+            let mut cursor = terminal_get_cursor_position(&mut terminal);
+            if tmux_mode {
+                cursor.visibility = termwiz::surface::CursorVisibility::Hidden;
+            }
+            let dims = terminal_get_dimensions(&mut terminal);
+            let stable_range = match viewport {
+                Some(top) => top..top + dims.viewport_rows as StableRowIndex,
+                None => dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
+            };
+            let seqno = terminal.current_seqno();
+            let title = terminal.get_title().to_string();
+            let is_mouse_grabbed = if tmux_mode {
+                false
+            } else {
+                terminal.is_mouse_grabbed()
+            };
+            let is_alt_screen_active = if tmux_mode {
+                false
+            } else {
+                terminal.is_alt_screen_active()
+            };
+            let palette = terminal.palette();
+            let palette_generation = terminal.palette_generation();
+            let (first_row, lines) = terminal_get_lines(&mut terminal, stable_range);
+
+            PaneRenderSnapshot::new(
+                lines,
+                first_row,
+                cursor,
+                dims,
+                seqno,
+                title,
+                is_mouse_grabbed,
+                is_alt_screen_active,
+                palette,
+                palette_generation,
+            )
+        })
     }
 
     fn copy_user_vars(&self) -> HashMap<String, String> {
@@ -328,42 +347,39 @@ impl Pane for LocalPane {
                 };
 
                 if let Some(status) = status {
-                    let success = match status.success() {
-                        true => true,
-                        false => configuration()
-                            .launch()
-                            .clean_exit_codes
-                            .contains(&status.exit_code()),
-                    };
-
-                    match (
-                        self.exit_behavior()
-                            .unwrap_or_else(|| configuration().launch().exit_behavior),
-                        success,
-                        killed,
-                    ) {
-                        (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
-                        (ExitBehavior::CloseOnCleanExit, false, _) => {
-                            brief = format!("⚠️  Process {cmd} didn't exit cleanly");
-                            terse = format!("{status}.");
-                            trailer = format!("{EXIT_BEHAVIOR}=\"CloseOnCleanExit\"");
-
-                            *proc = ProcessState::DeadPendingClose { killed: false }
-                        }
-                        (ExitBehavior::CloseOnCleanExit, ..) => *proc = ProcessState::Dead,
-                        (ExitBehavior::Hold, success, false) => {
-                            trailer = format!("{EXIT_BEHAVIOR}=\"Hold\"");
+                    let config = configuration();
+                    let launch = config.launch();
+                    let behavior = self.exit_behavior().unwrap_or_else(|| launch.exit_behavior);
+                    let success = is_clean_exit(&status, &launch.clean_exit_codes);
+
+                    match exit_disposition(&status, *killed, behavior, &launch.clean_exit_codes) {
+                        ExitDisposition::Close => *proc = ProcessState::Dead,
+                        ExitDisposition::Hold => {
+                            trailer = match behavior {
+                                ExitBehavior::Hold => format!("{EXIT_BEHAVIOR}=\"Hold\""),
+                                _ => format!("{EXIT_BEHAVIOR}=\"CloseOnCleanExit\""),
+                            };
 
                             if success {
                                 brief = format!("👍 Process {cmd} completed.");
                                 terse = "done".to_string();
                             } else {
                                 brief = format!("⚠️  Process {cmd} didn't exit cleanly");
-                                terse = format!("{status}");
+                                terse = match behavior {
+                                    ExitBehavior::Hold => format!("{status}"),
+                                    _ => format!("{status}."),
+                                };
+                            }
+
+                            if let Some(template) = &launch.exit_behavior_template {
+                                let command = self
+                                    .command_line
+                                    .as_deref()
+                                    .unwrap_or(&self.command_description);
+                                brief = expand_exit_banner(template, &status, command);
                             }
                             *proc = ProcessState::DeadPendingClose { killed: false }
                         }
-                        (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                     }
                     log::debug!("child terminated, new state is {:?}", proc);
                 }
@@ -507,10 +523,14 @@ impl Pane for LocalPane {
         self.terminal.lock().get_progress()
     }
 
-    fn palette(&self) -> ColorPalette {
+    fn palette(&self) -> Arc<ColorPalette> {
         self.terminal.lock().palette()
     }
 
+    fn palette_generation(&self) -> usize {
+        self.terminal.lock().palette_generation()
+    }
+
     fn domain_id(&self) -> DomainId {
         self.domain_id
     }
@@ -558,6 +578,10 @@ impl Pane for LocalPane {
             .or_else(|| self.divine_current_working_dir(policy))
     }
 
+    fn spawn_argv(&self) -> Option<Vec<String>> {
+        self.spawn_argv.clone()
+    }
+
     fn tty_name(&self) -> Option<String> {
         #[cfg(unix)]
         {
@@ -685,6 +709,11 @@ impl Pane for LocalPane {
         term.get_semantic_zones()
     }
 
+    fn get_command_zones(&self) -> anyhow::Result<Vec<CommandZone>> {
+        let mut term = self.terminal.lock();
+        term.get_command_zones()
+    }
+
     async fn search(
         &self,
         pattern: Pattern,
@@ -693,183 +722,128 @@ impl Pane for LocalPane {
     ) -> anyhow::Result<Vec<SearchResult>> {
         let term = self.terminal.lock();
         let screen = term.screen();
-
-        enum CompiledPattern {
-            CaseSensitiveString(String),
-            CaseInSensitiveString(String),
-            Regex(Regex),
-        }
-
-        let pattern = match pattern {
-            Pattern::CaseSensitiveString(s) => CompiledPattern::CaseSensitiveString(s),
-            Pattern::CaseInSensitiveString(s) => {
-                // normalize the case so we match everything lowercase
-                CompiledPattern::CaseInSensitiveString(s.to_lowercase())
-            }
-            Pattern::Regex(r) => CompiledPattern::Regex(Regex::new(&r)?),
-        };
-
-        let mut results = vec![];
-        let mut uniq_matches: HashMap<String, usize> = HashMap::new();
+        let pattern = CompiledPattern::new(&pattern)?;
+        let mut search = LineSearch::new(&pattern);
 
         screen.for_each_logical_line_in_stable_range(range, |sr, lines| {
             if let Some(limit) = limit {
-                if results.len() == limit as usize {
+                if search.results.len() == limit as usize {
                     // We've reach the limit, stop iteration.
                     return false;
                 }
             }
-
-            if lines.is_empty() {
-                // Nothing to do on this iteration, carry on with the next.
-                return true;
-            }
-            let haystack = if lines.len() == 1 {
-                lines[0].as_str()
-            } else {
-                let mut s = String::new();
-                for line in lines {
-                    s.push_str(&line.as_str());
-                }
-                Cow::Owned(s)
-            };
-            let stable_idx = sr.start;
-
-            if haystack.is_empty() {
-                return true;
-            }
-
-            let haystack = match &pattern {
-                CompiledPattern::CaseInSensitiveString(_) => Cow::Owned(haystack.to_lowercase()),
-                _ => haystack,
-            };
-            let mut coords = None;
-
-            match &pattern {
-                CompiledPattern::CaseInSensitiveString(s)
-                | CompiledPattern::CaseSensitiveString(s) => {
-                    for (idx, s) in haystack.match_indices(s) {
-                        found_match(
-                            s,
-                            idx,
-                            lines,
-                            stable_idx,
-                            &mut uniq_matches,
-                            &mut coords,
-                            &mut results,
-                        );
-                    }
-                }
-                CompiledPattern::Regex(re) => {
-                    // Allow for the regex to contain captures
-                    for capture_res in re.captures_iter(&haystack) {
-                        if let Ok(c) = capture_res {
-                            // Look for the captures in reverse order, as index==0 is
-                            // the whole matched string.  We can't just call
-                            // `c.iter().rev()` as the capture iterator isn't double-ended.
-                            for idx in (0..c.len()).rev() {
-                                if let Some(m) = c.get(idx) {
-                                    found_match(
-                                        m.as_str(),
-                                        m.start(),
-                                        lines,
-                                        stable_idx,
-                                        &mut uniq_matches,
-                                        &mut coords,
-                                        &mut results,
-                                    );
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+            search.search_logical_line(lines, sr.start);
 
             // Keep iterating
             true
         });
 
-        #[derive(Copy, Clone, Debug)]
-        struct Coord {
-            byte_idx: usize,
-            grapheme_idx: usize,
-            stable_row: StableRowIndex,
-        }
+        Ok(search.results)
+    }
+}
 
-        fn found_match(
-            text: &str,
-            byte_idx: usize,
-            lines: &[&Line],
-            stable_idx: StableRowIndex,
-            uniq_matches: &mut HashMap<String, usize>,
-            coords: &mut Option<Vec<Coord>>,
-            results: &mut Vec<SearchResult>,
-        ) {
-            if coords.is_none() {
-                coords.replace(make_coords(lines, stable_idx));
-            }
-            let coords = coords.as_ref().unwrap();
-
-            let match_id = match uniq_matches.get(text).copied() {
-                Some(id) => id,
-                None => {
-                    let id = uniq_matches.len();
-                    uniq_matches.insert(text.to_owned(), id);
-                    id
-                }
-            };
-            let (start_x, start_y) = haystack_idx_to_coord(byte_idx, coords);
-            let (end_x, end_y) = haystack_idx_to_coord(byte_idx + text.len(), coords);
-            results.push(SearchResult {
-                start_x,
-                start_y,
-                end_x,
-                end_y,
-                match_id,
-            });
-        }
+struct LocalPaneDCSHandler {
+    pane_id: PaneId,
+    tmux_domain: Option<Arc<TmuxDomainState>>,
+}
 
-        fn make_coords(lines: &[&Line], stable_row: StableRowIndex) -> Vec<Coord> {
-            let mut byte_idx = 0;
-            let mut coords = vec![];
-
-            for (row_idx, line) in lines.iter().enumerate() {
-                for cell in line.visible_cells() {
-                    coords.push(Coord {
-                        byte_idx,
-                        grapheme_idx: cell.cell_index(),
-                        stable_row: stable_row + row_idx as StableRowIndex,
-                    });
-                    byte_idx += cell.str().len();
-                }
-            }
+/// What to do with a pane once the program running in it has exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitDisposition {
+    /// Close the pane right away
+    Close,
+    /// Keep the pane open, showing the exit banner, until it is
+    /// explicitly closed
+    Hold,
+}
 
-            coords
-        }
+/// Returns true if `status` counts as a clean exit: either a zero exit
+/// code, or one of the codes listed in `clean_exit_codes`.
+/// A process terminated by a signal is never considered to be clean.
+pub fn is_clean_exit(status: &ExitStatus, clean_exit_codes: &[u32]) -> bool {
+    status.success()
+        || (status.signal().is_none() && clean_exit_codes.contains(&status.exit_code()))
+}
 
-        fn haystack_idx_to_coord(idx: usize, coords: &[Coord]) -> (usize, StableRowIndex) {
-            let c = coords
-                .binary_search_by(|ele| ele.byte_idx.cmp(&idx))
-                .or_else(|i| -> Result<usize, usize> { Ok(i) })
-                .unwrap();
-            let coord = coords.get(c).map(|c| *c).unwrap_or_else(|| {
-                let last = coords.last().unwrap();
-                Coord {
-                    grapheme_idx: last.grapheme_idx + 1,
-                    ..*last
-                }
-            });
-            (coord.grapheme_idx, coord.stable_row)
+/// Decides whether a pane should close or be held open after its
+/// program exited with `status`.  `killed` is true if we explicitly
+/// killed the program, which closes held panes immediately.
+pub fn exit_disposition(
+    status: &ExitStatus,
+    killed: bool,
+    behavior: ExitBehavior,
+    clean_exit_codes: &[u32],
+) -> ExitDisposition {
+    match behavior {
+        ExitBehavior::Close => ExitDisposition::Close,
+        ExitBehavior::CloseOnCleanExit => {
+            if is_clean_exit(status, clean_exit_codes) {
+                ExitDisposition::Close
+            } else {
+                ExitDisposition::Hold
+            }
         }
+        ExitBehavior::Hold if killed => ExitDisposition::Close,
+        ExitBehavior::Hold => ExitDisposition::Hold,
+    }
+}
 
-        Ok(results)
+/// Expands `launch.exit_behavior_template` for a held pane.
+/// The following placeholders are recognized; anything else is
+/// passed through unchanged:
+///
+/// * `{command}` - the command line of the program
+/// * `{code}` - the numeric exit code
+/// * `{signal}` - the name of the signal that terminated the program,
+///   or an empty string if it exited normally
+/// * `{status}` - a human readable description of the exit status
+///
+/// Newlines are emitted as CRLF so that multi-line templates render
+/// from the left margin.
+pub fn expand_exit_banner(template: &str, status: &ExitStatus, command: &str) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut remainder = template;
+
+    while let Some(start) = remainder.find('{') {
+        result.push_str(&remainder[..start]);
+        let tail = &remainder[start..];
+        let Some(end) = tail.find('}') else {
+            remainder = tail;
+            break;
+        };
+        match &tail[1..end] {
+            "command" => result.push_str(command),
+            "code" => result.push_str(&status.exit_code().to_string()),
+            "signal" => result.push_str(status.signal().unwrap_or("")),
+            "status" => result.push_str(&status.to_string()),
+            _ => result.push_str(&tail[..=end]),
+        }
+        remainder = &tail[end + 1..];
     }
+    // Either there were no more placeholders, or an unterminated `{`
+    // that we pass through as-is
+    result.push_str(remainder);
+
+    result.replace("\r\n", "\n").replace('\n', "\r\n")
 }
 
-struct LocalPaneDCSHandler {
-    pane_id: PaneId,
-    tmux_domain: Option<Arc<TmuxDomainState>>,
+/// Adds the metadata that reflects the state of `terminal` to `map`.
+/// A pane that is hosting tmux doesn't present its alternate screen.
+fn insert_terminal_metadata(map: &mut BTreeMap<Value, Value>, terminal: &Terminal, tmux: bool) {
+    map.insert(
+        Value::String("synchronized_output".to_string()),
+        Value::Bool(terminal.is_output_held()),
+    );
+    map.insert(
+        Value::String("is_alt_screen_active".to_string()),
+        Value::Bool(!tmux && terminal.is_alt_screen_active()),
+    );
+    if let Some(color) = terminal.get_tab_color() {
+        map.insert(
+            Value::String("tab_color".to_string()),
+            Value::String(color.to_rgb_string()),
+        );
+    }
 }
 
 pub(crate) fn emit_output_for_pane(pane_id: PaneId, message: &str) {
@@ -1061,9 +1035,26 @@ impl LocalPane {
             #[cfg(unix)]
             leader: Arc::new(Mutex::new(None)),
             command_description,
+            command_line: None,
+            spawn_argv: None,
+            held_snapshot: HeldSnapshot::default(),
         }
     }
 
+    /// Records the command line of the spawned program, as rendered by
+    /// `CommandBuilder::as_unix_command_line`
+    pub fn with_command_line(mut self, command_line: String) -> Self {
+        self.command_line = Some(command_line);
+        self
+    }
+
+    /// Records the argv that the pane was spawned with, so that it can
+    /// be respawned when restoring a saved session layout
+    pub fn with_spawn_argv(mut self, argv: Option<Vec<String>>) -> Self {
+        self.spawn_argv = argv;
+        self
+    }
+
     #[cfg(unix)]
     fn get_leader(&self, policy: CachePolicy) -> CachedLeaderInfo {
         let mut leader = self.leader.lock();
@@ -1185,3 +1176,116 @@ impl Drop for LocalPane {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn close_on_clean_exit_honors_clean_exit_codes() {
+        let behavior = ExitBehavior::CloseOnCleanExit;
+        let ok = ExitStatus::with_exit_code(0);
+        let less = ExitStatus::with_exit_code(1);
+
+        assert_eq!(
+            exit_disposition(&ok, false, behavior, &[]),
+            ExitDisposition::Close
+        );
+        assert_eq!(
+            exit_disposition(&less, false, behavior, &[]),
+            ExitDisposition::Hold
+        );
+        assert_eq!(
+            exit_disposition(&less, false, behavior, &[1, 130]),
+            ExitDisposition::Close
+        );
+        assert_eq!(
+            exit_disposition(&ExitStatus::with_exit_code(2), false, behavior, &[1, 130]),
+            ExitDisposition::Hold
+        );
+    }
+
+    #[test]
+    fn signals_are_never_clean() {
+        let hup = ExitStatus::with_signal("Hangup");
+        // A signal reports exit code 1; listing 1 as clean must not
+        // also treat signal termination as clean
+        assert!(!is_clean_exit(&hup, &[1]));
+        assert_eq!(
+            exit_disposition(&hup, false, ExitBehavior::CloseOnCleanExit, &[1]),
+            ExitDisposition::Hold
+        );
+    }
+
+    #[test]
+    fn close_and_hold_ignore_exit_status() {
+        let failed = ExitStatus::with_exit_code(3);
+        let ok = ExitStatus::with_exit_code(0);
+
+        assert_eq!(
+            exit_disposition(&failed, false, ExitBehavior::Close, &[]),
+            ExitDisposition::Close
+        );
+        assert_eq!(
+            exit_disposition(&ok, false, ExitBehavior::Hold, &[]),
+            ExitDisposition::Hold
+        );
+        assert_eq!(
+            exit_disposition(&failed, false, ExitBehavior::Hold, &[]),
+            ExitDisposition::Hold
+        );
+        // An explicit kill releases a held pane
+        assert_eq!(
+            exit_disposition(&ok, true, ExitBehavior::Hold, &[]),
+            ExitDisposition::Close
+        );
+    }
+
+    #[test]
+    fn banner_template_expansion() {
+        let status = ExitStatus::with_exit_code(2);
+        assert_eq!(
+            expand_exit_banner("{command} exited with {code}{signal}", &status, "make -j8"),
+            "make -j8 exited with 2"
+        );
+        assert_eq!(
+            expand_exit_banner("[{status}]", &status, "make"),
+            "[Exited with code 2]"
+        );
+
+        let killed = ExitStatus::with_signal("Killed");
+        assert_eq!(
+            expand_exit_banner("{command}: {signal}\n{unknown}", &killed, "sleep 100"),
+            "sleep 100: Killed\r\n{unknown}"
+        );
+        assert_eq!(
+            expand_exit_banner("brace { left {code", &status, "x"),
+            "brace { left {code"
+        );
+        assert_eq!(expand_exit_banner("a\r\nb", &status, "x"), "a\r\nb");
+    }
+
+    #[test]
+    fn metadata_reports_the_alt_screen() {
+        let mut terminal = Terminal::new(
+            TerminalSize::default(),
+            Arc::new(config::TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        let alt_screen = |terminal: &Terminal, tmux: bool| {
+            let mut map = BTreeMap::new();
+            insert_terminal_metadata(&mut map, terminal, tmux);
+            map.get(&Value::String("is_alt_screen_active".to_string()))
+                .cloned()
+        };
+
+        assert_eq!(alt_screen(&terminal, false), Some(Value::Bool(false)));
+        terminal.advance_bytes("\x1b[?1049h");
+        assert_eq!(alt_screen(&terminal, false), Some(Value::Bool(true)));
+        assert_eq!(alt_screen(&terminal, true), Some(Value::Bool(false)));
+        terminal.advance_bytes("\x1b[?1049l");
+        assert_eq!(alt_screen(&terminal, false), Some(Value::Bool(false)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::capture::CapturedRegion;
 use crate::domain::DomainId;
 use crate::renderable::*;
 use crate::ExitBehavior;
@@ -17,8 +18,8 @@ use url::Url;
 use phaedra_dynamic::Value;
 use phaedra_term::color::ColorPalette;
 use phaedra_term::{
-    Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress, SemanticZone,
-    StableRowIndex, TerminalConfiguration, TerminalSize,
+    Clipboard, CommandZone, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress,
+    SemanticZone, StableRowIndex, TerminalConfiguration, TerminalSize,
 };
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -53,7 +54,7 @@ pub struct SearchResult {
     pub match_id: usize,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub enum Pattern {
     CaseSensitiveString(String),
     CaseInSensitiveString(String),
@@ -172,9 +173,13 @@ pub trait TerminalView {
     fn mouse_grabbed(&self) -> bool;
     fn alt_screen_active(&self) -> bool;
     fn palette(&self) -> &ColorPalette;
+    /// Changes whenever the palette changes, so that it can stand in
+    /// for the palette in cache keys
+    fn palette_generation(&self) -> usize;
     fn content_hash(&self) -> u64;
 }
 
+#[derive(Clone)]
 pub struct PaneRenderSnapshot {
     lines: Vec<Line>,
     first_row: StableRowIndex,
@@ -184,7 +189,8 @@ pub struct PaneRenderSnapshot {
     title: String,
     is_mouse_grabbed: bool,
     is_alt_screen_active: bool,
-    palette: ColorPalette,
+    palette: Arc<ColorPalette>,
+    palette_generation: usize,
     hash: u64,
 }
 
@@ -198,7 +204,8 @@ impl PaneRenderSnapshot {
         title: String,
         is_mouse_grabbed: bool,
         is_alt_screen_active: bool,
-        palette: ColorPalette,
+        palette: Arc<ColorPalette>,
+        palette_generation: usize,
     ) -> Self {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
@@ -225,6 +232,7 @@ impl PaneRenderSnapshot {
             is_mouse_grabbed,
             is_alt_screen_active,
             palette,
+            palette_generation,
             hash,
         }
     }
@@ -284,11 +292,61 @@ impl TerminalView for PaneRenderSnapshot {
         &self.palette
     }
 
+    fn palette_generation(&self) -> usize {
+        self.palette_generation
+    }
+
     fn content_hash(&self) -> u64 {
         self.hash
     }
 }
 
+/// Remembers the most recent snapshot of a pane that was taken outside
+/// of a synchronized update (DECSET 2026), so that it can be presented
+/// in place of the partially updated content while an update is held.
+#[derive(Default)]
+pub struct HeldSnapshot {
+    last: parking_lot::Mutex<Option<(Option<StableRowIndex>, PaneRenderSnapshot)>>,
+}
+
+impl HeldSnapshot {
+    /// Returns the snapshot to render for `viewport`.  While `held`, that
+    /// is the one that was last taken for the same viewport, so that its
+    /// content hash is unchanged and the renderer skips the pane.
+    /// Otherwise, and if there is no such snapshot, `take` makes a new one.
+    pub fn resolve(
+        &self,
+        viewport: Option<StableRowIndex>,
+        held: bool,
+        take: impl FnOnce() -> PaneRenderSnapshot,
+    ) -> PaneRenderSnapshot {
+        let mut last = self.last.lock();
+        if held {
+            if let Some((last_viewport, snapshot)) = last.as_ref() {
+                if *last_viewport == viewport {
+                    return snapshot.clone();
+                }
+            }
+            return take();
+        }
+
+        let snapshot = take();
+        let unchanged = match last.as_ref() {
+            Some((last_viewport, prior)) => {
+                *last_viewport == viewport
+                    && prior.content_hash() == snapshot.content_hash()
+                    && Arc::ptr_eq(&prior.palette, &snapshot.palette)
+            }
+            None => false,
+        };
+        // Only copy the snapshot when it differs from the one we have
+        if !unchanged {
+            last.replace((viewport, snapshot.clone()));
+        }
+        snapshot
+    }
+}
+
 /// A Pane represents a view on a terminal
 #[async_trait(?Send)]
 pub trait Pane: Downcast + Send + Sync {
@@ -336,6 +394,15 @@ pub trait Pane: Downcast + Send + Sync {
 
     fn get_logical_lines(&self, lines: Range<StableRowIndex>) -> Vec<LogicalLine>;
 
+    /// Returns a serializable snapshot of the text in the specified
+    /// range, optionally including the colors and attributes of each
+    /// run of text.  As with `get_lines`, the range is clipped to the
+    /// rows that are present in the pane.
+    fn capture_region(&self, range: Range<StableRowIndex>, include_styles: bool) -> CapturedRegion {
+        let (first_row, lines) = self.get_lines(range);
+        CapturedRegion::from_lines(first_row, &lines, include_styles)
+    }
+
     fn apply_hyperlinks(&self, lines: Range<StableRowIndex>, rules: &[Rule]) {
         struct ApplyHyperLinks<'a> {
             rules: &'a [Rule],
@@ -375,6 +442,7 @@ pub trait Pane: Downcast + Send + Sync {
             self.is_mouse_grabbed(),
             self.is_alt_screen_active(),
             self.palette(),
+            self.palette_generation(),
         )
     }
 
@@ -398,7 +466,10 @@ pub trait Pane: Downcast + Send + Sync {
     fn perform_actions(&self, _actions: Vec<termwiz::escape::Action>) {}
     fn is_dead(&self) -> bool;
     fn kill(&self) {}
-    fn palette(&self) -> ColorPalette;
+    fn palette(&self) -> Arc<ColorPalette>;
+    /// Returns a number that changes whenever the palette returned
+    /// by `palette` changes
+    fn palette_generation(&self) -> usize;
     fn domain_id(&self) -> DomainId;
 
     fn get_keyboard_encoding(&self) -> KeyboardEncoding {
@@ -451,6 +522,11 @@ pub trait Pane: Downcast + Send + Sync {
         Ok(vec![])
     }
 
+    /// Retrieve the commands delimited by OSC 133 prompt marks
+    fn get_command_zones(&self) -> anyhow::Result<Vec<CommandZone>> {
+        Ok(vec![])
+    }
+
     /// Returns true if the terminal has grabbed the mouse and wants to
     /// give the embedded application a chance to process events.
     /// In practice this controls whether the gui will perform local
@@ -480,6 +556,12 @@ pub trait Pane: Downcast + Send + Sync {
         None
     }
 
+    /// The argv that the pane was spawned with.  None means that it
+    /// runs the default program, or that the command is not known.
+    fn spawn_argv(&self) -> Option<Vec<String>> {
+        None
+    }
+
     fn exit_behavior(&self) -> Option<ExitBehavior> {
         None
     }
@@ -787,9 +869,12 @@ mod test {
         fn is_dead(&self) -> bool {
             unimplemented!()
         }
-        fn palette(&self) -> ColorPalette {
+        fn palette(&self) -> Arc<ColorPalette> {
             unimplemented!()
         }
+        fn palette_generation(&self) -> usize {
+            0
+        }
         fn domain_id(&self) -> DomainId {
             unimplemented!()
         }
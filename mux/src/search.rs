@@ -0,0 +1,257 @@
+//! Matching search patterns against the text of a pane.  This is used
+//! both by `Pane::search`, for panes that keep their own lines, and by
+//! the gui to find matches in the lines that it has already fetched
+//! for rendering.
+use crate::pane::{Pattern, SearchResult};
+use fancy_regex::Regex;
+use phaedra_term::StableRowIndex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use termwiz::surface::Line;
+
+/// A search pattern that is ready to be matched
+#[derive(Debug, Clone)]
+pub enum CompiledPattern {
+    CaseSensitiveString(String),
+    /// The string is lowercase, and is matched against the lowercased
+    /// text
+    CaseInSensitiveString(String),
+    Regex(Regex),
+}
+
+impl CompiledPattern {
+    pub fn new(pattern: &Pattern) -> anyhow::Result<Self> {
+        Ok(match pattern {
+            Pattern::CaseSensitiveString(s) => Self::CaseSensitiveString(s.clone()),
+            Pattern::CaseInSensitiveString(s) => {
+                // normalize the case so we match everything lowercase
+                Self::CaseInSensitiveString(s.to_lowercase())
+            }
+            Pattern::Regex(r) => Self::Regex(Regex::new(r)?),
+        })
+    }
+}
+
+/// Accumulates the matches of a pattern across a series of logical
+/// lines, assigning the same match_id to matches of the same text
+pub struct LineSearch<'a> {
+    pattern: &'a CompiledPattern,
+    uniq_matches: HashMap<String, usize>,
+    pub results: Vec<SearchResult>,
+}
+
+impl<'a> LineSearch<'a> {
+    pub fn new(pattern: &'a CompiledPattern) -> Self {
+        Self {
+            pattern,
+            uniq_matches: HashMap::new(),
+            results: vec![],
+        }
+    }
+
+    /// Searches the logical line formed by `lines`, the first of
+    /// which is at `stable_idx`, adding its matches to the results
+    pub fn search_logical_line(&mut self, lines: &[&Line], stable_idx: StableRowIndex) {
+        if lines.is_empty() {
+            return;
+        }
+        let haystack = if lines.len() == 1 {
+            lines[0].as_str()
+        } else {
+            let mut s = String::new();
+            for line in lines {
+                s.push_str(&line.as_str());
+            }
+            Cow::Owned(s)
+        };
+
+        if haystack.is_empty() {
+            return;
+        }
+
+        let pattern = self.pattern;
+        let haystack = match pattern {
+            CompiledPattern::CaseInSensitiveString(_) => Cow::Owned(haystack.to_lowercase()),
+            _ => haystack,
+        };
+        let mut coords = None;
+
+        match pattern {
+            CompiledPattern::CaseInSensitiveString(s) | CompiledPattern::CaseSensitiveString(s) => {
+                for (idx, s) in haystack.match_indices(s.as_str()) {
+                    self.found_match(s, idx, lines, stable_idx, &mut coords);
+                }
+            }
+            CompiledPattern::Regex(re) => {
+                // Allow for the regex to contain captures
+                for capture_res in re.captures_iter(&haystack) {
+                    if let Ok(c) = capture_res {
+                        // Look for the captures in reverse order, as index==0 is
+                        // the whole matched string.  We can't just call
+                        // `c.iter().rev()` as the capture iterator isn't double-ended.
+                        for idx in (0..c.len()).rev() {
+                            if let Some(m) = c.get(idx) {
+                                self.found_match(
+                                    m.as_str(),
+                                    m.start(),
+                                    lines,
+                                    stable_idx,
+                                    &mut coords,
+                                );
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn found_match(
+        &mut self,
+        text: &str,
+        byte_idx: usize,
+        lines: &[&Line],
+        stable_idx: StableRowIndex,
+        coords: &mut Option<Vec<Coord>>,
+    ) {
+        let coords = coords.get_or_insert_with(|| make_coords(lines, stable_idx));
+
+        let match_id = match self.uniq_matches.get(text).copied() {
+            Some(id) => id,
+            None => {
+                let id = self.uniq_matches.len();
+                self.uniq_matches.insert(text.to_owned(), id);
+                id
+            }
+        };
+        let (start_x, start_y) = haystack_idx_to_coord(byte_idx, coords);
+        let (end_x, end_y) = haystack_idx_to_coord(byte_idx + text.len(), coords);
+        self.results.push(SearchResult {
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            match_id,
+        });
+    }
+}
+
+/// Searches `lines`, the first of which is at `first_row`, joining
+/// lines that wrap onto the next into a single logical line.
+/// A logical line that continues beyond the last of `lines` is
+/// searched as far as it goes.
+pub fn search_lines(
+    pattern: &CompiledPattern,
+    lines: &[Line],
+    first_row: StableRowIndex,
+) -> Vec<SearchResult> {
+    let mut search = LineSearch::new(pattern);
+    let mut logical: Vec<&Line> = vec![];
+    let mut logical_start = first_row;
+    for (idx, line) in lines.iter().enumerate() {
+        if logical.is_empty() {
+            logical_start = first_row + idx as StableRowIndex;
+        }
+        logical.push(line);
+        if !line.last_cell_was_wrapped() {
+            search.search_logical_line(&logical, logical_start);
+            logical.clear();
+        }
+    }
+    search.search_logical_line(&logical, logical_start);
+    search.results
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Coord {
+    byte_idx: usize,
+    grapheme_idx: usize,
+    stable_row: StableRowIndex,
+}
+
+fn make_coords(lines: &[&Line], stable_row: StableRowIndex) -> Vec<Coord> {
+    let mut byte_idx = 0;
+    let mut coords = vec![];
+
+    for (row_idx, line) in lines.iter().enumerate() {
+        for cell in line.visible_cells() {
+            coords.push(Coord {
+                byte_idx,
+                grapheme_idx: cell.cell_index(),
+                stable_row: stable_row + row_idx as StableRowIndex,
+            });
+            byte_idx += cell.str().len();
+        }
+    }
+
+    coords
+}
+
+fn haystack_idx_to_coord(idx: usize, coords: &[Coord]) -> (usize, StableRowIndex) {
+    let c = coords
+        .binary_search_by(|ele| ele.byte_idx.cmp(&idx))
+        .or_else(|i| -> Result<usize, usize> { Ok(i) })
+        .unwrap();
+    let coord = coords.get(c).map(|c| *c).unwrap_or_else(|| {
+        let last = coords.last().unwrap();
+        Coord {
+            grapheme_idx: last.grapheme_idx + 1,
+            ..*last
+        }
+    });
+    (coord.grapheme_idx, coord.stable_row)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn line(text: &str, wrapped: bool) -> Line {
+        let mut line = Line::from_text(text, &Default::default(), SEQ_ZERO, None);
+        line.set_last_cell_was_wrapped(wrapped, SEQ_ZERO);
+        line
+    }
+
+    #[test]
+    fn matches_span_wrapped_lines() {
+        let lines = vec![
+            line("hello wor", true),
+            line("ld foo", false),
+            line("world", false),
+        ];
+        let pattern =
+            CompiledPattern::new(&Pattern::CaseInSensitiveString("WORLD".to_string())).unwrap();
+
+        assert_eq!(
+            search_lines(&pattern, &lines, 10),
+            vec![
+                SearchResult {
+                    start_y: 10,
+                    start_x: 6,
+                    end_y: 11,
+                    end_x: 2,
+                    match_id: 0,
+                },
+                SearchResult {
+                    start_y: 12,
+                    start_x: 0,
+                    end_y: 12,
+                    end_x: 5,
+                    match_id: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn regex_reports_the_last_capture() {
+        let lines = vec![line("key=value", false)];
+        let pattern = CompiledPattern::new(&Pattern::Regex("key=(\\w+)".to_string())).unwrap();
+        let results = search_lines(&pattern, &lines, 0);
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].start_x, results[0].end_x), (4, 9));
+        assert!(CompiledPattern::new(&Pattern::Regex("(".to_string())).is_err());
+    }
+}
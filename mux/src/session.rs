@@ -0,0 +1,850 @@
+//! Saving and restoring the layout of the mux: its windows, their tabs
+//! and the tree of splits within each tab.  Restoring a layout respawns
+//! the command of each pane in the directory that it last reported; the
+//! content and scrollback of the panes are not preserved.
+//!
+//! Only panes from local domains can be respawned.  Panes from other
+//! domains are recorded as skipped, and the space that they occupied
+//! is given to their neighbors when the layout is restored.
+use crate::domain::{Domain, LocalDomain, SplitSource};
+use crate::pane::Pane;
+use crate::tab::{PaneEntry, PaneNode, SplitDirection, SplitRequest, SplitSize, Tab};
+use crate::window::WindowId;
+use crate::Mux;
+use anyhow::{anyhow, Context};
+use config::configuration;
+use config::observers::*;
+use phaedra_term::TerminalSize;
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The version of the schema below.  Bump this when making a change
+/// that prevents a previously saved layout from being loaded.
+pub const SESSION_LAYOUT_VERSION: u32 = 1;
+
+/// How often the layout is saved while `mux.save_session_layout` is enabled
+pub const SESSION_LAYOUT_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionLayout {
+    pub version: u32,
+    pub windows: Vec<WindowLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub workspace: String,
+    pub title: String,
+    /// The index of the active tab in `tabs`
+    pub active_tab: usize,
+    pub tabs: Vec<TabLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabLayout {
+    pub title: String,
+    pub size: TerminalSize,
+    pub root: LayoutNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LayoutNode {
+    /// `first` and `second` are the sizes of `left` and `right`
+    Split {
+        direction: SplitDirection,
+        first: TerminalSize,
+        second: TerminalSize,
+        left: Box<LayoutNode>,
+        right: Box<LayoutNode>,
+    },
+    Pane(PaneLayout),
+    /// A pane from a domain whose panes cannot be respawned
+    Skipped {
+        domain: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneLayout {
+    /// The name of the domain to spawn into
+    pub domain: String,
+    pub cwd: Option<String>,
+    /// The command to run; None for the default program
+    pub argv: Option<Vec<String>>,
+    pub is_active: bool,
+}
+
+/// The file that the session layout is saved to
+pub fn session_layout_path() -> PathBuf {
+    config::DATA_DIR.join("session-layout.json")
+}
+
+impl SessionLayout {
+    /// Capture the layout of all of the windows in the mux
+    pub fn capture(mux: &Mux) -> Self {
+        let mut window_ids = mux.iter_windows();
+        window_ids.sort();
+
+        let mut windows = vec![];
+        for window_id in window_ids {
+            // Don't hold the window lock while capturing the tabs,
+            // as that needs to resolve the window of each tab
+            let (workspace, title, active_tab, tabs) = match mux.get_window(window_id) {
+                Some(window) => (
+                    window.get_workspace().to_string(),
+                    window.get_title().to_string(),
+                    window.get_active_idx(),
+                    window.iter().map(Arc::clone).collect::<Vec<_>>(),
+                ),
+                None => continue,
+            };
+            let tabs: Vec<TabLayout> = tabs
+                .iter()
+                .filter_map(|tab| TabLayout::capture(mux, tab))
+                .collect();
+            if tabs.is_empty() {
+                continue;
+            }
+            windows.push(WindowLayout {
+                workspace,
+                title,
+                active_tab: active_tab.min(tabs.len() - 1),
+                tabs,
+            });
+        }
+
+        Self {
+            version: SESSION_LAYOUT_VERSION,
+            windows,
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let Version { version } =
+            serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?;
+        if version != SESSION_LAYOUT_VERSION {
+            anyhow::bail!(
+                "{} is a version {} session layout, but only version {} is supported",
+                path.display(),
+                version,
+                SESSION_LAYOUT_VERSION
+            );
+        }
+        serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Save the layout to `path`, replacing it atomically
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            config::create_user_owned_dirs(dir)?;
+        }
+        let data = serde_json::to_vec_pretty(self)?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, data).with_context(|| format!("writing {}", temp.display()))?;
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("renaming {} to {}", temp.display(), path.display()))?;
+        Ok(())
+    }
+
+    /// Rebuild the windows of this layout in the mux, returning the
+    /// ids of the windows that were created
+    pub async fn restore(self) -> anyhow::Result<Vec<WindowId>> {
+        let mut window_ids = vec![];
+        for window in self.windows {
+            let workspace = window.workspace.clone();
+            match window.restore().await {
+                Ok(Some(window_id)) => window_ids.push(window_id),
+                Ok(None) => {}
+                Err(err) => {
+                    log::error!(
+                        "while restoring a window in workspace {}: {:#}",
+                        workspace,
+                        err
+                    );
+                }
+            }
+        }
+        Ok(window_ids)
+    }
+}
+
+impl WindowLayout {
+    async fn restore(self) -> anyhow::Result<Option<WindowId>> {
+        let tabs: Vec<TabLayout> = self
+            .tabs
+            .into_iter()
+            .filter_map(|tab| {
+                Some(TabLayout {
+                    root: tab.root.prune()?,
+                    ..tab
+                })
+            })
+            .collect();
+        if tabs.is_empty() {
+            log::info!(
+                "skipping a window in workspace {}, as none of its panes can be restored",
+                self.workspace
+            );
+            return Ok(None);
+        }
+
+        let mux = Mux::get();
+        let window_id = *mux.new_empty_window(Some(self.workspace), None);
+        let mut restored = 0;
+        for tab in tabs {
+            match tab.restore(window_id).await {
+                Ok(()) => restored += 1,
+                Err(err) => log::error!("while restoring a tab: {:#}", err),
+            }
+        }
+        if restored == 0 {
+            mux.kill_window(window_id);
+            return Ok(None);
+        }
+
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if !self.title.is_empty() {
+                window.set_title(&self.title);
+            }
+            window.set_active_without_saving(self.active_tab.min(restored - 1));
+        }
+        Ok(Some(window_id))
+    }
+}
+
+impl TabLayout {
+    fn capture(mux: &Mux, tab: &Arc<Tab>) -> Option<Self> {
+        Some(Self {
+            title: tab.get_title(),
+            size: tab.get_size(),
+            root: LayoutNode::capture(mux, tab.codec_pane_tree())?,
+        })
+    }
+
+    /// Spawn the panes of a pruned tree into a new tab in `window_id`.
+    /// Each split is made by splitting the pane that occupies the whole
+    /// of the split, which then becomes its first pane.
+    async fn restore(self, window_id: WindowId) -> anyhow::Result<()> {
+        let first = self
+            .root
+            .first_pane()
+            .ok_or_else(|| anyhow!("tab has no panes"))?;
+        let tab = resolve_domain(&first.domain)?
+            .spawn(self.size, first.command(), first.command_dir(), window_id)
+            .await?;
+        let pane = tab
+            .get_active_pane()
+            .ok_or_else(|| anyhow!("missing active pane on tab!?"))?;
+
+        let mut active = None;
+        let mut pending = vec![(pane, self.root)];
+        while let Some((pane, node)) = pending.pop() {
+            match node {
+                LayoutNode::Pane(layout) => {
+                    if layout.is_active {
+                        active.replace(pane);
+                    }
+                }
+                LayoutNode::Split {
+                    direction,
+                    second,
+                    left,
+                    right,
+                    ..
+                } => {
+                    let target = right
+                        .first_pane()
+                        .ok_or_else(|| anyhow!("split has no second pane"))?;
+                    let size = match direction {
+                        SplitDirection::Horizontal => second.cols,
+                        SplitDirection::Vertical => second.rows,
+                    };
+                    let new_pane = resolve_domain(&target.domain)?
+                        .split_pane(
+                            SplitSource::Spawn {
+                                command: target.command(),
+                                command_dir: target.command_dir(),
+                            },
+                            tab.tab_id(),
+                            pane.pane_id(),
+                            SplitRequest {
+                                direction,
+                                target_is_second: true,
+                                top_level: false,
+                                size: SplitSize::Cells(size),
+                            },
+                        )
+                        .await?;
+                    pending.push((new_pane, *right));
+                    pending.push((pane, *left));
+                }
+                LayoutNode::Skipped { .. } => {}
+            }
+        }
+
+        if let Some(pane) = active {
+            tab.set_active_pane(&pane);
+        }
+        if !self.title.is_empty() {
+            tab.set_title(&self.title);
+        }
+        Ok(())
+    }
+}
+
+impl LayoutNode {
+    fn capture(mux: &Mux, node: PaneNode) -> Option<Self> {
+        match node {
+            PaneNode::Empty => None,
+            PaneNode::Split {
+                left,
+                right,
+                node: split,
+            } => match (Self::capture(mux, *left), Self::capture(mux, *right)) {
+                (Some(left), Some(right)) => Some(Self::Split {
+                    direction: split.direction,
+                    first: split.first,
+                    second: split.second,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+            PaneNode::Leaf(entry) => Some(Self::capture_pane(mux, &entry)),
+        }
+    }
+
+    fn capture_pane(mux: &Mux, entry: &PaneEntry) -> Self {
+        let pane = match mux.get_pane(entry.pane_id) {
+            Some(pane) => pane,
+            None => {
+                return Self::Skipped {
+                    domain: String::new(),
+                }
+            }
+        };
+        let domain = match mux.get_domain(pane.domain_id()) {
+            Some(domain) => domain,
+            None => {
+                return Self::Skipped {
+                    domain: String::new(),
+                }
+            }
+        };
+        if domain.downcast_ref::<LocalDomain>().is_none() {
+            return Self::Skipped {
+                domain: domain.domain_name().to_string(),
+            };
+        }
+        Self::Pane(PaneLayout {
+            domain: domain.domain_name().to_string(),
+            cwd: entry
+                .working_dir
+                .as_ref()
+                .and_then(|dir| crate::working_dir_to_command_dir(&dir.url)),
+            argv: pane.spawn_argv(),
+            is_active: entry.is_active_pane,
+        })
+    }
+
+    /// Remove the skipped panes, giving their space to their siblings
+    pub fn prune(self) -> Option<Self> {
+        match self {
+            Self::Split {
+                direction,
+                first,
+                second,
+                left,
+                right,
+            } => match (left.prune(), right.prune()) {
+                (Some(left), Some(right)) => Some(Self::Split {
+                    direction,
+                    first,
+                    second,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+                (Some(node), None) | (None, Some(node)) => Some(node),
+                (None, None) => None,
+            },
+            Self::Pane(pane) => Some(Self::Pane(pane)),
+            Self::Skipped { domain } => {
+                log::info!(
+                    "not restoring a pane from domain `{}`, as only panes \
+                     from local domains can be restored",
+                    domain
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the top-left pane, which is the one that occupies the
+    /// whole of this node before it is split
+    pub fn first_pane(&self) -> Option<&PaneLayout> {
+        match self {
+            Self::Split { left, .. } => left.first_pane(),
+            Self::Pane(pane) => Some(pane),
+            Self::Skipped { .. } => None,
+        }
+    }
+}
+
+impl PaneLayout {
+    fn command(&self) -> Option<CommandBuilder> {
+        self.argv
+            .as_ref()
+            .map(|argv| CommandBuilder::from_argv(argv.iter().map(Into::into).collect()))
+    }
+
+    /// The saved cwd, if it still exists
+    fn command_dir(&self) -> Option<String> {
+        let cwd = self.cwd.as_ref()?;
+        if Path::new(cwd).is_dir() {
+            Some(cwd.clone())
+        } else {
+            log::info!("{} no longer exists; using the default cwd", cwd);
+            None
+        }
+    }
+}
+
+fn resolve_domain(name: &str) -> anyhow::Result<Arc<dyn Domain>> {
+    Mux::get()
+        .get_domain_by_name(name)
+        .ok_or_else(|| anyhow!("domain `{}` not found", name))
+}
+
+/// Save the layout of the mux to `session_layout_path()` if
+/// `mux.save_session_layout` is enabled.  A layout without any windows
+/// is not saved, so that closing the last window before quitting
+/// doesn't discard the previously saved layout.
+pub fn save_session_layout_if_enabled(mux: &Mux) {
+    if !configuration().mux_config().save_session_layout {
+        return;
+    }
+    let layout = SessionLayout::capture(mux);
+    if layout.windows.is_empty() {
+        return;
+    }
+    let path = session_layout_path();
+    if let Err(err) = layout.save(&path) {
+        log::error!("saving session layout to {}: {:#}", path.display(), err);
+    }
+}
+
+/// Save the layout every `SESSION_LAYOUT_SAVE_INTERVAL` until the mux
+/// is shut down
+pub async fn save_session_layout_periodically() {
+    loop {
+        smol::Timer::after(SESSION_LAYOUT_SAVE_INTERVAL).await;
+        match Mux::try_get() {
+            Some(mux) => save_session_layout_if_enabled(&mux),
+            None => return,
+        }
+    }
+}
+
+/// Rebuild the layout that was saved to `session_layout_path()`
+pub async fn restore_saved_session_layout() -> anyhow::Result<Vec<WindowId>> {
+    let layout = SessionLayout::load(&session_layout_path())?;
+    layout.restore().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::domain::{alloc_domain_id, DomainId, DomainState};
+    use crate::pane::{
+        alloc_pane_id, CachePolicy, ForEachPaneLogicalLine, LogicalLine, PaneId, WithPaneLines,
+    };
+    use crate::renderable::*;
+    use async_trait::async_trait;
+    use parking_lot::{MappedMutexGuard, Mutex};
+    use phaedra_term::color::ColorPalette;
+    use phaedra_term::{KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+    use rangeset::RangeSet;
+    use std::ops::Range;
+    use termwiz::surface::SequenceNo;
+    use url::Url;
+
+    fn size(cols: usize, rows: usize) -> TerminalSize {
+        TerminalSize {
+            rows,
+            cols,
+            pixel_width: cols * 8,
+            pixel_height: rows * 16,
+            dpi: 96,
+        }
+    }
+
+    fn pane(cwd: Option<&str>, argv: Option<&[&str]>, is_active: bool) -> LayoutNode {
+        LayoutNode::Pane(PaneLayout {
+            domain: "mock".to_string(),
+            cwd: cwd.map(|cwd| cwd.to_string()),
+            argv: argv.map(|argv| argv.iter().map(|arg| arg.to_string()).collect()),
+            is_active,
+        })
+    }
+
+    /// An 80x24 tab with a pane on the left, and on the right a pane
+    /// above one from a domain that can't be restored, and another
+    fn sample_layout(cwd: &str) -> SessionLayout {
+        SessionLayout {
+            version: SESSION_LAYOUT_VERSION,
+            windows: vec![WindowLayout {
+                workspace: "default".to_string(),
+                title: "main".to_string(),
+                active_tab: 0,
+                tabs: vec![TabLayout {
+                    title: "work".to_string(),
+                    size: size(80, 24),
+                    root: LayoutNode::Split {
+                        direction: SplitDirection::Horizontal,
+                        first: size(40, 24),
+                        second: size(39, 24),
+                        left: Box::new(pane(Some(cwd), None, false)),
+                        right: Box::new(LayoutNode::Split {
+                            direction: SplitDirection::Vertical,
+                            first: size(39, 12),
+                            second: size(39, 11),
+                            left: Box::new(pane(None, Some(&["top", "-d", "1"]), true)),
+                            right: Box::new(LayoutNode::Split {
+                                direction: SplitDirection::Horizontal,
+                                first: size(19, 11),
+                                second: size(19, 11),
+                                left: Box::new(LayoutNode::Skipped {
+                                    domain: "SSH:host".to_string(),
+                                }),
+                                right: Box::new(pane(Some("/no/such/dir"), None, false)),
+                            }),
+                        }),
+                    },
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn layout_round_trips_through_json() {
+        let layout = sample_layout("/home/user");
+        let json = serde_json::to_string(&layout).unwrap();
+        let parsed: SessionLayout = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, layout);
+
+        let dir = std::env::temp_dir().join(format!("phaedra-session-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session-layout.json");
+        layout.save(&path).unwrap();
+        assert_eq!(SessionLayout::load(&path).unwrap(), layout);
+
+        let newer = SessionLayout {
+            version: SESSION_LAYOUT_VERSION + 1,
+            ..layout
+        };
+        newer.save(&path).unwrap();
+        assert!(SessionLayout::load(&path).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn prune_gives_skipped_space_to_sibling() {
+        let root = sample_layout("/home/user").windows[0].tabs[0].root.clone();
+        let expected = LayoutNode::Split {
+            direction: SplitDirection::Horizontal,
+            first: size(40, 24),
+            second: size(39, 24),
+            left: Box::new(pane(Some("/home/user"), None, false)),
+            right: Box::new(LayoutNode::Split {
+                direction: SplitDirection::Vertical,
+                first: size(39, 12),
+                second: size(39, 11),
+                left: Box::new(pane(None, Some(&["top", "-d", "1"]), true)),
+                right: Box::new(pane(Some("/no/such/dir"), None, false)),
+            }),
+        };
+        assert_eq!(root.prune(), Some(expected));
+
+        assert_eq!(
+            LayoutNode::Skipped {
+                domain: "SSH:host".to_string()
+            }
+            .prune(),
+            None
+        );
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct SpawnRequest {
+        size: TerminalSize,
+        argv: Option<Vec<String>>,
+        cwd: Option<String>,
+    }
+
+    struct MockDomain {
+        id: DomainId,
+        spawned: Mutex<Vec<SpawnRequest>>,
+    }
+
+    #[async_trait(?Send)]
+    impl Domain for MockDomain {
+        async fn spawn_pane(
+            &self,
+            size: TerminalSize,
+            command: Option<CommandBuilder>,
+            command_dir: Option<String>,
+        ) -> anyhow::Result<Arc<dyn Pane>> {
+            self.spawned.lock().push(SpawnRequest {
+                size,
+                argv: command.map(|cmd| {
+                    cmd.get_argv()
+                        .iter()
+                        .map(|arg| arg.to_string_lossy().into_owned())
+                        .collect()
+                }),
+                cwd: command_dir,
+            });
+            let pane: Arc<dyn Pane> = Arc::new(FakePane {
+                id: alloc_pane_id(),
+                domain_id: self.id,
+                size: Mutex::new(size),
+            });
+            Mux::get().add_pane(&pane)?;
+            Ok(pane)
+        }
+
+        fn detachable(&self) -> bool {
+            false
+        }
+
+        fn domain_id(&self) -> DomainId {
+            self.id
+        }
+
+        fn domain_name(&self) -> &str {
+            "mock"
+        }
+
+        async fn attach(&self, _window_id: Option<WindowId>) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn detach(&self) -> anyhow::Result<()> {
+            anyhow::bail!("detach not implemented");
+        }
+
+        fn state(&self) -> DomainState {
+            DomainState::Attached
+        }
+    }
+
+    struct FakePane {
+        id: PaneId,
+        domain_id: DomainId,
+        size: Mutex<TerminalSize>,
+    }
+
+    impl Pane for FakePane {
+        fn pane_id(&self) -> PaneId {
+            self.id
+        }
+
+        fn get_cursor_position(&self) -> StableCursorPosition {
+            StableCursorPosition::default()
+        }
+
+        fn get_current_seqno(&self) -> SequenceNo {
+            unimplemented!();
+        }
+
+        fn get_changed_since(
+            &self,
+            _lines: Range<StableRowIndex>,
+            _: SequenceNo,
+        ) -> RangeSet<StableRowIndex> {
+            unimplemented!();
+        }
+
+        fn with_lines_mut(
+            &self,
+            _stable_range: Range<StableRowIndex>,
+            _with_lines: &mut dyn WithPaneLines,
+        ) {
+            unimplemented!();
+        }
+
+        fn for_each_logical_line_in_stable_range_mut(
+            &self,
+            _lines: Range<StableRowIndex>,
+            _for_line: &mut dyn ForEachPaneLogicalLine,
+        ) {
+            unimplemented!();
+        }
+
+        fn get_lines(&self, _lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+            unimplemented!();
+        }
+
+        fn get_logical_lines(&self, _lines: Range<StableRowIndex>) -> Vec<LogicalLine> {
+            unimplemented!();
+        }
+
+        fn get_dimensions(&self) -> RenderableDimensions {
+            let size = self.size.lock();
+            RenderableDimensions {
+                cols: size.cols,
+                viewport_rows: size.rows,
+                dpi: size.dpi,
+                pixel_width: size.pixel_width,
+                pixel_height: size.pixel_height,
+                ..Default::default()
+            }
+        }
+
+        fn get_title(&self) -> String {
+            String::new()
+        }
+        fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
+            unimplemented!()
+        }
+        fn reader(&self) -> anyhow::Result<Option<Box<dyn std::io::Read + Send>>> {
+            Ok(None)
+        }
+        fn writer(&self) -> MappedMutexGuard<dyn std::io::Write> {
+            unimplemented!()
+        }
+        fn resize(&self, size: TerminalSize) -> anyhow::Result<()> {
+            *self.size.lock() = size;
+            Ok(())
+        }
+
+        fn key_down(&self, _key: KeyCode, _mods: KeyModifiers) -> anyhow::Result<()> {
+            unimplemented!()
+        }
+        fn key_up(&self, _: KeyCode, _: KeyModifiers) -> anyhow::Result<()> {
+            unimplemented!()
+        }
+        fn mouse_event(&self, _event: MouseEvent) -> anyhow::Result<()> {
+            unimplemented!()
+        }
+        fn is_dead(&self) -> bool {
+            false
+        }
+        fn palette(&self) -> Arc<ColorPalette> {
+            unimplemented!()
+        }
+        fn palette_generation(&self) -> usize {
+            0
+        }
+        fn domain_id(&self) -> DomainId {
+            self.domain_id
+        }
+        fn is_mouse_grabbed(&self) -> bool {
+            false
+        }
+        fn is_alt_screen_active(&self) -> bool {
+            false
+        }
+        fn get_current_working_dir(&self, _policy: CachePolicy) -> Option<Url> {
+            None
+        }
+    }
+
+    #[test]
+    fn restore_respawns_panes_in_mock_domain() {
+        config::use_test_configuration();
+        let domain = Arc::new(MockDomain {
+            id: alloc_domain_id(),
+            spawned: Mutex::new(vec![]),
+        });
+        let as_domain: Arc<dyn Domain> = domain.clone();
+        let mux = Arc::new(Mux::new(Some(as_domain)));
+        Mux::set_mux(&mux);
+
+        let cwd = std::env::temp_dir().to_string_lossy().into_owned();
+        let window_ids = smol::block_on(sample_layout(&cwd).restore()).unwrap();
+        assert_eq!(window_ids.len(), 1);
+
+        // Each split is made by splitting the first pane of the split,
+        // and the pane that followed the skipped pane takes its place
+        // without its cwd, which no longer exists
+        assert_eq!(
+            *domain.spawned.lock(),
+            vec![
+                SpawnRequest {
+                    size: size(80, 24),
+                    argv: None,
+                    cwd: Some(cwd.clone()),
+                },
+                SpawnRequest {
+                    size: size(39, 24),
+                    argv: Some(vec!["top".to_string(), "-d".to_string(), "1".to_string()]),
+                    cwd: None,
+                },
+                SpawnRequest {
+                    size: size(39, 11),
+                    argv: None,
+                    cwd: None,
+                },
+            ]
+        );
+
+        let window = mux.get_window(window_ids[0]).unwrap();
+        assert_eq!(window.get_workspace(), "default");
+        assert_eq!(window.get_title(), "main");
+        let tab = Arc::clone(window.get_active().unwrap());
+        drop(window);
+        assert_eq!(tab.get_title(), "work");
+
+        let panes = tab.iter_panes();
+        let geometry: Vec<_> = panes
+            .iter()
+            .map(|p| (p.left, p.top, p.width, p.height, p.is_active))
+            .collect();
+        assert_eq!(
+            geometry,
+            vec![
+                (0, 0, 40, 24, false),
+                (41, 0, 39, 12, true),
+                (41, 13, 39, 11, false),
+            ]
+        );
+
+        // The mock domain isn't a local domain, so capturing the
+        // restored layout records its panes as skipped
+        let captured = SessionLayout::capture(&mux);
+        let skipped = || {
+            Box::new(LayoutNode::Skipped {
+                domain: "mock".to_string(),
+            })
+        };
+        assert_eq!(
+            captured.windows[0].tabs[0].root,
+            LayoutNode::Split {
+                direction: SplitDirection::Horizontal,
+                first: size(40, 24),
+                second: size(39, 24),
+                left: skipped(),
+                right: Box::new(LayoutNode::Split {
+                    direction: SplitDirection::Vertical,
+                    first: size(39, 12),
+                    second: size(39, 11),
+                    left: skipped(),
+                    right: skipped(),
+                }),
+            }
+        );
+
+        Mux::shutdown();
+    }
+}
@@ -6,7 +6,7 @@ use crate::Mux;
 use anyhow::{anyhow, bail, Context};
 use async_trait::async_trait;
 use config::observers::*;
-use config::{Shell, SshBackend, SshDomain};
+use config::{Shell, SpawnContext, SshBackend, SshConnectionMultiplex, SshDomain};
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 use portable_pty::cmdbuilder::CommandBuilder;
 use portable_pty::{ChildKiller, ExitStatus, MasterPty, PtySize};
@@ -179,7 +179,7 @@ fn format_host_verification_for_terminal(failed: HostVerificationFailed) -> Vec<
 /// and the reader and writer instances so that we can inject the
 /// interactive setup.  The bulk of that is driven by `connect_ssh_session`.
 pub struct RemoteSshDomain {
-    session: Mutex<Option<Session>>,
+    sessions: Mutex<Vec<PooledSession>>,
     dom: SshDomain,
     id: DomainId,
     name: String,
@@ -224,19 +224,106 @@ pub fn ssh_domain_to_ssh_config(ssh_dom: &SshDomain) -> anyhow::Result<ConfigMap
     if ssh_dom.no_agent_auth {
         ssh_config.insert("identitiesonly".to_string(), "yes".to_string());
     }
+    // Both of these are expressed in whole seconds in ssh_config;
+    // round up so that a sub-second value doesn't disable them
+    if let Some(timeout) = ssh_dom.connection_timeout {
+        ssh_config.insert(
+            "connecttimeout".to_string(),
+            duration_to_ssh_seconds(timeout).to_string(),
+        );
+    }
+    if let Some(interval) = ssh_dom.keepalive_interval {
+        ssh_config.insert(
+            "serveraliveinterval".to_string(),
+            duration_to_ssh_seconds(interval).to_string(),
+        );
+    }
     if let Some("true") = ssh_config.get("phaedra_ssh_verbose").map(|s| s.as_str()) {
         log::info!("Using ssh config: {ssh_config:#?}");
     }
     Ok(ssh_config)
 }
 
+/// OpenSSH servers permit this many sessions per connection
+/// by default (the `MaxSessions` sshd_config option)
+const MAX_CHANNELS_PER_CONNECTION: usize = 10;
+
+/// The aspects of an established ssh connection that are
+/// considered when deciding where to open the channel for a new pane
+trait ChannelHost {
+    /// How many pane channels are currently open on this connection
+    fn open_channels(&self) -> usize;
+    /// The number of channels that were open when the server last
+    /// refused to open another one
+    fn refused_at(&self) -> Option<usize>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ChannelAllocation {
+    /// Open a channel on the connection at this index
+    Existing(usize),
+    NewConnection,
+}
+
+fn allocate_channel<H: ChannelHost>(
+    multiplex: SshConnectionMultiplex,
+    hosts: &[H],
+    max_channels: usize,
+) -> ChannelAllocation {
+    match multiplex {
+        SshConnectionMultiplex::NewConnectionPerPane => ChannelAllocation::NewConnection,
+        SshConnectionMultiplex::SharedConnection => hosts
+            .iter()
+            .position(|host| {
+                let limit = host
+                    .refused_at()
+                    .map_or(max_channels, |refused| refused.min(max_channels));
+                host.open_channels() < limit
+            })
+            .map(ChannelAllocation::Existing)
+            .unwrap_or(ChannelAllocation::NewConnection),
+    }
+}
+
+struct PooledSession {
+    session: Session,
+    panes: Vec<PaneId>,
+    refused_at: Option<usize>,
+}
+
+impl ChannelHost for PooledSession {
+    fn open_channels(&self) -> usize {
+        match Mux::try_get() {
+            Some(mux) => self
+                .panes
+                .iter()
+                .filter(|&&pane_id| mux.get_pane(pane_id).is_some())
+                .count(),
+            None => self.panes.len(),
+        }
+    }
+
+    fn refused_at(&self) -> Option<usize> {
+        self.refused_at
+    }
+}
+
+fn duration_to_ssh_seconds(duration: Duration) -> u64 {
+    let seconds = duration.as_secs();
+    if duration.subsec_nanos() > 0 {
+        seconds + 1
+    } else {
+        seconds
+    }
+}
+
 impl RemoteSshDomain {
     pub fn with_ssh_domain(dom: &SshDomain) -> anyhow::Result<Self> {
         let id = alloc_domain_id();
         Ok(Self {
             id,
             name: dom.name.clone(),
-            session: Mutex::new(None),
+            sessions: Mutex::new(vec![]),
             dom: dom.clone(),
         })
     }
@@ -245,6 +332,35 @@ impl RemoteSshDomain {
         ssh_domain_to_ssh_config(&self.dom)
     }
 
+    /// Expands the `default_prog` and `default_cwd` templates of this
+    /// domain for a spawn that didn't specify its own command or
+    /// directory.  The mux calls this rather than build_command doing
+    /// it, because only the mux knows which pane requested the spawn.
+    pub(crate) fn apply_spawn_templates(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        context: &SpawnContext,
+    ) -> anyhow::Result<(Option<CommandBuilder>, Option<String>)> {
+        let config = config::configuration();
+        let default_prog = self.dom.default_prog.as_ref();
+        let command = match command {
+            Some(mut cmd) => {
+                config.apply_cmd_defaults(&mut cmd, default_prog, None, Some(context))?;
+                Some(cmd)
+            }
+            None if default_prog.is_some() => {
+                Some(config.build_prog(None, default_prog, None, Some(context))?)
+            }
+            None => None,
+        };
+        let command_dir = match (command_dir, &self.dom.default_cwd) {
+            (None, Some(cwd)) => Some(context.expand(cwd)?),
+            (dir, _) => dir,
+        };
+        Ok((command, command_dir))
+    }
+
     fn build_command(
         &self,
         pane_id: PaneId,
@@ -254,10 +370,10 @@ impl RemoteSshDomain {
         let config = config::configuration();
         let cmd = match command {
             Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd, self.dom.default_prog.as_ref(), None);
+                config.apply_cmd_defaults(&mut cmd, self.dom.default_prog.as_ref(), None, None)?;
                 cmd
             }
-            None => config.build_prog(None, self.dom.default_prog.as_ref(), None)?,
+            None => config.build_prog(None, self.dom.default_prog.as_ref(), None, None)?,
         };
         let mut env: HashMap<String, String> = cmd
             .iter_extra_env_as_str()
@@ -327,13 +443,20 @@ impl RemoteSshDomain {
 
     async fn start_new_session(
         &self,
+        pane_id: PaneId,
         command_line: Option<String>,
         env: HashMap<String, String>,
         size: TerminalSize,
     ) -> anyhow::Result<StartNewSessionResult> {
         let (session, events) = Session::connect(self.ssh_config().context("obtain ssh config")?)
             .context("connect to ssh server")?;
-        self.session.lock().unwrap().replace(session.clone());
+        if self.dom.multiplex == SshConnectionMultiplex::SharedConnection {
+            self.sessions.lock().unwrap().push(PooledSession {
+                session: session.clone(),
+                panes: vec![pane_id],
+                refused_at: None,
+            });
+        }
 
         // We get to establish the session!
         //
@@ -412,6 +535,18 @@ impl RemoteSshDomain {
     }
 }
 
+impl RemoteSshDomain {
+    fn update_pooled_session<F: FnOnce(&mut PooledSession)>(&self, session: &Session, func: F) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(pooled) = sessions
+            .iter_mut()
+            .find(|pooled| pooled.session.same_session(session))
+        {
+            func(pooled);
+        }
+    }
+}
+
 struct StartNewSessionResult {
     pty: Box<dyn portable_pty::MasterPty + Send>,
     child: Box<dyn portable_pty::Child + Send>,
@@ -712,11 +847,23 @@ impl Domain for RemoteSshDomain {
             .build_command(pane_id, command, command_dir)
             .context("build_command")?;
 
-        // This needs to be separate from the if let block below in order
-        // for the lock to be released at the appropriate time
-        let mut session: Option<Session> = self.session.lock().unwrap().as_ref().cloned();
+        let StartNewSessionResult { pty, child, writer } = loop {
+            // This needs to be separate from the match below in order
+            // for the lock to be released before we await
+            let existing = {
+                let sessions = self.sessions.lock().unwrap();
+                match allocate_channel(self.dom.multiplex, &sessions, MAX_CHANNELS_PER_CONNECTION) {
+                    ChannelAllocation::Existing(idx) => Some(sessions[idx].session.clone()),
+                    ChannelAllocation::NewConnection => None,
+                }
+            };
+
+            let Some(session) = existing else {
+                break self
+                    .start_new_session(pane_id, command_line, env, size)
+                    .await?;
+            };
 
-        let StartNewSessionResult { pty, child, writer } = if let Some(session) = session.take() {
             match session
                 .request_pty(
                     &config::configuration().launch().term,
@@ -729,11 +876,13 @@ impl Domain for RemoteSshDomain {
                 .context("request ssh pty")
             {
                 Ok((concrete_pty, concrete_child)) => {
+                    self.update_pooled_session(&session, |pooled| pooled.panes.push(pane_id));
+
                     let pty = Box::new(concrete_pty);
                     let child = Box::new(concrete_child);
                     let writer = Box::new(pty.take_writer().context("take writer from pty")?);
 
-                    StartNewSessionResult { pty, child, writer }
+                    break StartNewSessionResult { pty, child, writer };
                 }
                 Err(err) => {
                     if err
@@ -742,16 +891,21 @@ impl Domain for RemoteSshDomain {
                         .is_some()
                     {
                         // Session died (perhaps they closed the initial tab?)
-                        // So we'll try making a new one
-                        self.start_new_session(command_line, env, size).await?
+                        // so forget it and try another
+                        self.sessions
+                            .lock()
+                            .unwrap()
+                            .retain(|pooled| !pooled.session.same_session(&session));
                     } else {
-                        log::error!("{err:#?}");
-                        return Err(err);
+                        // Most likely the server's channel limit; stop
+                        // offering this connection and try another
+                        log::warn!("Unable to open another channel on ssh session: {err:#}");
+                        self.update_pooled_session(&session, |pooled| {
+                            pooled.refused_at = Some(pooled.open_channels());
+                        });
                     }
                 }
             }
-        } else {
-            self.start_new_session(command_line, env, size).await?
         };
 
         // Wrap up the pty etc. in a LocalPane.  That allows for
@@ -1147,3 +1301,97 @@ impl std::io::Read for PtyReader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct MockHost {
+        open: usize,
+        refused_at: Option<usize>,
+    }
+
+    impl ChannelHost for MockHost {
+        fn open_channels(&self) -> usize {
+            self.open
+        }
+
+        fn refused_at(&self) -> Option<usize> {
+            self.refused_at
+        }
+    }
+
+    fn host(open: usize) -> MockHost {
+        MockHost {
+            open,
+            refused_at: None,
+        }
+    }
+
+    #[test]
+    fn shared_connection_reuses_first_with_capacity() {
+        let hosts = [host(2), host(0)];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::Existing(0)
+        );
+
+        let hosts = [host(10), host(3)];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::Existing(1)
+        );
+    }
+
+    #[test]
+    fn shared_connection_connects_when_limit_is_hit() {
+        let hosts: [MockHost; 0] = [];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::NewConnection
+        );
+
+        let hosts = [host(10), host(10)];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::NewConnection
+        );
+    }
+
+    #[test]
+    fn shared_connection_respects_server_refusal() {
+        let mut refused = host(4);
+        refused.refused_at = Some(4);
+        let hosts = [refused];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::NewConnection
+        );
+
+        // Once some of its panes have closed, the connection can
+        // accept channels again
+        let mut recovered = host(3);
+        recovered.refused_at = Some(4);
+        let hosts = [recovered];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::SharedConnection, &hosts, 10),
+            ChannelAllocation::Existing(0)
+        );
+    }
+
+    #[test]
+    fn new_connection_per_pane_never_reuses() {
+        let hosts = [host(0)];
+        assert_eq!(
+            allocate_channel(SshConnectionMultiplex::NewConnectionPerPane, &hosts, 10),
+            ChannelAllocation::NewConnection
+        );
+    }
+
+    #[test]
+    fn ssh_seconds_round_up() {
+        assert_eq!(duration_to_ssh_seconds(Duration::from_secs(30)), 30);
+        assert_eq!(duration_to_ssh_seconds(Duration::from_millis(1500)), 2);
+        assert_eq!(duration_to_ssh_seconds(Duration::from_millis(1)), 1);
+    }
+}
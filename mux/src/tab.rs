@@ -10,9 +10,10 @@ use parking_lot::Mutex;
 use rangeset::intersects_range;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::convert::TryInto;
 use std::sync::Arc;
 use url::Url;
+use phaedra_dynamic::Value;
+use phaedra_term::color::RgbColor;
 use phaedra_term::{StableRowIndex, TerminalSize};
 
 pub type Tree = bintree::Tree<Arc<dyn Pane>, SplitDirectionAndSize>;
@@ -46,6 +47,8 @@ struct TabInner {
     active: usize,
     zoomed: Option<Arc<dyn Pane>>,
     title: String,
+    /// The color set on the tab by `Tab::set_color`
+    color: Option<RgbColor>,
     recency: Recency,
 }
 
@@ -100,13 +103,20 @@ pub enum SplitDirection {
 }
 
 /// The size is of the (first, second) child of the split
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct SplitDirectionAndSize {
     pub direction: SplitDirection,
     pub first: TerminalSize,
     pub second: TerminalSize,
+    /// The fraction of the cells on either side of the divider that
+    /// belong to `first`.  The sizes of the children are derived from
+    /// it when the split is resized, rather than from their prior
+    /// sizes, so that the rounding to whole cells doesn't accumulate.
+    pub ratio: f32,
 }
 
+impl Eq for SplitDirectionAndSize {}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SplitSize {
     Cells(usize),
@@ -143,6 +153,82 @@ impl Default for SplitRequest {
 }
 
 impl SplitDirectionAndSize {
+    pub fn new(direction: SplitDirection, first: TerminalSize, second: TerminalSize) -> Self {
+        let mut split = Self {
+            direction,
+            first,
+            second,
+            ratio: 0.5,
+        };
+        split.update_ratio();
+        split
+    }
+
+    /// The number of cells on either side of the divider
+    fn available(&self) -> usize {
+        match self.direction {
+            SplitDirection::Horizontal => self.first.cols + self.second.cols,
+            SplitDirection::Vertical => self.first.rows + self.second.rows,
+        }
+    }
+
+    /// The number of cells belonging to `first`
+    fn first_extent(&self) -> usize {
+        match self.direction {
+            SplitDirection::Horizontal => self.first.cols,
+            SplitDirection::Vertical => self.first.rows,
+        }
+    }
+
+    /// Sets `ratio` to match the current sizes of the children
+    pub fn update_ratio(&mut self) {
+        let available = self.available();
+        if available > 0 {
+            self.ratio = self.first_extent() as f32 / available as f32;
+        }
+    }
+
+    /// Divides `size`, the size of the whole split, between the
+    /// children according to `ratio`, while keeping each child at
+    /// least as large as the (x, y) minimum given for it
+    fn apply_ratio(
+        &mut self,
+        size: &TerminalSize,
+        min_first: (usize, usize),
+        min_second: (usize, usize),
+        cell_dimensions: &TerminalSize,
+    ) {
+        let (extent, min_first, min_second) = match self.direction {
+            SplitDirection::Horizontal => (size.cols, min_first.0, min_second.0),
+            SplitDirection::Vertical => (size.rows, min_first.1, min_second.1),
+        };
+        let available = extent.saturating_sub(1);
+        let first = ((available as f32 * self.ratio).round() as usize)
+            .min(available.saturating_sub(min_second))
+            .max(min_first.min(available));
+        let second = available - first;
+
+        let mut first_size = *size;
+        let mut second_size = *size;
+        match self.direction {
+            SplitDirection::Horizontal => {
+                first_size.cols = first;
+                second_size.cols = second;
+            }
+            SplitDirection::Vertical => {
+                first_size.rows = first;
+                second_size.rows = second;
+            }
+        }
+        for child in [&mut first_size, &mut second_size] {
+            child.pixel_width = child.cols.saturating_mul(cell_dimensions.pixel_width);
+            child.pixel_height = child.rows.saturating_mul(cell_dimensions.pixel_height);
+            child.dpi = cell_dimensions.dpi;
+        }
+        self.first = first_size;
+        self.second = second_size;
+    }
+
     fn top_of_second(&self) -> usize {
         match self.direction {
             SplitDirection::Horizontal => 0,
@@ -317,7 +403,7 @@ where
 
 /// Computes the minimum (x, y) size based on the panes in this portion
 /// of the tree.
-fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
+fn compute_min_size(tree: &Tree) -> (usize, usize) {
     match tree {
         Tree::Node { data: None, .. } | Tree::Empty => (1, 1),
         Tree::Node {
@@ -325,8 +411,8 @@ fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
             right,
             data: Some(data),
         } => {
-            let (left_x, left_y) = compute_min_size(&mut *left);
-            let (right_x, right_y) = compute_min_size(&mut *right);
+            let (left_x, left_y) = compute_min_size(left);
+            let (right_x, right_y) = compute_min_size(right);
             match data.direction {
                 SplitDirection::Vertical => (left_x.max(right_x), left_y + right_y + 1),
                 SplitDirection::Horizontal => (left_x + right_x + 1, left_y.max(right_y)),
@@ -336,146 +422,23 @@ fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
     }
 }
 
-fn adjust_x_size(tree: &mut Tree, mut x_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (min_x, _) = compute_min_size(tree);
-    while x_adjust != 0 {
-        match tree {
-            Tree::Empty | Tree::Leaf(_) => return,
-            Tree::Node { data: None, .. } => return,
-            Tree::Node {
-                left,
-                right,
-                data: Some(data),
-            } => {
-                data.first.dpi = cell_dimensions.dpi;
-                data.second.dpi = cell_dimensions.dpi;
-                match data.direction {
-                    SplitDirection::Vertical => {
-                        let new_cols = (data.first.cols as isize)
-                            .saturating_add(x_adjust)
-                            .max(min_x as isize);
-                        x_adjust = new_cols.saturating_sub(data.first.cols as isize);
-
-                        if x_adjust != 0 {
-                            adjust_x_size(&mut *left, x_adjust, cell_dimensions);
-                            data.first.cols = new_cols.try_into().unwrap();
-                            data.first.pixel_width =
-                                data.first.cols.saturating_mul(cell_dimensions.pixel_width);
-
-                            adjust_x_size(&mut *right, x_adjust, cell_dimensions);
-                            data.second.cols = data.first.cols;
-                            data.second.pixel_width = data.first.pixel_width;
-                        }
-                        return;
-                    }
-                    SplitDirection::Horizontal if x_adjust > 0 => {
-                        adjust_x_size(&mut *left, 1, cell_dimensions);
-                        data.first.cols += 1;
-                        data.first.pixel_width =
-                            data.first.cols.saturating_mul(cell_dimensions.pixel_width);
-                        x_adjust -= 1;
-
-                        if x_adjust > 0 {
-                            adjust_x_size(&mut *right, 1, cell_dimensions);
-                            data.second.cols += 1;
-                            data.second.pixel_width =
-                                data.second.cols.saturating_mul(cell_dimensions.pixel_width);
-                            x_adjust -= 1;
-                        }
-                    }
-                    SplitDirection::Horizontal => {
-                        // x_adjust is negative
-                        if data.first.cols > 1 {
-                            adjust_x_size(&mut *left, -1, cell_dimensions);
-                            data.first.cols -= 1;
-                            data.first.pixel_width =
-                                data.first.cols.saturating_mul(cell_dimensions.pixel_width);
-                            x_adjust += 1;
-                        }
-                        if x_adjust < 0 && data.second.cols > 1 {
-                            adjust_x_size(&mut *right, -1, cell_dimensions);
-                            data.second.cols -= 1;
-                            data.second.pixel_width =
-                                data.second.cols.saturating_mul(cell_dimensions.pixel_width);
-                            x_adjust += 1;
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (_, min_y) = compute_min_size(tree);
-    while y_adjust != 0 {
-        match tree {
-            Tree::Empty | Tree::Leaf(_) => return,
-            Tree::Node { data: None, .. } => return,
-            Tree::Node {
-                left,
-                right,
-                data: Some(data),
-            } => {
-                data.first.dpi = cell_dimensions.dpi;
-                data.second.dpi = cell_dimensions.dpi;
-                match data.direction {
-                    SplitDirection::Horizontal => {
-                        let new_rows = (data.first.rows as isize)
-                            .saturating_add(y_adjust)
-                            .max(min_y as isize);
-                        y_adjust = new_rows.saturating_sub(data.first.rows as isize);
-
-                        if y_adjust != 0 {
-                            adjust_y_size(&mut *left, y_adjust, cell_dimensions);
-                            data.first.rows = new_rows.try_into().unwrap();
-                            data.first.pixel_height =
-                                data.first.rows.saturating_mul(cell_dimensions.pixel_height);
-
-                            adjust_y_size(&mut *right, y_adjust, cell_dimensions);
-                            data.second.rows = data.first.rows;
-                            data.second.pixel_height = data.first.pixel_height;
-                        }
-                        return;
-                    }
-                    SplitDirection::Vertical if y_adjust > 0 => {
-                        adjust_y_size(&mut *left, 1, cell_dimensions);
-                        data.first.rows += 1;
-                        data.first.pixel_height =
-                            data.first.rows.saturating_mul(cell_dimensions.pixel_height);
-                        y_adjust -= 1;
-                        if y_adjust > 0 {
-                            adjust_y_size(&mut *right, 1, cell_dimensions);
-                            data.second.rows += 1;
-                            data.second.pixel_height = data
-                                .second
-                                .rows
-                                .saturating_mul(cell_dimensions.pixel_height);
-                            y_adjust -= 1;
-                        }
-                    }
-                    SplitDirection::Vertical => {
-                        // y_adjust is negative
-                        if data.first.rows > 1 {
-                            adjust_y_size(&mut *left, -1, cell_dimensions);
-                            data.first.rows -= 1;
-                            data.first.pixel_height =
-                                data.first.rows.saturating_mul(cell_dimensions.pixel_height);
-                            y_adjust += 1;
-                        }
-                        if y_adjust < 0 && data.second.rows > 1 {
-                            adjust_y_size(&mut *right, -1, cell_dimensions);
-                            data.second.rows -= 1;
-                            data.second.pixel_height = data
-                                .second
-                                .rows
-                                .saturating_mul(cell_dimensions.pixel_height);
-                            y_adjust += 1;
-                        }
-                    }
-                }
-            }
-        }
+/// Lays out the splits of `tree`, whose overall size is `size`,
+/// according to their ratios
+fn apply_split_ratios(tree: &mut Tree, size: &TerminalSize, cell_dimensions: &TerminalSize) {
+    if let Tree::Node {
+        left,
+        right,
+        data: Some(data),
+    } = tree
+    {
+        data.apply_ratio(
+            size,
+            compute_min_size(left),
+            compute_min_size(right),
+            cell_dimensions,
+        );
+        apply_split_ratios(left, &data.first, cell_dimensions);
+        apply_split_ratios(right, &data.second, cell_dimensions);
     }
 }
 
@@ -507,6 +470,39 @@ fn cell_dimensions(size: &TerminalSize) -> TerminalSize {
     }
 }
 
+/// Returns the `tab_color` from the metadata of `pane`
+pub fn pane_tab_color(pane: &dyn Pane) -> Option<RgbColor> {
+    match pane.get_metadata() {
+        Value::Object(obj) => match obj.get_by_str("tab_color") {
+            Some(Value::String(color)) => RgbColor::from_rgb_str(color),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Decides the color of a tab from the color that was set on the tab,
+/// which takes precedence, and the colors of its panes, given with
+/// whether each is the active pane.  Of the panes, the color of the
+/// active pane wins, and otherwise that of the first pane that has one.
+fn aggregate_tab_color(
+    tab_color: Option<RgbColor>,
+    panes: impl Iterator<Item = (bool, Option<RgbColor>)>,
+) -> Option<RgbColor> {
+    if tab_color.is_some() {
+        return tab_color;
+    }
+    let mut first = None;
+    for (is_active, color) in panes {
+        match color {
+            Some(color) if is_active => return Some(color),
+            Some(color) if first.is_none() => first = Some(color),
+            _ => {}
+        }
+    }
+    first
+}
+
 impl Tab {
     pub fn new(size: &TerminalSize) -> Self {
         let inner = TabInner::new(size);
@@ -534,6 +530,42 @@ impl Tab {
         }
     }
 
+    /// Returns the color that was set on this tab by `set_color`
+    pub fn get_color(&self) -> Option<RgbColor> {
+        self.inner.lock().color
+    }
+
+    /// Tints the tab with `color` in the tab bar, or restores its
+    /// normal colors when `color` is None
+    pub fn set_color(&self, color: Option<RgbColor>) {
+        {
+            let mut inner = self.inner.lock();
+            if inner.color == color {
+                return;
+            }
+            inner.color = color;
+        }
+        if let Some(mux) = Mux::try_get() {
+            if let Some(window_id) = mux.window_containing_tab(self.tab_id) {
+                mux.notify(MuxNotification::WindowInvalidated(window_id));
+            }
+        }
+    }
+
+    /// Returns the color that the tab is tinted with in the tab bar:
+    /// the color set by `set_color`, or else the `tab_color` of its
+    /// panes, as set by `OSC 1337 ; SetColors=tab=RRGGBB`
+    pub fn effective_color(&self) -> Option<RgbColor> {
+        let color = self.get_color();
+        let panes = self.iter_panes_ignoring_zoom();
+        aggregate_tab_color(
+            color,
+            panes
+                .iter()
+                .map(|pos| (pos.is_active, pane_tab_color(&*pos.pane))),
+        )
+    }
+
     /// Called by the multiplexer client when building a local tab to
     /// mirror a remote tab.  The supplied `root` is the information
     /// about our counterpart in the the remote server.
@@ -647,6 +679,13 @@ impl Tab {
         self.inner.lock().adjust_pane_size(direction, amount)
     }
 
+    /// Moves the closest split divider in the specified direction from
+    /// the active pane, so that the part of the split containing the
+    /// active pane has the specified size.
+    pub fn set_split_size(&self, direction: PaneDirection, size: SplitSize) {
+        self.inner.lock().set_split_size(direction, size)
+    }
+
     /// Activate an adjacent pane in the specified direction.
     /// In cases where there are multiple adjacent panes in the
     /// intended direction, we take the pane that has the largest
@@ -764,6 +803,7 @@ impl TabInner {
             active: 0,
             zoomed: None,
             title: String::new(),
+            color: None,
             recency: Recency::default(),
         }
     }
@@ -1148,8 +1188,7 @@ impl TabInner {
             zoomed.resize(size).ok();
         } else {
             let dims = cell_dimensions(&size);
-            let (min_x, min_y) = compute_min_size(self.pane.as_mut().unwrap());
-            let current_size = self.size;
+            let (min_x, min_y) = compute_min_size(self.pane.as_ref().unwrap());
 
             // Constrain the new size to the minimum possible dimensions
             let cols = size.cols.max(min_x);
@@ -1163,16 +1202,7 @@ impl TabInner {
             };
 
             // Update the split nodes with adjusted sizes
-            adjust_x_size(
-                self.pane.as_mut().unwrap(),
-                cols as isize - current_size.cols as isize,
-                &dims,
-            );
-            adjust_y_size(
-                self.pane.as_mut().unwrap(),
-                rows as isize - current_size.rows as isize,
-                &dims,
-            );
+            apply_split_ratios(self.pane.as_mut().unwrap(), &size, &dims);
 
             self.size = size;
 
@@ -1192,27 +1222,22 @@ impl TabInner {
             .pixel_height
             .checked_div(pane_size.rows)
             .unwrap_or(1);
+        let cell_dimensions = TerminalSize {
+            rows: 1,
+            cols: 1,
+            pixel_width: cell_width,
+            pixel_height: cell_height,
+            dpi: pane_size.dpi,
+        };
+        let (min_first, min_second) = match cursor.subtree() {
+            Tree::Node { left, right, .. } => (compute_min_size(left), compute_min_size(right)),
+            _ => return,
+        };
         if let Ok(Some(node)) = cursor.node_mut() {
-            // Adjust the size of the node; we preserve the size of the first
-            // child and adjust the second, so if we are split down the middle
-            // and the window is made wider, the right column will grow in
-            // size, leaving the left at its current width.
-            if node.direction == SplitDirection::Horizontal {
-                node.first.rows = pane_size.rows;
-                node.second.rows = pane_size.rows;
-
-                node.second.cols = pane_size.cols.saturating_sub(1 + node.first.cols);
-            } else {
-                node.first.cols = pane_size.cols;
-                node.second.cols = pane_size.cols;
-
-                node.second.rows = pane_size.rows.saturating_sub(1 + node.first.rows);
-            }
-            node.first.pixel_width = node.first.cols * cell_width;
-            node.first.pixel_height = node.first.rows * cell_height;
-
-            node.second.pixel_width = node.second.cols * cell_width;
-            node.second.pixel_height = node.second.rows * cell_height;
+            // Divide the new size according to the ratio, so that a
+            // split keeps its proportions when the split containing it
+            // is adjusted
+            node.apply_ratio(&pane_size, min_first, min_second, &cell_dimensions);
         }
     }
 
@@ -1243,6 +1268,7 @@ impl TabInner {
                         if let Some(second) = compute_size(right) {
                             data.second = second;
                         }
+                        data.update_ratio();
                         Some(data.size())
                     } else {
                         None
@@ -1331,6 +1357,7 @@ impl TabInner {
                         .saturating_mul(cell_dimensions.pixel_height);
                 }
             }
+            node.update_ratio();
         }
     }
 
@@ -1375,20 +1402,18 @@ impl TabInner {
         Mux::try_get().map(|mux| mux.notify(MuxNotification::TabResized(self.id)));
     }
 
-    fn adjust_pane_size(&mut self, direction: PaneDirection, amount: usize) {
-        if self.zoomed.is_some() {
-            return;
-        }
+    /// Returns a cursor positioned on the active leaf, or None, with
+    /// the tree restored, if there is no such leaf
+    fn cursor_at_active_leaf(&mut self) -> Option<Cursor> {
         let active_index = self.active;
         let mut cursor = self.pane.take().unwrap().cursor();
         let mut index = 0;
 
-        // Position cursor on the active leaf
         loop {
             if cursor.is_leaf() {
                 if index == active_index {
                     // Found it
-                    break;
+                    return Some(cursor);
                 }
                 index += 1;
             }
@@ -1397,10 +1422,20 @@ impl TabInner {
                 Err(c) => {
                     // Didn't find it
                     self.pane.replace(c.tree());
-                    return;
+                    return None;
                 }
             }
         }
+    }
+
+    fn adjust_pane_size(&mut self, direction: PaneDirection, amount: usize) {
+        if self.zoomed.is_some() {
+            return;
+        }
+        let mut cursor = match self.cursor_at_active_leaf() {
+            Some(cursor) => cursor,
+            None => return,
+        };
 
         // We are on the active leaf.
         // Now we go up until we find the parent node that is
@@ -1437,6 +1472,61 @@ impl TabInner {
         }
     }
 
+    fn set_split_size(&mut self, direction: PaneDirection, size: SplitSize) {
+        if self.zoomed.is_some() {
+            return;
+        }
+        // The direction of the split, and whether the active pane is
+        // in its first part
+        let (split_direction, active_is_first) = match direction {
+            PaneDirection::Left => (SplitDirection::Horizontal, false),
+            PaneDirection::Right => (SplitDirection::Horizontal, true),
+            PaneDirection::Up => (SplitDirection::Vertical, false),
+            PaneDirection::Down => (SplitDirection::Vertical, true),
+            PaneDirection::Next | PaneDirection::Prev => return,
+        };
+        let mut cursor = match self.cursor_at_active_leaf() {
+            Some(cursor) => cursor,
+            None => return,
+        };
+
+        // Go up until we find the split whose divider is on the
+        // desired side of the active pane, then move the divider
+        // by the difference between the current and desired sizes
+        loop {
+            let is_first = cursor.is_left();
+            match cursor.go_up() {
+                Ok(mut c) => {
+                    if let Ok(Some(node)) = c.node_mut() {
+                        if node.direction == split_direction && is_first == active_is_first {
+                            let available = node.available();
+                            let target = match size {
+                                SplitSize::Cells(n) => n,
+                                SplitSize::Percent(n) => (available + 1) * (n as usize) / 100,
+                            };
+                            let first = if active_is_first {
+                                target
+                            } else {
+                                available.saturating_sub(target)
+                            };
+                            let delta = first as isize - node.first_extent() as isize;
+                            self.adjust_node_at_cursor(&mut c, delta);
+                            self.cascade_size_from_cursor(c);
+                            return;
+                        }
+                    }
+
+                    cursor = c;
+                }
+
+                Err(c) => {
+                    self.pane.replace(c.tree());
+                    return;
+                }
+            }
+        }
+    }
+
     fn activate_pane_direction(&mut self, direction: PaneDirection) {
         if self.zoomed.is_some() {
             if !configuration().window_config().unzoom_on_switch_pane {
@@ -1595,11 +1685,29 @@ impl TabInner {
     }
 
     fn remove_pane(&mut self, pane_id: PaneId) -> Option<Arc<dyn Pane>> {
-        let panes = self.remove_pane_if(|_, pane| pane.pane_id() == pane_id, false);
-        for pane in panes {
-            return Some(pane);
+        // Un-zoom while the pane is removed so that the remaining panes
+        // are resized to fill the space within the split tree, rather
+        // than the zoomed pane being shrunk to its slot in the tree.
+        // If some other pane was zoomed, zoom it again afterwards.
+        let zoomed_pane = self.zoomed.as_ref().map(|p| p.pane_id());
+        if zoomed_pane.is_some() {
+            self.set_zoomed(false);
         }
-        None
+
+        let removed = self
+            .remove_pane_if(|_, pane| pane.pane_id() == pane_id, false)
+            .into_iter()
+            .next();
+
+        // The zoomed pane is always the active pane, and removal keeps
+        // the active pane the same, so zooming again zooms that pane
+        if let Some(zoomed_pane) = zoomed_pane.filter(|&id| id != pane_id) {
+            if self.get_active_pane().map(|p| p.pane_id()) == Some(zoomed_pane) {
+                self.set_zoomed(true);
+            }
+        }
+
+        removed
     }
 
     fn remove_pane_if<F>(&mut self, f: F, kill: bool) -> Vec<Arc<dyn Pane>>
@@ -1904,23 +2012,23 @@ impl TabInner {
                 ),
             };
 
-            return Some(SplitDirectionAndSize {
-                direction: request.direction,
-                first: TerminalSize {
+            return Some(SplitDirectionAndSize::new(
+                request.direction,
+                TerminalSize {
                     rows: height1 as _,
                     cols: width1 as _,
                     pixel_height: cell_dims.pixel_height * height1,
                     pixel_width: cell_dims.pixel_width * width1,
                     dpi: cell_dims.dpi,
                 },
-                second: TerminalSize {
+                TerminalSize {
                     rows: height2 as _,
                     cols: width2 as _,
                     pixel_height: cell_dims.pixel_height * height2,
                     pixel_width: cell_dims.pixel_width * width2,
                     dpi: cell_dims.dpi,
                 },
-            });
+            ));
         }
 
         // Ensure that we're not zoomed, otherwise we'll end up in
@@ -1938,23 +2046,23 @@ impl TabInner {
                 }
             };
 
-            SplitDirectionAndSize {
-                direction: request.direction,
-                first: TerminalSize {
+            SplitDirectionAndSize::new(
+                request.direction,
+                TerminalSize {
                     rows: height1 as _,
                     cols: width1 as _,
                     pixel_height: cell_dims.pixel_height * height1,
                     pixel_width: cell_dims.pixel_width * width1,
                     dpi: cell_dims.dpi,
                 },
-                second: TerminalSize {
+                TerminalSize {
                     rows: height2 as _,
                     cols: width2 as _,
                     pixel_height: cell_dims.pixel_height * height2,
                     pixel_width: cell_dims.pixel_width * width2,
                     dpi: cell_dims.dpi,
                 },
-            }
+            )
         })
     }
 
@@ -2300,9 +2408,12 @@ mod test {
         fn is_dead(&self) -> bool {
             false
         }
-        fn palette(&self) -> ColorPalette {
+        fn palette(&self) -> Arc<ColorPalette> {
             unimplemented!()
         }
+        fn palette_generation(&self) -> usize {
+            0
+        }
         fn domain_id(&self) -> DomainId {
             1
         }
@@ -2317,6 +2428,26 @@ mod test {
         }
     }
 
+    #[test]
+    fn tab_color_prefers_the_tab_then_the_active_pane() {
+        let red = RgbColor::new_8bpc(0xff, 0, 0);
+        let green = RgbColor::new_8bpc(0, 0xff, 0);
+        let blue = RgbColor::new_8bpc(0, 0, 0xff);
+
+        let panes = [(false, Some(red)), (true, Some(green)), (false, None)];
+        assert_eq!(aggregate_tab_color(None, panes.into_iter()), Some(green));
+        assert_eq!(
+            aggregate_tab_color(Some(blue), panes.into_iter()),
+            Some(blue)
+        );
+
+        // An active pane without a color defers to the other panes
+        let panes = [(false, None), (true, None), (false, Some(red))];
+        assert_eq!(aggregate_tab_color(None, panes.into_iter()), Some(red));
+
+        assert_eq!(aggregate_tab_color(None, std::iter::empty()), None);
+    }
+
     #[test]
     fn tab_splitting() {
         let size = TerminalSize {
@@ -2376,6 +2507,7 @@ mod test {
                     pixel_height: 600,
                     dpi: 96,
                 },
+                ratio: 39. / 79.,
             }
         );
 
@@ -2405,7 +2537,8 @@ mod test {
                     pixel_width: 800,
                     pixel_height: 275,
                     dpi: 96,
-                }
+                },
+                ratio: 11. / 23.,
             }
         );
 
@@ -2522,6 +2655,280 @@ mod test {
         true
     }
 
+    fn tab_size() -> TerminalSize {
+        TerminalSize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+            dpi: 96,
+        }
+    }
+
+    /// Builds a tab with panes 1, 2 and 3 laid out left to right
+    fn three_pane_tab() -> Tab {
+        let size = tab_size();
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+        for (index, pane_id) in [(0, 2), (1, 3)] {
+            let request = SplitRequest {
+                direction: SplitDirection::Horizontal,
+                ..Default::default()
+            };
+            let split_size = tab.compute_split_size(index, request).unwrap();
+            tab.split_and_insert(index, request, FakePane::new(pane_id, split_size.second))
+                .unwrap();
+        }
+        tab
+    }
+
+    fn pane_layout(tab: &Tab) -> Vec<(PaneId, usize, usize)> {
+        tab.iter_panes()
+            .iter()
+            .map(|p| (p.pane.pane_id(), p.left, p.width))
+            .collect()
+    }
+
+    #[test]
+    fn remove_pane_for_move_to_new_tab() {
+        let tab = three_pane_tab();
+        assert_eq!(
+            pane_layout(&tab),
+            vec![(1, 0, 39), (2, 40, 19), (3, 60, 20)]
+        );
+
+        let pane = tab.remove_pane(2).unwrap();
+        assert_eq!(pane.pane_id(), 2);
+        // The sibling of the removed pane grows to fill its space
+        assert_eq!(pane_layout(&tab), vec![(1, 0, 39), (3, 40, 40)]);
+        assert!(tab.remove_pane(2).is_none());
+
+        let new_tab = Tab::new(&tab_size());
+        new_tab.assign_pane(&pane);
+        assert_eq!(pane_layout(&new_tab), vec![(2, 0, 80)]);
+    }
+
+    #[test]
+    fn remove_pane_keeps_other_pane_zoomed() {
+        let tab = three_pane_tab();
+        // The most recently inserted pane is active
+        tab.set_zoomed(true);
+        assert_eq!(tab.get_zoomed_pane().unwrap().pane_id(), 3);
+
+        let pane = tab.remove_pane(1).unwrap();
+        assert_eq!(pane.pane_id(), 1);
+
+        assert_eq!(tab.get_zoomed_pane().unwrap().pane_id(), 3);
+        let panes = tab.iter_panes();
+        assert_eq!(panes.len(), 1);
+        assert!(panes[0].is_zoomed);
+        assert_eq!(panes[0].width, 80);
+
+        // Once un-zoomed, the remaining panes fill the tab
+        tab.set_zoomed(false);
+        let layout = pane_layout(&tab);
+        assert_eq!(layout.len(), 2);
+        let (first, second) = (layout[0], layout[1]);
+        assert_eq!((first.0, first.1), (2, 0));
+        assert_eq!(second.0, 3);
+        assert_eq!(second.1, first.2 + 1);
+        assert_eq!(second.1 + second.2, 80);
+    }
+
+    #[test]
+    fn remove_zoomed_pane_unzooms_tab() {
+        let tab = three_pane_tab();
+        tab.set_zoomed(true);
+
+        let pane = tab.remove_pane(3).unwrap();
+        assert_eq!(pane.pane_id(), 3);
+
+        assert!(tab.get_zoomed_pane().is_none());
+        assert_eq!(pane_layout(&tab), vec![(1, 0, 39), (2, 40, 40)]);
+    }
+
+    fn sized(cols: usize, rows: usize) -> TerminalSize {
+        TerminalSize {
+            rows,
+            cols,
+            pixel_width: cols * 10,
+            pixel_height: rows * 25,
+            dpi: 96,
+        }
+    }
+
+    /// The ratio of each split, in preorder
+    fn split_ratios(tab: &Tab) -> Vec<f32> {
+        fn collect(tree: &Tree, ratios: &mut Vec<f32>) {
+            if let Tree::Node { left, right, data } = tree {
+                ratios.extend(data.map(|data| data.ratio));
+                collect(left, ratios);
+                collect(right, ratios);
+            }
+        }
+        let mut ratios = vec![];
+        collect(tab.inner.lock().pane.as_ref().unwrap(), &mut ratios);
+        ratios
+    }
+
+    #[test]
+    fn split_ratios_survive_resizing() {
+        let tab = three_pane_tab();
+        let layout = pane_layout(&tab);
+        let ratios = split_ratios(&tab);
+        assert_eq!(ratios, vec![39. / 79., 19. / 39.]);
+
+        for _ in 0..20 {
+            for (cols, rows) in [(37, 11), (123, 50), (41, 7), (200, 60), (79, 23)] {
+                tab.resize(sized(cols, rows));
+                for (ratio, original) in split_ratios(&tab).iter().zip(&ratios) {
+                    assert!((ratio - original).abs() < f32::EPSILON);
+                }
+                let panes = pane_layout(&tab);
+                let first_cols = ((cols - 1) as f32 * ratios[0]).round() as usize;
+                assert_eq!(panes[0].2, first_cols, "{cols}x{rows}");
+                let (_, left, width) = panes[2];
+                assert_eq!(left + width, cols, "{cols}x{rows}");
+            }
+        }
+
+        tab.resize(tab_size());
+        assert_eq!(pane_layout(&tab), layout);
+    }
+
+    #[test]
+    fn unzooming_keeps_split_ratios() {
+        let tab = three_pane_tab();
+        tab.set_zoomed(true);
+        tab.resize(sized(160, 40));
+        tab.resize(sized(120, 30));
+        tab.set_zoomed(false);
+
+        let unzoomed = three_pane_tab();
+        unzoomed.resize(sized(120, 30));
+        assert_eq!(pane_layout(&tab), pane_layout(&unzoomed));
+        assert_eq!(split_ratios(&tab), split_ratios(&unzoomed));
+    }
+
+    #[test]
+    fn set_split_size() {
+        let tab = three_pane_tab();
+
+        // Pane 3 is active, and is to the right of the divider
+        // between panes 2 and 3
+        tab.set_split_size(PaneDirection::Left, SplitSize::Cells(10));
+        assert_eq!(
+            pane_layout(&tab),
+            vec![(1, 0, 39), (2, 40, 29), (3, 70, 10)]
+        );
+        assert_eq!(split_ratios(&tab), vec![39. / 79., 29. / 39.]);
+
+        // There is no divider to the right of pane 3
+        tab.set_split_size(PaneDirection::Right, SplitSize::Percent(25));
+        assert_eq!(
+            pane_layout(&tab),
+            vec![(1, 0, 39), (2, 40, 29), (3, 70, 10)]
+        );
+
+        // Pane 1 takes a quarter of the tab, and panes 2 and 3 keep
+        // their proportions
+        tab.inner.lock().active = 0;
+        tab.set_split_size(PaneDirection::Right, SplitSize::Percent(25));
+        assert_eq!(
+            pane_layout(&tab),
+            vec![(1, 0, 20), (2, 21, 43), (3, 65, 15)]
+        );
+        assert_eq!(split_ratios(&tab), vec![20. / 79., 29. / 39.]);
+    }
+
+    /// Adds a window to `mux` with `count` tabs of `size`, each with
+    /// a single pane
+    fn window_with_tabs(mux: &Mux, size: TerminalSize, count: usize) -> (WindowId, Vec<TabId>) {
+        let window_id = *mux.new_empty_window(Some("default".to_string()), None);
+        let mut tab_ids = vec![];
+        for _ in 0..count {
+            let tab = Arc::new(Tab::new(&size));
+            tab.assign_pane(&FakePane::new(alloc_pane_id(), size));
+            mux.add_tab_and_active_pane(&tab).unwrap();
+            mux.add_tab_to_window(&tab, window_id).unwrap();
+            tab_ids.push(tab.tab_id());
+        }
+        (window_id, tab_ids)
+    }
+
+    fn tab_ids_of(mux: &Mux, window_id: WindowId) -> Vec<TabId> {
+        match mux.get_window(window_id) {
+            Some(window) => window.iter().map(|tab| tab.tab_id()).collect(),
+            None => vec![],
+        }
+    }
+
+    #[test]
+    fn move_tab_to_window() {
+        config::use_test_configuration();
+        let mux = Arc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+
+        let small = TerminalSize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+            dpi: 96,
+        };
+        let large = TerminalSize {
+            rows: 40,
+            cols: 120,
+            pixel_width: 1200,
+            pixel_height: 1000,
+            dpi: 96,
+        };
+        let (first, first_tabs) = window_with_tabs(&mux, small, 3);
+        let (second, second_tabs) = window_with_tabs(&mux, large, 2);
+
+        // Reordering within a window
+        mux.move_tab_to_window(first_tabs[0], first, Some(2))
+            .unwrap();
+        assert_eq!(
+            tab_ids_of(&mux, first),
+            vec![first_tabs[1], first_tabs[2], first_tabs[0]]
+        );
+        assert_eq!(mux.get_window(first).unwrap().get_active_idx(), 2);
+
+        // Into another window, where it takes on the size of the
+        // tabs that are already there
+        mux.move_tab_to_window(first_tabs[1], second, Some(1))
+            .unwrap();
+        assert_eq!(
+            tab_ids_of(&mux, second),
+            vec![second_tabs[0], first_tabs[1], second_tabs[1]]
+        );
+        assert_eq!(mux.get_window(second).unwrap().get_active_idx(), 1);
+        assert_eq!(mux.window_containing_tab(first_tabs[1]), Some(second));
+        assert_eq!(mux.get_tab(first_tabs[1]).unwrap().get_size(), large);
+        assert_eq!(tab_ids_of(&mux, first), vec![first_tabs[2], first_tabs[0]]);
+
+        // An index past the end appends the tab
+        mux.move_tab_to_window(first_tabs[2], second, Some(10))
+            .unwrap();
+        assert_eq!(
+            tab_ids_of(&mux, second),
+            vec![second_tabs[0], first_tabs[1], second_tabs[1], first_tabs[2]]
+        );
+
+        // Moving the last tab leaves the first window without tabs;
+        // the tab and its pane live on
+        mux.move_tab_to_window(first_tabs[0], second, None).unwrap();
+        assert!(tab_ids_of(&mux, first).is_empty());
+        assert_eq!(tab_ids_of(&mux, second).last(), Some(&first_tabs[0]));
+        let tab = mux.get_tab(first_tabs[0]).unwrap();
+        assert!(tab.get_active_pane().is_some());
+
+        assert!(mux
+            .move_tab_to_window(first_tabs[0], WindowId::MAX, None)
+            .is_err());
+    }
+
     #[test]
     fn tab_is_send_and_sync() {
         assert!(is_send_and_sync::<Tab>());
@@ -187,6 +187,7 @@ impl Pane for TermWizTerminalPane {
         let is_mouse_grabbed = terminal.is_mouse_grabbed();
         let is_alt_screen_active = terminal.is_alt_screen_active();
         let palette = terminal.palette();
+        let palette_generation = terminal.palette_generation();
         let (first_row, lines) = terminal_get_lines(&mut terminal, stable_range);
 
         PaneRenderSnapshot::new(
@@ -199,6 +200,7 @@ impl Pane for TermWizTerminalPane {
             is_mouse_grabbed,
             is_alt_screen_active,
             palette,
+            palette_generation,
         )
     }
 
@@ -302,10 +304,14 @@ impl Pane for TermWizTerminalPane {
         *self.dead.lock()
     }
 
-    fn palette(&self) -> ColorPalette {
+    fn palette(&self) -> Arc<ColorPalette> {
         self.terminal.lock().palette()
     }
 
+    fn palette_generation(&self) -> usize {
+        self.terminal.lock().palette_generation()
+    }
+
     fn domain_id(&self) -> DomainId {
         self.domain_id
     }
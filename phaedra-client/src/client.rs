@@ -89,6 +89,7 @@ macro_rules! rpc {
             metrics::counter!("rpc.count", "method" => stringify!($method_name)).increment(1);
             match result {
                 Ok(Pdu::$response_type(res)) => Ok(res),
+                Ok(Pdu::PermissionDenied(denied)) => Err(denied.into()),
                 Ok(_) => bail!("unexpected response {:?}", result),
                 Err(err) => Err(err),
             }
@@ -108,6 +109,7 @@ macro_rules! rpc {
             metrics::counter!("rpc.count", "method" => stringify!($method_name)).increment(1);
             match result {
                 Ok(Pdu::$response_type(res)) => Ok(res),
+                Ok(Pdu::PermissionDenied(denied)) => Err(denied.into()),
                 Ok(_) => bail!("unexpected response {:?}", result),
                 Err(err) => Err(err),
             }
@@ -1163,6 +1165,7 @@ impl Client {
                 self.set_client_id(SetClientId {
                     client_id: self.client_id.clone(),
                     is_proxy: false,
+                    read_only: self.client_domain_config.read_only(),
                 })
                 .await?;
                 Ok(info)
@@ -1357,6 +1360,11 @@ impl Client {
         LivenessResponse
     );
     rpc!(get_lines, GetLines, GetLinesResponse);
+    rpc!(
+        capture_pane_region,
+        CapturePaneRegion,
+        CapturePaneRegionResponse
+    );
     rpc!(
         get_dimensions,
         GetPaneRenderableDimensions,
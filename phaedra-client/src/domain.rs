@@ -199,6 +199,15 @@ impl ClientDomainConfig {
         }
     }
 
+    /// Whether the server should reject requests from this client
+    /// that would change the state of the mux
+    pub fn read_only(&self) -> bool {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.read_only,
+            ClientDomainConfig::Tls(_) | ClientDomainConfig::Ssh(_) => false,
+        }
+    }
+
     pub fn overlay_lag_indicator(&self) -> bool {
         match self {
             ClientDomainConfig::Unix(unix) => unix.overlay_lag_indicator,
@@ -416,6 +425,10 @@ impl ClientDomain {
         self.config.connect_automatically()
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.config.read_only()
+    }
+
     pub fn perform_detach(&self) {
         log::info!("detached domain {}", self.local_domain_id);
         self.inner.lock().unwrap().take();
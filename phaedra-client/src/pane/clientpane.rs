@@ -26,7 +26,7 @@ use termwiz::input::KeyEvent;
 use termwiz::surface::SequenceNo;
 use url::Url;
 use phaedra_dynamic::Value;
-use phaedra_term::color::ColorPalette;
+use phaedra_term::color::{ColorPalette, RgbColor};
 use phaedra_term::{
     Alert, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, Progress, StableRowIndex,
     TerminalConfiguration, TerminalSize,
@@ -38,8 +38,9 @@ pub struct ClientPane {
     pub remote_pane_id: PaneId,
     pub remote_tab_id: TabId,
     pub renderable: Mutex<RenderableState>,
-    configured_palette: Mutex<ColorPalette>,
-    palette: Mutex<ColorPalette>,
+    configured_palette: Mutex<Arc<ColorPalette>>,
+    palette: Mutex<Arc<ColorPalette>>,
+    palette_generation: Mutex<usize>,
     application_palette: Mutex<bool>,
     writer: Mutex<PaneWriter>,
     mouse: Arc<Mutex<MouseState>>,
@@ -50,6 +51,7 @@ pub struct ClientPane {
     config: Mutex<Option<Arc<dyn TerminalConfiguration>>>,
     unseen_output: Mutex<bool>,
     progress: Mutex<Progress>,
+    tab_color: Mutex<Option<RgbColor>>,
 }
 
 impl ClientPane {
@@ -96,11 +98,11 @@ impl ClientPane {
         };
 
         let config = configuration();
-        let palette: ColorPalette = config.color_config().resolved_palette.clone().into();
+        let palette = config.color_palette();
 
         // Advise the server of our palette preference
         promise::spawn::spawn({
-            let palette = palette.clone();
+            let palette = (*palette).clone();
             let client = Arc::clone(client);
             async move {
                 client
@@ -125,6 +127,7 @@ impl ClientPane {
             writer: Mutex::new(writer),
             configured_palette: Mutex::new(palette.clone()),
             palette: Mutex::new(palette),
+            palette_generation: Mutex::new(0),
             clipboard: Mutex::new(None),
             mouse_grabbed: Mutex::new(false),
             ignore_next_kill: Mutex::new(false),
@@ -132,6 +135,17 @@ impl ClientPane {
             user_vars: Mutex::new(HashMap::new()),
             config: Mutex::new(None),
             progress: Mutex::new(Progress::default()),
+            tab_color: Mutex::new(None),
+        }
+    }
+
+    /// Adopt `palette`, advancing the palette generation if it differs
+    /// from the palette that we already had
+    fn set_palette(&self, palette: Arc<ColorPalette>) {
+        let mut current = self.palette.lock();
+        if *current != palette {
+            *current = palette;
+            *self.palette_generation.lock() += 1;
         }
     }
 
@@ -170,9 +184,9 @@ impl ClientPane {
                 }
             },
             Pdu::SetPalette(SetPalette { palette, .. }) => {
-                *self.application_palette.lock() = palette != *self.configured_palette.lock();
+                *self.application_palette.lock() = palette != **self.configured_palette.lock();
 
-                *self.palette.lock() = palette;
+                self.set_palette(palette.intern());
                 let mux = Mux::get();
                 self.renderable.lock().inner.borrow_mut().make_all_stale();
                 mux.notify(MuxNotification::Alert {
@@ -193,6 +207,9 @@ impl ClientPane {
                             alert: Alert::OutputSinceFocusLost,
                         });
                     }
+                    Alert::TabColorChanged(color) => {
+                        *self.tab_color.lock() = *color;
+                    }
                     Alert::Progress(progress) => {
                         *self.progress.lock() = progress.clone();
                         mux.notify(MuxNotification::Alert {
@@ -272,6 +289,12 @@ impl Pane for ClientPane {
             Value::String("since_last_response_ms".to_string()),
             Value::U64(inner.last_recv_time.elapsed().as_millis() as u64),
         );
+        if let Some(color) = *self.tab_color.lock() {
+            map.insert(
+                Value::String("tab_color".to_string()),
+                Value::String(color.to_rgb_string()),
+            );
+        }
 
         Value::Object(map.into())
     }
@@ -309,6 +332,7 @@ impl Pane for ClientPane {
             *self.mouse_grabbed.lock(),
             false,
             self.palette.lock().clone(),
+            *self.palette_generation.lock(),
         )
     }
 
@@ -562,10 +586,14 @@ impl Pane for ClientPane {
         self.renderable.lock().inner.borrow().dead
     }
 
-    fn palette(&self) -> ColorPalette {
+    fn palette(&self) -> Arc<ColorPalette> {
         self.palette.lock().clone()
     }
 
+    fn palette_generation(&self) -> usize {
+        *self.palette_generation.lock()
+    }
+
     fn domain_id(&self) -> DomainId {
         self.client.local_domain_id
     }
@@ -645,13 +673,14 @@ impl Pane for ClientPane {
         // palette through escape sequences, speculatively adopt the
         // new palette so that it updates with the lowest latency.
         if !*self.application_palette.lock() {
-            *self.palette.lock() = palette.clone();
+            self.set_palette(Arc::clone(&palette));
         }
         *self.configured_palette.lock() = palette.clone();
 
         // and now send the color palette to the server
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
+        let palette = (*palette).clone();
         promise::spawn::spawn(async move {
             client
                 .client
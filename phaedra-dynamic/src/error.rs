@@ -4,7 +4,11 @@ use crate::value::Value;
 #[cfg(feature = "std")]
 use std::cell::RefCell;
 #[cfg(feature = "std")]
+use std::collections::HashSet;
+#[cfg(feature = "std")]
 use std::rc::Rc;
+#[cfg(feature = "std")]
+use std::sync::{Mutex, OnceLock};
 use thiserror::Error;
 
 #[cfg(not(feature = "std"))]
@@ -16,7 +20,7 @@ use alloc::{format, vec};
 
 #[cfg(feature = "std")]
 pub trait WarningCollector {
-    fn warn(&self, message: String);
+    fn warn(&self, warning: Warning);
 }
 
 #[cfg(feature = "std")]
@@ -24,6 +28,116 @@ thread_local! {
     static WARNING_COLLECTOR: RefCell<Option<Box<dyn WarningCollector>>> = RefCell::new(None);
 }
 
+/// The place in a configuration file that gave rise to a warning
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl core::fmt::Display for SourceLocation {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(fmt, "{}:{}", self.file, self.line)
+    }
+}
+
+/// A problem that was tolerated while converting a value, such as
+/// while loading the configuration
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Warning {
+    #[error("{}::{} is deprecated: {}", .struct_name, .field, .reason)]
+    DeprecatedField {
+        struct_name: &'static str,
+        field: &'static str,
+        reason: &'static str,
+    },
+    #[error("`{}` is not a valid {} field. {}{}", .field, .struct_name, .alternatives, Self::at(.location))]
+    UnknownField {
+        struct_name: &'static str,
+        field: String,
+        /// Suggestions for the field that may have been intended
+        alternatives: String,
+        location: Option<SourceLocation>,
+    },
+    #[error("{}::{}: {}{}", .struct_name, .field, .message, Self::at(.location))]
+    InvalidValue {
+        struct_name: &'static str,
+        field: String,
+        message: String,
+        location: Option<SourceLocation>,
+    },
+}
+
+impl Warning {
+    fn at(location: &Option<SourceLocation>) -> String {
+        match location {
+            Some(location) => format!(" (at {location})"),
+            None => String::new(),
+        }
+    }
+
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            Self::DeprecatedField { .. } => None,
+            Self::UnknownField { location, .. } | Self::InvalidValue { location, .. } => {
+                location.as_ref()
+            }
+        }
+    }
+
+    /// Log this warning, unless an identical warning has already been
+    /// logged by this process.  Returns true if the warning was logged.
+    /// This avoids repeating the same warnings each time that the
+    /// configuration is reloaded.
+    #[cfg(feature = "std")]
+    pub fn log_once(&self) -> bool {
+        static LOGGED: OnceLock<Mutex<HashSet<Warning>>> = OnceLock::new();
+        let is_new = LOGGED
+            .get_or_init(|| Mutex::new(HashSet::new()))
+            .lock()
+            .unwrap()
+            .insert(self.clone());
+        if is_new {
+            log::warn!("{self}");
+        }
+        is_new
+    }
+
+    /// Add self to a list of warnings, merging it with an
+    /// equivalent warning that is already present.
+    #[cfg(feature = "std")]
+    fn merge_into(self, warnings: &mut Vec<Warning>) {
+        if let Self::UnknownField {
+            struct_name,
+            field,
+            location: Some(location),
+            ..
+        } = &self
+        {
+            // The lua config layer reports the place where an unknown field
+            // was assigned, after from_dynamic has already warned about it.
+            for existing in warnings.iter_mut() {
+                if let Self::UnknownField {
+                    struct_name: existing_struct,
+                    field: existing_field,
+                    location: existing_location @ None,
+                    ..
+                } = existing
+                {
+                    if existing_struct == struct_name && existing_field == field {
+                        existing_location.replace(location.clone());
+                        return;
+                    }
+                }
+            }
+        }
+        if !warnings.contains(&self) {
+            warnings.push(self);
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
@@ -84,28 +198,28 @@ impl Error {
     /// Log a warning; if a warning collector is set for the current thread,
     /// use it, otherwise, log a regular warning message.
     #[cfg(feature = "std")]
-    pub fn warn(message: String) {
+    pub fn warn(warning: Warning) {
         WARNING_COLLECTOR.with(|collector| {
             let collector = collector.borrow();
             if let Some(collector) = collector.as_ref() {
-                collector.warn(message);
+                collector.warn(warning);
             } else {
-                log::warn!("{message}");
+                warning.log_once();
             }
         });
     }
 
     #[cfg(feature = "std")]
-    pub fn capture_warnings<F: FnOnce() -> T, T>(f: F) -> (T, Vec<String>) {
+    pub fn capture_warnings<F: FnOnce() -> T, T>(f: F) -> (T, Vec<Warning>) {
         let warnings = Rc::new(RefCell::new(vec![]));
 
         struct Collector {
-            warnings: Rc<RefCell<Vec<String>>>,
+            warnings: Rc<RefCell<Vec<Warning>>>,
         }
 
         impl WarningCollector for Collector {
-            fn warn(&self, message: String) {
-                self.warnings.borrow_mut().push(message);
+            fn warn(&self, warning: Warning) {
+                warning.merge_into(&mut self.warnings.borrow_mut());
             }
         }
 
@@ -176,17 +290,19 @@ impl Error {
         if options.deprecated_fields == UnknownFieldAction::Ignore {
             return Ok(());
         }
-        let err = Self::DeprecatedField {
-            type_name,
-            field_name,
-            reason,
-        };
-
         match options.deprecated_fields {
-            UnknownFieldAction::Deny => Err(err),
+            UnknownFieldAction::Deny => Err(Self::DeprecatedField {
+                type_name,
+                field_name,
+                reason,
+            }),
             UnknownFieldAction::Warn => {
                 #[cfg(feature = "std")]
-                Self::warn(format!("{:#}", err));
+                Self::warn(Warning::DeprecatedField {
+                    struct_name: type_name,
+                    field: field_name,
+                    reason,
+                });
                 Ok(())
             }
             UnknownFieldAction::Ignore => unreachable!(),
@@ -215,7 +331,28 @@ impl Error {
 
             if show_warning {
                 for err in &errors {
-                    Self::warn(format!("{:#}", err));
+                    Self::warn(match err {
+                        Self::UnknownFieldForStruct {
+                            field_name,
+                            type_name,
+                            possible,
+                        } => Warning::UnknownField {
+                            struct_name: *type_name,
+                            field: field_name.clone(),
+                            alternatives: Self::possible_matches(field_name, *possible),
+                            location: None,
+                        },
+                        Self::InvalidFieldType {
+                            type_name,
+                            key_type,
+                        } => Warning::InvalidValue {
+                            struct_name: *type_name,
+                            field: key_type.clone(),
+                            message: "not a valid type to use as a field name".to_string(),
+                            location: None,
+                        },
+                        _ => unreachable!("compute_unknown_fields only returns field errors"),
+                    });
                 }
             }
         }
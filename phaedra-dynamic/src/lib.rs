@@ -14,7 +14,7 @@ mod todynamic;
 mod value;
 
 pub use array::Array;
-pub use error::Error;
+pub use error::{Error, SourceLocation, Warning};
 pub use fromdynamic::{FromDynamic, FromDynamicOptions, UnknownFieldAction};
 pub use object::{BorrowedKey, Object, ObjectKeyTrait};
 pub use todynamic::{PlaceDynamic, ToDynamic};
@@ -288,3 +288,67 @@ fn enum_into() {
         EnumInto::from_dynamic(&Value::String("age:42".to_string()), Default::default()).unwrap()
     );
 }
+
+#[derive(FromDynamic, Debug, PartialEq)]
+struct StructWithDeprecatedFields {
+    #[dynamic(default, deprecated = "use `height` instead")]
+    rows: u8,
+    #[dynamic(default, deprecated = "use `width` instead")]
+    cols: u8,
+    #[dynamic(default)]
+    width: u8,
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn deprecated_fields_are_structured_warnings_logged_once() {
+    use phaedra_dynamic::{Error, Warning};
+
+    let load = || {
+        Error::capture_warnings(|| {
+            StructWithDeprecatedFields::from_dynamic(
+                &Value::Object(
+                    btreemap!(
+                    "rows".to_dynamic() => Value::U64(24),
+                    "cols".to_dynamic() => Value::U64(80),
+                    "width".to_dynamic() => Value::U64(80))
+                    .into(),
+                ),
+                Default::default(),
+            )
+            .unwrap()
+        })
+    };
+
+    let (value, warnings) = load();
+    assert_eq!(
+        value,
+        StructWithDeprecatedFields {
+            rows: 24,
+            cols: 80,
+            width: 80
+        }
+    );
+    assert_eq!(
+        warnings,
+        vec![
+            Warning::DeprecatedField {
+                struct_name: "StructWithDeprecatedFields",
+                field: "rows",
+                reason: "use `height` instead",
+            },
+            Warning::DeprecatedField {
+                struct_name: "StructWithDeprecatedFields",
+                field: "cols",
+                reason: "use `width` instead",
+            },
+        ]
+    );
+    assert!(warnings.iter().all(|w| w.log_once()));
+
+    // Loading the same thing again produces the same warnings, but
+    // they have already been logged
+    let (_, reloaded) = load();
+    assert_eq!(reloaded, warnings);
+    assert!(!reloaded.iter().any(|w| w.log_once()));
+}
@@ -5,6 +5,26 @@ use serde::{Deserialize, Serialize};
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 
 use crate::allocate::*;
+use crate::color::RgbColor;
+use crate::csi::Underline;
+
+/// How the cells of an implicit hyperlink are to be rendered,
+/// as specified by the rule that matched them
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, FromDynamic, ToDynamic)]
+pub struct HyperlinkStyle {
+    /// Underline the link in this style, in place of the
+    /// underline of its cells
+    #[dynamic(default)]
+    pub underline: Option<Underline>,
+    /// The color of the underline
+    #[dynamic(default)]
+    pub underline_color: Option<RgbColor>,
+    /// The color of the line that is drawn under the link while
+    /// the mouse hovers over it
+    #[dynamic(default)]
+    pub hover_color: Option<RgbColor>,
+}
 
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
@@ -14,6 +34,10 @@ pub struct Hyperlink {
     /// If the link was produced by an implicit or matching rule,
     /// this field will be set to true.
     implicit: bool,
+    /// The style of the rule that produced an implicit link
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    style: Option<HyperlinkStyle>,
 }
 
 impl Hyperlink {
@@ -28,6 +52,7 @@ impl Hyperlink {
             v.hash(hasher);
         }
         self.implicit.hash(hasher);
+        self.style.hash(hasher);
     }
 
     pub fn params(&self) -> &HashMap<String, String> {
@@ -39,6 +64,7 @@ impl Hyperlink {
             uri: uri.into(),
             params: HashMap::new(),
             implicit: false,
+            style: None,
         }
     }
 
@@ -47,11 +73,25 @@ impl Hyperlink {
         self.implicit
     }
 
+    pub fn style(&self) -> Option<&HyperlinkStyle> {
+        self.style.as_ref()
+    }
+
     pub fn new_implicit<S: Into<String>>(uri: S) -> Self {
         Self {
             uri: uri.into(),
             params: HashMap::new(),
             implicit: true,
+            style: None,
+        }
+    }
+
+    pub fn new_implicit_with_style<S: Into<String>>(uri: S, style: Option<HyperlinkStyle>) -> Self {
+        Self {
+            uri: uri.into(),
+            params: HashMap::new(),
+            implicit: true,
+            style,
         }
     }
 
@@ -62,6 +102,7 @@ impl Hyperlink {
             uri: uri.into(),
             params,
             implicit: false,
+            style: None,
         }
     }
 
@@ -70,6 +111,7 @@ impl Hyperlink {
             uri: uri.into(),
             params,
             implicit: false,
+            style: None,
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::color::SrgbaTuple;
+use crate::color::{RgbColor, SrgbaTuple};
 pub use crate::hyperlink::Hyperlink;
 use crate::{Result, bail, ensure, format_err};
 use base64::Engine;
@@ -930,6 +930,10 @@ pub enum ITermProprietary {
         value: String,
     },
     SetBadgeFormat(String),
+    /// Tint the tab that contains the session with the given color, or
+    /// restore its normal colors when None.  This is the `tab` key of
+    /// iTerm2's `SetColors`, whose value is either `RRGGBB` or `default`.
+    SetTabColor(Option<RgbColor>),
     /// Download file data from the application.
     File(Box<ITermFileData>),
 
@@ -1279,6 +1283,17 @@ impl ITermProprietary {
             }
         }
 
+        if osc.len() == 2 && keyword == "SetColors" {
+            if let Some(("tab", value)) = p1.and_then(|p1| p1.split_once('=')) {
+                if value == "default" {
+                    return Ok(ITermProprietary::SetTabColor(None));
+                }
+                if let Some(color) = parse_tab_color(value) {
+                    return Ok(ITermProprietary::SetTabColor(Some(color)));
+                }
+            }
+        }
+
         if osc.len() == 2 && keyword == "UnicodeVersion" {
             if let Some(p1) = p1 {
                 let mut iter = p1.splitn(2, ' ');
@@ -1312,6 +1327,21 @@ impl ITermProprietary {
     }
 }
 
+/// Parses the color of `SetColors=tab=`, which is given as `RRGGBB`,
+/// optionally preceded by `#`
+fn parse_tab_color(value: &str) -> Option<RgbColor> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 {
+        return None;
+    }
+    let bits = u32::from_str_radix(hex, 16).ok()?;
+    Some(RgbColor::new_8bpc(
+        (bits >> 16) as u8,
+        (bits >> 8) as u8,
+        bits as u8,
+    ))
+}
+
 /// base64::encode is deprecated, so make a less frustrating helper
 pub(crate) fn base64_encode<T: AsRef<[u8]>>(s: T) -> String {
     base64::engine::general_purpose::STANDARD.encode(s)
@@ -1363,6 +1393,11 @@ impl Display for ITermProprietary {
                 write!(f, "SetUserVar={}={}", name, base64_encode(value))?
             }
             SetBadgeFormat(s) => write!(f, "SetBadgeFormat={}", base64_encode(s))?,
+            SetTabColor(Some(color)) => {
+                let (red, green, blue) = color.to_tuple_rgb8();
+                write!(f, "SetColors=tab={red:02x}{green:02x}{blue:02x}")?
+            }
+            SetTabColor(None) => write!(f, "SetColors=tab=default")?,
             File(file) => file.fmt(f)?,
             UnicodeVersion(ITermUnicodeVersionOp::Set(n)) => write!(f, "UnicodeVersion={}", n)?,
             UnicodeVersion(ITermUnicodeVersionOp::Push(Some(label))) => {
@@ -1764,6 +1799,34 @@ mod test {
             })
         );
 
+        assert_eq!(
+            parse(
+                &["1337", "SetColors=tab=#Cc3300"],
+                "\x1b]1337;SetColors=tab=cc3300\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetTabColor(Some(
+                RgbColor::new_8bpc(0xcc, 0x33, 0x00)
+            )))
+        );
+        assert_eq!(
+            parse(
+                &["1337", "SetColors=tab=default"],
+                "\x1b]1337;SetColors=tab=default\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetTabColor(None))
+        );
+        // Only the tab color is understood, and only as hex digits
+        assert_eq!(
+            parse(
+                &["1337", "SetColors=tab=red"],
+                "\x1b]1337;SetColors=tab=red\x1b\\"
+            ),
+            OperatingSystemCommand::Unspecified(vec![
+                b"1337".to_vec(),
+                b"SetColors=tab=red".to_vec()
+            ])
+        );
+
         assert_eq!(
             parse(
                 &["1337", "SetBadgeFormat=", "aGVsbG8="],
@@ -6,6 +6,7 @@ use anyhow::Context;
 use config::{observers::*, ConfigHandle, FontAttributes};
 use rangeset::RangeSet;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 pub struct FontDatabase {
     by_full_name: HashMap<String, Vec<ParsedFont>>,
@@ -36,8 +37,13 @@ impl FontDatabase {
     /// Build up the database from the fonts found in the configured font dirs
     /// and from the built-in selection of fonts
     pub fn with_font_dirs(config: &ConfigHandle) -> anyhow::Result<Self> {
+        Self::with_dirs(&config.font_config().font_dirs)
+    }
+
+    /// Build up the database from the fonts found in `dirs`
+    pub fn with_dirs(dirs: &[PathBuf]) -> anyhow::Result<Self> {
         let mut font_info = vec![];
-        for path in &config.font_config().font_dirs {
+        for path in dirs {
             for entry in walkdir::WalkDir::new(path).into_iter() {
                 let entry = match entry {
                     Ok(entry) => entry,
@@ -478,6 +478,53 @@ struct FontConfigInner {
     fallback_channel: RefCell<Option<Sender<FallbackResolveInfo>>>,
 }
 
+/// Returns those of `fonts` that can't be found in the `font_dirs` of
+/// `config`, by its font locator, or among the built-in fonts.
+/// This is registered with `config::assign_font_resolver` so that
+/// fonts that can't be found are reported as configuration warnings.
+pub fn unresolved_fonts(config: &config::Config, fonts: &[FontAttributes]) -> Vec<FontAttributes> {
+    if fonts.is_empty() {
+        return vec![];
+    }
+
+    let font_dirs = match FontDatabase::with_dirs(&config.font_config.font_dirs) {
+        Ok(db) => db,
+        Err(err) => {
+            log::warn!("while checking fonts: {:#}", err);
+            return vec![];
+        }
+    };
+    let built_in = match FontDatabase::with_built_in() {
+        Ok(db) => db,
+        Err(err) => {
+            log::warn!("while checking fonts: {:#}", err);
+            return vec![];
+        }
+    };
+    let locator = new_locator(config.font_config.font_locator);
+    // The size only guides the choice among bitmap strikes, so assume
+    // the usual dpi rather than that of any particular screen
+    let dpi = config.font_config.dpi.unwrap_or(96.0);
+    let pixel_size = (config.font_config.font_size * dpi / 72.0) as u16;
+
+    fonts
+        .iter()
+        .filter(|attr| {
+            if !font_dirs.candidates(attr).is_empty() || !built_in.candidates(attr).is_empty() {
+                return false;
+            }
+            match locator.load_fonts(std::slice::from_ref(*attr), &mut HashSet::new(), pixel_size) {
+                Ok(located) => located.is_empty(),
+                Err(err) => {
+                    log::trace!("locating {}: {:#}", attr, err);
+                    true
+                }
+            }
+        })
+        .cloned()
+        .collect()
+}
+
 /// Matches and loads fonts for a given input style
 pub struct FontConfiguration {
     inner: Rc<FontConfigInner>,
@@ -1076,6 +1123,12 @@ impl FontConfiguration {
         self.inner.config_changed(config)
     }
 
+    /// Adopt a new config handle without discarding any loaded fonts.
+    /// Used when a reload didn't touch any font related config.
+    pub fn set_config(&self, config: &ConfigHandle) {
+        *self.inner.config.borrow_mut() = config.clone();
+    }
+
     pub fn config(&self) -> ConfigHandle {
         self.inner.config.borrow().clone()
     }
@@ -1,5 +1,7 @@
 use crate::parser::ParsedFont;
-use crate::shaper::{FallbackIdx, FontMetrics, FontShaper, GlyphInfo, PresentationWidth};
+use crate::shaper::{
+    explicit_presentation, FallbackIdx, FontMetrics, FontShaper, GlyphInfo, PresentationWidth,
+};
 use crate::units::*;
 use crate::{ftwrap, hbwrap as harfbuzz};
 use anyhow::{anyhow, Context};
@@ -45,7 +47,13 @@ fn get_only_char(s: &str) -> Option<char> {
     }
 }
 
-fn make_glyphinfo(text: &str, num_cells: u8, font_idx: usize, info: &Info) -> GlyphInfo {
+fn make_glyphinfo(
+    text: &str,
+    num_cells: u8,
+    font_idx: usize,
+    info: &Info,
+    presentation: Option<Presentation>,
+) -> GlyphInfo {
     let is_space = text == " ";
     let only_char = get_only_char(text);
     GlyphInfo {
@@ -61,6 +69,7 @@ fn make_glyphinfo(text: &str, num_cells: u8, font_idx: usize, info: &Info) -> Gl
         y_advance: PixelLength::new(f64::from(info.y_advance) / 64.0),
         x_offset: PixelLength::new(f64::from(info.x_offset) / 64.0),
         y_offset: PixelLength::new(f64::from(info.y_offset) / 64.0),
+        presentation,
     }
 }
 
@@ -87,6 +96,7 @@ pub struct HarfbuzzShaper {
     metrics: RefCell<HashMap<MetricsKey, FontMetrics>>,
     features: Vec<harfbuzz::hb_feature_t>,
     lang: harfbuzz::hb_language_t,
+    presentation_overrides: HashMap<u32, Presentation>,
 }
 
 /// Make a string holding a set of unicode replacement
@@ -134,6 +144,19 @@ impl HarfbuzzShaper {
             .filter_map(|s| harfbuzz::feature_from_string(s).ok())
             .collect();
 
+        let presentation_overrides = config::EmojiPresentationOverride::compile_to_map(
+            &config.text().emoji_presentation_overrides,
+        )
+        .into_iter()
+        .map(|(codepoint, presentation)| {
+            let presentation = match presentation {
+                config::EmojiPresentation::Text => Presentation::Text,
+                config::EmojiPresentation::Emoji => Presentation::Emoji,
+            };
+            (codepoint, presentation)
+        })
+        .collect();
+
         Ok(Self {
             fonts,
             handles,
@@ -141,6 +164,7 @@ impl HarfbuzzShaper {
             metrics: RefCell::new(HashMap::new()),
             features,
             lang,
+            presentation_overrides,
         })
     }
 
@@ -535,7 +559,13 @@ impl HarfbuzzShaper {
                 let weighted_cell_width = weighted_cell_width.min(remaining_cells);
                 remaining_cells = remaining_cells.saturating_sub(weighted_cell_width);
 
-                let glyph = make_glyphinfo(substr, weighted_cell_width, font_idx, info);
+                let glyph = make_glyphinfo(
+                    substr,
+                    weighted_cell_width,
+                    font_idx,
+                    info,
+                    explicit_presentation(substr, &self.presentation_overrides),
+                );
 
                 cluster.push(glyph);
                 direct_clusters += 1;
@@ -906,6 +936,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "b",
@@ -921,6 +952,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "c",
@@ -936,6 +968,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
@@ -974,6 +1007,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
@@ -1014,6 +1048,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "-",
@@ -1029,6 +1064,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
@@ -1067,6 +1103,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "-",
@@ -1082,6 +1119,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "-",
@@ -1097,6 +1135,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
@@ -1136,6 +1175,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: " ",
@@ -1151,6 +1191,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "x",
@@ -1166,6 +1207,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
@@ -1205,6 +1247,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "\u{3000}",
@@ -1220,6 +1263,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
     GlyphInfo {
         text: "x",
@@ -1235,6 +1279,7 @@ mod test {
         y_advance: 0.0,
         x_offset: 0.0,
         y_offset: 0.0,
+        presentation: None,
     },
 ]
 "#
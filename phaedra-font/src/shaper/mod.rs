@@ -1,6 +1,7 @@
 use crate::parser::ParsedFont;
 use crate::units::PixelLength;
 use config::observers::*;
+use std::collections::HashMap;
 use std::ops::Range;
 use termwiz::cell::Presentation;
 use termwiz::cellcluster::CellCluster;
@@ -39,6 +40,27 @@ pub struct GlyphInfo {
     pub x_offset: PixelLength,
     /// Destination render offset
     pub y_offset: PixelLength,
+    /// The presentation explicitly requested for this glyph, either by
+    /// a variation selector in its text or by an entry in
+    /// `emoji_presentation_overrides`
+    pub presentation: Option<Presentation>,
+}
+
+/// Returns the presentation explicitly requested for `text`.
+/// A configured override for its first codepoint takes precedence
+/// over a VS15 (text) or VS16 (emoji) variation selector.
+pub fn explicit_presentation(
+    text: &str,
+    overrides: &HashMap<u32, Presentation>,
+) -> Option<Presentation> {
+    if let Some(presentation) = text.chars().next().and_then(|c| overrides.get(&(c as u32))) {
+        return Some(*presentation);
+    }
+    text.chars().find_map(|c| match c {
+        '\u{FE0E}' => Some(Presentation::Text),
+        '\u{FE0F}' => Some(Presentation::Emoji),
+        _ => None,
+    })
 }
 
 /// Represents a numbered index in the fallback sequence for a `NamedFont`.
@@ -0,0 +1,439 @@
+//! Recordings of the render commands painted by the GUI, so that the
+//! frames leading up to a rendering glitch can be reproduced from a
+//! bug report.
+//!
+//! When `runtime.record_frames` is configured, the GUI appends one
+//! `RecordedFrame` per painted frame to the recording.  The commands of
+//! a pane are only stored when they differ from those it painted in the
+//! prior recorded frame, which keeps the recording small while the
+//! panes are idle.  `replay` resolves those deltas back into the full
+//! list of commands for each frame, and `phaedra-gui replay-frames`
+//! executes them.
+//!
+//! Each record is a little endian u32 length followed by that many
+//! bytes of zstd compressed varbincode, so that a recording can be
+//! appended to cheaply and a record that was still being written when
+//! the file was read can be detected and ignored.
+use anyhow::Context;
+use phaedra_render_command::RenderCommand;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Recording stops once the file would grow beyond this size, no
+/// matter how many frames `max_frames` allows
+pub const MAX_RECORDING_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The commands of one pane in a recorded frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedPane {
+    pub pane_id: usize,
+    pub command_hash: u64,
+    /// None if the pane painted the same commands in the prior
+    /// recorded frame
+    pub commands: Option<Vec<RenderCommand>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Counts the frames written to the recording, across all windows
+    pub frame: u64,
+    pub window_id: usize,
+    pub pixel_width: usize,
+    pub pixel_height: usize,
+    pub config_generation: usize,
+    /// The `RenderCommand::content_hash` of all of the commands of the
+    /// frame, in the order in which they were executed
+    pub content_hash: u64,
+    pub background: Vec<RenderCommand>,
+    pub panes: Vec<RecordedPane>,
+    /// The tab bar, splits, borders and modal
+    pub chrome: Vec<RenderCommand>,
+}
+
+/// Describes the window that painted a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub window_id: usize,
+    pub pixel_width: usize,
+    pub pixel_height: usize,
+    pub config_generation: usize,
+}
+
+/// The commands painted for one pane, as passed to
+/// `FrameRecorder::write_frame`
+#[derive(Debug, Clone, Copy)]
+pub struct PaneCommands<'a> {
+    pub pane_id: usize,
+    pub command_hash: u64,
+    pub commands: &'a [RenderCommand],
+}
+
+/// Appends frames to a recording
+pub struct FrameRecorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    max_frames: usize,
+    frames_written: u64,
+    bytes_written: u64,
+    /// The command hash of each pane as of the last frame that it
+    /// was recorded in
+    pane_hashes: HashMap<usize, u64>,
+}
+
+impl FrameRecorder {
+    /// Creates the recording at `path`, replacing any prior one.
+    /// At most `max_frames` frames are written to it.
+    pub fn create(path: &Path, max_frames: usize) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            max_frames,
+            frames_written: 0,
+            bytes_written: 0,
+            pane_hashes: HashMap::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Appends a frame and flushes it out, so that the recording is
+    /// usable even if the GUI doesn't exit cleanly.  Returns false,
+    /// without writing anything, once the recording is full.
+    pub fn write_frame(
+        &mut self,
+        info: FrameInfo,
+        background: &[RenderCommand],
+        panes: &[PaneCommands],
+        chrome: &[RenderCommand],
+    ) -> anyhow::Result<bool> {
+        if self.frames_written >= self.max_frames as u64 {
+            return Ok(false);
+        }
+
+        let content_hash = RenderCommand::content_hash_iter(
+            background
+                .iter()
+                .chain(panes.iter().flat_map(|pane| pane.commands.iter()))
+                .chain(chrome.iter()),
+        );
+        let frame = RecordedFrame {
+            frame: self.frames_written,
+            window_id: info.window_id,
+            pixel_width: info.pixel_width,
+            pixel_height: info.pixel_height,
+            config_generation: info.config_generation,
+            content_hash,
+            background: background.to_vec(),
+            panes: panes
+                .iter()
+                .map(|pane| RecordedPane {
+                    pane_id: pane.pane_id,
+                    command_hash: pane.command_hash,
+                    commands: if self.pane_hashes.get(&pane.pane_id) == Some(&pane.command_hash) {
+                        None
+                    } else {
+                        Some(pane.commands.to_vec())
+                    },
+                })
+                .collect(),
+            chrome: chrome.to_vec(),
+        };
+
+        let record = encode_frame(&frame)?;
+        let record_bytes = 4 + record.len() as u64;
+        if self.bytes_written + record_bytes > MAX_RECORDING_BYTES {
+            return Ok(false);
+        }
+        self.file
+            .write_all(&(record.len() as u32).to_le_bytes())
+            .and_then(|_| self.file.write_all(&record))
+            .and_then(|_| self.file.flush())
+            .with_context(|| format!("writing {}", self.path.display()))?;
+        self.bytes_written += record_bytes;
+        self.frames_written += 1;
+        for pane in panes {
+            self.pane_hashes.insert(pane.pane_id, pane.command_hash);
+        }
+        Ok(true)
+    }
+}
+
+fn encode_frame(frame: &RecordedFrame) -> anyhow::Result<Vec<u8>> {
+    let mut uncompressed = Vec::new();
+    let mut encode = varbincode::Serializer::new(&mut uncompressed);
+    frame
+        .serialize(&mut encode)
+        .context("serializing recorded frame")?;
+    zstd::encode_all(uncompressed.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)
+        .context("compressing recorded frame")
+}
+
+fn decode_frame(record: &[u8]) -> anyhow::Result<RecordedFrame> {
+    let uncompressed = zstd::decode_all(record).context("decompressing recorded frame")?;
+    let mut decode = varbincode::Deserializer::new(uncompressed.as_slice());
+    RecordedFrame::deserialize(&mut decode).context("parsing recorded frame")
+}
+
+/// Reads all of the frames in the recording at `path`.  A record at
+/// the end of the file that is incomplete, such as one that was still
+/// being written when the file was read, is ignored.
+pub fn read_recording(path: &Path) -> anyhow::Result<Vec<RecordedFrame>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut frames = vec![];
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+        let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+        match reader.read_exact(&mut record) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+        frames.push(
+            decode_frame(&record)
+                .with_context(|| format!("frame {} of {}", frames.len(), path.display()))?,
+        );
+    }
+    Ok(frames)
+}
+
+/// A recorded frame with the commands of its panes resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayedFrame {
+    pub frame: u64,
+    pub window_id: usize,
+    pub pixel_width: usize,
+    pub pixel_height: usize,
+    pub config_generation: usize,
+    /// The hash recorded for the frame
+    pub recorded_hash: u64,
+    /// All of the commands of the frame, in the order in which they
+    /// were executed
+    pub commands: Vec<RenderCommand>,
+}
+
+impl ReplayedFrame {
+    pub fn content_hash(&self) -> u64 {
+        RenderCommand::content_hash(&self.commands)
+    }
+
+    /// Returns true if the replayed commands are the ones that were
+    /// recorded
+    pub fn matches_recording(&self) -> bool {
+        self.content_hash() == self.recorded_hash
+    }
+}
+
+/// Calls `execute` with each of the recorded frames in turn, with the
+/// commands of each pane resolved against the prior frames.  Stops at
+/// the first error returned by `execute`.
+pub fn replay<F>(frames: &[RecordedFrame], mut execute: F) -> anyhow::Result<()>
+where
+    F: FnMut(&ReplayedFrame) -> anyhow::Result<()>,
+{
+    let mut pane_commands: HashMap<usize, &[RenderCommand]> = HashMap::new();
+    for frame in frames {
+        let mut commands = frame.background.clone();
+        for pane in &frame.panes {
+            if let Some(recorded) = &pane.commands {
+                pane_commands.insert(pane.pane_id, recorded.as_slice());
+            }
+            let resolved = pane_commands.get(&pane.pane_id).with_context(|| {
+                format!(
+                    "frame {} refers to the commands of pane {}, \
+                     which have not been recorded",
+                    frame.frame, pane.pane_id
+                )
+            })?;
+            commands.extend_from_slice(resolved);
+        }
+        commands.extend_from_slice(&frame.chrome);
+
+        execute(&ReplayedFrame {
+            frame: frame.frame,
+            window_id: frame.window_id,
+            pixel_width: frame.pixel_width,
+            pixel_height: frame.pixel_height,
+            config_generation: frame.config_generation,
+            recorded_hash: frame.content_hash,
+            commands,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use phaedra_color_types::LinearRgba;
+    use phaedra_render_command::LayerId;
+
+    fn fill(x: f32, color: LinearRgba) -> RenderCommand {
+        RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(x, 0., 10., 16.),
+            color,
+            hsv: None,
+        }
+    }
+
+    fn pane_commands(pane_id: usize, commands: &[RenderCommand]) -> PaneCommands {
+        PaneCommands {
+            pane_id,
+            command_hash: RenderCommand::content_hash(commands),
+            commands,
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let dir =
+            std::env::temp_dir().join(format!("phaedra-frame-recording-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frames.rec");
+
+        let red = LinearRgba(1., 0., 0., 1.);
+        let blue = LinearRgba(0., 0., 1., 1.);
+        let background = vec![RenderCommand::Clear {
+            color: LinearRgba(0., 0., 0., 0.),
+            layer: None,
+        }];
+        let chrome = vec![RenderCommand::DrawLine {
+            layer: LayerId::overlay(),
+            from: euclid::point2(0., 16.),
+            to: euclid::point2(80., 16.),
+            width: 1.,
+            color: blue,
+        }];
+        let first = vec![fill(0., red), RenderCommand::SetClipRect(None)];
+        let second = vec![fill(10., red)];
+        let changed = vec![fill(10., blue), RenderCommand::Nop];
+        let info = FrameInfo {
+            window_id: 1,
+            pixel_width: 80,
+            pixel_height: 32,
+            config_generation: 3,
+        };
+
+        // The second pane changes in the last frame only
+        let frames = [
+            vec![first.clone(), second.clone()],
+            vec![first.clone(), second.clone()],
+            vec![first.clone(), changed.clone()],
+        ];
+        let mut recorder = FrameRecorder::create(&path, 10).unwrap();
+        for panes in &frames {
+            let panes = [pane_commands(1, &panes[0]), pane_commands(2, &panes[1])];
+            assert!(recorder
+                .write_frame(info, &background, &panes, &chrome)
+                .unwrap());
+        }
+        drop(recorder);
+        // A partially written record is ignored
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[200, 0, 0, 0, 1, 2])
+            .unwrap();
+
+        let recording = read_recording(&path).unwrap();
+        assert_eq!(recording.len(), 3);
+        assert!(recording[0]
+            .panes
+            .iter()
+            .all(|pane| pane.commands.is_some()));
+        assert!(recording[1]
+            .panes
+            .iter()
+            .all(|pane| pane.commands.is_none()));
+        assert_eq!(recording[2].panes[0].commands, None);
+        assert_eq!(recording[2].panes[1].commands, Some(changed.clone()));
+
+        let mut replayed = vec![];
+        replay(&recording, |frame| {
+            replayed.push(frame.clone());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(replayed.len(), 3);
+        for (frame, panes) in replayed.iter().zip(frames.iter()) {
+            let mut expected = background.clone();
+            expected.extend(panes.iter().flatten().cloned());
+            expected.extend(chrome.iter().cloned());
+            assert_eq!(frame.commands, expected);
+            assert_eq!(frame.content_hash(), RenderCommand::content_hash(&expected));
+            assert!(frame.matches_recording());
+            assert_eq!((frame.pixel_width, frame.pixel_height), (80, 32));
+            assert_eq!(frame.config_generation, 3);
+        }
+        assert_ne!(replayed[1].content_hash(), replayed[2].content_hash());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recording_is_capped() {
+        let dir = std::env::temp_dir().join(format!(
+            "phaedra-frame-recording-cap-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frames.rec");
+        let info = FrameInfo {
+            window_id: 1,
+            pixel_width: 80,
+            pixel_height: 32,
+            config_generation: 1,
+        };
+
+        let mut recorder = FrameRecorder::create(&path, 2).unwrap();
+        for _ in 0..2 {
+            assert!(recorder.write_frame(info, &[], &[], &[]).unwrap());
+        }
+        assert!(!recorder.write_frame(info, &[], &[], &[]).unwrap());
+        assert_eq!(recorder.frames_written(), 2);
+        assert_eq!(read_recording(&path).unwrap().len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_requires_recorded_commands() {
+        let frame = RecordedFrame {
+            frame: 0,
+            window_id: 1,
+            pixel_width: 80,
+            pixel_height: 32,
+            config_generation: 1,
+            content_hash: 0,
+            background: vec![],
+            panes: vec![RecordedPane {
+                pane_id: 4,
+                command_hash: 0,
+                commands: None,
+            }],
+            chrome: vec![],
+        };
+        let err = replay(&[frame], |_| Ok(())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "frame 0 refers to the commands of pane 4, which have not been recorded"
+        );
+    }
+}
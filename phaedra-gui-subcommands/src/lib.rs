@@ -101,6 +101,13 @@ pub struct StartCommand {
     #[arg(long, requires = "domain")]
     pub attach: bool,
 
+    /// Rebuild the windows, tabs and panes that were saved while
+    /// `mux.save_session_layout` was enabled, respawning the command
+    /// of each pane in its saved directory.  PROG is only spawned
+    /// if there is no saved layout that can be restored.
+    #[arg(long)]
+    pub resurrect: bool,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `phaedra start -- bash -l` will spawn bash
     /// as if it were a login shell. [aliases: -e]
@@ -230,6 +237,31 @@ pub struct LsFontsCommand {
     pub rasterize_ascii: bool,
 }
 
+#[derive(Debug, Parser, Clone)]
+pub struct LsGpusCommand {
+    /// Only list adapters that aren't software renderers
+    #[arg(long)]
+    pub hardware_only: bool,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ReplayFramesCommand {
+    /// The recording written while runtime.record_frames was configured
+    #[arg(value_hint=ValueHint::FilePath)]
+    pub path: PathBuf,
+
+    /// Print the content hash of each frame, so that the frame at
+    /// which two recordings diverge can be found
+    #[arg(long)]
+    pub hashes: bool,
+
+    /// Rasterize each frame on the CPU and write it to this directory
+    /// as a PNG.  Glyphs and images are not part of the recording, so
+    /// only solid colors are drawn.
+    #[arg(long, value_hint=ValueHint::DirPath)]
+    pub png_dir: Option<PathBuf>,
+}
+
 #[derive(Debug, Parser, Clone)]
 pub struct ShowKeysCommand {
     /// Show the keys as lua config statements
@@ -0,0 +1,274 @@
+//! Tracks the in-memory caches used while rendering so that their
+//! combined size can be kept within `cache.soft_budget_bytes`, and so
+//! that their occupancy can be inspected from the debug overlay and
+//! from lua.
+use config::CacheConfig;
+use phaedra_dynamic::{FromDynamic, ToDynamic};
+use std::cell::Cell;
+
+/// A point-in-time view of one registered cache
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct CacheStats {
+    pub name: String,
+    pub entries: usize,
+    pub capacity: usize,
+    pub approx_bytes: usize,
+}
+luahelper::impl_lua_conversion_dynamic!(CacheStats);
+
+struct RegisteredCache<C> {
+    name: &'static str,
+    /// Approximate size of one entry, including the heap data that
+    /// it typically owns
+    entry_bytes: usize,
+    len: fn(&C) -> usize,
+    capacity: fn(&C) -> usize,
+    trim: fn(&C, usize),
+}
+
+/// The caches that can be reached through a context value of type `C`,
+/// which is the `TermWindow` in practice
+pub struct CacheRegistry<C> {
+    caches: Vec<RegisteredCache<C>>,
+    frames: Cell<u64>,
+}
+
+impl<C> CacheRegistry<C> {
+    pub fn new() -> Self {
+        Self {
+            caches: vec![],
+            frames: Cell::new(0),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        entry_bytes: usize,
+        len: fn(&C) -> usize,
+        capacity: fn(&C) -> usize,
+        trim: fn(&C, usize),
+    ) {
+        self.caches.push(RegisteredCache {
+            name,
+            entry_bytes,
+            len,
+            capacity,
+            trim,
+        });
+    }
+
+    pub fn snapshot(&self, ctx: &C) -> Vec<CacheStats> {
+        self.caches
+            .iter()
+            .map(|cache| {
+                let entries = (cache.len)(ctx);
+                CacheStats {
+                    name: cache.name.to_string(),
+                    entries,
+                    capacity: (cache.capacity)(ctx),
+                    approx_bytes: entries * cache.entry_bytes,
+                }
+            })
+            .collect()
+    }
+
+    /// Called once per painted frame.  Every `rebalance_interval_frames`
+    /// frames, trims any caches that are over their share of the budget.
+    /// Returns the number of caches that were trimmed.
+    pub fn frame(&self, ctx: &C, config: &CacheConfig) -> usize {
+        let frames = self.frames.get() + 1;
+        self.frames.set(frames);
+        if frames < config.rebalance_interval_frames.max(1) {
+            return 0;
+        }
+        self.frames.set(0);
+        match config.soft_budget_bytes {
+            Some(budget) => self.rebalance(ctx, budget),
+            None => 0,
+        }
+    }
+
+    pub fn rebalance(&self, ctx: &C, budget: usize) -> usize {
+        let stats = self.snapshot(ctx);
+        let entry_bytes: Vec<usize> = self.caches.iter().map(|c| c.entry_bytes).collect();
+        let trims = compute_trims(&stats, &entry_bytes, budget);
+        for &(idx, target) in &trims {
+            log::debug!(
+                "cache budget: trimming {} from {} to {} entries",
+                stats[idx].name,
+                stats[idx].entries,
+                target
+            );
+            (self.caches[idx].trim)(ctx, target);
+        }
+        metrics::histogram!("gui.cache.rebalance.trimmed").record(trims.len() as f64);
+        trims.len()
+    }
+}
+
+/// Decide which caches to trim, and to how many entries, so that their
+/// combined size fits within `budget`.  Each cache is entitled to a
+/// share of the budget in proportion to its configured capacity; only
+/// caches using more than their share are trimmed, and only down to it.
+/// Returns (index, target entry count) pairs.
+fn compute_trims(
+    stats: &[CacheStats],
+    entry_bytes: &[usize],
+    budget: usize,
+) -> Vec<(usize, usize)> {
+    let total: usize = stats.iter().map(|s| s.approx_bytes).sum();
+    if total <= budget {
+        return vec![];
+    }
+
+    let capacity_bytes: Vec<u128> = stats
+        .iter()
+        .zip(entry_bytes)
+        .map(|(s, &bytes)| s.capacity as u128 * bytes as u128)
+        .collect();
+    let total_capacity: u128 = capacity_bytes.iter().sum();
+    if total_capacity == 0 {
+        return vec![];
+    }
+
+    stats
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, s)| {
+            let share = (budget as u128 * capacity_bytes[idx] / total_capacity) as usize;
+            if s.approx_bytes <= share || entry_bytes[idx] == 0 {
+                return None;
+            }
+            Some((idx, share / entry_bytes[idx]))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn stats(entries: usize, capacity: usize, entry_bytes: usize) -> CacheStats {
+        CacheStats {
+            name: String::new(),
+            entries,
+            capacity,
+            approx_bytes: entries * entry_bytes,
+        }
+    }
+
+    #[test]
+    fn nothing_is_trimmed_within_budget() {
+        let caches = [stats(10, 100, 10), stats(50, 100, 10)];
+        assert_eq!(compute_trims(&caches, &[10, 10], 600), vec![]);
+    }
+
+    #[test]
+    fn only_caches_over_their_share_are_trimmed() {
+        // Equal capacities, so each gets half of the 1000 byte budget
+        let caches = [stats(10, 100, 10), stats(90, 100, 10)];
+        assert_eq!(compute_trims(&caches, &[10, 10], 1000), vec![]);
+        assert_eq!(compute_trims(&caches, &[10, 10], 800), vec![(1, 40)]);
+
+        // Shares follow capacity in bytes: 1/4 and 3/4 of 800
+        let caches = [stats(100, 100, 10), stats(100, 100, 30)];
+        assert_eq!(
+            compute_trims(&caches, &[10, 30], 800),
+            vec![(0, 20), (1, 20)]
+        );
+    }
+
+    struct FakeCaches {
+        lens: RefCell<Vec<usize>>,
+        trims: RefCell<Vec<(usize, usize)>>,
+    }
+
+    impl FakeCaches {
+        fn trim(&self, idx: usize, target: usize) {
+            self.trims.borrow_mut().push((idx, target));
+            self.lens.borrow_mut()[idx] = target;
+        }
+    }
+
+    fn registry() -> CacheRegistry<FakeCaches> {
+        let mut registry = CacheRegistry::new();
+        registry.register(
+            "small",
+            10,
+            |c: &FakeCaches| c.lens.borrow()[0],
+            |_| 100,
+            |c, target| c.trim(0, target),
+        );
+        registry.register(
+            "large",
+            100,
+            |c: &FakeCaches| c.lens.borrow()[1],
+            |_| 100,
+            |c, target| c.trim(1, target),
+        );
+        registry
+    }
+
+    #[test]
+    fn trim_callbacks_receive_target_sizes() {
+        let caches = FakeCaches {
+            lens: RefCell::new(vec![100, 100]),
+            trims: RefCell::new(vec![]),
+        };
+        let registry = registry();
+        assert_eq!(
+            registry.snapshot(&caches),
+            vec![
+                CacheStats {
+                    name: "small".to_string(),
+                    entries: 100,
+                    capacity: 100,
+                    approx_bytes: 1000,
+                },
+                CacheStats {
+                    name: "large".to_string(),
+                    entries: 100,
+                    capacity: 100,
+                    approx_bytes: 10000,
+                },
+            ]
+        );
+
+        // 1/11 of 5500 is 500 bytes, 10/11 is 5000
+        assert_eq!(registry.rebalance(&caches, 5500), 2);
+        assert_eq!(*caches.trims.borrow(), vec![(0, 50), (1, 50)]);
+
+        // Now within budget, so nothing further happens
+        assert_eq!(registry.rebalance(&caches, 5500), 0);
+        assert_eq!(caches.trims.borrow().len(), 2);
+    }
+
+    #[test]
+    fn rebalance_runs_every_n_frames() {
+        let caches = FakeCaches {
+            lens: RefCell::new(vec![100, 100]),
+            trims: RefCell::new(vec![]),
+        };
+        let registry = registry();
+        let config = CacheConfig {
+            soft_budget_bytes: Some(5500),
+            rebalance_interval_frames: 3,
+            ..CacheConfig::default()
+        };
+
+        assert_eq!(registry.frame(&caches, &config), 0);
+        assert_eq!(registry.frame(&caches, &config), 0);
+        assert!(caches.trims.borrow().is_empty());
+        assert_eq!(registry.frame(&caches, &config), 2);
+
+        let config = CacheConfig {
+            soft_budget_bytes: None,
+            rebalance_interval_frames: 1,
+            ..CacheConfig::default()
+        };
+        caches.lens.borrow_mut()[1] = 100;
+        assert_eq!(registry.frame(&caches, &config), 0);
+    }
+}
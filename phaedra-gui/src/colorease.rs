@@ -1,3 +1,4 @@
+use crate::render_command::HsbTransform;
 use config::EasingFunction;
 use config::observers::*;
 use std::time::{Duration, Instant};
@@ -57,25 +58,31 @@ impl ColorEase {
         }
     }
 
-    pub fn intensity_one_shot(&mut self) -> Option<(f32, Instant)> {
-        let start = self.start?;
-        let elapsed = start.elapsed().as_secs_f32();
-
-        let intensity = if elapsed < self.in_duration {
+    /// The intensity `elapsed` seconds after the start, or None once
+    /// it has faded out
+    fn intensity_at(&self, elapsed: f32) -> Option<f32> {
+        if elapsed < self.in_duration {
             Some(
                 self.in_function
                     .evaluate_at_position(elapsed / self.in_duration),
             )
         } else {
             let completion = (elapsed - self.in_duration) / self.out_duration;
-            if completion >= 1.0 {
+            // With no fade out, completion is NaN at the very end of
+            // the fade in, which is also complete
+            if completion >= 1.0 || completion.is_nan() {
                 None
             } else {
                 Some(1.0 - self.out_function.evaluate_at_position(completion))
             }
-        };
+        }
+    }
+
+    pub fn intensity_one_shot(&mut self) -> Option<(f32, Instant)> {
+        let start = self.start?;
+        let elapsed = start.elapsed().as_secs_f32();
 
-        match intensity {
+        match self.intensity_at(elapsed) {
             Some(i) => {
                 let now = Instant::now();
                 let fps = if self.in_function == EasingFunction::Constant
@@ -113,3 +120,189 @@ impl ColorEase {
         }
     }
 }
+
+/// A value that can be eased from one value to another
+pub trait Interpolate: Clone {
+    /// Returns the value a `fraction` of the way from `self`, at 0.0,
+    /// to `other`, at 1.0
+    fn interpolate(&self, other: &Self, fraction: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, fraction: f32) -> Self {
+        self + (other - self) * fraction
+    }
+}
+
+impl Interpolate for HsbTransform {
+    fn interpolate(&self, other: &Self, fraction: f32) -> Self {
+        self.lerp(other, fraction)
+    }
+}
+
+/// Eases a value from where it was towards a target, such as the hsb
+/// transform of a pane as it gains or loses focus.  The easing is the
+/// fade in of a ColorEase that has no fade out, so the value rests at
+/// the target once the fade in has completed.
+#[derive(Debug, Clone)]
+pub struct Transition<T: Interpolate> {
+    from: T,
+    to: T,
+    ease: ColorEase,
+}
+
+impl<T: Interpolate> Transition<T> {
+    /// A transition that has already arrived at `value`
+    pub fn settled(value: T) -> Self {
+        Self {
+            from: value.clone(),
+            to: value,
+            ease: ColorEase::new(0, EasingFunction::Linear, 0, EasingFunction::Linear, None),
+        }
+    }
+
+    pub fn target(&self) -> &T {
+        &self.to
+    }
+
+    /// Starts easing from the current value to `to` over `duration_ms`,
+    /// unless it is already headed there.  Changing direction part way
+    /// through starts from wherever it had got to, rather than jumping.
+    pub fn retarget(&mut self, to: T, duration_ms: u64, function: EasingFunction)
+    where
+        T: PartialEq,
+    {
+        if self.to == to {
+            return;
+        }
+        let from = self.value_at(self.elapsed());
+        *self = Self {
+            from,
+            to,
+            ease: ColorEase::new(
+                duration_ms,
+                function,
+                0,
+                EasingFunction::Linear,
+                Some(Instant::now()),
+            ),
+        };
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.ease
+            .start
+            .map_or(f32::INFINITY, |start| start.elapsed().as_secs_f32())
+    }
+
+    /// The value `elapsed` seconds after the transition started
+    pub fn value_at(&self, elapsed: f32) -> T {
+        match self.ease.intensity_at(elapsed) {
+            Some(fraction) if self.ease.start.is_some() => {
+                self.from.interpolate(&self.to, fraction)
+            }
+            _ => self.to.clone(),
+        }
+    }
+
+    /// Returns the current value and, while it is still changing,
+    /// when it should next be sampled
+    pub fn value(&mut self) -> (T, Option<Instant>) {
+        match self.ease.intensity_one_shot() {
+            Some((fraction, next)) => (self.from.interpolate(&self.to, fraction), Some(next)),
+            None => (self.to.clone(), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dim() -> HsbTransform {
+        HsbTransform {
+            hue: 1.,
+            saturation: 0.9,
+            brightness: 0.8,
+        }
+    }
+
+    fn assert_close(a: &HsbTransform, b: &HsbTransform) {
+        for (a, b) in [
+            (a.hue, b.hue),
+            (a.saturation, b.saturation),
+            (a.brightness, b.brightness),
+        ] {
+            assert!((a - b).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn fade_in_without_fade_out() {
+        let ease = ColorEase::new(100, EasingFunction::Linear, 0, EasingFunction::Linear, None);
+        assert_eq!(ease.intensity_at(0.), Some(0.));
+        assert_eq!(ease.intensity_at(0.05), Some(0.5));
+        assert_eq!(ease.intensity_at(0.1), None);
+        assert_eq!(ease.intensity_at(1.), None);
+    }
+
+    #[test]
+    fn focus_timeline() {
+        let mut transition = Transition::settled(HsbTransform::IDENTITY);
+        assert_eq!(transition.value_at(0.), HsbTransform::IDENTITY);
+
+        transition.retarget(dim(), 200, EasingFunction::Linear);
+        assert_eq!(transition.target(), &dim());
+        assert_close(&transition.value_at(0.), &HsbTransform::IDENTITY);
+        assert_close(
+            &transition.value_at(0.1),
+            &HsbTransform {
+                hue: 1.,
+                saturation: 0.95,
+                brightness: 0.9,
+            },
+        );
+        assert_eq!(transition.value_at(0.2), dim());
+        assert_eq!(transition.value_at(5.), dim());
+
+        // Retargeting to where it is already headed doesn't restart it
+        let before = transition.clone();
+        transition.retarget(dim(), 200, EasingFunction::Linear);
+        assert_eq!(transition.ease, before.ease);
+    }
+
+    #[test]
+    fn eased_timeline() {
+        let mut transition = Transition::settled(0.);
+        transition.retarget(1., 100, EasingFunction::EaseOut);
+        let samples: Vec<f32> = (0..=10)
+            .map(|i| transition.value_at(i as f32 / 100.))
+            .collect();
+        assert_eq!(samples[0], 0.);
+        assert_eq!(
+            samples[5],
+            EasingFunction::EaseOut.evaluate_at_position(0.5)
+        );
+        assert!(samples.windows(2).all(|w| w[0] <= w[1]), "{samples:?}");
+        assert_eq!(samples[10], 1.);
+    }
+
+    #[test]
+    fn no_duration_is_immediate() {
+        let mut transition = Transition::settled(HsbTransform::IDENTITY);
+        transition.retarget(dim(), 0, EasingFunction::Ease);
+        assert_eq!(transition.value_at(0.), dim());
+        assert_eq!(transition.value().0, dim());
+    }
+
+    #[test]
+    fn reversing_starts_from_the_current_value() {
+        let mut transition = Transition::<f32>::settled(0.);
+        transition.retarget(1., 60_000, EasingFunction::Linear);
+        transition.retarget(0., 60_000, EasingFunction::Linear);
+        // Barely any time has passed, so it turns back from about 0.0
+        // rather than jumping to 1.0
+        assert!(transition.from < 0.01, "{}", transition.from);
+        assert_eq!(transition.target(), &0.);
+    }
+}
@@ -1,4 +1,3 @@
-use crate::inputmap::InputMap;
 use config::observers::*;
 use config::keyassignment::*;
 use config::window::WindowLevel;
@@ -54,6 +53,101 @@ fn us_layout_shift(s: &str) -> String {
     }
 }
 
+/// Merges the user's key assignments into `commands`.
+/// An assignment whose action matches an existing command contributes
+/// its key chord to that command rather than producing a duplicate entry.
+/// Other assignments get an entry of their own, described either by their
+/// CommandDef or, failing that, by the names of the effects they produce.
+/// Assignments from named key tables are included without a chord,
+/// as the chord only applies while that table is active.
+pub fn merge_key_bindings(commands: &mut Vec<ExpandedCommand>, tables: &KeyTables) {
+    let mut default_keys = tables.default.iter().collect::<Vec<_>>();
+    default_keys.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut table_names = tables.by_name.keys().collect::<Vec<_>>();
+    table_names.sort();
+    let table_entries = table_names.into_iter().flat_map(|name| {
+        let mut entries = tables.by_name[name].iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries
+            .into_iter()
+            .flat_map(|(_, entries)| entries.iter().map(|entry| (None, entry)))
+    });
+
+    let assignments = default_keys
+        .into_iter()
+        .flat_map(|((keycode, mods), entries)| {
+            entries
+                .iter()
+                .map(move |entry| (Some((*mods, keycode.clone())), entry))
+        })
+        .chain(table_entries);
+
+    for (chord, entry) in assignments {
+        if let Some(existing) = commands.iter_mut().find(|cmd| cmd.action == entry.action) {
+            if let Some(chord) = chord {
+                if !existing.keys.contains(&chord) {
+                    existing.keys.push(chord);
+                }
+            }
+            continue;
+        }
+
+        let keys = chord.into_iter().collect();
+        if let Some(cmd) = derive_command_from_key_assignment(&entry.action) {
+            commands.push(ExpandedCommand {
+                brief: cmd.brief.into(),
+                doc: cmd.doc.into(),
+                keys,
+                action: entry.action.clone(),
+                menubar: cmd.menubar,
+                icon: cmd.icon.map(Cow::Borrowed),
+            });
+        } else if let Some(brief) = describe_assignment_effects(&entry.action) {
+            commands.push(ExpandedCommand {
+                brief: brief.into(),
+                doc: format!("{:?}", entry.action).into(),
+                keys,
+                action: entry.action.clone(),
+                menubar: &[],
+                icon: None,
+            });
+        }
+    }
+}
+
+/// Produces a label such as "Spawn tab, Emit event" from the
+/// names of the effects that an assignment interprets to.
+/// Returns None if the assignment has no effect.
+fn describe_assignment_effects(action: &KeyAssignment) -> Option<String> {
+    let mut names = vec![];
+    for effect in crate::interpret::interpret_assignment(action) {
+        names = effect.fold(names, &|mut names: Vec<&'static str>, effect| {
+            if !effect.is_nop() {
+                names.push(crate::execute::describe_effect(effect));
+            }
+            names
+        });
+    }
+    names.dedup();
+
+    if names.is_empty() {
+        return None;
+    }
+
+    let labels = names
+        .into_iter()
+        .map(|name| {
+            let mut label = name.replace('_', " ");
+            if let Some(first) = label.get_mut(0..1) {
+                first.make_ascii_uppercase();
+            }
+            label
+        })
+        .collect::<Vec<_>>();
+    Some(labels.join(", "))
+}
+
 /// `CommandDef` defines a command in the UI.
 pub struct CommandDef {
     /// Brief description
@@ -321,47 +415,7 @@ impl CommandDef {
         }
 
         // And sweep to pick up stuff from their key assignments
-        let inputmap = InputMap::new(config);
-        for ((keycode, mods), entry) in inputmap.keys.default.iter() {
-            if result
-                .iter()
-                .position(|cmd| cmd.action == entry.action)
-                .is_some()
-            {
-                continue;
-            }
-            if let Some(cmd) = derive_command_from_key_assignment(&entry.action) {
-                result.push(ExpandedCommand {
-                    brief: cmd.brief.into(),
-                    doc: cmd.doc.into(),
-                    keys: vec![(*mods, keycode.clone())],
-                    action: entry.action.clone(),
-                    menubar: cmd.menubar,
-                    icon: cmd.icon.map(Cow::Borrowed),
-                });
-            }
-        }
-        for table in inputmap.keys.by_name.values() {
-            for entry in table.values() {
-                if result
-                    .iter()
-                    .position(|cmd| cmd.action == entry.action)
-                    .is_some()
-                {
-                    continue;
-                }
-                if let Some(cmd) = derive_command_from_key_assignment(&entry.action) {
-                    result.push(ExpandedCommand {
-                        brief: cmd.brief.into(),
-                        doc: cmd.doc.into(),
-                        keys: vec![],
-                        action: entry.action.clone(),
-                        menubar: cmd.menubar,
-                        icon: cmd.icon.map(Cow::Borrowed),
-                    });
-                }
-            }
-        }
+        merge_key_bindings(&mut result, &config.key_bindings());
 
         result
     }
@@ -381,7 +435,7 @@ impl CommandDef {
     pub fn recreate_menubar(config: &ConfigHandle) {
         use window::os::macos::menu::*;
 
-        let inputmap = InputMap::new(config);
+        let inputmap = crate::inputmap::InputMap::new(config);
 
         let mut candidates_for_removal = vec![];
         #[allow(unexpected_cfgs)] // <https://github.com/SSheldon/rust-objc/issues/125>
@@ -674,6 +728,26 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Edit"],
             icon: Some("md_content_paste"),
         },
+        PasteAsOsc52(ClipboardPasteSource::Clipboard) => CommandDef {
+            brief: "Paste from clipboard as OSC 52".into(),
+            doc: "Sends the clipboard to the pane as an OSC 52 sequence, \
+                  so that a remote program can place it in its own clipboard"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("md_content_paste"),
+        },
+        PasteAsOsc52(ClipboardPasteSource::PrimarySelection) => CommandDef {
+            brief: "Paste primary selection as OSC 52".into(),
+            doc: "Sends the primary selection to the pane as an OSC 52 sequence, \
+                  so that a remote program can place it in its own primary selection"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: Some("md_content_paste"),
+        },
         ToggleFullScreen => CommandDef {
             brief: "Toggle full screen mode".into(),
             doc: "Switch between normal and full screen mode".into(),
@@ -902,6 +976,36 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Window"],
             icon: Some("cod_multiple_windows"),
         },
+        MovePaneToNewTab { keep_focus } => CommandDef {
+            brief: if *keep_focus {
+                "Move the active pane into its own tab, keeping focus".into()
+            } else {
+                "Move the active pane into its own tab".into()
+            },
+            doc: "Removes the active pane from its split and places it into a new tab".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("cod_multiple_windows"),
+        },
+        MovePaneToWindow(target) => CommandDef {
+            brief: match target {
+                MoveTarget::NewWindow => "Move the active pane into its own window".into(),
+                MoveTarget::Window(window_id) => {
+                    format!("Move the active pane to window {window_id}").into()
+                }
+                MoveTarget::Workspace(workspace) => {
+                    format!("Move the active pane to workspace {workspace}").into()
+                }
+            },
+            doc: "Removes the active pane from its split and places it into a new tab \
+                  in another window"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("cod_multiple_windows"),
+        },
         DecreaseFontSize => CommandDef {
             brief: "Decrease font size".into(),
             doc: "Scales the font size smaller by 10%".into(),
@@ -943,6 +1047,81 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["View", "Font Size"],
             icon: Some("md_format_size"),
         },
+        IncreasePaneFontSize => CommandDef {
+            brief: "Increase pane font size".into(),
+            doc: "Scales the font size of the current pane larger by 10%".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View", "Font Size"],
+            icon: Some("md_format_size"),
+        },
+        DecreasePaneFontSize => CommandDef {
+            brief: "Decrease pane font size".into(),
+            doc: "Scales the font size of the current pane smaller by 10%".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View", "Font Size"],
+            icon: Some("md_format_size"),
+        },
+        ResetPaneFontSize => CommandDef {
+            brief: "Reset pane font size".into(),
+            doc: "Restores the font size of the current pane to match the window".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View", "Font Size"],
+            icon: Some("md_format_size"),
+        },
+        SetWindowPadding(_) => CommandDef {
+            brief: "Set window padding".into(),
+            doc: "Changes the padding around the panes of the current window".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &[],
+            icon: None,
+        },
+        ResetWindowPadding => CommandDef {
+            brief: "Reset window padding".into(),
+            doc: "Restores the configured padding of the current window".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["View"],
+            icon: None,
+        },
+        AdjustWindowOpacity(delta) => {
+            let delta = delta.into_inner();
+            CommandDef {
+                brief: if delta < 0.0 {
+                    "Decrease window opacity".into()
+                } else {
+                    "Increase window opacity".into()
+                },
+                doc: format!("Changes the opacity of the window background by {delta}").into(),
+                keys: vec![],
+                args: &[ArgType::ActiveWindow],
+                menubar: &["View"],
+                icon: None,
+            }
+        }
+        SetWindowBackgroundBlur { enabled, .. } => CommandDef {
+            brief: if *enabled {
+                "Blur window background".into()
+            } else {
+                "Stop blurring window background".into()
+            },
+            doc: "Sets whether what is behind the window background is blurred".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &[],
+            icon: None,
+        },
+        ResetWindowBackground => CommandDef {
+            brief: "Reset window opacity and blur".into(),
+            doc: "Restores the configured opacity and blur of the window background".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["View"],
+            icon: None,
+        },
         SpawnTab(SpawnTabDomain::CurrentPaneDomain) => CommandDef {
             brief: "New Tab".into(),
             doc: "Create a new tab in the same domain as the current pane".into(),
@@ -995,7 +1174,10 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &[],
             icon: Some("md_open_in_new"),
         },
-        ActivateTab(-1) => CommandDef {
+        ActivateTab(TabIndex {
+            position: TabPosition::FromEnd(1),
+            ..
+        }) => CommandDef {
             brief: "Activate right-most tab".into(),
             doc: "Activates the tab on the far right".into(),
             keys: vec![(Modifiers::SUPER, "9".into())],
@@ -1003,10 +1185,13 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Window", "Select Tab"],
             icon: None,
         },
-        ActivateTab(n) => {
+        ActivateTab(TabIndex {
+            position: TabPosition::Absolute(n),
+            out_of_range,
+        }) => {
             let n = *n;
-            let ordinal = english_ordinal(n + 1);
-            let keys = if n >= 0 && n <= 7 {
+            let ordinal = english_ordinal(n as isize + 1);
+            let keys = if n <= 7 && *out_of_range == TabIndexOutOfRange::Ignore {
                 vec![(Modifiers::SUPER, (n + 1).to_string())]
             } else {
                 vec![]
@@ -1020,6 +1205,21 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 icon: None,
             }
         }
+        ActivateTab(TabIndex {
+            position: TabPosition::FromEnd(n),
+            ..
+        }) => {
+            let ordinal = english_ordinal(*n as isize);
+            CommandDef {
+                brief: format!("Activate {ordinal} Tab From The Right").into(),
+                doc: format!("Activates the {ordinal} tab, counting back from the right-most tab")
+                    .into(),
+                keys: vec![],
+                args: &[ArgType::ActiveWindow],
+                menubar: &["Window", "Select Tab"],
+                icon: None,
+            }
+        }
         ActivatePaneByIndex(n) => {
             let n = *n;
             let ordinal = english_ordinal(n as isize);
@@ -1052,6 +1252,81 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &[],
             icon: Some("md_fullscreen"),
         },
+        SetPaneRenderFrozen(true) => CommandDef {
+            brief: "Freeze the rendering of the current Pane".into(),
+            doc: "Keeps showing the current content of the pane, without \
+                  rendering new output or blinking the cursor"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
+        SetPaneRenderFrozen(false) => CommandDef {
+            brief: "Unfreeze the rendering of the current Pane".into(),
+            doc: "Resumes rendering the output of the current pane".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
+        SetPaneHighlightPattern(None) => CommandDef {
+            brief: "Clear the highlighted matches in the current Pane".into(),
+            doc: "Stops highlighting the matches of the pattern that was set \
+                  by SetPaneHighlightPattern"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
+        TogglePaneBroadcast { scope } => CommandDef {
+            brief: match scope {
+                BroadcastScope::Tab => "Toggle broadcasting input to the panes of the current Tab",
+                BroadcastScope::Window => "Toggle broadcasting input to the panes of the Window",
+                BroadcastScope::Selection => "Toggle broadcasting input to the selected panes",
+            }
+            .into(),
+            doc: "While broadcasting, what is typed, sent or pasted into the current pane \
+                  is also sent to the other panes in scope"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: Some("md_broadcast"),
+        },
+        SetPaneBroadcastParticipation(participation) => CommandDef {
+            brief: match participation {
+                PaneBroadcastParticipation::Default => "Include the current Pane in broadcasts",
+                PaneBroadcastParticipation::Selected => {
+                    "Select the current Pane for broadcasting"
+                }
+                PaneBroadcastParticipation::OptOut => "Exclude the current Pane from broadcasts",
+            }
+            .into(),
+            doc: "Sets how the current pane takes part in broadcasting input".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
+        TogglePaneEscapeLog { path } => CommandDef {
+            brief: match path {
+                Some(path) => format!(
+                    "Toggle logging the output of the current Pane to {}",
+                    path.display()
+                )
+                .into(),
+                None => "Toggle logging the output of the current Pane".into(),
+            },
+            doc: "Logs the escape sequences written by the program in the current pane, \
+                  for debugging programs that misbehave"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Help"],
+            icon: Some("cod_debug"),
+        },
         EmitEvent(name) => CommandDef {
             brief: format!("Emit event `{name}`").into(),
             doc: format!(
@@ -1431,6 +1706,26 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["View"],
             icon: Some("md_format_align_bottom"),
         },
+        ScrollToLastCommandStart => CommandDef {
+            brief: "Scroll to the last command".into(),
+            doc: "Scrolls the viewport to the prompt of the most recent \
+                  command that produced some output"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View"],
+            icon: Some("oct_terminal"),
+        },
+        SelectLastCommandOutput => CommandDef {
+            brief: "Select the output of the last command".into(),
+            doc: "Selects the output of the most recent command that \
+                  produced some, so that it can be copied"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("md_content_copy"),
+        },
         ScrollToTop => CommandDef {
             brief: "Scroll to the top".into(),
             doc: "Scrolls to the top of the viewport".into(),
@@ -1558,6 +1853,7 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             icon: None,
         },
         AdjustPaneSize(PaneDirection::Next | PaneDirection::Prev, _) => return None,
+        SetSplitSize(..) => return None,
         ActivatePaneDirection(PaneDirection::Next | PaneDirection::Prev) => return None,
         ActivatePaneDirection(PaneDirection::Left) => CommandDef {
             brief: "Activate Pane Left".into(),
@@ -2048,11 +2344,13 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         CopyTo(ClipboardCopyDestination::PrimarySelection),
         CopyTo(ClipboardCopyDestination::Clipboard),
         PasteFrom(ClipboardPasteSource::Clipboard),
+        PasteAsOsc52(ClipboardPasteSource::Clipboard),
         ClearScrollback(ScrollbackEraseMode::ScrollbackOnly),
         ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport),
         QuickSelect,
         CharSelect(CharSelectArguments::default()),
         ActivateCopyMode,
+        SelectLastCommandOutput,
         ClearKeyTableStack,
         ActivateCommandPalette,
         // ----------------- View
@@ -2060,10 +2358,18 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         IncreaseFontSize,
         ResetFontSize,
         ResetFontAndWindowSize,
+        IncreasePaneFontSize,
+        DecreasePaneFontSize,
+        ResetPaneFontSize,
+        ResetWindowPadding,
+        AdjustWindowOpacity(NotNan::new(-0.1).unwrap()),
+        AdjustWindowOpacity(NotNan::new(0.1).unwrap()),
+        ResetWindowBackground,
         ScrollByPage(NotNan::new(-1.0).unwrap()),
         ScrollByPage(NotNan::new(1.0).unwrap()),
         ScrollToTop,
         ScrollToBottom,
+        ScrollToLastCommandStart,
         // ----------------- Window
         ToggleFullScreen,
         ToggleAlwaysOnTop,
@@ -2098,17 +2404,19 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
             mode: PaneSelectMode::MoveToNewWindow,
             show_pane_ids: false,
         }),
+        MovePaneToNewTab { keep_focus: false },
+        MovePaneToWindow(MoveTarget::NewWindow),
         RotatePanes(RotationDirection::Clockwise),
         RotatePanes(RotationDirection::CounterClockwise),
-        ActivateTab(0),
-        ActivateTab(1),
-        ActivateTab(2),
-        ActivateTab(3),
-        ActivateTab(4),
-        ActivateTab(5),
-        ActivateTab(6),
-        ActivateTab(7),
-        ActivateTab(-1),
+        ActivateTab(TabIndex::absolute(0)),
+        ActivateTab(TabIndex::absolute(1)),
+        ActivateTab(TabIndex::absolute(2)),
+        ActivateTab(TabIndex::absolute(3)),
+        ActivateTab(TabIndex::absolute(4)),
+        ActivateTab(TabIndex::absolute(5)),
+        ActivateTab(TabIndex::absolute(6)),
+        ActivateTab(TabIndex::absolute(7)),
+        ActivateTab(TabIndex::last()),
         ActivateTabRelative(-1),
         ActivateTabRelative(1),
         ActivateWindow(0),
@@ -2142,6 +2450,7 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         OpenUri("https://github.com/PaleRoses/phaedra/discussions/".to_string()),
         OpenUri("https://github.com/PaleRoses/phaedra/issues/".to_string()),
         ShowDebugOverlay,
+        TogglePaneEscapeLog { path: None },
         // ----------------- Misc
         OpenLinkAtMouseCursor,
     ];
@@ -5060,6 +5060,7 @@ impl GlyphCache {
         width: u8,
     ) -> anyhow::Result<Sprite> {
         if let Some(sprite) = self.cursor_glyphs.get(&(shape, width)) {
+            self.atlas.mark_used(sprite);
             return Ok(sprite.clone());
         }
 
@@ -5159,6 +5160,7 @@ impl GlyphCache {
                 descender_plus_two: 0,
                 underline_height: *underline_height,
                 strike_row: 0,
+                overline_row: 0,
                 cell_size: cell_size.clone(),
             },
             _ => render_metrics.clone(),
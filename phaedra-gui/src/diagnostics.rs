@@ -0,0 +1,149 @@
+//! Writes a crash report when the GUI panics or loses its GPU device;
+//! see `phaedra_render_diagnostics::crash` for what goes into one, and
+//! `phaedra diag last-report` for finding the most recent one.
+//!
+//! Each painted frame is noted with `record_frame`, so that the report
+//! can say what was being drawn even when the panic happened outside
+//! of the window that was painting.
+use crate::render_plan::RenderPlan;
+use phaedra_render_diagnostics::crash::{
+    report_dir, report_file_name, write_report, CrashContext, ReportThrottle, MAX_FRAME_HASHES,
+    MAX_LOG_LINES,
+};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Default)]
+struct RecentFrames {
+    frame_hashes: VecDeque<u64>,
+    render_plan: Option<String>,
+    adapter: Option<String>,
+}
+
+impl RecentFrames {
+    fn record(&mut self, frame_hash: u64, render_plan: String, adapter: Option<&str>) {
+        if self.frame_hashes.len() == MAX_FRAME_HASHES {
+            self.frame_hashes.pop_front();
+        }
+        self.frame_hashes.push_back(frame_hash);
+        self.render_plan.replace(render_plan);
+        if self.adapter.as_deref() != adapter {
+            self.adapter = adapter.map(|adapter| adapter.to_string());
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref RECENT_FRAMES: Mutex<RecentFrames> = Mutex::new(RecentFrames::default());
+    static ref THROTTLE: Mutex<ReportThrottle> = Mutex::new(ReportThrottle::default());
+}
+
+/// Notes the plan of a frame that has just been built, for inclusion
+/// in a later crash report
+pub fn record_frame(plan: &RenderPlan, adapter: Option<&str>) {
+    if let Ok(mut frames) = RECENT_FRAMES.lock() {
+        frames.record(plan.content_hash(), plan.summary(), adapter);
+    }
+}
+
+/// Returns the context for a report about `reason`, made from the
+/// recorded frames, the current configuration and the log.
+/// This is called from the panic hook, so it doesn't wait for the
+/// frame record, which the panicking thread may be holding.
+pub fn crash_context(reason: &str) -> CrashContext {
+    let mut context = CrashContext {
+        reason: reason.to_string(),
+        version: config::phaedra_version().to_string(),
+        timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        ..CrashContext::default()
+    };
+
+    if let Ok(frames) = RECENT_FRAMES.try_lock() {
+        context.frame_hashes = frames.frame_hashes.iter().copied().collect();
+        context.render_plan = frames.render_plan.clone();
+        context.adapter = frames.adapter.clone();
+    }
+
+    let config = config::configuration();
+    context.config_generation = config.generation();
+    context.config_diff = config
+        .diff_from_default()
+        .iter()
+        .map(|delta| delta.to_string())
+        .collect();
+
+    let entries = env_bootstrap::ringlog::get_entries();
+    let skip = entries.len().saturating_sub(MAX_LOG_LINES);
+    context.log_lines = entries[skip..]
+        .iter()
+        .map(|entry| {
+            format!(
+                "{} {:<5} {} > {}",
+                entry.then.format("%H:%M:%S%.3f"),
+                entry.level,
+                entry.target,
+                entry.msg
+            )
+        })
+        .collect();
+
+    context
+}
+
+/// Writes a report for `context` to the cache directory, unless one
+/// has been written within the last minute, and returns its path
+pub fn write_crash_report(context: &CrashContext) -> Option<PathBuf> {
+    let permitted = THROTTLE
+        .try_lock()
+        .map(|mut throttle| throttle.permit(Instant::now()))
+        .unwrap_or(false);
+    if !permitted {
+        return None;
+    }
+
+    let file_name = report_file_name(
+        &chrono::Local::now().format("%Y%m%d-%H%M%S").to_string(),
+        std::process::id(),
+    );
+    match write_report(&report_dir(&config::CACHE_DIR), &file_name, context) {
+        Ok(path) => {
+            log::error!("wrote crash report to {}", path.display());
+            Some(path)
+        }
+        Err(err) => {
+            log::error!("failed to write crash report: {:#}", err);
+            None
+        }
+    }
+}
+
+/// Writes a report about `reason`, subject to the same limit as
+/// `write_crash_report`
+pub fn report_crash(reason: &str) -> Option<PathBuf> {
+    write_crash_report(&crash_context(reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recent_frames_are_bounded() {
+        let mut frames = RecentFrames::default();
+        for hash in 0..MAX_FRAME_HASHES as u64 + 5 {
+            frames.record(hash, format!("plan {hash}"), Some("WebGPU: test"));
+        }
+        assert_eq!(frames.frame_hashes.len(), MAX_FRAME_HASHES);
+        assert_eq!(frames.frame_hashes.front(), Some(&5));
+        assert_eq!(
+            frames.render_plan.as_deref(),
+            Some(format!("plan {}", MAX_FRAME_HASHES + 4).as_str())
+        );
+        assert_eq!(frames.adapter.as_deref(), Some("WebGPU: test"));
+
+        frames.record(0, "plan".to_string(), None);
+        assert_eq!(frames.adapter, None);
+    }
+}
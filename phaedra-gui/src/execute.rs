@@ -4,24 +4,57 @@ use crate::overlay::{
     confirm_quit_program, start_overlay, CopyModeParams, CopyOverlay, QuickSelectOverlay,
 };
 use crate::spawn::SpawnWhere;
+use crate::termwindow::broadcast::BroadcastInput;
 use crate::termwindow::keyevent::KeyTableArgs;
 use crate::termwindow::TermWindow;
 use anyhow::anyhow;
 use config::observers::{KeyInputObserver, WindowConfigObserver};
 use config::keyassignment::{
-    KeyAssignment, LauncherActionArgs, PaneDirection, RotationDirection, SpawnCommand, SplitSize,
+    KeyAssignment, LauncherActionArgs, MoveTarget, PaneDirection, RotationDirection, SpawnCommand,
+    SplitSize, WindowPaddingOverride,
 };
 use config::WindowCloseConfirmation;
+use futures::future::{FutureExt, LocalBoxFuture};
 use config::window::WindowLevel;
 use mux::pane::{Pane, Pattern as MuxPattern};
 use mux::tab::{SplitDirection, SplitRequest, SplitSize as MuxSplitSize};
 use mux::Mux;
+use phaedra_dynamic::ToDynamic;
+use std::future::Future;
 use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::escape::{Action, Esc, EscCode};
 use window::Connection;
 use window::{ConnectionOps, WindowOps};
 
+/// The result of `window:perform_effect`, as it is passed to Lua
+#[derive(Debug, Clone, PartialEq, Eq, ToDynamic)]
+pub struct EffectOutcome {
+    pub ok: bool,
+    /// The `describe_effect` name of the effect that failed, if the
+    /// failure can be attributed to one
+    pub effect: Option<String>,
+    pub error: Option<String>,
+}
+
+impl EffectOutcome {
+    pub fn success() -> Self {
+        Self {
+            ok: true,
+            effect: None,
+            error: None,
+        }
+    }
+
+    pub fn failure(effect: Option<&str>, err: &anyhow::Error) -> Self {
+        Self {
+            ok: false,
+            effect: effect.map(|effect| effect.to_string()),
+            error: Some(format!("{:#}", err)),
+        }
+    }
+}
+
 pub fn describe_effect(effect: &InputEffect) -> &'static str {
     match effect {
         InputEffect::ActivateKeyTable { .. } => "activate_key_table",
@@ -43,6 +76,13 @@ pub fn describe_effect(effect: &InputEffect) -> &'static str {
         InputEffect::AdjustFontSize { .. } => "adjust_font_size",
         InputEffect::ResetFontSize => "reset_font_size",
         InputEffect::ResetFontAndWindowSize => "reset_font_and_window_size",
+        InputEffect::AdjustPaneFontSize { .. } => "adjust_pane_font_size",
+        InputEffect::ResetPaneFontSize => "reset_pane_font_size",
+        InputEffect::SetWindowPadding { .. } => "set_window_padding",
+        InputEffect::ResetWindowPadding => "reset_window_padding",
+        InputEffect::AdjustWindowOpacity { .. } => "adjust_window_opacity",
+        InputEffect::SetWindowBackgroundBlur { .. } => "set_window_background_blur",
+        InputEffect::ResetWindowBackground => "reset_window_background",
         InputEffect::ActivateTab { .. } => "activate_tab",
         InputEffect::ActivateTabRelative { .. } => "activate_tab_relative",
         InputEffect::ActivateLastTab => "activate_last_tab",
@@ -52,8 +92,14 @@ pub fn describe_effect(effect: &InputEffect) -> &'static str {
         InputEffect::ActivatePaneByIndex { .. } => "activate_pane_by_index",
         InputEffect::ActivatePaneDirection { .. } => "activate_pane_direction",
         InputEffect::AdjustPaneSize { .. } => "adjust_pane_size",
+        InputEffect::SetSplitSize { .. } => "set_split_size",
         InputEffect::TogglePaneZoom => "toggle_pane_zoom",
         InputEffect::SetPaneZoom { .. } => "set_pane_zoom",
+        InputEffect::SetPaneRenderFrozen { .. } => "set_pane_render_frozen",
+        InputEffect::SetPaneHighlightPattern { .. } => "set_pane_highlight_pattern",
+        InputEffect::TogglePaneBroadcast { .. } => "toggle_pane_broadcast",
+        InputEffect::SetPaneBroadcastParticipation { .. } => "set_pane_broadcast_participation",
+        InputEffect::TogglePaneEscapeLog { .. } => "toggle_pane_escape_log",
         InputEffect::ClosePane { .. } => "close_pane",
         InputEffect::RotatePanes { .. } => "rotate_panes",
         InputEffect::ActivateWindow { .. } => "activate_window",
@@ -61,12 +107,15 @@ pub fn describe_effect(effect: &InputEffect) -> &'static str {
         InputEffect::CopySelection { .. } => "copy_selection",
         InputEffect::CopyText { .. } => "copy_text",
         InputEffect::Paste { .. } => "paste",
+        InputEffect::PasteAsOsc52 { .. } => "paste_as_osc52",
         InputEffect::CompleteSelection { .. } => "complete_selection",
         InputEffect::CompleteSelectionOrOpenLink { .. } => "complete_selection_or_open_link",
         InputEffect::ScrollByPage { .. } => "scroll_by_page",
         InputEffect::ScrollByLine { .. } => "scroll_by_line",
         InputEffect::ScrollByWheelDelta => "scroll_by_wheel_delta",
         InputEffect::ScrollToPrompt { .. } => "scroll_to_prompt",
+        InputEffect::ScrollToLastCommandStart => "scroll_to_last_command_start",
+        InputEffect::SelectLastCommandOutput => "select_last_command_output",
         InputEffect::ScrollToTop => "scroll_to_top",
         InputEffect::ScrollToBottom => "scroll_to_bottom",
         InputEffect::SelectAtMouseCursor { .. } => "select_at_mouse_cursor",
@@ -86,6 +135,8 @@ pub fn describe_effect(effect: &InputEffect) -> &'static str {
         InputEffect::ShowDebugOverlay => "show_debug_overlay",
         InputEffect::ShowLauncher { .. } => "show_launcher",
         InputEffect::ShowPaneSelect { .. } => "show_pane_select",
+        InputEffect::MovePaneToNewTab { .. } => "move_pane_to_new_tab",
+        InputEffect::MovePaneToWindow { .. } => "move_pane_to_window",
         InputEffect::ShowCharSelect { .. } => "show_char_select",
         InputEffect::ShowCommandPalette => "show_command_palette",
         InputEffect::ShowPromptInput { .. } => "show_prompt_input",
@@ -108,6 +159,99 @@ pub fn describe_effect(effect: &InputEffect) -> &'static str {
 }
 
 impl TermWindow {
+    /// Moves the active pane out of its split and into a new tab,
+    /// either in an existing window or in a new window.
+    /// The moved pane is focused if `focus` is true.
+    fn move_active_pane(
+        &mut self,
+        window_id: Option<mux::window::WindowId>,
+        workspace_for_new_window: Option<String>,
+        focus: bool,
+    ) {
+        // The pane that received the key may be an overlay; the mux
+        // only knows about the pane beneath it
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let pane_id = pane.pane_id();
+        self.cancel_overlay_for_pane(pane_id);
+
+        promise::spawn::spawn(async move {
+            let mux = Mux::get();
+            if let Err(err) = mux
+                .move_pane_to_new_tab(pane_id, window_id, workspace_for_new_window)
+                .await
+            {
+                log::error!("failed to move_pane_to_new_tab: {err:#}");
+                return;
+            }
+            if focus {
+                mux.focus_pane_and_containing_tab(pane_id).ok();
+            }
+        })
+        .detach();
+    }
+
+    /// Attaches the named domain to this window, spawning a tab in
+    /// it if the domain has no panes once it is attached
+    fn attach_domain(&self, name: String) -> impl Future<Output = anyhow::Result<()>> {
+        let window = self.mux_window_id;
+        let dpi = self.dimensions.dpi as u32;
+        async move {
+            let mux = Mux::get();
+            let domain = mux
+                .get_domain_by_name(&name)
+                .ok_or_else(|| anyhow!("{} is not a valid domain name", name))?;
+            domain.attach(Some(window)).await?;
+            let have_panes_in_domain = mux
+                .iter_panes()
+                .iter()
+                .any(|pane| pane.domain_id() == domain.domain_id());
+            if !have_panes_in_domain {
+                let config = config::configuration();
+                let _tab = domain
+                    .spawn(
+                        config
+                            .initial_size(dpi, Some(crate::cell_pixel_dims(&config, dpi as f64)?)),
+                        None,
+                        None,
+                        window,
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Executes effects on behalf of `window:perform_effect`, stopping at
+    /// the first one that fails.  Effects that complete asynchronously
+    /// add a future to `pending`, along with their name, rather than
+    /// being spawned.
+    pub fn execute_effects_reporting(
+        &mut self,
+        effects: Vec<InputEffect>,
+        pane: &Arc<dyn Pane>,
+        pending: &mut Vec<(&'static str, LocalBoxFuture<'static, anyhow::Result<()>>)>,
+    ) -> Result<(), EffectOutcome> {
+        for effect in effects {
+            let name = describe_effect(&effect);
+            match effect {
+                InputEffect::Multiple(effects) => {
+                    self.execute_effects_reporting(effects, pane, pending)?;
+                }
+                InputEffect::AttachDomain { name: domain } => {
+                    pending.push((name, self.attach_domain(domain).boxed_local()));
+                }
+                effect => {
+                    self.execute_one(effect, pane)
+                        .map_err(|err| EffectOutcome::failure(Some(name), &err))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn execute_effects(
         &mut self,
         effects: Vec<InputEffect>,
@@ -250,6 +394,37 @@ impl TermWindow {
                     self.reset_font_and_window_size(&window)?;
                 }
             }
+            InputEffect::AdjustPaneFontSize { delta } => {
+                self.adjust_pane_font_size(pane.pane_id(), delta);
+            }
+            InputEffect::ResetPaneFontSize => {
+                self.reset_pane_font_size(pane.pane_id());
+            }
+            InputEffect::SetWindowPadding {
+                left,
+                right,
+                top,
+                bottom,
+            } => {
+                self.set_window_padding(&WindowPaddingOverride {
+                    left,
+                    right,
+                    top,
+                    bottom,
+                });
+            }
+            InputEffect::ResetWindowPadding => {
+                self.reset_window_padding();
+            }
+            InputEffect::AdjustWindowOpacity { delta } => {
+                self.adjust_window_opacity(delta);
+            }
+            InputEffect::SetWindowBackgroundBlur { enabled, radius } => {
+                self.set_window_background_blur(enabled, radius);
+            }
+            InputEffect::ResetWindowBackground => {
+                self.reset_window_background();
+            }
             InputEffect::ActivateTab { index } => {
                 self.activate_tab(index)?;
             }
@@ -301,6 +476,20 @@ impl TermWindow {
                     tab.adjust_pane_size(direction, amount);
                 }
             }
+            InputEffect::SetSplitSize { direction, size } => {
+                let mux = Mux::get();
+                let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab,
+                    None => return Ok(()),
+                };
+                let size = match size {
+                    SplitSize::Percent(n) => MuxSplitSize::Percent(n),
+                    SplitSize::Cells(n) => MuxSplitSize::Cells(n),
+                };
+                if self.tab_state(tab.tab_id()).overlay.is_none() {
+                    tab.set_split_size(direction, size);
+                }
+            }
             InputEffect::TogglePaneZoom => {
                 let mux = Mux::get();
                 if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
@@ -313,6 +502,23 @@ impl TermWindow {
                     tab.set_zoomed(zoomed);
                 }
             }
+            InputEffect::SetPaneRenderFrozen { frozen } => {
+                self.set_pane_render_frozen(pane.pane_id(), frozen);
+            }
+            InputEffect::SetPaneHighlightPattern { pattern } => {
+                let pattern = pattern.map(|pattern| self.resolve_search_pattern(pattern, pane));
+                self.set_pane_highlight_pattern(pane.pane_id(), pattern);
+            }
+            InputEffect::TogglePaneBroadcast { scope } => {
+                self.toggle_pane_broadcast(pane, scope);
+            }
+            InputEffect::SetPaneBroadcastParticipation { participation } => {
+                self.set_pane_broadcast_participation(pane, participation);
+            }
+            InputEffect::TogglePaneEscapeLog { path } => {
+                let dims = pane.get_dimensions();
+                mux::escape_log::toggle(pane.pane_id(), path, dims.viewport_rows, dims.cols)?;
+            }
             InputEffect::ClosePane { confirm } => {
                 self.close_current_pane(confirm);
             }
@@ -343,6 +549,9 @@ impl TermWindow {
             InputEffect::Paste { source } => {
                 self.paste_from_clipboard(pane, source);
             }
+            InputEffect::PasteAsOsc52 { source } => {
+                self.paste_as_osc52(pane, source);
+            }
             InputEffect::CompleteSelection { destination } => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
@@ -375,6 +584,12 @@ impl TermWindow {
             InputEffect::ScrollToPrompt { direction } => {
                 self.scroll_to_prompt(direction, pane)?;
             }
+            InputEffect::ScrollToLastCommandStart => {
+                self.scroll_to_last_command_start(pane)?;
+            }
+            InputEffect::SelectLastCommandOutput => {
+                self.select_last_command_output(pane);
+            }
             InputEffect::ScrollToTop => {
                 self.scroll_to_top(pane);
             }
@@ -395,6 +610,7 @@ impl TermWindow {
             }
             InputEffect::SendString { text } => {
                 pane.writer().write_all(text.as_bytes())?;
+                self.broadcast_input(pane, BroadcastInput::Text(&text));
             }
             InputEffect::SendKey { key } => {
                 use crate::termwindow::keyevent::Key;
@@ -403,6 +619,7 @@ impl TermWindow {
                     &key.key.resolve(self.config.key_input().key_map_preference),
                 ) {
                     pane.key_down(key, mods)?;
+                    self.broadcast_input(pane, BroadcastInput::Key(key, mods));
                 }
             }
             InputEffect::SendToPane { pane_id, data } => {
@@ -524,6 +741,29 @@ impl TermWindow {
                     self.show_launcher();
                 }
             }
+            InputEffect::MovePaneToNewTab { keep_focus } => {
+                let window_id = self.mux_window_id;
+                self.move_active_pane(Some(window_id), None, !keep_focus);
+            }
+            InputEffect::MovePaneToWindow { window } => {
+                let mux = Mux::get();
+                match window {
+                    MoveTarget::NewWindow => self.move_active_pane(None, None, true),
+                    MoveTarget::Window(window_id) => {
+                        if mux.get_window(window_id).is_none() {
+                            log::error!("MovePaneToWindow: window {window_id} does not exist");
+                            return Ok(());
+                        }
+                        self.move_active_pane(Some(window_id), None, true);
+                    }
+                    MoveTarget::Workspace(workspace) => {
+                        match mux.iter_windows_in_workspace(&workspace).first() {
+                            Some(&window_id) => self.move_active_pane(Some(window_id), None, true),
+                            None => self.move_active_pane(None, Some(workspace), true),
+                        }
+                    }
+                }
+            }
             InputEffect::ShowPaneSelect { args } => {
                 let modal = crate::termwindow::paneselect::PaneSelector::new(self, &args);
                 self.set_modal(Rc::new(modal));
@@ -572,35 +812,7 @@ impl TermWindow {
                 domain.detach()?;
             }
             InputEffect::AttachDomain { name } => {
-                let window = self.mux_window_id;
-                let dpi = self.dimensions.dpi as u32;
-                promise::spawn::spawn(async move {
-                    let mux = Mux::get();
-                    let domain = mux
-                        .get_domain_by_name(&name)
-                        .ok_or_else(|| anyhow!("{} is not a valid domain name", name))?;
-                    domain.attach(Some(window)).await?;
-                    let have_panes_in_domain = mux
-                        .iter_panes()
-                        .iter()
-                        .any(|pane| pane.domain_id() == domain.domain_id());
-                    if !have_panes_in_domain {
-                        let config = config::configuration();
-                        let _tab = domain
-                            .spawn(
-                                config.initial_size(
-                                    dpi,
-                                    Some(crate::cell_pixel_dims(&config, dpi as f64)?),
-                                ),
-                                None,
-                                None,
-                                window,
-                            )
-                            .await?;
-                    }
-                    Result::<(), anyhow::Error>::Ok(())
-                })
-                .detach();
+                promise::spawn::spawn(self.attach_domain(name)).detach();
             }
             InputEffect::QuitApplication => {
                 let mux = Mux::get();
@@ -657,3 +869,43 @@ impl TermWindow {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use phaedra_dynamic::Value;
+
+    #[test]
+    fn outcome_to_dynamic() {
+        let ok = EffectOutcome::success().to_dynamic();
+        assert_eq!(
+            ok,
+            Value::Object(
+                vec![
+                    (Value::String("ok".to_string()), Value::Bool(true)),
+                    (Value::String("effect".to_string()), Value::Null),
+                    (Value::String("error".to_string()), Value::Null),
+                ]
+                .into_iter()
+                .collect()
+            )
+        );
+    }
+
+    #[test]
+    fn failure_includes_the_cause() {
+        let err = anyhow!("no such domain").context("attaching");
+        assert_eq!(
+            EffectOutcome::failure(Some("attach_domain"), &err),
+            EffectOutcome {
+                ok: false,
+                effect: Some("attach_domain".to_string()),
+                error: Some("attaching: no such domain".to_string()),
+            }
+        );
+        assert_eq!(
+            EffectOutcome::failure(None, &anyhow!("pane id 3 is not valid")).effect,
+            None
+        );
+    }
+}
@@ -1,9 +1,15 @@
-use crate::quad::{QuadTrait, TripleLayerQuadAllocatorTrait};
-use crate::render_command::{HsbTransform as CmdHsbTransform, QuadMode, RenderCommand};
-use crate::render_plan::ExecutionStats;
+use crate::quad::{
+    Quad, QuadImpl, QuadTrait, TripleLayerQuadAllocatorTrait, Vertex, VertexMode,
+    VERTICES_PER_CELL, V_BOT_LEFT, V_BOT_RIGHT, V_TOP_LEFT, V_TOP_RIGHT,
+};
+use crate::render_command::{
+    HsbTransform as CmdHsbTransform, LayerId, PointF, QuadMode, RectF, RenderCommand, TextureCoords,
+};
+use crate::render_plan::{ExecutionStats, LayerQuadSnapshot};
 use crate::renderstate::RenderState;
-use std::collections::HashSet;
-use ::window::bitmaps::TextureRect;
+use ::window::color::LinearRgba;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 
 pub struct ExecutionHistory {
     pub quads_emitted: usize,
@@ -41,25 +47,220 @@ impl ExecutionHistory {
     }
 }
 
+/// Provides the quad allocator for the render layer at a given zindex.
+/// This allows the command execution to be exercised without a GPU.
+pub trait QuadTarget {
+    fn with_quad_allocator(
+        &self,
+        zindex: i8,
+        f: &mut dyn FnMut(&mut dyn TripleLayerQuadAllocatorTrait) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>;
+
+    /// Discard the quads written so far to sub-layer `layer` of
+    /// every render layer
+    fn clear_layer(&self, layer: usize) -> anyhow::Result<()>;
+
+    /// The texture coordinates of the rounded corner sprite, which the
+    /// corners of a FillRoundedRect sample.  When it is None, rounded
+    /// rects are filled square.
+    fn rounded_corner_texture(&self) -> Option<TextureCoords>;
+}
+
+impl QuadTarget for RenderState {
+    fn with_quad_allocator(
+        &self,
+        zindex: i8,
+        f: &mut dyn FnMut(&mut dyn TripleLayerQuadAllocatorTrait) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let render_layer = self.layer_for_zindex(zindex)?;
+        let mut layers = render_layer.quad_allocator();
+        f(&mut layers)
+    }
+
+    fn clear_layer(&self, layer: usize) -> anyhow::Result<()> {
+        // The quads can't be removed from the buffer, as the ranges of
+        // the sections that were already recorded refer to them, so we
+        // note how many there are and skip over them when drawing
+        let mut clears = self.layer_clears.borrow_mut();
+        for render_layer in self.layers.borrow().iter() {
+            clears.push(LayerQuadSnapshot {
+                layer: render_layer.layer_id(layer),
+                quad_count: render_layer.vb.borrow()[layer].current_quad_count(),
+            });
+        }
+        Ok(())
+    }
+
+    fn rounded_corner_texture(&self) -> Option<TextureCoords> {
+        let coords = self.util_sprites.rounded_corner.texture_coords();
+        Some(TextureCoords {
+            left: coords.min_x(),
+            top: coords.min_y(),
+            right: coords.max_x(),
+            bottom: coords.max_y(),
+        })
+    }
+}
+
+/// Collects the vertices that executing the commands would write into
+/// the GPU buffers.  Quads are written through the same `Quad` helper
+/// that fills the mapped vertex buffers, so the vertices are identical.
+#[derive(Default)]
+struct DryRunTarget {
+    layers: RefCell<BTreeMap<i8, DryRunLayers>>,
+}
+
+#[derive(Default)]
+struct DryRunLayers {
+    vertices: [Vec<Vertex>; 3],
+}
+
+impl TripleLayerQuadAllocatorTrait for DryRunLayers {
+    fn allocate(&mut self, layer_num: usize) -> anyhow::Result<QuadImpl<'_>> {
+        let vertices = &mut self.vertices[layer_num];
+        let start = vertices.len();
+        vertices.resize(start + VERTICES_PER_CELL, Vertex::default());
+        Ok(QuadImpl::Vert(Quad {
+            vert: &mut vertices[start..],
+        }))
+    }
+
+    fn extend_with(&mut self, layer_num: usize, vertices: &[Vertex]) {
+        self.vertices[layer_num].extend_from_slice(vertices);
+    }
+}
+
+impl QuadTarget for DryRunTarget {
+    fn with_quad_allocator(
+        &self,
+        zindex: i8,
+        f: &mut dyn FnMut(&mut dyn TripleLayerQuadAllocatorTrait) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        f(self.layers.borrow_mut().entry(zindex).or_default())
+    }
+
+    fn clear_layer(&self, layer: usize) -> anyhow::Result<()> {
+        for layers in self.layers.borrow_mut().values_mut() {
+            layers.vertices[layer].clear();
+        }
+        Ok(())
+    }
+
+    /// There is no atlas, so the whole texture stands in for the sprite
+    fn rounded_corner_texture(&self) -> Option<TextureCoords> {
+        Some(TextureCoords {
+            left: 0.,
+            top: 0.,
+            right: 1.,
+            bottom: 1.,
+        })
+    }
+}
+
+/// Execute the commands without a GPU, returning the vertices that
+/// `execute_commands` would leave in each buffer that has any, ordered
+/// by zindex and then sub-layer.  This allows the output of a frame to
+/// be checked without comparing rendered images.
+pub fn execute_commands_dry_run(
+    commands: &[RenderCommand],
+    left_offset: f32,
+    top_offset: f32,
+) -> anyhow::Result<Vec<(LayerId, Vec<Vertex>)>> {
+    let target = DryRunTarget::default();
+    execute_commands(commands, &target, left_offset, top_offset)?;
+    let mut result = vec![];
+    for (zindex, layers) in target.layers.into_inner() {
+        for (sub_layer, vertices) in IntoIterator::into_iter(layers.vertices).enumerate() {
+            if !vertices.is_empty() {
+                let layer = LayerId::new(zindex, sub_layer).expect("a valid sub-layer");
+                result.push((layer, vertices));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Execute the commands, writing their quads into the render layers.
+/// The commands are partitioned by the buffer that they target, so that
+/// each render layer is looked up and mapped once per run of commands
+/// between barriers, rather than once per command, and each buffer is
+/// then filled in a tight loop.  The resulting buffer contents are the
+/// same as executing the commands in sequence.
 pub fn execute_commands(
     commands: &[RenderCommand],
-    render_state: &RenderState,
+    render_state: &impl QuadTarget,
+    left_offset: f32,
+    top_offset: f32,
+) -> anyhow::Result<()> {
+    execute_commands_with_hsv(commands, render_state, left_offset, top_offset, None)
+}
+
+/// Execute the commands as `execute_commands` does, applying `hsv`
+/// after the transform of each command.  This is the same as executing
+/// the commands that `RenderCommand::with_hsv` returns, but leaves the
+/// commands themselves untouched, so that a transform that changes from
+/// frame to frame, such as the dimming of a pane as it loses focus,
+/// doesn't invalidate the commands that were cached for it.
+pub fn execute_commands_with_hsv(
+    commands: &[RenderCommand],
+    render_state: &impl QuadTarget,
+    left_offset: f32,
+    top_offset: f32,
+    hsv: Option<&CmdHsbTransform>,
+) -> anyhow::Result<()> {
+    let rounded_corner = render_state.rounded_corner_texture();
+    for run in RenderCommand::partition_runs(commands) {
+        let mut groups = run.groups.iter().peekable();
+        while let Some(&(&layer, _)) = groups.peek() {
+            let zindex = layer.zindex();
+            render_state.with_quad_allocator(zindex, &mut |quads| {
+                while let Some((&layer, group)) =
+                    groups.next_if(|(group_layer, _)| group_layer.zindex() == zindex)
+                {
+                    for cmd in group {
+                        emit_quads(
+                            cmd,
+                            quads,
+                            layer.sub_layer(),
+                            rounded_corner.as_ref(),
+                            left_offset,
+                            top_offset,
+                            hsv,
+                        )?;
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        if let Some(barrier) = run.barrier {
+            execute_command(barrier, render_state, left_offset, top_offset, hsv)?;
+        }
+    }
+    Ok(())
+}
+
+/// Execute the commands one at a time, in order.  This is the
+/// reference behavior for `execute_commands`.
+pub fn execute_commands_sequential(
+    commands: &[RenderCommand],
+    render_state: &impl QuadTarget,
     left_offset: f32,
     top_offset: f32,
-    filled_box: &TextureRect,
 ) -> anyhow::Result<()> {
     for cmd in commands {
-        execute_command(cmd, render_state, left_offset, top_offset, filled_box)?;
+        execute_command(cmd, render_state, left_offset, top_offset, None)?;
     }
     Ok(())
 }
 
+/// Execute the commands in sequence, applying `hsv` as
+/// `execute_commands_with_hsv` does, and recording what was drawn
 pub fn execute_commands_with_history(
     commands: &[RenderCommand],
-    render_state: &RenderState,
+    render_state: &impl QuadTarget,
     left_offset: f32,
     top_offset: f32,
-    filled_box: &TextureRect,
+    hsv: Option<&CmdHsbTransform>,
 ) -> anyhow::Result<ExecutionHistory> {
     let mut history = ExecutionHistory::new();
     execute_commands_with_history_mut(
@@ -67,7 +268,7 @@ pub fn execute_commands_with_history(
         render_state,
         left_offset,
         top_offset,
-        filled_box,
+        hsv,
         &mut history,
     )?;
     Ok(history)
@@ -75,87 +276,340 @@ pub fn execute_commands_with_history(
 
 fn execute_commands_with_history_mut(
     commands: &[RenderCommand],
-    render_state: &RenderState,
+    render_state: &impl QuadTarget,
     left_offset: f32,
     top_offset: f32,
-    filled_box: &TextureRect,
+    hsv: Option<&CmdHsbTransform>,
     history: &mut ExecutionHistory,
 ) -> anyhow::Result<()> {
     for cmd in commands {
-        execute_command_with_history(
-            cmd,
-            render_state,
-            left_offset,
-            top_offset,
-            filled_box,
-            history,
-        )?;
+        execute_command_with_history(cmd, render_state, left_offset, top_offset, hsv, history)?;
     }
     Ok(())
 }
 
 fn execute_command(
     cmd: &RenderCommand,
-    render_state: &RenderState,
+    render_state: &impl QuadTarget,
     left_offset: f32,
     top_offset: f32,
-    filled_box: &TextureRect,
+    hsv: Option<&CmdHsbTransform>,
 ) -> anyhow::Result<()> {
     match cmd {
-        RenderCommand::Clear { .. }
-        | RenderCommand::SetClipRect(_)
-        | RenderCommand::BeginPostProcess
+        RenderCommand::Clear {
+            layer: Some(layer), ..
+        } => render_state.clear_layer(*layer),
+        RenderCommand::Clear { layer: None, .. } => {
+            for layer in 0..3 {
+                render_state.clear_layer(layer)?;
+            }
+            Ok(())
+        }
+        RenderCommand::SetClipRect(_)
+        | RenderCommand::BeginPostProcess { .. }
         | RenderCommand::Nop => Ok(()),
-        RenderCommand::Batch(commands) => execute_commands(
-            commands,
-            render_state,
+        RenderCommand::Batch(commands) => {
+            for cmd in commands {
+                execute_command(cmd, render_state, left_offset, top_offset, hsv)?;
+            }
+            Ok(())
+        }
+        RenderCommand::FillRect { .. }
+        | RenderCommand::DrawQuad { .. }
+        | RenderCommand::DrawLine { .. }
+        | RenderCommand::FillRoundedRect { .. } => {
+            execute_drawing_command(cmd, render_state, left_offset, top_offset, hsv)?;
+            Ok(())
+        }
+    }
+}
+
+/// Execute a command that draws, returning the number of quads
+/// that it wrote
+fn execute_drawing_command(
+    cmd: &RenderCommand,
+    render_state: &impl QuadTarget,
+    left_offset: f32,
+    top_offset: f32,
+    hsv: Option<&CmdHsbTransform>,
+) -> anyhow::Result<usize> {
+    let layer = match cmd.partition_key() {
+        Some(layer) => layer,
+        None => return Ok(0),
+    };
+    let rounded_corner = render_state.rounded_corner_texture();
+    let mut count = 0;
+    render_state.with_quad_allocator(layer.zindex(), &mut |quads| {
+        count = emit_quads(
+            cmd,
+            quads,
+            layer.sub_layer(),
+            rounded_corner.as_ref(),
             left_offset,
             top_offset,
-            filled_box,
-        ),
-        RenderCommand::FillRect {
-            layer,
-            zindex,
+            hsv,
+        )?;
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// Write the quads for a drawing command into sub-layer `layer`,
+/// returning the number of quads that were written.  A line is a
+/// single quad rotated to follow it; a rounded rect is three solid
+/// bands with a quad sampling `rounded_corner` at each corner.
+/// `pane_hsv` is applied after the transform of the command.
+fn emit_quads(
+    cmd: &RenderCommand,
+    quads: &mut dyn TripleLayerQuadAllocatorTrait,
+    layer: usize,
+    rounded_corner: Option<&TextureCoords>,
+    left_offset: f32,
+    top_offset: f32,
+    pane_hsv: Option<&CmdHsbTransform>,
+) -> anyhow::Result<usize> {
+    match cmd {
+        RenderCommand::DrawLine {
+            from,
+            to,
+            width,
+            color,
+            ..
+        } => {
+            let mut corners = match line_corners(*from, *to, *width) {
+                Some(corners) => corners,
+                None => return Ok(0),
+            };
+            for corner in corners.iter_mut() {
+                corner[0] -= left_offset;
+                corner[1] -= top_offset;
+            }
+            let mut quad = quads.allocate(layer)?;
+            quad.set_corners(corners);
+            quad.set_texture_discrete(0., 0., 0., 0.);
+            quad.set_is_background();
+            quad.set_fg_color(*color);
+            quad.set_hsv(None);
+            Ok(1)
+        }
+        RenderCommand::FillRoundedRect {
             rect,
+            radius,
             color,
             hsv,
+            ..
         } => {
-            let render_layer = render_state.layer_for_zindex(*zindex)?;
-            let mut layers = render_layer.quad_allocator();
-            let mut quad = layers.allocate(*layer)?;
+            let hsv = &CmdHsbTransform::compose_opt(hsv, pane_hsv);
+            let radius = radius.min(rect.width() / 2.).min(rect.height() / 2.);
+            let corner = match rounded_corner {
+                Some(corner) if radius > 0. => corner,
+                _ => {
+                    let mut quad = quads.allocate(layer)?;
+                    fill_solid_rect(&mut quad, rect, color, hsv, left_offset, top_offset);
+                    return Ok(1);
+                }
+            };
 
-            quad.set_position(
-                rect.min_x() - left_offset,
-                rect.min_y() - top_offset,
-                rect.max_x() - left_offset,
-                rect.max_y() - top_offset,
-            );
-            quad.set_texture_discrete(
-                filled_box.min_x(),
-                filled_box.max_x(),
-                filled_box.min_y(),
-                filled_box.max_y(),
-            );
-            quad.set_is_background();
-            quad.set_fg_color(color.clone());
-            quad.set_hsv(to_config_hsb_transform(hsv));
+            let mut count = 0;
+            let bands = [
+                euclid::rect(
+                    rect.min_x() + radius,
+                    rect.min_y(),
+                    rect.width() - 2. * radius,
+                    radius,
+                ),
+                euclid::rect(
+                    rect.min_x(),
+                    rect.min_y() + radius,
+                    rect.width(),
+                    rect.height() - 2. * radius,
+                ),
+                euclid::rect(
+                    rect.min_x() + radius,
+                    rect.max_y() - radius,
+                    rect.width() - 2. * radius,
+                    radius,
+                ),
+            ];
+            for band in bands.iter().filter(|band| !band.is_empty()) {
+                let mut quad = quads.allocate(layer)?;
+                fill_solid_rect(&mut quad, band, color, hsv, left_offset, top_offset);
+                count += 1;
+            }
 
-            Ok(())
+            // The sprite is the top left corner; the others mirror it
+            let corners = [
+                (rect.min_x(), rect.min_y(), false, false),
+                (rect.max_x() - radius, rect.min_y(), true, false),
+                (rect.min_x(), rect.max_y() - radius, false, true),
+                (rect.max_x() - radius, rect.max_y() - radius, true, true),
+            ];
+            for &(x, y, mirror_x, mirror_y) in corners.iter() {
+                let mut quad = quads.allocate(layer)?;
+                quad.set_position(
+                    x - left_offset,
+                    y - top_offset,
+                    x + radius - left_offset,
+                    y + radius - top_offset,
+                );
+                let (left, right) = if mirror_x {
+                    (corner.right, corner.left)
+                } else {
+                    (corner.left, corner.right)
+                };
+                let (top, bottom) = if mirror_y {
+                    (corner.bottom, corner.top)
+                } else {
+                    (corner.top, corner.bottom)
+                };
+                quad.set_texture_discrete(left, right, top, bottom);
+                quad.set_fg_color(*color);
+                quad.set_hsv(to_config_hsb_transform(hsv));
+                quad.set_mode(VertexMode::GrayScale);
+                count += 1;
+            }
+            Ok(count)
+        }
+        _ => {
+            let mut quad = quads.allocate(layer)?;
+            fill_quad(cmd, &mut quad, left_offset, top_offset, pane_hsv);
+            Ok(1)
+        }
+    }
+}
+
+/// The corners of the quad covering a line of `width` from `from` to
+/// `to`, indexed by V_TOP_LEFT etc, or None if the line has no area
+fn line_corners(from: PointF, to: PointF, width: f32) -> Option<[[f32; 2]; VERTICES_PER_CELL]> {
+    let delta = to - from;
+    let length = (delta.x * delta.x + delta.y * delta.y).sqrt();
+    if length == 0. || width <= 0. {
+        return None;
+    }
+    // Half of the width, perpendicular to the line
+    let normal = euclid::vec2(-delta.y, delta.x) * (width / 2. / length);
+    let point = |p: PointF| [p.x, p.y];
+    let mut corners = [[0.; 2]; VERTICES_PER_CELL];
+    corners[V_TOP_LEFT] = point(from + normal);
+    corners[V_TOP_RIGHT] = point(to + normal);
+    corners[V_BOT_LEFT] = point(from - normal);
+    corners[V_BOT_RIGHT] = point(to - normal);
+    Some(corners)
+}
+
+fn fill_solid_rect(
+    quad: &mut impl QuadTrait,
+    rect: &RectF,
+    color: &LinearRgba,
+    hsv: &Option<CmdHsbTransform>,
+    left_offset: f32,
+    top_offset: f32,
+) {
+    quad.set_position(
+        rect.min_x() - left_offset,
+        rect.min_y() - top_offset,
+        rect.max_x() - left_offset,
+        rect.max_y() - top_offset,
+    );
+    // The shader doesn't sample the texture for a solid color,
+    // so the quad doesn't depend on the contents of the atlas
+    quad.set_texture_discrete(0., 0., 0., 0.);
+    quad.set_is_background();
+    quad.set_fg_color(transform_solid_color(*color, hsv));
+    quad.set_hsv(None);
+}
+
+/// A solid color is the same at every pixel, so its transform is
+/// applied here rather than in the shader; that way a color produces
+/// the same vertices whether or not its pane is dimmed
+fn transform_solid_color(color: LinearRgba, hsv: &Option<CmdHsbTransform>) -> LinearRgba {
+    match hsv {
+        Some(transform) => apply_hsb_to_color(color, transform),
+        None => color,
+    }
+}
+
+/// Applies `transform` to `color` exactly as `apply_hsv` in
+/// shader.wgsl does; the two must be kept in sync
+pub fn apply_hsb_to_color(color: LinearRgba, transform: &CmdHsbTransform) -> LinearRgba {
+    let LinearRgba(r, g, b, a) = color;
+    let (h, s, v) = rgb_to_hsv(r, g, b);
+    let (r, g, b) = hsv_to_rgb(
+        h * transform.hue,
+        s * transform.saturation,
+        v * transform.brightness,
+    );
+    LinearRgba(r, g, b, a)
+}
+
+/// `rgb2hsv` from shader.wgsl.  The shader selects between vectors by
+/// mixing them with a step, which is the same as choosing one of them.
+fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let p = if g >= b {
+        [g, b, 0., -1. / 3.]
+    } else {
+        [b, g, -1., 2. / 3.]
+    };
+    let q = if r >= p[0] {
+        [r, p[1], p[2], p[0]]
+    } else {
+        [p[0], p[1], p[3], r]
+    };
+
+    let d = q[0] - q[3].min(q[1]);
+    let e = 1.0e-10;
+    (
+        (q[2] + (q[3] - q[1]) / (6. * d + e)).abs(),
+        d / (q[0] + e),
+        q[0],
+    )
+}
+
+/// `hsv2rgb` from shader.wgsl
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let channel = |k: f32| {
+        // fract() rather than rem_euclid(), as that is what wraps
+        // the hue around in the shader
+        let x = h + k;
+        let p = ((x - x.floor()) * 6. - 3.).abs();
+        let c = (p - 1.).clamp(0., 1.);
+        v * ((1. - s) + c * s)
+    };
+    (channel(1.), channel(2. / 3.), channel(1. / 3.))
+}
+
+/// `mix` from wgsl, for each channel
+fn mix_colors(a: LinearRgba, b: LinearRgba, t: f32) -> LinearRgba {
+    let mix = |a: f32, b: f32| a * (1. - t) + b * t;
+    LinearRgba(mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2), mix(a.3, b.3))
+}
+
+/// Populate a quad from a FillRect or DrawQuad command, applying
+/// `pane_hsv` after the transform of the command
+fn fill_quad(
+    cmd: &RenderCommand,
+    quad: &mut impl QuadTrait,
+    left_offset: f32,
+    top_offset: f32,
+    pane_hsv: Option<&CmdHsbTransform>,
+) {
+    match cmd {
+        RenderCommand::FillRect {
+            rect, color, hsv, ..
+        } => {
+            let hsv = &CmdHsbTransform::compose_opt(hsv, pane_hsv);
+            fill_solid_rect(quad, rect, color, hsv, left_offset, top_offset)
         }
         RenderCommand::DrawQuad {
-            layer,
-            zindex,
             position,
             texture,
             fg_color,
             alt_color,
             hsv,
             mode,
+            ..
         } => {
-            let render_layer = render_state.layer_for_zindex(*zindex)?;
-            let mut layers = render_layer.quad_allocator();
-            let mut quad = layers.allocate(*layer)?;
-
+            let hsv = &CmdHsbTransform::compose_opt(hsv, pane_hsv);
             quad.set_position(
                 position.min_x() - left_offset,
                 position.min_y() - top_offset,
@@ -163,33 +617,38 @@ fn execute_command(
                 position.max_y() - top_offset,
             );
             quad.set_texture_discrete(texture.left, texture.right, texture.top, texture.bottom);
-            quad.set_fg_color(fg_color.clone());
 
-            if let Some((alt, mix)) = alt_color {
-                quad.set_alt_color_and_mix_value(alt.clone(), *mix);
-            }
+            if *mode == QuadMode::SolidColor && hsv.is_some() {
+                // Mix here too, as the transform has to apply to the
+                // mixed color, just as it does in the shader
+                let color = match alt_color {
+                    Some((alt, mix)) => mix_colors(*fg_color, *alt, *mix),
+                    None => *fg_color,
+                };
+                quad.set_fg_color(transform_solid_color(color, hsv));
+                quad.set_hsv(None);
+            } else {
+                quad.set_fg_color(fg_color.clone());
 
-            quad.set_hsv(to_config_hsb_transform(hsv));
+                if let Some((alt, mix)) = alt_color {
+                    quad.set_alt_color_and_mix_value(alt.clone(), *mix);
+                }
 
-            match mode {
-                QuadMode::Glyph => quad.set_has_color(false),
-                QuadMode::ColorEmoji => quad.set_has_color(true),
-                QuadMode::BackgroundImage => quad.set_is_background_image(),
-                QuadMode::SolidColor => quad.set_is_background(),
-                QuadMode::GrayScale => quad.set_grayscale(),
+                quad.set_hsv(to_config_hsb_transform(hsv));
             }
 
-            Ok(())
+            quad.set_mode(mode.into());
         }
+        _ => {}
     }
 }
 
 fn execute_command_with_history(
     cmd: &RenderCommand,
-    render_state: &RenderState,
+    render_state: &impl QuadTarget,
     left_offset: f32,
     top_offset: f32,
-    filled_box: &TextureRect,
+    hsv: Option<&CmdHsbTransform>,
     history: &mut ExecutionHistory,
 ) -> anyhow::Result<()> {
     match cmd {
@@ -198,7 +657,7 @@ fn execute_command_with_history(
             render_state,
             left_offset,
             top_offset,
-            filled_box,
+            hsv,
             history,
         ),
         RenderCommand::FillRect { rect, .. } => {
@@ -207,7 +666,7 @@ fn execute_command_with_history(
             let max_x = rect.max_x() - left_offset;
             let max_y = rect.max_y() - top_offset;
             history.mark_position(position_fingerprint(min_x, min_y, max_x, max_y));
-            execute_command(cmd, render_state, left_offset, top_offset, filled_box)?;
+            execute_command(cmd, render_state, left_offset, top_offset, hsv)?;
             history.fills_emitted += 1;
             history.quads_emitted += 1;
             Ok(())
@@ -218,12 +677,36 @@ fn execute_command_with_history(
             let max_x = position.max_x() - left_offset;
             let max_y = position.max_y() - top_offset;
             history.mark_position(position_fingerprint(min_x, min_y, max_x, max_y));
-            execute_command(cmd, render_state, left_offset, top_offset, filled_box)?;
+            execute_command(cmd, render_state, left_offset, top_offset, hsv)?;
             history.draws_emitted += 1;
             history.quads_emitted += 1;
             Ok(())
         }
-        _ => execute_command(cmd, render_state, left_offset, top_offset, filled_box),
+        RenderCommand::DrawLine { from, to, .. } => {
+            history.mark_position(position_fingerprint(
+                from.x - left_offset,
+                from.y - top_offset,
+                to.x - left_offset,
+                to.y - top_offset,
+            ));
+            let quads = execute_drawing_command(cmd, render_state, left_offset, top_offset, hsv)?;
+            history.fills_emitted += quads;
+            history.quads_emitted += quads;
+            Ok(())
+        }
+        RenderCommand::FillRoundedRect { rect, .. } => {
+            history.mark_position(position_fingerprint(
+                rect.min_x() - left_offset,
+                rect.min_y() - top_offset,
+                rect.max_x() - left_offset,
+                rect.max_y() - top_offset,
+            ));
+            let quads = execute_drawing_command(cmd, render_state, left_offset, top_offset, hsv)?;
+            history.fills_emitted += quads;
+            history.quads_emitted += quads;
+            Ok(())
+        }
+        _ => execute_command(cmd, render_state, left_offset, top_offset, hsv),
     }
 }
 
@@ -238,3 +721,693 @@ fn to_config_hsb_transform(hsv: &Option<CmdHsbTransform>) -> Option<config::HsbT
         brightness: value.brightness,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::colorease::Transition;
+    use crate::quad::HeapQuadAllocator;
+    use crate::render_command::QuadMode;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct HeapTarget {
+        layers: RefCell<BTreeMap<i8, HeapQuadAllocator>>,
+    }
+
+    impl QuadTarget for HeapTarget {
+        fn with_quad_allocator(
+            &self,
+            zindex: i8,
+            f: &mut dyn FnMut(&mut dyn TripleLayerQuadAllocatorTrait) -> anyhow::Result<()>,
+        ) -> anyhow::Result<()> {
+            f(self.layers.borrow_mut().entry(zindex).or_default())
+        }
+
+        fn clear_layer(&self, layer: usize) -> anyhow::Result<()> {
+            for quads in self.layers.borrow_mut().values_mut() {
+                quads.clear_layer(layer);
+            }
+            Ok(())
+        }
+
+        fn rounded_corner_texture(&self) -> Option<TextureCoords> {
+            None
+        }
+    }
+
+    impl HeapTarget {
+        /// The raw vertex bytes of each buffer
+        fn contents(&self) -> BTreeMap<(i8, usize), Vec<u8>> {
+            let mut result = BTreeMap::new();
+            for (&zindex, layers) in self.layers.borrow().iter() {
+                for layer in 0..3 {
+                    let vertices = layers.vertices(layer);
+                    result.insert((zindex, layer), bytemuck::cast_slice(&vertices).to_vec());
+                }
+            }
+            result
+        }
+    }
+
+    fn rect(i: usize) -> RectF {
+        euclid::rect(i as f32, (i % 7) as f32, 1. + (i % 3) as f32, 2.)
+    }
+
+    /// Produce a frame with interleaved commands for several buffers,
+    /// nested batches and barriers
+    fn synthetic_frame(n: usize) -> Vec<RenderCommand> {
+        let mut commands = vec![RenderCommand::Clear {
+            color: LinearRgba(0., 0., 0., 1.),
+            layer: None,
+        }];
+        for i in 0..n {
+            let layer = LayerId::new([0i8, 2, -1][i % 3], (i / 3) % 3).unwrap();
+            let color = LinearRgba(i as f32 / n as f32, 0.5, 0.25, 1.);
+            let cmd = if i % 2 == 0 {
+                RenderCommand::FillRect {
+                    layer,
+                    rect: rect(i),
+                    color,
+                    hsv: None,
+                }
+            } else {
+                RenderCommand::DrawQuad {
+                    layer,
+                    position: rect(i),
+                    texture: TextureCoords {
+                        left: 0.,
+                        top: 0.,
+                        right: 0.5,
+                        bottom: 0.5,
+                    },
+                    fg_color: color,
+                    alt_color: if i % 5 == 0 { Some((color, 0.5)) } else { None },
+                    hsv: if i % 4 == 1 {
+                        Some(CmdHsbTransform {
+                            hue: 1.,
+                            saturation: 0.5,
+                            brightness: 1.5,
+                        })
+                    } else {
+                        None
+                    },
+                    mode: [QuadMode::Glyph, QuadMode::ColorEmoji, QuadMode::GrayScale][i % 3]
+                        .clone(),
+                }
+            };
+            if i % 11 == 0 {
+                commands.push(RenderCommand::Batch(vec![cmd, RenderCommand::Nop]));
+            } else {
+                commands.push(cmd);
+            }
+            if i % 17 == 0 {
+                commands.push(RenderCommand::SetClipRect(Some(rect(i))));
+            }
+            if i == n / 2 {
+                commands.push(RenderCommand::Clear {
+                    color: LinearRgba(0., 0., 0., 0.),
+                    layer: Some(1),
+                });
+            }
+        }
+        commands.push(RenderCommand::BeginPostProcess {
+            region: Some(rect(0)),
+            effect: None,
+        });
+        commands
+    }
+
+    #[test]
+    fn partitioned_matches_sequential() {
+        let commands = synthetic_frame(500);
+
+        let sequential = HeapTarget::default();
+        execute_commands_sequential(&commands, &sequential, 10., 20.).unwrap();
+
+        let partitioned = HeapTarget::default();
+        execute_commands(&commands, &partitioned, 10., 20.).unwrap();
+
+        let expected = sequential.contents();
+        assert_eq!(expected.len(), 9);
+        assert!(expected.values().all(|bytes| !bytes.is_empty()));
+        assert_eq!(partitioned.contents(), expected);
+    }
+
+    #[test]
+    fn execute_time_hsv_matches_applied_hsv() {
+        let commands = synthetic_frame(200);
+        let dim = CmdHsbTransform {
+            hue: 1.,
+            saturation: 0.9,
+            brightness: 0.8,
+        };
+
+        let applied: Vec<RenderCommand> = commands
+            .iter()
+            .map(|cmd| cmd.clone().with_hsv(&dim))
+            .collect();
+        let expected = HeapTarget::default();
+        execute_commands(&applied, &expected, 10., 20.).unwrap();
+
+        let partitioned = HeapTarget::default();
+        execute_commands_with_hsv(&commands, &partitioned, 10., 20., Some(&dim)).unwrap();
+        assert_eq!(partitioned.contents(), expected.contents());
+
+        let history = HeapTarget::default();
+        execute_commands_with_history(&commands, &history, 10., 20., Some(&dim)).unwrap();
+        let sequential = HeapTarget::default();
+        execute_commands_sequential(&applied, &sequential, 10., 20.).unwrap();
+        assert_eq!(history.contents(), sequential.contents());
+    }
+
+    #[test]
+    fn fading_leaves_the_commands_untouched() {
+        let commands = synthetic_frame(100);
+        let hash = RenderCommand::content_hash(&commands);
+        let dim = CmdHsbTransform {
+            hue: 1.,
+            saturation: 0.9,
+            brightness: 0.8,
+        };
+        let mut fade = Transition::settled(CmdHsbTransform::IDENTITY);
+        fade.retarget(dim, 100, config::EasingFunction::Linear);
+
+        let mut frames = vec![];
+        for step in 0..=4 {
+            let hsv = fade.value_at(step as f32 * 0.025);
+            let target = HeapTarget::default();
+            execute_commands_with_hsv(&commands, &target, 0., 0., Some(&hsv)).unwrap();
+            assert_eq!(RenderCommand::content_hash(&commands), hash);
+            frames.push(target.contents());
+        }
+        // Each step of the fade is drawn differently
+        assert!(frames.windows(2).all(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn layer_clear_discards_earlier_quads() {
+        let fill = |layer, zindex, i| RenderCommand::FillRect {
+            layer: LayerId::new(zindex, layer).unwrap(),
+            rect: rect(i),
+            color: LinearRgba(1., 1., 1., 1.),
+            hsv: None,
+        };
+        let quad_count = |target: &HeapTarget, zindex, layer| {
+            target.layers.borrow()[&zindex].vertices(layer).len() / VERTICES_PER_CELL
+        };
+
+        let target = HeapTarget::default();
+        let commands = vec![
+            fill(0, 0, 0),
+            fill(1, 0, 1),
+            fill(1, 2, 2),
+            RenderCommand::Clear {
+                color: LinearRgba(0., 0., 0., 0.),
+                layer: Some(1),
+            },
+            fill(1, 0, 3),
+        ];
+        execute_commands(&commands, &target, 0., 0.).unwrap();
+        assert_eq!(quad_count(&target, 0, 0), 1);
+        assert_eq!(quad_count(&target, 0, 1), 1);
+        assert_eq!(quad_count(&target, 2, 1), 0);
+
+        // A whole-frame clear discards every layer
+        execute_commands(
+            &[RenderCommand::Clear {
+                color: LinearRgba(0., 0., 0., 1.),
+                layer: None,
+            }],
+            &target,
+            0.,
+            0.,
+        )
+        .unwrap();
+        assert!(target.contents().values().all(|bytes| bytes.is_empty()));
+    }
+
+    /// The vertices of a quad in buffer order, all sharing the same
+    /// colors and flags
+    fn quad_vertices(
+        [left, top, right, bottom]: [f32; 4],
+        [tex_left, tex_top, tex_right, tex_bottom]: [f32; 4],
+        fg_color: [f32; 4],
+        alt_color: [f32; 4],
+        hsv: [f32; 3],
+        has_color: f32,
+        mix_value: f32,
+    ) -> Vec<Vertex> {
+        [
+            ([left, top], [tex_left, tex_top]),
+            ([right, top], [tex_right, tex_top]),
+            ([left, bottom], [tex_left, tex_bottom]),
+            ([right, bottom], [tex_right, tex_bottom]),
+        ]
+        .iter()
+        .map(|&(position, tex)| Vertex {
+            position,
+            tex,
+            fg_color,
+            alt_color,
+            hsv,
+            has_color,
+            mix_value,
+        })
+        .collect()
+    }
+
+    /// Vertex has no PartialEq; its Debug representation prints each
+    /// float exactly, so comparing that is comparing the values
+    fn assert_vertices(actual: &[(LayerId, Vec<Vertex>)], expected: &[(LayerId, Vec<Vertex>)]) {
+        assert_eq!(format!("{:#?}", actual), format!("{:#?}", expected));
+    }
+
+    #[test]
+    fn dry_run_fill_rect() {
+        let commands = vec![RenderCommand::FillRect {
+            layer: LayerId::text(),
+            rect: euclid::rect(10., 20., 4., 6.),
+            color: LinearRgba(0.5, 0.25, 1., 1.),
+            hsv: None,
+        }];
+        let vertices = execute_commands_dry_run(&commands, 2., 4.).unwrap();
+
+        let color = [0.5, 0.25, 1., 1.];
+        assert_vertices(
+            &vertices,
+            &[(
+                LayerId::text(),
+                quad_vertices(
+                    [8., 16., 12., 22.],
+                    [0., 0., 0., 0.],
+                    color,
+                    color,
+                    [1., 1., 1.],
+                    3.,
+                    0.,
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn dry_run_vertex_modes() {
+        let quad = |mode| RenderCommand::DrawQuad {
+            layer: LayerId::text(),
+            position: euclid::rect(0., 0., 8., 16.),
+            texture: TextureCoords {
+                left: 0.25,
+                top: 0.25,
+                right: 0.5,
+                bottom: 0.5,
+            },
+            fg_color: LinearRgba(1., 1., 1., 1.),
+            alt_color: None,
+            hsv: None,
+            mode,
+        };
+        let commands = vec![
+            RenderCommand::FillRect {
+                layer: LayerId::text(),
+                rect: euclid::rect(0., 0., 8., 16.),
+                color: LinearRgba(1., 0., 0., 1.),
+                hsv: None,
+            },
+            quad(QuadMode::SolidColor),
+            quad(QuadMode::Glyph),
+            quad(QuadMode::ColorEmoji),
+            quad(QuadMode::BackgroundImage),
+            quad(QuadMode::GrayScale),
+        ];
+        let vertices = execute_commands_dry_run(&commands, 0., 0.).unwrap();
+        let vertices = &vertices[0].1;
+        assert!(vertices
+            .chunks(VERTICES_PER_CELL)
+            .all(|quad| quad.iter().all(|v| v.mode() == quad[0].mode())));
+        let modes: Vec<_> = vertices
+            .chunks(VERTICES_PER_CELL)
+            .map(|quad| quad[0].mode())
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                Some(VertexMode::SolidColor),
+                Some(VertexMode::SolidColor),
+                Some(VertexMode::Glyph),
+                Some(VertexMode::ColorEmoji),
+                Some(VertexMode::BackgroundImage),
+                Some(VertexMode::GrayScale),
+            ]
+        );
+        // A FillRect doesn't refer to the atlas at all
+        assert!(vertices[..VERTICES_PER_CELL]
+            .iter()
+            .all(|v| v.tex == [0., 0.]));
+    }
+
+    #[test]
+    fn dry_run_alt_color_and_hsv() {
+        let commands = vec![
+            RenderCommand::DrawQuad {
+                layer: LayerId::text().with_zindex(2),
+                position: euclid::rect(0., 0., 8., 16.),
+                texture: TextureCoords {
+                    left: 0.,
+                    top: 0.5,
+                    right: 0.25,
+                    bottom: 0.75,
+                },
+                fg_color: LinearRgba(1., 1., 1., 1.),
+                alt_color: Some((LinearRgba(0., 0.5, 0., 0.5), 0.25)),
+                hsv: None,
+                mode: QuadMode::Glyph,
+            },
+            RenderCommand::FillRect {
+                layer: LayerId::background().with_zindex(-1),
+                rect: euclid::rect(0., 0., 100., 50.),
+                color: LinearRgba(0., 0., 0.25, 1.),
+                hsv: Some(CmdHsbTransform {
+                    hue: 0.5,
+                    saturation: 2.,
+                    brightness: 0.75,
+                }),
+            },
+        ];
+        let vertices = execute_commands_dry_run(&commands, 0., 0.).unwrap();
+
+        // The FillRect is transformed here rather than in the shader
+        let transformed = apply_hsb_to_color(
+            LinearRgba(0., 0., 0.25, 1.),
+            &CmdHsbTransform {
+                hue: 0.5,
+                saturation: 2.,
+                brightness: 0.75,
+            },
+        );
+        let transformed = [transformed.0, transformed.1, transformed.2, transformed.3];
+        assert_vertices(
+            &vertices,
+            &[
+                (
+                    LayerId::background().with_zindex(-1),
+                    quad_vertices(
+                        [0., 0., 100., 50.],
+                        [0., 0., 0., 0.],
+                        transformed,
+                        transformed,
+                        [1., 1., 1.],
+                        3.,
+                        0.,
+                    ),
+                ),
+                (
+                    LayerId::text().with_zindex(2),
+                    quad_vertices(
+                        [0., 0., 8., 16.],
+                        [0., 0.5, 0.25, 0.75],
+                        [1., 1., 1., 1.],
+                        [0., 0.5, 0., 0.5],
+                        [1., 1., 1.],
+                        0.,
+                        0.25,
+                    ),
+                ),
+            ],
+        );
+    }
+
+    fn assert_color_near(actual: LinearRgba, expected: LinearRgba) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2)
+                && close(actual.3, expected.3),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn hsb(hue: f32, saturation: f32, brightness: f32) -> CmdHsbTransform {
+        CmdHsbTransform {
+            hue,
+            saturation,
+            brightness,
+        }
+    }
+
+    #[test]
+    fn cpu_hsb_transform() {
+        let identity = hsb(1., 1., 1.);
+        for color in [
+            LinearRgba(0.2, 0.5, 0.8, 1.),
+            LinearRgba(0.9, 0.1, 0.3, 0.5),
+            LinearRgba(0., 0., 0., 1.),
+            LinearRgba(1., 1., 1., 1.),
+        ] {
+            assert_color_near(apply_hsb_to_color(color, &identity), color);
+        }
+
+        // Dimming, as for an inactive pane
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(1., 0., 0., 1.), &hsb(1., 1., 0.5)),
+            LinearRgba(0.5, 0., 0., 1.),
+        );
+        // Desaturating leaves only the brightness
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(0., 0.5, 0., 0.25), &hsb(1., 0., 1.)),
+            LinearRgba(0.5, 0.5, 0.5, 0.25),
+        );
+        // Blue has a hue of 2/3; halving it gives green
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(0., 0., 1., 1.), &hsb(0.5, 1., 1.)),
+            LinearRgba(0., 1., 0., 1.),
+        );
+    }
+
+    #[test]
+    fn cpu_hsb_transform_wraps_hue() {
+        // Green has a hue of 1/3; 3.5 times that is 7/6, which
+        // wraps around to the 1/6 of yellow
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(0., 1., 0., 1.), &hsb(3.5, 1., 1.)),
+            LinearRgba(1., 1., 0., 1.),
+        );
+        // Blue at 1.5 times its hue lands exactly on a whole turn,
+        // which is red again
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(0., 0., 1., 1.), &hsb(1.5, 1., 1.)),
+            LinearRgba(1., 0., 0., 1.),
+        );
+        // Cyan has a hue of 1/2; twice that wraps to red, and a
+        // quarter of the way round from there is chartreuse
+        assert_color_near(
+            apply_hsb_to_color(LinearRgba(0., 0.5, 0.5, 1.), &hsb(2.5, 1., 1.)),
+            LinearRgba(0.25, 0.5, 0., 1.),
+        );
+    }
+
+    #[test]
+    fn dry_run_solid_color_quad_mixes_before_transform() {
+        let transform = hsb(1., 1., 0.5);
+        let commands = vec![RenderCommand::DrawQuad {
+            layer: LayerId::background(),
+            position: euclid::rect(0., 0., 8., 16.),
+            texture: TextureCoords {
+                left: 0.,
+                top: 0.,
+                right: 0.,
+                bottom: 0.,
+            },
+            fg_color: LinearRgba(1., 0., 0., 1.),
+            alt_color: Some((LinearRgba(0., 0., 1., 1.), 0.5)),
+            hsv: Some(transform),
+            mode: QuadMode::SolidColor,
+        }];
+        let vertices = execute_commands_dry_run(&commands, 0., 0.).unwrap();
+        let v = &vertices[0].1[0];
+        assert_eq!(v.hsv, [1., 1., 1.]);
+        assert_eq!(v.mix_value, 0.);
+        assert_color_near(
+            LinearRgba(v.fg_color[0], v.fg_color[1], v.fg_color[2], v.fg_color[3]),
+            LinearRgba(0.25, 0., 0.25, 1.),
+        );
+    }
+
+    #[test]
+    fn dry_run_clipped_quads() {
+        let clip = euclid::rect(4., 0., 8., 8.);
+        let glyph = |x| RenderCommand::DrawQuad {
+            layer: LayerId::background(),
+            position: euclid::rect(x, 0., 8., 16.),
+            texture: TextureCoords {
+                left: 0.,
+                top: 0.,
+                right: 0.5,
+                bottom: 1.,
+            },
+            fg_color: LinearRgba(1., 0., 0., 1.),
+            alt_color: None,
+            hsv: None,
+            mode: QuadMode::ColorEmoji,
+        };
+        // The first glyph straddles the left edge of the clip, the
+        // second lies entirely outside of it
+        let commands: Vec<_> = vec![glyph(0.), glyph(16.)]
+            .into_iter()
+            .map(|cmd| cmd.clip_to_rect(&clip))
+            .collect();
+        let vertices = execute_commands_dry_run(&commands, 0., 0.).unwrap();
+
+        let red = [1., 0., 0., 1.];
+        assert_vertices(
+            &vertices,
+            &[(
+                LayerId::background(),
+                quad_vertices(
+                    [4., 0., 8., 8.],
+                    [0.25, 0., 0.5, 0.5],
+                    red,
+                    red,
+                    [1., 1., 1.],
+                    1.,
+                    0.,
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn dry_run_diagonal_line() {
+        let commands = vec![RenderCommand::DrawLine {
+            layer: LayerId::overlay(),
+            from: euclid::point2(1., 2.),
+            to: euclid::point2(5., 6.),
+            width: 2. * std::f32::consts::SQRT_2,
+            color: LinearRgba(0., 1., 0., 1.),
+        }];
+        let vertices = execute_commands_dry_run(&commands, 1., 2.).unwrap();
+        assert_eq!(vertices.len(), 1);
+        let (key, vertices) = &vertices[0];
+        assert_eq!(*key, LayerId::overlay());
+        assert_eq!(vertices.len(), VERTICES_PER_CELL);
+
+        // The quad is offset by half of the width on either side of
+        // the line, perpendicular to it
+        let expected = [[-1., 1.], [3., 5.], [1., -1.], [5., 3.]];
+        for (vertex, expected) in vertices.iter().zip(expected.iter()) {
+            assert!(
+                (vertex.position[0] - expected[0]).abs() < 1e-5
+                    && (vertex.position[1] - expected[1]).abs() < 1e-5,
+                "{:?} != {:?}",
+                vertex.position,
+                expected
+            );
+            assert_eq!(vertex.mode(), Some(VertexMode::SolidColor));
+            assert_eq!(vertex.tex, [0., 0.]);
+        }
+
+        // A line of no length draws nothing
+        let commands = vec![RenderCommand::DrawLine {
+            layer: LayerId::overlay(),
+            from: euclid::point2(1., 2.),
+            to: euclid::point2(1., 2.),
+            width: 1.,
+            color: LinearRgba(0., 1., 0., 1.),
+        }];
+        assert!(execute_commands_dry_run(&commands, 0., 0.)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn dry_run_rounded_rect() {
+        let rounded = RenderCommand::FillRoundedRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(0., 0., 20., 10.),
+            radius: 4.,
+            color: LinearRgba(1., 1., 1., 1.),
+            hsv: None,
+        };
+        let vertices = execute_commands_dry_run(&[rounded.clone()], 0., 0.).unwrap();
+        let vertices = &vertices[0].1;
+        let modes: Vec<_> = vertices
+            .chunks(VERTICES_PER_CELL)
+            .map(|quad| quad[0].mode())
+            .collect();
+        // Three bands, then the four corners
+        assert_eq!(
+            modes,
+            vec![
+                Some(VertexMode::SolidColor),
+                Some(VertexMode::SolidColor),
+                Some(VertexMode::SolidColor),
+                Some(VertexMode::GrayScale),
+                Some(VertexMode::GrayScale),
+                Some(VertexMode::GrayScale),
+                Some(VertexMode::GrayScale),
+            ]
+        );
+        // The bottom right corner mirrors the sprite in both directions
+        let corner = &vertices[6 * VERTICES_PER_CELL..];
+        assert_eq!(corner[0].position, [16., 6.]);
+        assert_eq!(corner[0].tex, [1., 1.]);
+        assert_eq!(corner[3].position, [20., 10.]);
+        assert_eq!(corner[3].tex, [0., 0.]);
+
+        // Clipping falls back to a square fill of the visible part
+        let clipped = rounded.clip_to_rect(&euclid::rect(10., 0., 20., 20.));
+        let vertices = execute_commands_dry_run(&[clipped], 0., 0.).unwrap();
+        let white = [1., 1., 1., 1.];
+        assert_vertices(
+            &vertices,
+            &[(
+                LayerId::background(),
+                quad_vertices(
+                    [10., 0., 20., 10.],
+                    [0., 0., 0., 0.],
+                    white,
+                    white,
+                    [1., 1., 1.],
+                    3.,
+                    0.,
+                ),
+            )],
+        );
+    }
+
+    #[test]
+    fn dry_run_matches_buffers() {
+        let commands = synthetic_frame(200);
+        let vertices = execute_commands_dry_run(&commands, 10., 20.).unwrap();
+
+        let target = HeapTarget::default();
+        execute_commands(&commands, &target, 10., 20.).unwrap();
+        let positions = |vertices: &[Vertex]| -> Vec<[f32; 2]> {
+            vertices.iter().map(|v| v.position).collect()
+        };
+        let expected: Vec<_> = target
+            .layers
+            .borrow()
+            .iter()
+            .flat_map(|(&zindex, layers)| {
+                (0..3).filter_map(move |layer| {
+                    let vertices = layers.vertices(layer);
+                    if vertices.is_empty() {
+                        None
+                    } else {
+                        Some((LayerId::new(zindex, layer).unwrap(), positions(&vertices)))
+                    }
+                })
+            })
+            .collect();
+        let actual: Vec<_> = vertices
+            .iter()
+            .map(|(key, vertices)| (*key, positions(vertices)))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
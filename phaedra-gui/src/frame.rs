@@ -1,4 +1,4 @@
-use crate::render_command::RenderCommand;
+use crate::render_command::{HsbTransform, RenderCommand};
 use crate::render_plan::ExecutionStats;
 use crate::termwindow::UIItem;
 use mux::pane::PaneId;
@@ -16,11 +16,59 @@ pub struct PaneFrame {
     pub is_active: bool,
     pub bounds: phaedra_render_command::RectF,
     pub command_hash: u64,
-    pub cache_key: u64,
+    /// None for frames that must be described afresh on the next
+    /// paint, such as the placeholder for a pane that failed to describe
+    pub cache_key: Option<u64>,
     pub commands: Arc<[RenderCommand]>,
     pub ui_items: Vec<UIItem>,
     pub last_execution_stats: Option<ExecutionStats>,
     pub skip_streak: u32,
+    /// The hsb transform that the commands were executed with, such as
+    /// the dimming of an inactive pane.  It is applied when executing
+    /// the commands rather than being part of them, so that it can
+    /// change without the pane being described again.
+    pub hsv: Option<HsbTransform>,
+}
+
+impl PaneFrame {
+    /// Returns this frame moved by the content alignment offset
+    /// (dx, dy): its commands, bounds and ui items move together, so
+    /// that hit testing matches what is drawn.  The moved commands are
+    /// hashed with `salt`, which is the `pane_hash_salt` that they
+    /// were described with.
+    pub fn aligned(self, dx: f32, dy: f32, salt: u64) -> Self {
+        if dx == 0. && dy == 0. {
+            return self;
+        }
+        let commands: Arc<[RenderCommand]> = align_commands(self.commands.to_vec(), dx, dy).into();
+        Self {
+            bounds: self.bounds.translate(euclid::vec2(dx, dy)),
+            command_hash: RenderCommand::content_hash_with_salt(&commands, salt),
+            commands,
+            ui_items: align_ui_items(self.ui_items, dx, dy),
+            ..self
+        }
+    }
+}
+
+/// Returns `commands` moved by the content alignment offset (dx, dy)
+pub fn align_commands(commands: Vec<RenderCommand>, dx: f32, dy: f32) -> Vec<RenderCommand> {
+    if dx == 0. && dy == 0. {
+        return commands;
+    }
+    let offset = euclid::vec2(dx, dy);
+    commands
+        .into_iter()
+        .map(|cmd| cmd.map_rects(&|rect| rect.translate(offset)))
+        .collect()
+}
+
+/// Returns `items` moved by the content alignment offset (dx, dy)
+pub fn align_ui_items(items: Vec<UIItem>, dx: f32, dy: f32) -> Vec<UIItem> {
+    items
+        .into_iter()
+        .map(|item| item.translated(dx, dy))
+        .collect()
 }
 
 #[derive(Debug, Default)]
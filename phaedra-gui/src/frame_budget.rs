@@ -0,0 +1,174 @@
+//! Warns when painting a frame takes longer than
+//! `runtime.frame_time_warning_ms`, naming the stage of the frame that
+//! took the longest, so that a frame that is perceived as a hang can be
+//! traced back to its cause from the logs.
+use mux::pane::PaneId;
+use std::time::{Duration, Instant};
+
+/// The least time between two warnings of the same window
+pub const FRAME_BUDGET_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A part of painting a frame whose duration is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStage {
+    DescribePane(PaneId),
+    ExecutePane(PaneId),
+    DescribeTabBar,
+    ExecuteChrome,
+    AtlasRebuild,
+    Draw,
+}
+
+impl std::fmt::Display for FrameStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::DescribePane(pane_id) => write!(f, "describe pane {pane_id}"),
+            Self::ExecutePane(pane_id) => write!(f, "execute pane {pane_id}"),
+            Self::DescribeTabBar => write!(f, "describe tab bar"),
+            Self::ExecuteChrome => write!(f, "execute chrome"),
+            Self::AtlasRebuild => write!(f, "atlas rebuild"),
+            Self::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+/// The time spent in each stage of a frame.  A frame that is painted
+/// in several passes, such as when the texture atlas is grown, adds up
+/// the time spent in each stage across the passes.
+#[derive(Debug, Default)]
+pub struct FrameTimings {
+    stages: Vec<(FrameStage, Duration)>,
+}
+
+impl FrameTimings {
+    pub fn clear(&mut self) {
+        self.stages.clear();
+    }
+
+    pub fn record(&mut self, stage: FrameStage, duration: Duration) {
+        match self.stages.iter_mut().find(|(s, _)| *s == stage) {
+            Some((_, total)) => *total += duration,
+            None => self.stages.push((stage, duration)),
+        }
+    }
+
+    /// Records the time elapsed since `start` against `stage`
+    pub fn record_since(&mut self, stage: FrameStage, start: Instant) {
+        self.record(stage, start.elapsed());
+    }
+
+    /// Returns the stage that took the longest.  Of stages that took
+    /// the same time, the first to be recorded is returned.
+    pub fn slowest(&self) -> Option<(FrameStage, Duration)> {
+        self.stages
+            .iter()
+            .copied()
+            .reduce(|slowest, stage| if stage.1 > slowest.1 { stage } else { slowest })
+    }
+}
+
+/// Decides when a frame that exceeded its budget is worth a warning
+#[derive(Debug, Default)]
+pub struct FrameBudget {
+    last_warning: Option<Instant>,
+}
+
+impl FrameBudget {
+    /// Returns the warning for a frame that took `elapsed`, if it took
+    /// longer than `threshold_ms` and no warning has been given within
+    /// `FRAME_BUDGET_WARNING_INTERVAL` of `now`.  A `threshold_ms` of
+    /// zero disables the warning.
+    pub fn check(
+        &mut self,
+        threshold_ms: u64,
+        elapsed: Duration,
+        timings: &FrameTimings,
+        now: Instant,
+    ) -> Option<String> {
+        if threshold_ms == 0 || elapsed <= Duration::from_millis(threshold_ms) {
+            return None;
+        }
+        if let Some(last) = self.last_warning {
+            if now.saturating_duration_since(last) < FRAME_BUDGET_WARNING_INTERVAL {
+                return None;
+            }
+        }
+        self.last_warning.replace(now);
+
+        let slowest = match timings.slowest() {
+            Some((stage, duration)) => format!(
+                "slowest_stage=\"{stage}\" stage_ms={}",
+                duration.as_millis()
+            ),
+            None => "slowest_stage=unknown".to_string(),
+        };
+        Some(format!(
+            "frame exceeded budget: frame_ms={} budget_ms={threshold_ms} {slowest}",
+            elapsed.as_millis()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn slowest_stage_adds_up_passes() {
+        let mut timings = FrameTimings::default();
+        assert_eq!(timings.slowest(), None);
+
+        timings.record(FrameStage::DescribePane(1), ms(30));
+        timings.record(FrameStage::ExecuteChrome, ms(50));
+        timings.record(FrameStage::DescribePane(2), ms(50));
+        assert_eq!(timings.slowest(), Some((FrameStage::ExecuteChrome, ms(50))));
+
+        // A second pass over the same pane counts towards its total
+        timings.record(FrameStage::DescribePane(1), ms(40));
+        assert_eq!(
+            timings.slowest(),
+            Some((FrameStage::DescribePane(1), ms(70)))
+        );
+
+        timings.clear();
+        assert_eq!(timings.slowest(), None);
+    }
+
+    #[test]
+    fn warnings_are_rate_limited() {
+        let mut timings = FrameTimings::default();
+        timings.record(FrameStage::AtlasRebuild, ms(120));
+        timings.record(FrameStage::Draw, ms(10));
+        let mut budget = FrameBudget::default();
+        let start = Instant::now();
+
+        assert_eq!(budget.check(100, ms(90), &timings, start), None);
+        assert_eq!(
+            budget.check(100, ms(130), &timings, start).as_deref(),
+            Some(
+                "frame exceeded budget: frame_ms=130 budget_ms=100 \
+                 slowest_stage=\"atlas rebuild\" stage_ms=120"
+            )
+        );
+        assert_eq!(
+            budget.check(100, ms(130), &timings, start + ms(9_000)),
+            None
+        );
+        assert!(budget
+            .check(
+                100,
+                ms(130),
+                &timings,
+                start + FRAME_BUDGET_WARNING_INTERVAL
+            )
+            .is_some());
+        assert_eq!(
+            budget.check(0, ms(10_000), &timings, start + ms(60_000)),
+            None
+        );
+    }
+}
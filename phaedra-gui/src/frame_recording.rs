@@ -0,0 +1,193 @@
+//! Records the render commands of each painted frame while
+//! `runtime.record_frames` is configured; see the phaedra-frame-recording
+//! crate for the format, and `phaedra-gui replay-frames` for replaying
+//! a recording.
+//!
+//! All windows append to the same recording, which is created when
+//! recording is enabled and closed when it is disabled or full.
+use crate::execute_render::execute_commands_dry_run;
+use crate::render_command::RenderCommand;
+use crate::software_render::{rasterize_commands, SoftwareAtlas};
+use anyhow::Context;
+use config::observers::*;
+use config::{ConfigHandle, RecordFramesConfig};
+use mux::pane::PaneId;
+use phaedra_frame_recording::{FrameInfo, FrameRecorder, PaneCommands, ReplayedFrame};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct RecorderState {
+    /// The configuration that `recorder` was created for.  This is
+    /// retained after the recording fills up, so that it isn't
+    /// restarted until the configuration changes.
+    config: Option<RecordFramesConfig>,
+    recorder: Option<FrameRecorder>,
+}
+
+lazy_static::lazy_static! {
+    static ref RECORDER: Mutex<RecorderState> = Mutex::new(RecorderState::default());
+}
+
+/// The commands that a window executed while painting a frame, in
+/// the order in which they were executed
+#[derive(Default)]
+pub struct FrameCommands {
+    pub background: Vec<RenderCommand>,
+    pub panes: Vec<(PaneId, u64, Arc<[RenderCommand]>)>,
+    pub chrome: Vec<RenderCommand>,
+}
+
+/// Returns true if the commands of each frame should be collected for
+/// `record_frame`
+pub fn is_recording(config: &ConfigHandle) -> bool {
+    config.runtime().record_frames.is_some()
+}
+
+/// Appends `frame` to the recording, creating the recording if
+/// recording has just been enabled, or closes it if it has just been
+/// disabled
+pub fn record_frame(config: &ConfigHandle, info: FrameInfo, frame: Option<FrameCommands>) {
+    let mut state = RECORDER.lock().unwrap();
+    let record_config = &config.runtime().record_frames;
+    if state.config != *record_config {
+        state.recorder.take();
+        state.config = record_config.clone();
+        if let Some(record_config) = record_config {
+            match FrameRecorder::create(&record_config.path, record_config.max_frames) {
+                Ok(recorder) => {
+                    log::info!("recording frames to {}", record_config.path.display());
+                    state.recorder.replace(recorder);
+                }
+                Err(err) => log::error!("frame recording: {:#}", err),
+            }
+        }
+    }
+
+    let (recorder, frame) = match (state.recorder.as_mut(), frame) {
+        (Some(recorder), Some(frame)) => (recorder, frame),
+        _ => return,
+    };
+    let panes: Vec<PaneCommands> = frame
+        .panes
+        .iter()
+        .map(|(pane_id, command_hash, commands)| PaneCommands {
+            pane_id: *pane_id,
+            command_hash: *command_hash,
+            commands,
+        })
+        .collect();
+    match recorder.write_frame(info, &frame.background, &panes, &frame.chrome) {
+        Ok(true) => {}
+        Ok(false) => {
+            log::info!(
+                "frame recording {} is complete after {} frames",
+                recorder.path().display(),
+                recorder.frames_written()
+            );
+            state.recorder.take();
+        }
+        Err(err) => {
+            log::error!("frame recording: {:#}", err);
+            state.recorder.take();
+        }
+    }
+}
+
+/// Executes a replayed frame without a GPU, returning the number of
+/// vertices that it produced
+pub fn execute_replayed_frame(frame: &ReplayedFrame) -> anyhow::Result<usize> {
+    let buffers = execute_commands_dry_run(
+        &frame.commands,
+        frame.pixel_width as f32 / 2.0,
+        frame.pixel_height as f32 / 2.0,
+    )?;
+    Ok(buffers.iter().map(|(_, vertices)| vertices.len()).sum())
+}
+
+/// Rasterizes a replayed frame on the CPU, and writes it into `dir` as
+/// a PNG named for the frame.  The recording doesn't include the glyph
+/// atlas, so anything sampled from it is left out.
+pub fn write_replayed_frame_png(frame: &ReplayedFrame, dir: &Path) -> anyhow::Result<PathBuf> {
+    let rasterized = rasterize_commands(
+        &frame.commands,
+        frame.pixel_width,
+        frame.pixel_height,
+        &SoftwareAtlas::default(),
+    )?;
+    let image = image::RgbaImage::from_raw(
+        frame.pixel_width as u32,
+        frame.pixel_height as u32,
+        rasterized.to_srgba8(),
+    )
+    .context("frame size doesn't match its pixels")?;
+    let path = dir.join(format!("frame-{:06}.png", frame.frame));
+    image
+        .save(&path)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render_command::LayerId;
+    use ::window::color::LinearRgba;
+    use phaedra_frame_recording::{read_recording, replay};
+
+    #[test]
+    fn replay_through_dry_run() {
+        let dir = std::env::temp_dir().join(format!("phaedra-gui-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frames.rec");
+
+        let info = FrameInfo {
+            window_id: 0,
+            pixel_width: 80,
+            pixel_height: 32,
+            config_generation: 1,
+        };
+        let fill = |x: f32| RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(x, 0., 10., 16.),
+            color: LinearRgba(1., 0., 0., 1.),
+            hsv: None,
+        };
+        let pane: Vec<RenderCommand> = vec![fill(0.), fill(10.)];
+        let chrome = vec![fill(70.)];
+
+        let mut recorder = FrameRecorder::create(&path, 10).unwrap();
+        for _ in 0..3 {
+            let panes = [PaneCommands {
+                pane_id: 1,
+                command_hash: RenderCommand::content_hash(&pane),
+                commands: &pane,
+            }];
+            assert!(recorder.write_frame(info, &[], &panes, &chrome).unwrap());
+        }
+        drop(recorder);
+
+        let mut executed = vec![];
+        let mut pngs = vec![];
+        replay(&read_recording(&path).unwrap(), |frame| {
+            assert!(frame.matches_recording());
+            executed.push(execute_replayed_frame(frame)?);
+            pngs.push(write_replayed_frame_png(frame, &dir)?);
+            Ok(())
+        })
+        .unwrap();
+        // Three quads in each frame, even though the pane commands
+        // were only recorded in the first
+        let quad = crate::quad::VERTICES_PER_CELL;
+        assert_eq!(executed, vec![3 * quad, 3 * quad, 3 * quad]);
+
+        // The last frame is drawn from the pane commands of the first
+        let image = image::open(&pngs[2]).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (80, 32));
+        assert_eq!(image.get_pixel(15, 8).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(40, 8).0, [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(75, 20).0, [0, 0, 0, 0]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use crate::scripting::guiwin::GuiWin;
 use config::observers::*;
 use crate::spawn::SpawnWhere;
+use crate::termwindow::tabdrag::TabDropTarget;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use ::window::*;
@@ -23,6 +24,7 @@ pub struct GuiFrontEnd {
     switching_workspaces: RefCell<bool>,
     spawned_mux_window: RefCell<HashSet<MuxWindowId>>,
     known_windows: RefCell<BTreeMap<Window, MuxWindowId>>,
+    tab_drop_targets: RefCell<BTreeMap<Window, TabDropTarget>>,
     client_id: Arc<ClientId>,
     config_subscription: RefCell<Option<ConfigSubscription>>,
 }
@@ -46,6 +48,7 @@ impl GuiFrontEnd {
             switching_workspaces: RefCell::new(false),
             spawned_mux_window: RefCell::new(HashSet::new()),
             known_windows: RefCell::new(BTreeMap::new()),
+            tab_drop_targets: RefCell::new(BTreeMap::new()),
             client_id: client_id.clone(),
             config_subscription: RefCell::new(None),
         });
@@ -148,6 +151,7 @@ impl GuiFrontEnd {
                         | Alert::WindowTitleChanged(_)
                         | Alert::TabTitleChanged(_)
                         | Alert::IconTitleChanged(_)
+                        | Alert::TabColorChanged(_)
                         | Alert::SetUserVar { .. },
                 } => {}
                 MuxNotification::Empty => {
@@ -204,9 +208,14 @@ impl GuiFrontEnd {
             }
             true
         });
-        // Re-evaluate the config so that folks that are using
+        // Tell the config about the appearance, so that a color
+        // scheme chosen according to it is resolved, and re-evaluate
+        // the config so that folks that are using
         // `phaedra.gui.get_appearance()` can have that take effect
         // before any windows are created
+        if let Some(conn) = Connection::get() {
+            config::set_system_appearance(system_appearance(conn.get_appearance()));
+        }
         config::reload();
 
         // And build the initial menu bar.
@@ -464,11 +473,38 @@ impl GuiFrontEnd {
 
     pub fn forget_known_window(&self, window: &Window) {
         self.known_windows.borrow_mut().remove(window);
+        self.tab_drop_targets.borrow_mut().remove(window);
         if !self.is_switching_workspace() {
             self.reconcile_workspace();
         }
     }
 
+    /// Records where the tab bar of `window` is on screen, so that
+    /// tabs dragged out of other windows can be dropped onto it
+    pub fn set_tab_drop_target(&self, window: &Window, target: Option<TabDropTarget>) {
+        let mut targets = self.tab_drop_targets.borrow_mut();
+        match target {
+            Some(target) => {
+                targets.insert(window.clone(), target);
+            }
+            None => {
+                targets.remove(window);
+            }
+        }
+    }
+
+    /// Returns the tab bars that a tab can be dropped onto, skipping
+    /// any whose window has since been repurposed for another mux window
+    pub fn tab_drop_targets(&self) -> Vec<TabDropTarget> {
+        let windows = self.known_windows.borrow();
+        self.tab_drop_targets
+            .borrow()
+            .iter()
+            .filter(|(window, target)| windows.get(window) == Some(&target.mux_window_id))
+            .map(|(_, target)| target.clone())
+            .collect()
+    }
+
     pub fn is_switching_workspace(&self) -> bool {
         *self.switching_workspaces.borrow()
     }
@@ -495,6 +531,17 @@ pub fn try_front_end() -> Option<Rc<GuiFrontEnd>> {
     FRONT_END.with(|f| f.borrow().as_ref().map(Rc::clone))
 }
 
+/// Converts the appearance reported by the windowing environment to
+/// the form used by the config
+pub fn system_appearance(appearance: Appearance) -> config::SystemAppearance {
+    match appearance {
+        Appearance::Light => config::SystemAppearance::Light,
+        Appearance::Dark => config::SystemAppearance::Dark,
+        Appearance::LightHighContrast => config::SystemAppearance::LightHighContrast,
+        Appearance::DarkHighContrast => config::SystemAppearance::DarkHighContrast,
+    }
+}
+
 pub fn front_end() -> Rc<GuiFrontEnd> {
     FRONT_END
         .with(|f| f.borrow().as_ref().map(Rc::clone))
@@ -536,6 +583,7 @@ pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
         move || {
             promise::spawn::spawn_into_main_thread(async {
                 crate::commands::CommandDef::recreate_menubar(&config::configuration());
+                trigger_and_log_config_reloaded().await;
             })
             .detach();
             true
@@ -548,3 +596,18 @@ pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
 
     Ok(front_end)
 }
+
+async fn trigger_config_reloaded(lua: Option<Rc<mlua::Lua>>) -> anyhow::Result<()> {
+    if let Some(lua) = lua {
+        let deltas: Vec<config::ConfigDelta> = config::configuration_deltas().to_vec();
+        let args = lua.pack_multi(deltas)?;
+        config::lua::emit_event(&lua, ("config-reloaded".to_string(), args)).await?;
+    }
+    Ok(())
+}
+
+async fn trigger_and_log_config_reloaded() {
+    if let Err(err) = config::with_lua_config_on_main_thread(trigger_config_reloaded).await {
+        log::error!("while processing config-reloaded event: {:#}", err);
+    }
+}
@@ -16,7 +16,7 @@ use image::{
 use lfucache::LfuCache;
 use ordered_float::NotNan;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Seek;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -192,6 +192,13 @@ struct LineKey {
     underline: Underline,
     overline: bool,
     size: CellMetricKey,
+    /// The decoration placement varies with the font used for the run,
+    /// so it needs to be part of the key alongside the cell size
+    descender_row: IntPixelLength,
+    descender_plus_two: IntPixelLength,
+    underline_height: IntPixelLength,
+    strike_row: IntPixelLength,
+    overline_row: IntPixelLength,
 }
 
 /// A helper struct to implement BitmapImage for ImageDataType while
@@ -556,8 +563,21 @@ impl DecodedImage {
     }
 }
 
+/// Counts of the sprites that have been evicted from the atlas to make
+/// room for new ones
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EvictionCounters {
+    /// The number of times that space was reclaimed
+    pub reclaims: usize,
+    /// The total number of sprites evicted
+    pub sprites: usize,
+}
+
 /// A number of items here are HashMaps rather than LfuCaches;
-/// eviction is managed by recreating Self when the Atlas is filled
+/// eviction is managed by the Atlas, which tracks when each sprite
+/// was last used.  When the Atlas is filled, stale sprites are evicted
+/// and the entries that reference them removed by `reclaim`, and if that
+/// doesn't free enough space Self is recreated.
 pub struct GlyphCache {
     glyph_cache: HashMap<GlyphKey, Rc<CachedGlyph>>,
     pub atlas: Atlas,
@@ -569,6 +589,7 @@ pub struct GlyphCache {
     pub cursor_glyphs: HashMap<(Option<CursorShape>, u8), Sprite>,
     pub color: HashMap<(RgbColor, NotNan<f32>), Sprite>,
     min_frame_duration: Duration,
+    evictions: EvictionCounters,
 }
 
 impl GlyphCache {
@@ -592,6 +613,7 @@ impl GlyphCache {
             cursor_glyphs: HashMap::new(),
             color: HashMap::new(),
             min_frame_duration: Duration::from_millis(1000 / fonts.config().gpu().max_fps as u64),
+            evictions: EvictionCounters::default(),
         })
     }
 }
@@ -621,6 +643,7 @@ impl GlyphCache {
             cursor_glyphs: HashMap::new(),
             color: HashMap::new(),
             min_frame_duration: Duration::from_millis(1000 / fonts.config().gpu().max_fps as u64),
+            evictions: EvictionCounters::default(),
         })
     }
 }
@@ -649,6 +672,9 @@ impl GlyphCache {
 
         if let Some(entry) = self.glyph_cache.get(&key as &dyn GlyphKeyTrait) {
             metrics::histogram!("glyph_cache.glyph_cache.hit.rate").record(1.);
+            if let Some(texture) = &entry.texture {
+                self.atlas.mark_used(texture);
+            }
             return Ok(Rc::clone(entry));
         }
         metrics::histogram!("glyph_cache.glyph_cache.miss.rate").record(1.);
@@ -692,6 +718,53 @@ impl GlyphCache {
         Ok(glyph)
     }
 
+    /// Advance the frame number used to decide which sprites are stale.
+    /// This should be called once per paint.
+    pub fn begin_frame(&mut self) {
+        self.atlas.begin_frame();
+    }
+
+    /// Returns the fraction of the atlas that is in use
+    pub fn atlas_utilization(&self) -> f32 {
+        self.atlas.utilization()
+    }
+
+    pub fn eviction_counters(&self) -> EvictionCounters {
+        self.evictions
+    }
+
+    /// Evict the sprites that have not been used in the last `max_age`
+    /// frames, and remove the cache entries that reference them.
+    /// Returns the number of sprites that were evicted.
+    /// Sprites obtained from this cache must not be retained by the
+    /// caller across a reclaim that evicts anything; any caches built
+    /// on top of this one need to be cleared.
+    pub fn reclaim(&mut self, max_age: u64) -> usize {
+        let evicted: HashSet<(isize, isize)> = self
+            .atlas
+            .evict_unused(max_age)
+            .into_iter()
+            .map(|rect| (rect.origin.x, rect.origin.y))
+            .collect();
+        if evicted.is_empty() {
+            return 0;
+        }
+
+        let live =
+            |sprite: &Sprite| !evicted.contains(&(sprite.coords.origin.x, sprite.coords.origin.y));
+        self.glyph_cache
+            .retain(|_, glyph| glyph.texture.as_ref().map_or(true, live));
+        self.frame_cache.retain(|_, sprite| live(sprite));
+        self.line_glyphs.retain(|_, sprite| live(sprite));
+        self.block_glyphs.retain(|_, sprite| live(sprite));
+        self.cursor_glyphs.retain(|_, sprite| live(sprite));
+        self.color.retain(|_, sprite| live(sprite));
+
+        self.evictions.reclaims += 1;
+        self.evictions.sprites += evicted.len();
+        evicted.len()
+    }
+
     pub fn config_changed(&mut self) {
         let config = self.fonts.config();
         self.image_cache.update_config(&config);
@@ -918,6 +991,7 @@ impl GlyphCache {
         match &*handle.h {
             ImageDataType::Rgba8 { hash, .. } => {
                 if let Some(sprite) = frame_cache.get(hash) {
+                    atlas.mark_used(sprite);
                     return Ok((sprite.clone(), None, LoadState::Loaded));
                 }
                 let sprite = atlas
@@ -973,6 +1047,7 @@ impl GlyphCache {
                 let hash = hashes[*decoded_current_frame];
 
                 if let Some(sprite) = frame_cache.get(&hash) {
+                    atlas.mark_used(sprite);
                     return Ok((sprite.clone(), next, LoadState::Loaded));
                 }
 
@@ -1036,6 +1111,7 @@ impl GlyphCache {
                 let hash = frames.frame_hash();
 
                 if let Some(sprite) = frame_cache.get(&hash) {
+                    atlas.mark_used(sprite);
                     return Ok((sprite.clone(), next, frames.load_state));
                 }
 
@@ -1118,6 +1194,7 @@ impl GlyphCache {
         let key = (color, NotNan::new(alpha).unwrap());
 
         if let Some(s) = self.color.get(&key) {
+            self.atlas.mark_used(s);
             return Ok(s.clone());
         }
 
@@ -1145,233 +1222,383 @@ impl GlyphCache {
             size: metrics.into(),
         };
         if let Some(s) = self.block_glyphs.get(&key) {
+            self.atlas.mark_used(s);
             return Ok(s.clone());
         }
         self.block_sprite(metrics, key)
     }
 
     fn line_sprite(&mut self, key: LineKey, metrics: &RenderMetrics) -> anyhow::Result<Sprite> {
-        let mut buffer = Image::new(
-            metrics.cell_size.width as usize,
-            metrics.cell_size.height as usize,
-        );
-        let black = SrgbaPixel::rgba(0, 0, 0, 0);
-        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
-
-        let cell_rect = Rect::new(Point::new(0, 0), metrics.cell_size);
-
-        let draw_single = |buffer: &mut Image| {
-            for row in 0..metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + metrics.descender_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + metrics.cell_size.width,
-                        cell_rect.origin.y + metrics.descender_row + row,
-                    ),
-                    white,
-                );
-            }
-        };
+        let buffer = line_image(&key, metrics);
+        let sprite = self.atlas.allocate(&buffer)?;
+        self.line_glyphs.insert(key, sprite.clone());
+        Ok(sprite)
+    }
 
-        let draw_dotted = |buffer: &mut Image| {
-            for row in 0..metrics.underline_height {
-                let y = (cell_rect.origin.y + metrics.descender_row + row) as usize;
-                if y >= metrics.cell_size.height as usize {
-                    break;
-                }
+    /// Figure out what we're going to draw for the underline.
+    /// If the current cell is part of the current URL highlight
+    /// then we want to show the underline.
+    pub fn cached_line_sprite(
+        &mut self,
+        is_highlited_hyperlink: bool,
+        is_strike_through: bool,
+        underline: Underline,
+        overline: bool,
+        metrics: &RenderMetrics,
+    ) -> anyhow::Result<Sprite> {
+        let effective_underline = effective_underline(is_highlited_hyperlink, underline);
 
-                let mut color = white;
-                let segment_length = (metrics.cell_size.width / 4) as usize;
-                let mut count = segment_length;
-                let range =
-                    buffer.horizontal_pixel_range_mut(0, metrics.cell_size.width as usize, y);
-                for c in range.iter_mut() {
-                    *c = color.as_srgba32();
-                    count -= 1;
-                    if count == 0 {
-                        color = if color == white { black } else { white };
-                        count = segment_length;
-                    }
-                }
-            }
+        let key = LineKey {
+            strike_through: is_strike_through,
+            overline,
+            underline: effective_underline,
+            size: metrics.into(),
+            descender_row: metrics.descender_row,
+            descender_plus_two: metrics.descender_plus_two,
+            underline_height: metrics.underline_height,
+            strike_row: metrics.strike_row,
+            overline_row: metrics.overline_row,
         };
 
-        let draw_dashed = |buffer: &mut Image| {
-            for row in 0..metrics.underline_height {
-                let y = (cell_rect.origin.y + metrics.descender_row + row) as usize;
-                if y >= metrics.cell_size.height as usize {
-                    break;
-                }
-                let mut color = white;
-                let third = (metrics.cell_size.width / 3) as usize + 1;
-                let mut count = third;
-                let range =
-                    buffer.horizontal_pixel_range_mut(0, metrics.cell_size.width as usize, y);
-                for c in range.iter_mut() {
-                    *c = color.as_srgba32();
-                    count -= 1;
-                    if count == 0 {
-                        color = if color == white { black } else { white };
-                        count = third;
-                    }
-                }
-            }
-        };
+        if let Some(s) = self.line_glyphs.get(&key) {
+            self.atlas.mark_used(s);
+            return Ok(s.clone());
+        }
 
-        let draw_curly = |buffer: &mut Image| {
-            let max_y = metrics.cell_size.height as usize - 1;
-            let x_factor = (2. * std::f32::consts::PI) / metrics.cell_size.width as f32;
+        self.line_sprite(key, metrics)
+    }
+}
 
-            // Have the wave go from the descender to the bottom of the cell
-            let wave_height =
-                metrics.cell_size.height - (cell_rect.origin.y + metrics.descender_row);
+/// Returns the underline drawn for a cell with `underline`.  The cells
+/// of a highlighted hyperlink get a single underline, or a double one
+/// if they were already underlined, so that the highlight stands out.
+fn effective_underline(is_highlited_hyperlink: bool, underline: Underline) -> Underline {
+    match (is_highlited_hyperlink, underline) {
+        (true, Underline::None) => Underline::Single,
+        (true, Underline::Single) => Underline::Double,
+        (true, _) => Underline::Single,
+        (false, u) => u,
+    }
+}
 
-            let half_height = (wave_height as f32 / 4.).max(1.);
-            let y = ((cell_rect.origin.y + metrics.descender_row) as usize)
-                .saturating_sub(half_height as usize);
+/// Draws the strikethrough, overline and underline of `key` into an
+/// image of a single cell.  The lines are white, so that they take on
+/// the underline color when drawn as a glyph.
+fn line_image(key: &LineKey, metrics: &RenderMetrics) -> Image {
+    let mut buffer = Image::new(
+        metrics.cell_size.width as usize,
+        metrics.cell_size.height as usize,
+    );
+    let black = SrgbaPixel::rgba(0, 0, 0, 0);
+    let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
+
+    let cell_rect = Rect::new(Point::new(0, 0), metrics.cell_size);
+
+    let draw_single = |buffer: &mut Image| {
+        for row in 0..metrics.underline_height {
+            buffer.draw_line(
+                Point::new(
+                    cell_rect.origin.x,
+                    cell_rect.origin.y + metrics.descender_row + row,
+                ),
+                Point::new(
+                    cell_rect.origin.x + metrics.cell_size.width,
+                    cell_rect.origin.y + metrics.descender_row + row,
+                ),
+                white,
+            );
+        }
+    };
 
-            fn add(x: usize, y: usize, val: u8, max_y: usize, buffer: &mut Image) {
-                let y = y.min(max_y);
-                let pixel = buffer.pixel_mut(x, y);
-                let (current, _, _, _) = SrgbaPixel::with_srgba_u32(*pixel).as_rgba();
-                let value = current.saturating_add(val);
-                *pixel = SrgbaPixel::rgba(value, value, value, value).as_srgba32();
+    let draw_dotted = |buffer: &mut Image| {
+        for row in 0..metrics.underline_height {
+            let y = (cell_rect.origin.y + metrics.descender_row + row) as usize;
+            if y >= metrics.cell_size.height as usize {
+                break;
             }
 
-            for x in 0..metrics.cell_size.width as usize {
-                let vertical = -half_height * (x as f32 * x_factor).sin() + half_height;
-                let v1 = vertical.floor();
-                let v2 = vertical.ceil();
-
-                for row in 0..metrics.underline_height as usize {
-                    let value = (255. * (vertical - v1).abs()) as u8;
-                    add(
-                        x,
-                        row.saturating_add(y).saturating_add(v1 as usize),
-                        255u8.saturating_sub(value),
-                        max_y,
-                        buffer,
-                    );
-                    add(
-                        x,
-                        row.saturating_add(y).saturating_add(v2 as usize),
-                        value,
-                        max_y,
-                        buffer,
-                    );
+            let mut color = white;
+            let segment_length = (metrics.cell_size.width / 4) as usize;
+            let mut count = segment_length;
+            let range = buffer.horizontal_pixel_range_mut(0, metrics.cell_size.width as usize, y);
+            for c in range.iter_mut() {
+                *c = color.as_srgba32();
+                count -= 1;
+                if count == 0 {
+                    color = if color == white { black } else { white };
+                    count = segment_length;
                 }
             }
-        };
+        }
+    };
 
-        let draw_double = |buffer: &mut Image| {
-            let first_line = metrics
-                .descender_row
-                .min(metrics.descender_plus_two - 2 * metrics.underline_height);
-
-            for row in 0..metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + first_line + row),
-                    Point::new(
-                        cell_rect.origin.x + metrics.cell_size.width,
-                        cell_rect.origin.y + first_line + row,
-                    ),
-                    white,
-                );
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + metrics.descender_plus_two + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + metrics.cell_size.width,
-                        cell_rect.origin.y + metrics.descender_plus_two + row,
-                    ),
-                    white,
-                );
+    let draw_dashed = |buffer: &mut Image| {
+        for row in 0..metrics.underline_height {
+            let y = (cell_rect.origin.y + metrics.descender_row + row) as usize;
+            if y >= metrics.cell_size.height as usize {
+                break;
             }
-        };
-
-        let draw_strike = |buffer: &mut Image| {
-            for row in 0..metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(
-                        cell_rect.origin.x,
-                        cell_rect.origin.y + metrics.strike_row + row,
-                    ),
-                    Point::new(
-                        cell_rect.origin.x + metrics.cell_size.width,
-                        cell_rect.origin.y + metrics.strike_row + row,
-                    ),
-                    white,
-                );
+            let mut color = white;
+            let third = (metrics.cell_size.width / 3) as usize + 1;
+            let mut count = third;
+            let range = buffer.horizontal_pixel_range_mut(0, metrics.cell_size.width as usize, y);
+            for c in range.iter_mut() {
+                *c = color.as_srgba32();
+                count -= 1;
+                if count == 0 {
+                    color = if color == white { black } else { white };
+                    count = third;
+                }
             }
-        };
+        }
+    };
 
-        let draw_overline = |buffer: &mut Image| {
-            for row in 0..metrics.underline_height {
-                buffer.draw_line(
-                    Point::new(cell_rect.origin.x, cell_rect.origin.y + row),
-                    Point::new(
-                        cell_rect.origin.x + metrics.cell_size.width,
-                        cell_rect.origin.y + row,
-                    ),
-                    white,
+    let draw_curly = |buffer: &mut Image| {
+        let max_y = metrics.cell_size.height as usize - 1;
+        let x_factor = (2. * std::f32::consts::PI) / metrics.cell_size.width as f32;
+
+        // Have the wave go from the descender to the bottom of the cell
+        let wave_height = metrics.cell_size.height - (cell_rect.origin.y + metrics.descender_row);
+
+        let half_height = (wave_height as f32 / 4.).max(1.);
+        let y = ((cell_rect.origin.y + metrics.descender_row) as usize)
+            .saturating_sub(half_height as usize);
+
+        fn add(x: usize, y: usize, val: u8, max_y: usize, buffer: &mut Image) {
+            let y = y.min(max_y);
+            let pixel = buffer.pixel_mut(x, y);
+            let (current, _, _, _) = SrgbaPixel::with_srgba_u32(*pixel).as_rgba();
+            let value = current.saturating_add(val);
+            *pixel = SrgbaPixel::rgba(value, value, value, value).as_srgba32();
+        }
+
+        for x in 0..metrics.cell_size.width as usize {
+            let vertical = -half_height * (x as f32 * x_factor).sin() + half_height;
+            let v1 = vertical.floor();
+            let v2 = vertical.ceil();
+
+            for row in 0..metrics.underline_height as usize {
+                let value = (255. * (vertical - v1).abs()) as u8;
+                add(
+                    x,
+                    row.saturating_add(y).saturating_add(v1 as usize),
+                    255u8.saturating_sub(value),
+                    max_y,
+                    buffer,
+                );
+                add(
+                    x,
+                    row.saturating_add(y).saturating_add(v2 as usize),
+                    value,
+                    max_y,
+                    buffer,
                 );
             }
-        };
-
-        buffer.clear_rect(cell_rect, black);
-        if key.overline {
-            draw_overline(&mut buffer);
         }
-        match key.underline {
-            Underline::None => {}
-            Underline::Single => draw_single(&mut buffer),
-            Underline::Curly => draw_curly(&mut buffer),
-            Underline::Dashed => draw_dashed(&mut buffer),
-            Underline::Dotted => draw_dotted(&mut buffer),
-            Underline::Double => draw_double(&mut buffer),
+    };
+
+    let draw_double = |buffer: &mut Image| {
+        let first_line = metrics
+            .descender_row
+            .min(metrics.descender_plus_two - 2 * metrics.underline_height);
+
+        for row in 0..metrics.underline_height {
+            buffer.draw_line(
+                Point::new(cell_rect.origin.x, cell_rect.origin.y + first_line + row),
+                Point::new(
+                    cell_rect.origin.x + metrics.cell_size.width,
+                    cell_rect.origin.y + first_line + row,
+                ),
+                white,
+            );
+            buffer.draw_line(
+                Point::new(
+                    cell_rect.origin.x,
+                    cell_rect.origin.y + metrics.descender_plus_two + row,
+                ),
+                Point::new(
+                    cell_rect.origin.x + metrics.cell_size.width,
+                    cell_rect.origin.y + metrics.descender_plus_two + row,
+                ),
+                white,
+            );
         }
-        if key.strike_through {
-            draw_strike(&mut buffer);
+    };
+
+    let draw_strike = |buffer: &mut Image| {
+        for row in 0..metrics.underline_height {
+            buffer.draw_line(
+                Point::new(
+                    cell_rect.origin.x,
+                    cell_rect.origin.y + metrics.strike_row + row,
+                ),
+                Point::new(
+                    cell_rect.origin.x + metrics.cell_size.width,
+                    cell_rect.origin.y + metrics.strike_row + row,
+                ),
+                white,
+            );
         }
-        let sprite = self.atlas.allocate(&buffer)?;
-        self.line_glyphs.insert(key, sprite.clone());
-        Ok(sprite)
+    };
+
+    let draw_overline = |buffer: &mut Image| {
+        for row in 0..metrics.underline_height {
+            buffer.draw_line(
+                Point::new(
+                    cell_rect.origin.x,
+                    cell_rect.origin.y + metrics.overline_row + row,
+                ),
+                Point::new(
+                    cell_rect.origin.x + metrics.cell_size.width,
+                    cell_rect.origin.y + metrics.overline_row + row,
+                ),
+                white,
+            );
+        }
+    };
+
+    buffer.clear_rect(cell_rect, black);
+    if key.overline {
+        draw_overline(&mut buffer);
     }
+    match key.underline {
+        Underline::None => {}
+        Underline::Single => draw_single(&mut buffer),
+        Underline::Curly => draw_curly(&mut buffer),
+        Underline::Dashed => draw_dashed(&mut buffer),
+        Underline::Dotted => draw_dotted(&mut buffer),
+        Underline::Double => draw_double(&mut buffer),
+    }
+    if key.strike_through {
+        draw_strike(&mut buffer);
+    }
+    buffer
+}
 
-    /// Figure out what we're going to draw for the underline.
-    /// If the current cell is part of the current URL highlight
-    /// then we want to show the underline.
-    pub fn cached_line_sprite(
-        &mut self,
-        is_highlited_hyperlink: bool,
-        is_strike_through: bool,
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metrics() -> RenderMetrics {
+        RenderMetrics {
+            descender: PixelLength::new(3.),
+            descender_row: 13,
+            descender_plus_two: 15,
+            underline_height: 1,
+            strike_row: 8,
+            overline_row: 0,
+            cell_size: ::window::Size::new(8, 18),
+        }
+    }
+
+    /// Returns, for each row of the sprite for `underline`, the number
+    /// of pixels that are lit
+    fn lit_rows(underline: Underline) -> Vec<usize> {
+        sprite_rows(&metrics(), false, underline, false)
+    }
+
+    fn sprite_rows(
+        metrics: &RenderMetrics,
+        strike_through: bool,
         underline: Underline,
         overline: bool,
-        metrics: &RenderMetrics,
-    ) -> anyhow::Result<Sprite> {
-        let effective_underline = match (is_highlited_hyperlink, underline) {
-            (true, Underline::None) => Underline::Single,
-            (true, Underline::Single) => Underline::Double,
-            (true, _) => Underline::Single,
-            (false, u) => u,
-        };
-
+    ) -> Vec<usize> {
         let key = LineKey {
-            strike_through: is_strike_through,
+            strike_through,
+            underline,
             overline,
-            underline: effective_underline,
             size: metrics.into(),
+            descender_row: metrics.descender_row,
+            descender_plus_two: metrics.descender_plus_two,
+            underline_height: metrics.underline_height,
+            strike_row: metrics.strike_row,
+            overline_row: metrics.overline_row,
         };
+        let image = line_image(&key, metrics);
+        let (width, height) = image.image_dimensions();
+        (0..height)
+            .map(|y| {
+                image
+                    .horizontal_pixel_range(0, width, y)
+                    .iter()
+                    .filter(|&&pixel| pixel != 0)
+                    .count()
+            })
+            .collect()
+    }
 
-        if let Some(s) = self.line_glyphs.get(&key) {
-            return Ok(s.clone());
+    #[test]
+    fn each_underline_style_has_its_own_sprite() {
+        let lit = |rows: &[usize]| -> Vec<usize> {
+            (0..rows.len()).filter(|&y| rows[y] > 0).collect()
+        };
+
+        assert_eq!(lit(&lit_rows(Underline::None)), Vec::<usize>::new());
+
+        let single = lit_rows(Underline::Single);
+        assert_eq!(lit(&single), vec![13]);
+        assert_eq!(single[13], 8);
+
+        assert_eq!(lit(&lit_rows(Underline::Double)), vec![13, 15]);
+
+        // Dotted and dashed underlines are broken lines on the same row
+        // as a single underline, with shorter dots than dashes
+        let dotted = lit_rows(Underline::Dotted);
+        let dashed = lit_rows(Underline::Dashed);
+        assert_eq!(lit(&dotted), vec![13]);
+        assert_eq!(lit(&dashed), vec![13]);
+        assert!(dotted[13] > 0 && dotted[13] < 8);
+        assert!(dashed[13] > 0 && dashed[13] < 8);
+        assert_ne!(dotted, dashed);
+
+        // A curly underline waves across several rows
+        assert!(lit(&lit_rows(Underline::Curly)).len() > 1);
+    }
+
+    #[test]
+    fn explicit_strikethrough_and_overline_positions() {
+        use crate::utilsprites::decoration_row;
+        use config::Dimension;
+
+        // The same font at 72 and at 144 dpi
+        for (dpi, cell_height, px, cells) in
+            [(72., 18, (2, 3), (4, 9)), (144., 36, (2, 3), (9, 18))]
+        {
+            let mut metrics = metrics();
+            metrics.cell_size.height = cell_height;
+            metrics.descender_row = cell_height - 5;
+            metrics.descender_plus_two = cell_height - 3;
+
+            let row = |position: Dimension| {
+                decoration_row(
+                    &position,
+                    dpi,
+                    metrics.descender_row as f32 / 2.,
+                    cell_height,
+                )
+            };
+
+            metrics.overline_row = row(Dimension::Pixels(2.));
+            metrics.strike_row = row(Dimension::Pixels(3.));
+            let rows = sprite_rows(&metrics, true, Underline::None, true);
+            let lit: Vec<usize> = (0..rows.len()).filter(|&y| rows[y] > 0).collect();
+            assert_eq!(lit, vec![px.0, px.1], "px at {dpi} dpi");
+
+            metrics.overline_row = row(Dimension::Cells(0.25));
+            metrics.strike_row = row(Dimension::Cells(0.5));
+            let rows = sprite_rows(&metrics, true, Underline::None, true);
+            let lit: Vec<usize> = (0..rows.len()).filter(|&y| rows[y] > 0).collect();
+            assert_eq!(lit, vec![cells.0, cells.1], "cells at {dpi} dpi");
         }
+    }
 
-        self.line_sprite(key, metrics)
+    #[test]
+    fn highlighted_hyperlinks_are_underlined() {
+        assert_eq!(effective_underline(true, Underline::None), Underline::Single);
+        assert_eq!(effective_underline(true, Underline::Single), Underline::Double);
+        assert_eq!(effective_underline(true, Underline::Curly), Underline::Single);
+        for underline in [Underline::None, Underline::Curly, Underline::Dotted] {
+            assert_eq!(effective_underline(false, underline), underline);
+        }
     }
 }
@@ -1,7 +1,8 @@
 use config::keyassignment::*;
-use config::KeyNoAction;
+use config::{Dimension, KeyNoAction};
 use config::window::WindowLevel;
 use mux::pane::PaneId;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub enum InputEffect {
@@ -43,8 +44,27 @@ pub enum InputEffect {
     },
     ResetFontSize,
     ResetFontAndWindowSize,
+    AdjustPaneFontSize {
+        delta: f64,
+    },
+    ResetPaneFontSize,
+    SetWindowPadding {
+        left: Option<Dimension>,
+        right: Option<Dimension>,
+        top: Option<Dimension>,
+        bottom: Option<Dimension>,
+    },
+    ResetWindowPadding,
+    AdjustWindowOpacity {
+        delta: f32,
+    },
+    SetWindowBackgroundBlur {
+        enabled: bool,
+        radius: Option<u32>,
+    },
+    ResetWindowBackground,
     ActivateTab {
-        index: isize,
+        index: TabIndex,
     },
     ActivateTabRelative {
         delta: isize,
@@ -70,10 +90,29 @@ pub enum InputEffect {
         direction: PaneDirection,
         amount: usize,
     },
+    SetSplitSize {
+        direction: PaneDirection,
+        size: SplitSize,
+    },
     TogglePaneZoom,
     SetPaneZoom {
         zoomed: bool,
     },
+    SetPaneRenderFrozen {
+        frozen: bool,
+    },
+    SetPaneHighlightPattern {
+        pattern: Option<Pattern>,
+    },
+    TogglePaneBroadcast {
+        scope: BroadcastScope,
+    },
+    SetPaneBroadcastParticipation {
+        participation: PaneBroadcastParticipation,
+    },
+    TogglePaneEscapeLog {
+        path: Option<PathBuf>,
+    },
     ClosePane {
         confirm: bool,
     },
@@ -97,6 +136,9 @@ pub enum InputEffect {
     Paste {
         source: ClipboardPasteSource,
     },
+    PasteAsOsc52 {
+        source: ClipboardPasteSource,
+    },
     CompleteSelection {
         destination: ClipboardCopyDestination,
     },
@@ -113,6 +155,8 @@ pub enum InputEffect {
     ScrollToPrompt {
         direction: isize,
     },
+    ScrollToLastCommandStart,
+    SelectLastCommandOutput,
     ScrollToTop,
     ScrollToBottom,
     SelectAtMouseCursor {
@@ -155,6 +199,12 @@ pub enum InputEffect {
     ShowPaneSelect {
         args: PaneSelectArguments,
     },
+    MovePaneToNewTab {
+        keep_focus: bool,
+    },
+    MovePaneToWindow {
+        window: MoveTarget,
+    },
     ShowCharSelect {
         args: CharSelectArguments,
     },
@@ -1,19 +1,27 @@
 use crate::commands::CommandDef;
 use config::observers::*;
 use config::keyassignment::{
-    ClipboardCopyDestination, ClipboardPasteSource, KeyAssignment, KeyTableEntry, KeyTables,
-    MouseEventTrigger, SelectionMode,
+    select_key_table_entry, unconditional_key_table_entry, ClipboardCopyDestination,
+    ClipboardPasteSource, KeyAssignment, KeyTableEntry, KeyTables, MouseEventTrigger,
+    SelectionMode,
+};
+use config::{
+    ConfigHandle, KeyConditionSubject, MouseEventAltScreen, MouseEventTriggerMods, MouseRegion,
 };
-use config::{ConfigHandle, MouseEventAltScreen, MouseEventTriggerMods};
 use std::collections::{BTreeMap, HashMap};
 use std::time::Duration;
 use phaedra_dynamic::{ToDynamic, Value};
 use phaedra_term::input::MouseButton;
 use window::{KeyCode, Modifiers, PhysKeyCode, UIKeyCapRendering};
 
+pub type MouseBindings = HashMap<(MouseEventTrigger, MouseEventTriggerMods), KeyAssignment>;
+
 pub struct InputMap {
     pub keys: KeyTables,
-    pub mouse: HashMap<(MouseEventTrigger, MouseEventTriggerMods), KeyAssignment>,
+    pub mouse: MouseBindings,
+    /// Bindings that only apply over a particular region of the window.
+    /// These are consulted before `mouse`.
+    pub mouse_by_region: HashMap<MouseRegion, MouseBindings>,
     leader: Option<(KeyCode, Modifiers, Duration)>,
 }
 
@@ -24,11 +32,11 @@ impl InputMap {
     }
 
     pub fn new(config: &ConfigHandle) -> Self {
-        let mut mouse = config
-            .mouse()
-            .mouse_bindings
-            .iter()
-            .map(|mouse| ((mouse.event.clone(), mouse.mods), mouse.action.clone()))
+        let mut mouse_bindings = config.mouse_bindings();
+        let mut mouse = mouse_bindings.remove(&None).unwrap_or_default();
+        let mut mouse_by_region = mouse_bindings
+            .into_iter()
+            .filter_map(|(region, bindings)| Some((region?, bindings)))
             .collect::<HashMap<_, _>>();
 
         let mut keys = config.key_bindings();
@@ -82,13 +90,18 @@ impl InputMap {
                 let (disable_code, disable_mods) = code.normalize_shift(mods);
                 if keys
                     .default
-                    .contains_key(&(disable_code.clone(), disable_mods))
+                    .get(&(disable_code.clone(), disable_mods))
+                    .and_then(|entries| unconditional_key_table_entry(entries))
+                    .is_some()
                 {
                     continue;
                 }
-                keys.default
-                    .entry((code, mods))
-                    .or_insert(KeyTableEntry { action });
+                // The default assignment is the fallback for any
+                // conditional bindings of the same key
+                let entries = keys.default.entry((code, mods)).or_default();
+                if unconditional_key_table_entry(entries).is_none() {
+                    entries.push(KeyTableEntry::new(action));
+                }
             }
         }
 
@@ -355,30 +368,14 @@ impl InputMap {
             );
         }
 
-        keys.default
-            .retain(|_, v| v.action != KeyAssignment::DisableDefaultAssignment);
-
-        mouse.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
-        // Expand MouseEventAltScreen::Any to individual True/False entries
-        let mut expanded_mouse = vec![];
-        for ((code, mods), v) in &mouse {
-            if mods.alt_screen == MouseEventAltScreen::Any {
-                let mods_true = MouseEventTriggerMods {
-                    alt_screen: MouseEventAltScreen::True,
-                    ..*mods
-                };
-                let mods_false = MouseEventTriggerMods {
-                    alt_screen: MouseEventAltScreen::False,
-                    ..*mods
-                };
-                expanded_mouse.push((code.clone(), mods_true, v.clone()));
-                expanded_mouse.push((code.clone(), mods_false, v.clone()));
-            }
+        for entries in keys.default.values_mut() {
+            entries.retain(|entry| entry.action != KeyAssignment::DisableDefaultAssignment);
         }
-        // Eliminate ::Any
-        mouse.retain(|(_, mods), _| mods.alt_screen != MouseEventAltScreen::Any);
-        for (code, mods, v) in expanded_mouse {
-            mouse.insert((code, mods), v);
+        keys.default.retain(|_, entries| !entries.is_empty());
+
+        expand_mouse_bindings(&mut mouse);
+        for bindings in mouse_by_region.values_mut() {
+            expand_mouse_bindings(bindings);
         }
 
         keys.by_name
@@ -392,6 +389,7 @@ impl InputMap {
             keys,
             leader,
             mouse,
+            mouse_by_region,
         }
     }
 
@@ -409,11 +407,13 @@ impl InputMap {
     ) -> Vec<(KeyCode, Modifiers)> {
         let mut candidates = vec![];
 
-        for ((key, mods), entry) in &self.keys.default {
+        for ((key, mods), entries) in &self.keys.default {
             if mods.contains(Modifiers::LEADER) {
                 continue;
             }
-            if entry.action == *action {
+            // A shortcut that only applies to some panes can't be
+            // made application-wide
+            if entries.len() == 1 && entries[0].when.is_none() && entries[0].action == *action {
                 candidates.push((key.clone(), mods.clone()));
             }
         }
@@ -444,29 +444,58 @@ impl InputMap {
         self.keys.by_name.contains_key(name)
     }
 
+    /// Looks up the binding for a key in the named table, or the
+    /// default table.  When several bindings are conditional on the
+    /// pane, the first that applies to `pane` is returned.
     pub fn lookup_key(
         &self,
         key: &KeyCode,
         mods: Modifiers,
         table_name: Option<&str>,
+        pane: &dyn KeyConditionSubject,
     ) -> Option<KeyTableEntry> {
         let table = match table_name {
             Some(name) => self.keys.by_name.get(name)?,
             None => &self.keys.default,
         };
 
-        table
-            .get(&key.normalize_shift(mods.remove_positional_mods()))
-            .cloned()
+        let entries = table.get(&key.normalize_shift(mods.remove_positional_mods()))?;
+        select_key_table_entry(entries, pane).cloned()
     }
 
+    /// Looks up a mouse binding for an event over `region`, preferring
+    /// a binding restricted to that region and falling back to the
+    /// region-less bindings.
     pub fn lookup_mouse(
         &self,
         event: MouseEventTrigger,
         mut mods: MouseEventTriggerMods,
+        region: MouseRegion,
     ) -> Option<KeyAssignment> {
         mods.mods = mods.mods.remove_positional_mods();
-        self.mouse.get(&(event, mods)).cloned()
+        let key = (event, mods);
+        self.mouse_by_region
+            .get(&region)
+            .and_then(|bindings| bindings.get(&key))
+            .or_else(|| self.mouse.get(&key))
+            .cloned()
+    }
+
+    /// Looks up a mouse binding that is explicitly restricted to `region`.
+    /// This is used for UI elements such as the tab bar and scroll bar,
+    /// which have their own default behavior that region-less bindings
+    /// must not override.
+    pub fn lookup_mouse_in_region(
+        &self,
+        event: MouseEventTrigger,
+        mut mods: MouseEventTriggerMods,
+        region: MouseRegion,
+    ) -> Option<KeyAssignment> {
+        mods.mods = mods.mods.remove_positional_mods();
+        self.mouse_by_region
+            .get(&region)?
+            .get(&(event, mods))
+            .cloned()
     }
 
     pub fn dump_config(&self, key_table: Option<&str>) {
@@ -526,50 +555,128 @@ impl InputMap {
     }
 
     fn show_mouse(&self) {
-        for (label, alt_screen, mouse_reporting) in [
-            ("Mouse", MouseEventAltScreen::False, false),
-            ("Mouse: alt_screen", MouseEventAltScreen::True, false),
-            ("Mouse: mouse_reporting", MouseEventAltScreen::False, true),
-            (
-                "Mouse: mouse_reporting + alt_screen",
-                MouseEventAltScreen::True,
-                true,
-            ),
-        ] {
-            let ordered = self
-                .mouse
-                .iter()
-                .filter(|((_, m), _)| {
-                    m.alt_screen == alt_screen && m.mouse_reporting == mouse_reporting
-                })
-                .collect::<BTreeMap<_, _>>();
-
-            if ordered.is_empty() {
-                continue;
-            }
+        show_mouse_bindings(&self.mouse, "Mouse");
 
-            section_header(label);
+        let mut regions = self.mouse_by_region.keys().collect::<Vec<_>>();
+        regions.sort();
+        for region in regions {
+            show_mouse_bindings(
+                &self.mouse_by_region[region],
+                &format!("Mouse ({region:?})"),
+            );
+        }
+    }
+}
 
-            let mut trigger_width = 0;
-            let mut mod_width = 0;
-            for (trigger, mods) in ordered.keys() {
-                mod_width = mod_width.max(format!("{:?}", mods.mods).len());
-                trigger_width = trigger_width.max(format!("{trigger:?}").len());
-            }
+/// The InputMap of a window, along with the generations of the config
+/// and of the keyboard layout that it was built for.  The bindings are
+/// resolved against the layout when the map is built, so it must be
+/// built again when either generation changes, but not otherwise.
+pub struct InputMapCache {
+    map: InputMap,
+    config_generation: usize,
+    keyboard_generation: usize,
+}
 
-            for ((trigger, mods), action) in ordered {
-                let mods = if mods.mods == Modifiers::NONE {
-                    String::new()
-                } else {
-                    format!("{:?}", mods.mods)
-                };
-                let trigger = format!("{trigger:?}");
-                println!("\t{mods:mod_width$}   {trigger:trigger_width$}   ->   {action:?}");
-            }
+impl InputMapCache {
+    pub fn new(config: &ConfigHandle, keyboard_generation: usize) -> Self {
+        Self {
+            map: InputMap::new(config),
+            config_generation: config.generation(),
+            keyboard_generation,
+        }
+    }
 
-            println!();
+    /// Builds the map again if it was built for a different generation
+    /// of the config or the keyboard layout.  Returns true if it did.
+    pub fn refresh(&mut self, config: &ConfigHandle, keyboard_generation: usize) -> bool {
+        if self.config_generation == config.generation()
+            && self.keyboard_generation == keyboard_generation
+        {
+            return false;
+        }
+        *self = Self::new(config, keyboard_generation);
+        true
+    }
+}
+
+impl std::ops::Deref for InputMapCache {
+    type Target = InputMap;
+
+    fn deref(&self) -> &InputMap {
+        &self.map
+    }
+}
+
+fn show_mouse_bindings(bindings: &MouseBindings, title: &str) {
+    for (label, alt_screen, mouse_reporting) in [
+        ("", MouseEventAltScreen::False, false),
+        (": alt_screen", MouseEventAltScreen::True, false),
+        (": mouse_reporting", MouseEventAltScreen::False, true),
+        (
+            ": mouse_reporting + alt_screen",
+            MouseEventAltScreen::True,
+            true,
+        ),
+    ] {
+        let ordered = bindings
+            .iter()
+            .filter(|((_, m), _)| {
+                m.alt_screen == alt_screen && m.mouse_reporting == mouse_reporting
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        if ordered.is_empty() {
+            continue;
+        }
+
+        section_header(&format!("{title}{label}"));
+
+        let mut trigger_width = 0;
+        let mut mod_width = 0;
+        for (trigger, mods) in ordered.keys() {
+            mod_width = mod_width.max(format!("{:?}", mods.mods).len());
+            trigger_width = trigger_width.max(format!("{trigger:?}").len());
+        }
+
+        for ((trigger, mods), action) in ordered {
+            let mods = if mods.mods == Modifiers::NONE {
+                String::new()
+            } else {
+                format!("{:?}", mods.mods)
+            };
+            let trigger = format!("{trigger:?}");
+            println!("\t{mods:mod_width$}   {trigger:trigger_width$}   ->   {action:?}");
+        }
+
+        println!();
+    }
+}
+
+/// Removes disabled entries and expands MouseEventAltScreen::Any
+/// into individual True/False entries
+fn expand_mouse_bindings(mouse: &mut MouseBindings) {
+    mouse.retain(|_, v| *v != KeyAssignment::DisableDefaultAssignment);
+    let mut expanded_mouse = vec![];
+    for ((code, mods), v) in mouse.iter() {
+        if mods.alt_screen == MouseEventAltScreen::Any {
+            let mods_true = MouseEventTriggerMods {
+                alt_screen: MouseEventAltScreen::True,
+                ..*mods
+            };
+            let mods_false = MouseEventTriggerMods {
+                alt_screen: MouseEventAltScreen::False,
+                ..*mods
+            };
+            expanded_mouse.push((code.clone(), mods_true, v.clone()));
+            expanded_mouse.push((code.clone(), mods_false, v.clone()));
         }
     }
+    // Eliminate ::Any
+    mouse.retain(|(_, mods), _| mods.alt_screen != MouseEventAltScreen::Any);
+    for (code, mods, v) in expanded_mouse {
+        mouse.insert((code, mods), v);
+    }
 }
 
 fn section_header(title: &str) {
@@ -772,8 +879,8 @@ fn quote_lua_string(s: &str) -> String {
     result
 }
 
-fn lua_key(key: &KeyCode, mods: Modifiers, action: &KeyAssignment) -> String {
-    let dyn_action = action.to_dynamic();
+fn lua_key(key: &KeyCode, mods: Modifiers, entry: &KeyTableEntry) -> String {
+    let dyn_action = entry.action.to_dynamic();
     // println!(" -- {dyn_action:?}");
     let action = luaify(dyn_action, true);
     let key = lua_key_code(key);
@@ -781,7 +888,13 @@ fn lua_key(key: &KeyCode, mods: Modifiers, action: &KeyAssignment) -> String {
 
     let mods = format!("{mods:?}").replace(" ", "");
 
-    format!("{{ key = {key}, mods = '{mods}', action = {action} }}")
+    match &entry.when {
+        Some(when) => {
+            let when = luaify(when.to_dynamic(), false);
+            format!("{{ key = {key}, mods = '{mods}', action = {action}, when = {when} }}")
+        }
+        None => format!("{{ key = {key}, mods = '{mods}', action = {action} }}"),
+    }
 }
 
 fn show_key_table(table: &config::keyassignment::KeyTable) {
@@ -794,15 +907,22 @@ fn show_key_table(table: &config::keyassignment::KeyTable) {
         key_width = key_width.max(human_key(key).len());
     }
 
-    for ((key, mods), entry) in ordered {
-        let action = &entry.action;
+    for ((key, mods), entries) in ordered {
         let mods = if *mods == Modifiers::NONE {
             String::new()
         } else {
             format!("{mods:?}")
         };
         let key = human_key(key);
-        println!("\t{mods:mod_width$}   {key:key_width$}   ->   {action:?}");
+        for entry in entries {
+            let action = &entry.action;
+            match &entry.when {
+                Some(when) => println!(
+                    "\t{mods:mod_width$}   {key:key_width$}   ->   {action:?} when {when:?}"
+                ),
+                None => println!("\t{mods:mod_width$}   {key:key_width$}   ->   {action:?}"),
+            }
+        }
     }
 }
 
@@ -810,8 +930,197 @@ fn show_key_table_as_lua(table: &config::keyassignment::KeyTable, indent: usize)
     let ordered = table.iter().collect::<BTreeMap<_, _>>();
 
     let pad = " ".repeat(indent);
-    for ((key, mods), entry) in ordered {
-        let action = &entry.action;
-        println!("{pad}{},", lua_key(key, *mods, action));
+    for ((key, mods), entries) in ordered {
+        for entry in entries {
+            println!("{pad}{},", lua_key(key, *mods, entry));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn left_down() -> MouseEventTrigger {
+        MouseEventTrigger::Down {
+            streak: 1,
+            button: MouseButton::Left,
+        }
+    }
+
+    fn mods(mods: Modifiers) -> MouseEventTriggerMods {
+        MouseEventTriggerMods {
+            mods,
+            mouse_reporting: false,
+            alt_screen: MouseEventAltScreen::False,
+        }
+    }
+
+    fn with_scroll_bar_binding(chord: Modifiers) -> InputMap {
+        let mut map = InputMap::default_input_map();
+        let mut bindings = MouseBindings::new();
+        bindings.insert(
+            (
+                left_down(),
+                MouseEventTriggerMods {
+                    alt_screen: MouseEventAltScreen::Any,
+                    ..mods(chord)
+                },
+            ),
+            KeyAssignment::ScrollToTop,
+        );
+        expand_mouse_bindings(&mut bindings);
+        map.mouse_by_region.insert(MouseRegion::ScrollBar, bindings);
+        map
+    }
+
+    #[test]
+    fn regional_binding_only_fires_in_its_region() {
+        let chord = Modifiers::CTRL | Modifiers::SHIFT | Modifiers::ALT;
+        let map = with_scroll_bar_binding(chord);
+
+        assert_eq!(
+            map.lookup_mouse_in_region(left_down(), mods(chord), MouseRegion::ScrollBar),
+            Some(KeyAssignment::ScrollToTop)
+        );
+        assert_eq!(
+            map.lookup_mouse(left_down(), mods(chord), MouseRegion::ScrollBar),
+            Some(KeyAssignment::ScrollToTop)
+        );
+
+        for region in [
+            MouseRegion::Text,
+            MouseRegion::TabBar,
+            MouseRegion::SplitDivider,
+            MouseRegion::Border,
+        ] {
+            assert_eq!(
+                map.lookup_mouse_in_region(left_down(), mods(chord), region),
+                None
+            );
+            assert_ne!(
+                map.lookup_mouse(left_down(), mods(chord), region),
+                Some(KeyAssignment::ScrollToTop)
+            );
+        }
+    }
+
+    struct FakePane(Option<&'static str>);
+
+    impl KeyConditionSubject for FakePane {
+        fn foreground_process_name(&self) -> Option<String> {
+            self.0.map(|name| name.to_string())
+        }
+
+        fn title(&self) -> String {
+            String::new()
+        }
+
+        fn domain_name(&self) -> Option<String> {
+            None
+        }
+    }
+
+    #[test]
+    fn conditional_key_bindings_fall_through() {
+        use config::keyassignment::{add_key_table_entry, PaneDirection};
+        use config::KeyCondition;
+
+        let vim = || KeyCondition {
+            foreground_process_name: Some("*vim".to_string()),
+            ..Default::default()
+        };
+        let ctrl_h = (KeyCode::Char('h'), Modifiers::CTRL);
+        let ctrl_l = (KeyCode::Char('l'), Modifiers::CTRL);
+
+        let mut map = InputMap::default_input_map();
+        let entries = map.keys.default.entry(ctrl_h.clone()).or_default();
+        add_key_table_entry(
+            entries,
+            KeyTableEntry::new(KeyAssignment::ActivatePaneDirection(PaneDirection::Left)),
+        );
+        add_key_table_entry(
+            entries,
+            KeyTableEntry {
+                action: KeyAssignment::Nop,
+                when: Some(vim()),
+            },
+        );
+        // Only bound for vim; other panes receive the key
+        add_key_table_entry(
+            map.keys.default.entry(ctrl_l.clone()).or_default(),
+            KeyTableEntry {
+                action: KeyAssignment::ActivatePaneDirection(PaneDirection::Right),
+                when: Some(vim()),
+            },
+        );
+
+        let lookup = |(key, mods): &(KeyCode, Modifiers), pane: &FakePane| {
+            map.lookup_key(key, *mods, None, pane)
+                .map(|entry| entry.action)
+        };
+        let nvim = FakePane(Some("/usr/bin/nvim"));
+        let zsh = FakePane(Some("/bin/zsh"));
+
+        assert_eq!(lookup(&ctrl_h, &nvim), Some(KeyAssignment::Nop));
+        assert_eq!(
+            lookup(&ctrl_h, &zsh),
+            Some(KeyAssignment::ActivatePaneDirection(PaneDirection::Left))
+        );
+        assert_eq!(
+            lookup(&ctrl_h, &FakePane(None)),
+            Some(KeyAssignment::ActivatePaneDirection(PaneDirection::Left))
+        );
+        assert_eq!(
+            lookup(&ctrl_l, &nvim),
+            Some(KeyAssignment::ActivatePaneDirection(PaneDirection::Right))
+        );
+        assert_eq!(lookup(&ctrl_l, &zsh), None);
+    }
+
+    #[test]
+    fn default_bindings_fall_back_from_any_region() {
+        let map = with_scroll_bar_binding(Modifiers::CTRL | Modifiers::SHIFT | Modifiers::ALT);
+        let expected = Some(KeyAssignment::SelectTextAtMouseCursor(SelectionMode::Cell));
+
+        assert_eq!(
+            map.lookup_mouse(left_down(), mods(Modifiers::NONE), MouseRegion::Text),
+            expected
+        );
+        // The regional table doesn't bind this chord, so the default applies
+        assert_eq!(
+            map.lookup_mouse(left_down(), mods(Modifiers::NONE), MouseRegion::ScrollBar),
+            expected
+        );
+        // UI items only consult regional bindings, leaving their own
+        // default behavior intact
+        assert_eq!(
+            map.lookup_mouse_in_region(left_down(), mods(Modifiers::NONE), MouseRegion::TabBar),
+            None
+        );
+    }
+
+    #[test]
+    fn input_map_is_rebuilt_for_a_new_keyboard_layout() {
+        let config = ConfigHandle::default_config();
+        let mut cache = InputMapCache::new(&config, 3);
+
+        assert!(cache.refresh(&config, 4));
+        assert_eq!(cache.keyboard_generation, 4);
+        assert!(!cache.keys.default.is_empty());
+        assert!(!cache.refresh(&config, 4));
+    }
+
+    #[test]
+    fn input_map_is_kept_while_the_generations_are_unchanged() {
+        let config = ConfigHandle::default_config();
+        let mut cache = InputMapCache::new(&config, 0);
+        let keys = cache.keys.default.len();
+
+        for _ in 0..3 {
+            assert!(!cache.refresh(&config, 0));
+        }
+        assert_eq!(cache.keys.default.len(), keys);
+        assert_eq!(cache.config_generation, config.generation());
     }
 }
@@ -1,6 +1,8 @@
 use config::keyassignment::*;
 
 use crate::input_effect::InputEffect;
+use anyhow::anyhow;
+use phaedra_dynamic::{FromDynamic, FromDynamicOptions, Value};
 
 pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
     match assignment {
@@ -20,6 +22,9 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
             destination: *destination,
         }],
         KeyAssignment::PasteFrom(source) => vec![InputEffect::Paste { source: *source }],
+        KeyAssignment::PasteAsOsc52(source) => {
+            vec![InputEffect::PasteAsOsc52 { source: *source }]
+        }
         KeyAssignment::ActivateTabRelative(delta) => vec![InputEffect::ActivateTabRelative {
             delta: *delta,
             wrap: true,
@@ -34,6 +39,30 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
         KeyAssignment::DecreaseFontSize => vec![InputEffect::AdjustFontSize { delta: -1.0 }],
         KeyAssignment::ResetFontSize => vec![InputEffect::ResetFontSize],
         KeyAssignment::ResetFontAndWindowSize => vec![InputEffect::ResetFontAndWindowSize],
+        KeyAssignment::IncreasePaneFontSize => {
+            vec![InputEffect::AdjustPaneFontSize { delta: 1.0 }]
+        }
+        KeyAssignment::DecreasePaneFontSize => {
+            vec![InputEffect::AdjustPaneFontSize { delta: -1.0 }]
+        }
+        KeyAssignment::ResetPaneFontSize => vec![InputEffect::ResetPaneFontSize],
+        KeyAssignment::SetWindowPadding(padding) => vec![InputEffect::SetWindowPadding {
+            left: padding.left,
+            right: padding.right,
+            top: padding.top,
+            bottom: padding.bottom,
+        }],
+        KeyAssignment::ResetWindowPadding => vec![InputEffect::ResetWindowPadding],
+        KeyAssignment::AdjustWindowOpacity(delta) => vec![InputEffect::AdjustWindowOpacity {
+            delta: delta.into_inner() as f32,
+        }],
+        KeyAssignment::SetWindowBackgroundBlur { enabled, radius } => {
+            vec![InputEffect::SetWindowBackgroundBlur {
+                enabled: *enabled,
+                radius: *radius,
+            }]
+        }
+        KeyAssignment::ResetWindowBackground => vec![InputEffect::ResetWindowBackground],
         KeyAssignment::ActivateTab(index) => vec![InputEffect::ActivateTab { index: *index }],
         KeyAssignment::ActivateLastTab => vec![InputEffect::ActivateLastTab],
         KeyAssignment::SendString(text) => vec![InputEffect::SendString { text: text.clone() }],
@@ -69,6 +98,11 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
             },
             InputEffect::Invalidate,
         ],
+        KeyAssignment::ScrollToLastCommandStart => vec![
+            InputEffect::ScrollToLastCommandStart,
+            InputEffect::Invalidate,
+        ],
+        KeyAssignment::SelectLastCommandOutput => vec![InputEffect::SelectLastCommandOutput],
         KeyAssignment::ScrollToTop => vec![InputEffect::ScrollToTop, InputEffect::Invalidate],
         KeyAssignment::ScrollToBottom => vec![InputEffect::ScrollToBottom, InputEffect::Invalidate],
         KeyAssignment::ShowTabNavigator => vec![InputEffect::ShowTabNavigator],
@@ -131,6 +165,10 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
             direction: *direction,
             amount: *amount,
         }],
+        KeyAssignment::SetSplitSize(direction, size) => vec![InputEffect::SetSplitSize {
+            direction: *direction,
+            size: size.clone(),
+        }],
         KeyAssignment::ActivatePaneDirection(direction) => {
             vec![InputEffect::ActivatePaneDirection {
                 direction: *direction,
@@ -143,6 +181,25 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
         KeyAssignment::SetPaneZoomState(zoomed) => {
             vec![InputEffect::SetPaneZoom { zoomed: *zoomed }]
         }
+        KeyAssignment::SetPaneRenderFrozen(frozen) => {
+            vec![InputEffect::SetPaneRenderFrozen { frozen: *frozen }]
+        }
+        KeyAssignment::SetPaneHighlightPattern(pattern) => {
+            vec![InputEffect::SetPaneHighlightPattern {
+                pattern: pattern.clone(),
+            }]
+        }
+        KeyAssignment::TogglePaneBroadcast { scope } => {
+            vec![InputEffect::TogglePaneBroadcast { scope: *scope }]
+        }
+        KeyAssignment::SetPaneBroadcastParticipation(participation) => {
+            vec![InputEffect::SetPaneBroadcastParticipation {
+                participation: *participation,
+            }]
+        }
+        KeyAssignment::TogglePaneEscapeLog { path } => {
+            vec![InputEffect::TogglePaneEscapeLog { path: path.clone() }]
+        }
         KeyAssignment::CloseCurrentPane { confirm } => {
             vec![InputEffect::ClosePane { confirm: *confirm }]
         }
@@ -192,6 +249,12 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
             split: split.clone(),
         }],
         KeyAssignment::PaneSelect(args) => vec![InputEffect::ShowPaneSelect { args: args.clone() }],
+        KeyAssignment::MovePaneToNewTab { keep_focus } => vec![InputEffect::MovePaneToNewTab {
+            keep_focus: *keep_focus,
+        }],
+        KeyAssignment::MovePaneToWindow(window) => vec![InputEffect::MovePaneToWindow {
+            window: window.clone(),
+        }],
         KeyAssignment::CharSelect(args) => vec![InputEffect::ShowCharSelect { args: args.clone() }],
         KeyAssignment::ResetTerminal => vec![InputEffect::ResetTerminal],
         KeyAssignment::OpenUri(uri) => vec![InputEffect::OpenUri { uri: uri.clone() }],
@@ -218,3 +281,98 @@ pub fn interpret_assignment(assignment: &KeyAssignment) -> Vec<InputEffect> {
         }
     }
 }
+
+/// Converts the argument of `window:perform_effect` into effects.
+/// Anything that is a valid KeyAssignment is interpreted as one; in
+/// addition, effects that have no KeyAssignment may be named by string.
+pub fn effects_from_dynamic(value: &Value) -> anyhow::Result<Vec<InputEffect>> {
+    let err = match KeyAssignment::from_dynamic(value, FromDynamicOptions::default()) {
+        Ok(assignment) => return Ok(interpret_assignment(&assignment)),
+        Err(err) => err,
+    };
+    match value {
+        Value::String(name) if name == "Invalidate" => Ok(vec![InputEffect::Invalidate]),
+        Value::String(name) if name == "UpdateTitle" => Ok(vec![InputEffect::UpdateTitle]),
+        _ => Err(anyhow!(
+            "expected a KeyAssignment, \"Invalidate\" or \"UpdateTitle\": {}",
+            err
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::execute::describe_effect;
+
+    fn names(value: Value) -> Vec<&'static str> {
+        effects_from_dynamic(&value)
+            .unwrap()
+            .iter()
+            .map(describe_effect)
+            .collect()
+    }
+
+    fn variant(name: &str, value: Value) -> Value {
+        Value::Object(
+            vec![(Value::String(name.to_string()), value)]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn effects_from_key_assignments() {
+        assert_eq!(
+            names(Value::String("ToggleFullScreen".to_string())),
+            vec!["toggle_full_screen"]
+        );
+        assert_eq!(
+            names(variant("ActivateTab", Value::I64(2))),
+            vec!["activate_tab"]
+        );
+        assert_eq!(
+            names(Value::String("IncreaseFontSize".to_string())),
+            vec!["adjust_font_size"]
+        );
+        assert_eq!(
+            names(variant(
+                "Multiple",
+                Value::Array(vec![Value::String("ResetFontSize".to_string())].into())
+            )),
+            vec!["multiple"]
+        );
+        assert_eq!(
+            names(variant("AttachDomain", Value::String("unix".to_string()))),
+            vec!["attach_domain"]
+        );
+    }
+
+    #[test]
+    fn effects_without_key_assignments() {
+        assert_eq!(
+            names(Value::String("Invalidate".to_string())),
+            vec!["invalidate"]
+        );
+        assert_eq!(
+            names(Value::String("UpdateTitle".to_string())),
+            vec!["update_title"]
+        );
+    }
+
+    #[test]
+    fn invalid_effects() {
+        for value in [
+            Value::String("NoSuchEffect".to_string()),
+            Value::I64(1),
+            variant("ActivateTab", Value::String("first".to_string())),
+        ] {
+            let err = effects_from_dynamic(&value).unwrap_err().to_string();
+            assert!(
+                err.starts_with("expected a KeyAssignment, \"Invalidate\" or \"UpdateTitle\": "),
+                "{}",
+                err
+            );
+        }
+    }
+}
@@ -35,9 +35,11 @@ use phaedra_gui_subcommands::*;
 use phaedra_mux_server_impl::update_mux_domains;
 use phaedra_toast_notification::*;
 
+mod cache_registry;
 mod colorease;
 mod commands;
 mod customglyph;
+mod diagnostics;
 mod download;
 mod frontend;
 mod glyphcache;
@@ -45,12 +47,16 @@ mod inputmap;
 pub mod execute;
 pub mod execute_render;
 pub mod frame;
+mod frame_budget;
+mod frame_recording;
 pub mod input_effect;
 pub mod interpret;
 pub mod observers;
+mod output_throttle;
 mod overlay;
 mod quad;
 pub mod render_command;
+mod render_diagnostics;
 pub mod render_plan;
 pub mod render_optics;
 mod renderstate;
@@ -59,6 +65,7 @@ mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod software_render;
 mod spawn;
 mod stats;
 mod tabbar;
@@ -101,6 +108,16 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Override specific configuration values with JSON, which is
+    /// merged into the configuration without being evaluated as lua.
+    /// The name may be a dotted path, such as `font_config.font_size`
+    #[arg(
+        long = "config-json",
+        name = "name=json",
+        value_parser=ValueParser::new(name_equals_value),
+        number_of_values = 1)]
+    config_json_override: Vec<(String, String)>,
+
     /// On Windows, whether to attempt to attach to the parent
     /// process console to display logging output
     #[arg(long = "attach-parent-console")]
@@ -136,6 +153,15 @@ enum SubCommand {
 
     #[command(name = "show-keys", about = "Show key assignments")]
     ShowKeys(ShowKeysCommand),
+
+    #[command(name = "ls-gpus", about = "Display information about GPU adapters")]
+    LsGpus(LsGpusCommand),
+
+    #[command(
+        name = "replay-frames",
+        about = "Replay the render commands recorded while runtime.record_frames is configured"
+    )]
+    ReplayFrames(ReplayFramesCommand),
 }
 
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
@@ -374,11 +400,27 @@ async fn async_run_terminal_gui(
     if let Err(err) = spawn_mux_server(unix_socket_path, should_publish) {
         log::warn!("{:#}", err);
     }
+    promise::spawn::spawn(mux::session::save_session_layout_periodically()).detach();
 
     if !opts.no_auto_connect {
         connect_to_auto_connect_domains().await?;
     }
 
+    if opts.resurrect {
+        config::configuration().update_ulimit()?;
+        match mux::session::restore_saved_session_layout().await {
+            Ok(window_ids) if !window_ids.is_empty() => {
+                log::info!(
+                    "restored {} window(s) from the saved session layout",
+                    window_ids.len()
+                );
+                return Ok(());
+            }
+            Ok(_) => log::info!("the saved session layout has no panes that can be restored"),
+            Err(err) => log::error!("while restoring the saved session layout: {:#}", err),
+        }
+    }
+
     let spawn_command = match &cmd {
         Some(cmd) => Some(SpawnCommand::from_command_builder(cmd)?),
         None => None,
@@ -689,6 +731,7 @@ fn run_terminal_gui(opts: StartCommand, default_domain_name: Option<String>) ->
             if prog.is_empty() { None } else { Some(prog) },
             config.launch().default_prog.as_ref(),
             config.launch().default_cwd.as_ref(),
+            None,
         )?;
         if let Some(cwd) = &opts.cwd {
             builder.cwd(if cwd.is_relative() {
@@ -759,6 +802,7 @@ fn notify_on_panic() {
         if let Some(s) = info.payload().downcast_ref::<&str>() {
             fatal_toast_notification("Phaedra panic", s);
         }
+        diagnostics::report_crash(&info.to_string());
         default_hook(info);
     }));
 }
@@ -787,20 +831,38 @@ fn main() {
 
     config::designate_this_as_the_main_thread();
     config::assign_error_callback(mux::connui::show_configuration_error_message);
+    config::assign_warning_callback(show_configuration_warnings);
+    config::assign_font_resolver(phaedra_font::unresolved_fonts);
     notify_on_panic();
     if let Err(e) = run() {
         terminate_with_error(e);
     }
+    if let Some(mux) = Mux::try_get() {
+        mux::session::save_session_layout_if_enabled(&mux);
+    }
     Mux::shutdown();
     frontend::shutdown();
 }
 
 fn maybe_show_configuration_error_window() {
-    let warnings = config::configuration_warnings_and_errors();
-    if !warnings.is_empty() {
-        let err = warnings.join("\n");
-        mux::connui::show_configuration_error_message(&err);
+    if let Err(err) = config::configuration_result() {
+        mux::connui::show_configuration_error_message(&format!("{err:#}"));
+    }
+    show_configuration_warnings(&config::configuration_warnings());
+}
+
+/// Present configuration warnings as a notification that lists each
+/// of the offending fields, and where they were set when that is known
+fn show_configuration_warnings(warnings: &[phaedra_dynamic::Warning]) {
+    if warnings.is_empty() {
+        return;
     }
+    let message = warnings
+        .iter()
+        .map(|warning| format!("• {warning}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    persistent_toast_notification("Configuration Warnings", &message);
 }
 
 fn run_show_keys(config: config::ConfigHandle, cmd: &ShowKeysCommand) -> anyhow::Result<()> {
@@ -813,6 +875,61 @@ fn run_show_keys(config: config::ConfigHandle, cmd: &ShowKeysCommand) -> anyhow:
     Ok(())
 }
 
+fn run_ls_gpus(cmd: &LsGpusCommand) -> anyhow::Result<()> {
+    let gpus = crate::termwindow::webgpu::enumerate_adapters_standalone();
+    if gpus.is_empty() {
+        println!("No GPU adapters were found");
+        return Ok(());
+    }
+    for gpu in gpus {
+        if cmd.hardware_only && gpu.is_software {
+            continue;
+        }
+        println!("{}", gpu.to_string());
+    }
+    println!();
+    println!(
+        "Surface compatibility is only known once a window has been created; \
+         see webgpu_preferred_adapter to select one of the adapters above."
+    );
+    Ok(())
+}
+
+fn run_replay_frames(cmd: &ReplayFramesCommand) -> anyhow::Result<()> {
+    let frames = phaedra_frame_recording::read_recording(&cmd.path)?;
+    let mut mismatched = 0;
+    phaedra_frame_recording::replay(&frames, |frame| {
+        let vertices = crate::frame_recording::execute_replayed_frame(frame)
+            .with_context(|| format!("executing frame {}", frame.frame))?;
+        let matches = frame.matches_recording();
+        if !matches {
+            mismatched += 1;
+        }
+        if let Some(dir) = &cmd.png_dir {
+            crate::frame_recording::write_replayed_frame_png(frame, dir)
+                .with_context(|| format!("rasterizing frame {}", frame.frame))?;
+        }
+        if cmd.hashes {
+            println!(
+                "frame {:>6} window {:>3} hash {:016x} vertices {:>8}{}",
+                frame.frame,
+                frame.window_id,
+                frame.content_hash(),
+                vertices,
+                if matches { "" } else { " (differs from recording)" }
+            );
+        }
+        Ok(())
+    })?;
+    println!(
+        "replayed {} frames from {}; {} differed from the recording",
+        frames.len(),
+        cmd.path.display(),
+        mismatched
+    );
+    Ok(())
+}
+
 pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyhow::Result<()> {
     use phaedra_font::parser::ParsedFont;
 
@@ -1167,6 +1284,7 @@ fn run() -> anyhow::Result<()> {
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
+        &opts.config_json_override,
         opts.skip_config,
     )?;
     let config = config::configuration();
@@ -1228,5 +1346,7 @@ fn run() -> anyhow::Result<()> {
         ),
         SubCommand::LsFonts(cmd) => run_ls_fonts(config, &cmd),
         SubCommand::ShowKeys(cmd) => run_show_keys(config, &cmd),
+        SubCommand::LsGpus(cmd) => run_ls_gpus(&cmd),
+        SubCommand::ReplayFrames(cmd) => run_replay_frames(&cmd),
     }
 }
@@ -0,0 +1,310 @@
+//! Limits how often output from a pane repaints the window, per
+//! `runtime.output_coalesce_ms` and
+//! `runtime.max_render_updates_per_second_per_pane`.  A process that
+//! writes continuously would otherwise have each chunk of its output
+//! invalidate the window and re-describe the pane.  The output still
+//! accumulates in the terminal model; only the painting of it is
+//! deferred.
+use mux::pane::PaneId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Allows events at `rate` per second on average, and in bursts of up
+/// to `rate` events
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Returns a full bucket.  `rate` must be greater than zero.
+    pub fn new(rate: u32, now: Instant) -> Self {
+        assert!(rate > 0, "token bucket rate must be greater than zero");
+        Self {
+            rate,
+            tokens: rate as f64,
+            last_refill: now,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.rate as f64);
+        self.last_refill = self.last_refill.max(now);
+    }
+
+    /// Takes a token, if one is available at `now`
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes a token whether or not one is available, leaving the
+    /// bucket in debt until it has refilled
+    pub fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens -= 1.;
+    }
+
+    /// Returns how long after `now` a token will be available
+    pub fn time_until_available(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1. {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1. - self.tokens) / self.rate as f64)
+        }
+    }
+}
+
+/// What to do about output from a pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputAction {
+    /// Invalidate the window now
+    Invalidate,
+    /// Invalidate the window after this delay, and then call
+    /// `OutputThrottle::deferred_due`
+    Defer(Duration),
+    /// An invalidation is already deferred, and will cover this output
+    Pending,
+}
+
+#[derive(Debug, Default)]
+struct PaneThrottle {
+    bucket: Option<TokenBucket>,
+    last_invalidation: Option<Instant>,
+    deferred: bool,
+    /// Whether the most recent output was deferred because the pane
+    /// exceeded its update rate
+    throttled: bool,
+    /// The number of times that output was deferred because the pane
+    /// exceeded its update rate
+    throttled_count: u64,
+}
+
+/// Decides when output from each pane invalidates the window
+#[derive(Debug, Default)]
+pub struct OutputThrottle {
+    panes: HashMap<PaneId, PaneThrottle>,
+}
+
+impl OutputThrottle {
+    /// Returns what to do about output from `pane_id` at `now`.
+    /// Output within `coalesce` of the previous invalidation for the pane
+    /// is deferred to the end of that window, and output beyond
+    /// `max_per_second` invalidations is deferred until the pane's
+    /// token bucket refills.  Zero disables either limit.
+    pub fn on_output(
+        &mut self,
+        pane_id: PaneId,
+        now: Instant,
+        coalesce: Duration,
+        max_per_second: u32,
+    ) -> OutputAction {
+        let pane = self.panes.entry(pane_id).or_default();
+        if pane.deferred {
+            return OutputAction::Pending;
+        }
+
+        let coalesce_delay = match pane.last_invalidation {
+            Some(last) => (last + coalesce).saturating_duration_since(now),
+            None => Duration::ZERO,
+        };
+
+        let rate_delay = if max_per_second == 0 {
+            pane.bucket = None;
+            Duration::ZERO
+        } else {
+            let bucket = match &mut pane.bucket {
+                Some(bucket) if bucket.rate() == max_per_second => bucket,
+                bucket => bucket.insert(TokenBucket::new(max_per_second, now)),
+            };
+            bucket.time_until_available(now)
+        };
+
+        if coalesce_delay.is_zero() && rate_delay.is_zero() {
+            if let Some(bucket) = pane.bucket.as_mut() {
+                bucket.take(now);
+            }
+            pane.last_invalidation.replace(now);
+            pane.throttled = false;
+            return OutputAction::Invalidate;
+        }
+
+        pane.deferred = true;
+        pane.throttled = !rate_delay.is_zero();
+        if pane.throttled {
+            pane.throttled_count += 1;
+        }
+        OutputAction::Defer(coalesce_delay.max(rate_delay))
+    }
+
+    /// Called when a deferred invalidation for `pane_id` is due.
+    /// Returns true if the window should be invalidated, which it
+    /// should unless the pane has been forgotten in the meantime.
+    pub fn deferred_due(&mut self, pane_id: PaneId, now: Instant) -> bool {
+        let Some(pane) = self.panes.get_mut(&pane_id) else {
+            return false;
+        };
+        if !pane.deferred {
+            return false;
+        }
+        pane.deferred = false;
+        // The delay was chosen so that a token is available by now, but
+        // the timer may fire a little early
+        if let Some(bucket) = pane.bucket.as_mut() {
+            bucket.take(now);
+        }
+        pane.last_invalidation.replace(now);
+        true
+    }
+
+    /// Forgets the state of a pane that has been removed
+    pub fn forget(&mut self, pane_id: PaneId) {
+        self.panes.remove(&pane_id);
+    }
+
+    pub fn is_throttled(&self, pane_id: PaneId) -> bool {
+        self.panes
+            .get(&pane_id)
+            .map_or(false, |pane| pane.throttled)
+    }
+
+    /// Returns the panes that are currently throttled, with the number
+    /// of times that each has had its output deferred by the limit
+    pub fn throttled_panes(&self) -> Vec<(PaneId, u64)> {
+        let mut panes: Vec<(PaneId, u64)> = self
+            .panes
+            .iter()
+            .filter(|(_, pane)| pane.throttled)
+            .map(|(&pane_id, pane)| (pane_id, pane.throttled_count))
+            .collect();
+        panes.sort_unstable();
+        panes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// Feeds output from pane 0 every millisecond for `duration_ms`,
+    /// firing deferred invalidations when they fall due, and returns
+    /// the times, in milliseconds, at which the window was invalidated
+    fn simulate(
+        throttle: &mut OutputThrottle,
+        start: Instant,
+        duration_ms: u64,
+        coalesce: Duration,
+        max_per_second: u32,
+    ) -> Vec<u64> {
+        let mut invalidations = vec![];
+        let mut due: Option<Instant> = None;
+        for t in 0..=duration_ms + 1000 {
+            let now = start + ms(t);
+            if due.map_or(false, |due| due <= now) {
+                due = None;
+                if throttle.deferred_due(0, now) {
+                    invalidations.push(t);
+                }
+            }
+            if t >= duration_ms {
+                continue;
+            }
+            match throttle.on_output(0, now, coalesce, max_per_second) {
+                OutputAction::Invalidate => invalidations.push(t),
+                OutputAction::Defer(delay) => {
+                    assert!(due.is_none());
+                    due.replace(now + delay);
+                }
+                OutputAction::Pending => assert!(due.is_some()),
+            }
+        }
+        invalidations
+    }
+
+    #[test]
+    fn token_bucket_refills_at_its_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10, start);
+        for _ in 0..10 {
+            assert!(bucket.try_take(start));
+        }
+        assert!(!bucket.try_take(start));
+        assert_eq!(bucket.time_until_available(start), ms(100));
+        let wait = bucket.time_until_available(start + ms(40));
+        assert!(wait > ms(59) && wait <= ms(61), "{wait:?}");
+        assert!(!bucket.try_take(start + ms(99)));
+        assert!(bucket.try_take(start + ms(101)));
+        assert!(!bucket.try_take(start + ms(101)));
+
+        // Taking beyond the available tokens delays the next one
+        bucket.take(start + ms(101));
+        assert!(bucket.time_until_available(start + ms(101)) > ms(190));
+
+        // The bucket holds no more than a second's worth of tokens
+        let later = start + ms(60_000);
+        assert_eq!(bucket.time_until_available(later), Duration::ZERO);
+        for _ in 0..10 {
+            assert!(bucket.try_take(later));
+        }
+        assert!(!bucket.try_take(later));
+    }
+
+    #[test]
+    fn burst_is_coalesced() {
+        let mut throttle = OutputThrottle::default();
+        let invalidations = simulate(&mut throttle, Instant::now(), 100, ms(10), 0);
+        // The first output paints at once, and the rest of the burst
+        // paints once per window, including the tail of the burst
+        assert_eq!(invalidations, (0..=100).step_by(10).collect::<Vec<_>>());
+        assert!(!throttle.is_throttled(0));
+    }
+
+    #[test]
+    fn fast_panes_are_throttled() {
+        let mut throttle = OutputThrottle::default();
+        let start = Instant::now();
+        let invalidations = simulate(&mut throttle, start, 1000, Duration::ZERO, 20);
+
+        // A burst of 20 paints at once, and then 20 a second thereafter
+        assert_eq!(invalidations[..20], (0..20).collect::<Vec<u64>>());
+        assert!(
+            (39..=41).contains(&invalidations.len()),
+            "{invalidations:?}"
+        );
+        for pair in invalidations[20..].windows(2) {
+            assert!(pair[1] - pair[0] >= 49, "{invalidations:?}");
+        }
+        assert!(throttle.is_throttled(0));
+        assert_eq!(throttle.throttled_panes().len(), 1);
+
+        // Once the pane has been quiet, its output paints at once
+        let later = start + ms(5_000);
+        assert_eq!(
+            throttle.on_output(0, later, Duration::ZERO, 20),
+            OutputAction::Invalidate
+        );
+        assert!(!throttle.is_throttled(0));
+        assert!(throttle.throttled_panes().is_empty());
+
+        throttle.forget(0);
+        assert!(!throttle.deferred_due(0, later));
+    }
+}
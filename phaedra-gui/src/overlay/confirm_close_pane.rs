@@ -1,10 +1,12 @@
 use super::confirm;
+use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use mux::pane::PaneId;
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
+use window::WindowOps;
 
 pub fn confirm_close_pane(
     pane_id: PaneId,
@@ -28,6 +30,27 @@ pub fn confirm_close_pane(
     Ok(())
 }
 
+pub fn confirm_pane_broadcast(
+    pane_id: PaneId,
+    mut term: TermWizTerminal,
+    num_panes: usize,
+    window: ::window::Window,
+) -> anyhow::Result<()> {
+    let accepted = confirm::run_confirmation(
+        &format!(
+            "📡 Panes joined the broadcast. Keep sending input to all {} panes?",
+            num_panes
+        ),
+        &mut term,
+    )?;
+    window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+        term_window.pane_broadcast_confirmed(accepted);
+    })));
+    TermWindow::schedule_cancel_overlay_for_pane(window, pane_id);
+
+    Ok(())
+}
+
 pub fn confirm_close_tab(
     tab_id: TabId,
     mut term: TermWizTerminal,
@@ -1309,10 +1309,14 @@ impl Pane for CopyOverlay {
         self.delegate.is_dead()
     }
 
-    fn palette(&self) -> ColorPalette {
+    fn palette(&self) -> Arc<ColorPalette> {
         self.delegate.palette()
     }
 
+    fn palette_generation(&self) -> usize {
+        self.delegate.palette_generation()
+    }
+
     fn domain_id(&self) -> DomainId {
         self.delegate.domain_id()
     }
@@ -1679,7 +1683,7 @@ pub fn search_key_table() -> KeyTable {
             KeyAssignment::CopyMode(CopyModeAssignment::ClearPattern),
         ),
     ] {
-        table.insert((key, mods), KeyTableEntry { action });
+        table.insert((key, mods), vec![KeyTableEntry::new(action)]);
     }
     table
 }
@@ -2018,7 +2022,7 @@ pub fn copy_key_table() -> KeyTable {
             KeyAssignment::CopyMode(CopyModeAssignment::MoveToEndOfLineContent),
         ),
     ] {
-        table.insert((key, mods), KeyTableEntry { action });
+        table.insert((key, mods), vec![KeyTableEntry::new(action)]);
     }
     table
 }
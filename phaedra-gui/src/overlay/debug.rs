@@ -1,9 +1,11 @@
+use crate::cache_registry::CacheStats;
 use crate::scripting::guiwin::GuiWin;
 use chrono::prelude::*;
 use futures::FutureExt;
 use log::Level;
 use luahelper::ValuePrinter;
 use mlua::Value;
+use mux::pane::PaneId;
 use mux::termwiztermtab::TermWizTerminal;
 use std::io::Write;
 use std::path::PathBuf;
@@ -133,10 +135,15 @@ pub fn show_debug_overlay(
     gui_win: GuiWin,
     opengl_info: String,
     connection_info: String,
+    display_info: String,
+    cache_stats: Vec<CacheStats>,
+    throttled_panes: Vec<(PaneId, u64)>,
 ) -> anyhow::Result<()> {
     term.no_grab_mouse_in_raw_mode();
 
-    let config::LoadedConfig { lua, .. } = config::Config::load();
+    let config::LoadedConfig {
+        lua, eval_duration, ..
+    } = config::Config::load();
     // Try hard to fall back to some kind of working lua context even
     // if the user's config file is temporarily out of whack
     let lua = match lua {
@@ -193,6 +200,29 @@ pub fn show_debug_overlay(
         term.render(&changes)
     }
 
+    let mut cache_info = String::new();
+    for stats in &cache_stats {
+        cache_info.push_str(&format!(
+            "Cache {}: {}/{} entries, ~{} KiB\r\n",
+            stats.name,
+            stats.entries,
+            stats.capacity,
+            stats.approx_bytes / 1024
+        ));
+    }
+
+    let mut throttle_info = String::new();
+    for (pane_id, deferred) in &throttled_panes {
+        throttle_info.push_str(&format!(
+            "Output throttled: pane {pane_id}, {deferred} renders deferred\r\n"
+        ));
+    }
+
+    let config_info = match eval_duration {
+        Some(duration) => format!("Config loaded in {} ms\r\n", duration.as_millis()),
+        None => String::new(),
+    };
+
     let version = config::phaedra_version();
     let triple = config::phaedra_target_triple();
 
@@ -200,8 +230,12 @@ pub fn show_debug_overlay(
         "Debug Overlay\r\n\
          phaedra version: {version} {triple}\r\n\
          Window Environment: {connection_info}\r\n\
+         Display: {display_info}\r\n\
          Lua Version: {lua_version}\r\n\
          {opengl_info}\r\n\
+         {cache_info}\
+         {throttle_info}\
+         {config_info}\
          Enter lua statements or expressions and hit Enter.\r\n\
          Press ESC or CTRL-D to exit\r\n",
     ))])?;
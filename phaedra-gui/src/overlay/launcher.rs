@@ -12,7 +12,7 @@ use crate::overlay::quickselect;
 use crate::overlay::selector::{matcher_pattern, matcher_score};
 use crate::termwindow::TermWindowNotif;
 use config::configuration;
-use config::keyassignment::{KeyAssignment, SpawnCommand, SpawnTabDomain};
+use config::keyassignment::{KeyAssignment, SpawnCommand, SpawnTabDomain, TabIndex};
 use mux::domain::{DomainId, DomainState};
 use mux::pane::PaneId;
 use mux::termwiztermtab::TermWizTerminal;
@@ -301,7 +301,7 @@ impl LauncherState {
                     Some(pane_count) => format!("{}. {pane_count} panes", tab.title),
                     None => format!("{}.", tab.title),
                 },
-                action: KeyAssignment::ActivateTab(tab.tab_idx as isize),
+                action: KeyAssignment::ActivateTab(TabIndex::absolute(tab.tab_idx)),
             });
         }
 
@@ -328,6 +328,9 @@ impl LauncherState {
             let mut key_entries: Vec<Entry> = vec![];
             // Give a consistent order to the entries
             let keys: BTreeMap<_, _> = input_map.keys.default.into_iter().collect();
+            let keys = keys.into_iter().flat_map(|(chord, entries)| {
+                entries.into_iter().map(move |entry| (chord.clone(), entry))
+            });
             for ((keycode, mods), entry) in keys {
                 if matches!(
                     &entry.action,
@@ -20,7 +20,8 @@ use termwiz::surface::{SequenceNo, SEQ_ZERO};
 use url::Url;
 use phaedra_term::color::ColorPalette;
 use phaedra_term::{
-    Clipboard, Intensity, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex, TerminalSize,
+    Clipboard, Intensity, KeyCode, KeyModifiers, Line, MouseEvent, SemanticZone, StableRowIndex,
+    TerminalSize,
 };
 use window::WindowOps;
 
@@ -194,6 +195,45 @@ mod alphabet_test {
     }
 }
 
+/// Returns true if the search result, whose end is exclusive, lies
+/// within the zone, whose end is inclusive
+fn zone_contains_result(zone: &SemanticZone, result: &SearchResult) -> bool {
+    (result.start_y, result.start_x) >= (zone.start_y, zone.start_x)
+        && (result.end_y, result.end_x) <= (zone.end_y, zone.end_x + 1)
+}
+
+#[cfg(test)]
+mod zone_test {
+    use super::*;
+    use phaedra_term::SemanticType;
+
+    fn result(start: (usize, StableRowIndex), end: (usize, StableRowIndex)) -> SearchResult {
+        SearchResult {
+            start_x: start.0,
+            start_y: start.1,
+            end_x: end.0,
+            end_y: end.1,
+            match_id: 0,
+        }
+    }
+
+    #[test]
+    fn results_within_output() {
+        let output = SemanticZone {
+            start_x: 4,
+            start_y: 1,
+            end_x: 2,
+            end_y: 3,
+            semantic_type: SemanticType::Output,
+        };
+        assert!(zone_contains_result(&output, &result((4, 1), (8, 1))));
+        assert!(zone_contains_result(&output, &result((0, 2), (3, 3))));
+        assert!(!zone_contains_result(&output, &result((3, 1), (8, 1))));
+        assert!(!zone_contains_result(&output, &result((0, 3), (4, 3))));
+        assert!(!zone_contains_result(&output, &result((0, 4), (1, 4))));
+    }
+}
+
 pub struct QuickSelectOverlay {
     renderer: Mutex<QuickSelectRenderable>,
     delegate: Arc<dyn Pane>,
@@ -205,6 +245,150 @@ struct MatchResult {
     label: String,
 }
 
+/// Returns the columns that each of the graphemes of `line` occupies
+fn grapheme_columns(line: &Line) -> Vec<Range<usize>> {
+    line.visible_cells()
+        .map(|cell| cell.cell_index()..cell.cell_index() + cell.width().max(1))
+        .collect()
+}
+
+/// Computes where the label for a match that starts at column `start`
+/// is drawn, given the columns occupied by the graphemes of its line.
+/// A label never starts part way through a grapheme, such as a wide
+/// emoji; it is moved right to the start of the next one instead.
+/// Returns the column at which the label starts and the columns that
+/// need to be blanked beneath it, which extend to the end of the last
+/// grapheme that it covers so that none is left partially drawn.
+fn label_placement(
+    graphemes: &[Range<usize>],
+    start: usize,
+    label_width: usize,
+) -> (usize, Range<usize>) {
+    let col = match graphemes.iter().find(|g| g.contains(&start)) {
+        Some(g) if g.start != start => g.end,
+        _ => start,
+    };
+    let end = col + label_width;
+    let end = graphemes
+        .iter()
+        .find(|g| g.start < end && g.end > end)
+        .map_or(end, |g| g.end);
+    (col, col..end)
+}
+
+/// Draws the label of `m` over the start of its match
+fn draw_label(line: &mut Line, m: &MatchResult, colors: &config::Palette) {
+    let (col, covered) = label_placement(
+        &grapheme_columns(line),
+        m.range.start,
+        m.label.chars().count(),
+    );
+    let mut attr = line
+        .get_cell(col)
+        .map(|cell| cell.attrs().clone())
+        .unwrap_or_else(|| CellAttributes::default());
+    attr.set_background(
+        colors
+            .quick_select_label_bg
+            .unwrap_or(AnsiColor::Black.into()),
+    )
+    .set_foreground(
+        colors
+            .quick_select_label_fg
+            .unwrap_or(AnsiColor::Olive.into()),
+    )
+    .set_reverse(false)
+    .set_intensity(Intensity::Bold);
+    for idx in covered {
+        line.set_cell(idx, Cell::new(' ', attr.clone()), SEQ_ZERO);
+    }
+    for (idx, c) in m.label.chars().enumerate() {
+        line.set_cell(col + idx, Cell::new(c, attr.clone()), SEQ_ZERO);
+    }
+}
+
+#[cfg(test)]
+mod label_test {
+    use super::*;
+
+    fn line(text: &str) -> Line {
+        Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None)
+    }
+
+    /// Draws `label` for a match starting at `start` and returns the
+    /// column and text of each of the resulting graphemes
+    fn labelled(text: &str, start: usize, label: &str) -> Vec<(usize, String)> {
+        let mut line = line(text);
+        let m = MatchResult {
+            range: start..line.len(),
+            label: label.to_string(),
+        };
+        draw_label(&mut line, &m, &config::Palette::default());
+        line.visible_cells()
+            .map(|cell| (cell.cell_index(), cell.str().to_string()))
+            .collect()
+    }
+
+    fn cells(cells: &[(usize, &str)]) -> Vec<(usize, String)> {
+        cells.iter().map(|(idx, s)| (*idx, s.to_string())).collect()
+    }
+
+    #[test]
+    fn wide_emoji() {
+        // A family made of emoji joined by zero width joiners is a single
+        // grapheme that occupies two cells
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let text = format!("{family}{family}x");
+        assert_eq!(grapheme_columns(&line(&text)), vec![0..2, 2..4, 4..5]);
+
+        // The whole of the emoji is blanked beneath the label
+        assert_eq!(
+            labelled(&text, 0, "a"),
+            cells(&[(0, "a"), (1, " "), (2, family), (4, "x")])
+        );
+        // A match that starts on the second cell of the emoji has its
+        // label moved to the next one
+        assert_eq!(
+            labelled(&text, 1, "a"),
+            cells(&[(0, family), (2, "a"), (3, " "), (4, "x")])
+        );
+        // A label that ends part way through an emoji blanks all of it
+        assert_eq!(
+            labelled(&text, 0, "abc"),
+            cells(&[(0, "a"), (1, "b"), (2, "c"), (3, " "), (4, "x")])
+        );
+    }
+
+    #[test]
+    fn flags() {
+        let flag = "\u{1F1EF}\u{1F1F5}";
+        let text = format!("{flag}ab");
+        assert_eq!(
+            label_placement(&grapheme_columns(&line(&text)), 1, 1),
+            (2, 2..3)
+        );
+        assert_eq!(
+            labelled(&text, 0, "z"),
+            cells(&[(0, "z"), (1, " "), (2, "a"), (3, "b")])
+        );
+    }
+
+    #[test]
+    fn combining_characters() {
+        // e and a combining acute accent make a single cell
+        let text = "e\u{301}e\u{301}x";
+        assert_eq!(grapheme_columns(&line(text)), vec![0..1, 1..2, 2..3]);
+        assert_eq!(
+            labelled(text, 1, "a"),
+            cells(&[(0, "e\u{301}"), (1, "a"), (2, "x")])
+        );
+        assert_eq!(
+            labelled(text, 0, "ab"),
+            cells(&[(0, "a"), (1, "b"), (2, "x")])
+        );
+    }
+}
+
 struct QuickSelectRenderable {
     delegate: Arc<dyn Pane>,
     /// The text that the user entered
@@ -450,9 +634,13 @@ impl Pane for QuickSelectOverlay {
         self.delegate.is_dead()
     }
 
-    fn palette(&self) -> ColorPalette {
+    fn palette(&self) -> Arc<ColorPalette> {
         self.delegate.palette()
     }
+
+    fn palette_generation(&self) -> usize {
+        self.delegate.palette_generation()
+    }
     fn domain_id(&self) -> DomainId {
         self.delegate.domain_id()
     }
@@ -606,25 +794,7 @@ impl Pane for QuickSelectOverlay {
                                         .set_intensity(Intensity::Bold);
                                 }
                             }
-                            for (idx, c) in m.label.chars().enumerate() {
-                                let mut attr = line
-                                    .get_cell(idx)
-                                    .map(|cell| cell.attrs().clone())
-                                    .unwrap_or_else(|| CellAttributes::default());
-                                attr.set_background(
-                                    colors
-                                        .quick_select_label_bg
-                                        .unwrap_or(AnsiColor::Black.into()),
-                                )
-                                .set_foreground(
-                                    colors
-                                        .quick_select_label_fg
-                                        .unwrap_or(AnsiColor::Olive.into()),
-                                )
-                                .set_reverse(false)
-                                .set_intensity(Intensity::Bold);
-                                line.set_cell(m.range.start + idx, Cell::new(c, attr), SEQ_ZERO);
-                            }
+                            draw_label(&mut line, m, &colors);
                         }
                         line.clear_appdata();
                     }
@@ -698,25 +868,7 @@ impl Pane for QuickSelectOverlay {
                                 .set_intensity(Intensity::Bold);
                         }
                     }
-                    for (idx, c) in m.label.chars().enumerate() {
-                        let mut attr = line
-                            .get_cell(idx)
-                            .map(|cell| cell.attrs().clone())
-                            .unwrap_or_else(|| CellAttributes::default());
-                        attr.set_background(
-                            colors
-                                .quick_select_label_bg
-                                .unwrap_or(AnsiColor::Black.into()),
-                        )
-                        .set_foreground(
-                            colors
-                                .quick_select_label_fg
-                                .unwrap_or(AnsiColor::Olive.into()),
-                        )
-                        .set_reverse(false)
-                        .set_intensity(Intensity::Bold);
-                        line.set_cell(m.range.start + idx, Cell::new(c, attr), SEQ_ZERO);
-                    }
+                    draw_label(line, m, &colors);
                 }
             }
         }
@@ -862,6 +1014,7 @@ impl QuickSelectRenderable {
             let window = self.window.clone();
             let pattern = self.pattern.clone();
             let scope = self.args.scope_lines;
+            let only_command_output = self.args.only_command_output;
             let viewport = self.viewport;
             promise::spawn::spawn(async move {
                 let dims = pane.get_dimensions();
@@ -871,6 +1024,18 @@ impl QuickSelectRenderable {
                     ..top + (dims.viewport_rows + scope) as StableRowIndex;
                 let limit = None;
                 let mut results = pane.search(pattern, range, limit).await?;
+                if only_command_output {
+                    let outputs: Vec<SemanticZone> = pane
+                        .get_command_zones()?
+                        .into_iter()
+                        .filter_map(|command| command.output)
+                        .collect();
+                    results.retain(|result| {
+                        outputs
+                            .iter()
+                            .any(|output| zone_contains_result(output, result))
+                    });
+                }
                 results.sort();
 
                 let pane_id = pane.pane_id();
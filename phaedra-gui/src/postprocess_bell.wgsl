@@ -0,0 +1,40 @@
+// Built-in visual bell post-processing shader.  It is used while a
+// bell whose target is PostProcess is ringing and no webgpu_shader is
+// configured, and briefly shakes the frame while flashing it brighter.
+
+struct PostProcessUniform {
+    resolution: vec2<f32>,
+    time: f32,
+    bell_intensity: f32,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: PostProcessUniform;
+@group(0) @binding(1) var input_texture: texture_2d<f32>;
+@group(0) @binding(2) var input_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+    out.position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>((x + 1.0) * 0.5, (1.0 - y) * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let intensity = clamp(uniforms.bell_intensity, 0.0, 1.0);
+    // Up to 4 pixels either side
+    let shake = sin(uniforms.time * 90.0) * intensity * 4.0 / uniforms.resolution.x;
+    let color = textureSample(input_texture, input_sampler, in.uv + vec2<f32>(shake, 0.0));
+    // The flash is scaled by alpha, so that transparent parts of the
+    // window stay transparent
+    let flash = mix(color.rgb, vec3<f32>(color.a), intensity * 0.3);
+    return vec4<f32>(flash, color.a);
+}
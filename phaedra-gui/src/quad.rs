@@ -1,3 +1,4 @@
+use crate::render_command::QuadMode;
 use crate::renderstate::BorrowedLayers;
 use ::window::bitmaps::TextureRect;
 use ::window::color::LinearRgba;
@@ -11,18 +12,54 @@ pub const V_TOP_RIGHT: usize = 1;
 pub const V_BOT_LEFT: usize = 2;
 pub const V_BOT_RIGHT: usize = 3;
 
-/// a regular monochrome text glyph
-const IS_GLYPH: f32 = 0.0;
-/// a color emoji glyph
-const IS_COLOR_EMOJI: f32 = 1.0;
-/// a full color texture attached as the
-/// background image of the window
-const IS_BG_IMAGE: f32 = 2.0;
-/// like 2.0, except that instead of an
-/// image, we use the solid bg color
-const IS_SOLID_COLOR: f32 = 3.0;
-/// Grayscale poly quad for non-aa text render layers
-const IS_GRAY_SCALE: f32 = 4.0;
+/// How the fragment shader colors a quad.  The discriminant is the
+/// value of the `has_color` vertex attribute, and must match the
+/// constants in shader.wgsl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexMode {
+    /// a regular monochrome text glyph
+    Glyph = 0,
+    /// a color emoji glyph
+    ColorEmoji = 1,
+    /// a full color texture attached as the
+    /// background image of the window
+    BackgroundImage = 2,
+    /// the fg color, without sampling the texture
+    SolidColor = 3,
+    /// Grayscale poly quad for non-aa text render layers
+    GrayScale = 4,
+}
+
+impl VertexMode {
+    pub fn value(self) -> f32 {
+        self as u8 as f32
+    }
+
+    pub fn from_value(value: f32) -> Option<Self> {
+        [
+            Self::Glyph,
+            Self::ColorEmoji,
+            Self::BackgroundImage,
+            Self::SolidColor,
+            Self::GrayScale,
+        ]
+        .iter()
+        .copied()
+        .find(|mode| mode.value() == value)
+    }
+}
+
+impl From<&QuadMode> for VertexMode {
+    fn from(mode: &QuadMode) -> Self {
+        match mode {
+            QuadMode::Glyph => Self::Glyph,
+            QuadMode::ColorEmoji => Self::ColorEmoji,
+            QuadMode::BackgroundImage => Self::BackgroundImage,
+            QuadMode::SolidColor => Self::SolidColor,
+            QuadMode::GrayScale => Self::GrayScale,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, Default, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -38,6 +75,10 @@ pub struct Vertex {
     pub mix_value: f32,
 }
 impl Vertex {
+    pub fn mode(&self) -> Option<VertexMode> {
+        VertexMode::from_value(self.has_color)
+    }
+
     const ATTRIBS: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
     0 => Float32x2,
     1 => Float32x2,
@@ -69,25 +110,35 @@ pub trait QuadTrait {
     fn set_texture_discrete(&mut self, x1: f32, x2: f32, y1: f32, y2: f32);
     fn set_has_color_impl(&mut self, has_color: f32);
 
+    fn set_mode(&mut self, mode: VertexMode) {
+        self.set_has_color_impl(mode.value());
+    }
+
     /// Set the color glyph "flag"
     fn set_has_color(&mut self, has_color: bool) {
-        self.set_has_color_impl(if has_color { IS_COLOR_EMOJI } else { IS_GLYPH });
+        self.set_mode(if has_color {
+            VertexMode::ColorEmoji
+        } else {
+            VertexMode::Glyph
+        });
     }
 
     /// Mark as a grayscale polyquad; color and alpha will be
     /// multipled with those in the texture
     fn set_grayscale(&mut self) {
-        self.set_has_color_impl(IS_GRAY_SCALE);
+        self.set_mode(VertexMode::GrayScale);
     }
 
     /// Mark this quad as a background image.
     /// Mutually exclusive with set_has_color.
     fn set_is_background_image(&mut self) {
-        self.set_has_color_impl(IS_BG_IMAGE);
+        self.set_mode(VertexMode::BackgroundImage);
     }
 
+    /// Mark this quad as a solid color; the texture coordinates
+    /// are ignored
     fn set_is_background(&mut self) {
-        self.set_has_color_impl(IS_SOLID_COLOR);
+        self.set_mode(VertexMode::SolidColor);
     }
 
     fn set_fg_color(&mut self, color: LinearRgba);
@@ -97,6 +148,10 @@ pub trait QuadTrait {
 
     fn set_hsv(&mut self, hsv: Option<HsbTransform>);
     fn set_position(&mut self, left: f32, top: f32, right: f32, bottom: f32);
+
+    /// Position each vertex individually, indexed by V_TOP_LEFT etc,
+    /// so that the quad needn't be aligned with the axes
+    fn set_corners(&mut self, corners: [[f32; 2]; VERTICES_PER_CELL]);
 }
 
 pub enum QuadImpl<'a> {
@@ -146,6 +201,13 @@ impl<'a> QuadTrait for QuadImpl<'a> {
             Self::Boxed(q) => q.set_position(left, top, right, bottom),
         }
     }
+
+    fn set_corners(&mut self, corners: [[f32; 2]; VERTICES_PER_CELL]) {
+        match self {
+            Self::Vert(q) => q.set_corners(corners),
+            Self::Boxed(q) => q.set_corners(corners),
+        }
+    }
 }
 
 /// A helper for updating the 4 vertices that compose a glyph cell
@@ -197,6 +259,12 @@ impl<'a> QuadTrait for Quad<'a> {
         self.vert[V_BOT_LEFT].position = [left, bottom];
         self.vert[V_BOT_RIGHT].position = [right, bottom];
     }
+
+    fn set_corners(&mut self, corners: [[f32; 2]; VERTICES_PER_CELL]) {
+        for (v, corner) in self.vert.iter_mut().zip(corners.iter()) {
+            v.position = *corner;
+        }
+    }
 }
 
 pub trait QuadAllocator {
@@ -216,7 +284,7 @@ pub trait TripleLayerQuadAllocatorTrait {
 /// in unused capacity
 #[derive(Default)]
 pub struct BoxedQuad {
-    position: (f32, f32, f32, f32),
+    corners: [[f32; 2]; VERTICES_PER_CELL],
     fg_color: [f32; 4],
     alt_color: [f32; 4],
     tex: (f32, f32, f32, f32),
@@ -249,7 +317,14 @@ impl QuadTrait for BoxedQuad {
     }
 
     fn set_position(&mut self, left: f32, top: f32, right: f32, bottom: f32) {
-        self.position = (left, top, right, bottom);
+        self.corners[V_TOP_LEFT] = [left, top];
+        self.corners[V_TOP_RIGHT] = [right, top];
+        self.corners[V_BOT_LEFT] = [left, bottom];
+        self.corners[V_BOT_RIGHT] = [right, bottom];
+    }
+
+    fn set_corners(&mut self, corners: [[f32; 2]; VERTICES_PER_CELL]) {
+        self.corners = corners;
     }
 }
 
@@ -258,11 +333,14 @@ impl BoxedQuad {
         let [x1, y1] = verts[V_TOP_LEFT].tex;
         let [x2, y2] = verts[V_BOT_RIGHT].tex;
 
-        let [left, top] = verts[V_TOP_LEFT].position;
-        let [right, bottom] = verts[V_BOT_RIGHT].position;
         Self {
             tex: (x1, x2, y1, y2),
-            position: (left, top, right, bottom),
+            corners: [
+                verts[V_TOP_LEFT].position,
+                verts[V_TOP_RIGHT].position,
+                verts[V_BOT_LEFT].position,
+                verts[V_BOT_RIGHT].position,
+            ],
             has_color: verts[V_TOP_LEFT].has_color,
             alt_color: verts[V_TOP_LEFT].alt_color,
             fg_color: verts[V_TOP_LEFT].fg_color,
@@ -278,8 +356,7 @@ impl BoxedQuad {
         let (x1, x2, y1, y2) = self.tex;
         quad.set_texture_discrete(x1, x2, y1, y2);
 
-        let (left, top, right, bottom) = self.position;
-        quad.set_position(left, top, right, bottom);
+        quad.set_corners(self.corners);
 
         quad.set_has_color_impl(self.has_color);
         let [hue, saturation, brightness] = self.hsv;
@@ -326,6 +403,31 @@ impl HeapQuadAllocator {
     }
 }
 
+impl HeapQuadAllocator {
+    /// Returns the vertices that have been allocated in a layer
+    #[cfg(test)]
+    pub fn vertices(&self, layer_num: usize) -> Vec<Vertex> {
+        let quads = match layer_num {
+            0 => &self.layer0,
+            1 => &self.layer1,
+            2 => &self.layer2,
+            _ => unreachable!(),
+        };
+        quads.iter().flat_map(|quad| quad.to_vertices()).collect()
+    }
+
+    /// Discards the quads that have been allocated in a layer
+    #[cfg(test)]
+    pub fn clear_layer(&mut self, layer_num: usize) {
+        match layer_num {
+            0 => self.layer0.clear(),
+            1 => self.layer1.clear(),
+            2 => self.layer2.clear(),
+            _ => unreachable!(),
+        }
+    }
+}
+
 impl TripleLayerQuadAllocatorTrait for HeapQuadAllocator {
     fn allocate(&mut self, layer_num: usize) -> anyhow::Result<QuadImpl<'_>> {
         let quads = match layer_num {
@@ -391,5 +493,5 @@ impl<'a> TripleLayerQuadAllocatorTrait for TripleLayerQuadAllocator<'a> {
 #[test]
 fn size() {
     assert_eq!(std::mem::size_of::<Vertex>() * VERTICES_PER_CELL, 272);
-    assert_eq!(std::mem::size_of::<BoxedQuad>(), 84);
+    assert_eq!(std::mem::size_of::<BoxedQuad>(), 100);
 }
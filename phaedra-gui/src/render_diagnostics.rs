@@ -0,0 +1,132 @@
+//! Records a `FrameTrace` for each painted frame while
+//! `runtime.render_diagnostics` is enabled; see the
+//! phaedra-render-diagnostics crate for the trace format, and
+//! `phaedra diag render` for reading it back.
+//!
+//! Recording is done through the `render_diag!` macro, which compiles
+//! to nothing when the `render-diagnostics` feature is disabled.
+use config::observers::*;
+use config::ConfigHandle;
+use phaedra_render_diagnostics::{trace_file_name, FrameTrace, SectionTrace, TraceWriter};
+use std::cell::RefCell;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Runs `$body` against the trace of the frame being painted, if any.
+/// `$body` is not evaluated unless diagnostics are enabled, so it may
+/// compute values that are otherwise not needed for rendering.
+#[cfg(feature = "render-diagnostics")]
+macro_rules! render_diag {
+    ($diag:expr, |$trace:ident| $body:expr) => {
+        $diag.record(|$trace: &mut phaedra_render_diagnostics::FrameTrace| $body)
+    };
+}
+
+#[cfg(not(feature = "render-diagnostics"))]
+macro_rules! render_diag {
+    ($diag:expr, |$trace:ident| $body:expr) => {};
+}
+
+pub(crate) use render_diag;
+
+#[derive(Default)]
+struct TraceState {
+    writer: Option<TraceWriter>,
+    current: Option<FrameTrace>,
+    frames: u64,
+}
+
+#[derive(Default)]
+pub struct RenderDiagnostics {
+    state: RefCell<TraceState>,
+}
+
+impl RenderDiagnostics {
+    /// Starts the trace of a new frame when diagnostics are enabled.
+    /// Closes the trace file when they have been disabled.
+    pub fn begin_frame(&self, config: &ConfigHandle, window_id: usize) {
+        let mut state = self.state.borrow_mut();
+        if !cfg!(feature = "render-diagnostics") || !config.runtime().render_diagnostics {
+            state.writer.take();
+            state.current.take();
+            return;
+        }
+        state.frames += 1;
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        state.current = Some(FrameTrace {
+            frame: state.frames,
+            window_id,
+            timestamp_ms,
+            panes: vec![],
+            chrome: vec![],
+            total_us: 0,
+        });
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.borrow().current.is_some()
+    }
+
+    /// Returns the current time if a frame is being traced, for use
+    /// with `elapsed_us`
+    pub fn start_timer(&self) -> Option<Instant> {
+        if self.is_active() {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    #[cfg_attr(not(feature = "render-diagnostics"), allow(dead_code))]
+    pub fn record<F: FnOnce(&mut FrameTrace)>(&self, func: F) {
+        if let Some(trace) = self.state.borrow_mut().current.as_mut() {
+            func(trace);
+        }
+    }
+
+    /// Completes the trace of the current frame and appends it to the
+    /// trace file, which is created on first use
+    pub fn end_frame(&self, total: Duration) {
+        let mut state = self.state.borrow_mut();
+        let mut trace = match state.current.take() {
+            Some(trace) => trace,
+            None => return,
+        };
+        trace.total_us = total.as_micros() as u64;
+
+        if state.writer.is_none() {
+            let path = config::RUNTIME_DIR.join(trace_file_name(std::process::id()));
+            match TraceWriter::create(&path) {
+                Ok(writer) => {
+                    log::info!("writing render diagnostics to {}", path.display());
+                    state.writer.replace(writer);
+                }
+                Err(err) => {
+                    log::error!("render diagnostics: {:#}", err);
+                    return;
+                }
+            }
+        }
+        if let Some(writer) = state.writer.as_mut() {
+            if let Err(err) = writer.write_frame(&trace) {
+                log::error!("render diagnostics: {:#}", err);
+                state.writer.take();
+            }
+        }
+    }
+}
+
+/// Microseconds since a time returned by `RenderDiagnostics::start_timer`
+pub fn elapsed_us(start: Option<Instant>) -> u64 {
+    start.map_or(0, |start| start.elapsed().as_micros() as u64)
+}
+
+#[cfg_attr(not(feature = "render-diagnostics"), allow(dead_code))]
+pub fn section(name: &str, start: Option<Instant>) -> SectionTrace {
+    SectionTrace {
+        name: name.to_string(),
+        duration_us: elapsed_us(start),
+    }
+}
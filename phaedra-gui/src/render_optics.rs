@@ -1,5 +1,8 @@
 use crate::frame::{Frame, PaneFrame};
-use crate::render_command::{HsbTransform, QuadMode, RectF, RenderCommand, TextureCoords};
+use crate::render_command::{
+    HsbTransform, LayerId, PointF, QuadMode, RectF, RenderCommand, TextureCoords,
+};
+use std::ops::ControlFlow;
 use std::sync::Arc;
 use window::color::LinearRgba;
 
@@ -18,12 +21,18 @@ pub trait Traversal<S, A> {
     fn traverse<F: FnMut(&A) -> A>(&self, source: S, f: F) -> S;
 }
 
+/// A traversal whose callbacks also receive the path to each target:
+/// the indices through the nested containers that lead to it
+pub trait IndexedTraversal<S, A> {
+    fn fold<B, F: FnMut(B, &[usize], &A) -> B>(&self, source: &S, init: B, f: F) -> B;
+    fn traverse<F: FnMut(&[usize], &A) -> A>(&self, source: S, f: F) -> S;
+}
+
 pub struct Compose<Outer, Inner>(pub Outer, pub Inner);
 
 #[derive(Debug, Clone)]
 pub struct FillRectFields {
-    pub layer: usize,
-    pub zindex: i8,
+    pub layer: LayerId,
     pub rect: RectF,
     pub color: LinearRgba,
     pub hsv: Option<HsbTransform>,
@@ -31,8 +40,7 @@ pub struct FillRectFields {
 
 #[derive(Debug, Clone)]
 pub struct DrawQuadFields {
-    pub layer: usize,
-    pub zindex: i8,
+    pub layer: LayerId,
     pub position: RectF,
     pub texture: TextureCoords,
     pub fg_color: LinearRgba,
@@ -41,6 +49,24 @@ pub struct DrawQuadFields {
     pub mode: QuadMode,
 }
 
+#[derive(Debug, Clone)]
+pub struct DrawLineFields {
+    pub layer: LayerId,
+    pub from: PointF,
+    pub to: PointF,
+    pub width: f32,
+    pub color: LinearRgba,
+}
+
+#[derive(Debug, Clone)]
+pub struct FillRoundedRectFields {
+    pub layer: LayerId,
+    pub rect: RectF,
+    pub radius: f32,
+    pub color: LinearRgba,
+    pub hsv: Option<HsbTransform>,
+}
+
 pub struct PaneBounds;
 
 impl Lens<PaneFrame, RectF> for PaneBounds {
@@ -59,6 +85,7 @@ impl Lens<PaneFrame, RectF> for PaneBounds {
             ui_items,
             last_execution_stats,
             skip_streak,
+            hsv,
         } = source;
         PaneFrame {
             pane_id,
@@ -70,6 +97,7 @@ impl Lens<PaneFrame, RectF> for PaneBounds {
             ui_items,
             last_execution_stats,
             skip_streak,
+            hsv,
         }
     }
 }
@@ -96,6 +124,7 @@ impl Lens<PaneFrame, Arc<[RenderCommand]>> for PaneCommands {
             ui_items,
             last_execution_stats,
             skip_streak,
+            hsv,
         } = source;
         PaneFrame {
             pane_id,
@@ -107,6 +136,7 @@ impl Lens<PaneFrame, Arc<[RenderCommand]>> for PaneCommands {
             ui_items,
             last_execution_stats,
             skip_streak,
+            hsv,
         }
     }
 }
@@ -118,13 +148,11 @@ impl Prism<RenderCommand, FillRectFields> for AsFillRect {
         match source {
             RenderCommand::FillRect {
                 layer,
-                zindex,
                 rect,
                 color,
                 hsv,
             } => Some(FillRectFields {
                 layer: *layer,
-                zindex: *zindex,
                 rect: *rect,
                 color: *color,
                 hsv: hsv.clone(),
@@ -136,7 +164,6 @@ impl Prism<RenderCommand, FillRectFields> for AsFillRect {
     fn review(&self, value: FillRectFields) -> RenderCommand {
         RenderCommand::FillRect {
             layer: value.layer,
-            zindex: value.zindex,
             rect: value.rect,
             color: value.color,
             hsv: value.hsv,
@@ -171,7 +198,6 @@ impl Prism<RenderCommand, DrawQuadFields> for AsDrawQuad {
         match source {
             RenderCommand::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color,
@@ -180,7 +206,6 @@ impl Prism<RenderCommand, DrawQuadFields> for AsDrawQuad {
                 mode,
             } => Some(DrawQuadFields {
                 layer: *layer,
-                zindex: *zindex,
                 position: *position,
                 texture: texture.clone(),
                 fg_color: *fg_color,
@@ -195,7 +220,6 @@ impl Prism<RenderCommand, DrawQuadFields> for AsDrawQuad {
     fn review(&self, value: DrawQuadFields) -> RenderCommand {
         RenderCommand::DrawQuad {
             layer: value.layer,
-            zindex: value.zindex,
             position: value.position,
             texture: value.texture,
             fg_color: value.fg_color,
@@ -226,6 +250,117 @@ impl Traversal<RenderCommand, DrawQuadFields> for AsDrawQuad {
     }
 }
 
+pub struct AsDrawLine;
+
+impl Prism<RenderCommand, DrawLineFields> for AsDrawLine {
+    fn preview(&self, source: &RenderCommand) -> Option<DrawLineFields> {
+        match source {
+            RenderCommand::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color,
+            } => Some(DrawLineFields {
+                layer: *layer,
+                from: *from,
+                to: *to,
+                width: *width,
+                color: *color,
+            }),
+            _ => None,
+        }
+    }
+
+    fn review(&self, value: DrawLineFields) -> RenderCommand {
+        RenderCommand::DrawLine {
+            layer: value.layer,
+            from: value.from,
+            to: value.to,
+            width: value.width,
+            color: value.color,
+        }
+    }
+}
+
+impl Traversal<RenderCommand, DrawLineFields> for AsDrawLine {
+    fn fold<B, F: FnMut(B, &DrawLineFields) -> B>(&self, source: &RenderCommand, init: B, mut f: F) -> B {
+        match self.preview(source) {
+            Some(fields) => f(init, &fields),
+            None => init,
+        }
+    }
+
+    fn traverse<F: FnMut(&DrawLineFields) -> DrawLineFields>(
+        &self,
+        source: RenderCommand,
+        mut f: F,
+    ) -> RenderCommand {
+        match self.preview(&source) {
+            Some(fields) => self.review(f(&fields)),
+            None => source,
+        }
+    }
+}
+
+pub struct AsFillRoundedRect;
+
+impl Prism<RenderCommand, FillRoundedRectFields> for AsFillRoundedRect {
+    fn preview(&self, source: &RenderCommand) -> Option<FillRoundedRectFields> {
+        match source {
+            RenderCommand::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color,
+                hsv,
+            } => Some(FillRoundedRectFields {
+                layer: *layer,
+                rect: *rect,
+                radius: *radius,
+                color: *color,
+                hsv: hsv.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn review(&self, value: FillRoundedRectFields) -> RenderCommand {
+        RenderCommand::FillRoundedRect {
+            layer: value.layer,
+            rect: value.rect,
+            radius: value.radius,
+            color: value.color,
+            hsv: value.hsv,
+        }
+    }
+}
+
+impl Traversal<RenderCommand, FillRoundedRectFields> for AsFillRoundedRect {
+    fn fold<B, F: FnMut(B, &FillRoundedRectFields) -> B>(
+        &self,
+        source: &RenderCommand,
+        init: B,
+        mut f: F,
+    ) -> B {
+        match self.preview(source) {
+            Some(fields) => f(init, &fields),
+            None => init,
+        }
+    }
+
+    fn traverse<F: FnMut(&FillRoundedRectFields) -> FillRoundedRectFields>(
+        &self,
+        source: RenderCommand,
+        mut f: F,
+    ) -> RenderCommand {
+        match self.preview(&source) {
+            Some(fields) => self.review(f(&fields)),
+            None => source,
+        }
+    }
+}
+
 pub struct DeepCommands;
 
 fn fold_deep_command<B, F>(command: &RenderCommand, init: B, f: &mut F) -> B
@@ -297,6 +432,175 @@ impl Traversal<Vec<RenderCommand>, RenderCommand> for DeepCommands {
     }
 }
 
+fn fold_deep_command_while<B, F>(command: &RenderCommand, init: B, f: &mut F) -> ControlFlow<B, B>
+where
+    F: FnMut(B, &RenderCommand) -> ControlFlow<B, B>,
+{
+    match command {
+        RenderCommand::Batch(commands) => commands
+            .iter()
+            .try_fold(init, |acc, nested| fold_deep_command_while(nested, acc, f)),
+        _ => f(init, command),
+    }
+}
+
+impl DeepCommands {
+    /// Like `fold`, except that `f` can stop the fold by returning
+    /// `ControlFlow::Break`, in which case the commands after the one
+    /// that it stopped at are not visited
+    pub fn fold_while<B, F>(&self, source: &[RenderCommand], init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &RenderCommand) -> ControlFlow<B, B>,
+    {
+        match source.iter().try_fold(init, |acc, command| {
+            fold_deep_command_while(command, acc, &mut f)
+        }) {
+            ControlFlow::Continue(acc) | ControlFlow::Break(acc) => acc,
+        }
+    }
+
+    /// Returns the first command, in drawing order, that matches `pred`
+    pub fn find_first<P>(&self, source: &[RenderCommand], mut pred: P) -> Option<RenderCommand>
+    where
+        P: FnMut(&RenderCommand) -> bool,
+    {
+        self.fold_while(source, None, |_, command| {
+            if pred(command) {
+                ControlFlow::Break(Some(command.clone()))
+            } else {
+                ControlFlow::Continue(None)
+            }
+        })
+    }
+}
+
+/// Like `DeepCommands`, but passes the path to each command, which can
+/// be given to `CommandPath::get_at_path` and `set_at_path`
+pub struct IndexedDeepCommands;
+
+fn fold_indexed_commands<B, F>(
+    commands: &[RenderCommand],
+    path: &mut Vec<usize>,
+    init: B,
+    f: &mut F,
+) -> B
+where
+    F: FnMut(B, &[usize], &RenderCommand) -> B,
+{
+    commands
+        .iter()
+        .enumerate()
+        .fold(init, |acc, (idx, command)| {
+            path.push(idx);
+            let acc = match command {
+                RenderCommand::Batch(nested) => fold_indexed_commands(nested, path, acc, f),
+                _ => f(acc, path, command),
+            };
+            path.pop();
+            acc
+        })
+}
+
+fn traverse_indexed_commands<F>(
+    commands: impl Iterator<Item = RenderCommand>,
+    path: &mut Vec<usize>,
+    f: &mut F,
+) -> Vec<RenderCommand>
+where
+    F: FnMut(&[usize], &RenderCommand) -> RenderCommand,
+{
+    commands
+        .enumerate()
+        .map(|(idx, command)| {
+            path.push(idx);
+            let command = match command {
+                RenderCommand::Batch(nested) => {
+                    RenderCommand::Batch(traverse_indexed_commands(nested.into_iter(), path, f))
+                }
+                other => f(path, &other),
+            };
+            path.pop();
+            command
+        })
+        .collect()
+}
+
+impl IndexedTraversal<Arc<[RenderCommand]>, RenderCommand> for IndexedDeepCommands {
+    fn fold<B, F: FnMut(B, &[usize], &RenderCommand) -> B>(
+        &self,
+        source: &Arc<[RenderCommand]>,
+        init: B,
+        mut f: F,
+    ) -> B {
+        fold_indexed_commands(source, &mut vec![], init, &mut f)
+    }
+
+    fn traverse<F: FnMut(&[usize], &RenderCommand) -> RenderCommand>(
+        &self,
+        source: Arc<[RenderCommand]>,
+        mut f: F,
+    ) -> Arc<[RenderCommand]> {
+        let transformed = traverse_indexed_commands(source.iter().cloned(), &mut vec![], &mut f);
+        Arc::from(transformed.into_boxed_slice())
+    }
+}
+
+impl IndexedTraversal<Vec<RenderCommand>, RenderCommand> for IndexedDeepCommands {
+    fn fold<B, F: FnMut(B, &[usize], &RenderCommand) -> B>(
+        &self,
+        source: &Vec<RenderCommand>,
+        init: B,
+        mut f: F,
+    ) -> B {
+        fold_indexed_commands(source, &mut vec![], init, &mut f)
+    }
+
+    fn traverse<F: FnMut(&[usize], &RenderCommand) -> RenderCommand>(
+        &self,
+        source: Vec<RenderCommand>,
+        mut f: F,
+    ) -> Vec<RenderCommand> {
+        traverse_indexed_commands(source.into_iter(), &mut vec![], &mut f)
+    }
+}
+
+/// Access to a command by the path that `IndexedDeepCommands` passed
+/// for it.  Every index but the last must lead to a `Batch`.
+pub trait CommandPath {
+    fn get_at_path(&self, path: &[usize]) -> Option<&RenderCommand>;
+    /// Replaces the command at `path`, returning the one that was
+    /// there, or returns None and changes nothing if there is no such
+    /// command
+    fn set_at_path(&mut self, path: &[usize], command: RenderCommand) -> Option<RenderCommand>;
+}
+
+impl CommandPath for Vec<RenderCommand> {
+    fn get_at_path(&self, path: &[usize]) -> Option<&RenderCommand> {
+        let (&last, parents) = path.split_last()?;
+        let mut commands: &[RenderCommand] = self;
+        for &idx in parents {
+            match commands.get(idx)? {
+                RenderCommand::Batch(nested) => commands = nested.as_slice(),
+                _ => return None,
+            }
+        }
+        commands.get(last)
+    }
+
+    fn set_at_path(&mut self, path: &[usize], command: RenderCommand) -> Option<RenderCommand> {
+        let (&last, parents) = path.split_last()?;
+        let mut commands: &mut [RenderCommand] = self;
+        for &idx in parents {
+            match commands.get_mut(idx)? {
+                RenderCommand::Batch(nested) => commands = nested.as_mut_slice(),
+                _ => return None,
+            }
+        }
+        let slot = commands.get_mut(last)?;
+        Some(std::mem::replace(slot, command))
+    }
+}
+
 pub struct AllPanes;
 
 impl Traversal<Frame, PaneFrame> for AllPanes {
@@ -367,8 +671,7 @@ mod tests {
 
     fn draw_quad(x: f32) -> RenderCommand {
         RenderCommand::DrawQuad {
-            layer: 0,
-            zindex: 0,
+            layer: LayerId::background(),
             position: rect(x, 0.0, 1.0, 1.0),
             texture: TextureCoords {
                 left: 0.0,
@@ -385,8 +688,7 @@ mod tests {
 
     fn fill_rect() -> RenderCommand {
         RenderCommand::FillRect {
-            layer: 1,
-            zindex: 2,
+            layer: LayerId::text().with_zindex(2),
             rect: rect(1.0, 2.0, 3.0, 4.0),
             color: LinearRgba::with_components(0.2, 0.3, 0.4, 1.0),
             hsv: Some(HsbTransform {
@@ -403,11 +705,12 @@ mod tests {
             is_active: true,
             bounds: rect(0.0, 0.0, 10.0, 10.0),
             command_hash: 0,
-            cache_key: 0,
+            cache_key: Some(0),
             commands: Arc::from(commands.into_boxed_slice()),
             ui_items: Vec::new(),
             last_execution_stats: None,
             skip_streak: 0,
+            hsv: None,
         }
     }
 
@@ -450,13 +753,11 @@ mod tests {
         match rebuilt {
             RenderCommand::FillRect {
                 layer,
-                zindex,
                 rect,
                 color,
                 hsv,
             } => {
                 assert_eq!(layer, fields.layer);
-                assert_eq!(zindex, fields.zindex);
                 assert_eq!(rect, fields.rect);
                 assert_eq!(color, fields.color);
                 let rebuilt_hsv = hsv.map(|v| (v.hue, v.saturation, v.brightness));
@@ -467,6 +768,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chrome_prisms_only_match_their_variant() {
+        let line = RenderCommand::DrawLine {
+            layer: LayerId::overlay(),
+            from: euclid::point2(0., 0.),
+            to: euclid::point2(0., 10.),
+            width: 1.,
+            color: LinearRgba(1., 1., 1., 1.),
+        };
+        assert!(AsFillRoundedRect.preview(&line).is_none());
+        let moved = AsDrawLine.traverse(line, |fields| DrawLineFields {
+            to: euclid::point2(5., 10.),
+            ..fields.clone()
+        });
+        let fields = AsDrawLine.preview(&moved).expect("expected DrawLine fields");
+        assert_eq!(fields.from, euclid::point2(0., 0.));
+        assert_eq!(fields.to, euclid::point2(5., 10.));
+
+        let rounded = RenderCommand::FillRoundedRect {
+            layer: LayerId::background(),
+            rect: rect(0., 0., 20., 10.),
+            radius: 4.,
+            color: LinearRgba(1., 1., 1., 1.),
+            hsv: None,
+        };
+        assert!(AsDrawLine.preview(&rounded).is_none());
+        let radius = AsFillRoundedRect.fold(&rounded, 0., |_, fields| fields.radius);
+        assert_eq!(radius, 4.);
+        assert_eq!(AsFillRect.fold(&rounded, 0, |count, _| count + 1), 0);
+    }
+
     #[test]
     fn traversal_fold_counts_draw_quads_in_frame() {
         let pane_a = pane(
@@ -494,6 +826,107 @@ mod tests {
         assert_eq!(count, 3);
     }
 
+    #[test]
+    fn fold_while_stops_early() {
+        let commands = vec![
+            fill_rect(),
+            RenderCommand::Batch(vec![
+                draw_quad(1.0),
+                RenderCommand::Batch(vec![draw_quad(2.0)]),
+            ]),
+            draw_quad(3.0),
+        ];
+
+        let mut calls = 0;
+        let quads = DeepCommands.fold_while(&commands, 0, |quads, command| {
+            calls += 1;
+            match command {
+                RenderCommand::DrawQuad { .. } if quads == 1 => ControlFlow::Break(quads + 1),
+                RenderCommand::DrawQuad { .. } => ControlFlow::Continue(quads + 1),
+                _ => ControlFlow::Continue(quads),
+            }
+        });
+        assert_eq!(quads, 2);
+        // The quad at 3.0 was never visited
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let found = DeepCommands.find_first(&commands, |command| {
+            calls += 1;
+            matches!(command, RenderCommand::DrawQuad { .. })
+        });
+        let found = AsDrawQuad.preview(&found.unwrap()).unwrap();
+        assert_eq!(found.position.origin.x, 1.0);
+        assert_eq!(calls, 2);
+
+        assert!(DeepCommands
+            .find_first(&commands, |command| matches!(command, RenderCommand::Nop))
+            .is_none());
+    }
+
+    #[test]
+    fn indexed_paths_address_nested_commands() {
+        let mut commands = vec![
+            fill_rect(),
+            RenderCommand::Batch(vec![
+                draw_quad(1.0),
+                RenderCommand::Batch(vec![fill_rect(), draw_quad(2.0)]),
+            ]),
+        ];
+
+        let paths = IndexedDeepCommands.fold(&commands, vec![], |mut paths, path, _| {
+            paths.push(path.to_vec());
+            paths
+        });
+        assert_eq!(
+            paths,
+            vec![vec![0], vec![1, 0], vec![1, 1, 0], vec![1, 1, 1]]
+        );
+
+        let nested_quad =
+            IndexedDeepCommands.fold(&commands, None, |found, path, command| match command {
+                RenderCommand::DrawQuad { position, .. } if position.origin.x == 2.0 => {
+                    Some(path.to_vec())
+                }
+                _ => found,
+            });
+        let path = nested_quad.unwrap();
+
+        let moved = AsDrawQuad.traverse(commands.get_at_path(&path).unwrap().clone(), |fields| {
+            DrawQuadFields {
+                position: rect(20.0, 0.0, 1.0, 1.0),
+                ..fields.clone()
+            }
+        });
+        let old = commands.set_at_path(&path, moved).unwrap();
+        assert_eq!(AsDrawQuad.preview(&old).unwrap().position.origin.x, 2.0);
+        let sum_x = DeepCommands.fold(&commands, 0.0f32, |acc, command| match command {
+            RenderCommand::DrawQuad { position, .. } => acc + position.origin.x,
+            _ => acc,
+        });
+        assert_eq!(sum_x, 21.0);
+
+        // Paths that lead nowhere, or through something other than a
+        // batch, change nothing
+        assert!(commands.get_at_path(&[]).is_none());
+        assert!(commands.get_at_path(&[1, 5]).is_none());
+        assert!(commands.set_at_path(&[0, 0], fill_rect()).is_none());
+
+        // Traversal passes the same paths as fold
+        let shared: Arc<[RenderCommand]> = Arc::from(commands.into_boxed_slice());
+        let traversed = IndexedDeepCommands.traverse(shared, |path, command| {
+            if path == [1, 0] {
+                fill_rect()
+            } else {
+                command.clone()
+            }
+        });
+        assert!(matches!(
+            traversed.to_vec().get_at_path(&[1, 0]),
+            Some(RenderCommand::FillRect { .. })
+        ));
+    }
+
     #[test]
     fn composed_all_panes_pane_commands_and_deep_commands() {
         let pane_a = pane(
@@ -509,7 +942,6 @@ mod tests {
         let transformed = optic.traverse(frame, |command| match command {
             RenderCommand::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color,
@@ -518,7 +950,6 @@ mod tests {
                 mode,
             } => RenderCommand::DrawQuad {
                 layer: *layer,
-                zindex: *zindex,
                 position: rect(
                     position.origin.x + 10.0,
                     position.origin.y,
@@ -1,6 +1,10 @@
-use phaedra_render_command::RectF;
+use ::window::color::LinearRgba;
+use phaedra_render_command::{LayerId, RectF, RenderCommand};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ScissorRect {
     pub x: u32,
     pub y: u32,
@@ -21,12 +25,24 @@ impl ScissorRect {
             height: bottom.saturating_sub(y),
         }
     }
+
+    /// Returns true if nothing drawn within this rect can overlap
+    /// anything drawn within `other`
+    pub fn is_disjoint_from(&self, other: &Self) -> bool {
+        self.width == 0
+            || self.height == 0
+            || other.width == 0
+            || other.height == 0
+            || self.x.saturating_add(self.width) <= other.x
+            || other.x.saturating_add(other.width) <= self.x
+            || self.y.saturating_add(self.height) <= other.y
+            || other.y.saturating_add(other.height) <= self.y
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LayerQuadSnapshot {
-    pub zindex: i8,
-    pub sub_idx: usize,
+    pub layer: LayerId,
     pub quad_count: usize,
 }
 
@@ -103,9 +119,45 @@ pub struct RenderSection {
     pub stats: Option<ExecutionStats>,
 }
 
+/// A group of sections that share a scissor rect, and so can be drawn
+/// one after another without changing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionBatch {
+    pub scissor: Option<ScissorRect>,
+    /// Indices into `RenderPlan::sections`, in the order in which they
+    /// are to be drawn
+    pub sections: Vec<usize>,
+}
+
+/// A request, recorded from a `RenderCommand::BeginPostProcess`, to
+/// post-process part or all of the frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostProcessRegion {
+    /// None means the whole frame
+    pub scissor: Option<ScissorRect>,
+    /// None means the default `webgpu_shader`
+    pub effect: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct RenderPlan {
+    /// The sections in the order in which they were described, which
+    /// is the order used to find their quads in the previous frame
     pub sections: Vec<RenderSection>,
+    /// The order in which to draw `sections`, as computed by
+    /// `optimize`.  When empty, they are drawn in order.
+    pub batches: Vec<SectionBatch>,
+    pub post_process: Vec<PostProcessRegion>,
+    /// The color from the last whole-frame `RenderCommand::Clear`.
+    /// When None, the frame is drawn over the existing contents of
+    /// the render target.
+    pub clear_color: Option<LinearRgba>,
+    /// The number of quads at the start of each buffer that were
+    /// discarded by a `RenderCommand::Clear`, and must not be drawn
+    pub layer_clears: Vec<LayerQuadSnapshot>,
+    /// The intensity of a visual bell whose target is PostProcess,
+    /// which is 0.0 unless one of the panes is ringing
+    pub visual_bell_intensity: f32,
     pub viewport_width: u32,
     pub viewport_height: u32,
 }
@@ -114,11 +166,133 @@ impl RenderPlan {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             sections: Vec::new(),
+            batches: Vec::new(),
+            post_process: Vec::new(),
+            clear_color: None,
+            layer_clears: Vec::new(),
+            visual_bell_intensity: 0.,
             viewport_width: width,
             viewport_height: height,
         }
     }
 
+    /// Collect the requests in `commands` that affect how the frame is
+    /// drawn, rather than what goes into its vertex buffers
+    pub fn record(&mut self, commands: &[RenderCommand]) {
+        self.record_post_process(commands);
+        self.record_clear(commands);
+    }
+
+    /// Collect the color of the last whole-frame clear in `commands`,
+    /// including those nested in batches
+    pub fn record_clear(&mut self, commands: &[RenderCommand]) {
+        for cmd in commands {
+            match cmd {
+                RenderCommand::Clear { color, layer: None } => {
+                    self.clear_color = Some(*color);
+                }
+                RenderCommand::Batch(commands) => self.record_clear(commands),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the number of quads at the start of the buffer for
+    /// `layer` that were discarded by the clears in `layer_clears`
+    pub fn discarded_quads(&self, layer: LayerId) -> usize {
+        self.layer_clears
+            .iter()
+            .filter(|clear| clear.layer == layer)
+            .map(|clear| clear.quad_count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Collect the post-process requests present in `commands`,
+    /// including those nested in batches.  Regions that fall entirely
+    /// outside of the viewport are dropped.
+    pub fn record_post_process(&mut self, commands: &[RenderCommand]) {
+        for cmd in commands {
+            match cmd {
+                RenderCommand::BeginPostProcess { region, effect } => {
+                    let scissor = match region {
+                        Some(region) => {
+                            let scissor = ScissorRect::from_pane_bounds(
+                                region,
+                                self.viewport_width,
+                                self.viewport_height,
+                            );
+                            if scissor.width == 0 || scissor.height == 0 {
+                                continue;
+                            }
+                            Some(scissor)
+                        }
+                        None => None,
+                    };
+                    self.post_process.push(PostProcessRegion {
+                        scissor,
+                        effect: effect.clone(),
+                    });
+                }
+                RenderCommand::Batch(commands) => self.record_post_process(commands),
+                _ => {}
+            }
+        }
+    }
+
+    /// Groups the sections by scissor rect, so that drawing them needs
+    /// fewer scissor changes and fewer draw calls.
+    /// A section joins the most recent batch with the same scissor as
+    /// long as every batch after that one has a scissor disjoint from
+    /// its own, so that moving it ahead of them can't change what is
+    /// drawn over what.  A section without a scissor overlaps every
+    /// other section, and so is never moved.
+    pub fn optimize(&mut self) {
+        let mut batches: Vec<SectionBatch> = vec![];
+        for (idx, section) in self.sections.iter().enumerate() {
+            let mut target = None;
+            for (batch_idx, batch) in batches.iter().enumerate().rev() {
+                if batch.scissor == section.scissor {
+                    target = Some(batch_idx);
+                    break;
+                }
+                let disjoint = match (&batch.scissor, &section.scissor) {
+                    (Some(a), Some(b)) => a.is_disjoint_from(b),
+                    _ => false,
+                };
+                if !disjoint {
+                    break;
+                }
+            }
+            match target {
+                Some(batch_idx) => batches[batch_idx].sections.push(idx),
+                None => batches.push(SectionBatch {
+                    scissor: section.scissor.clone(),
+                    sections: vec![idx],
+                }),
+            }
+        }
+        self.batches = batches;
+    }
+
+    /// Returns the batches computed by `optimize`, or, if it wasn't
+    /// called, a batch for each section in turn
+    pub fn batches(&self) -> Cow<'_, [SectionBatch]> {
+        if !self.batches.is_empty() || self.sections.is_empty() {
+            return Cow::Borrowed(&self.batches);
+        }
+        Cow::Owned(
+            self.sections
+                .iter()
+                .enumerate()
+                .map(|(idx, section)| SectionBatch {
+                    scissor: section.scissor.clone(),
+                    sections: vec![idx],
+                })
+                .collect(),
+        )
+    }
+
     pub fn pane_section_count(&self) -> usize {
         self.sections.iter().filter(|section| section.scissor.is_some()).count()
     }
@@ -129,16 +303,42 @@ impl RenderPlan {
             .filter(|section| section.scissor.is_some() && section.skippable)
             .count()
     }
+
+    /// Returns a hash of the content of the sections and the size of
+    /// the viewport, which identifies the frame in crash reports
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.viewport_width, self.viewport_height).hash(&mut hasher);
+        for section in &self.sections {
+            section.content_hash.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Describes the plan in a line, for crash reports
+    pub fn summary(&self) -> String {
+        format!(
+            "{}x{}, {} sections ({} pane sections, {} skippable) in {} batches, \
+             {} post-process regions, {} layer clears, clear color {:?}, \
+             visual bell intensity {:.2}",
+            self.viewport_width,
+            self.viewport_height,
+            self.sections.len(),
+            self.pane_section_count(),
+            self.skippable_pane_section_count(),
+            self.batches().len(),
+            self.post_process.len(),
+            self.layer_clears.len(),
+            self.clear_color,
+            self.visual_bell_intensity,
+        )
+    }
 }
 
-pub fn quad_count_for_snapshot(
-    snapshots: &[LayerQuadSnapshot],
-    zindex: i8,
-    sub_idx: usize,
-) -> usize {
+pub fn quad_count_for_snapshot(snapshots: &[LayerQuadSnapshot], layer: LayerId) -> usize {
     snapshots
         .iter()
-        .find(|snapshot| snapshot.zindex == zindex && snapshot.sub_idx == sub_idx)
+        .find(|snapshot| snapshot.layer == layer)
         .map(|snapshot| snapshot.quad_count)
         .unwrap_or(0)
 }
@@ -146,14 +346,259 @@ pub fn quad_count_for_snapshot(
 pub fn snapshot_layers(render_state: &crate::renderstate::RenderState) -> Vec<LayerQuadSnapshot> {
     let layers = render_state.layers.borrow();
     let mut snaps = Vec::new();
-    for layer in layers.iter() {
-        for sub_idx in 0..3 {
+    for render_layer in layers.iter() {
+        for sub_idx in 0..LayerId::SUB_LAYERS {
             snaps.push(LayerQuadSnapshot {
-                zindex: layer.zindex(),
-                sub_idx,
-                quad_count: layer.vb.borrow()[sub_idx].current_quad_count(),
+                layer: render_layer.layer_id(sub_idx),
+                quad_count: render_layer.vb.borrow()[sub_idx].current_quad_count(),
             });
         }
     }
     snaps
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn post_process(region: Option<RectF>, effect: Option<&str>) -> RenderCommand {
+        RenderCommand::BeginPostProcess {
+            region,
+            effect: effect.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn regions_are_collected_in_order() {
+        let mut plan = RenderPlan::new(100, 50);
+        plan.record_post_process(&[
+            RenderCommand::Clear {
+                color: LinearRgba(0., 0., 0., 1.),
+                layer: None,
+            },
+            post_process(Some(euclid::rect(10., 5., 20., 10.)), Some("blur")),
+            RenderCommand::Batch(vec![RenderCommand::Nop, post_process(None, None)]),
+        ]);
+        plan.record_post_process(&[post_process(Some(euclid::rect(90., 40., 30., 30.)), None)]);
+
+        assert_eq!(
+            plan.post_process,
+            vec![
+                PostProcessRegion {
+                    scissor: Some(ScissorRect {
+                        x: 10,
+                        y: 5,
+                        width: 20,
+                        height: 10,
+                    }),
+                    effect: Some("blur".to_string()),
+                },
+                PostProcessRegion {
+                    scissor: None,
+                    effect: None,
+                },
+                // Clamped to the viewport
+                PostProcessRegion {
+                    scissor: Some(ScissorRect {
+                        x: 90,
+                        y: 40,
+                        width: 10,
+                        height: 10,
+                    }),
+                    effect: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn offscreen_regions_are_dropped() {
+        let mut plan = RenderPlan::new(100, 50);
+        plan.record_post_process(&[
+            post_process(Some(euclid::rect(200., 0., 10., 10.)), Some("blur")),
+            post_process(Some(euclid::rect(0., 0., 0., 10.)), Some("blur")),
+        ]);
+        assert!(plan.post_process.is_empty());
+    }
+
+    fn clear(color: LinearRgba, layer: Option<usize>) -> RenderCommand {
+        RenderCommand::Clear { color, layer }
+    }
+
+    #[test]
+    fn last_frame_clear_sets_the_clear_color() {
+        let mut plan = RenderPlan::new(100, 50);
+        plan.record(&[post_process(None, None)]);
+        assert_eq!(plan.clear_color, None);
+
+        let translucent = LinearRgba(0.1, 0.2, 0.3, 0.5);
+        plan.record(&[
+            clear(LinearRgba(0., 0., 0., 1.), None),
+            RenderCommand::Batch(vec![clear(translucent, None)]),
+            // Layer clears don't affect the frame clear color
+            clear(LinearRgba(1., 1., 1., 1.), Some(1)),
+        ]);
+        assert_eq!(plan.clear_color, Some(translucent));
+        assert_eq!(plan.post_process.len(), 1);
+    }
+
+    #[test]
+    fn discarded_quads_are_per_buffer() {
+        let mut plan = RenderPlan::new(100, 50);
+        assert_eq!(plan.discarded_quads(LayerId::text()), 0);
+
+        let raised = LayerId::text().with_zindex(2);
+        let clear = |layer, quad_count| LayerQuadSnapshot { layer, quad_count };
+        plan.layer_clears = vec![
+            clear(LayerId::text(), 10),
+            clear(raised, 3),
+            clear(LayerId::text(), 25),
+        ];
+        assert_eq!(plan.discarded_quads(LayerId::text()), 25);
+        assert_eq!(plan.discarded_quads(raised), 3);
+        assert_eq!(plan.discarded_quads(LayerId::background()), 0);
+    }
+
+    fn section(scissor: Option<(u32, u32, u32, u32)>) -> RenderSection {
+        RenderSection {
+            scissor: scissor.map(|(x, y, width, height)| ScissorRect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            content_hash: 0,
+            quad_range: QuadRange {
+                start: vec![],
+                end: vec![],
+            },
+            skippable: false,
+            stats: None,
+        }
+    }
+
+    fn plan_of(scissors: &[Option<(u32, u32, u32, u32)>]) -> RenderPlan {
+        let mut plan = RenderPlan::new(100, 50);
+        plan.sections = scissors.iter().copied().map(section).collect();
+        plan
+    }
+
+    fn batched_sections(plan: &RenderPlan) -> Vec<Vec<usize>> {
+        plan.batches()
+            .iter()
+            .map(|batch| batch.sections.clone())
+            .collect()
+    }
+
+    /// Asserts that every section is drawn exactly once, with the scissor
+    /// of its batch, and after every earlier section that it overlaps
+    fn assert_draw_order_is_valid(plan: &RenderPlan) {
+        let mut position = vec![None; plan.sections.len()];
+        for (idx, section_idx) in plan
+            .batches()
+            .iter()
+            .flat_map(|batch| {
+                for &section_idx in &batch.sections {
+                    assert_eq!(plan.sections[section_idx].scissor, batch.scissor);
+                }
+                batch.sections.clone()
+            })
+            .enumerate()
+        {
+            assert_eq!(position[section_idx], None);
+            position[section_idx] = Some(idx);
+        }
+
+        for (later, section) in plan.sections.iter().enumerate() {
+            for earlier in 0..later {
+                let disjoint = match (&plan.sections[earlier].scissor, &section.scissor) {
+                    (Some(a), Some(b)) => a.is_disjoint_from(b),
+                    _ => false,
+                };
+                if !disjoint {
+                    assert!(
+                        position[earlier] < position[later],
+                        "{earlier} must be drawn before {later} in {:?}",
+                        plan.batches
+                    );
+                }
+            }
+        }
+    }
+
+    const LEFT: Option<(u32, u32, u32, u32)> = Some((0, 0, 50, 50));
+    const RIGHT: Option<(u32, u32, u32, u32)> = Some((50, 0, 50, 50));
+    const MIDDLE: Option<(u32, u32, u32, u32)> = Some((25, 10, 50, 20));
+
+    #[test]
+    fn summary_and_content_hash() {
+        let mut plan = plan_of(&[None, LEFT, RIGHT, LEFT]);
+        plan.sections[2].skippable = true;
+        plan.optimize();
+        assert_eq!(
+            plan.summary(),
+            "100x50, 4 sections (3 pane sections, 1 skippable) in 3 batches, \
+             0 post-process regions, 0 layer clears, clear color None, \
+             visual bell intensity 0.00"
+        );
+
+        let hash = plan.content_hash();
+        assert_eq!(hash, plan_of(&[None, LEFT, RIGHT, LEFT]).content_hash());
+        plan.sections[1].content_hash = 1;
+        assert_ne!(plan.content_hash(), hash);
+    }
+
+    #[test]
+    fn unoptimized_sections_are_drawn_in_turn() {
+        let plan = plan_of(&[None, LEFT, RIGHT, None]);
+        assert_eq!(
+            batched_sections(&plan),
+            vec![vec![0], vec![1], vec![2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn interleaved_sections_are_grouped() {
+        let mut plan = plan_of(&[None, LEFT, RIGHT, LEFT, RIGHT, RIGHT, None]);
+        plan.optimize();
+        assert_eq!(
+            batched_sections(&plan),
+            vec![vec![0], vec![1, 3], vec![2, 4, 5], vec![6]]
+        );
+        assert_draw_order_is_valid(&plan);
+    }
+
+    #[test]
+    fn overlapping_sections_are_not_moved() {
+        // MIDDLE overlaps both panes, so the second LEFT can't be
+        // drawn ahead of it
+        let mut plan = plan_of(&[LEFT, RIGHT, MIDDLE, LEFT]);
+        plan.optimize();
+        assert_eq!(
+            batched_sections(&plan),
+            vec![vec![0], vec![1], vec![2], vec![3]]
+        );
+
+        // Nor can anything be drawn ahead of a section without a
+        // scissor, such as the chrome
+        let mut plan = plan_of(&[None, LEFT, RIGHT, None, LEFT, None, None]);
+        plan.optimize();
+        assert_eq!(
+            batched_sections(&plan),
+            vec![vec![0], vec![1], vec![2], vec![3], vec![4], vec![5, 6]]
+        );
+    }
+
+    #[test]
+    fn every_arrangement_keeps_overlapping_sections_in_order() {
+        let choices = [None, LEFT, RIGHT, MIDDLE, Some((60, 40, 0, 10))];
+        for n in 0..choices.len().pow(5) {
+            let scissors: Vec<_> = (0..5)
+                .map(|digit| choices[n / choices.len().pow(digit) % choices.len()])
+                .collect();
+            let mut plan = plan_of(&scissors);
+            plan.optimize();
+            assert_draw_order_is_valid(&plan);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use super::glyphcache::GlyphCache;
 use super::quad::*;
 use super::utilsprites::{RenderMetrics, UtilSprites};
+use crate::render_command::LayerId;
 use crate::termwindow::webgpu::{adapter_info_to_gpu_info, WebGpuState, WebGpuTexture};
 use ::window::bitmaps::atlas::OutOfTextureSpace;
 use ::window::bitmaps::Texture2d;
@@ -11,6 +12,9 @@ use std::convert::TryFrom;
 use std::ops::Range;
 use std::rc::Rc;
 use std::iter::FromIterator;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use phaedra_font::FontConfiguration;
 use wgpu::util::DeviceExt;
 
@@ -217,9 +221,58 @@ impl<'a> QuadAllocator for MappedQuads<'a> {
     }
 }
 
+/// Identifies a queue submission and whether the GPU has finished
+/// executing it
+#[derive(Clone)]
+pub struct SubmissionFence {
+    index: wgpu::SubmissionIndex,
+    done: Arc<AtomicBool>,
+}
+
+impl SubmissionFence {
+    /// Submits `command_buffers` to `queue`, returning a fence that
+    /// completes once the GPU has finished with them
+    pub fn submit<I: IntoIterator<Item = wgpu::CommandBuffer>>(
+        queue: &wgpu::Queue,
+        command_buffers: I,
+    ) -> Self {
+        let index = queue.submit(command_buffers);
+        let done = Arc::new(AtomicBool::new(false));
+        let signal = Arc::clone(&done);
+        queue.on_submitted_work_done(move || signal.store(true, Ordering::Release));
+        Self { index, done }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Blocks until the submission has completed.
+    /// Returns true if it was still in flight, and we had to wait.
+    pub fn wait(&self, device: &wgpu::Device) -> bool {
+        if !self.is_complete() {
+            // Completion callbacks only run when the device is polled
+            let _ = device.poll(wgpu::PollType::Poll);
+        }
+        if self.is_complete() {
+            return false;
+        }
+
+        let start = Instant::now();
+        if let Err(err) = device.poll(wgpu::PollType::WaitForSubmissionIndex(self.index.clone())) {
+            log::warn!("waiting for vertex buffer submission: {err:#}");
+        }
+        metrics::histogram!("gui.vertex_buffer.wait").record(start.elapsed());
+        metrics::histogram!("gui.vertex_buffer.wait.rate").record(1.);
+        true
+    }
+}
+
 pub struct TripleVertexBuffer {
     pub index: RefCell<usize>,
     pub bufs: RefCell<[VertexBuffer; 3]>,
+    /// The submission that last drew from each of `bufs`
+    pub fences: RefCell<[Option<SubmissionFence>; 3]>,
     pub indices: IndexBuffer,
     pub capacity: usize,
     pub next_quad: RefCell<usize>,
@@ -291,6 +344,7 @@ impl TripleVertexBuffer {
     }
 
     pub fn map(&self) -> MappedQuads<'_> {
+        self.wait_for_current_slot();
         let bufs = self.current_vb_mut();
 
         // To map the vertex buffer, we need to hold a mutable reference to
@@ -318,6 +372,23 @@ impl TripleVertexBuffer {
         unsafe { RefMut::map(bufs, |bufs| &mut bufs[index]).extend_lifetime() }
     }
 
+    /// Records that `fence` draws from the buffer in `slot`, so that
+    /// the slot won't be written again until the GPU is done with it
+    pub fn mark_in_flight(&self, slot: usize, fence: &SubmissionFence) {
+        self.fences.borrow_mut()[slot] = Some(fence.clone());
+    }
+
+    /// If the current slot may still be in use by an earlier submission,
+    /// block until the GPU has finished with it
+    fn wait_for_current_slot(&self) {
+        let index = *self.index.borrow();
+        let Some(fence) = self.fences.borrow_mut()[index].take() else {
+            return;
+        };
+        let bufs = self.bufs.borrow();
+        fence.wait(&bufs[index].webgpu().state.device);
+    }
+
     pub fn next_index(&self) {
         let mut index = self.index.borrow_mut();
         *index += 1;
@@ -379,6 +450,12 @@ impl RenderLayer {
         self.zindex
     }
 
+    /// Returns the id of the sub-layer at `sub_idx` of this layer
+    pub fn layer_id(&self, sub_idx: usize) -> LayerId {
+        LayerId::new(self.zindex, sub_idx)
+            .expect("render layers have LayerId::SUB_LAYERS sub-layers")
+    }
+
     pub fn reallocate_quads(&self, idx: usize, num_quads: usize) -> anyhow::Result<()> {
         let vb = Self::compute_vertices(&self.context, num_quads)?;
         self.vb.borrow_mut()[idx] = vb;
@@ -424,6 +501,7 @@ impl RenderLayer {
                 context.allocate_vertex_buffer(num_quads, &verts)?,
                 context.allocate_vertex_buffer(num_quads, &verts)?,
             ]),
+            fences: RefCell::new([None, None, None]),
             capacity: num_quads,
             indices: context.allocate_index_buffer(&indices)?,
             next_quad: RefCell::new(0),
@@ -450,33 +528,32 @@ impl TripleLayerQuadAllocatorTrait for BorrowedLayers {
     }
 }
 
-type LayerKey = (i8, usize);
 type SectionLayerRange = (usize, usize);
 const INDICES_PER_QUAD: u32 = 6;
 
 #[derive(Default)]
 pub struct LayerBuffers {
-    by_layer: HashMap<LayerKey, wgpu::Buffer>,
+    by_layer: HashMap<LayerId, wgpu::Buffer>,
 }
 
 impl LayerBuffers {
-    pub fn push(&mut self, (zindex, sub_idx, buffer): (i8, usize, wgpu::Buffer)) {
-        self.insert(zindex, sub_idx, buffer);
+    pub fn push(&mut self, (layer, buffer): (LayerId, wgpu::Buffer)) {
+        self.insert(layer, buffer);
     }
 
-    pub fn insert(&mut self, zindex: i8, sub_idx: usize, buffer: wgpu::Buffer) {
-        self.by_layer.insert((zindex, sub_idx), buffer);
+    pub fn insert(&mut self, layer: LayerId, buffer: wgpu::Buffer) {
+        self.by_layer.insert(layer, buffer);
     }
 
-    pub fn get(&self, zindex: i8, sub_idx: usize) -> Option<&wgpu::Buffer> {
-        self.by_layer.get(&(zindex, sub_idx))
+    pub fn get(&self, layer: LayerId) -> Option<&wgpu::Buffer> {
+        self.by_layer.get(&layer)
     }
 }
 
 #[derive(Default)]
 pub struct SectionRanges {
     sections: Vec<crate::render_plan::QuadRange>,
-    indexed_ranges: Vec<HashMap<LayerKey, SectionLayerRange>>,
+    indexed_ranges: Vec<HashMap<LayerId, SectionLayerRange>>,
 }
 
 impl SectionRanges {
@@ -488,7 +565,7 @@ impl SectionRanges {
         }
     }
 
-    fn ranges_for_section(&self, section_idx: usize) -> Option<&HashMap<LayerKey, SectionLayerRange>> {
+    fn ranges_for_section(&self, section_idx: usize) -> Option<&HashMap<LayerId, SectionLayerRange>> {
         self.indexed_ranges.get(section_idx)
     }
 
@@ -496,16 +573,8 @@ impl SectionRanges {
         self.sections.get(section_idx)
     }
 
-    pub fn section_quad_range(
-        &self,
-        section_idx: usize,
-        zindex: i8,
-        sub_idx: usize,
-    ) -> Option<(usize, usize)> {
-        self.indexed_ranges
-            .get(section_idx)?
-            .get(&(zindex, sub_idx))
-            .copied()
+    pub fn section_quad_range(&self, section_idx: usize, layer: LayerId) -> Option<(usize, usize)> {
+        self.indexed_ranges.get(section_idx)?.get(&layer).copied()
     }
 }
 
@@ -517,20 +586,21 @@ impl FromIterator<crate::render_plan::QuadRange> for SectionRanges {
 
 fn index_section_quad_ranges(
     section: &crate::render_plan::QuadRange,
-) -> HashMap<LayerKey, SectionLayerRange> {
+) -> HashMap<LayerId, SectionLayerRange> {
     let mut start_quads = HashMap::with_capacity(section.start.len());
     for snapshot in &section.start {
         start_quads
-            .entry((snapshot.zindex, snapshot.sub_idx))
+            .entry(snapshot.layer)
             .or_insert(snapshot.quad_count);
     }
 
     let mut ranges = HashMap::with_capacity(section.end.len());
     for snapshot in &section.end {
-        let key = (snapshot.zindex, snapshot.sub_idx);
-        let start_quad = start_quads.get(&key).copied().unwrap_or(0);
+        let start_quad = start_quads.get(&snapshot.layer).copied().unwrap_or(0);
         if snapshot.quad_count > start_quad {
-            ranges.entry(key).or_insert((start_quad, snapshot.quad_count));
+            ranges
+                .entry(snapshot.layer)
+                .or_insert((start_quad, snapshot.quad_count));
         }
     }
     ranges
@@ -547,8 +617,8 @@ pub struct FrameBuffersBuilder {
 }
 
 impl FrameBuffersBuilder {
-    pub fn with_buffer(mut self, zindex: i8, sub_idx: usize, buffer: wgpu::Buffer) -> Self {
-        self.frame_buffers.buffers.insert(zindex, sub_idx, buffer);
+    pub fn with_buffer(mut self, layer: LayerId, buffer: wgpu::Buffer) -> Self {
+        self.frame_buffers.buffers.insert(layer, buffer);
         self
     }
 
@@ -572,8 +642,8 @@ impl FrameBuffers {
         }
     }
 
-    pub fn with_buffer(mut self, zindex: i8, sub_idx: usize, buffer: wgpu::Buffer) -> Self {
-        self.buffers.insert(zindex, sub_idx, buffer);
+    pub fn with_buffer(mut self, layer: LayerId, buffer: wgpu::Buffer) -> Self {
+        self.buffers.insert(layer, buffer);
         self
     }
 
@@ -585,39 +655,28 @@ impl FrameBuffers {
         self
     }
 
-    pub fn buffer(&self, zindex: i8, sub_idx: usize) -> Option<&wgpu::Buffer> {
-        self.buffers.get(zindex, sub_idx)
+    pub fn buffer(&self, layer: LayerId) -> Option<&wgpu::Buffer> {
+        self.buffers.get(layer)
     }
 
-    pub fn section_quad_range(
-        &self,
-        section_idx: usize,
-        zindex: i8,
-        sub_idx: usize,
-    ) -> Option<(usize, usize)> {
-        self.section_ranges
-            .section_quad_range(section_idx, zindex, sub_idx)
+    pub fn section_quad_range(&self, section_idx: usize, layer: LayerId) -> Option<(usize, usize)> {
+        self.section_ranges.section_quad_range(section_idx, layer)
     }
 
     pub fn can_skip_section(&self, section_idx: usize) -> bool {
         self.section_ranges
             .ranges_for_section(section_idx)
-            .map(|ranges| {
-                ranges
-                    .keys()
-                    .any(|(zindex, sub_idx)| self.buffer(*zindex, *sub_idx).is_some())
-            })
+            .map(|ranges| ranges.keys().any(|layer| self.buffer(*layer).is_some()))
             .unwrap_or(false)
     }
 
     pub fn section_draw_source(
         &self,
         section_idx: usize,
-        zindex: i8,
-        sub_idx: usize,
+        layer: LayerId,
     ) -> Option<(&wgpu::Buffer, Range<u32>)> {
-        let (start_quad, end_quad) = self.section_quad_range(section_idx, zindex, sub_idx)?;
-        let buffer = self.buffer(zindex, sub_idx)?;
+        let (start_quad, end_quad) = self.section_quad_range(section_idx, layer)?;
+        let buffer = self.buffer(layer)?;
         let start = u32::try_from(start_quad)
             .ok()?
             .checked_mul(INDICES_PER_QUAD)?;
@@ -634,6 +693,9 @@ pub struct RenderState {
     pub util_sprites: UtilSprites,
     pub layers: RefCell<Vec<Rc<RenderLayer>>>,
     pub prev_frame_buffers: RefCell<Option<FrameBuffers>>,
+    /// The quad counts of the buffers at each layer `Clear` executed
+    /// in the frame that is being painted
+    pub layer_clears: RefCell<Vec<crate::render_plan::LayerQuadSnapshot>>,
 }
 
 impl RenderState {
@@ -656,6 +718,7 @@ impl RenderState {
                         util_sprites,
                         layers: RefCell::new(vec![main_layer]),
                         prev_frame_buffers: RefCell::new(None),
+                        layer_clears: RefCell::new(vec![]),
                     });
                 }
                 Err(OutOfTextureSpace {
@@ -770,6 +833,8 @@ impl RenderState {
     ) -> anyhow::Result<()> {
         let size = size.unwrap_or_else(|| self.glyph_cache.borrow().atlas.size());
         let mut new_glyph_cache = GlyphCache::new_gl(&self.context, fonts, size)?;
+        // The util sprites go first, so that they keep their coordinates
+        // across rebuilds of the same size
         self.util_sprites = UtilSprites::new(&mut new_glyph_cache, metrics)?;
 
         let mut glyph_cache = self.glyph_cache.borrow_mut();
@@ -785,3 +850,87 @@ impl RenderState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Rotates through three buffers the way TripleVertexBuffer does,
+    /// rewriting each one as soon as its fence allows, and checks via
+    /// readback that every frame's draw saw the data written for it.
+    #[test]
+    fn slots_are_not_rewritten_while_in_flight() {
+        const FRAMES: u64 = 300;
+        const WORDS: u64 = 1024;
+        const SLOT_BYTES: u64 = WORDS * 4;
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let Ok(adapter) = futures::executor::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        ) else {
+            // No usable adapter on this machine; there is nothing to check
+            return;
+        };
+        let Ok((device, queue)) =
+            futures::executor::block_on(adapter.request_device(&Default::default()))
+        else {
+            return;
+        };
+
+        let slots: Vec<wgpu::Buffer> = (0..3)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: None,
+                    size: SLOT_BYTES,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: FRAMES * SLOT_BYTES,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut fences: [Option<SubmissionFence>; 3] = [None, None, None];
+        for frame in 0..FRAMES {
+            let slot = (frame % 3) as usize;
+            if let Some(fence) = fences[slot].take() {
+                fence.wait(&device);
+                assert!(fence.is_complete());
+            }
+
+            let data: Vec<u32> = (0..WORDS)
+                .map(|word| (frame * WORDS + word) as u32)
+                .collect();
+            queue.write_buffer(&slots[slot], 0, bytemuck::cast_slice(&data));
+
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.copy_buffer_to_buffer(
+                &slots[slot],
+                0,
+                &readback,
+                frame * SLOT_BYTES,
+                SLOT_BYTES,
+            );
+            fences[slot] = Some(SubmissionFence::submit(
+                &queue,
+                std::iter::once(encoder.finish()),
+            ));
+        }
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+        for fence in fences.iter().flatten() {
+            assert!(fence.is_complete());
+        }
+
+        let mapped = readback.slice(..).get_mapped_range();
+        let words: &[u32] = bytemuck::cast_slice(&mapped);
+        for (idx, &word) in words.iter().enumerate() {
+            assert_eq!(word, idx as u32, "frame {}", idx as u64 / WORDS);
+        }
+    }
+}
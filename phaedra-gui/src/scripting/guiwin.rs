@@ -1,5 +1,8 @@
 //! GuiWin represents a Gui TermWindow (as opposed to a Mux window) in lua code
 use super::luaerr;
+use crate::execute::EffectOutcome;
+use crate::interpret::effects_from_dynamic;
+use crate::tabbar::StatusSegments;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, KeyAssignment};
@@ -104,6 +107,14 @@ impl UserData for GuiWin {
             this.window.notify(TermWindowNotif::SetLeftStatus(status));
             Ok(())
         });
+        methods.add_method(
+            "set_status_segments",
+            |_, this, segments: StatusSegments| {
+                this.window
+                    .notify(TermWindowNotif::SetStatusSegments(segments));
+                Ok(())
+            },
+        );
         methods.add_async_method("get_dimensions", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window.notify(TermWindowNotif::GetDimensions(tx));
@@ -157,6 +168,15 @@ impl UserData for GuiWin {
             let result = rx.recv().await.map_err(mlua::Error::external)?;
             luahelper::dynamic_to_lua_value(lua, result)
         });
+        methods.add_async_method("cache_stats", |lua, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.cache_stats().to_dynamic()).ok();
+                })));
+            let result = rx.recv().await.map_err(mlua::Error::external)?;
+            luahelper::dynamic_to_lua_value(lua, result)
+        });
         methods.add_async_method(
             "perform_action",
             |_, this, (assignment, pane): (KeyAssignment, UserDataRef<MuxPane>)| async move {
@@ -170,6 +190,27 @@ impl UserData for GuiWin {
                 result.map_err(mlua::Error::external)
             },
         );
+        methods.add_async_method(
+            "perform_effect",
+            |lua, this, (effect, pane): (mlua::Value, UserDataRef<MuxPane>)| async move {
+                let effects = lua_value_to_dynamic(effect)
+                    .map_err(|err| anyhow::anyhow!("{:#}", err))
+                    .and_then(|value| effects_from_dynamic(&value));
+                let outcome = match effects {
+                    Ok(effects) => {
+                        let (tx, rx) = smol::channel::bounded(1);
+                        this.window.notify(TermWindowNotif::PerformEffects {
+                            pane_id: pane.0,
+                            effects,
+                            tx,
+                        });
+                        rx.recv().await.map_err(mlua::Error::external)?
+                    }
+                    Err(err) => EffectOutcome::failure(None, &err),
+                };
+                dynamic_to_lua_value(lua, outcome.to_dynamic())
+            },
+        );
         methods.add_async_method("effective_config", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window.notify(TermWindowNotif::GetEffectiveConfig(tx));
@@ -232,7 +273,7 @@ impl UserData for GuiWin {
                 .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
                     tx.try_send(match term_window.composition_status() {
                         DeadKeyStatus::None => None,
-                        DeadKeyStatus::Composing(s) => Some(s.clone()),
+                        DeadKeyStatus::Composing(c) => Some(c.text.clone()),
                     })
                     .ok();
                 })));
@@ -35,14 +35,17 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
 
     fn key_table_to_lua(table: &KeyTable) -> Vec<Key> {
         let mut keys = vec![];
-        for ((key, mods), entry) in table {
-            keys.push(Key {
-                key: KeyNoAction {
-                    key: DeferredKeyCode::KeyCode(key.clone()),
-                    mods: *mods,
-                },
-                action: entry.action.clone(),
-            });
+        for ((key, mods), entries) in table {
+            for entry in entries {
+                keys.push(Key {
+                    key: KeyNoAction {
+                        key: DeferredKeyCode::KeyCode(key.clone()),
+                        mods: *mods,
+                    },
+                    action: entry.action.clone(),
+                    when: entry.when.clone(),
+                });
+            }
         }
         keys
     }
@@ -81,19 +84,7 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
     window_mod.set(
         "enumerate_gpus",
         lua.create_function(|_, _: ()| {
-            let backends = wgpu::Backends::all();
-            let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-                backends,
-                ..Default::default()
-            });
-            let gpus: Vec<GpuInfo> = instance
-                .enumerate_adapters(backends)
-                .into_iter()
-                .map(|adapter| {
-                    let info = adapter.get_info();
-                    crate::termwindow::webgpu::adapter_info_to_gpu_info(info)
-                })
-                .collect();
+            let gpus: Vec<GpuInfo> = crate::termwindow::webgpu::enumerate_adapters_standalone();
             Ok(gpus)
         })?,
     )?;
@@ -28,8 +28,7 @@ const IS_COLOR_EMOJI: f32 = 1.0;
 // background image of the window
 const IS_BG_IMAGE: f32 = 2.0;
 
-// like 2.0, except that instead of an
-// image, we use the solid bg color
+// the solid fg color, without sampling the texture
 const IS_SOLID_COLOR: f32 = 3.0;
 
 // Grayscale poly quad for non-aa text render layers
@@ -66,6 +65,8 @@ fn hsv2rgb(c: vec3<f32>) -> vec3<f32>
     return c.z * mix(K.xxx, clamp(p - K.xxx, vec3(0.0), vec3(1.0)), c.y);
 }
 
+// Solid colors are transformed on the CPU by apply_hsb_to_color in
+// execute_render.rs, which must be kept in sync with this
 fn apply_hsv(c: vec4<f32>, transform: vec3<f32>) -> vec4<f32>
 {
   let hsv = rgb2hsv(c.rgb) * transform;
@@ -89,32 +90,35 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-  var color: vec4<f32>;
-  var linear_tex: vec4<f32> = textureSample(atlas_linear_tex, atlas_linear_sampler, in.tex);
-  var nearest_tex: vec4<f32> = textureSample(atlas_nearest_tex, atlas_nearest_sampler, in.tex);
+  if in.has_color == IS_SOLID_COLOR {
+    // Solid color block; its texture coordinates are meaningless,
+    // so don't sample the atlas at all
+    return apply_hsv(in.fg_color, in.hsv);
+  }
 
+  // textureSample may only be used in uniform control flow, and the
+  // mode varies by vertex.  The atlas has no mipmaps, so sampling the
+  // base level explicitly is equivalent.
+  var color: vec4<f32>;
   var hsv = in.hsv;
 
-  if in.has_color == IS_SOLID_COLOR {
-    // Solid color block
-    color = in.fg_color;
-  } else if in.has_color == IS_BG_IMAGE {
+  if in.has_color == IS_BG_IMAGE {
     // Window background attachment
     // Apply window_background_image_opacity to the background image
-    color = linear_tex;
+    color = textureSampleLevel(atlas_linear_tex, atlas_linear_sampler, in.tex, 0.0);
     color.a *= in.fg_color.a;
   } else if in.has_color == IS_COLOR_EMOJI {
     // the texture is full color info (eg: color emoji glyph)
-    color = nearest_tex;
+    color = textureSampleLevel(atlas_nearest_tex, atlas_nearest_sampler, in.tex, 0.0);
   } else if in.has_color == IS_GRAY_SCALE {
     // Grayscale poly quad for non-aa text render layers
     color = in.fg_color;
-    color.a *= nearest_tex.a;
+    color.a *= textureSampleLevel(atlas_nearest_tex, atlas_nearest_sampler, in.tex, 0.0).a;
   } else if in.has_color == IS_GLYPH {
     // the texture is the alpha channel/color mask
     // and we need to tint with the fg_color
     color = in.fg_color;
-    color.a = nearest_tex.a;
+    color.a = textureSampleLevel(atlas_nearest_tex, atlas_nearest_sampler, in.tex, 0.0).a;
     hsv *= uniforms.foreground_text_hsb;
   }
 
@@ -1,14 +1,20 @@
 use crate::customglyph::BlockKey;
 use crate::glyphcache::CachedGlyph;
+use crate::render_command::QuadMode;
 use config::TextStyle;
+use std::ops::Range;
 use std::rc::Rc;
-use phaedra_font::shaper::GlyphInfo;
+use termwiz::cell::Presentation;
+use phaedra_font::shaper::{FallbackIdx, GlyphInfo};
 use phaedra_font::units::*;
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct ShapeCacheKey {
     pub style: TextStyle,
     pub text: String,
+    /// The bits of the scale of the fonts that shaped the text, so that
+    /// panes with their own font scale don't share shaped glyphs
+    pub font_scale: u64,
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,6 +31,10 @@ pub struct ShapedInfo {
     pub glyph: Rc<CachedGlyph>,
     pub pos: GlyphPosition,
     pub block_key: Option<BlockKey>,
+    /// Which font alternative the glyph was rendered from
+    pub font_idx: FallbackIdx,
+    /// The presentation explicitly requested for the glyph
+    pub presentation: Option<Presentation>,
 }
 
 impl ShapedInfo {
@@ -47,10 +57,80 @@ impl ShapedInfo {
                 },
                 glyph: Rc::clone(glyph),
                 block_key: info.only_char.and_then(BlockKey::from_char),
+                font_idx: info.font_idx,
+                presentation: info.presentation,
             });
         }
         pos
     }
+
+    pub fn quad_mode(&self) -> QuadMode {
+        quad_mode_for_glyph(&self.glyph, self.presentation)
+    }
+}
+
+/// Decide how a glyph should be drawn.  An explicit text presentation
+/// draws a color glyph using only its coverage, tinted with the foreground
+/// color, and an explicit emoji presentation uses the colors from the
+/// texture even if the font only supplied a monochrome glyph.
+/// Otherwise, whether the font produced a color glyph decides.
+pub fn quad_mode_for_glyph(glyph: &CachedGlyph, presentation: Option<Presentation>) -> QuadMode {
+    match presentation {
+        Some(Presentation::Text) => QuadMode::Glyph,
+        Some(Presentation::Emoji) => QuadMode::ColorEmoji,
+        None if glyph.has_color => QuadMode::ColorEmoji,
+        None => QuadMode::Glyph,
+    }
+}
+
+/// A span of cells within a shaped cluster whose glyphs were all
+/// rendered from the same font
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontRun {
+    pub font_idx: FallbackIdx,
+    /// The scale that was applied to the glyphs of this run
+    pub scale: f64,
+    /// Cell offsets relative to the start of the cluster
+    pub cells: Range<usize>,
+}
+
+/// Group the shaped glyphs of a cluster into runs of consecutive glyphs
+/// that share the same font.  Glyphs that occupy no cells of their own,
+/// such as combining marks, are folded into the preceding run.  The final
+/// run is extended to cover `cluster_width` cells.
+/// `infos` are in visual order, so for a right-to-left cluster the cell
+/// ranges are mirrored back into logical order.
+pub fn font_runs(infos: &[ShapedInfo], cluster_width: usize, rtl: bool) -> Vec<FontRun> {
+    let mut runs: Vec<FontRun> = vec![];
+    let mut cell = 0;
+
+    for info in infos {
+        let num_cells = info.pos.num_cells as usize;
+        match runs.last_mut() {
+            Some(run) if run.font_idx == info.font_idx || num_cells == 0 => {
+                run.cells.end += num_cells;
+            }
+            _ => runs.push(FontRun {
+                font_idx: info.font_idx,
+                scale: info.glyph.scale,
+                cells: cell..cell + num_cells,
+            }),
+        }
+        cell += num_cells;
+    }
+
+    if let Some(last) = runs.last_mut() {
+        last.cells.end = last.cells.end.max(cluster_width);
+    }
+
+    if rtl {
+        let width = cell.max(cluster_width);
+        for run in &mut runs {
+            run.cells = width - run.cells.end..width - run.cells.start;
+        }
+    }
+
+    runs
 }
 
 /// We'd like to avoid allocating when resolving from the cache
@@ -62,6 +142,7 @@ impl ShapedInfo {
 pub struct BorrowedShapeCacheKey<'a> {
     pub style: &'a TextStyle,
     pub text: &'a str,
+    pub font_scale: u64,
 }
 
 impl<'a> BorrowedShapeCacheKey<'a> {
@@ -69,6 +150,7 @@ impl<'a> BorrowedShapeCacheKey<'a> {
         ShapeCacheKey {
             style: self.style.clone(),
             text: self.text.to_owned(),
+            font_scale: self.font_scale,
         }
     }
 }
@@ -82,6 +164,7 @@ impl ShapeCacheKeyTrait for ShapeCacheKey {
         BorrowedShapeCacheKey {
             style: &self.style,
             text: &self.text,
+            font_scale: self.font_scale,
         }
     }
 }
@@ -114,15 +197,17 @@ impl<'a> std::hash::Hash for dyn ShapeCacheKeyTrait + 'a {
 
 #[cfg(test)]
 mod test {
-    use crate::glyphcache::GlyphCache;
-    use crate::shapecache::{GlyphPosition, ShapedInfo};
+    use crate::glyphcache::{CachedGlyph, GlyphCache};
+    use crate::render_command::QuadMode;
+    use crate::shapecache::{font_runs, FontRun, GlyphPosition, ShapedInfo};
     use crate::utilsprites::RenderMetrics;
     use config::{FontAttributes, TextStyle};
     use std::rc::Rc;
-    use termwiz::cell::CellAttributes;
+    use termwiz::cell::{CellAttributes, Presentation};
     use termwiz::surface::{Line, SEQ_ZERO};
     use phaedra_bidi::Direction;
     use phaedra_font::shaper::PresentationWidth;
+    use phaedra_font::units::PixelLength;
     use phaedra_font::{FontConfiguration, LoadedFont};
 
     fn cluster_and_shape(
@@ -695,4 +780,193 @@ mod test {
 "
         );
     }
+
+    fn fake_font_metrics(
+        underline_thickness: f64,
+        underline_position: f64,
+    ) -> phaedra_font::FontMetrics {
+        phaedra_font::FontMetrics {
+            cell_width: PixelLength::new(8.),
+            cell_height: PixelLength::new(16.),
+            descender: PixelLength::new(-4.),
+            underline_thickness: PixelLength::new(underline_thickness),
+            underline_position: PixelLength::new(underline_position),
+            cap_height_ratio: None,
+            cap_height: None,
+            is_scaled: true,
+            presentation: termwiz::cell::Presentation::Text,
+            force_y_adjust: PixelLength::new(0.),
+        }
+    }
+
+    fn fake_shaped(font_idx: usize, num_cells: u8) -> ShapedInfo {
+        ShapedInfo {
+            glyph: Rc::new(CachedGlyph {
+                has_color: false,
+                brightness_adjust: 1.,
+                x_offset: PixelLength::new(0.),
+                y_offset: PixelLength::new(0.),
+                x_advance: PixelLength::new(8. * num_cells as f64),
+                bearing_x: PixelLength::new(0.),
+                bearing_y: PixelLength::new(0.),
+                texture: None,
+                scale: 1.0,
+            }),
+            pos: GlyphPosition {
+                glyph_idx: 0,
+                num_cells,
+                x_offset: PixelLength::new(0.),
+                bearing_x: 0.,
+                bitmap_pixel_width: 0,
+            },
+            block_key: None,
+            font_idx,
+            presentation: None,
+        }
+    }
+
+    #[test]
+    fn fallback_font_runs_use_their_own_underline_metrics() {
+        // "ab" from the primary font followed by a double-width glyph
+        // and a combining mark from a fallback font
+        let shaped = vec![
+            fake_shaped(0, 1),
+            fake_shaped(0, 1),
+            fake_shaped(1, 2),
+            fake_shaped(1, 0),
+        ];
+
+        let runs = font_runs(&shaped, 4, false);
+        assert_eq!(
+            runs,
+            vec![
+                FontRun {
+                    font_idx: 0,
+                    scale: 1.0,
+                    cells: 0..2,
+                },
+                FontRun {
+                    font_idx: 1,
+                    scale: 1.0,
+                    cells: 2..4,
+                },
+            ]
+        );
+
+        let primary = fake_font_metrics(1., -1.);
+        let fallback = fake_font_metrics(2., -3.);
+        let text = config::TextConfig::default();
+
+        let render_metrics = RenderMetrics::with_font_metrics(&primary);
+        let fallback_metrics =
+            render_metrics.with_run_font_metrics(&fallback, runs[1].scale, &text);
+
+        // The primary run derives the same metrics as the global value
+        let primary_metrics = render_metrics.with_run_font_metrics(&primary, runs[0].scale, &text);
+        assert_eq!(primary_metrics.descender_row, render_metrics.descender_row);
+        assert_eq!(
+            primary_metrics.underline_height,
+            render_metrics.underline_height
+        );
+
+        // while the fallback run sits lower and is thicker
+        assert_eq!(render_metrics.descender_row, 13);
+        assert_eq!(render_metrics.underline_height, 1);
+        assert_eq!(fallback_metrics.descender_row, 15);
+        assert_eq!(fallback_metrics.underline_height, 2);
+        assert_eq!(fallback_metrics.cell_size, render_metrics.cell_size);
+    }
+
+    #[test]
+    fn font_runs_rtl_are_logical() {
+        let shaped = vec![fake_shaped(1, 1), fake_shaped(0, 2)];
+        let runs = font_runs(&shaped, 3, true);
+        assert_eq!(runs[0].cells, 2..3);
+        assert_eq!(runs[1].cells, 0..2);
+    }
+
+    /// Shape `text` the way that the terminal does, including the
+    /// presentation of each cluster
+    fn shape_with_presentation(
+        render_metrics: &RenderMetrics,
+        glyph_cache: &mut GlyphCache,
+        style: &TextStyle,
+        font: &Rc<LoadedFont>,
+        text: &str,
+    ) -> Vec<ShapedInfo> {
+        let line = Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None);
+        let mut shaped = vec![];
+        for cluster in line.cluster(None) {
+            let presentation_width = PresentationWidth::with_cluster(&cluster);
+            let infos = font
+                .shape(
+                    &cluster.text,
+                    || {},
+                    |_| {},
+                    Some(cluster.presentation),
+                    Direction::LeftToRight,
+                    None,
+                    Some(&presentation_width),
+                )
+                .unwrap();
+            let glyphs = infos
+                .iter()
+                .map(|info| {
+                    let num_cells = cluster.byte_to_cell_width(info.cluster as usize);
+                    glyph_cache
+                        .cached_glyph(info, style, false, font, render_metrics, num_cells)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            shaped.append(&mut ShapedInfo::process(&infos, &glyphs));
+        }
+        shaped
+    }
+
+    #[test]
+    fn variation_selectors_choose_quad_mode() {
+        config::use_test_configuration();
+        let config = config::configuration();
+
+        let fonts = Rc::new(
+            FontConfiguration::new(
+                None,
+                config.font_config().dpi.unwrap_or_else(|| ::window::default_dpi()) as usize,
+            )
+            .unwrap(),
+        );
+        let render_metrics = RenderMetrics::new(&fonts).unwrap();
+        let mut glyph_cache = GlyphCache::new_in_memory(&fonts, 1024).unwrap();
+        let style = TextStyle::default();
+        let font = fonts.resolve_font(&style).unwrap();
+
+        // U+2602 UMBRELLA with VS15 requests text presentation; even if
+        // only the color emoji font has it, it must be drawn monochrome
+        let text = shape_with_presentation(
+            &render_metrics,
+            &mut glyph_cache,
+            &style,
+            &font,
+            "\u{2602}\u{FE0E}",
+        );
+        assert!(!text.is_empty());
+        for info in &text {
+            assert_eq!(info.presentation, Some(Presentation::Text));
+            assert!(matches!(info.quad_mode(), QuadMode::Glyph));
+        }
+
+        // and with VS16 requests emoji presentation
+        let emoji = shape_with_presentation(
+            &render_metrics,
+            &mut glyph_cache,
+            &style,
+            &font,
+            "\u{2602}\u{FE0F}",
+        );
+        assert!(emoji.iter().any(|info| info.glyph.has_color));
+        for info in &emoji {
+            assert_eq!(info.presentation, Some(Presentation::Emoji));
+            assert!(matches!(info.quad_mode(), QuadMode::ColorEmoji));
+        }
+    }
 }
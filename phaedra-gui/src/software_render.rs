@@ -0,0 +1,311 @@
+//! Rasterizes a frame on the CPU, for use where there is no GPU.
+//!
+//! The commands are executed into the same vertices that would be
+//! written into the GPU buffers, and each quad is then filled by
+//! evaluating the fragment shader of shader.wgsl at the center of each
+//! pixel that it covers, blending as the GPU pipeline does.  There is
+//! no post-processing, and the atlas is sampled at the nearest texel.
+//! Performance is not a goal: the result is meant to be correct
+//! rather than fast.
+use crate::execute_render::{apply_hsb_to_color, execute_commands_dry_run};
+use crate::quad::{Vertex, VertexMode, VERTICES_PER_CELL, V_BOT_LEFT, V_TOP_LEFT, V_TOP_RIGHT};
+use crate::render_command::{HsbTransform, RenderCommand};
+use crate::render_plan::RenderPlan;
+use ::window::color::LinearRgba;
+
+/// A texture atlas held in memory, in place of the GPU texture that
+/// the quads of a frame sample from
+#[derive(Debug, Default)]
+pub struct SoftwareAtlas {
+    width: usize,
+    height: usize,
+    pixels: Vec<LinearRgba>,
+}
+
+impl SoftwareAtlas {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn new(width: usize, height: usize, pixels: Vec<LinearRgba>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns the texel nearest to the texture coordinates, or
+    /// transparent if they are outside of the atlas
+    fn sample(&self, tex: [f32; 2]) -> LinearRgba {
+        let x = (tex[0] * self.width as f32).floor();
+        let y = (tex[1] * self.height as f32).floor();
+        if x < 0. || y < 0. || x >= self.width as f32 || y >= self.height as f32 {
+            return LinearRgba::TRANSPARENT;
+        }
+        self.pixels[y as usize * self.width + x as usize]
+    }
+}
+
+/// The linear colors of a rasterized frame, in row major order
+#[derive(Debug)]
+pub struct SoftwareFrame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<LinearRgba>,
+}
+
+impl SoftwareFrame {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn pixel(&self, x: usize, y: usize) -> LinearRgba {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns the frame as 8-bit SRGBA, as it would be presented
+    pub fn to_srgba8(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            let (r, g, b, a) = pixel.srgba_pixel().as_rgba();
+            data.extend_from_slice(&[r, g, b, a]);
+        }
+        data
+    }
+
+    /// Blends `color` over the pixel, as `wgpu::BlendState::ALPHA_BLENDING`
+    fn blend(&mut self, x: usize, y: usize, color: LinearRgba) {
+        let dest = &mut self.pixels[y * self.width + x];
+        let LinearRgba(r, g, b, a) = color;
+        let keep = 1. - a;
+        *dest = LinearRgba(
+            r * a + dest.0 * keep,
+            g * a + dest.1 * keep,
+            b * a + dest.2 * keep,
+            a + dest.3 * keep,
+        );
+    }
+}
+
+/// Rasterizes the commands of a frame whose top left is at 0,0.  The
+/// frame starts out with the color of the last whole-frame Clear in
+/// `commands`, or transparent if there is none.
+pub fn rasterize_commands(
+    commands: &[RenderCommand],
+    width: usize,
+    height: usize,
+    atlas: &SoftwareAtlas,
+) -> anyhow::Result<SoftwareFrame> {
+    let mut plan = RenderPlan::new(width as u32, height as u32);
+    plan.record_clear(commands);
+    let mut frame = SoftwareFrame {
+        width,
+        height,
+        pixels: vec![plan.clear_color.unwrap_or(LinearRgba::TRANSPARENT); width * height],
+    };
+
+    // The buffers are ordered by zindex and then sub-layer, which is
+    // the order in which they are drawn
+    for (_, vertices) in execute_commands_dry_run(commands, 0., 0.)? {
+        for quad in vertices.chunks_exact(VERTICES_PER_CELL) {
+            rasterize_quad(quad, atlas, &mut frame);
+        }
+    }
+    Ok(frame)
+}
+
+/// Fills the pixels whose centers are covered by the quad.  Every quad
+/// is a parallelogram, so a point maps to the quad's texture by its
+/// position along the top and left edges; the right and bottom edges
+/// are excluded, so that adjacent quads don't both fill the pixels on
+/// their shared edge.  The colors are the same at every vertex of a
+/// quad, so those of the top left vertex are used throughout.
+fn rasterize_quad(quad: &[Vertex], atlas: &SoftwareAtlas, frame: &mut SoftwareFrame) {
+    let origin = quad[V_TOP_LEFT];
+    let across = sub(quad[V_TOP_RIGHT].position, origin.position);
+    let down = sub(quad[V_BOT_LEFT].position, origin.position);
+    let det = across[0] * down[1] - across[1] * down[0];
+    if det == 0. {
+        return;
+    }
+
+    let xs = quad.iter().map(|v| v.position[0]);
+    let ys = quad.iter().map(|v| v.position[1]);
+    let min_x = xs.clone().fold(f32::INFINITY, f32::min).floor().max(0.) as usize;
+    let max_x = xs.fold(f32::NEG_INFINITY, f32::max).ceil().max(0.) as usize;
+    let min_y = ys.clone().fold(f32::INFINITY, f32::min).floor().max(0.) as usize;
+    let max_y = ys.fold(f32::NEG_INFINITY, f32::max).ceil().max(0.) as usize;
+
+    let tex_across = sub(quad[V_TOP_RIGHT].tex, origin.tex);
+    let tex_down = sub(quad[V_BOT_LEFT].tex, origin.tex);
+
+    for y in min_y..max_y.min(frame.height) {
+        for x in min_x..max_x.min(frame.width) {
+            let p = sub([x as f32 + 0.5, y as f32 + 0.5], origin.position);
+            let u = (p[0] * down[1] - p[1] * down[0]) / det;
+            let v = (across[0] * p[1] - across[1] * p[0]) / det;
+            if !(0. ..1.).contains(&u) || !(0. ..1.).contains(&v) {
+                continue;
+            }
+            let tex = [
+                origin.tex[0] + u * tex_across[0] + v * tex_down[0],
+                origin.tex[1] + u * tex_across[1] + v * tex_down[1],
+            ];
+            frame.blend(x, y, shade(&origin, tex, atlas));
+        }
+    }
+}
+
+/// `fs_main` from shader.wgsl, which must be kept in sync with it.
+/// `foreground_text_hsb` is not applied to glyphs, as it is a uniform
+/// rather than part of the frame.
+fn shade(vertex: &Vertex, tex: [f32; 2], atlas: &SoftwareAtlas) -> LinearRgba {
+    let fg: [f32; 4] = std::array::from_fn(|i| {
+        let t = vertex.mix_value;
+        vertex.fg_color[i] * (1. - t) + vertex.alt_color[i] * t
+    });
+    let fg = LinearRgba::from(fg);
+    let color = match vertex.mode() {
+        Some(VertexMode::SolidColor) | None => fg,
+        Some(VertexMode::BackgroundImage) => {
+            let color = atlas.sample(tex);
+            color.mul_alpha(fg.3)
+        }
+        Some(VertexMode::ColorEmoji) => atlas.sample(tex),
+        Some(VertexMode::GrayScale) => fg.mul_alpha(atlas.sample(tex).3),
+        Some(VertexMode::Glyph) => LinearRgba(fg.0, fg.1, fg.2, atlas.sample(tex).3),
+    };
+    let [hue, saturation, brightness] = vertex.hsv;
+    apply_hsb_to_color(
+        color,
+        &HsbTransform {
+            hue,
+            saturation,
+            brightness,
+        },
+    )
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render_command::{LayerId, QuadMode, TextureCoords};
+
+    fn assert_pixel(frame: &SoftwareFrame, x: usize, y: usize, expected: LinearRgba) {
+        let actual = frame.pixel(x, y);
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(
+            close(actual.0, expected.0)
+                && close(actual.1, expected.1)
+                && close(actual.2, expected.2)
+                && close(actual.3, expected.3),
+            "pixel {x},{y} is {actual:?}, expected {expected:?}"
+        );
+    }
+
+    fn fill(zindex: i8, rect: (f32, f32, f32, f32), color: LinearRgba) -> RenderCommand {
+        RenderCommand::FillRect {
+            layer: LayerId::background().with_zindex(zindex),
+            rect: euclid::rect(rect.0, rect.1, rect.2, rect.3),
+            color,
+            hsv: None,
+        }
+    }
+
+    #[test]
+    fn solid_rects() {
+        let black = LinearRgba(0., 0., 0., 1.);
+        let red = LinearRgba(1., 0., 0., 1.);
+        let blue = LinearRgba(0., 0., 1., 1.);
+        let commands = vec![
+            RenderCommand::Clear {
+                color: black,
+                layer: None,
+            },
+            // Drawn over the red rect, despite coming first, as its
+            // zindex is higher
+            fill(1, (4., 4., 4., 4.), blue),
+            fill(0, (2., 2., 4., 4.), red),
+        ];
+        let frame = rasterize_commands(&commands, 10, 10, &SoftwareAtlas::default()).unwrap();
+
+        assert_pixel(&frame, 0, 0, black);
+        assert_pixel(&frame, 2, 2, red);
+        assert_pixel(&frame, 3, 5, red);
+        assert_pixel(&frame, 4, 4, blue);
+        assert_pixel(&frame, 7, 7, blue);
+        // The right and bottom edges are exclusive
+        assert_pixel(&frame, 8, 8, black);
+        assert_pixel(&frame, 1, 3, black);
+        let is_red = |pixel: &&LinearRgba| pixel.0 > 0.5 && pixel.2 < 0.5;
+        assert_eq!(frame.pixels.iter().filter(is_red).count(), 16 - 4);
+    }
+
+    #[test]
+    fn translucent_rects_blend() {
+        let white = LinearRgba(1., 1., 1., 1.);
+        let commands = vec![
+            RenderCommand::Clear {
+                color: white,
+                layer: None,
+            },
+            fill(0, (0., 0., 2., 2.), LinearRgba(0., 0., 1., 0.25)),
+            // Outside of the frame, apart from one pixel
+            fill(0, (-5., -5., 6., 6.), LinearRgba(0., 0., 0., 0.5)),
+        ];
+        let frame = rasterize_commands(&commands, 4, 4, &SoftwareAtlas::default()).unwrap();
+
+        assert_pixel(&frame, 0, 0, LinearRgba(0.375, 0.375, 0.5, 1.));
+        assert_pixel(&frame, 1, 1, LinearRgba(0.75, 0.75, 1., 1.));
+        assert_pixel(&frame, 3, 3, white);
+    }
+
+    #[test]
+    fn glyphs_are_masked_by_the_atlas() {
+        // A 2x2 atlas whose left column is opaque
+        let opaque = LinearRgba(1., 1., 1., 1.);
+        let clear = LinearRgba::TRANSPARENT;
+        let atlas = SoftwareAtlas::new(2, 2, vec![opaque, clear, opaque, clear]);
+        let green = LinearRgba(0., 1., 0., 1.);
+        let commands = vec![RenderCommand::DrawQuad {
+            layer: LayerId::text(),
+            position: euclid::rect(0., 0., 4., 4.),
+            texture: TextureCoords {
+                left: 0.,
+                top: 0.,
+                right: 1.,
+                bottom: 1.,
+            },
+            fg_color: green,
+            alt_color: None,
+            hsv: None,
+            mode: QuadMode::Glyph,
+        }];
+        let frame = rasterize_commands(&commands, 4, 4, &atlas).unwrap();
+
+        for y in 0..4 {
+            assert_pixel(&frame, 0, y, green);
+            assert_pixel(&frame, 1, y, green);
+            assert_pixel(&frame, 2, y, LinearRgba::TRANSPARENT);
+            assert_pixel(&frame, 3, y, LinearRgba::TRANSPARENT);
+        }
+    }
+
+    #[test]
+    fn diagonal_line() {
+        let commands = vec![RenderCommand::DrawLine {
+            layer: LayerId::background(),
+            from: euclid::point2(0., 0.),
+            to: euclid::point2(8., 8.),
+            width: 1.,
+            color: LinearRgba(1., 1., 1., 1.),
+        }];
+        let frame = rasterize_commands(&commands, 8, 8, &SoftwareAtlas::default()).unwrap();
+
+        for i in 0..8 {
+            assert_pixel(&frame, i, i, LinearRgba(1., 1., 1., 1.));
+        }
+        assert_pixel(&frame, 7, 0, LinearRgba::TRANSPARENT);
+        assert_pixel(&frame, 0, 7, LinearRgba::TRANSPARENT);
+    }
+}
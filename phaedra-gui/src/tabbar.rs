@@ -1,15 +1,17 @@
 use crate::termwindow::{PaneInformation, TabInformation, UIItem, UIItemType};
+use config::keyassignment::KeyAssignment;
 use config::observers::*;
 use config::{ConfigHandle, TabBarColors};
 use finl_unicode::grapheme_clusters::Graphemes;
 use mlua::FromLua;
 use termwiz::cell::{unicode_column_width, Cell, CellAttributes};
-use termwiz::color::{AnsiColor, ColorSpec};
+use termwiz::color::{AnsiColor, ColorSpec, RgbColor, SrgbaTuple};
 use termwiz::escape::csi::Sgr;
 use termwiz::escape::parser::Parser;
 use termwiz::escape::{Action, ControlCode, CSI};
 use termwiz::surface::SEQ_ZERO;
 use termwiz_funcs::{format_as_escapes, FormatColor, FormatItem};
+use phaedra_dynamic::{FromDynamic, ToDynamic};
 use phaedra_term::{Line, Progress};
 use window::{IntegratedTitleButton, IntegratedTitleButtonAlignment, IntegratedTitleButtonStyle};
 
@@ -27,6 +29,43 @@ pub enum TabBarItem {
     Tab { tab_idx: usize, active: bool },
     NewTabButton,
     WindowButton(IntegratedTitleButton),
+    /// An index into `StatusSegments::get`
+    StatusSegment(usize),
+}
+
+/// A piece of the left or right status that is set by
+/// `window:set_status_segments` and can be clicked
+#[derive(Clone, Debug, Default, PartialEq, FromDynamic, ToDynamic)]
+pub struct StatusSegment {
+    /// The content, which may contain escape sequences that change
+    /// presentation, as with `window:set_right_status`
+    pub text: String,
+    /// Performed when the segment is clicked with the left button
+    #[dynamic(default)]
+    pub on_click: Option<KeyAssignment>,
+    /// When there isn't room for all of the segments on a side,
+    /// those with the lowest priority are dropped first
+    #[dynamic(default)]
+    pub priority: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, FromDynamic, ToDynamic)]
+pub struct StatusSegments {
+    /// Displayed after the left status
+    #[dynamic(default)]
+    pub left: Vec<StatusSegment>,
+    /// Displayed after the right status, at the right edge of the bar
+    #[dynamic(default)]
+    pub right: Vec<StatusSegment>,
+}
+luahelper::impl_lua_conversion_dynamic!(StatusSegments);
+
+impl StatusSegments {
+    /// Returns a segment by its index across the left segments
+    /// followed by the right segments
+    pub fn get(&self, index: usize) -> Option<&StatusSegment> {
+        self.left.iter().chain(self.right.iter()).nth(index)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -171,6 +210,18 @@ fn compute_tab_title(
                     title = format!("{}{classic_spacing}", title);
                 }
 
+                if tab.is_broadcasting {
+                    let badge = "\u{f1720} ".to_string();
+                    len += unicode_column_width(&badge, None);
+                    items.push(FormatItem::Text(badge));
+                }
+
+                if pane.is_alt_screen_active && !config.tab_bar().alt_screen_indicator.is_empty() {
+                    let indicator = format!("{} ", config.tab_bar().alt_screen_indicator);
+                    len += unicode_column_width(&indicator, None);
+                    items.push(FormatItem::Text(indicator));
+                }
+
                 match pane.progress {
                     Progress::None => {}
                     Progress::Percentage(pct) | Progress::Error(pct) => {
@@ -213,6 +264,114 @@ fn compute_tab_title(
     }
 }
 
+/// Chooses which of `widths` fit within `available` cells, returning
+/// a flag for each that is true if it should be displayed.  Each width
+/// is paired with its priority; while the total is too wide, the
+/// displayed segment with the lowest priority is dropped, choosing the
+/// last of several with the same priority.
+fn fit_status_segments(widths: &[(usize, i64)], available: usize) -> Vec<bool> {
+    let mut shown = vec![true; widths.len()];
+    let mut total: usize = widths.iter().map(|&(width, _)| width).sum();
+    while total > available {
+        let drop = widths
+            .iter()
+            .enumerate()
+            .filter(|&(idx, _)| shown[idx])
+            .min_by_key(|&(idx, &(_, priority))| (priority, std::cmp::Reverse(idx)))
+            .map(|(idx, _)| idx);
+        match drop {
+            Some(idx) => {
+                shown[idx] = false;
+                total -= widths[idx].0;
+            }
+            None => break,
+        }
+    }
+    shown
+}
+
+/// Parses the segments and fits them within `available` cells,
+/// returning the lines of those that are displayed together with their
+/// index, which starts at `first_index`
+fn layout_status_segments(
+    segments: &[StatusSegment],
+    first_index: usize,
+    available: usize,
+    default_cell: &CellAttributes,
+) -> Vec<(usize, Line)> {
+    let lines: Vec<Line> = segments
+        .iter()
+        .map(|segment| parse_status_text(&segment.text, default_cell.clone()))
+        .collect();
+    let widths: Vec<(usize, i64)> = lines
+        .iter()
+        .zip(segments.iter())
+        .map(|(line, segment)| (line.len(), segment.priority))
+        .collect();
+    let shown = fit_status_segments(&widths, available);
+    lines
+        .into_iter()
+        .enumerate()
+        .filter(|&(idx, _)| shown[idx])
+        .map(|(idx, line)| (first_index + idx, line))
+        .collect()
+}
+
+/// Appends the segment lines produced by `layout_status_segments`
+/// to the tab bar, starting at column `x`
+fn append_status_segments(
+    segments: Vec<(usize, Line)>,
+    x: &mut usize,
+    items: &mut Vec<TabEntry>,
+    line: &mut Line,
+) {
+    for (index, segment_line) in segments {
+        let width = segment_line.len();
+        items.push(TabEntry {
+            item: TabBarItem::StatusSegment(index),
+            title: segment_line.clone(),
+            x: *x,
+            width,
+        });
+        *x += width;
+        line.append_line(segment_line, SEQ_ZERO);
+    }
+}
+
+/// The least contrast ratio between the title of a tinted tab and the
+/// tint; a title color that contrasts less is replaced
+const TINTED_TAB_MIN_CONTRAST: f32 = 4.5;
+
+/// Returns the color of the title of a tab that is tinted with `tint`:
+/// `title` if it is readable against the tint, otherwise black or
+/// white, whichever contrasts more with it
+fn tinted_tab_title_color(title: SrgbaTuple, tint: SrgbaTuple) -> SrgbaTuple {
+    if title.contrast_ratio(&tint) >= TINTED_TAB_MIN_CONTRAST {
+        return title;
+    }
+    let black = SrgbaTuple(0., 0., 0., 1.);
+    let white = SrgbaTuple(1., 1., 1., 1.);
+    if black.contrast_ratio(&tint) >= white.contrast_ratio(&tint) {
+        black
+    } else {
+        white
+    }
+}
+
+/// Returns `attrs` with the background replaced by `tint` and the
+/// foreground adjusted to remain readable against it
+fn tinted_tab_attrs(
+    mut attrs: CellAttributes,
+    title: SrgbaTuple,
+    tint: RgbColor,
+) -> CellAttributes {
+    let tint: SrgbaTuple = tint.into();
+    attrs
+        .set_background(ColorSpec::TrueColor(tint))
+        .set_foreground(ColorSpec::TrueColor(tinted_tab_title_color(title, tint)));
+    attrs
+}
+
 fn is_tab_hover(mouse_x: Option<usize>, x: usize, tab_title_len: usize) -> bool {
     return mouse_x
         .map(|mouse_x| mouse_x >= x && mouse_x < x + tab_title_len)
@@ -340,6 +499,7 @@ impl TabBarState {
         config: &ConfigHandle,
         left_status: &str,
         right_status: &str,
+        status_segments: &StatusSegments,
     ) -> Self {
         let colors = colors.cloned().unwrap_or_else(TabBarColors::default);
 
@@ -453,6 +613,14 @@ impl TabBarState {
             line.append_line(left_status_line, SEQ_ZERO);
         }
 
+        let left_segments = layout_status_segments(
+            &status_segments.left,
+            0,
+            title_width.saturating_sub(x),
+            black_cell.attrs(),
+        );
+        append_status_segments(left_segments, &mut x, &mut items, &mut line);
+
         for (tab_idx, tab_title) in tab_titles.iter().enumerate() {
             let tab_title_len = tab_title.len.min(tab_width_max);
             let active = tab_idx == active_tab_no;
@@ -469,25 +637,29 @@ impl TabBarState {
                 tab_title_len,
             );
 
-            let cell_attrs = if active {
-                &active_cell_attrs
+            let (cell_attrs, tab_colors) = if active {
+                (&active_cell_attrs, colors.active_tab())
             } else if hover {
-                &inactive_hover_attrs
+                (&inactive_hover_attrs, colors.inactive_tab_hover())
+            } else {
+                (&inactive_cell_attrs, colors.inactive_tab())
+            };
+            let base_attrs = if config.tab_bar().use_fancy_tab_bar {
+                CellAttributes::default()
             } else {
-                &inactive_cell_attrs
+                cell_attrs.clone()
+            };
+            // The fancy tab bar takes the colors of the tab from its
+            // first cell, so the tint applies to both styles
+            let base_attrs = match tab_info[tab_idx].tab_color {
+                Some(tint) => tinted_tab_attrs(base_attrs, *tab_colors.fg_color, tint),
+                None => base_attrs,
             };
 
             let tab_start_idx = x;
 
             let esc = format_as_escapes(tab_title.items.clone()).expect("already parsed ok above");
-            let mut tab_line = parse_status_text(
-                &esc,
-                if config.tab_bar().use_fancy_tab_bar {
-                    CellAttributes::default()
-                } else {
-                    cell_attrs.clone()
-                },
-            );
+            let mut tab_line = parse_status_text(&esc, base_attrs);
 
             let title = tab_line.clone();
             if tab_line.len() > tab_width_max {
@@ -579,6 +751,17 @@ impl TabBarState {
 
         let status_space_available = title_width.saturating_sub(x);
 
+        // The right segments take priority over the right status, which
+        // is given whatever space they leave
+        let right_segments = layout_status_segments(
+            &status_segments.right,
+            status_segments.left.len(),
+            status_space_available,
+            black_cell.attrs(),
+        );
+        let right_segments_width: usize = right_segments.iter().map(|(_, l)| l.len()).sum();
+        let right_status_space = status_space_available - right_segments_width;
+
         let mut right_status_line = parse_status_text(right_status, black_cell.attrs().clone());
         items.push(TabEntry {
             item: TabBarItem::RightStatus,
@@ -587,11 +770,13 @@ impl TabBarState {
             width: status_space_available,
         });
 
-        while right_status_line.len() > status_space_available {
+        while right_status_line.len() > right_status_space {
             right_status_line.remove_cell(0, SEQ_ZERO);
         }
 
+        let mut segment_x = title_width - right_segments_width;
         line.append_line(right_status_line, SEQ_ZERO);
+        append_status_segments(right_segments, &mut segment_x, &mut items, &mut line);
         while line.len() < title_width {
             line.insert_cell(x, black_cell.clone(), title_width, SEQ_ZERO);
         }
@@ -611,12 +796,17 @@ impl TabBarState {
         let mut items = vec![];
 
         for entry in self.items.iter() {
+            let item_type = match entry.item {
+                TabBarItem::StatusSegment(index) => UIItemType::StatusSegment(index),
+                item => UIItemType::TabBar(item),
+            };
             items.push(UIItem {
                 x: entry.x * cell_width,
                 width: entry.width * cell_width,
                 y,
                 height: cell_height,
-                item_type: UIItemType::TabBar(entry.item),
+                item_type,
+                tooltip: None,
             });
         }
 
@@ -728,3 +918,109 @@ pub fn parse_status_text(text: &str, default_cell: CellAttributes) -> Line {
     flush_print(&mut print_buffer, &mut cells, &pen);
     Line::from_cells(cells, SEQ_ZERO)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segment(text: &str, priority: i64) -> StatusSegment {
+        StatusSegment {
+            text: text.to_string(),
+            on_click: None,
+            priority,
+        }
+    }
+
+    #[test]
+    fn tinted_tab_title_stays_readable() {
+        let light = SrgbaTuple(0.9, 0.9, 0.9, 1.);
+        let dark_tint = SrgbaTuple(0.1, 0.1, 0.3, 1.);
+        let light_tint = SrgbaTuple(1.0, 0.9, 0.5, 1.);
+
+        // A title that is readable against the tint is kept
+        assert_eq!(tinted_tab_title_color(light, dark_tint), light);
+
+        // Otherwise it is replaced by whichever of black and white
+        // contrasts more with the tint
+        assert_eq!(
+            tinted_tab_title_color(light, light_tint),
+            SrgbaTuple(0., 0., 0., 1.)
+        );
+        assert_eq!(
+            tinted_tab_title_color(SrgbaTuple(0.2, 0.2, 0.2, 1.), dark_tint),
+            SrgbaTuple(1., 1., 1., 1.)
+        );
+        assert!(
+            tinted_tab_title_color(light, light_tint).contrast_ratio(&light_tint)
+                >= TINTED_TAB_MIN_CONTRAST
+        );
+    }
+
+    #[test]
+    fn fit_segments() {
+        assert_eq!(fit_status_segments(&[(3, 0), (4, 0)], 7), vec![true, true]);
+        assert_eq!(
+            fit_status_segments(&[(3, 5), (4, 1), (2, 3)], 5),
+            vec![true, false, true]
+        );
+        assert_eq!(
+            fit_status_segments(&[(4, 2), (4, 1), (4, 0)], 4),
+            vec![true, false, false]
+        );
+        // Of several with the same priority, the last is dropped first
+        assert_eq!(
+            fit_status_segments(&[(2, 0), (2, 0), (2, 0)], 4),
+            vec![true, true, false]
+        );
+        assert_eq!(fit_status_segments(&[(3, 0)], 2), vec![false]);
+        assert_eq!(fit_status_segments(&[], 0), Vec::<bool>::new());
+    }
+
+    #[test]
+    fn segment_ui_items() {
+        let attrs = CellAttributes::default();
+        let segments = vec![segment("abc", 0), segment("de", 1), segment("fghij", 0)];
+
+        let mut line = parse_status_text("  ", attrs.clone());
+        let mut items = vec![];
+        let mut x = line.len();
+        let fitted = layout_status_segments(&segments, 1, 6, &attrs);
+        append_status_segments(fitted, &mut x, &mut items, &mut line);
+        assert_eq!(x, 7);
+        assert_eq!(line.len(), 7);
+
+        let state = TabBarState { line, items };
+        assert_eq!(
+            state.compute_ui_items(4, 20, 10),
+            vec![
+                UIItem {
+                    x: 20,
+                    y: 4,
+                    width: 30,
+                    height: 20,
+                    item_type: UIItemType::StatusSegment(1),
+                    tooltip: None,
+                },
+                UIItem {
+                    x: 50,
+                    y: 4,
+                    width: 20,
+                    height: 20,
+                    item_type: UIItemType::StatusSegment(2),
+                    tooltip: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn segment_index() {
+        let segments = StatusSegments {
+            left: vec![segment("left", 0)],
+            right: vec![segment("right", 0), segment("end", 0)],
+        };
+        assert_eq!(segments.get(0).map(|s| s.text.as_str()), Some("left"));
+        assert_eq!(segments.get(2).map(|s| s.text.as_str()), Some("end"));
+        assert_eq!(segments.get(3), None);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::color::LinearRgba;
 use config::observers::*;
 use crate::glyphcache::LoadState;
-use crate::render_command::{HsbTransform, QuadMode, RenderCommand, TextureCoords};
+use crate::render_command::{HsbTransform, LayerId, QuadMode, RenderCommand, TextureCoords};
 use crate::termwindow::RenderState;
 use crate::utilsprites::RenderMetrics;
 use crate::Dimensions;
@@ -394,6 +394,26 @@ pub fn reload_background_image(
     result
 }
 
+/// Computes how far a scroll-attached layer has moved when the viewport
+/// starts at stable row `top`.  Returns the number of pixels to shift the
+/// layer origin upwards, which is always within `0..repeat`, together with
+/// the index of the tile that is now at that origin.  The tile index is
+/// used to keep mirrored tiles alternating consistently as they wrap.
+pub(crate) fn scroll_tile_offset(
+    top: StableRowIndex,
+    cell_height: f32,
+    factor: f32,
+    repeat: f32,
+) -> (f32, isize) {
+    if repeat <= 0. {
+        return (0., 0);
+    }
+    let distance = top as f32 * cell_height * factor;
+    let start_tile = (distance / repeat).floor() as isize;
+    let adjust = distance.rem_euclid(repeat).floor();
+    (adjust, start_tile)
+}
+
 impl crate::TermWindow {
     pub fn describe_backgrounds(
         &self,
@@ -524,12 +544,19 @@ impl crate::TermWindow {
             .map(|size| size.evaluate_as_pixels(v_context))
             .unwrap_or(height);
 
+        // Scroll-attached layers are positioned from the viewport top
+        // alone; the background section is described every frame, so
+        // this never feeds into the pane describe cache keys.
         let mut start_tile = 0;
         if let Some(factor) = layer.def.attachment.scroll_factor() {
-            let distance = top as f32 * self.render_metrics.cell_size.height as f32 * factor;
-            let num_tiles = distance / repeat_y;
-            origin_y -= (num_tiles.fract() * repeat_y).floor();
-            start_tile = num_tiles.floor() as usize;
+            let (adjust, tile) = scroll_tile_offset(
+                top,
+                self.render_metrics.cell_size.height as f32,
+                factor,
+                repeat_y,
+            );
+            origin_y -= adjust;
+            start_tile = tile;
         }
 
         let limit_y = pixel_height;
@@ -564,13 +591,12 @@ impl crate::TermWindow {
                 if layer.def.repeat_x == BackgroundRepeat::Mirror && x_step % 2 == 1 {
                     std::mem::swap(&mut x1, &mut x2);
                 }
-                if layer.def.repeat_y == BackgroundRepeat::Mirror && y_step % 2 == 1 {
+                if layer.def.repeat_y == BackgroundRepeat::Mirror && y_step.rem_euclid(2) == 1 {
                     std::mem::swap(&mut y1, &mut y2);
                 }
 
                 commands.push(RenderCommand::DrawQuad {
-                    layer: 0,
-                    zindex: layer_index,
+                    layer: LayerId::background().with_zindex(layer_index),
                     position: euclid::rect(origin_x, origin_y, width, height),
                     texture: TextureCoords {
                         left: x1,
@@ -592,5 +618,47 @@ impl crate::TermWindow {
 
         Ok((commands, emitted))
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scroll_tile_offset;
+
+    #[test]
+    fn fixed_viewport_has_no_offset() {
+        assert_eq!(scroll_tile_offset(0, 20., 1.0, 500.), (0., 0));
+    }
+
+    #[test]
+    fn scroll_moves_with_content() {
+        // 10 rows of 20px scrolled at full speed moves 200px into a 500px image
+        assert_eq!(scroll_tile_offset(10, 20., 1.0, 500.), (200., 0));
+        // half speed only moves half as far
+        assert_eq!(scroll_tile_offset(10, 20., 0.5, 500.), (100., 0));
+    }
+
+    #[test]
+    fn scroll_wraps_around_tiles() {
+        // 1300px of scrolling through 500px tiles lands 300px into the third tile
+        assert_eq!(scroll_tile_offset(65, 20., 1.0, 500.), (300., 2));
+        // exactly on a tile boundary
+        assert_eq!(scroll_tile_offset(50, 20., 1.0, 500.), (0., 2));
+    }
+
+    #[test]
+    fn negative_speed_wraps_backwards() {
+        // -200px is 300px into the tile before the first one
+        assert_eq!(scroll_tile_offset(10, 20., -1.0, 500.), (300., -1));
+    }
+
+    #[test]
+    fn parallax_fraction() {
+        assert_eq!(scroll_tile_offset(1000, 20., 0.1, 500.), (0., 4));
+        assert_eq!(scroll_tile_offset(1010, 20., 0.1, 500.), (20., 4));
+    }
 
+    #[test]
+    fn degenerate_repeat() {
+        assert_eq!(scroll_tile_offset(10, 20., 1.0, 0.), (0., 0));
+    }
 }
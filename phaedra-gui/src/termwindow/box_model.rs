@@ -1,11 +1,16 @@
 #![allow(dead_code)]
 use crate::color::LinearRgba;
+use crate::colorease::Transition;
 use crate::customglyph::{BlockKey, Poly};
 use crate::glyphcache::CachedGlyph;
 use crate::quad::{QuadImpl, QuadTrait};
 use crate::render_command::{
-    HsbTransform, QuadMode, RectF as CmdRectF, RenderCommand, TextureCoords as CmdTextureCoords,
+    HsbTransform, LayerId, QuadMode, RectF as CmdRectF, RenderCommand,
+    TextureCoords as CmdTextureCoords,
 };
+use crate::shapecache::quad_mode_for_glyph;
+use crate::tabbar::TabBarItem;
+use crate::termwindow::render::corners::{TOP_LEFT_ROUNDED_CORNER, TOP_RIGHT_ROUNDED_CORNER};
 use crate::termwindow::{
     ColorEase, MouseCapture, RenderState, TermWindowNotif, UIItem, UIItemType,
 };
@@ -102,6 +107,24 @@ pub struct PixelCorners {
     pub bottom_right: PixelSizedPoly,
 }
 
+/// Returns the radius of the top corners of `corners` when both are
+/// the same size and rounded, so that they can be drawn as part of a
+/// `RenderCommand::FillRoundedRect` instead of from poly sprites
+fn rounded_top_radius(corners: &PixelCorners) -> Option<f32> {
+    let (left, right) = (&corners.top_left, &corners.top_right);
+    if left.poly == TOP_LEFT_ROUNDED_CORNER
+        && right.poly == TOP_RIGHT_ROUNDED_CORNER
+        && left.width > 0.
+        && left.width == left.height
+        && right.width == left.width
+        && right.height == left.height
+    {
+        Some(left.width)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Corners {
     pub top_left: SizedPoly,
@@ -461,6 +484,7 @@ impl ComputedElement {
                 width: self.bounds.width().max(0.) as usize,
                 height: self.bounds.height().max(0.) as usize,
                 item_type: item_type.clone(),
+                tooltip: None,
             });
         }
 
@@ -489,7 +513,8 @@ pub enum ComputedElementContent {
 #[derive(Debug, Clone)]
 pub enum ElementCell {
     Sprite(Sprite),
-    Glyph(Rc<CachedGlyph>),
+    /// A glyph, along with the presentation explicitly requested for it
+    Glyph(Rc<CachedGlyph>, Option<Presentation>),
 }
 
 #[derive(Debug)]
@@ -659,7 +684,7 @@ impl super::TermWindow {
                         pixel_width += glyph.x_advance.get() as f32;
                         x_pos += glyph.x_advance.get() as f32;
 
-                        computed_cells.push(ElementCell::Glyph(glyph));
+                        computed_cells.push(ElementCell::Glyph(glyph, info.presentation));
                     }
                 }
 
@@ -832,6 +857,7 @@ impl super::TermWindow {
         element: &ComputedElement,
         inherited_colors: Option<&ElementColors>,
     ) -> anyhow::Result<Vec<RenderCommand>> {
+        let fading;
         let colors = match &element.hover_colors {
             Some(hc) => {
                 let hovering =
@@ -846,10 +872,16 @@ impl super::TermWindow {
                         }
                         None => false,
                     } && matches!(self.current_mouse_capture, None | Some(MouseCapture::UI));
-                if hovering {
-                    hc
-                } else {
-                    &element.colors
+                let colors = if hovering { hc } else { &element.colors };
+                match self.tab_hover_bg(element, hc, hovering) {
+                    Some(bg) => {
+                        fading = ElementColors {
+                            bg: bg.into(),
+                            ..colors.clone()
+                        };
+                        &fading
+                    }
+                    None => colors,
                 }
             }
             None => &element.colors,
@@ -875,8 +907,7 @@ impl super::TermWindow {
 
                             let resolved = self.resolve_text(colors, inherited_colors);
                             commands.push(RenderCommand::DrawQuad {
-                                layer: 2,
-                                zindex: element.zindex,
+                                layer: LayerId::overlay().with_zindex(element.zindex),
                                 position: Self::command_rect(euclid::rect(pos_x, pos_y, width, height)),
                                 texture: Self::command_texture_coords(sprite.texture_coords()),
                                 fg_color: resolved.color,
@@ -886,7 +917,7 @@ impl super::TermWindow {
                             });
                             pos_x += width;
                         }
-                        ElementCell::Glyph(glyph) => {
+                        ElementCell::Glyph(glyph, presentation) => {
                             if let Some(texture) = glyph.texture.as_ref() {
                                 let pos_y = element.content_rect.min_y()
                                     - (glyph.y_offset + glyph.bearing_y).get() as f32
@@ -904,18 +935,13 @@ impl super::TermWindow {
                                 let resolved = self.resolve_text(colors, inherited_colors);
 
                                 commands.push(RenderCommand::DrawQuad {
-                                    layer: 1,
-                                    zindex: element.zindex,
+                                    layer: LayerId::text().with_zindex(element.zindex),
                                     position: Self::command_rect(euclid::rect(pos_x, pos_y, width, height)),
                                     texture: Self::command_texture_coords(texture.texture_coords()),
                                     fg_color: resolved.color,
                                     alt_color: Self::command_alt_color(&resolved),
                                     hsv: Self::no_hsv(),
-                                    mode: if glyph.has_color {
-                                        QuadMode::ColorEmoji
-                                    } else {
-                                        QuadMode::Glyph
-                                    },
+                                    mode: quad_mode_for_glyph(glyph, *presentation),
                                 });
                             }
                             pos_x += glyph.x_advance.get() as f32;
@@ -946,8 +972,7 @@ impl super::TermWindow {
                         )?;
                     let resolved = self.resolve_text(colors, inherited_colors);
                     commands.push(RenderCommand::DrawQuad {
-                        layer: 1,
-                        zindex: element.zindex,
+                        layer: LayerId::text().with_zindex(element.zindex),
                         position: Self::command_rect(euclid::rect(
                             element.content_rect.origin.x,
                             element.content_rect.origin.y,
@@ -967,6 +992,67 @@ impl super::TermWindow {
         Ok(commands)
     }
 
+    /// Eases the background of a tab in the fancy tab bar between its
+    /// own color and its `hover` color as the mouse enters and leaves
+    /// it, returning the background to draw while that is in progress
+    fn tab_hover_bg(
+        &self,
+        element: &ComputedElement,
+        hover: &ElementColors,
+        hovering: bool,
+    ) -> Option<LinearRgba> {
+        let item = match &element.item_type {
+            Some(UIItemType::TabBar(item @ TabBarItem::Tab { .. })) => *item,
+            _ => return None,
+        };
+        let (normal, hovered) = match (&element.colors.bg, &hover.bg) {
+            (InheritableColor::Color(normal), InheritableColor::Color(hovered)) => {
+                (*normal, *hovered)
+            }
+            _ => return None,
+        };
+
+        let mut fades = self.tab_hover_fades.borrow_mut();
+        let idx = match fades.iter().position(|(fading, _)| *fading == item) {
+            Some(idx) => idx,
+            None if hovering => {
+                fades.push((item, Transition::settled(0.)));
+                fades.len() - 1
+            }
+            // Neither hovered nor fading out
+            None => return None,
+        };
+
+        let tab_bar = self.config.tab_bar();
+        let fade = &mut fades[idx].1;
+        let duration_ms = if self.animations_enabled() {
+            tab_bar.hover_fade_duration_ms
+        } else {
+            0
+        };
+        fade.retarget(
+            if hovering { 1. } else { 0. },
+            duration_ms,
+            tab_bar.hover_fade_function,
+        );
+        let (fraction, next_due) = fade.value();
+        if next_due.is_none() {
+            if !hovering {
+                fades.remove(idx);
+            }
+            return None;
+        }
+        self.update_next_frame_time(next_due);
+
+        let mix = |a: f32, b: f32| a + (b - a) * fraction;
+        Some(LinearRgba(
+            mix(normal.0, hovered.0),
+            mix(normal.1, hovered.1),
+            mix(normal.2, hovered.2),
+            mix(normal.3, hovered.3),
+        ))
+    }
+
     fn resolve_text(
         &self,
         colors: &ElementColors,
@@ -983,7 +1069,7 @@ impl super::TermWindow {
                 alt_color,
                 ease,
                 one_shot,
-            } => {
+            } if self.animations_enabled() => {
                 if let Some((mix_value, next)) = ease.borrow_mut().intensity(*one_shot) {
                     self.update_next_frame_time(Some(next));
                     ResolvedColor {
@@ -995,6 +1081,7 @@ impl super::TermWindow {
                     (*color).into()
                 }
             }
+            InheritableColor::Animated { color, .. } => (*color).into(),
         }
     }
 
@@ -1014,7 +1101,7 @@ impl super::TermWindow {
                 alt_color,
                 ease,
                 one_shot,
-            } => {
+            } if self.animations_enabled() => {
                 if let Some((mix_value, next)) = ease.borrow_mut().intensity(*one_shot) {
                     self.update_next_frame_time(Some(next));
                     ResolvedColor {
@@ -1026,6 +1113,7 @@ impl super::TermWindow {
                     (*color).into()
                 }
             }
+            InheritableColor::Animated { color, .. } => (*color).into(),
         }
     }
 
@@ -1085,7 +1173,32 @@ impl super::TermWindow {
             bottom_right_width = c.bottom_right.width;
             bottom_right_height = c.bottom_right.height;
 
-            if top_left_width > 0. && top_left_height > 0. {
+            let vector_radius = if self.config.gpu().experimental_vector_chrome
+                && matches!(element.item_type, Some(UIItemType::TabBar(_)))
+            {
+                rounded_top_radius(c)
+            } else {
+                None
+            };
+
+            if let Some(radius) = vector_radius {
+                // The lower half of the rounded rect is covered by the
+                // fills below, leaving only its top corners visible
+                commands.push(RenderCommand::FillRoundedRect {
+                    layer: LayerId::background().with_zindex(element.zindex),
+                    rect: Self::command_rect(euclid::rect(
+                        element.border_rect.min_x(),
+                        element.border_rect.min_y(),
+                        element.border_rect.width(),
+                        2. * radius,
+                    )),
+                    radius,
+                    color: colors.border.top,
+                    hsv: Self::no_hsv(),
+                });
+            }
+
+            if vector_radius.is_none() && top_left_width > 0. && top_left_height > 0. {
                 let sprite = self
                     .render_state
                     .as_ref()
@@ -1101,8 +1214,7 @@ impl super::TermWindow {
                         &self.render_metrics,
                     )?;
                 commands.push(RenderCommand::DrawQuad {
-                    layer: 0,
-                    zindex: element.zindex,
+                    layer: LayerId::background().with_zindex(element.zindex),
                     position: Self::command_rect(euclid::rect(
                         element.border_rect.origin.x,
                         element.border_rect.origin.y,
@@ -1116,7 +1228,7 @@ impl super::TermWindow {
                     mode: QuadMode::GrayScale,
                 });
             }
-            if top_right_width > 0. && top_right_height > 0. {
+            if vector_radius.is_none() && top_right_width > 0. && top_right_height > 0. {
                 let sprite = self
                     .render_state
                     .as_ref()
@@ -1132,8 +1244,7 @@ impl super::TermWindow {
                         &self.render_metrics,
                     )?;
                 commands.push(RenderCommand::DrawQuad {
-                    layer: 0,
-                    zindex: element.zindex,
+                    layer: LayerId::background().with_zindex(element.zindex),
                     position: Self::command_rect(euclid::rect(
                         element.border_rect.max_x() - top_right_width,
                         element.border_rect.min_y(),
@@ -1163,8 +1274,7 @@ impl super::TermWindow {
                         &self.render_metrics,
                     )?;
                 commands.push(RenderCommand::DrawQuad {
-                    layer: 0,
-                    zindex: element.zindex,
+                    layer: LayerId::background().with_zindex(element.zindex),
                     position: Self::command_rect(euclid::rect(
                         element.border_rect.min_x(),
                         element.border_rect.max_y() - bottom_left_height,
@@ -1194,8 +1304,7 @@ impl super::TermWindow {
                         &self.render_metrics,
                     )?;
                 commands.push(RenderCommand::DrawQuad {
-                    layer: 0,
-                    zindex: element.zindex,
+                    layer: LayerId::background().with_zindex(element.zindex),
                     position: Self::command_rect(euclid::rect(
                         element.border_rect.max_x() - bottom_right_width,
                         element.border_rect.max_y() - bottom_right_height,
@@ -1211,8 +1320,7 @@ impl super::TermWindow {
             }
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x() + top_left_width,
                     element.border_rect.min_y(),
@@ -1224,8 +1332,7 @@ impl super::TermWindow {
             });
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x() + bottom_left_width,
                     element.border_rect.max_y() - bottom_left_height.max(bottom_right_height),
@@ -1237,8 +1344,7 @@ impl super::TermWindow {
             });
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x(),
                     element.border_rect.min_y() + top_left_height,
@@ -1250,8 +1356,7 @@ impl super::TermWindow {
             });
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.max_x() - top_right_width,
                     element.border_rect.min_y() + top_right_height,
@@ -1263,8 +1368,7 @@ impl super::TermWindow {
             });
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x() + top_left_width,
                     element.border_rect.min_y() + top_right_height.min(top_left_height),
@@ -1278,8 +1382,7 @@ impl super::TermWindow {
             });
         } else if colors.bg != InheritableColor::Color(LinearRgba::TRANSPARENT) {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(element.padding),
                 color: self.resolve_bg(colors, inherited_colors).color,
                 hsv: Self::no_hsv(),
@@ -1292,8 +1395,7 @@ impl super::TermWindow {
 
         if element.border.top > 0. && colors.border.top != LinearRgba::TRANSPARENT {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x() + top_left_width,
                     element.border_rect.min_y(),
@@ -1306,8 +1408,7 @@ impl super::TermWindow {
         }
         if element.border.bottom > 0. && colors.border.bottom != LinearRgba::TRANSPARENT {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x() + bottom_left_width,
                     element.border_rect.max_y() - element.border.bottom,
@@ -1320,8 +1421,7 @@ impl super::TermWindow {
         }
         if element.border.left > 0. && colors.border.left != LinearRgba::TRANSPARENT {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.min_x(),
                     element.border_rect.min_y() + top_left_height,
@@ -1334,8 +1434,7 @@ impl super::TermWindow {
         }
         if element.border.right > 0. && colors.border.right != LinearRgba::TRANSPARENT {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: element.zindex,
+                layer: LayerId::background().with_zindex(element.zindex),
                 rect: Self::command_rect(euclid::rect(
                     element.border_rect.max_x() - element.border.right,
                     element.border_rect.min_y() + top_right_height,
@@ -0,0 +1,452 @@
+//! Broadcasting input to several panes at once.  While a broadcast is
+//! on, the keys typed into its focused pane, the text and keys sent by
+//! `SendString` and `SendKey`, and pastes are also written to the other
+//! panes in its scope, which is handy for driving several similar
+//! sessions, such as ssh connections to a set of hosts, in lockstep.
+use crate::overlay::{confirm_pane_broadcast, start_overlay_pane};
+use crate::TermWindow;
+use config::keyassignment::{BroadcastScope, PaneBroadcastParticipation};
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::Mux;
+use phaedra_term::{KeyCode, KeyModifiers};
+use std::io::Write;
+use std::sync::Arc;
+use window::WindowOps;
+
+/// A pane of the window, as far as broadcasting is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BroadcastCandidate {
+    pub pane_id: PaneId,
+    pub tab_id: TabId,
+    pub participation: PaneBroadcastParticipation,
+}
+
+/// Returns the panes of `candidates` that are in `scope` for a
+/// broadcast that was turned on from the tab `tab_id`
+pub fn panes_in_scope(
+    scope: BroadcastScope,
+    tab_id: TabId,
+    candidates: &[BroadcastCandidate],
+) -> Vec<PaneId> {
+    candidates
+        .iter()
+        .filter(|c| match scope {
+            BroadcastScope::Tab => {
+                c.tab_id == tab_id && c.participation != PaneBroadcastParticipation::OptOut
+            }
+            BroadcastScope::Window => c.participation != PaneBroadcastParticipation::OptOut,
+            BroadcastScope::Selection => c.participation == PaneBroadcastParticipation::Selected,
+        })
+        .map(|c| c.pane_id)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broadcast {
+    pub scope: BroadcastScope,
+    /// The tab that the broadcast was turned on from
+    pub tab_id: TabId,
+    /// The tabs holding the panes that are broadcast between
+    pub tabs: Vec<TabId>,
+    /// The panes that are broadcast between
+    pub panes: Vec<PaneId>,
+}
+
+impl Broadcast {
+    fn new(scope: BroadcastScope, tab_id: TabId, candidates: &[BroadcastCandidate]) -> Self {
+        let panes = panes_in_scope(scope, tab_id, candidates);
+        let mut tabs: Vec<TabId> = candidates
+            .iter()
+            .filter(|c| panes.contains(&c.pane_id))
+            .map(|c| c.tab_id)
+            .collect();
+        tabs.sort_unstable();
+        tabs.dedup();
+        Self {
+            scope,
+            tab_id,
+            tabs,
+            panes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BroadcastState {
+    #[default]
+    Off,
+    On(Broadcast),
+    /// Panes were added to the scope of the broadcast, which is held
+    /// until the user confirms that they should receive input too
+    AwaitingConfirmation(Broadcast),
+}
+
+/// How `BroadcastState::reconcile` changed the broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeChange {
+    Unchanged,
+    Disabled,
+    NeedsConfirmation,
+}
+
+impl BroadcastState {
+    pub fn broadcast(&self) -> Option<&Broadcast> {
+        match self {
+            Self::Off => None,
+            Self::On(broadcast) | Self::AwaitingConfirmation(broadcast) => Some(broadcast),
+        }
+    }
+
+    /// Turns broadcasting to `scope` from the tab `tab_id` on or off.
+    /// A broadcast with another scope, or a tab broadcast from another
+    /// tab, is replaced rather than turned off.  Returns true if the
+    /// broadcast is now on.
+    pub fn toggle(
+        &mut self,
+        scope: BroadcastScope,
+        tab_id: TabId,
+        candidates: &[BroadcastCandidate],
+    ) -> bool {
+        let same = self.broadcast().map_or(false, |b| {
+            b.scope == scope && (scope != BroadcastScope::Tab || b.tab_id == tab_id)
+        });
+        if same {
+            *self = Self::Off;
+            return false;
+        }
+        *self = Self::On(Broadcast::new(scope, tab_id, candidates));
+        true
+    }
+
+    /// Brings the broadcast up to date with the panes of the window.
+    /// It is turned off when a tab that it spans has been closed or no
+    /// panes remain in its scope.  Panes that left the scope are simply
+    /// dropped, but panes that joined it hold the broadcast until the
+    /// user confirms them.
+    pub fn reconcile(&mut self, candidates: &[BroadcastCandidate]) -> ScopeChange {
+        let prior = match self.broadcast() {
+            Some(broadcast) => broadcast,
+            None => return ScopeChange::Unchanged,
+        };
+        let tab_closed = prior
+            .tabs
+            .iter()
+            .any(|tab_id| !candidates.iter().any(|c| c.tab_id == *tab_id));
+        let broadcast = Broadcast::new(prior.scope, prior.tab_id, candidates);
+        if tab_closed || broadcast.panes.is_empty() {
+            *self = Self::Off;
+            return ScopeChange::Disabled;
+        }
+        let added = broadcast
+            .panes
+            .iter()
+            .any(|pane_id| !prior.panes.contains(pane_id));
+        let held = matches!(self, Self::AwaitingConfirmation(_));
+        if held || added {
+            *self = Self::AwaitingConfirmation(broadcast);
+        } else {
+            *self = Self::On(broadcast);
+        }
+        if added && !held {
+            ScopeChange::NeedsConfirmation
+        } else {
+            ScopeChange::Unchanged
+        }
+    }
+
+    /// Resumes a held broadcast if `accepted`, otherwise turns it off
+    pub fn confirm(&mut self, accepted: bool) {
+        if let Self::AwaitingConfirmation(broadcast) = self {
+            *self = if accepted {
+                Self::On(broadcast.clone())
+            } else {
+                Self::Off
+            };
+        }
+    }
+
+    /// Returns the panes that input to `focused` is broadcast to
+    pub fn targets(&self, focused: PaneId) -> Vec<PaneId> {
+        match self {
+            Self::On(broadcast) if broadcast.panes.contains(&focused) => broadcast
+                .panes
+                .iter()
+                .copied()
+                .filter(|pane_id| *pane_id != focused)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns true if `pane_id` is part of a broadcast that is on
+    pub fn includes(&self, pane_id: PaneId) -> bool {
+        matches!(self, Self::On(broadcast) if broadcast.panes.contains(&pane_id))
+    }
+}
+
+/// Input that was written to the focused pane of a broadcast
+#[derive(Debug, Clone, Copy)]
+pub enum BroadcastInput<'a> {
+    Key(KeyCode, KeyModifiers),
+    Text(&'a str),
+    Paste(&'a str),
+}
+
+impl BroadcastInput<'_> {
+    /// Writes this input to `pane`.  A paste is framed according to
+    /// whether `pane` itself has enabled bracketed paste, rather than
+    /// the pane that it was pasted into.
+    fn write_to(&self, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
+        match self {
+            Self::Key(key, mods) => pane.key_down(*key, *mods),
+            Self::Text(text) => Ok(pane.writer().write_all(text.as_bytes())?),
+            Self::Paste(text) => pane.send_paste(text),
+        }
+    }
+}
+
+impl TermWindow {
+    /// Returns the panes of all of the tabs of this window
+    fn broadcast_candidates(&self) -> Vec<BroadcastCandidate> {
+        let mux = Mux::get();
+        let panes: Vec<(TabId, PaneId)> = match mux.get_window(self.mux_window_id) {
+            Some(window) => window
+                .iter()
+                .flat_map(|tab| {
+                    let tab_id = tab.tab_id();
+                    tab.iter_panes_ignoring_zoom()
+                        .into_iter()
+                        .map(move |pos| (tab_id, pos.pane.pane_id()))
+                })
+                .collect(),
+            None => return vec![],
+        };
+        panes
+            .into_iter()
+            .map(|(tab_id, pane_id)| BroadcastCandidate {
+                pane_id,
+                tab_id,
+                participation: self.pane_state(pane_id).broadcast,
+            })
+            .collect()
+    }
+
+    fn pane_broadcast_changed(&mut self) {
+        self.update_title_post_status();
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn toggle_pane_broadcast(&mut self, pane: &Arc<dyn Pane>, scope: BroadcastScope) {
+        let mux = Mux::get();
+        let tab_id = match mux.resolve_pane_id(pane.pane_id()) {
+            Some((_domain, _window, tab_id)) => tab_id,
+            None => match mux.get_active_tab_for_window(self.mux_window_id) {
+                Some(tab) => tab.tab_id(),
+                None => return,
+            },
+        };
+        let candidates = self.broadcast_candidates();
+        let on = self.pane_broadcast.toggle(scope, tab_id, &candidates);
+        log::debug!(
+            "pane broadcast to {scope:?} is now {}",
+            if on { "on" } else { "off" }
+        );
+        self.pane_broadcast_changed();
+    }
+
+    pub fn set_pane_broadcast_participation(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        participation: PaneBroadcastParticipation,
+    ) {
+        self.pane_state(pane.pane_id()).broadcast = participation;
+        self.reconcile_pane_broadcast();
+        self.pane_broadcast_changed();
+    }
+
+    /// Brings the broadcast up to date after the panes of the window
+    /// may have changed, asking the user to confirm any panes that
+    /// joined its scope
+    pub fn reconcile_pane_broadcast(&mut self) {
+        if self.pane_broadcast == BroadcastState::Off {
+            return;
+        }
+        let candidates = self.broadcast_candidates();
+        match self.pane_broadcast.reconcile(&candidates) {
+            ScopeChange::Unchanged => {}
+            ScopeChange::Disabled => self.pane_broadcast_changed(),
+            ScopeChange::NeedsConfirmation => {
+                self.show_pane_broadcast_confirmation();
+                self.pane_broadcast_changed();
+            }
+        }
+    }
+
+    fn show_pane_broadcast_confirmation(&mut self) {
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let num_panes = self
+            .pane_broadcast
+            .broadcast()
+            .map_or(0, |broadcast| broadcast.panes.len());
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
+            confirm_pane_broadcast(pane_id, term, num_panes, window)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Called with the answer to the question of whether the panes that
+    /// joined the scope of the broadcast should receive input too
+    pub fn pane_broadcast_confirmed(&mut self, accepted: bool) {
+        self.pane_broadcast.confirm(accepted);
+        self.pane_broadcast_changed();
+    }
+
+    pub fn is_pane_broadcasting(&self, pane_id: PaneId) -> bool {
+        self.pane_broadcast.includes(pane_id)
+    }
+
+    /// Writes `input`, which was just written to `pane`, to the other
+    /// panes of the broadcast.  This is the only place that broadcast
+    /// input is written, so that typed keys, `SendString`, `SendKey`
+    /// and pastes all reach the same panes.
+    pub fn broadcast_input(&mut self, pane: &Arc<dyn Pane>, input: BroadcastInput) {
+        if self.pane_broadcast == BroadcastState::Off {
+            return;
+        }
+        self.reconcile_pane_broadcast();
+        let mux = Mux::get();
+        for pane_id in self.pane_broadcast.targets(pane.pane_id()) {
+            if let Some(target) = mux.get_pane(pane_id) {
+                if let Err(err) = input.write_to(&target) {
+                    log::warn!("broadcasting input to pane {pane_id}: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use PaneBroadcastParticipation::*;
+
+    fn candidate(
+        pane_id: PaneId,
+        tab_id: TabId,
+        participation: PaneBroadcastParticipation,
+    ) -> BroadcastCandidate {
+        BroadcastCandidate {
+            pane_id,
+            tab_id,
+            participation,
+        }
+    }
+
+    fn window() -> Vec<BroadcastCandidate> {
+        vec![
+            candidate(1, 10, Default),
+            candidate(2, 10, OptOut),
+            candidate(3, 10, Selected),
+            candidate(4, 20, Selected),
+            candidate(5, 20, Default),
+        ]
+    }
+
+    #[test]
+    fn scope_resolution() {
+        let candidates = window();
+        assert_eq!(
+            panes_in_scope(BroadcastScope::Tab, 10, &candidates),
+            vec![1, 3]
+        );
+        assert_eq!(
+            panes_in_scope(BroadcastScope::Tab, 20, &candidates),
+            vec![4, 5]
+        );
+        assert_eq!(
+            panes_in_scope(BroadcastScope::Window, 10, &candidates),
+            vec![1, 3, 4, 5]
+        );
+        assert_eq!(
+            panes_in_scope(BroadcastScope::Selection, 10, &candidates),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn toggle_on_and_off() {
+        let candidates = window();
+        let mut state = BroadcastState::default();
+        assert!(state.toggle(BroadcastScope::Tab, 10, &candidates));
+        assert_eq!(state.targets(1), vec![3]);
+        // Opted out panes neither send nor receive
+        assert!(state.targets(2).is_empty());
+        assert!(state.includes(3) && !state.includes(2));
+
+        // Toggling from another tab moves the broadcast there
+        assert!(state.toggle(BroadcastScope::Tab, 20, &candidates));
+        assert_eq!(state.targets(5), vec![4]);
+        // As does toggling another scope
+        assert!(state.toggle(BroadcastScope::Window, 20, &candidates));
+        assert_eq!(state.targets(1), vec![3, 4, 5]);
+
+        assert!(!state.toggle(BroadcastScope::Window, 10, &candidates));
+        assert_eq!(state, BroadcastState::Off);
+        assert!(state.targets(1).is_empty());
+    }
+
+    #[test]
+    fn added_panes_need_confirmation() {
+        let mut candidates = window();
+        let mut state = BroadcastState::default();
+        state.toggle(BroadcastScope::Tab, 10, &candidates);
+
+        // Removing a pane shrinks the broadcast
+        candidates.retain(|c| c.pane_id != 3);
+        assert_eq!(state.reconcile(&candidates), ScopeChange::Unchanged);
+        assert_eq!(state.broadcast().unwrap().panes, vec![1]);
+
+        // Splitting the tab holds the broadcast until it is confirmed
+        candidates.push(candidate(6, 10, Default));
+        assert_eq!(state.reconcile(&candidates), ScopeChange::NeedsConfirmation);
+        assert!(state.targets(1).is_empty());
+        assert_eq!(state.reconcile(&candidates), ScopeChange::Unchanged);
+        assert!(matches!(state, BroadcastState::AwaitingConfirmation(_)));
+        state.confirm(true);
+        assert_eq!(state.targets(1), vec![6]);
+
+        candidates.push(candidate(7, 10, Default));
+        state.reconcile(&candidates);
+        state.confirm(false);
+        assert_eq!(state, BroadcastState::Off);
+    }
+
+    #[test]
+    fn closing_a_tab_disables() {
+        let mut candidates = window();
+        let mut state = BroadcastState::default();
+        state.toggle(BroadcastScope::Selection, 10, &candidates);
+        assert_eq!(state.targets(3), vec![4]);
+
+        candidates.retain(|c| c.tab_id != 20);
+        assert_eq!(state.reconcile(&candidates), ScopeChange::Disabled);
+        assert_eq!(state, BroadcastState::Off);
+        assert_eq!(state.reconcile(&candidates), ScopeChange::Unchanged);
+
+        // As does opting the last pane in scope out
+        let mut candidates = window();
+        state.toggle(BroadcastScope::Tab, 20, &candidates);
+        for c in candidates.iter_mut().filter(|c| c.tab_id == 20) {
+            c.participation = OptOut;
+        }
+        assert_eq!(state.reconcile(&candidates), ScopeChange::Disabled);
+    }
+}
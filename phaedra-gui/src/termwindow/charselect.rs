@@ -1,5 +1,6 @@
 use crate::overlay::selector::{matcher_pattern, matcher_score};
 use config::observers::*;
+use crate::render_command::LayerId;
 use crate::termwindow::box_model::*;
 use crate::termwindow::modal::Modal;
 use crate::termwindow::render::corners::{
@@ -540,7 +541,7 @@ impl CharSelector {
                 ),
                 metrics: &metrics,
                 gl_state: term_window.render_state.as_ref().unwrap(),
-                zindex: 100,
+                zindex: LayerId::modal().zindex(),
             },
             &element,
         )?;
@@ -1,11 +1,51 @@
+use crate::termwindow::broadcast::BroadcastInput;
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
+use base64::Engine;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
-use mux::pane::Pane;
+use config::observers::*;
+use mux::pane::{Pane, PaneId};
 use mux::Mux;
+use std::io::Write;
 use std::sync::Arc;
 use window::{Clipboard, WindowOps};
 
+/// The fixed parts of an OSC 52 sequence, other than the selection name:
+/// `ESC ] 5 2 ;` before it, `;` after it and `ESC \` at the end
+const OSC52_OVERHEAD: usize = 8;
+
+/// Encode `text` as OSC 52 sequences that set `selection`, which is `c`
+/// for the clipboard or `p` for the primary selection.  When
+/// `max_sequence_length` is set, the payload is split so that no sequence
+/// is longer than that; each sequence then carries a whole number of
+/// base64 quanta, so that they can be decoded independently.
+pub fn osc52_paste_sequences(
+    text: &str,
+    selection: &str,
+    max_sequence_length: Option<usize>,
+) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let chunk_bytes = match max_sequence_length {
+        Some(max) => {
+            // Every 3 bytes of the text become 4 base64 characters
+            let quanta = max.saturating_sub(OSC52_OVERHEAD + selection.len()) / 4;
+            quanta.max(1) * 3
+        }
+        None => bytes.len().max(1),
+    };
+
+    let mut result = vec![];
+    let mut chunks = bytes.chunks(chunk_bytes).peekable();
+    if chunks.peek().is_none() {
+        result.extend_from_slice(format!("\x1b]52;{selection};\x1b\\").as_bytes());
+    }
+    for chunk in chunks {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(chunk);
+        result.extend_from_slice(format!("\x1b]52;{selection};{encoded}\x1b\\").as_bytes());
+    }
+    result
+}
+
 impl TermWindow {
     pub fn copy_to_clipboard(&self, clipboard: ClipboardCopyDestination, text: String) {
         let clipboard = match clipboard {
@@ -39,17 +79,9 @@ impl TermWindow {
         promise::spawn::spawn(async move {
             if let Ok(clip) = future.await {
                 window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
-                    if let Some(pane) = myself
-                        .pane_state(pane_id)
-                        .overlay
-                        .as_ref()
-                        .map(|overlay| overlay.pane.clone())
-                        .or_else(|| {
-                            let mux = Mux::get();
-                            mux.get_pane(pane_id)
-                        })
-                    {
+                    if let Some(pane) = myself.paste_target(pane_id) {
                         pane.send_paste(&clip).ok();
+                        myself.broadcast_input(&pane, BroadcastInput::Paste(&clip));
                     }
                 })));
             }
@@ -57,4 +89,118 @@ impl TermWindow {
         .detach();
         self.maybe_scroll_to_bottom_for_input(&pane);
     }
+
+    /// Sends the content of `clipboard` to the pane as OSC 52 sequences,
+    /// so that a program on a remote host, such as tmux or vim, can place
+    /// it in its own clipboard
+    pub fn paste_as_osc52(&mut self, pane: &Arc<dyn Pane>, clipboard: ClipboardPasteSource) {
+        let pane_id = pane.pane_id();
+        let window = self.window.as_ref().unwrap().clone();
+        let (clipboard, selection) = match clipboard {
+            ClipboardPasteSource::Clipboard => (Clipboard::Clipboard, "c"),
+            ClipboardPasteSource::PrimarySelection => (Clipboard::PrimarySelection, "p"),
+        };
+        let future = window.get_clipboard(clipboard);
+        promise::spawn::spawn(async move {
+            if let Ok(clip) = future.await {
+                window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
+                    let features = myself.config.terminal_features();
+                    if clip.len() > features.osc52_paste_max_bytes {
+                        log::error!(
+                            "PasteAsOsc52: the clipboard holds {} bytes, which is more \
+                             than terminal_features.osc52_paste_max_bytes ({})",
+                            clip.len(),
+                            features.osc52_paste_max_bytes
+                        );
+                        return;
+                    }
+                    let sequences = osc52_paste_sequences(
+                        &clip,
+                        selection,
+                        features.osc52_paste_max_sequence_length,
+                    );
+                    if let Some(pane) = myself.paste_target(pane_id) {
+                        let mut writer = pane.writer();
+                        if let Err(err) = writer.write_all(&sequences).and_then(|_| writer.flush())
+                        {
+                            log::error!("PasteAsOsc52: {:#}", err);
+                        }
+                    }
+                })));
+            }
+        })
+        .detach();
+        self.maybe_scroll_to_bottom_for_input(&pane);
+    }
+
+    /// The pane that receives a paste into `pane_id`, which is its
+    /// overlay while one is active
+    fn paste_target(&self, pane_id: PaneId) -> Option<Arc<dyn Pane>> {
+        self.pane_state(pane_id)
+            .overlay
+            .as_ref()
+            .map(|overlay| overlay.pane.clone())
+            .or_else(|| {
+                let mux = Mux::get();
+                mux.get_pane(pane_id)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_payload_is_one_sequence() {
+        assert_eq!(
+            osc52_paste_sequences("hi", "c", None),
+            b"\x1b]52;c;aGk=\x1b\\".to_vec()
+        );
+        assert_eq!(
+            osc52_paste_sequences("a\nb\n", "p", Some(4096)),
+            b"\x1b]52;p;YQpiCg==\x1b\\".to_vec()
+        );
+        assert_eq!(
+            osc52_paste_sequences("", "c", None),
+            b"\x1b]52;c;\x1b\\".to_vec()
+        );
+    }
+
+    #[test]
+    fn large_payload_is_chunked() {
+        // 16 bytes leaves room for 7 base64 characters, which is one
+        // whole quantum of 3 input bytes
+        assert_eq!(
+            osc52_paste_sequences("a\nb\n", "c", Some(16)),
+            b"\x1b]52;c;YQpi\x1b\\\x1b]52;c;Cg==\x1b\\".to_vec()
+        );
+
+        let text = "line one\nline two\r\nline three\n";
+        let max = 24;
+        let encoded = osc52_paste_sequences(text, "c", Some(max));
+        let encoded = String::from_utf8(encoded).unwrap();
+        let sequences: Vec<&str> = encoded.split_inclusive("\x1b\\").collect();
+        assert_eq!(sequences.len(), 4);
+        let mut decoded = vec![];
+        for sequence in sequences {
+            assert!(sequence.len() <= max, "{sequence:?}");
+            let payload = sequence
+                .strip_prefix("\x1b]52;c;")
+                .and_then(|s| s.strip_suffix("\x1b\\"))
+                .unwrap();
+            decoded.extend(
+                base64::engine::general_purpose::STANDARD
+                    .decode(payload)
+                    .unwrap(),
+            );
+        }
+        assert_eq!(decoded, text.as_bytes());
+
+        // A limit too small for any payload still makes progress
+        assert_eq!(
+            osc52_paste_sequences("hi", "c", Some(1)),
+            b"\x1b]52;c;aGk=\x1b\\".to_vec()
+        );
+    }
 }
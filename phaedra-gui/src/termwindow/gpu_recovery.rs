@@ -0,0 +1,409 @@
+//! Recovery from a lost wgpu device, such as after a driver reset or
+//! a GPU hang.  The GPU state of the window is torn down and rebuilt
+//! from scratch, leaving the rest of the TermWindow (and the mux) as
+//! it was.  `GpuRecovery` decides when to make each attempt; it is
+//! kept separate from the GPU calls so that the policy can be tested.
+use crate::renderstate::{RenderContext, RenderState};
+use crate::termwindow::webgpu::{load_postprocess_shaders, WebGpuState};
+use ::window::WindowOps;
+use smol::Timer;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Returned when drawing finds that the device or surface has been
+/// lost and cannot be used again
+#[derive(Debug, Error)]
+#[error("the GPU device was lost")]
+pub struct GpuDeviceLost;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    /// How many times to try to initialize the configured adapter
+    pub max_attempts: u32,
+    /// The delay before the first attempt; it doubles with each
+    /// failed attempt, up to `max_backoff`
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// If the device is lost again within this long of recovering,
+    /// the earlier attempts still count towards `max_attempts`
+    pub stable_after: Duration,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(4),
+            stable_after: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The kind of adapter that an attempt should initialize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAttempt {
+    /// The adapter selected by the configuration
+    Configured,
+    /// The software adapter, which composites on the CPU; this is
+    /// tried once after the configured adapter has failed
+    /// `max_attempts` times
+    Fallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Healthy {
+        /// When the most recent recovery completed, and how many
+        /// attempts it took
+        recovered: Option<(Instant, u32)>,
+    },
+    Recovering {
+        failed_attempts: u32,
+        next_attempt: Instant,
+    },
+    /// Neither the configured nor the fallback adapter could be
+    /// initialized; no further attempts are made
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuRecovery {
+    policy: RecoveryPolicy,
+    state: State,
+}
+
+impl Default for GpuRecovery {
+    fn default() -> Self {
+        Self::new(RecoveryPolicy::default())
+    }
+}
+
+impl GpuRecovery {
+    pub fn new(policy: RecoveryPolicy) -> Self {
+        Self {
+            policy,
+            state: State::Healthy { recovered: None },
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        matches!(self.state, State::Healthy { .. })
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self.state, State::Failed)
+    }
+
+    /// Records that the device has been lost.  This has no effect if
+    /// a recovery is already in progress or has given up.
+    pub fn device_lost(&mut self, now: Instant) {
+        if let State::Healthy { recovered } = self.state {
+            let failed_attempts = match recovered {
+                Some((when, attempts)) if now.duration_since(when) < self.policy.stable_after => {
+                    attempts
+                }
+                _ => 0,
+            };
+            self.state = match self.attempt_for(failed_attempts) {
+                Some(_) => State::Recovering {
+                    failed_attempts,
+                    next_attempt: now + self.backoff(failed_attempts),
+                },
+                None => State::Failed,
+            };
+        }
+    }
+
+    /// When the next attempt should be made, if one is pending
+    pub fn next_attempt(&self) -> Option<Instant> {
+        match self.state {
+            State::Recovering { next_attempt, .. } => Some(next_attempt),
+            _ => None,
+        }
+    }
+
+    /// Returns the attempt to make now, if one is due
+    pub fn due_attempt(&self, now: Instant) -> Option<RecoveryAttempt> {
+        match self.state {
+            State::Recovering {
+                failed_attempts,
+                next_attempt,
+            } if now >= next_attempt => self.attempt_for(failed_attempts),
+            _ => None,
+        }
+    }
+
+    pub fn attempt_succeeded(&mut self, now: Instant) {
+        if let State::Recovering {
+            failed_attempts, ..
+        } = self.state
+        {
+            self.state = State::Healthy {
+                recovered: Some((now, failed_attempts + 1)),
+            };
+        }
+    }
+
+    pub fn attempt_failed(&mut self, now: Instant) {
+        if let State::Recovering {
+            failed_attempts, ..
+        } = self.state
+        {
+            let failed_attempts = failed_attempts + 1;
+            self.state = match self.attempt_for(failed_attempts) {
+                Some(_) => State::Recovering {
+                    failed_attempts,
+                    next_attempt: now + self.backoff(failed_attempts),
+                },
+                None => State::Failed,
+            };
+        }
+    }
+
+    fn attempt_for(&self, failed_attempts: u32) -> Option<RecoveryAttempt> {
+        if failed_attempts < self.policy.max_attempts {
+            Some(RecoveryAttempt::Configured)
+        } else if failed_attempts == self.policy.max_attempts {
+            Some(RecoveryAttempt::Fallback)
+        } else {
+            None
+        }
+    }
+
+    fn backoff(&self, failed_attempts: u32) -> Duration {
+        self.policy
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(failed_attempts))
+            .min(self.policy.max_backoff)
+    }
+}
+
+impl crate::TermWindow {
+    /// Called when drawing reports `GpuDeviceLost`
+    pub fn gpu_device_lost(&mut self) {
+        log::error!("the GPU device was lost; reinitializing the renderer");
+        crate::diagnostics::report_crash("the GPU device was lost");
+        self.gpu_recovery.device_lost(Instant::now());
+        self.schedule_gpu_recovery();
+    }
+
+    /// Makes the next recovery attempt, if it is due.  Returns true if
+    /// the window has a usable renderer, and can be painted.
+    pub fn poll_gpu_recovery(&mut self) -> bool {
+        let now = Instant::now();
+        let attempt = match self.gpu_recovery.due_attempt(now) {
+            Some(attempt) => attempt,
+            None => return self.gpu_recovery.is_healthy(),
+        };
+        match self.reinitialize_gpu(attempt) {
+            Ok(()) => {
+                log::info!(
+                    "renderer reinitialized using {}",
+                    self.webgpu
+                        .as_ref()
+                        .map(|webgpu| webgpu.adapter_info.name.clone())
+                        .unwrap_or_default()
+                );
+                self.gpu_recovery.attempt_succeeded(now);
+                true
+            }
+            Err(err) => {
+                log::error!(
+                    "failed to reinitialize the renderer ({:?}): {:#}",
+                    attempt,
+                    err
+                );
+                self.gpu_recovery.attempt_failed(now);
+                if self.gpu_recovery.is_failed() {
+                    phaedra_toast_notification::persistent_toast_notification(
+                        "Unable to render",
+                        "The GPU could not be reinitialized, so this window can no \
+                         longer be drawn.  Its panes are still running and can be \
+                         reached from a new window.",
+                    );
+                } else {
+                    self.schedule_gpu_recovery();
+                }
+                false
+            }
+        }
+    }
+
+    /// Repaints the window when the next recovery attempt is due
+    fn schedule_gpu_recovery(&self) {
+        let (Some(when), Some(window)) = (self.gpu_recovery.next_attempt(), self.window.clone())
+        else {
+            return;
+        };
+        promise::spawn::spawn(async move {
+            Timer::at(when).await;
+            window.invalidate();
+        })
+        .detach();
+    }
+
+    /// Tears down the GPU state and builds it again from a new
+    /// adapter, device and surface
+    fn reinitialize_gpu(&mut self, attempt: RecoveryAttempt) -> anyhow::Result<()> {
+        let window = self
+            .window
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("window has no native window"))?;
+        let atlas_size = self
+            .render_state
+            .as_ref()
+            .map(|render_state| render_state.glyph_cache.borrow().atlas.size());
+
+        // Everything that refers to the old device has to go before
+        // the surface can be created again for the same window
+        self.render_state = None;
+        self.webgpu = None;
+
+        let force_fallback_adapter = match attempt {
+            RecoveryAttempt::Configured => self.config.gpu().webgpu_force_fallback_adapter,
+            RecoveryAttempt::Fallback => true,
+        };
+        let webgpu = Rc::new(futures::executor::block_on(WebGpuState::new_with_adapter(
+            &window,
+            self.dimensions,
+            &self.config,
+            force_fallback_adapter,
+        ))?);
+        load_postprocess_shaders(&webgpu, &self.config);
+
+        let context = RenderContext::new(Rc::clone(&webgpu));
+        self.opengl_info.replace(context.renderer_info());
+        let render_state = RenderState::new(
+            context,
+            &self.fonts,
+            &self.render_metrics,
+            super::ATLAS_SIZE,
+        )?;
+        self.webgpu.replace(webgpu);
+        self.render_state.replace(render_state);
+
+        // Restore the atlas to the size the old one had grown to, and
+        // discard the shaped lines that refer to sprites in it
+        self.recreate_texture_atlas(atlas_size)?;
+        self.invalidate_fancy_tab_bar();
+        self.invalidate_modal();
+        self.prev_pane_frames.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy() -> RecoveryPolicy {
+        RecoveryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            stable_after: Duration::from_secs(10),
+        }
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn backs_off_then_falls_back_then_gives_up() {
+        let start = Instant::now();
+        let mut recovery = GpuRecovery::new(policy());
+        assert!(recovery.is_healthy());
+        assert_eq!(recovery.next_attempt(), None);
+
+        recovery.device_lost(start);
+        assert!(!recovery.is_healthy());
+        assert_eq!(recovery.next_attempt(), Some(start + ms(100)));
+        assert_eq!(recovery.due_attempt(start), None);
+
+        let mut now = start + ms(100);
+        let mut delays = vec![];
+        let mut attempts = vec![];
+        while let Some(attempt) = recovery.due_attempt(now) {
+            attempts.push(attempt);
+            recovery.attempt_failed(now);
+            match recovery.next_attempt() {
+                Some(next) => {
+                    delays.push(next - now);
+                    now = next;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(
+            attempts,
+            vec![
+                RecoveryAttempt::Configured,
+                RecoveryAttempt::Configured,
+                RecoveryAttempt::Configured,
+                RecoveryAttempt::Fallback,
+            ]
+        );
+        assert_eq!(delays, vec![ms(200), ms(300), ms(300)]);
+        assert!(recovery.is_failed());
+
+        // Having given up, further losses don't start again
+        recovery.device_lost(now);
+        assert!(recovery.is_failed());
+        assert_eq!(recovery.due_attempt(now + ms(1000)), None);
+    }
+
+    #[test]
+    fn success_restores_health() {
+        let start = Instant::now();
+        let mut recovery = GpuRecovery::new(policy());
+        recovery.device_lost(start);
+        assert_eq!(
+            recovery.due_attempt(start + ms(100)),
+            Some(RecoveryAttempt::Configured)
+        );
+        recovery.attempt_succeeded(start + ms(100));
+        assert!(recovery.is_healthy());
+        assert_eq!(recovery.due_attempt(start + ms(200)), None);
+
+        // A loss once the device has been stable starts afresh
+        let later = start + ms(100) + Duration::from_secs(10);
+        recovery.device_lost(later);
+        assert_eq!(recovery.next_attempt(), Some(later + ms(100)));
+    }
+
+    #[test]
+    fn repeated_losses_count_towards_the_limit() {
+        let start = Instant::now();
+        let mut recovery = GpuRecovery::new(policy());
+        let mut now = start;
+        for _ in 0..3 {
+            recovery.device_lost(now);
+            now = recovery.next_attempt().unwrap();
+            assert_eq!(recovery.due_attempt(now), Some(RecoveryAttempt::Configured));
+            recovery.attempt_succeeded(now);
+            now += ms(50);
+        }
+        // The device keeps being lost right after recovering, so the
+        // configured adapter isn't tried again
+        recovery.device_lost(now);
+        let next = recovery.next_attempt().unwrap();
+        assert_eq!(recovery.due_attempt(next), Some(RecoveryAttempt::Fallback));
+        recovery.attempt_succeeded(next);
+        recovery.device_lost(next + ms(50));
+        assert!(recovery.is_failed());
+    }
+
+    #[test]
+    fn losses_while_recovering_are_ignored() {
+        let start = Instant::now();
+        let mut recovery = GpuRecovery::new(policy());
+        recovery.device_lost(start);
+        recovery.attempt_failed(start + ms(100));
+        let next = recovery.next_attempt();
+        recovery.device_lost(start + ms(150));
+        assert_eq!(recovery.next_attempt(), next);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::termwindow::broadcast::BroadcastInput;
 use crate::termwindow::InputMap;
 use config::observers::*;
 use ::window::{
@@ -5,7 +6,9 @@ use ::window::{
 };
 use anyhow::Context;
 use config::keyassignment::{KeyAssignment, KeyTableEntry};
-use mux::pane::{Pane, PerformAssignmentResult};
+use config::KeyConditionSubject;
+use mux::pane::{CachePolicy, Pane, PerformAssignmentResult};
+use mux::Mux;
 use smol::Timer;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -34,6 +37,32 @@ pub struct KeyTableArgs<'a> {
     pub prevent_fallback: bool,
 }
 
+/// A point-in-time view of one activation on the key table stack,
+/// used to describe the stack to lua and to the tab bar.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct KeyTableStackEntry {
+    pub name: String,
+    pub one_shot: bool,
+    pub until_unknown: bool,
+    pub prevent_fallback: bool,
+    /// How long until this activation expires, if it has a timeout
+    pub remaining_milliseconds: Option<u64>,
+}
+
+/// Formats the stack as the short label shown in the tab bar when
+/// `show_active_key_table` is enabled, eg: ` resize_pane > copy_mode 3s `.
+/// The remaining time is rounded up to whole seconds.
+pub fn format_key_table_chip(stack: &[KeyTableStackEntry]) -> Option<String> {
+    let top = stack.last()?;
+    let names: Vec<&str> = stack.iter().map(|entry| entry.name.as_str()).collect();
+    let mut chip = format!(" {}", names.join(" > "));
+    if let Some(ms) = top.remaining_milliseconds {
+        chip.push_str(&format!(" {}s", ms.div_ceil(1000)));
+    }
+    chip.push(' ');
+    Some(chip)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct KeyTableState {
     stack: Vec<KeyTableStateEntry>,
@@ -96,11 +125,45 @@ impl KeyTableState {
         self.stack.last().map(|entry| entry.name.as_str())
     }
 
+    /// Returns the stack as it would be seen at `now`, ordered from the
+    /// bottom of the stack to the top.  Expired activations at the top of
+    /// the stack are omitted, mirroring what `process_expiration` would pop.
+    pub fn snapshot_at(&self, now: Instant) -> Vec<KeyTableStackEntry> {
+        let live = self
+            .stack
+            .iter()
+            .rposition(|entry| match entry.expiration {
+                Some(deadline) => now < deadline,
+                None => true,
+            })
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+
+        self.stack[..live]
+            .iter()
+            .map(|entry| KeyTableStackEntry {
+                name: entry.name.clone(),
+                one_shot: entry.one_shot,
+                until_unknown: entry.until_unknown,
+                prevent_fallback: entry.prevent_fallback,
+                remaining_milliseconds: entry
+                    .expiration
+                    .map(|deadline| deadline.saturating_duration_since(now).as_millis() as u64),
+            })
+            .collect()
+    }
+
+    /// The earliest expiration of any activation on the stack
+    pub fn next_expiration(&self) -> Option<Instant> {
+        self.stack.iter().filter_map(|entry| entry.expiration).min()
+    }
+
     fn lookup_key(
         &mut self,
         input_map: &InputMap,
         key: &KeyCode,
         mods: Modifiers,
+        pane: &dyn KeyConditionSubject,
         only_key_bindings: OnlyKeyBindings,
     ) -> Option<(KeyTableEntry, Option<String>)> {
         while self.process_expiration() {}
@@ -110,7 +173,7 @@ impl KeyTableState {
 
         for stack_entry in self.stack.iter_mut().rev() {
             let name = stack_entry.name.as_str();
-            if let Some(entry) = input_map.lookup_key(key, mods, Some(name)) {
+            if let Some(entry) = input_map.lookup_key(key, mods, Some(name), pane) {
                 if let Some(timeout) = stack_entry.timeout_milliseconds {
                     stack_entry
                         .expiration
@@ -130,9 +193,7 @@ impl KeyTableState {
                 // Prior to that, we mustn't prevent subsequent phases.
                 if only_key_bindings == OnlyKeyBindings::No {
                     result = Some((
-                        KeyTableEntry {
-                            action: KeyAssignment::Nop,
-                        },
+                        KeyTableEntry::new(KeyAssignment::Nop),
                         Some(name.to_string()),
                     ));
                 }
@@ -175,6 +236,24 @@ impl KeyTableState {
     }
 }
 
+/// Presents a pane to the conditions of key bindings
+struct PaneKeyConditionSubject<'a>(&'a dyn Pane);
+
+impl KeyConditionSubject for PaneKeyConditionSubject<'_> {
+    fn foreground_process_name(&self) -> Option<String> {
+        self.0.get_foreground_process_name(CachePolicy::AllowStale)
+    }
+
+    fn title(&self) -> String {
+        self.0.get_title()
+    }
+
+    fn domain_name(&self) -> Option<String> {
+        let domain = Mux::try_get()?.get_domain(self.0.domain_id())?;
+        Some(domain.domain_name().to_string())
+    }
+}
+
 #[derive(Debug)]
 pub enum Key {
     Code(::termwiz::input::KeyCode),
@@ -216,24 +295,29 @@ impl super::TermWindow {
         mods: Modifiers,
         only_key_bindings: OnlyKeyBindings,
     ) -> Option<(KeyTableEntry, Option<String>)> {
+        let subject = PaneKeyConditionSubject(&**pane);
         if let Some(overlay) = self.pane_state(pane.pane_id()).overlay.as_mut() {
             if let Some((entry, table_name)) = overlay.key_table_state.lookup_key(
                 &self.input_map,
                 keycode,
                 mods,
+                &subject,
                 only_key_bindings,
             ) {
                 return Some((entry, table_name.map(|s| s.to_string())));
             }
         }
-        if let Some((entry, table_name)) =
-            self.key_table_state
-                .lookup_key(&self.input_map, keycode, mods, only_key_bindings)
-        {
+        if let Some((entry, table_name)) = self.key_table_state.lookup_key(
+            &self.input_map,
+            keycode,
+            mods,
+            &subject,
+            only_key_bindings,
+        ) {
             return Some((entry, table_name.map(|s| s.to_string())));
         }
         self.input_map
-            .lookup_key(keycode, mods, None)
+            .lookup_key(keycode, mods, None, &subject)
             .map(|entry| (entry, None))
     }
 
@@ -403,6 +487,12 @@ impl super::TermWindow {
                     };
 
                     if did_encode {
+                        if is_down {
+                            self.broadcast_input(
+                                &pane,
+                                BroadcastInput::Key(term_key, tw_raw_modifiers),
+                            );
+                        }
                         if is_down
                             && !keycode.is_modifier()
                             && self.pane_state(pane.pane_id()).overlay.is_none()
@@ -557,6 +647,45 @@ impl super::TermWindow {
         }
     }
 
+    /// Returns the effective key table stack, bottom first.  The window
+    /// level stack comes first, followed by the stack belonging to the
+    /// overlay of the active pane, if any, as that is consulted first
+    /// when resolving keys.
+    /// If any activation has a timeout, the next frame is scheduled so
+    /// that anything displaying the remaining time stays current.
+    pub fn key_table_stack(&mut self) -> Vec<KeyTableStackEntry> {
+        let now = Instant::now();
+        while self.key_table_state.process_expiration() {}
+        let mut stack = self.key_table_state.snapshot_at(now);
+        let mut next_due = self.key_table_state.next_expiration();
+
+        if let Some(pane) = self.get_active_pane_or_overlay() {
+            if let Some(overlay) = self.pane_state(pane.pane_id()).overlay.as_mut() {
+                while overlay.key_table_state.process_expiration() {}
+                stack.extend(overlay.key_table_state.snapshot_at(now));
+                next_due = match (next_due, overlay.key_table_state.next_expiration()) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+
+        if let Some(deadline) = next_due {
+            // Tick once per second so that a countdown can be shown,
+            // and land exactly on the deadline for the final tick.
+            let remaining = deadline.saturating_duration_since(now);
+            let sub_second = Duration::from_nanos((remaining.as_nanos() % 1_000_000_000) as u64);
+            let tick = if sub_second.is_zero() {
+                Duration::from_secs(1)
+            } else {
+                sub_second
+            };
+            self.update_next_frame_time(Some((now + tick).min(deadline)));
+        }
+
+        stack
+    }
+
     pub fn current_key_table_name(&mut self) -> Option<String> {
         let mut name = None;
 
@@ -704,6 +833,9 @@ impl super::TermWindow {
                 };
 
                 if res.is_ok() {
+                    if window_key.key_is_down {
+                        self.broadcast_input(&pane, BroadcastInput::Key(key, modifiers));
+                    }
                     if window_key.key_is_down
                         && !key.is_modifier()
                         && self.pane_state(pane.pane_id()).overlay.is_none()
@@ -737,6 +869,7 @@ impl super::TermWindow {
                     log::info!("send to pane string={:?}", s);
                 }
                 pane.writer().write_all(s.as_bytes()).ok();
+                self.broadcast_input(&pane, BroadcastInput::Text(&s));
                 self.maybe_scroll_to_bottom_for_input(&pane);
                 context.invalidate();
             }
@@ -868,3 +1001,154 @@ impl super::TermWindow {
         Key::Code(code)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(name: &str) -> KeyTableArgs<'_> {
+        KeyTableArgs {
+            name,
+            timeout_milliseconds: None,
+            replace_current: false,
+            one_shot: false,
+            until_unknown: false,
+            prevent_fallback: false,
+        }
+    }
+
+    fn names(stack: &[KeyTableStackEntry]) -> Vec<&str> {
+        stack.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn snapshot_tracks_activate_and_pop() {
+        let mut state = KeyTableState::default();
+        assert!(state.snapshot_at(Instant::now()).is_empty());
+
+        state.activate(args("resize_pane"));
+        state.activate(KeyTableArgs {
+            one_shot: true,
+            prevent_fallback: true,
+            ..args("activate_pane")
+        });
+
+        let stack = state.snapshot_at(Instant::now());
+        assert_eq!(
+            stack,
+            vec![
+                KeyTableStackEntry {
+                    name: "resize_pane".to_string(),
+                    one_shot: false,
+                    until_unknown: false,
+                    prevent_fallback: false,
+                    remaining_milliseconds: None,
+                },
+                KeyTableStackEntry {
+                    name: "activate_pane".to_string(),
+                    one_shot: true,
+                    until_unknown: false,
+                    prevent_fallback: true,
+                    remaining_milliseconds: None,
+                },
+            ]
+        );
+
+        state.pop();
+        assert_eq!(
+            names(&state.snapshot_at(Instant::now())),
+            vec!["resize_pane"]
+        );
+
+        state.activate(KeyTableArgs {
+            replace_current: true,
+            until_unknown: true,
+            ..args("copy_mode")
+        });
+        let stack = state.snapshot_at(Instant::now());
+        assert_eq!(names(&stack), vec!["copy_mode"]);
+        assert!(stack[0].until_unknown);
+
+        state.clear_stack();
+        assert!(state.snapshot_at(Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn snapshot_reports_remaining_timeout() {
+        let mut state = KeyTableState::default();
+        state.activate(args("base"));
+        state.activate(KeyTableArgs {
+            timeout_milliseconds: Some(2000),
+            ..args("leader_like")
+        });
+        let activated = Instant::now();
+
+        let stack = state.snapshot_at(activated);
+        assert_eq!(names(&stack), vec!["base", "leader_like"]);
+        assert_eq!(stack[0].remaining_milliseconds, None);
+        let remaining = stack[1].remaining_milliseconds.unwrap();
+        assert!(remaining <= 2000 && remaining > 1500, "{remaining}");
+
+        let stack = state.snapshot_at(activated + Duration::from_millis(1500));
+        let remaining = stack[1].remaining_milliseconds.unwrap();
+        assert!(remaining <= 500, "{remaining}");
+        assert!(state.next_expiration().is_some());
+    }
+
+    #[test]
+    fn chip_shows_stack_and_countdown() {
+        assert_eq!(format_key_table_chip(&[]), None);
+
+        let entry = |name: &str, remaining_milliseconds| KeyTableStackEntry {
+            name: name.to_string(),
+            one_shot: false,
+            until_unknown: false,
+            prevent_fallback: false,
+            remaining_milliseconds,
+        };
+        assert_eq!(
+            format_key_table_chip(&[entry("resize_pane", None)]).as_deref(),
+            Some(" resize_pane ")
+        );
+        assert_eq!(
+            format_key_table_chip(&[entry("resize_pane", None), entry("copy_mode", Some(2001))])
+                .as_deref(),
+            Some(" resize_pane > copy_mode 3s ")
+        );
+        assert_eq!(
+            format_key_table_chip(&[entry("copy_mode", Some(0))]).as_deref(),
+            Some(" copy_mode 0s ")
+        );
+    }
+
+    #[test]
+    fn snapshot_omits_expired_activations() {
+        let mut state = KeyTableState::default();
+        state.activate(KeyTableArgs {
+            timeout_milliseconds: Some(60_000),
+            ..args("outer")
+        });
+        state.activate(args("middle"));
+        state.activate(KeyTableArgs {
+            timeout_milliseconds: Some(1000),
+            ..args("inner")
+        });
+
+        let later = Instant::now() + Duration::from_secs(5);
+        // Only the expired activation at the top is dropped
+        assert_eq!(names(&state.snapshot_at(later)), vec!["outer", "middle"]);
+
+        state.pop();
+        state.pop();
+        let much_later = Instant::now() + Duration::from_secs(120);
+        assert!(state.snapshot_at(much_later).is_empty());
+
+        state.activate(KeyTableArgs {
+            timeout_milliseconds: Some(0),
+            ..args("instant")
+        });
+        assert!(state.process_expiration());
+        assert!(state.snapshot_at(Instant::now()).is_empty());
+        assert_eq!(state.next_expiration(), None);
+    }
+}
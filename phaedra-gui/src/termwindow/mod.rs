@@ -2,11 +2,19 @@
 use super::renderstate::*;
 use config::observers::*;
 use super::utilsprites::RenderMetrics;
-use crate::colorease::ColorEase;
+use crate::cache_registry::{CacheRegistry, CacheStats};
+use crate::colorease::{ColorEase, Transition};
+use crate::execute::EffectOutcome;
 use crate::frame::PaneFrame;
+use crate::frame_budget::{FrameBudget, FrameTimings};
+use crate::frame_recording::FrameCommands;
 use crate::frontend::{front_end, try_front_end};
-use crate::inputmap::InputMap;
+use crate::input_effect::InputEffect;
+use crate::inputmap::InputMapCache;
 use crate::observers::{PaneLayoutObserver, TransientRenderObserver, WindowGeometryObserver};
+use crate::output_throttle::{OutputAction, OutputThrottle};
+use crate::render_command::{HsbTransform as CmdHsbTransform, RenderCommand};
+use crate::render_diagnostics::RenderDiagnostics;
 use crate::render_plan::RenderPlan;
 use crate::overlay::{
     confirm_close_pane, confirm_close_tab, confirm_close_window, launcher, start_overlay,
@@ -18,27 +26,33 @@ use crate::scripting::guiwin::GuiWin;
 use crate::scrollbar::*;
 use crate::selection::Selection;
 use crate::shapecache::*;
-use crate::tabbar::{TabBarItem, TabBarState};
+use crate::tabbar::{StatusSegments, TabBarItem, TabBarState};
 use crate::termwindow::background::{
     load_background_image, reload_background_image, LoadedBackgroundLayer,
 };
-use crate::termwindow::keyevent::{KeyTableArgs, KeyTableState};
+use crate::termwindow::broadcast::BroadcastState;
+use crate::termwindow::keyevent::{
+    format_key_table_chip, KeyTableArgs, KeyTableStackEntry, KeyTableState,
+};
 use crate::termwindow::modal::Modal;
+use crate::termwindow::pane_font::PaneFont;
+use crate::termwindow::render::describe::{DescribeFailures, FrozenPanes, PaintPanics};
 use crate::termwindow::render::paint::AllowImage;
 use crate::termwindow::render::{
     CachedLineState, LineCommandCacheValue, LineQuadCacheKey, LineToEleShapeCacheKey,
     LineToElementShapeItem,
 };
-use crate::termwindow::webgpu::WebGpuState;
+use crate::termwindow::webgpu::{load_postprocess_shaders, WebGpuState};
 use ::phaedra_term::input::{ClickPosition, MouseButton as TMB};
 use ::window::*;
 use anyhow::{anyhow, ensure, Context};
 use config::keyassignment::{
-    Confirmation, KeyAssignment, LauncherActionArgs, Pattern, PromptInputLine, SpawnCommand,
+    Confirmation, KeyAssignment, LauncherActionArgs, PaneBroadcastParticipation, Pattern,
+    PromptInputLine, SpawnCommand, TabIndex, WindowPaddingOverride,
 };
 use config::{
     configuration, AudibleBell, ConfigHandle, Dimension, DimensionContext, GeometryOrigin,
-    GuiPosition, TermConfig, WindowCloseConfirmation,
+    GuiPosition, TermConfig, WindowCloseConfirmation, WindowProperties,
 };
 use lfucache::*;
 use mlua::{FromLua, LuaSerdeExt, UserData, UserDataFields};
@@ -46,6 +60,7 @@ use mux::pane::{
     CachePolicy, CloseReason, Pane, PaneId, Pattern as MuxPattern, PerformAssignmentResult,
 };
 use mux::renderable::RenderableDimensions;
+use mux::search::CompiledPattern;
 use mux::tab::{
     PositionedPane, PositionedSplit, Tab, TabId,
 };
@@ -54,9 +69,8 @@ use mux::{Mux, MuxNotification};
 use mux_lua::MuxPane;
 use smol::channel::Sender;
 use smol::Timer;
-use std::cell::{Cell, RefCell, RefMut};
+use std::cell::{Cell, OnceCell, RefCell, RefMut};
 use std::collections::{HashMap, LinkedList};
-use std::ops::Add;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -65,24 +79,37 @@ use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::SequenceNo;
 use phaedra_dynamic::Value;
 use phaedra_font::FontConfiguration;
-use phaedra_term::color::ColorPalette;
+use phaedra_hash_cons::{InternTable, SliceInternTable};
+use phaedra_hit_index::{HitIndex, HitRect};
+use phaedra_term::color::{ColorPalette, RgbColor};
 use phaedra_term::input::LastMouseClick;
-use phaedra_term::{Alert, Progress, StableRowIndex, TerminalConfiguration, TerminalSize};
+use phaedra_term::{
+    prompt_scroll_target, Alert, CommandZone, Progress, StableRowIndex, TerminalConfiguration,
+    TerminalSize,
+};
 
 pub mod background;
 pub mod box_model;
+pub mod broadcast;
 pub mod charselect;
 pub mod clipboard;
+mod gpu_recovery;
 pub mod keyevent;
 pub mod modal;
 mod mouseevent;
+mod opacity;
+mod overscroll;
 pub mod palette;
+pub mod pane_font;
 pub mod paneselect;
 mod prevcursor;
 pub mod render;
 pub mod resize;
 mod selection;
 pub mod spawn;
+pub mod tabdrag;
+pub mod tooltip;
+mod urgency;
 pub mod webgpu;
 use crate::spawn::SpawnWhere;
 use prevcursor::PrevCursorPos;
@@ -108,6 +135,107 @@ pub fn get_window_class() -> String {
     WINDOW_CLASS.lock().unwrap().clone()
 }
 
+/// Returns the properties of the mux window that are matched
+/// against `window_overrides`
+fn window_properties(mux_window_id: MuxWindowId) -> WindowProperties {
+    let mut props = WindowProperties {
+        window_id: mux_window_id as u64,
+        window_class: get_window_class(),
+        ..Default::default()
+    };
+    if let Some(window) = Mux::get().get_window(mux_window_id) {
+        props.workspace = window.get_workspace().to_string();
+        props.title = window.get_title().to_string();
+    }
+    props
+}
+
+/// Returns the config for a window with the specified properties:
+/// the matching `window_overrides`, followed by `explicit`, are
+/// applied on top of the config file
+fn effective_window_config(props: &WindowProperties, explicit: &Value) -> ConfigHandle {
+    let overrides = config::window_overrides::resolve_window_overrides(
+        &configuration().window_config().window_overrides,
+        props,
+        explicit,
+    );
+    match config::overridden_config(&overrides) {
+        Ok(config) => config,
+        Err(err) => {
+            log::error!(
+                "Failed to apply config overrides to window: {:#}: {:?}",
+                err,
+                overrides
+            );
+            configuration()
+        }
+    }
+}
+
+/// Registers the render caches so that they can be inspected and kept
+/// within the configured budget.  The entry sizes are rough estimates
+/// that include the heap data typically owned by each entry.
+fn render_cache_registry() -> CacheRegistry<TermWindow> {
+    let mut registry: CacheRegistry<TermWindow> = CacheRegistry::new();
+    registry.register(
+        "shape_cache",
+        1024,
+        |tw| tw.shape_cache.borrow().len(),
+        |tw| tw.shape_cache.borrow().capacity(),
+        |tw, len| tw.shape_cache.borrow_mut().trim_to(len),
+    );
+    registry.register(
+        "line_to_ele_shape_cache",
+        2048,
+        |tw| tw.line_to_ele_shape_cache.borrow().len(),
+        |tw| tw.line_to_ele_shape_cache.borrow().capacity(),
+        |tw, len| tw.line_to_ele_shape_cache.borrow_mut().trim_to(len),
+    );
+    registry.register(
+        "line_state_cache",
+        512,
+        |tw| tw.line_state_cache.borrow().len(),
+        |tw| tw.line_state_cache.borrow().capacity(),
+        |tw, len| tw.line_state_cache.borrow_mut().trim_to(len),
+    );
+    registry.register(
+        "line_command_cache",
+        4096,
+        |tw| tw.line_command_cache.borrow().len(),
+        |tw| tw.line_command_cache.borrow().capacity(),
+        |tw, len| tw.line_command_cache.borrow_mut().trim_to(len),
+    );
+    registry.register(
+        "glyph_cache.image_cache",
+        256 * 1024,
+        |tw| {
+            tw.render_state
+                .as_ref()
+                .map_or(0, |rs| rs.glyph_cache.borrow().image_cache.len())
+        },
+        |tw| {
+            tw.render_state
+                .as_ref()
+                .map_or(0, |rs| rs.glyph_cache.borrow().image_cache.capacity())
+        },
+        |tw, len| {
+            if let Some(rs) = tw.render_state.as_ref() {
+                rs.glyph_cache.borrow_mut().image_cache.trim_to(len);
+            }
+        },
+    );
+    registry.register(
+        "palette_cache",
+        std::mem::size_of::<ColorPalette>(),
+        |_| ColorPalette::interned_count(),
+        |_| ColorPalette::interned_count(),
+        // Palettes are freed once no pane or window refers to them,
+        // so there is nothing to trim
+        |_, _| {},
+    );
+    registry
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MouseCapture {
     UI,
@@ -123,8 +251,14 @@ pub enum TermWindowNotif {
         assignment: KeyAssignment,
         tx: Option<Sender<anyhow::Result<()>>>,
     },
+    PerformEffects {
+        pane_id: PaneId,
+        effects: Vec<InputEffect>,
+        tx: Sender<EffectOutcome>,
+    },
     SetLeftStatus(String),
     SetRightStatus(String),
+    SetStatusSegments(StatusSegments),
     GetDimensions(Sender<(Dimensions, WindowState)>),
     GetSelectionForPane {
         pane_id: PaneId,
@@ -160,6 +294,8 @@ pub enum UIItemType {
     ScrollThumb,
     BelowScrollThumb,
     Split(PositionedSplit),
+    /// An index into `StatusSegments::get`
+    StatusSegment(usize),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -169,14 +305,80 @@ pub struct UIItem {
     pub width: usize,
     pub height: usize,
     pub item_type: UIItemType,
+    pub tooltip: Option<tooltip::TooltipSpec>,
+}
+
+impl UIItemType {
+    /// The region used to select regional mouse bindings for this item
+    pub fn mouse_region(&self) -> config::MouseRegion {
+        match self {
+            Self::TabBar(_) | Self::CloseTab(_) | Self::StatusSegment(_) => {
+                config::MouseRegion::TabBar
+            }
+            Self::AboveScrollThumb | Self::ScrollThumb | Self::BelowScrollThumb => {
+                config::MouseRegion::ScrollBar
+            }
+            Self::Split(_) => config::MouseRegion::SplitDivider,
+        }
+    }
 }
 
 impl UIItem {
     pub fn hit_test(&self, x: isize, y: isize) -> bool {
-        x >= self.x as isize
-            && x <= (self.x + self.width) as isize
-            && y >= self.y as isize
-            && y <= (self.y + self.height) as isize
+        self.hit_rect().contains(x, y)
+    }
+
+    pub fn hit_rect(&self) -> HitRect {
+        HitRect {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Returns this item moved by (dx, dy), which are whole pixels, as
+    /// the commands that draw it are by `RenderCommand::translate`
+    pub fn translated(self, dx: f32, dy: f32) -> Self {
+        Self {
+            x: self.x.saturating_add_signed(dx as isize),
+            y: self.y.saturating_add_signed(dy as isize),
+            ..self
+        }
+    }
+}
+
+/// The UI items of the most recently painted frame.  Hit testing goes
+/// through a spatial index that is built by the first hit test after
+/// the items are replaced, so that frames painted while the mouse is
+/// still don't pay for it.
+#[derive(Default)]
+pub struct UIItems {
+    items: Vec<UIItem>,
+    index: OnceCell<HitIndex>,
+}
+
+impl UIItems {
+    pub fn set(&mut self, items: Vec<UIItem>) {
+        self.items = items;
+        self.index = OnceCell::new();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(vec![]);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UIItem> {
+        self.items.iter()
+    }
+
+    /// Returns the item under the point.  Where items overlap, the
+    /// one that was added last is returned.
+    pub fn hit_test(&self, x: isize, y: isize) -> Option<&UIItem> {
+        let index = self
+            .index
+            .get_or_init(|| HitIndex::new(self.items.iter().map(UIItem::hit_rect).collect()));
+        index.hit_test(x, y).map(|idx| &self.items[idx])
     }
 }
 
@@ -184,6 +386,7 @@ impl UIItem {
 pub struct SemanticZoneCache {
     seqno: SequenceNo,
     zones: Vec<StableRowIndex>,
+    commands: Vec<CommandZone>,
 }
 
 pub struct OverlayState {
@@ -197,6 +400,9 @@ pub struct PaneState {
     /// Otherwise, the viewport is at the bottom of the
     /// scrollback.
     viewport: Option<StableRowIndex>,
+    /// How far the wheel has scrolled the viewport past the top of the
+    /// scrollback, when `scroll.overscroll` is enabled
+    overscroll: overscroll::Overscroll,
     selection: Selection,
     /// If is_some(), rather than display the actual tab
     /// contents, we're overlaying a little internal application
@@ -205,6 +411,26 @@ pub struct PaneState {
 
     bell_start: Option<Instant>,
     pub mouse_terminal_coords: Option<(ClickPosition, StableRowIndex)>,
+    /// If is_some(), the pane is rendered with a font this many
+    /// times the size of the window font
+    font_scale: Option<f64>,
+    /// Whether the pane takes part in broadcasts of input
+    broadcast: PaneBroadcastParticipation,
+    /// Whether the pane was using the alternate screen when its
+    /// output was last seen, so that the tab bar can be updated
+    /// when that changes
+    alt_screen_active: bool,
+    /// If is_some(), the matches of this pattern are highlighted
+    /// wherever they are visible in the pane
+    highlight_pattern: Option<Rc<PaneHighlightPattern>>,
+}
+
+/// A pattern set by `SetPaneHighlightPattern`, along with its
+/// compiled form
+#[derive(Debug)]
+pub struct PaneHighlightPattern {
+    pub pattern: MuxPattern,
+    pub compiled: CompiledPattern,
 }
 
 /// Data used when synchronously formatting pane and window titles
@@ -217,6 +443,10 @@ pub struct TabInformation {
     pub active_pane: Option<PaneInformation>,
     pub window_id: MuxWindowId,
     pub tab_title: String,
+    /// Whether input is being broadcast between panes of this tab
+    pub is_broadcasting: bool,
+    /// The color that the tab is tinted with, if any
+    pub tab_color: Option<RgbColor>,
 }
 
 impl UserData for TabInformation {
@@ -246,6 +476,10 @@ impl UserData for TabInformation {
         });
         fields.add_field_method_get("window_id", |_, this| Ok(this.window_id));
         fields.add_field_method_get("tab_title", |_, this| Ok(this.tab_title.clone()));
+        fields.add_field_method_get("is_broadcasting", |_, this| Ok(this.is_broadcasting));
+        fields.add_field_method_get("tab_color", |_, this| {
+            Ok(this.tab_color.map(|color| color.to_rgb_string()))
+        });
         fields.add_field_method_get("window_title", |_, this| {
             let mux = Mux::get();
             let window = mux.get_window(this.window_id).ok_or_else(|| {
@@ -264,6 +498,7 @@ pub struct PaneInformation {
     pub is_active: bool,
     pub is_zoomed: bool,
     pub has_unseen_output: bool,
+    pub is_alt_screen_active: bool,
     pub left: usize,
     pub top: usize,
     pub width: usize,
@@ -273,6 +508,9 @@ pub struct PaneInformation {
     pub title: String,
     pub user_vars: HashMap<String, String>,
     pub progress: Progress,
+    /// The key table stack in effect for this pane; only populated
+    /// for the active pane of the active tab
+    pub key_table_stack: Vec<KeyTableStackEntry>,
 }
 
 impl UserData for PaneInformation {
@@ -282,6 +520,9 @@ impl UserData for PaneInformation {
         fields.add_field_method_get("is_active", |_, this| Ok(this.is_active));
         fields.add_field_method_get("is_zoomed", |_, this| Ok(this.is_zoomed));
         fields.add_field_method_get("has_unseen_output", |_, this| Ok(this.has_unseen_output));
+        fields.add_field_method_get("is_alt_screen_active", |_, this| {
+            Ok(this.is_alt_screen_active)
+        });
         fields.add_field_method_get("left", |_, this| Ok(this.left));
         fields.add_field_method_get("top", |_, this| Ok(this.top));
         fields.add_field_method_get("width", |_, this| Ok(this.width));
@@ -289,6 +530,9 @@ impl UserData for PaneInformation {
         fields.add_field_method_get("pixel_width", |_, this| Ok(this.pixel_width));
         fields.add_field_method_get("pixel_height", |_, this| Ok(this.pixel_height));
         fields.add_field_method_get("progress", |lua, this| lua.to_value(&this.progress));
+        fields.add_field_method_get("key_table_stack", |lua, this| {
+            lua.to_value(&this.key_table_stack)
+        });
         fields.add_field_method_get("title", |_, this| Ok(this.title.clone()));
         fields.add_field_method_get("user_vars", |_, this| Ok(this.user_vars.clone()));
         fields.add_field_method_get("foreground_process_name", |_, this| {
@@ -367,9 +611,21 @@ pub struct TermWindow {
     pub window: Option<Window>,
     pub config: ConfigHandle,
     pub config_overrides: phaedra_dynamic::Value,
+    /// The properties of the window that were last matched
+    /// against `window_overrides`
+    window_properties: WindowProperties,
+    /// The sides of the padding that have been set by
+    /// `SetWindowPadding`, replacing the configured `window_padding`
+    window_padding_override: WindowPaddingOverride,
+    /// The opacity and blur of the window background that have been
+    /// set by AdjustWindowOpacity and SetWindowBackgroundBlur
+    window_background_override: opacity::WindowBackgroundOverride,
     os_parameters: Option<parameters::Parameters>,
     /// When we most recently received keyboard focus
     pub focused: Option<Instant>,
+    /// Whether a bell has set the urgency hint of the window, which is
+    /// cleared when the window is focused
+    urgency_hint: bool,
     fonts: Rc<FontConfiguration>,
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
@@ -386,18 +642,38 @@ pub struct TermWindow {
     pub render_plan: Option<RenderPlan>,
     prev_pane_frames: HashMap<PaneId, PaneFrame>,
     prev_pane_order: Vec<PaneId>,
-    input_map: InputMap,
+    /// The hsb transform of each pane as it eases between its active
+    /// and inactive appearance
+    pane_fades: RefCell<HashMap<PaneId, Transition<CmdHsbTransform>>>,
+    /// How far each tab in the fancy tab bar has eased towards its
+    /// hover colors
+    tab_hover_fades: RefCell<Vec<(TabBarItem, Transition<f32>)>>,
+    frozen_panes: FrozenPanes,
+    /// The panes that input is broadcast between
+    pane_broadcast: BroadcastState,
+    /// The pane describe errors that have already been logged
+    describe_failures: RefCell<DescribeFailures>,
+    /// The paints of this window that panicked
+    paint_panics: PaintPanics,
+    input_map: InputMapCache,
     /// If is_some, the LEADER modifier is active until the specified instant.
     leader_is_down: Option<std::time::Instant>,
     dead_key_status: DeadKeyStatus,
+    /// The rect most recently reported to the window for placing
+    /// the input method candidate window
+    text_cursor_rect: Option<Rect>,
     key_table_state: KeyTableState,
+    /// The key table chip most recently placed in the right status
+    key_table_chip: Option<String>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
     fancy_tab_bar: Option<box_model::ComputedElement>,
     pub right_status: String,
     pub left_status: String,
+    pub status_segments: StatusSegments,
     last_ui_item: Option<UIItem>,
+    tooltip: Option<tooltip::TooltipHover>,
     /// Tracks whether the current mouse-down event is part of click-focus.
     /// If so, we ignore mouse events until released
     is_click_to_focus_window: bool,
@@ -434,16 +710,31 @@ pub struct TermWindow {
     next_line_state_id: Cell<u64>,
 
     line_command_cache: RefCell<LfuCache<LineQuadCacheKey, LineCommandCacheValue>>,
+    /// Holds the distinct command slices referenced by line_command_cache,
+    /// so that equal lines share a single allocation
+    line_command_interner: RefCell<SliceInternTable<RenderCommand>>,
+    /// Holds the compositions referenced by line_command_cache
+    composing_interner: RefCell<InternTable<Composition>>,
+    /// The fonts for panes with their own font scale, keyed by the
+    /// bits of that scale
+    pane_fonts: RefCell<HashMap<u64, Rc<PaneFont>>>,
+
+    /// Keeps the caches above, and the glyph image cache, within
+    /// `cache.soft_budget_bytes`
+    cache_registry: CacheRegistry<TermWindow>,
 
     last_status_call: Instant,
     cursor_blink_state: RefCell<ColorEase>,
     blink_state: RefCell<ColorEase>,
     rapid_blink_state: RefCell<ColorEase>,
 
-    palette: Option<ColorPalette>,
+    palette: Option<Arc<ColorPalette>>,
 
-    ui_items: Vec<UIItem>,
+    ui_items: UIItems,
     dragging: Option<(UIItem, MouseEvent)>,
+    tab_drag: Option<tabdrag::TabDrag>,
+    /// The top left of the window on screen, as of the last mouse event
+    window_origin: Option<::window::ScreenPoint>,
     last_split_resize: Option<Instant>,
     pending_split_resize: Option<(usize, isize)>,
 
@@ -460,6 +751,17 @@ pub struct TermWindow {
     created: Instant,
 
     pub last_frame_duration: Duration,
+    /// Traces the render decisions of each frame when
+    /// `runtime.render_diagnostics` is enabled
+    render_diagnostics: RenderDiagnostics,
+    /// The time spent in each stage of the frame being painted
+    frame_timings: FrameTimings,
+    frame_budget: FrameBudget,
+    /// Decides when output from each pane repaints the window
+    output_throttle: OutputThrottle,
+    /// The commands of the frame being painted, while
+    /// `runtime.record_frames` is configured
+    recorded_frame: Option<FrameCommands>,
     last_fps_check_time: Instant,
     num_frames: usize,
     pub fps: f32,
@@ -467,6 +769,8 @@ pub struct TermWindow {
     connection_name: String,
 
     webgpu: Option<Rc<WebGpuState>>,
+    /// Decides when to reinitialize `webgpu` after the device is lost
+    gpu_recovery: gpu_recovery::GpuRecovery,
     config_subscription: Option<config::ConfigSubscription>,
 }
 
@@ -530,7 +834,6 @@ impl TermWindow {
     fn focus_changed(&mut self, focused: bool, window: &Window) {
         log::trace!("Setting focus to {:?}", focused);
         self.focused = if focused { Some(Instant::now()) } else { None };
-        self.quad_generation += 1;
         self.load_os_parameters();
 
         if self.focused.is_none() {
@@ -544,6 +847,11 @@ impl TermWindow {
             }
         }
 
+        if focused && self.urgency_hint {
+            self.urgency_hint = false;
+            window.set_urgency_hint(false);
+        }
+
         // Reset the cursor blink phase
         self.prev_cursor.bump();
 
@@ -588,7 +896,12 @@ impl TermWindow {
 
 impl TermWindow {
     pub async fn new_window(mux_window_id: MuxWindowId) -> anyhow::Result<()> {
-        let config = configuration();
+        let window_properties = window_properties(mux_window_id);
+        let config = if configuration().window_config().window_overrides.is_empty() {
+            configuration()
+        } else {
+            effective_window_config(&window_properties, &Value::Null)
+        };
         let dpi = config.font_config().dpi.unwrap_or_else(|| ::window::default_dpi()) as usize;
         let fontconfig = Rc::new(FontConfiguration::new(Some(config.clone()), dpi)?);
 
@@ -640,20 +953,24 @@ impl TermWindow {
             };
         }
 
+        // The size of the window isn't known yet, so percentages are
+        // taken of the terminal size; they are resolved against the
+        // window once it has been created and resized
+        let padding = &config.window_config().window_padding;
         let h_context = DimensionContext {
             dpi: dpi as f32,
             pixel_max: terminal_size.pixel_width as f32,
             pixel_cell: render_metrics.cell_size.width as f32,
         };
-        let padding_left = config.window_config().window_padding.left.evaluate_as_pixels(h_context) as usize;
-        let padding_right = resize::effective_right_padding(&config, h_context) as usize;
+        let padding_left = padding.left.evaluate_as_pixels(h_context) as usize;
+        let padding_right = resize::effective_right_padding(&config, padding, h_context) as usize;
         let v_context = DimensionContext {
             dpi: dpi as f32,
             pixel_max: terminal_size.pixel_height as f32,
             pixel_cell: render_metrics.cell_size.height as f32,
         };
-        let padding_top = config.window_config().window_padding.top.evaluate_as_pixels(v_context) as usize;
-        let padding_bottom = config.window_config().window_padding.bottom.evaluate_as_pixels(v_context) as usize;
+        let padding_top = padding.top.evaluate_as_pixels(v_context) as usize;
+        let padding_bottom = padding.bottom.evaluate_as_pixels(v_context) as usize;
 
         let mut dimensions = Dimensions {
             pixel_width: (terminal_size.pixel_width + padding_left + padding_right) as usize,
@@ -688,16 +1005,26 @@ impl TermWindow {
             last_fps_check_time: Instant::now(),
             num_frames: 0,
             last_frame_duration: Duration::ZERO,
+            render_diagnostics: RenderDiagnostics::default(),
+            frame_timings: FrameTimings::default(),
+            frame_budget: FrameBudget::default(),
+            output_throttle: OutputThrottle::default(),
+            recorded_frame: None,
             fps: 0.,
             config_subscription: None,
             os_parameters: None,
             webgpu: None,
+            gpu_recovery: gpu_recovery::GpuRecovery::default(),
             window: None,
             window_background,
             config: config.clone(),
             config_overrides: phaedra_dynamic::Value::default(),
+            window_properties,
+            window_padding_override: WindowPaddingOverride::default(),
+            window_background_override: opacity::WindowBackgroundOverride::default(),
             palette: None,
             focused: None,
+            urgency_hint: false,
             mux_window_id,
             mux_window_id_for_subscriptions: Arc::new(Mutex::new(mux_window_id)),
             fonts: Rc::clone(&fontconfig),
@@ -712,15 +1039,23 @@ impl TermWindow {
             render_plan: None,
             prev_pane_frames: HashMap::new(),
             prev_pane_order: Vec::new(),
-            input_map: InputMap::new(&config),
+            pane_fades: RefCell::new(HashMap::new()),
+            tab_hover_fades: RefCell::new(vec![]),
+            frozen_panes: FrozenPanes::default(),
+            pane_broadcast: BroadcastState::default(),
+            describe_failures: RefCell::new(DescribeFailures::default()),
+            paint_panics: PaintPanics::default(),
+            input_map: InputMapCache::new(&config, keyboard_generation()),
             leader_is_down: None,
             dead_key_status: DeadKeyStatus::None,
+            text_cursor_rect: None,
             show_tab_bar,
             show_scroll_bar: config.scroll().enable_scroll_bar,
             tab_bar: TabBarState::default(),
             fancy_tab_bar: None,
             right_status: String::new(),
             left_status: String::new(),
+            status_segments: StatusSegments::default(),
             last_mouse_coords: (0, -1),
             window_drag_position: None,
             current_mouse_event: None,
@@ -754,12 +1089,16 @@ impl TermWindow {
                 |config| config.cache().line_quad_cache_size,
                 &config,
             )),
+            line_command_interner: RefCell::new(SliceInternTable::default()),
+            composing_interner: RefCell::new(InternTable::default()),
+            pane_fonts: RefCell::new(HashMap::new()),
             line_to_ele_shape_cache: RefCell::new(LfuCache::new(
                 "line_to_ele_shape_cache.hit.rate",
                 "line_to_ele_shape_cache.miss.rate",
                 |config| config.cache().line_to_ele_shape_cache_size,
                 &config,
             )),
+            cache_registry: render_cache_registry(),
             last_status_call: Instant::now(),
             cursor_blink_state: RefCell::new(ColorEase::new(
                 config.cursor().cursor_blink_rate,
@@ -788,13 +1127,17 @@ impl TermWindow {
             scheduled_animation: RefCell::new(None),
             allow_images: AllowImage::Yes,
             semantic_zones: HashMap::new(),
-            ui_items: vec![],
+            ui_items: UIItems::default(),
             dragging: None,
+            tab_drag: None,
+            window_origin: None,
             last_split_resize: None,
             pending_split_resize: None,
             last_ui_item: None,
+            tooltip: None,
             is_click_to_focus_window: false,
             key_table_state: KeyTableState::default(),
+            key_table_chip: None,
             modal: RefCell::new(None),
             opengl_info: None,
         };
@@ -873,22 +1216,7 @@ impl TermWindow {
                 );
             }
 
-            if let Some(shader_path) = &config.gpu().webgpu_shader {
-                match std::fs::read_to_string(shader_path) {
-                    Ok(shader_source) => {
-                        if let Err(e) = webgpu.load_postprocess_shader(&shader_source) {
-                            log::error!(
-                                "Failed to load WebGPU shader from {:?}: {}",
-                                shader_path,
-                                e
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Failed to read WebGPU shader file {:?}: {}", shader_path, e);
-                    }
-                }
-            }
+            load_postprocess_shaders(&webgpu, &config);
             myself.webgpu.replace(Rc::clone(&webgpu));
             myself.created(RenderContext::new(Rc::clone(&webgpu)))?;
             myself.load_os_parameters();
@@ -926,18 +1254,19 @@ impl TermWindow {
             }
             WindowEvent::AppearanceChanged(appearance) => {
                 log::debug!("Appearance is now {:?}", appearance);
-                // This is a bit fugly; we get per-window notifications
-                // for appearance changes which successfully updates the
-                // per-window config, but we need to explicitly tell the
-                // global config to reload, otherwise things that acces
-                // the config via config::configuration() will see the
-                // prior version of the config.
-                // What's fugly about this is that we'll reload the
-                // global config here once per window, which could
-                // be nasty for folks with a lot of windows.
+                // We get per-window notifications for appearance
+                // changes, but the appearance is recorded in the global
+                // config, and only the first window to be told about a
+                // change finds that it differs.  A color scheme that is
+                // chosen according to the appearance is swapped in
+                // without reloading the config; it only needs to be
+                // reloaded if it consulted `phaedra.gui.get_appearance()`.
+                // Either way, the windows are told that the config
+                // changed through their config subscription.
                 // <https://github.com/PaleRoses/phaedra/issues/2295>
-                config::reload();
-                self.config_was_reloaded();
+                if config::set_system_appearance(crate::frontend::system_appearance(appearance)) {
+                    config::reload();
+                }
                 Ok(true)
             }
             WindowEvent::PerformKeyAssignment(action) => {
@@ -976,6 +1305,12 @@ impl TermWindow {
                 self.apply_pending_scale_changes();
                 Ok(true)
             }
+            WindowEvent::KeyboardLayoutChanged => {
+                if self.input_map.refresh(&self.config, keyboard_generation()) {
+                    log::debug!("keyboard layout changed; key bindings were resolved again");
+                }
+                Ok(true)
+            }
             WindowEvent::AdviseModifiersLedStatus(modifiers, leds) => {
                 self.current_modifier_and_leds = (modifiers, leds);
                 self.update_title();
@@ -1064,6 +1399,11 @@ impl TermWindow {
     }
 
     fn do_paint_webgpu(&mut self) -> anyhow::Result<bool> {
+        if !self.gpu_recovery.is_healthy() && !self.poll_gpu_recovery() {
+            // There is nothing to draw with until the renderer has
+            // been reinitialized
+            return Ok(true);
+        }
         self.webgpu.as_mut().unwrap().resize(self.dimensions);
         match self.do_paint_webgpu_impl() {
             Ok(ok) => Ok(ok),
@@ -1102,23 +1442,8 @@ impl TermWindow {
                 assignment,
                 tx,
             } => {
-                let mux = Mux::get();
                 let result = || -> anyhow::Result<()> {
-                    // The CopyMode overlay doesn't exist in the mux, but aliases
-                    // itself with the overlaid pane's pane_id.
-                    // So we do a bit of fancy footwork here to resolve the overlay
-                    // and use that if it has the same pane_id, but otherwise fall
-                    // back to what we get from the mux.
-                    // <https://github.com/PaleRoses/phaedra/issues/3209>
-                    let active_pane = self
-                        .get_active_pane_or_overlay()
-                        .ok_or_else(|| anyhow!("there is no active pane!?"))?;
-                    let pane = if active_pane.pane_id() == pane_id {
-                        active_pane
-                    } else {
-                        mux.get_pane(pane_id)
-                            .ok_or_else(|| anyhow!("pane id {} is not valid", pane_id))?
-                    };
+                    let pane = self.resolve_notif_pane(pane_id)?;
                     self.perform_key_assignment(&pane, &assignment)
                         .context("perform_key_assignment")?;
                     Ok(())
@@ -1128,6 +1453,38 @@ impl TermWindow {
                     tx.try_send(result).ok();
                 }
             }
+            TermWindowNotif::PerformEffects {
+                pane_id,
+                effects,
+                tx,
+            } => {
+                let mut pending = vec![];
+                let result = self
+                    .resolve_notif_pane(pane_id)
+                    .map_err(|err| EffectOutcome::failure(None, &err))
+                    .and_then(|pane| self.execute_effects_reporting(effects, &pane, &mut pending));
+                window.invalidate();
+                match result {
+                    Err(outcome) => {
+                        tx.try_send(outcome).ok();
+                    }
+                    Ok(()) if pending.is_empty() => {
+                        tx.try_send(EffectOutcome::success()).ok();
+                    }
+                    Ok(()) => {
+                        promise::spawn::spawn(async move {
+                            for (name, future) in pending {
+                                if let Err(err) = future.await {
+                                    tx.try_send(EffectOutcome::failure(Some(name), &err)).ok();
+                                    return;
+                                }
+                            }
+                            tx.try_send(EffectOutcome::success()).ok();
+                        })
+                        .detach();
+                    }
+                }
+            }
             TermWindowNotif::SetRightStatus(status) => {
                 if status != self.right_status {
                     self.right_status = status;
@@ -1144,6 +1501,14 @@ impl TermWindow {
                     self.schedule_next_status_update();
                 }
             }
+            TermWindowNotif::SetStatusSegments(segments) => {
+                if segments != self.status_segments {
+                    self.status_segments = segments;
+                    self.update_title_post_status();
+                } else {
+                    self.schedule_next_status_update();
+                }
+            }
             TermWindowNotif::GetDimensions(tx) => {
                 tx.try_send((self.dimensions, self.window_state))
                     .map_err(chan_err)
@@ -1181,14 +1546,21 @@ impl TermWindow {
                 } => {
                     self.emit_user_var_event(pane_id, name, value);
                 }
-                MuxNotification::WindowTitleChanged { .. }
-                | MuxNotification::Alert {
+                MuxNotification::WindowTitleChanged { .. } => {
+                    self.update_title();
+                    self.window_properties_changed();
+                }
+                MuxNotification::WindowWorkspaceChanged(_) => {
+                    self.window_properties_changed();
+                }
+                MuxNotification::Alert {
                     alert:
                         Alert::OutputSinceFocusLost
                         | Alert::CurrentWorkingDirectoryChanged
                         | Alert::WindowTitleChanged(_)
                         | Alert::TabTitleChanged(_)
                         | Alert::IconTitleChanged(_)
+                        | Alert::TabColorChanged(_)
                         | Alert::Progress(_),
                     ..
                 } => {
@@ -1219,6 +1591,15 @@ impl TermWindow {
                         AudibleBell::Disabled => {}
                     }
 
+                    if urgency::bell_sets_urgency_hint(
+                        self.config.bell(),
+                        self.config.terminal_features().notification_handling,
+                        self.focused.is_some(),
+                    ) {
+                        self.urgency_hint = true;
+                        window.set_urgency_hint(true);
+                    }
+
                     log::trace!("Ding! (this is the bell) in pane {}", pane_id);
                     self.emit_window_event("bell", Some(pane_id));
 
@@ -1265,6 +1646,7 @@ impl TermWindow {
                     self.mux_pane_output_event(pane_id);
                 }
                 MuxNotification::WindowInvalidated(_) => {
+                    self.reconcile_pane_broadcast();
                     window.invalidate();
                     self.update_title_post_status();
                 }
@@ -1281,19 +1663,23 @@ impl TermWindow {
                     // Also handled by clientpane
                     self.update_title_post_status();
                 }
-                MuxNotification::TabResized(_) => {
+                MuxNotification::TabResized(tab_id) => {
                     // Also handled by phaedra-client
+                    self.apply_pane_font_sizes(tab_id);
+                    self.reconcile_pane_broadcast();
                     self.quad_generation += 1;
                     self.line_command_cache.borrow_mut().clear();
+                    self.line_command_interner.borrow_mut().purge_unused();
                     self.update_title_post_status();
                 }
                 MuxNotification::TabTitleChanged { .. } => {
                     self.update_title_post_status();
                 }
+                MuxNotification::PaneRemoved(pane_id) => {
+                    self.output_throttle.forget(pane_id);
+                }
                 MuxNotification::PaneAdded(_)
                 | MuxNotification::WorkspaceRenamed { .. }
-                | MuxNotification::PaneRemoved(_)
-                | MuxNotification::WindowWorkspaceChanged(_)
                 | MuxNotification::ActiveWorkspaceChanged(_)
                 | MuxNotification::Empty
                 | MuxNotification::WindowCreated(_) => {}
@@ -1330,6 +1716,7 @@ impl TermWindow {
                     }
                 };
                 self.update_title();
+                self.window_properties_changed();
                 window.invalidate();
             }
             TermWindowNotif::SetInnerSize { width, height } => {
@@ -1416,8 +1803,50 @@ impl TermWindow {
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate").record(1.);
         if self.is_pane_visible(pane_id) {
-            if let Some(ref win) = self.window {
-                win.invalidate();
+            self.invalidate_for_pane_output(pane_id);
+        }
+        if let Some(pane) = Mux::get().get_pane(pane_id) {
+            let alt_screen_active = pane.is_alt_screen_active();
+            let mut state = self.pane_state(pane_id);
+            if state.alt_screen_active != alt_screen_active {
+                state.alt_screen_active = alt_screen_active;
+                drop(state);
+                if self.window_contains_pane(pane_id) {
+                    self.update_title();
+                }
+            }
+        }
+    }
+
+    /// Invalidates the window for output from `pane_id`, as limited by
+    /// `runtime.output_coalesce_ms` and
+    /// `runtime.max_render_updates_per_second_per_pane`
+    fn invalidate_for_pane_output(&mut self, pane_id: PaneId) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        let runtime = self.config.runtime();
+        let now = Instant::now();
+        match self.output_throttle.on_output(
+            pane_id,
+            now,
+            Duration::from_millis(runtime.output_coalesce_ms),
+            runtime.max_render_updates_per_second_per_pane,
+        ) {
+            OutputAction::Invalidate => window.invalidate(),
+            OutputAction::Pending => {}
+            OutputAction::Defer(delay) => {
+                let target = now + delay;
+                promise::spawn::spawn(async move {
+                    Timer::at(target).await;
+                    let win = window.clone();
+                    window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                        if tw.output_throttle.deferred_due(pane_id, Instant::now()) {
+                            win.invalidate();
+                        }
+                    })));
+                })
+                .detach();
             }
         }
     }
@@ -1442,6 +1871,7 @@ impl TermWindow {
                     | Alert::WindowTitleChanged(_)
                     | Alert::TabTitleChanged(_)
                     | Alert::IconTitleChanged(_)
+                    | Alert::TabColorChanged(_)
                     | Alert::Progress(_)
                     | Alert::SetUserVar { .. }
                     | Alert::Bell,
@@ -1475,6 +1905,7 @@ impl TermWindow {
             }
             MuxNotification::TabAddedToWindow { window_id, .. }
             | MuxNotification::WindowTitleChanged { window_id, .. }
+            | MuxNotification::WindowWorkspaceChanged(window_id)
             | MuxNotification::WindowInvalidated(window_id) => {
                 if window_id != mux_window_id {
                     return true;
@@ -1506,8 +1937,7 @@ impl TermWindow {
             | MuxNotification::WindowCreated(_)
             | MuxNotification::ActiveWorkspaceChanged(_)
             | MuxNotification::WorkspaceRenamed { .. }
-            | MuxNotification::Empty
-            | MuxNotification::WindowWorkspaceChanged(_) => return true,
+            | MuxNotification::Empty => return true,
             MuxNotification::Alert {
                 alert: Alert::PaletteChanged { .. },
                 ..
@@ -1660,26 +2090,41 @@ impl TermWindow {
 }
 
 impl TermWindow {
+    /// Re-resolve `window_overrides` if any of the properties that
+    /// they match against have changed
+    fn window_properties_changed(&mut self) {
+        if window_properties(self.mux_window_id) != self.window_properties
+            && !configuration().window_config().window_overrides.is_empty()
+        {
+            self.config_was_reloaded();
+        }
+    }
+
     pub fn config_was_reloaded(&mut self) {
         log::debug!(
             "config was reloaded, overrides: {:?}",
             self.config_overrides
         );
-        self.key_table_state.clear_stack();
         self.connection_name = Connection::get().unwrap().name();
-        let config = match config::overridden_config(&self.config_overrides) {
-            Ok(config) => config,
-            Err(err) => {
-                log::error!(
-                    "Failed to apply config overrides to window: {:#}: {:?}",
-                    err,
-                    self.config_overrides
-                );
-                configuration()
-            }
-        };
+        self.window_properties = window_properties(self.mux_window_id);
+        let config = effective_window_config(&self.window_properties, &self.config_overrides);
+        let changed = config.changed_sections(&self.config);
+        log::debug!(
+            "config sections changed: {:?}",
+            changed.iter().collect::<Vec<_>>()
+        );
+        self.apply_config_sections(config, changed);
+    }
+
+    /// Applies `config`, only recomputing the state that depends
+    /// on the sections in `changed`.
+    fn apply_config_sections(&mut self, config: ConfigHandle, changed: ConfigSectionSet) {
+        use config::ConfigSection as S;
+
         self.config = config.clone();
-        self.palette.take();
+        if changed.contains(S::ColorConfig) {
+            self.palette.take();
+        }
 
         let mux = Mux::get();
         let window = match mux.get_window(self.mux_window_id) {
@@ -1691,34 +2136,47 @@ impl TermWindow {
         } else {
             self.show_tab_bar = config.tab_bar().enable_tab_bar;
         }
-        *self.cursor_blink_state.borrow_mut() = ColorEase::new(
-            config.cursor().cursor_blink_rate,
-            config.cursor().cursor_blink_ease_in,
-            config.cursor().cursor_blink_rate,
-            config.cursor().cursor_blink_ease_out,
-            None,
-        );
-        *self.blink_state.borrow_mut() = ColorEase::new(
-            config.text().text_blink_rate,
-            config.text().text_blink_ease_in,
-            config.text().text_blink_rate,
-            config.text().text_blink_ease_out,
-            None,
-        );
-        *self.rapid_blink_state.borrow_mut() = ColorEase::new(
-            config.text().text_blink_rate_rapid,
-            config.text().text_blink_rapid_ease_in,
-            config.text().text_blink_rate_rapid,
-            config.text().text_blink_rapid_ease_out,
-            None,
-        );
+        if changed.contains(S::Cursor) {
+            *self.cursor_blink_state.borrow_mut() = ColorEase::new(
+                config.cursor().cursor_blink_rate,
+                config.cursor().cursor_blink_ease_in,
+                config.cursor().cursor_blink_rate,
+                config.cursor().cursor_blink_ease_out,
+                None,
+            );
+        }
+        if changed.contains(S::Text) {
+            *self.blink_state.borrow_mut() = ColorEase::new(
+                config.text().text_blink_rate,
+                config.text().text_blink_ease_in,
+                config.text().text_blink_rate,
+                config.text().text_blink_ease_out,
+                None,
+            );
+            *self.rapid_blink_state.borrow_mut() = ColorEase::new(
+                config.text().text_blink_rate_rapid,
+                config.text().text_blink_rapid_ease_in,
+                config.text().text_blink_rate_rapid,
+                config.text().text_blink_rapid_ease_out,
+                None,
+            );
+        }
 
         self.show_scroll_bar = config.scroll().enable_scroll_bar;
-        self.shape_generation += 1;
+
+        // Shaped lines bake in colors as well as glyphs, so they need
+        // a new generation when either changes, but the glyph shaping
+        // cache only depends on the font and text settings.
+        let fonts_changed = changed.any_of(&[S::FontConfig, S::Text, S::WindowConfig]);
+        if fonts_changed || changed.contains(S::ColorConfig) {
+            self.shape_generation += 1;
+        }
         {
             let mut shape_cache = self.shape_cache.borrow_mut();
             shape_cache.update_config(&config);
-            shape_cache.clear();
+            if fonts_changed {
+                shape_cache.clear();
+            }
         }
         self.line_state_cache.borrow_mut().update_config(&config);
         self.line_command_cache.borrow_mut().update_config(&config);
@@ -1728,14 +2186,21 @@ impl TermWindow {
         self.fancy_tab_bar.take();
         self.invalidate_fancy_tab_bar();
         self.invalidate_modal();
-        self.input_map = InputMap::new(&config);
-        self.leader_is_down = None;
-        self.render_state.as_mut().map(|rs| rs.config_changed());
-        let dimensions = self.dimensions;
+        if changed.any_of(&[S::KeyInput, S::Mouse]) {
+            self.key_table_state.clear_stack();
+            self.input_map.refresh(&config, keyboard_generation());
+            self.leader_is_down = None;
+        }
 
-        if let Err(err) = self.fonts.config_changed(&config) {
-            log::error!("Failed to load font configuration: {:#}", err);
+        if fonts_changed || changed.contains(S::ColorConfig) {
+            if let Err(err) = self.fonts.config_changed(&config) {
+                log::error!("Failed to load font configuration: {:#}", err);
+            }
+        } else {
+            self.fonts.set_config(&config);
         }
+        self.render_state.as_mut().map(|rs| rs.config_changed());
+        let dimensions = self.dimensions;
 
         if let Some(window) = mux.get_window(self.mux_window_id) {
             let term_config: Arc<dyn TerminalConfiguration> =
@@ -1767,12 +2232,14 @@ impl TermWindow {
 
         // Do this after we've potentially adjusted scaling based on config/padding
         // and window size
-        self.window_background = reload_background_image(
-            &config,
-            &self.window_background,
-            &self.dimensions,
-            &self.render_metrics,
-        );
+        if changed.any_of(&[S::ColorConfig, S::WindowConfig, S::FontConfig]) {
+            self.window_background = reload_background_image(
+                &config,
+                &self.window_background,
+                &self.dimensions,
+                &self.render_metrics,
+            );
+        }
 
         self.invalidate_modal();
         self.emit_window_event("window-config-reloaded", None);
@@ -1889,6 +2356,22 @@ impl TermWindow {
         .detach();
     }
 
+    /// Returns true if the active pane belongs to a client domain
+    /// that is attached read-only
+    fn is_active_pane_read_only(&self) -> bool {
+        let Some(pane) = self.get_active_pane_or_overlay() else {
+            return false;
+        };
+        Mux::get()
+            .get_domain(pane.domain_id())
+            .map(|domain| {
+                domain
+                    .downcast_ref::<phaedra_client::domain::ClientDomain>()
+                    .map_or(false, |domain| domain.is_read_only())
+            })
+            .unwrap_or(false)
+    }
+
     /// Called by window:set_right_status after the status has
     /// been updated; let's update the bar
     pub fn update_title_post_status(&mut self) {
@@ -1901,8 +2384,19 @@ impl TermWindow {
             Some(window) => window,
             _ => return,
         };
-        let tabs = self.get_tab_information();
-        let panes = self.get_pane_information();
+        let key_table_stack = self.key_table_stack();
+        let mut tabs = self.get_tab_information();
+        let mut panes = self.get_pane_information();
+        for pane in panes.iter_mut().filter(|p| p.is_active) {
+            pane.key_table_stack = key_table_stack.clone();
+        }
+        if let Some(pane) = tabs
+            .iter_mut()
+            .find(|t| t.is_active)
+            .and_then(|t| t.active_pane.as_mut())
+        {
+            pane.key_table_stack = key_table_stack.clone();
+        }
         let active_tab = tabs.iter().find(|t| t.is_active).cloned();
         let active_pane = panes.iter().find(|p| p.is_active).cloned();
 
@@ -1925,6 +2419,19 @@ impl TermWindow {
             None => false,
         };
 
+        self.key_table_chip = if self.config.key_input().show_active_key_table {
+            format_key_table_chip(&key_table_stack)
+        } else {
+            None
+        };
+        let mut right_status = match &self.key_table_chip {
+            Some(chip) => format!("{chip}{}", self.right_status),
+            None => self.right_status.clone(),
+        };
+        if self.is_active_pane_read_only() {
+            right_status.insert_str(0, " READ ONLY ");
+        }
+
         let new_tab_bar = TabBarState::new(
             self.dimensions.pixel_width / self.render_metrics.cell_size.width as usize,
             if hovering_in_tab_bar {
@@ -1937,7 +2444,8 @@ impl TermWindow {
             self.config.color_config().resolved_palette.tab_bar.as_ref(),
             &self.config,
             &self.left_status,
-            &self.right_status,
+            &right_status,
+            &self.status_segments,
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -2022,7 +2530,10 @@ impl TermWindow {
             // to piggy back on the config reloading code for that, so that
             // is what we're doing.
             if show_tab_bar != self.show_tab_bar {
-                self.config_was_reloaded();
+                self.apply_config_sections(
+                    self.config.clone(),
+                    ConfigSectionSet::from(ConfigSection::TabBar),
+                );
             }
         }
         self.schedule_next_status_update();
@@ -2055,20 +2566,28 @@ impl TermWindow {
             } else {
                 0.0
             };
-            let (padding_left, padding_top) = self.padding_left_top();
+            let (padding_left, padding_top) = self.aligned_padding_left_top();
+            let composing = match &self.dead_key_status {
+                DeadKeyStatus::Composing(composing) => Some(composing),
+                DeadKeyStatus::None => None,
+            };
 
-            let r = Rect::new(
+            let r = render::preedit::caret_rect(
+                (cursor.x + pos.left) as isize,
+                cursor.y + pos.top as isize - top,
+                composing,
+                self.render_metrics.cell_size,
                 Point::new(
-                    (((cursor.x + pos.left) as isize).max(0) * self.render_metrics.cell_size.width)
-                        .add(padding_left as isize),
-                    ((cursor.y + pos.top as isize - top).max(0)
-                        * self.render_metrics.cell_size.height)
-                        .add(tab_bar_height as isize)
-                        .add(padding_top as isize),
+                    padding_left as isize,
+                    tab_bar_height as isize + padding_top as isize,
                 ),
-                self.render_metrics.cell_size,
             );
-            win.set_text_cursor_position(r);
+            // Only tell the window when the rect moves, as the input
+            // method re-queries it each time that it is told
+            if self.text_cursor_rect != Some(r) {
+                self.text_cursor_rect.replace(r);
+                win.set_text_cursor_position(r);
+            }
         }
     }
 
@@ -2117,7 +2636,7 @@ impl TermWindow {
         Ok(())
     }
 
-    pub(crate) fn activate_tab(&mut self, tab_idx: isize) -> anyhow::Result<()> {
+    pub(crate) fn activate_tab(&mut self, index: TabIndex) -> anyhow::Result<()> {
         let mux = Mux::get();
         let mut window = mux
             .get_window_mut(self.mux_window_id)
@@ -2125,15 +2644,7 @@ impl TermWindow {
 
         // This logic is coupled with the CliSubCommand::ActivateTab
         // logic in phaedra/src/main.rs. If you update this, update that!
-        let max = window.len();
-
-        let tab_idx = if tab_idx < 0 {
-            max.saturating_sub(tab_idx.abs() as usize)
-        } else {
-            tab_idx as usize
-        };
-
-        if tab_idx < max {
+        if let Some(tab_idx) = index.resolve(window.len()) {
             window.save_and_then_set_active(tab_idx);
 
             drop(window);
@@ -2174,7 +2685,7 @@ impl TermWindow {
             }
         };
         drop(window);
-        self.activate_tab(tab)
+        self.activate_tab(TabIndex::absolute(tab as usize))
     }
 
     pub(crate) fn activate_last_tab(&mut self) -> anyhow::Result<()> {
@@ -2186,7 +2697,7 @@ impl TermWindow {
         let last_idx = window.get_last_active_idx();
         drop(window);
         match last_idx {
-            Some(idx) => self.activate_tab(idx as isize),
+            Some(idx) => self.activate_tab(TabIndex::absolute(idx)),
             None => Ok(()),
         }
     }
@@ -2289,6 +2800,11 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    /// Returns the occupancy of each of the render caches
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        self.cache_registry.snapshot(self)
+    }
+
     pub(crate) fn show_debug_overlay(&mut self) {
         let mux = Mux::get();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -2300,9 +2816,26 @@ impl TermWindow {
 
         let opengl_info = self.opengl_info.as_deref().unwrap_or("Unknown").to_string();
         let connection_info = self.connection_name.clone();
+        let cache_stats = self.cache_stats();
+        let throttled_panes = self.output_throttle.throttled_panes();
+        // The active screen is the one holding the focused window,
+        // which is this one as it received the key press
+        let monitor = Connection::get()
+            .and_then(|conn| conn.screens().ok())
+            .map(|screens| screens.active.name)
+            .unwrap_or_else(|| "Unknown".to_string());
+        let display_info = format!("{monitor} at {} dpi", self.dimensions.dpi);
 
         let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
-            crate::overlay::show_debug_overlay(term, gui_win, opengl_info, connection_info)
+            crate::overlay::show_debug_overlay(
+                term,
+                gui_win,
+                opengl_info,
+                connection_info,
+                display_info,
+                cache_stats,
+                throttled_panes,
+            )
         });
         self.assign_overlay(tab.tab_id(), overlay);
         promise::spawn::spawn(future).detach();
@@ -2408,7 +2941,7 @@ impl TermWindow {
     }
 
     /// Returns the Prompt semantic zones
-    fn get_semantic_prompt_zones(&mut self, pane: &Arc<dyn Pane>) -> &[StableRowIndex] {
+    fn get_semantic_zone_cache(&mut self, pane: &Arc<dyn Pane>) -> &SemanticZoneCache {
         let cache = self
             .semantic_zones
             .entry(pane.pane_id())
@@ -2416,26 +2949,44 @@ impl TermWindow {
 
         let seqno = pane.get_current_seqno();
         if cache.seqno != seqno {
-            let zones = pane.get_semantic_zones().unwrap_or_else(|_| vec![]);
-            let mut zones: Vec<StableRowIndex> = zones
-                .into_iter()
-                .filter_map(|zone| {
-                    if zone.semantic_type == phaedra_term::SemanticType::Prompt {
-                        Some(zone.start_y)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+            let commands = pane.get_command_zones().unwrap_or_else(|_| vec![]);
+            let mut zones: Vec<StableRowIndex> = if commands.is_empty() {
+                // Without any OSC 133 `A` marks, fall back to the rows
+                // on which prompt zones begin
+                let zones = pane.get_semantic_zones().unwrap_or_else(|_| vec![]);
+                zones
+                    .into_iter()
+                    .filter_map(|zone| {
+                        if zone.semantic_type == phaedra_term::SemanticType::Prompt {
+                            Some(zone.start_y)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            } else {
+                commands.iter().map(|command| command.start_y).collect()
+            };
             // dedup to avoid issues where both left and right prompts are
             // defined: we only care if there were 1+ prompts on a line,
             // not about how many prompts are on a line.
             // <https://github.com/PaleRoses/phaedra/issues/1121>
             zones.dedup();
             cache.zones = zones;
+            cache.commands = commands;
             cache.seqno = seqno;
         }
-        &cache.zones
+        cache
+    }
+
+    /// Returns the most recent command in the pane that produced some
+    /// output
+    pub(crate) fn get_last_command_with_output(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+    ) -> Option<CommandZone> {
+        let cache = self.get_semantic_zone_cache(pane);
+        CommandZone::last_with_output(&cache.commands).copied()
     }
 
     pub(crate) fn scroll_to_prompt(&mut self, amount: isize, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
@@ -2444,12 +2995,8 @@ impl TermWindow {
             .get_viewport(pane.pane_id())
             .unwrap_or(dims.physical_top);
         let zone = {
-            let zones = self.get_semantic_prompt_zones(&pane);
-            let idx = match zones.binary_search(&position) {
-                Ok(idx) | Err(idx) => idx,
-            };
-            let idx = ((idx as isize) + amount).max(0) as usize;
-            zones.get(idx).cloned()
+            let zones = &self.get_semantic_zone_cache(&pane).zones;
+            prompt_scroll_target(zones, position, amount)
         };
         if let Some(zone) = zone {
             self.set_viewport(pane.pane_id(), Some(zone), dims);
@@ -2461,6 +3008,20 @@ impl TermWindow {
         Ok(())
     }
 
+    pub(crate) fn scroll_to_last_command_start(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+    ) -> anyhow::Result<()> {
+        if let Some(command) = self.get_last_command_with_output(pane) {
+            let dims = pane.get_dimensions();
+            self.set_viewport(pane.pane_id(), Some(command.start_y), dims);
+            if let Some(win) = self.window.as_ref() {
+                win.invalidate();
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn scroll_by_page(&mut self, amount: f64, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
         let dims = pane.get_dimensions();
         let position = self
@@ -2483,11 +3044,74 @@ impl TermWindow {
                 MouseEventKind::VertWheel(amount) => -amount,
                 _ => return Ok(()),
             };
-            self.scroll_by_line(amount.into(), pane)?;
+            if self.config.scroll().overscroll && self.animations_enabled() {
+                self.overscroll_by_line(amount.into(), pane);
+            } else {
+                self.scroll_by_line(amount.into(), pane)?;
+            }
         }
         Ok(())
     }
 
+    /// Scrolls as scroll_by_line does, except that the viewport may go
+    /// up to `MAX_OVERSCROLL_ROWS` past the top of the scrollback,
+    /// from where advance_overscroll eases it back once the scrolling
+    /// stops
+    fn overscroll_by_line(&mut self, amount: isize, pane: &Arc<dyn Pane>) {
+        let pane_id = pane.pane_id();
+        let dims = pane.get_dimensions();
+        let position = self
+            .get_viewport(pane_id)
+            .unwrap_or(dims.physical_top)
+            .saturating_add(amount);
+        self.set_viewport_within(
+            pane_id,
+            Some(position),
+            dims,
+            overscroll::MAX_OVERSCROLL_ROWS,
+        );
+        let rows = self
+            .get_viewport(pane_id)
+            .map_or(0, |top| (dims.scrollback_top - top).max(0));
+        self.pane_state(pane_id)
+            .overscroll
+            .scrolled(rows, Instant::now());
+    }
+
+    /// Moves each overscrolled viewport along its return to the top of
+    /// the scrollback, and schedules the frame that moves it next
+    pub(crate) fn advance_overscroll(&mut self, now: Instant) {
+        let pane_ids: Vec<PaneId> = self
+            .pane_state
+            .borrow()
+            .iter()
+            .filter(|(_, state)| state.overscroll.is_active())
+            .map(|(&pane_id, _)| pane_id)
+            .collect();
+        let mux = Mux::get();
+        for pane_id in pane_ids {
+            let (rows, next_frame) = {
+                let mut state = self.pane_state(pane_id);
+                let rows = state.overscroll.rows_at(now);
+                (rows, state.overscroll.next_frame(now))
+            };
+            let (Some(rows), Some(pane)) = (rows, mux.get_pane(pane_id)) else {
+                continue;
+            };
+            let dims = pane.get_dimensions();
+            let viewport = overscroll::clamp_viewport(
+                dims.scrollback_top - rows,
+                dims.scrollback_top,
+                dims.physical_top,
+                rows,
+            );
+            if viewport != self.get_viewport(pane_id) {
+                self.set_viewport_within(pane_id, viewport, dims, rows);
+            }
+            self.update_next_frame_time(next_frame);
+        }
+    }
+
     pub(crate) fn scroll_by_line(&mut self, amount: isize, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
         let dims = pane.get_dimensions();
         let position = self
@@ -2720,7 +3344,7 @@ impl TermWindow {
 
         let tab_id = tab.tab_id();
         if confirm && !tab.can_close_without_prompting(CloseReason::Tab) {
-            if self.activate_tab(tab_idx as isize).is_err() {
+            if self.activate_tab(TabIndex::absolute(tab_idx)).is_err() {
                 return;
             }
 
@@ -2761,6 +3385,58 @@ impl TermWindow {
         })
     }
 
+    pub fn is_pane_render_frozen(&self, pane_id: PaneId) -> bool {
+        self.frozen_panes.contains(pane_id)
+    }
+
+    /// Freezes the rendering of `pane_id`, so that it keeps showing its
+    /// current frame until it is unfrozen, when it is described again
+    pub fn set_pane_render_frozen(&mut self, pane_id: PaneId, frozen: bool) {
+        if !self
+            .frozen_panes
+            .set(pane_id, frozen, &mut self.prev_pane_frames)
+        {
+            return;
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn pane_highlight_pattern(&self, pane_id: PaneId) -> Option<Rc<PaneHighlightPattern>> {
+        self.pane_state(pane_id).highlight_pattern.clone()
+    }
+
+    /// Highlights the matches of `pattern` wherever they are visible in
+    /// `pane_id`.  A pattern that is None or empty clears the highlight.
+    pub fn set_pane_highlight_pattern(&mut self, pane_id: PaneId, pattern: Option<MuxPattern>) {
+        let highlight = match pattern.filter(|pattern| !pattern.is_empty()) {
+            Some(pattern) => match CompiledPattern::new(&pattern) {
+                Ok(compiled) => Some(Rc::new(PaneHighlightPattern { pattern, compiled })),
+                Err(err) => {
+                    log::error!("Invalid highlight pattern {pattern:?}: {err:#}");
+                    return;
+                }
+            },
+            None => None,
+        };
+        {
+            let mut state = self.pane_state(pane_id);
+            let unchanged = match (&state.highlight_pattern, &highlight) {
+                (Some(a), Some(b)) => a.pattern == b.pattern,
+                (None, None) => true,
+                _ => false,
+            };
+            if unchanged {
+                return;
+            }
+            state.highlight_pattern = highlight;
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
     pub fn tab_state(&self, tab_id: TabId) -> RefMut<'_, TabState> {
         RefMut::map(self.tab_state.borrow_mut(), |state| {
             state.entry(tab_id).or_insert_with(TabState::default)
@@ -2806,17 +3482,23 @@ impl TermWindow {
         position: Option<StableRowIndex>,
         dims: RenderableDimensions,
     ) {
-        let pos = match position {
-            Some(pos) => {
-                // Drop out of scrolling mode if we're off the bottom
-                if pos >= dims.physical_top {
-                    None
-                } else {
-                    Some(pos.max(dims.scrollback_top))
-                }
-            }
-            None => None,
-        };
+        self.pane_state(pane_id).overscroll.cancel();
+        self.set_viewport_within(pane_id, position, dims, 0);
+    }
+
+    /// Sets the viewport, allowing it to be up to `overscroll_rows`
+    /// past the top of the scrollback
+    fn set_viewport_within(
+        &mut self,
+        pane_id: PaneId,
+        position: Option<StableRowIndex>,
+        dims: RenderableDimensions,
+        overscroll_rows: StableRowIndex,
+    ) {
+        // Drop out of scrolling mode if we're off the bottom
+        let pos = position.and_then(|pos| {
+            overscroll::clamp_viewport(pos, dims.scrollback_top, dims.physical_top, overscroll_rows)
+        });
 
         let mut state = self.pane_state(pane_id);
         if pos != state.viewport {
@@ -2862,6 +3544,26 @@ impl TermWindow {
     /// then that will be returned instead.  Otherwise, if the pane has
     /// an active overlay (such as search or copy mode) then that will
     /// be returned.
+    /// Resolves the pane that a notification from Lua refers to.
+    /// The CopyMode overlay doesn't exist in the mux, but aliases
+    /// itself with the overlaid pane's pane_id.
+    /// So we do a bit of fancy footwork here to resolve the overlay
+    /// and use that if it has the same pane_id, but otherwise fall
+    /// back to what we get from the mux.
+    /// <https://github.com/PaleRoses/phaedra/issues/3209>
+    fn resolve_notif_pane(&self, pane_id: PaneId) -> anyhow::Result<Arc<dyn Pane>> {
+        let active_pane = self
+            .get_active_pane_or_overlay()
+            .ok_or_else(|| anyhow!("there is no active pane!?"))?;
+        if active_pane.pane_id() == pane_id {
+            Ok(active_pane)
+        } else {
+            Mux::get()
+                .get_pane(pane_id)
+                .ok_or_else(|| anyhow!("pane id {} is not valid", pane_id))
+        }
+    }
+
     pub fn get_active_pane_or_overlay(&self) -> Option<Arc<dyn Pane>> {
         let mux = Mux::get();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -2896,6 +3598,7 @@ impl TermWindow {
             is_active: pos.is_active,
             is_zoomed: pos.is_zoomed,
             has_unseen_output: pos.pane.has_unseen_output(),
+            is_alt_screen_active: pos.pane.is_alt_screen_active(),
             left: pos.left,
             top: pos.top,
             width: pos.width,
@@ -2905,6 +3608,7 @@ impl TermWindow {
             title: pos.pane.get_title(),
             user_vars: pos.pane.copy_user_vars(),
             progress: pos.pane.get_progress(),
+            key_table_stack: vec![],
         }
     }
 
@@ -2932,6 +3636,10 @@ impl TermWindow {
                         .unwrap_or(false),
                     window_id: self.mux_window_id,
                     tab_title: tab.get_title(),
+                    is_broadcasting: panes
+                        .iter()
+                        .any(|p| self.is_pane_broadcasting(p.pane.pane_id())),
+                    tab_color: tab.effective_color(),
                     active_pane: panes
                         .iter()
                         .find(|p| p.is_active)
@@ -3016,7 +3724,7 @@ impl TermWindow {
         window.notify(TermWindowNotif::CancelOverlayForTab { tab_id, pane_id });
     }
 
-    fn cancel_overlay_for_pane(&mut self, pane_id: PaneId) {
+    pub(crate) fn cancel_overlay_for_pane(&mut self, pane_id: PaneId) {
         if let Some(overlay) = self.pane_state(pane_id).overlay.take() {
             // Ungh, when I built the CopyOverlay, its pane doesn't get
             // added to the mux and instead it reports the overlaid
@@ -3090,36 +3798,24 @@ impl WindowGeometryObserver for TermWindow {
     fn padding(&self) -> (f32, f32, f32, f32) {
         let h_context = DimensionContext {
             dpi: self.dimensions.dpi as f32,
-            pixel_max: self.terminal_size.pixel_width as f32,
+            pixel_max: self.dimensions.pixel_width as f32,
             pixel_cell: self.render_metrics.cell_size.width as f32,
         };
         let v_context = DimensionContext {
             dpi: self.dimensions.dpi as f32,
-            pixel_max: self.terminal_size.pixel_height as f32,
+            pixel_max: self.dimensions.pixel_height as f32,
             pixel_cell: self.render_metrics.cell_size.height as f32,
         };
 
-        let left = self
-            .config
-            .window_config().window_padding
-            .left
-            .evaluate_as_pixels(h_context);
-        let right = if self.show_scroll_bar
-            && self.config.window_config().window_padding.right.is_zero()
-        {
+        let padding = self.window_padding();
+        let left = padding.left.evaluate_as_pixels(h_context);
+        let right = if self.show_scroll_bar && padding.right.is_zero() {
             h_context.pixel_cell
         } else {
-            self.config
-                .window_config().window_padding
-                .right
-                .evaluate_as_pixels(h_context)
+            padding.right.evaluate_as_pixels(h_context)
         };
-        let top = self.config.window_config().window_padding.top.evaluate_as_pixels(v_context);
-        let bottom = self
-            .config
-            .window_config().window_padding
-            .bottom
-            .evaluate_as_pixels(v_context);
+        let top = padding.top.evaluate_as_pixels(v_context);
+        let bottom = padding.bottom.evaluate_as_pixels(v_context);
 
         (left, top, right, bottom)
     }
@@ -7,8 +7,8 @@ use ::window::{
     MouseButtons as WMB, MouseCursor, MouseEvent, MouseEventKind as WMEK, MousePress,
     WindowDecorations, WindowOps, WindowState,
 };
-use config::keyassignment::{KeyAssignment, MouseEventTrigger, SpawnTabDomain};
-use config::MouseEventAltScreen;
+use config::keyassignment::{KeyAssignment, MouseEventTrigger, SpawnTabDomain, TabIndex};
+use config::{MouseEventAltScreen, MouseEventTriggerMods, MouseRegion};
 use mux::pane::{Pane, WithPaneLines};
 use mux::tab::SplitDirection;
 use mux::Mux;
@@ -28,16 +28,12 @@ impl super::TermWindow {
     fn resolve_ui_item(&self, event: &MouseEvent) -> Option<UIItem> {
         let x = event.coords.x;
         let y = event.coords.y;
-        self.ui_items
-            .iter()
-            .rev()
-            .find(|item| item.hit_test(x, y))
-            .cloned()
+        self.ui_items.hit_test(x, y).cloned()
     }
 
     fn leave_ui_item(&mut self, item: &UIItem) {
         match item.item_type {
-            UIItemType::TabBar(_) => {
+            UIItemType::TabBar(_) | UIItemType::StatusSegment(_) => {
                 self.update_title_post_status();
             }
             UIItemType::CloseTab(_)
@@ -55,7 +51,8 @@ impl super::TermWindow {
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::StatusSegment(_) => {}
         }
     }
 
@@ -67,6 +64,7 @@ impl super::TermWindow {
         };
 
         self.current_mouse_event.replace(event.clone());
+        self.update_window_origin(&event);
 
         let border = self.get_os_border();
 
@@ -76,7 +74,7 @@ impl super::TermWindow {
             0
         } + border.top.get() as isize;
 
-        let (padding_left, padding_top) = self.padding_left_top();
+        let (padding_left, padding_top) = self.aligned_padding_left_top();
 
         let y = (event
             .coords
@@ -130,9 +128,20 @@ impl super::TermWindow {
                     // Completed a window drag
                     return;
                 }
-                if press == &MousePress::Left && self.dragging.take().is_some() {
-                    // Completed a drag
-                    return;
+                if press == &MousePress::Left {
+                    if let Some((item, _)) = self.dragging.take() {
+                        match item.item_type {
+                            // A tab that was pressed but never dragged
+                            // is released as the end of a click
+                            UIItemType::TabBar(TabBarItem::Tab { .. }) => {
+                                if self.drop_tab(context) {
+                                    return;
+                                }
+                            }
+                            // Completed a drag
+                            _ => return,
+                        }
+                    }
                 }
             }
 
@@ -216,16 +225,24 @@ impl super::TermWindow {
         } else {
             None
         };
+        self.update_tooltip_hover(ui_item.as_ref(), &event, context);
 
         if let Some(item) = ui_item.clone() {
             if capture_mouse {
                 self.current_mouse_capture = Some(MouseCapture::UI);
             }
-            self.mouse_event_ui_item(item, pane, y, event, context);
+            if !self.mouse_event_region_binding(&pane, item.item_type.mouse_region(), &event) {
+                self.mouse_event_ui_item(item, pane, y, event, context);
+            }
         } else if matches!(
             self.current_mouse_capture,
             None | Some(MouseCapture::TerminalPane(_))
         ) {
+            let region = if self.is_over_window_border(&event) {
+                MouseRegion::Border
+            } else {
+                MouseRegion::Text
+            };
             self.mouse_event_terminal(
                 pane,
                 ClickPosition {
@@ -237,6 +254,7 @@ impl super::TermWindow {
                 event,
                 context,
                 capture_mouse,
+                region,
             );
         }
 
@@ -245,8 +263,126 @@ impl super::TermWindow {
         }
     }
 
+    /// Returns true if the event is over the padding or border that
+    /// surrounds the terminal cells, rather than over the cells themselves
+    fn is_over_window_border(&self, event: &MouseEvent) -> bool {
+        let border = self.get_os_border();
+        let (padding_left, padding_top) = self.aligned_padding_left_top();
+        let top_bar_height = if self.show_tab_bar && !self.config.tab_bar().tab_bar_at_bottom {
+            self.tab_bar_pixel_height().unwrap_or(0.)
+        } else {
+            0.
+        };
+
+        let left = padding_left + border.left.get() as f32;
+        let top = padding_top + top_bar_height + border.top.get() as f32;
+        let right =
+            left + (self.terminal_size.cols * self.render_metrics.cell_size.width as usize) as f32;
+        let bottom =
+            top + (self.terminal_size.rows * self.render_metrics.cell_size.height as usize) as f32;
+
+        let x = event.coords.x as f32;
+        let y = event.coords.y as f32;
+        x < left || y < top || x >= right || y >= bottom
+    }
+
+    /// Computes the mouse binding trigger for an event, taking the
+    /// click streak into account.  Returns None for events that cannot
+    /// trigger a binding.
+    fn mouse_event_trigger(&self, event: &MouseEvent) -> Option<MouseEventTrigger> {
+        match &event.kind {
+            WMEK::Press(press) => {
+                let press = mouse_press_to_tmb(press);
+                match self.last_mouse_click.as_ref() {
+                    Some(LastMouseClick { streak, button, .. }) if *button == press => {
+                        Some(MouseEventTrigger::Down {
+                            streak: *streak,
+                            button: press,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            WMEK::Release(press) => {
+                let press = mouse_press_to_tmb(press);
+                match self.last_mouse_click.as_ref() {
+                    Some(LastMouseClick { streak, button, .. }) if *button == press => {
+                        Some(MouseEventTrigger::Up {
+                            streak: *streak,
+                            button: press,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            WMEK::Move => {
+                if !self.current_mouse_buttons.is_empty() {
+                    if let Some(LastMouseClick { streak, button, .. }) =
+                        self.last_mouse_click.as_ref()
+                    {
+                        if Some(*button)
+                            == self.current_mouse_buttons.last().map(mouse_press_to_tmb)
+                        {
+                            Some(MouseEventTrigger::Drag {
+                                streak: *streak,
+                                button: *button,
+                            })
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                }
+            }
+            WMEK::VertWheel(_) | WMEK::HorzWheel(_) => wheel_event_trigger(&event.kind),
+        }
+    }
+
+    /// Performs a mouse binding that is restricted to `region`, if one
+    /// matches the event.  Returns true if a binding was performed, in
+    /// which case the default handling for the UI item is skipped.
+    fn mouse_event_region_binding(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        region: MouseRegion,
+        event: &MouseEvent,
+    ) -> bool {
+        let Some(mut event_trigger_type) = self.mouse_event_trigger(event) else {
+            return false;
+        };
+        let trigger_value = event_trigger_type.to_dynamic();
+        normalize_mouse_event_trigger(&mut event_trigger_type);
+
+        // Mouse reporting doesn't apply outside of the terminal cells
+        let mouse_mods = MouseEventTriggerMods {
+            mods: event.modifiers,
+            mouse_reporting: false,
+            alt_screen: if pane.is_alt_screen_active() {
+                MouseEventAltScreen::True
+            } else {
+                MouseEventAltScreen::False
+            },
+        };
+
+        match self
+            .input_map
+            .lookup_mouse_in_region(event_trigger_type, mouse_mods, region)
+        {
+            Some(action) => {
+                self.current_event = Some(trigger_value);
+                self.perform_key_assignment(pane, &action).ok();
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn mouse_leave_impl(&mut self, context: &dyn WindowOps) {
         self.current_mouse_event = None;
+        self.hide_tooltip();
         self.update_title();
         context.set_cursor(Some(MouseCursor::Arrow));
         context.invalidate();
@@ -374,6 +510,9 @@ impl super::TermWindow {
             UIItemType::ScrollThumb => {
                 self.drag_scroll_thumb(item, start_event, event, context);
             }
+            UIItemType::TabBar(TabBarItem::Tab { .. }) => {
+                self.drag_tab(item, start_event, event, context);
+            }
             _ => {
                 log::error!("drag not implemented for {:?}", item);
             }
@@ -408,7 +547,37 @@ impl super::TermWindow {
             UIItemType::CloseTab(idx) => {
                 self.mouse_event_close_tab(idx, event, context);
             }
+            UIItemType::StatusSegment(index) => {
+                self.mouse_event_status_segment(index, event, context);
+            }
+        }
+    }
+
+    /// A left click performs the segment's on_click assignment, if
+    /// it has one; otherwise the segment behaves like the rest of
+    /// the status area
+    pub fn mouse_event_status_segment(
+        &mut self,
+        index: usize,
+        event: MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        let on_click = match self
+            .status_segments
+            .get(index)
+            .and_then(|segment| segment.on_click.clone())
+        {
+            Some(on_click) => on_click,
+            None => return self.mouse_event_tab_bar(TabBarItem::None, event, context),
+        };
+        if let WMEK::Press(MousePress::Left) = event.kind {
+            if let Some(pane) = self.get_active_pane_or_overlay() {
+                if let Err(err) = self.perform_key_assignment(&pane, &on_click) {
+                    log::error!("status segment {}: {:#}", index, err);
+                }
+            }
         }
+        context.set_cursor(Some(MouseCursor::Hand));
     }
 
     pub fn mouse_event_close_tab(
@@ -488,12 +657,19 @@ impl super::TermWindow {
         match event.kind {
             WMEK::Press(MousePress::Left) => match item {
                 TabBarItem::Tab { tab_idx, .. } => {
-                    self.activate_tab(tab_idx as isize).ok();
+                    self.activate_tab(TabIndex::absolute(tab_idx)).ok();
+                    // Potentially starting a drag of the tab
+                    if let Some(item) = self.last_ui_item.clone() {
+                        self.start_tab_drag(item, tab_idx, event.clone());
+                    }
                 }
                 TabBarItem::NewTabButton { .. } => {
                     self.do_new_tab_button_click(MousePress::Left);
                 }
-                TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {
+                TabBarItem::None
+                | TabBarItem::LeftStatus
+                | TabBarItem::RightStatus
+                | TabBarItem::StatusSegment(_) => {
                     let maximized = self
                         .window_state
                         .intersects(WindowState::MAXIMIZED | WindowState::FULL_SCREEN);
@@ -546,6 +722,7 @@ impl super::TermWindow {
                 TabBarItem::None
                 | TabBarItem::LeftStatus
                 | TabBarItem::RightStatus
+                | TabBarItem::StatusSegment(_)
                 | TabBarItem::WindowButton(_) => {}
             },
             WMEK::Press(MousePress::Right) => match item {
@@ -558,10 +735,14 @@ impl super::TermWindow {
                 TabBarItem::None
                 | TabBarItem::LeftStatus
                 | TabBarItem::RightStatus
+                | TabBarItem::StatusSegment(_)
                 | TabBarItem::WindowButton(_) => {}
             },
             WMEK::Move => match item {
-                TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {
+                TabBarItem::None
+                | TabBarItem::LeftStatus
+                | TabBarItem::RightStatus
+                | TabBarItem::StatusSegment(_) => {
                     context.set_window_drag_position(event.screen_coords);
                 }
                 TabBarItem::WindowButton(window::IntegratedTitleButton::Maximize) => {
@@ -678,6 +859,7 @@ impl super::TermWindow {
         event: MouseEvent,
         context: &dyn WindowOps,
         capture_mouse: bool,
+        region: MouseRegion,
     ) {
         let mut is_click_to_focus_pane = false;
 
@@ -721,8 +903,8 @@ impl super::TermWindow {
                                 context.invalidate();
                             }
                         }
-                        WMEK::Release(_) | WMEK::HorzWheel(_) => {}
-                        WMEK::VertWheel(_) => {
+                        WMEK::Release(_) => {}
+                        WMEK::VertWheel(_) | WMEK::HorzWheel(_) => {
                             // Let wheel events route to the hovered pane,
                             // even if it doesn't have focus
                             pane = Arc::clone(&pos.pane);
@@ -750,6 +932,19 @@ impl super::TermWindow {
             }
         }
 
+        if let Some(font) = self.pane_font(pane.pane_id()) {
+            // The pane is positioned in window cells, but its content
+            // is laid out in the cells of its own font
+            let cell_size = self.render_metrics.cell_size;
+            let pane_cell_size = font.render_metrics.cell_size;
+            let x = column as isize * cell_size.width + x_pixel_offset;
+            let y = row as isize * cell_size.height + y_pixel_offset;
+            column = (x.max(0) / pane_cell_size.width) as usize;
+            row = (y.max(0) / pane_cell_size.height) as i64;
+            x_pixel_offset = x - column as isize * pane_cell_size.width;
+            y_pixel_offset = y - row as isize * pane_cell_size.height;
+        }
+
         if capture_mouse {
             self.current_mouse_capture = Some(MouseCapture::TerminalPane(pane.pane_id()));
         }
@@ -869,76 +1064,10 @@ impl super::TermWindow {
             MouseCursor::Text
         }));
 
-        let event_trigger_type = match &event.kind {
-            WMEK::Press(press) => {
-                let press = mouse_press_to_tmb(press);
-                match self.last_mouse_click.as_ref() {
-                    Some(LastMouseClick { streak, button, .. }) if *button == press => {
-                        Some(MouseEventTrigger::Down {
-                            streak: *streak,
-                            button: press,
-                        })
-                    }
-                    _ => None,
-                }
-            }
-            WMEK::Release(press) => {
-                let press = mouse_press_to_tmb(press);
-                match self.last_mouse_click.as_ref() {
-                    Some(LastMouseClick { streak, button, .. }) if *button == press => {
-                        Some(MouseEventTrigger::Up {
-                            streak: *streak,
-                            button: press,
-                        })
-                    }
-                    _ => None,
-                }
-            }
-            WMEK::Move => {
-                if !self.current_mouse_buttons.is_empty() {
-                    if let Some(LastMouseClick { streak, button, .. }) =
-                        self.last_mouse_click.as_ref()
-                    {
-                        if Some(*button)
-                            == self.current_mouse_buttons.last().map(mouse_press_to_tmb)
-                        {
-                            Some(MouseEventTrigger::Drag {
-                                streak: *streak,
-                                button: *button,
-                            })
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            }
-            WMEK::VertWheel(amount) => Some(match *amount {
-                0 => return,
-                1.. => MouseEventTrigger::Down {
-                    streak: 1,
-                    button: MouseButton::WheelUp(*amount as usize),
-                },
-                _ => MouseEventTrigger::Down {
-                    streak: 1,
-                    button: MouseButton::WheelDown(-amount as usize),
-                },
-            }),
-            WMEK::HorzWheel(amount) => Some(match *amount {
-                0 => return,
-                1.. => MouseEventTrigger::Down {
-                    streak: 1,
-                    button: MouseButton::WheelLeft(*amount as usize),
-                },
-                _ => MouseEventTrigger::Down {
-                    streak: 1,
-                    button: MouseButton::WheelRight(-amount as usize),
-                },
-            }),
-        };
+        if matches!(event.kind, WMEK::VertWheel(0) | WMEK::HorzWheel(0)) {
+            return;
+        }
+        let event_trigger_type = self.mouse_event_trigger(&event);
 
         if allow_action {
             if let Some(mut event_trigger_type) = event_trigger_type {
@@ -964,40 +1093,9 @@ impl super::TermWindow {
                     self.scroll_to_bottom(&pane);
                 }
 
-                // normalize delta and streak to make mouse assignment
-                // easier to wrangle
-                match event_trigger_type {
-                    MouseEventTrigger::Down {
-                        ref mut streak,
-                        button:
-                            MouseButton::WheelUp(ref mut delta)
-                            | MouseButton::WheelDown(ref mut delta)
-                            | MouseButton::WheelLeft(ref mut delta)
-                            | MouseButton::WheelRight(ref mut delta),
-                    }
-                    | MouseEventTrigger::Up {
-                        ref mut streak,
-                        button:
-                            MouseButton::WheelUp(ref mut delta)
-                            | MouseButton::WheelDown(ref mut delta)
-                            | MouseButton::WheelLeft(ref mut delta)
-                            | MouseButton::WheelRight(ref mut delta),
-                    }
-                    | MouseEventTrigger::Drag {
-                        ref mut streak,
-                        button:
-                            MouseButton::WheelUp(ref mut delta)
-                            | MouseButton::WheelDown(ref mut delta)
-                            | MouseButton::WheelLeft(ref mut delta)
-                            | MouseButton::WheelRight(ref mut delta),
-                    } => {
-                        *streak = 1;
-                        *delta = 1;
-                    }
-                    _ => {}
-                };
+                normalize_mouse_event_trigger(&mut event_trigger_type);
 
-                let mouse_mods = config::MouseEventTriggerMods {
+                let mouse_mods = MouseEventTriggerMods {
                     mods: modifiers,
                     mouse_reporting,
                     alt_screen: if pane.is_alt_screen_active() {
@@ -1007,7 +1105,32 @@ impl super::TermWindow {
                     },
                 };
 
-                if let Some(action) = self.input_map.lookup_mouse(event_trigger_type, mouse_mods) {
+                // Unless the alternate screen has bindings of its own,
+                // let the wheel scroll the viewport as it would for the
+                // primary screen when so configured
+                let wheel_scrolls_history = !mouse_reporting
+                    && mouse_mods.alt_screen == MouseEventAltScreen::True
+                    && matches!(event.kind, WMEK::VertWheel(_) | WMEK::HorzWheel(_))
+                    && self.config.alternate_buffer_wheel_behavior()
+                        == config::AlternateBufferWheelBehavior::ScrollHistory;
+
+                if let Some(action) = self
+                    .input_map
+                    .lookup_mouse(event_trigger_type.clone(), mouse_mods, region)
+                    .or_else(|| {
+                        if !wheel_scrolls_history {
+                            return None;
+                        }
+                        self.input_map.lookup_mouse(
+                            event_trigger_type,
+                            MouseEventTriggerMods {
+                                alt_screen: MouseEventAltScreen::False,
+                                ..mouse_mods
+                            },
+                            region,
+                        )
+                    })
+                {
                     self.perform_key_assignment(&pane, &action).ok();
                     return;
                 }
@@ -1077,3 +1200,108 @@ fn mouse_press_to_tmb(press: &MousePress) -> TMB {
         MousePress::Middle => TMB::Middle,
     }
 }
+
+/// Returns the binding trigger for a wheel event: scrolling in either
+/// direction presses the corresponding wheel button, with the amount
+/// scrolled as its delta
+fn wheel_event_trigger(kind: &WMEK) -> Option<MouseEventTrigger> {
+    let button = match *kind {
+        WMEK::VertWheel(0) | WMEK::HorzWheel(0) => return None,
+        WMEK::VertWheel(amount @ 1..) => MouseButton::WheelUp(amount as usize),
+        WMEK::VertWheel(amount) => MouseButton::WheelDown(-amount as usize),
+        WMEK::HorzWheel(amount @ 1..) => MouseButton::WheelLeft(amount as usize),
+        WMEK::HorzWheel(amount) => MouseButton::WheelRight(-amount as usize),
+        WMEK::Move | WMEK::Press(_) | WMEK::Release(_) => return None,
+    };
+    Some(MouseEventTrigger::Down { streak: 1, button })
+}
+
+/// Normalize wheel delta and streak to make mouse assignment
+/// easier to wrangle
+fn normalize_mouse_event_trigger(trigger: &mut MouseEventTrigger) {
+    match trigger {
+        MouseEventTrigger::Down {
+            streak,
+            button:
+                MouseButton::WheelUp(delta)
+                | MouseButton::WheelDown(delta)
+                | MouseButton::WheelLeft(delta)
+                | MouseButton::WheelRight(delta),
+        }
+        | MouseEventTrigger::Up {
+            streak,
+            button:
+                MouseButton::WheelUp(delta)
+                | MouseButton::WheelDown(delta)
+                | MouseButton::WheelLeft(delta)
+                | MouseButton::WheelRight(delta),
+        }
+        | MouseEventTrigger::Drag {
+            streak,
+            button:
+                MouseButton::WheelUp(delta)
+                | MouseButton::WheelDown(delta)
+                | MouseButton::WheelLeft(delta)
+                | MouseButton::WheelRight(delta),
+        } => {
+            *streak = 1;
+            *delta = 1;
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::inputmap::InputMap;
+    use ::window::Modifiers;
+
+    fn wheel_down(button: MouseButton) -> MouseEventTrigger {
+        MouseEventTrigger::Down { streak: 1, button }
+    }
+
+    fn normalized_trigger(kind: WMEK) -> MouseEventTrigger {
+        let mut trigger = wheel_event_trigger(&kind).unwrap();
+        normalize_mouse_event_trigger(&mut trigger);
+        trigger
+    }
+
+    #[test]
+    fn horizontal_wheel_triggers_bindings() {
+        assert_eq!(wheel_event_trigger(&WMEK::HorzWheel(0)), None);
+        assert_eq!(
+            wheel_event_trigger(&WMEK::HorzWheel(3)),
+            Some(wheel_down(MouseButton::WheelLeft(3)))
+        );
+        assert_eq!(
+            wheel_event_trigger(&WMEK::HorzWheel(-2)),
+            Some(wheel_down(MouseButton::WheelRight(2)))
+        );
+        assert_eq!(
+            normalized_trigger(WMEK::VertWheel(4)),
+            wheel_down(MouseButton::WheelUp(1))
+        );
+
+        let left = wheel_down(MouseButton::WheelLeft(1));
+        let mods = MouseEventTriggerMods {
+            mods: Modifiers::NONE,
+            mouse_reporting: false,
+            alt_screen: MouseEventAltScreen::False,
+        };
+        let mut map = InputMap::default_input_map();
+        let lookup = |map: &InputMap, kind| {
+            map.lookup_mouse(normalized_trigger(kind), mods, MouseRegion::Text)
+        };
+        // The horizontal wheel does nothing by default
+        assert_eq!(lookup(&map, WMEK::HorzWheel(1)), None);
+
+        map.mouse
+            .insert((left, mods), KeyAssignment::ActivateTabRelative(-1));
+        assert_eq!(
+            lookup(&map, WMEK::HorzWheel(5)),
+            Some(KeyAssignment::ActivateTabRelative(-1))
+        );
+        assert_eq!(lookup(&map, WMEK::HorzWheel(-5)), None);
+    }
+}
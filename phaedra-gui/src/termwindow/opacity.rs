@@ -0,0 +1,113 @@
+//! The opacity and blur of the window background, which key
+//! assignments can change while the window is open: AdjustWindowOpacity
+//! and SetWindowBackgroundBlur override the configuration until
+//! ResetWindowBackground restores it.
+use crate::termwindow::TermWindow;
+use ::window::WindowOps;
+
+/// The least opacity that AdjustWindowOpacity will go down to, so that
+/// the window can't be made invisible
+pub const MIN_WINDOW_OPACITY: f32 = 0.1;
+/// The blur radius used when blur is enabled without a radius, and
+/// none is configured
+pub const DEFAULT_BLUR_RADIUS: u32 = 20;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WindowBackgroundOverride {
+    pub opacity: Option<f32>,
+    /// The blur radius, which is 0 when blur has been turned off
+    pub blur_radius: Option<u32>,
+}
+
+impl WindowBackgroundOverride {
+    /// Returns the opacity of the window background
+    pub fn opacity(&self) -> f32 {
+        self.opacity.unwrap_or(1.0)
+    }
+
+    /// Changes the opacity by `delta`, limited to between
+    /// `MIN_WINDOW_OPACITY` and 1.0, and returns the new opacity
+    pub fn adjust_opacity(&mut self, delta: f32) -> f32 {
+        let opacity = (self.opacity() + delta).clamp(MIN_WINDOW_OPACITY, 1.0);
+        self.opacity.replace(opacity);
+        opacity
+    }
+
+    /// Turns blur on, with `radius` or else the `configured` radius, or
+    /// off, and returns the new radius
+    pub fn set_blur(&mut self, enabled: bool, radius: Option<u32>, configured: i64) -> u32 {
+        let radius = match (enabled, radius) {
+            (false, _) => 0,
+            (true, Some(radius)) => radius,
+            (true, None) if configured > 0 => configured.min(u32::MAX as i64) as u32,
+            (true, None) => DEFAULT_BLUR_RADIUS,
+        };
+        self.blur_radius.replace(radius);
+        radius
+    }
+}
+
+impl TermWindow {
+    /// Returns the opacity of the window background, which the
+    /// background colors of the window and its panes are drawn with
+    pub fn window_background_opacity(&self) -> f32 {
+        self.window_background_override.opacity()
+    }
+
+    pub fn adjust_window_opacity(&mut self, delta: f32) {
+        let opacity = self.window_background_override.adjust_opacity(delta);
+        log::debug!("window background opacity is now {opacity}");
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    pub fn set_window_background_blur(&mut self, enabled: bool, radius: Option<u32>) {
+        let radius = self.window_background_override.set_blur(
+            enabled,
+            radius,
+            self.config.window_config().macos_window_background_blur,
+        );
+        if let Some(window) = self.window.as_ref() {
+            window.set_background_blur(Some(radius as i64));
+        }
+    }
+
+    pub fn reset_window_background(&mut self) {
+        let blurred = self.window_background_override.blur_radius.is_some();
+        self.window_background_override = WindowBackgroundOverride::default();
+        if let Some(window) = self.window.as_ref() {
+            if blurred {
+                window.set_background_blur(None);
+            }
+            window.invalidate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn opacity_accumulates_within_limits() {
+        let mut state = WindowBackgroundOverride::default();
+        assert_eq!(state.opacity(), 1.0);
+        assert_eq!(state.adjust_opacity(0.1), 1.0);
+        assert_eq!(state.adjust_opacity(-0.25), 0.75);
+        assert_eq!(state.adjust_opacity(-0.25), 0.5);
+        assert_eq!(state.adjust_opacity(-2.0), MIN_WINDOW_OPACITY);
+        assert_eq!(state.adjust_opacity(0.15), MIN_WINDOW_OPACITY + 0.15);
+        assert_eq!(state.adjust_opacity(5.0), 1.0);
+    }
+
+    #[test]
+    fn blur_radius_falls_back_to_configuration() {
+        let mut state = WindowBackgroundOverride::default();
+        assert_eq!(state.set_blur(true, Some(8), 30), 8);
+        assert_eq!(state.set_blur(true, None, 30), 30);
+        assert_eq!(state.set_blur(true, None, 0), DEFAULT_BLUR_RADIUS);
+        assert_eq!(state.set_blur(false, Some(8), 30), 0);
+        assert_eq!(state.blur_radius, Some(0));
+    }
+}
@@ -0,0 +1,189 @@
+//! Lets wheel scrolling carry the viewport a few rows past the top of
+//! the scrollback, per `scroll.overscroll`, and eases it back to the
+//! top once the scrolling stops, so that reaching the start of the
+//! scrollback can be seen.
+use config::EasingFunction;
+use phaedra_term::StableRowIndex;
+use std::time::{Duration, Instant};
+
+/// How many rows past the top of the scrollback the viewport may go
+pub const MAX_OVERSCROLL_ROWS: StableRowIndex = 3;
+/// How long after the last wheel event the scrolling is taken to have
+/// stopped.  The wheel reports no end to a gesture.
+pub const SETTLE_DELAY: Duration = Duration::from_millis(150);
+/// How long the viewport takes to return to the top
+pub const SNAP_BACK_DURATION: Duration = Duration::from_millis(250);
+/// The interval between the frames of the snap-back
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Returns the viewport to use for a request to show `position` at the
+/// top of the pane: None when that is at or below the bottom of the
+/// scrollback, and otherwise no more than `overscroll_rows` rows above
+/// `scrollback_top`.
+pub fn clamp_viewport(
+    position: StableRowIndex,
+    scrollback_top: StableRowIndex,
+    physical_top: StableRowIndex,
+    overscroll_rows: StableRowIndex,
+) -> Option<StableRowIndex> {
+    if position >= physical_top {
+        None
+    } else {
+        Some(position.max(scrollback_top - overscroll_rows))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Idle,
+    /// The wheel has put the viewport `rows` past the top
+    Scrolling {
+        rows: StableRowIndex,
+        last_scroll: Instant,
+    },
+    /// The viewport is returning to the top from `rows` past it
+    SnappingBack {
+        rows: StableRowIndex,
+        start: Instant,
+    },
+}
+
+/// Tracks the overscroll of the viewport of a pane
+#[derive(Debug, Default)]
+pub struct Overscroll {
+    state: State,
+}
+
+impl Overscroll {
+    /// Called when the wheel has moved the viewport, which is now
+    /// `rows` past the top of the scrollback
+    pub fn scrolled(&mut self, rows: StableRowIndex, now: Instant) {
+        self.state = if rows > 0 {
+            State::Scrolling {
+                rows,
+                last_scroll: now,
+            }
+        } else {
+            State::Idle
+        };
+    }
+
+    /// Forgets the overscroll, as when the viewport has been moved by
+    /// something other than the wheel
+    pub fn cancel(&mut self) {
+        self.state = State::Idle;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    /// Returns how many rows past the top the viewport should be at
+    /// `now`, or None if it isn't overscrolled.  Once the snap-back
+    /// has finished, this returns zero once and then None.
+    pub fn rows_at(&mut self, now: Instant) -> Option<StableRowIndex> {
+        match self.state {
+            State::Idle => None,
+            State::Scrolling { rows, last_scroll } => {
+                let settled = last_scroll + SETTLE_DELAY;
+                if now < settled {
+                    return Some(rows);
+                }
+                self.state = State::SnappingBack {
+                    rows,
+                    start: settled,
+                };
+                self.rows_at(now)
+            }
+            State::SnappingBack { rows, start } => {
+                let elapsed = now.saturating_duration_since(start);
+                if elapsed >= SNAP_BACK_DURATION {
+                    self.state = State::Idle;
+                    return Some(0);
+                }
+                let progress = elapsed.as_secs_f32() / SNAP_BACK_DURATION.as_secs_f32();
+                let eased = EasingFunction::EaseOut.evaluate_at_position(progress);
+                Some((rows as f32 * (1. - eased)).round() as StableRowIndex)
+            }
+        }
+    }
+
+    /// Returns when the viewport next needs to be moved
+    pub fn next_frame(&self, now: Instant) -> Option<Instant> {
+        match self.state {
+            State::Idle => None,
+            State::Scrolling { last_scroll, .. } => Some(last_scroll + SETTLE_DELAY),
+            State::SnappingBack { .. } => Some(now + FRAME_INTERVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn viewport_is_clamped() {
+        // Scrollback from row 0, with the live screen from row 100
+        assert_eq!(clamp_viewport(100, 0, 100, 0), None);
+        assert_eq!(clamp_viewport(150, 0, 100, 3), None);
+        assert_eq!(clamp_viewport(50, 0, 100, 0), Some(50));
+        assert_eq!(clamp_viewport(-2, 0, 100, 0), Some(0));
+        assert_eq!(clamp_viewport(-2, 0, 100, 3), Some(-2));
+        assert_eq!(clamp_viewport(-10, 0, 100, 3), Some(-3));
+        // Once lines have been dropped from the scrollback
+        assert_eq!(clamp_viewport(10, 40, 100, 3), Some(37));
+    }
+
+    #[test]
+    fn snaps_back_once_scrolling_stops() {
+        let start = Instant::now();
+        let mut overscroll = Overscroll::default();
+        assert!(!overscroll.is_active());
+        assert_eq!(overscroll.rows_at(start), None);
+        assert_eq!(overscroll.next_frame(start), None);
+
+        overscroll.scrolled(3, start);
+        assert!(overscroll.is_active());
+        assert_eq!(overscroll.next_frame(start), Some(start + SETTLE_DELAY));
+        assert_eq!(overscroll.rows_at(start + ms(100)), Some(3));
+
+        // Scrolling again puts off the snap-back
+        overscroll.scrolled(2, start + ms(100));
+        assert_eq!(overscroll.rows_at(start + ms(200)), Some(2));
+
+        let settled = start + ms(100) + SETTLE_DELAY;
+        assert_eq!(overscroll.rows_at(settled), Some(2));
+        let mut last = 2;
+        let mut t = settled;
+        while t < settled + SNAP_BACK_DURATION {
+            let rows = overscroll.rows_at(t).unwrap();
+            assert!(rows <= last, "{rows} after {last}");
+            assert_eq!(overscroll.next_frame(t), Some(t + FRAME_INTERVAL));
+            last = rows;
+            t += ms(10);
+        }
+        assert_eq!(overscroll.rows_at(t), Some(0));
+        assert!(!overscroll.is_active());
+        assert_eq!(overscroll.rows_at(t), None);
+    }
+
+    #[test]
+    fn scrolling_down_or_other_scrolling_ends_overscroll() {
+        let start = Instant::now();
+        let mut overscroll = Overscroll::default();
+        overscroll.scrolled(3, start);
+        overscroll.scrolled(0, start + ms(10));
+        assert!(!overscroll.is_active());
+
+        overscroll.scrolled(3, start);
+        assert_eq!(overscroll.rows_at(start + SETTLE_DELAY + ms(200)), Some(1));
+        overscroll.cancel();
+        assert_eq!(overscroll.rows_at(start + ms(1000)), None);
+    }
+}
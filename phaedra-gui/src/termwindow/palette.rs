@@ -1,5 +1,6 @@
 use crate::commands::{CommandDef, ExpandedCommand};
 use config::observers::*;
+use crate::render_command::LayerId;
 use crate::overlay::selector::{matcher_pattern, matcher_score};
 use crate::termwindow::box_model::*;
 use crate::termwindow::modal::Modal;
@@ -10,7 +11,7 @@ use crate::termwindow::render::corners::{
 use crate::termwindow::{DimensionContext, GuiWin, TermWindow};
 use crate::utilsprites::RenderMetrics;
 use config::keyassignment::KeyAssignment;
-use config::Dimension;
+use config::{CommandPaletteConfig, Dimension, PaletteFuzzyAlgorithm};
 use frecency::Frecency;
 use luahelper::{from_lua_value_dynamic, impl_lua_conversion_dynamic};
 use mux_lua::MuxPane;
@@ -25,7 +26,7 @@ use termwiz::nerdfonts::NERD_FONTS;
 use phaedra_dynamic::{FromDynamic, ToDynamic};
 use phaedra_term::{KeyCode, KeyModifiers, MouseEvent};
 use window::color::LinearRgba;
-use window::Modifiers;
+use window::{Modifiers, UIKeyCapRendering};
 
 struct MatchResults {
     selection: String,
@@ -192,8 +193,34 @@ impl MatchResult {
     }
 }
 
-fn compute_matches(selection: &str, commands: &[ExpandedCommand]) -> Vec<usize> {
-    if selection.is_empty() {
+/// Scores `text` against `selection` for the Substring and Prefix
+/// algorithms; earlier matches score higher
+fn literal_score(algorithm: PaletteFuzzyAlgorithm, selection: &str, text: &str) -> Option<u32> {
+    let selection = selection.to_lowercase();
+    let text = text.to_lowercase();
+    let pos = match algorithm {
+        PaletteFuzzyAlgorithm::SkimV2 | PaletteFuzzyAlgorithm::Substring => {
+            text.find(&selection)?
+        }
+        PaletteFuzzyAlgorithm::Prefix => {
+            let mut offset = 0;
+            text.split_inclusive(|c: char| !c.is_alphanumeric())
+                .find_map(|word| {
+                    let start = offset;
+                    offset += word.len();
+                    word.starts_with(&selection).then_some(start)
+                })?
+        }
+    };
+    Some(u32::MAX / 2 - (pos.min(u32::MAX as usize / 2)) as u32)
+}
+
+fn compute_matches(
+    selection: &str,
+    commands: &[ExpandedCommand],
+    config: &CommandPaletteConfig,
+) -> Vec<usize> {
+    let mut matches: Vec<usize> = if selection.is_empty() {
         commands.iter().enumerate().map(|(idx, _)| idx).collect()
     } else {
         let pattern = matcher_pattern(selection);
@@ -203,16 +230,132 @@ fn compute_matches(selection: &str, commands: &[ExpandedCommand]) -> Vec<usize>
             .par_iter()
             .enumerate()
             .filter_map(|(row_idx, entry)| {
-                let group = entry.menubar.join(" ");
-                let text = format!("{group}: {}. {} {:?}", entry.brief, entry.doc, entry.action);
-                matcher_score(&pattern, &text)
-                    .map(|score| MatchResult::new(row_idx, score, selection, commands))
+                let score = match config.fuzzy_algorithm {
+                    PaletteFuzzyAlgorithm::SkimV2 => {
+                        let group = entry.menubar.join(" ");
+                        let text =
+                            format!("{group}: {}. {} {:?}", entry.brief, entry.doc, entry.action);
+                        matcher_score(&pattern, &text)
+                    }
+                    PaletteFuzzyAlgorithm::Substring => {
+                        let group = entry.menubar.join(" ");
+                        let text = format!("{group}: {}. {}", entry.brief, entry.doc);
+                        literal_score(config.fuzzy_algorithm, selection, &text)
+                    }
+                    PaletteFuzzyAlgorithm::Prefix => {
+                        literal_score(config.fuzzy_algorithm, selection, &entry.brief)
+                    }
+                };
+                score.map(|score| MatchResult::new(row_idx, score, selection, commands))
             })
             .collect();
+        // Stable sort so that equal scores retain the frecency ordering
         scores.sort_by(|a, b| a.score.cmp(&b.score).reverse());
         log::trace!("matching took {:?}", start.elapsed());
 
         scores.iter().map(|result| result.row_idx).collect()
+    };
+
+    if let Some(max_results) = config.max_results {
+        matches.truncate(max_results);
+    }
+    matches
+}
+
+/// Formats the key chords bound to a command for display alongside it,
+/// eg: `CTRL-SHIFT-P, SUPER-P`.  The chords that are most natural for
+/// the current OS are listed first.
+fn format_key_chords(
+    keys: &[(Modifiers, ::window::KeyCode)],
+    ui_key_cap_rendering: UIKeyCapRendering,
+    max_chords: usize,
+) -> String {
+    let mut keys = keys.to_vec();
+
+    keys.sort_by(|(a_mods, a_key), (b_mods, b_key)| {
+        fn score_mods(mods: &Modifiers) -> usize {
+            let mut score: usize = mods.bits() as usize;
+            // Prefer keys with CMD on macOS, but not on other systems,
+            // where CMD tends to be reserved by the desktop environment
+            if cfg!(target_os = "macos") && mods.contains(Modifiers::SUPER) {
+                score += 1000;
+            } else if !cfg!(target_os = "macos") && !mods.contains(Modifiers::SUPER) {
+                score += 1000;
+            }
+            score
+        }
+
+        let a_mods = score_mods(a_mods);
+        let b_mods = score_mods(b_mods);
+
+        match b_mods.cmp(&a_mods) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+
+        a_key.cmp(&b_key)
+    });
+
+    let separator = if ui_key_cap_rendering == UIKeyCapRendering::AppleSymbols {
+        " "
+    } else {
+        "-"
+    };
+
+    let mut keys = keys
+        .into_iter()
+        .map(|(mods, keycode)| {
+            let mut mod_string = mods.to_string_with_separator(::window::ModifierToStringArgs {
+                separator,
+                want_none: false,
+                ui_key_cap_rendering: Some(ui_key_cap_rendering),
+            });
+            if !mod_string.is_empty() {
+                mod_string.push_str(separator);
+            }
+            let keycode = crate::inputmap::ui_key(&keycode, ui_key_cap_rendering);
+            format!("{mod_string}{keycode}")
+        })
+        .collect::<Vec<_>>();
+
+    keys.dedup();
+    keys.truncate(max_chords);
+
+    keys.join(", ")
+}
+
+/// Returns the command shown in the selected row
+fn selected_command<'a>(
+    commands: &'a [ExpandedCommand],
+    matches: Option<&MatchResults>,
+    selected_row: usize,
+) -> Option<&'a ExpandedCommand> {
+    let idx = *matches?.matches.get(selected_row)?;
+    commands.get(idx)
+}
+
+/// Performs the action of the command chosen from the palette
+trait PaletteDispatch {
+    fn dispatch(&mut self, action: &KeyAssignment) -> anyhow::Result<()>;
+}
+
+impl PaletteDispatch for TermWindow {
+    /// Dispatches through perform_key_assignment, which is the same
+    /// path taken when the bound key is pressed
+    fn dispatch(&mut self, action: &KeyAssignment) -> anyhow::Result<()> {
+        if let Some(pane) = self.get_active_pane_or_overlay() {
+            self.perform_key_assignment(&pane, action)?;
+        }
+        Ok(())
+    }
+}
+
+fn activate_command(command: &ExpandedCommand, dispatcher: &mut dyn PaletteDispatch) {
+    if let Err(err) = save_recent(command) {
+        log::error!("Error while saving recents: {err:#}");
+    }
+    if let Err(err) = dispatcher.dispatch(&command.action) {
+        log::error!("Error while performing {command:?}: {err:#}");
     }
 }
 
@@ -346,64 +489,14 @@ impl CommandPalette {
             ];
 
             if !command.keys.is_empty() {
-                let mut keys = command.keys.clone();
-
-                keys.sort_by(|(a_mods, a_key), (b_mods, b_key)| {
-                    fn score_mods(mods: &Modifiers) -> usize {
-                        let mut score: usize = mods.bits() as usize;
-                        // Prefer keys with CMD on macOS, but not on other systems,
-                        // where CMD tends to be reserved by the desktop environment
-                        if cfg!(target_os = "macos") && mods.contains(Modifiers::SUPER) {
-                            score += 1000;
-                        } else if !cfg!(target_os = "macos") && !mods.contains(Modifiers::SUPER) {
-                            score += 1000;
-                        }
-                        score
-                    }
-
-                    let a_mods = score_mods(a_mods);
-                    let b_mods = score_mods(b_mods);
-
-                    match b_mods.cmp(&a_mods) {
-                        Ordering::Equal => {}
-                        ordering => return ordering,
-                    }
-
-                    a_key.cmp(&b_key)
-                });
-
-                let separator = if term_window.config.key_input().ui_key_cap_rendering
-                    == ::window::UIKeyCapRendering::AppleSymbols
-                {
-                    " "
-                } else {
-                    "-"
-                };
-
-                let mut keys = keys
-                    .into_iter()
-                    .map(|(mods, keycode)| {
-                        let mut mod_string =
-                            mods.to_string_with_separator(::window::ModifierToStringArgs {
-                                separator,
-                                want_none: false,
-                                ui_key_cap_rendering: Some(term_window.config.key_input().ui_key_cap_rendering),
-                            });
-                        if !mod_string.is_empty() {
-                            mod_string.push_str(separator);
-                        }
-                        let keycode = crate::inputmap::ui_key(
-                            &keycode,
-                            term_window.config.key_input().ui_key_cap_rendering,
-                        );
-                        format!("{mod_string}{keycode}")
-                    })
-                    .collect::<Vec<_>>();
-
-                keys.dedup();
-                keys.truncate(term_window.config.runtime().palette_max_key_assigments_for_action);
-
-                let key_label = keys.join(", ");
+                let key_label = format_key_chords(
+                    &command.keys,
+                    term_window.config.key_input().ui_key_cap_rendering,
+                    term_window
+                        .config
+                        .runtime()
+                        .palette_max_key_assigments_for_action,
+                );
 
                 row.push(
                     Element::new(&font, ElementContent::Text(key_label))
@@ -532,7 +625,7 @@ impl CommandPalette {
                 ),
                 metrics: &metrics,
                 gl_state: term_window.render_state.as_ref().unwrap(),
-                zindex: 100,
+                zindex: LayerId::modal().zindex(),
             },
             &element,
         )?;
@@ -621,26 +714,17 @@ impl Modal for CommandPalette {
                 self.updated_input();
             }
             (KeyCode::Enter, KeyModifiers::NONE) => {
-                // Enter the selected character to the current pane
-                let selected_idx = *self.selected_row.borrow();
-                let alias_idx = match self.matches.borrow().as_ref() {
+                // Perform the action of the selected command
+                let item = match selected_command(
+                    &self.commands,
+                    self.matches.borrow().as_ref(),
+                    *self.selected_row.borrow(),
+                ) {
+                    Some(item) => item.clone(),
                     None => return Ok(true),
-                    Some(results) => match results.matches.get(selected_idx) {
-                        Some(i) => *i,
-                        None => return Ok(true),
-                    },
                 };
-                let item = &self.commands[alias_idx];
-                if let Err(err) = save_recent(item) {
-                    log::error!("Error while saving recents: {err:#}");
-                }
                 term_window.cancel_modal();
-
-                if let Some(pane) = term_window.get_active_pane_or_overlay() {
-                    if let Err(err) = term_window.perform_key_assignment(&pane, &item.action) {
-                        log::error!("Error while performing {item:?}: {err:#}");
-                    }
-                }
+                activate_command(&item, term_window);
                 return Ok(true);
             }
             _ => return Ok(false),
@@ -679,7 +763,11 @@ impl Modal for CommandPalette {
         if rebuild_matches {
             results.replace(MatchResults {
                 selection: selection.to_string(),
-                matches: compute_matches(selection, &self.commands),
+                matches: compute_matches(
+                    selection,
+                    &self.commands,
+                    &term_window.config.runtime().command_palette,
+                ),
             });
         };
         let matches = results.as_ref().unwrap();
@@ -705,3 +793,160 @@ impl Modal for CommandPalette {
         self.element.borrow_mut().take();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commands::merge_key_bindings;
+    use crate::execute::describe_effect;
+    use crate::interpret::interpret_assignment;
+    use config::keyassignment::{KeyTableEntry, KeyTables, PaneDirection, TabIndex};
+    use window::KeyCode as WKeyCode;
+
+    fn command(brief: &'static str, action: KeyAssignment) -> ExpandedCommand {
+        ExpandedCommand {
+            brief: brief.into(),
+            doc: "".into(),
+            action,
+            keys: vec![],
+            menubar: &[],
+            icon: None,
+        }
+    }
+
+    fn bind(tables: &mut KeyTables, key: char, mods: Modifiers, action: KeyAssignment) {
+        tables
+            .default
+            .insert((WKeyCode::Char(key), mods), vec![KeyTableEntry::new(action)]);
+    }
+
+    #[test]
+    fn key_bindings_dedup_against_builtins() {
+        let mut commands = vec![command(
+            "Activate tab 1",
+            KeyAssignment::ActivateTab(TabIndex::absolute(0)),
+        )];
+
+        let mut tables = KeyTables::default();
+        bind(
+            &mut tables,
+            '1',
+            Modifiers::ALT,
+            KeyAssignment::ActivateTab(TabIndex::absolute(0)),
+        );
+        bind(
+            &mut tables,
+            'n',
+            Modifiers::CTRL,
+            KeyAssignment::AdjustPaneSize(PaneDirection::Next, 2),
+        );
+        let mut resize = config::keyassignment::KeyTable::new();
+        resize.insert(
+            (WKeyCode::Char('1'), Modifiers::NONE),
+            vec![KeyTableEntry::new(KeyAssignment::ActivateTab(TabIndex::absolute(0)))],
+        );
+        tables.by_name.insert("resize".to_string(), resize);
+
+        merge_key_bindings(&mut commands, &tables);
+
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].brief, "Activate tab 1");
+        assert_eq!(
+            commands[0].keys,
+            vec![(Modifiers::ALT, WKeyCode::Char('1'))]
+        );
+
+        // Not derivable as a built-in; named after its effects instead
+        assert_eq!(commands[1].brief, "Adjust pane size");
+        assert_eq!(
+            commands[1].action,
+            KeyAssignment::AdjustPaneSize(PaneDirection::Next, 2)
+        );
+        assert_eq!(
+            commands[1].keys,
+            vec![(Modifiers::CTRL, WKeyCode::Char('n'))]
+        );
+
+        // Merging again must not duplicate anything
+        merge_key_bindings(&mut commands, &tables);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].keys.len(), 1);
+    }
+
+    #[test]
+    fn chord_formatting() {
+        let keys = [
+            (Modifiers::ALT, WKeyCode::Char('b')),
+            (Modifiers::CTRL | Modifiers::SHIFT, WKeyCode::Char('p')),
+            (Modifiers::CTRL | Modifiers::SHIFT, WKeyCode::Char('p')),
+        ];
+        assert_eq!(
+            format_key_chords(&keys, UIKeyCapRendering::UnixLong, 3),
+            "Shift-Ctrl-P, Alt-B"
+        );
+        assert_eq!(
+            format_key_chords(&keys, UIKeyCapRendering::UnixLong, 1),
+            "Shift-Ctrl-P"
+        );
+        assert_eq!(
+            format_key_chords(&keys, UIKeyCapRendering::Emacs, 1),
+            "S-C-P"
+        );
+        assert_eq!(
+            format_key_chords(
+                &[(Modifiers::NONE, WKeyCode::Char('\r'))],
+                UIKeyCapRendering::UnixLong,
+                1
+            ),
+            "Enter"
+        );
+    }
+
+    /// Records the effects that dispatching an action would execute
+    #[derive(Default)]
+    struct RecordingDispatch {
+        effects: Vec<&'static str>,
+    }
+
+    impl PaletteDispatch for RecordingDispatch {
+        fn dispatch(&mut self, action: &KeyAssignment) -> anyhow::Result<()> {
+            self.effects
+                .extend(interpret_assignment(action).iter().map(describe_effect));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn selection_executes_mapped_effect() {
+        let commands = vec![
+            command("Activate tab 1", KeyAssignment::ActivateTab(TabIndex::absolute(0))),
+            command("Scroll to top", KeyAssignment::ScrollToTop),
+        ];
+        let matches = MatchResults {
+            selection: "top".to_string(),
+            matches: vec![1],
+        };
+
+        let selected = selected_command(&commands, Some(&matches), 0).unwrap();
+        assert_eq!(selected.action, KeyAssignment::ScrollToTop);
+        assert!(selected_command(&commands, Some(&matches), 1).is_none());
+        assert!(selected_command(&commands, None, 0).is_none());
+
+        let mut dispatch = RecordingDispatch::default();
+        assert!(dispatch.dispatch(&selected.action).is_ok());
+        assert_eq!(dispatch.effects, vec!["scroll_to_top", "invalidate"]);
+    }
+
+    #[test]
+    fn literal_scoring() {
+        assert!(literal_score(PaletteFuzzyAlgorithm::Substring, "TAB", "Activate tab 1").is_some());
+        assert!(literal_score(PaletteFuzzyAlgorithm::Substring, "tba", "Activate tab 1").is_none());
+        assert!(
+            literal_score(PaletteFuzzyAlgorithm::Substring, "act", "Activate tab")
+                > literal_score(PaletteFuzzyAlgorithm::Substring, "act", "Reactivate tab")
+        );
+
+        assert!(literal_score(PaletteFuzzyAlgorithm::Prefix, "tab", "Activate tab 1").is_some());
+        assert!(literal_score(PaletteFuzzyAlgorithm::Prefix, "ivate", "Activate tab 1").is_none());
+    }
+}
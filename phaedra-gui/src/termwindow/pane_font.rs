@@ -0,0 +1,291 @@
+//! Per-pane font scaling.  A scaled pane keeps the area that the tab
+//! layout assigns to it, which is measured in cells of the window font,
+//! and renders its content with a font that is `scale` times the size of
+//! the window font.  Only the rows and cols of the pane itself change,
+//! so the splits around it stay where they are.
+use crate::utilsprites::RenderMetrics;
+use mux::pane::PaneId;
+use mux::tab::{PositionedPane, TabId};
+use mux::Mux;
+use phaedra_font::FontConfiguration;
+use phaedra_term::TerminalSize;
+use std::rc::Rc;
+
+/// The range of scales that can be applied to a pane, relative to
+/// the window font
+const MIN_PANE_FONT_SCALE: f64 = 0.25;
+const MAX_PANE_FONT_SCALE: f64 = 4.0;
+
+/// The fonts and metrics used to render the panes that share a font scale
+pub struct PaneFont {
+    pub scale: f64,
+    pub fonts: Rc<FontConfiguration>,
+    pub render_metrics: RenderMetrics,
+    /// The font scale, dpi and config generation of the window fonts
+    /// that these fonts were derived from
+    basis: (u64, usize, usize),
+}
+
+impl PaneFont {
+    pub fn new(window_fonts: &FontConfiguration, scale: f64) -> anyhow::Result<Self> {
+        let dpi = window_fonts.get_dpi();
+        let fonts = Rc::new(FontConfiguration::new(Some(window_fonts.config()), dpi)?);
+        fonts.change_scaling(window_fonts.get_font_scale() * scale, dpi);
+        let render_metrics = RenderMetrics::new(&fonts)?;
+        Ok(Self {
+            scale,
+            fonts,
+            render_metrics,
+            basis: font_basis(window_fonts),
+        })
+    }
+
+    /// Returns false if the window fonts have been rescaled or
+    /// reconfigured since these fonts were built
+    pub fn is_current(&self, window_fonts: &FontConfiguration) -> bool {
+        self.basis == font_basis(window_fonts)
+    }
+}
+
+fn font_basis(fonts: &FontConfiguration) -> (u64, usize, usize) {
+    (
+        fonts.get_font_scale().to_bits(),
+        fonts.get_dpi(),
+        fonts.config().generation(),
+    )
+}
+
+/// Clamp `scale` to the supported range, returning None when it is
+/// close enough to 1.0 that the window fonts should be used instead
+fn normalize_pane_font_scale(scale: f64) -> Option<f64> {
+    let scale = scale.clamp(MIN_PANE_FONT_SCALE, MAX_PANE_FONT_SCALE);
+    if (scale - 1.0).abs() < 1e-6 {
+        None
+    } else {
+        Some(scale)
+    }
+}
+
+/// Compute the terminal size of a pane that the layout has given
+/// `pixel_width` x `pixel_height` pixels, when it is rendered with
+/// cells of `cell_width` x `cell_height`.  As for the window as a
+/// whole, the pixel dimensions are those of the whole cells that fit.
+pub fn pane_terminal_size(
+    pixel_width: usize,
+    pixel_height: usize,
+    cell_width: usize,
+    cell_height: usize,
+    dpi: u32,
+) -> TerminalSize {
+    let cols = (pixel_width / cell_width.max(1)).max(1);
+    let rows = (pixel_height / cell_height.max(1)).max(1);
+    TerminalSize {
+        rows,
+        cols,
+        pixel_width: cols * cell_width,
+        pixel_height: rows * cell_height,
+        dpi,
+    }
+}
+
+impl super::TermWindow {
+    /// Returns the fonts used to render `pane_id`, if it has been given
+    /// its own font scale
+    pub fn pane_font(&self, pane_id: PaneId) -> Option<Rc<PaneFont>> {
+        let scale = self.pane_state(pane_id).font_scale?;
+        let mut pane_fonts = self.pane_fonts.borrow_mut();
+        pane_fonts.retain(|_, font| font.is_current(&self.fonts));
+        if let Some(font) = pane_fonts.get(&scale.to_bits()) {
+            return Some(Rc::clone(font));
+        }
+        match PaneFont::new(&self.fonts, scale) {
+            Ok(font) => {
+                let font = Rc::new(font);
+                pane_fonts.insert(scale.to_bits(), Rc::clone(&font));
+                Some(font)
+            }
+            Err(err) => {
+                log::error!(
+                    "{:#} while attempting to scale the font of pane {} by {}",
+                    err,
+                    pane_id,
+                    scale
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns the metrics used to render `pane_id`
+    pub fn pane_render_metrics(&self, pane_id: PaneId) -> RenderMetrics {
+        match self.pane_font(pane_id) {
+            Some(font) => font.render_metrics,
+            None => self.render_metrics,
+        }
+    }
+
+    pub fn adjust_pane_font_size(&mut self, pane_id: PaneId, delta: f64) {
+        let scale = self.pane_state(pane_id).font_scale.unwrap_or(1.0);
+        let scale = if delta > 0.0 {
+            scale * 1.1
+        } else if delta < 0.0 {
+            scale / 1.1
+        } else {
+            return;
+        };
+        self.set_pane_font_scale(pane_id, normalize_pane_font_scale(scale));
+    }
+
+    pub fn reset_pane_font_size(&mut self, pane_id: PaneId) {
+        self.set_pane_font_scale(pane_id, None);
+    }
+
+    fn set_pane_font_scale(&mut self, pane_id: PaneId, scale: Option<f64>) {
+        if self.pane_state(pane_id).font_scale == scale {
+            return;
+        }
+        self.pane_state(pane_id).font_scale = scale;
+        self.quad_generation += 1;
+
+        let mux = Mux::get();
+        if let Some((_domain, _window, tab_id)) = mux.resolve_pane_id(pane_id) {
+            if let Some(tab) = mux.get_tab(tab_id) {
+                if let Some(pos) = tab
+                    .iter_panes()
+                    .into_iter()
+                    .find(|pos| pos.pane.pane_id() == pane_id)
+                {
+                    self.resize_pane_for_font(&pos);
+                }
+            }
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Resize the panes of `tab_id` that have their own font scale, so
+    /// that their terminal size reflects the number of their cells that
+    /// fit in the space that the layout has assigned to them.  The tab
+    /// sizes all of its panes in window cells whenever it is resized, so
+    /// this needs to be re-applied after that.
+    pub fn apply_pane_font_sizes(&self, tab_id: TabId) {
+        let mux = Mux::get();
+        let tab = match mux.get_tab(tab_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        for pos in tab.iter_panes() {
+            if self.pane_state(pos.pane.pane_id()).font_scale.is_some() {
+                self.resize_pane_for_font(&pos);
+            }
+        }
+    }
+
+    fn resize_pane_for_font(&self, pos: &PositionedPane) {
+        let cell_size = self.render_metrics.cell_size;
+        let pane_cell_size = self.pane_render_metrics(pos.pane.pane_id()).cell_size;
+        let size = pane_terminal_size(
+            pos.width * cell_size.width as usize,
+            pos.height * cell_size.height as usize,
+            pane_cell_size.width as usize,
+            pane_cell_size.height as usize,
+            self.dimensions.dpi as u32,
+        );
+        let dims = pos.pane.get_dimensions();
+        if dims.cols != size.cols
+            || dims.viewport_rows != size.rows
+            || dims.pixel_width != size.pixel_width
+            || dims.pixel_height != size.pixel_height
+        {
+            if let Err(err) = pos.pane.resize(size) {
+                log::error!("failed to resize pane {}: {:#}", pos.pane.pane_id(), err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCALES: [f64; 5] = [0.5, 0.75, 1.0, 1.5, 2.0];
+
+    #[test]
+    fn terminal_size_fits_whole_scaled_cells() {
+        // An 80x24 pane laid out with 8x16 window cells
+        let (pixel_width, pixel_height) = (80 * 8, 24 * 16);
+        let expected = [(160, 48), (106, 32), (80, 24), (53, 16), (40, 12)];
+        for (scale, (cols, rows)) in SCALES.iter().zip(expected) {
+            let cell_width = (8. * scale) as usize;
+            let cell_height = (16. * scale) as usize;
+            let size = pane_terminal_size(pixel_width, pixel_height, cell_width, cell_height, 96);
+            assert_eq!((size.cols, size.rows), (cols, rows), "scale {scale}");
+            assert_eq!(size.pixel_width, cols * cell_width);
+            assert_eq!(size.pixel_height, rows * cell_height);
+            assert!(size.pixel_width <= pixel_width && size.pixel_height <= pixel_height);
+            assert_eq!(size.dpi, 96);
+        }
+
+        // A pane is never sized smaller than a single cell
+        let size = pane_terminal_size(10, 10, 16, 32, 96);
+        assert_eq!((size.cols, size.rows), (1, 1));
+    }
+
+    #[test]
+    fn scale_is_clamped_and_normalized() {
+        assert_eq!(normalize_pane_font_scale(1.5), Some(1.5));
+        assert_eq!(normalize_pane_font_scale(1.1 * 1.1 / 1.1 / 1.1), None);
+        assert_eq!(normalize_pane_font_scale(0.01), Some(MIN_PANE_FONT_SCALE));
+        assert_eq!(normalize_pane_font_scale(100.), Some(MAX_PANE_FONT_SCALE));
+    }
+
+    #[test]
+    fn cell_size_follows_scale() {
+        config::use_test_configuration();
+        let window_fonts = Rc::new(FontConfiguration::new(None, 96).unwrap());
+        let window_metrics = RenderMetrics::new(&window_fonts).unwrap();
+        let layout_width = 80 * window_metrics.cell_size.width as usize;
+        let layout_height = 24 * window_metrics.cell_size.height as usize;
+
+        let mut prior_cols = usize::MAX;
+        for scale in SCALES {
+            let font = PaneFont::new(&window_fonts, scale).unwrap();
+            assert!(font.is_current(&window_fonts));
+            let metrics = &font.render_metrics;
+            let expected_width = window_metrics.cell_size.width as f64 * scale;
+            let expected_height = window_metrics.cell_size.height as f64 * scale;
+            // Cell dimensions are rounded up from the font metrics, so
+            // they may differ from the exact product by a pixel or so
+            assert!(
+                (metrics.cell_size.width as f64 - expected_width).abs() <= 1.5,
+                "scale {scale}: width {} vs {expected_width}",
+                metrics.cell_size.width
+            );
+            assert!(
+                (metrics.cell_size.height as f64 - expected_height).abs() <= 1.5,
+                "scale {scale}: height {} vs {expected_height}",
+                metrics.cell_size.height
+            );
+            assert!(metrics.underline_height >= 1);
+
+            let cell_width = metrics.cell_size.width as usize;
+            let cell_height = metrics.cell_size.height as usize;
+            let size = pane_terminal_size(layout_width, layout_height, cell_width, cell_height, 96);
+            assert!(size.pixel_width <= layout_width);
+            assert!(size.pixel_width + cell_width > layout_width);
+            assert!(size.pixel_height <= layout_height);
+            assert!(size.pixel_height + cell_height > layout_height);
+            assert!(size.cols <= prior_cols, "scale {scale}");
+            prior_cols = size.cols;
+            if scale == 1.0 {
+                assert_eq!((size.cols, size.rows), (80, 24));
+            }
+        }
+
+        window_fonts.change_scaling(1.2, 96);
+        let font = PaneFont::new(&window_fonts, 2.0).unwrap();
+        window_fonts.change_scaling(1.0, 96);
+        assert!(!font.is_current(&window_fonts));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::render_command::LayerId;
 use crate::termwindow::box_model::*;
 use config::observers::*;
 use crate::termwindow::modal::Modal;
@@ -148,7 +149,7 @@ impl PaneSelector {
                     ),
                     metrics: &metrics,
                     gl_state: term_window.render_state.as_ref().unwrap(),
-                    zindex: 100,
+                    zindex: LayerId::modal().zindex(),
                 },
                 &element,
             )?;
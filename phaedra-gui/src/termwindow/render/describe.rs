@@ -1,28 +1,603 @@
 use crate::frame::PaneFrame;
-use crate::render_command::{HsbTransform as CmdHsbTransform, RectF, RenderCommand};
+use crate::render_command::{LayerId, QuadMode, RectF, RenderCommand, TextureCoords};
 use crate::selection::{SelectionRange, SelectionX};
+use crate::termwindow::pane_font::PaneFont;
 use crate::termwindow::render::paint::AllowImage;
 use crate::termwindow::render::{
-    same_hyperlink, CursorProperties, LineCommandCacheValue, LineQuadCacheKey, LineSeed,
-    LineToEleShapeCacheKey, RenderScreenLineParams, RenderScreenLineResult,
+    effective_underline, hyperlink_style, place_line_commands, same_hyperlink, CursorProperties,
+    LineCommandCacheValue, LineQuadCacheKey, LineQuadFlags, LineQuadKeyFields, LineSeed,
+    LineToEleShapeCacheKey, RenderScreenLineParams,
 };
+use crate::termwindow::tooltip::TooltipSpec;
 use crate::termwindow::{ScrollHit, UIItem, UIItemType};
+use crate::utilsprites::RenderMetrics;
 use anyhow::Context;
-use ::window::DeadKeyStatus;
+use ::window::{DeadKeyStatus, PointF};
 use config::observers::*;
-use config::{TermConfig, VisualBellTarget};
-use mux::pane::{Pane, PaneId, PaneRenderSnapshot, TerminalView};
+use config::VisualBellTarget;
+use mux::pane::{Pane, PaneId, PaneRenderSnapshot, SearchResult, TerminalView};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
+use mux::search::search_lines;
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
-use ordered_float::NotNan;
 use phaedra_dynamic::Value;
 use phaedra_term::color::{ColorAttribute, ColorPalette};
-use phaedra_term::{Line, StableRowIndex, TerminalConfiguration};
+use phaedra_term::{CellAttributes, Line, StableRowIndex, TerminalConfiguration};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::Instant;
+use termwiz::cell::Underline;
+use termwiz::hyperlink::Hyperlink;
+use termwiz::image::ImageCell;
 use window::bitmaps::TextureRect;
 use window::color::LinearRgba;
 
+/// A run of cells on a single visible row that belong to the
+/// hyperlink currently under the mouse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HyperlinkHoverSpan {
+    pub line_idx: usize,
+    pub cols: Range<usize>,
+    /// true if the cells already carry an underline of their own,
+    /// in which case the hover line is drawn below it.
+    pub underlined: bool,
+}
+
+/// A run of cells on a single visible row that match the pattern
+/// set by `SetPaneHighlightPattern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PaneHighlightSpan {
+    pub line_idx: usize,
+    pub cols: Range<usize>,
+}
+
+/// The color of the pane highlight when the palette doesn't specify
+/// `pane_highlight_bg`
+const DEFAULT_PANE_HIGHLIGHT_BG: LinearRgba = LinearRgba::with_components(1.0, 0.6, 0.0, 0.35);
+
+/// Hashes the parts of a snapshot that determine how its pane is
+/// described.  The palette generation is included because applications
+/// can change the palette (eg: via OSC 4/10/11) without altering the
+/// content hash.
+pub(crate) fn snapshot_cache_key(snapshot: &PaneRenderSnapshot) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    snapshot.content_hash().hash(&mut hasher);
+    snapshot.palette_generation().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Folds the generations of the state that a pane is drawn with, but
+/// that isn't part of its snapshot, into the salt of its command hash
+/// and cache key: the window palette, which colors the cursor; the
+/// texture atlas, which the glyph quads refer to; and the
+/// configuration.
+pub(crate) fn pane_hash_salt(
+    palette_generation: usize,
+    atlas_generation: usize,
+    config_generation: usize,
+) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    palette_generation.hash(&mut hasher);
+    atlas_generation.hash(&mut hasher);
+    config_generation.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Fills the whole window, which is `width` by `height` pixels, with
+/// `background` drawn at `opacity`
+pub(crate) fn window_background_fill(
+    background: LinearRgba,
+    opacity: f32,
+    width: f32,
+    height: f32,
+) -> RenderCommand {
+    RenderCommand::FillRect {
+        layer: LayerId::background(),
+        rect: euclid::rect(0., 0., width, height),
+        color: background.mul_alpha(opacity),
+        hsv: None,
+    }
+}
+
+/// Returns the number of blank rows above the first line of a pane
+/// whose viewport is overscrolled past the top of its scrollback, and
+/// how many of its `line_count` lines fit below them.  The lines of
+/// such a pane start at `first_row`, the top of the scrollback, rather
+/// than at the top of the viewport.
+pub(crate) fn overscrolled_rows(
+    viewport: Option<StableRowIndex>,
+    first_row: StableRowIndex,
+    line_count: usize,
+    viewport_rows: usize,
+) -> (usize, usize) {
+    let blank_rows = viewport
+        .map_or(0, |top| (first_row - top).max(0) as usize)
+        .min(viewport_rows);
+    (blank_rows, line_count.min(viewport_rows - blank_rows))
+}
+
+pub(crate) fn palette_hash(palette: &ColorPalette) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    palette.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Formats an error from describing a pane as a single line, for
+/// display in the placeholder that stands in for the pane
+pub(crate) fn pane_error_message(err: &anyhow::Error) -> String {
+    let message = format!("{err:#}");
+    let message: Vec<&str> = message.lines().map(str::trim).collect();
+    format!("Unable to render this pane: {}", message.join(" "))
+}
+
+/// Builds the frame for a pane that could not be described: a
+/// background over `bounds`, followed by the commands that show the
+/// error.  The frame has no cache key, so that the pane is described
+/// again on the next paint rather than reusing the placeholder.
+pub(crate) fn placeholder_pane_frame(
+    pane_id: PaneId,
+    is_active: bool,
+    bounds: RectF,
+    background: LinearRgba,
+    message: Vec<RenderCommand>,
+) -> PaneFrame {
+    let mut commands = vec![RenderCommand::FillRect {
+        layer: LayerId::background(),
+        rect: bounds,
+        color: background,
+        hsv: None,
+    }];
+    commands.extend(message);
+    let command_hash = RenderCommand::content_hash(&commands);
+
+    PaneFrame {
+        pane_id,
+        is_active,
+        bounds,
+        command_hash,
+        cache_key: None,
+        commands: commands.into(),
+        ui_items: vec![],
+        last_execution_stats: None,
+        skip_streak: 0,
+        hsv: None,
+    }
+}
+
+/// Remembers which describe errors have been logged, so that a pane
+/// that fails the same way on every paint is only logged once
+#[derive(Debug, Default)]
+pub(crate) struct DescribeFailures {
+    logged: HashSet<(PaneId, String)>,
+}
+
+impl DescribeFailures {
+    /// Returns true if `pane_id` has not failed with `message` before
+    pub fn is_new(&mut self, pane_id: PaneId, message: &str) -> bool {
+        self.logged.insert((pane_id, message.to_string()))
+    }
+
+    /// Forgets the failures of panes that are no longer painted
+    pub fn retain_panes(&mut self, pane_ids: &[PaneId]) {
+        self.logged
+            .retain(|(pane_id, _)| pane_ids.contains(pane_id));
+    }
+}
+
+/// Returns the frame produced by `describe`.  If that fails or panics,
+/// the error is logged (once for each distinct error of the pane) and
+/// the frame produced by `placeholder` is returned instead, so that a
+/// single pane can't prevent the rest of the window from painting.
+/// `failures` is only borrowed once `describe` has returned.
+pub(crate) fn describe_or_placeholder(
+    pane_id: PaneId,
+    failures: &RefCell<DescribeFailures>,
+    describe: impl FnOnce() -> anyhow::Result<PaneFrame>,
+    placeholder: impl FnOnce(&anyhow::Error) -> PaneFrame,
+) -> PaneFrame {
+    match catch_panic(describe) {
+        Ok(frame) => frame,
+        Err(err) => {
+            if failures.borrow_mut().is_new(pane_id, &format!("{err:#}")) {
+                log::error!("pane {pane_id}: unable to describe: {err:#}");
+            }
+            placeholder(&err)
+        }
+    }
+}
+
+/// The error that stands in for a panic caught by `catch_panic`
+#[derive(Debug, thiserror::Error)]
+#[error("panicked: {0}")]
+pub(crate) struct Panicked(pub String);
+
+/// Calls `f`, returning a `Panicked` error if it panics.
+/// RefCell borrows held by `f` are released as it unwinds, but any
+/// state that `f` was part way through updating is left as it was;
+/// callers are responsible for discarding it.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            s.to_string()
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.clone()
+        } else {
+            "unknown panic".to_string()
+        };
+        Err(Panicked(message).into())
+    })
+}
+
+/// The number of consecutive paints of a window that may panic before
+/// its rendering is disabled
+pub(crate) const MAX_CONSECUTIVE_PAINT_PANICS: usize = 3;
+
+/// Tracks the paints of a window that panicked, so that a window that
+/// panics on every paint shows a static error rather than panicking in
+/// a loop
+#[derive(Debug, Default)]
+pub(crate) struct PaintPanics {
+    consecutive: usize,
+    disabled: Option<String>,
+}
+
+impl PaintPanics {
+    /// Returns the error to show in place of the window's content, if
+    /// its rendering has been disabled
+    pub fn disabled(&self) -> Option<&str> {
+        self.disabled.as_deref()
+    }
+
+    /// Records the result of a paint, disabling rendering once
+    /// `MAX_CONSECUTIVE_PAINT_PANICS` paints in a row have panicked.
+    /// Returns true if `result` is a panic.
+    pub fn record<T>(&mut self, result: &anyhow::Result<T>) -> bool {
+        let panic = match result {
+            Err(err) => err.root_cause().downcast_ref::<Panicked>(),
+            Ok(_) => None,
+        };
+        match panic {
+            Some(panic) => {
+                self.consecutive += 1;
+                if self.consecutive >= MAX_CONSECUTIVE_PAINT_PANICS && self.disabled.is_none() {
+                    log::error!(
+                        "rendering panicked {} times in a row; disabling rendering of this window",
+                        self.consecutive
+                    );
+                    self.disabled.replace(format!(
+                        "Rendering of this window has been disabled \
+                         because it failed repeatedly: {panic}"
+                    ));
+                }
+                true
+            }
+            None => {
+                self.consecutive = 0;
+                false
+            }
+        }
+    }
+}
+
+/// The panes whose rendering has been frozen with SetPaneRenderFrozen,
+/// which keep showing the frame that they had when they were frozen
+#[derive(Debug, Default)]
+pub(crate) struct FrozenPanes {
+    panes: HashSet<PaneId>,
+}
+
+impl FrozenPanes {
+    pub fn contains(&self, pane_id: PaneId) -> bool {
+        self.panes.contains(&pane_id)
+    }
+
+    /// Freezes or unfreezes `pane_id`, returning true if that changed
+    /// its state.  Unfreezing discards the prior frame of the pane from
+    /// `frames`, so that the pane is described again on the next paint
+    /// even if its cache key happens to match that frame.
+    pub fn set(
+        &mut self,
+        pane_id: PaneId,
+        frozen: bool,
+        frames: &mut HashMap<PaneId, PaneFrame>,
+    ) -> bool {
+        if frozen {
+            return self.panes.insert(pane_id);
+        }
+        if !self.panes.remove(&pane_id) {
+            return false;
+        }
+        frames.remove(&pane_id);
+        true
+    }
+}
+
+/// Returns the prior frame of a pane if paint_pass can reuse it rather
+/// than describing the pane again.  A frozen pane reuses whatever frame
+/// it has; any other pane only reuses a frame with the same cache key.
+pub(crate) fn reusable_pane_frame(
+    prior: Option<&PaneFrame>,
+    cache_key: u64,
+    frozen: bool,
+) -> Option<&PaneFrame> {
+    prior.filter(|frame| frozen || frame.cache_key == Some(cache_key))
+}
+
+/// Returns the command that draws the indicator of a frozen pane: a
+/// small square of `size` pixels, inset by `size` from the top right
+/// corner of the pane `bounds`
+pub(crate) fn frozen_pane_indicator(bounds: RectF, size: f32, color: LinearRgba) -> RenderCommand {
+    RenderCommand::FillRect {
+        layer: LayerId::overlay(),
+        rect: euclid::rect(
+            bounds.max_x() - 2. * size,
+            bounds.min_y() + size,
+            size,
+            size,
+        ),
+        color,
+        hsv: None,
+    }
+}
+
+/// Returns the commands that draw the border of a pane that input is
+/// being broadcast to: four edges of `thickness` pixels just inside the
+/// pane `bounds`
+pub(crate) fn broadcast_pane_border(
+    bounds: RectF,
+    thickness: f32,
+    color: LinearRgba,
+) -> [RenderCommand; 4] {
+    let edge = |x: f32, y: f32, width: f32, height: f32| RenderCommand::FillRect {
+        layer: LayerId::overlay(),
+        rect: euclid::rect(x, y, width, height),
+        color,
+        hsv: None,
+    };
+    [
+        edge(bounds.min_x(), bounds.min_y(), bounds.width(), thickness),
+        edge(
+            bounds.min_x(),
+            bounds.max_y() - thickness,
+            bounds.width(),
+            thickness,
+        ),
+        edge(bounds.min_x(), bounds.min_y(), thickness, bounds.height()),
+        edge(
+            bounds.max_x() - thickness,
+            bounds.min_y(),
+            thickness,
+            bounds.height(),
+        ),
+    ]
+}
+
+/// Returns the command that draws a split as `rect`.  With
+/// `vector_chrome`, the split is a line along the center of `rect`
+/// rather than a fill of it.
+pub(crate) fn split_command(rect: RectF, color: LinearRgba, vector_chrome: bool) -> RenderCommand {
+    if !vector_chrome {
+        return RenderCommand::FillRect {
+            layer: LayerId::overlay(),
+            rect,
+            color,
+            hsv: None,
+        };
+    }
+    let center = rect.center();
+    let (from, to, width) = if rect.height() >= rect.width() {
+        (
+            euclid::point2(center.x, rect.min_y()),
+            euclid::point2(center.x, rect.max_y()),
+            rect.width(),
+        )
+    } else {
+        (
+            euclid::point2(rect.min_x(), center.y),
+            euclid::point2(rect.max_x(), center.y),
+            rect.height(),
+        )
+    };
+    RenderCommand::DrawLine {
+        layer: LayerId::overlay(),
+        from,
+        to,
+        width,
+        color,
+    }
+}
+
+/// Returns the color of the line that is drawn under a hovered link:
+/// that of the style of the rule that made the link, else that of
+/// the underline of the cell, else its foreground color
+pub(crate) fn hyperlink_hover_color(attrs: &CellAttributes, palette: &ColorPalette) -> LinearRgba {
+    if let Some(color) = hyperlink_style(attrs).and_then(|style| style.hover_color) {
+        return color.to_linear_tuple_rgba();
+    }
+    match attrs.underline_color() {
+        ColorAttribute::Default => palette.resolve_fg(attrs.foreground()),
+        c => palette.resolve_fg(c),
+    }
+    .to_linear()
+}
+
+/// Computes the hover spans for `highlight` across `lines`.
+/// A link that wraps onto the following row yields one span per row.
+pub(crate) fn hyperlink_hover_spans(
+    lines: &[Line],
+    highlight: Option<&Arc<Hyperlink>>,
+) -> Vec<HyperlinkHoverSpan> {
+    let mut spans = vec![];
+    if highlight.is_none() {
+        return spans;
+    }
+
+    for (line_idx, line) in lines.iter().enumerate() {
+        let mut current: Option<HyperlinkHoverSpan> = None;
+        for cell in line.visible_cells() {
+            let attrs = cell.attrs();
+            let idx = cell.cell_index();
+            if !same_hyperlink(attrs.hyperlink(), highlight) {
+                spans.extend(current.take());
+                continue;
+            }
+            let underlined = effective_underline(attrs) != Underline::None;
+            match current.as_mut() {
+                Some(span) if span.cols.end == idx && span.underlined == underlined => {
+                    span.cols.end = idx + cell.width();
+                }
+                _ => {
+                    spans.extend(current.take());
+                    current.replace(HyperlinkHoverSpan {
+                        line_idx,
+                        cols: idx..idx + cell.width(),
+                        underlined,
+                    });
+                }
+            }
+        }
+        spans.extend(current.take());
+    }
+
+    spans
+}
+
+/// Converts the matches in `results` into spans of the `num_rows`
+/// visible rows that start at `first_row`.  A match that wraps onto
+/// the following rows yields one span per row, running to the edge of
+/// the `cols` wide pane on every row but its last.
+pub(crate) fn pane_highlight_spans(
+    results: &[SearchResult],
+    first_row: StableRowIndex,
+    num_rows: usize,
+    cols: usize,
+) -> Vec<PaneHighlightSpan> {
+    let mut spans = vec![];
+    for result in results {
+        for row in result.start_y.max(first_row)..=result.end_y {
+            let line_idx = (row - first_row) as usize;
+            if line_idx >= num_rows {
+                break;
+            }
+            let start = if row == result.start_y {
+                result.start_x
+            } else {
+                0
+            };
+            let end = if row == result.end_y {
+                result.end_x.min(cols)
+            } else {
+                cols
+            };
+            if start < end {
+                spans.push(PaneHighlightSpan {
+                    line_idx,
+                    cols: start..end,
+                });
+            }
+        }
+    }
+    spans
+}
+
+/// Emits the highlight for `spans` as fills on the background layer.
+/// They follow the line commands, which put the backgrounds of the
+/// cells on that same layer, so they are drawn over those backgrounds
+/// but beneath the glyphs on the text layer.
+pub(crate) fn pane_highlight_commands(
+    spans: &[PaneHighlightSpan],
+    cell_width: f32,
+    cell_height: f32,
+    top_pixel_y: f32,
+    left_pixel_x: f32,
+    color: LinearRgba,
+) -> Vec<RenderCommand> {
+    spans
+        .iter()
+        .map(|span| RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(
+                left_pixel_x + span.cols.start as f32 * cell_width,
+                top_pixel_y + span.line_idx as f32 * cell_height,
+                span.cols.len() as f32 * cell_width,
+                cell_height,
+            ),
+            color,
+            hsv: None,
+        })
+        .collect()
+}
+
+/// Returns the position of the quad that draws `image` in the cell at
+/// `cell_rect`, and the texture coords of the part of the image that
+/// the cell shows.  `sprite_rect` is where the image was placed in an
+/// atlas of `atlas_size` pixels.  The coordinates of an image cell are
+/// fractions of the whole image, so a cell that is the visible part of
+/// a placement that has partly scrolled out of the viewport maps onto
+/// just its rows of the sprite.  Because they are scaled by the size of
+/// the sprite, they map in the same way when `AllowImage::Scale` has
+/// downsampled the image to fit it into the atlas.
+pub(crate) fn image_cell_quad(
+    image: &ImageCell,
+    cell_rect: RectF,
+    sprite_rect: RectF,
+    atlas_size: (f32, f32),
+) -> (RectF, TextureCoords) {
+    let top_left = image.top_left();
+    let bottom_right = image.bottom_right();
+    let (atlas_width, atlas_height) = atlas_size;
+
+    // The texture coords are computed in floating point rather than
+    // via the integer pixel coordinates of the atlas, which would lose
+    // precision and show as seams between the cells of the image
+    let texture = TextureCoords {
+        left: (sprite_rect.origin.x + *top_left.x * sprite_rect.size.width) / atlas_width,
+        top: (sprite_rect.origin.y + *top_left.y * sprite_rect.size.height) / atlas_height,
+        right: (sprite_rect.origin.x + *bottom_right.x * sprite_rect.size.width) / atlas_width,
+        bottom: (sprite_rect.origin.y + *bottom_right.y * sprite_rect.size.height) / atlas_height,
+    };
+
+    let (padding_left, padding_top, padding_right, padding_bottom) = image.padding();
+    let position = euclid::rect(
+        cell_rect.origin.x + padding_left as f32,
+        cell_rect.origin.y + padding_top as f32,
+        cell_rect.size.width - padding_right as f32,
+        cell_rect.size.height - padding_bottom as f32,
+    );
+
+    (position, texture)
+}
+
+/// Wraps the image quads of a pane in a batch that clips them to the
+/// `clip` rect of the pane.  Each quad is also clipped here, which
+/// narrows its texture coords to the part of the image that is still
+/// inside the pane, so that an image that extends beyond the pane
+/// never spills into a neighboring pane.
+pub(crate) fn pane_image_commands(quads: Vec<RenderCommand>, clip: RectF) -> Option<RenderCommand> {
+    let mut commands = vec![RenderCommand::SetClipRect(Some(clip))];
+    commands.extend(
+        quads
+            .into_iter()
+            .map(|quad| quad.clip_to_rect(&clip))
+            .filter(|quad| !matches!(quad, RenderCommand::Nop)),
+    );
+    if commands.len() == 1 {
+        return None;
+    }
+    commands.push(RenderCommand::SetClipRect(None));
+    Some(RenderCommand::Batch(commands))
+}
+
 impl crate::TermWindow {
     pub fn describe_window_borders(&self) -> Vec<RenderCommand> {
         let border_dimensions = self.get_os_border();
@@ -40,8 +615,7 @@ impl crate::TermWindow {
             if border_top > 0.0 {
                 let rect: RectF = euclid::rect(0.0, 0.0, width, border_top);
                 commands.push(RenderCommand::FillRect {
-                    layer: 1,
-                    zindex: 0,
+                    layer: LayerId::text(),
                     rect,
                     color: self
                         .config
@@ -58,8 +632,7 @@ impl crate::TermWindow {
             if border_left > 0.0 {
                 let rect: RectF = euclid::rect(0.0, 0.0, border_left, height);
                 commands.push(RenderCommand::FillRect {
-                    layer: 1,
-                    zindex: 0,
+                    layer: LayerId::text(),
                     rect,
                     color: self
                         .config
@@ -76,8 +649,7 @@ impl crate::TermWindow {
             if border_bottom > 0.0 {
                 let rect: RectF = euclid::rect(0.0, height - border_bottom, width, height);
                 commands.push(RenderCommand::FillRect {
-                    layer: 1,
-                    zindex: 0,
+                    layer: LayerId::text(),
                     rect,
                     color: self
                         .config
@@ -94,8 +666,7 @@ impl crate::TermWindow {
             if border_right > 0.0 {
                 let rect: RectF = euclid::rect(width - border_right, 0.0, border_right, height);
                 commands.push(RenderCommand::FillRect {
-                    layer: 1,
-                    zindex: 0,
+                    layer: LayerId::text(),
                     rect,
                     color: self
                         .config
@@ -116,6 +687,7 @@ impl crate::TermWindow {
         &self,
         split: &PositionedSplit,
         pane: &Arc<dyn Pane>,
+        tooltip: Option<TooltipSpec>,
     ) -> (Vec<RenderCommand>, Vec<UIItem>) {
         let palette = pane.palette();
         let foreground = palette.split.to_linear();
@@ -134,6 +706,7 @@ impl crate::TermWindow {
         let pos_y = split.top as f32 * cell_height + first_row_offset + padding_top;
         let pos_x = split.left as f32 * cell_width + padding_left + border.left.get() as f32;
 
+        let vector_chrome = self.config.gpu().experimental_vector_chrome;
         let mut commands = Vec::with_capacity(1);
         let mut ui_items = Vec::with_capacity(1);
 
@@ -144,13 +717,7 @@ impl crate::TermWindow {
                 self.render_metrics.underline_height as f32,
                 (1.0 + split.size as f32) * cell_height,
             );
-            commands.push(RenderCommand::FillRect {
-                layer: 2,
-                zindex: 0,
-                rect,
-                color: foreground,
-                hsv: None,
-            });
+            commands.push(split_command(rect, foreground, vector_chrome));
             ui_items.push(UIItem {
                 x: border.left.get() as usize
                     + padding_left as usize
@@ -161,6 +728,7 @@ impl crate::TermWindow {
                     + split.top * cell_height as usize,
                 height: split.size * cell_height as usize,
                 item_type: UIItemType::Split(split.clone()),
+                tooltip,
             });
         } else {
             let rect: RectF = euclid::rect(
@@ -169,13 +737,7 @@ impl crate::TermWindow {
                 (1.0 + split.size as f32) * cell_width,
                 self.render_metrics.underline_height as f32,
             );
-            commands.push(RenderCommand::FillRect {
-                layer: 2,
-                zindex: 0,
-                rect,
-                color: foreground,
-                hsv: None,
-            });
+            commands.push(split_command(rect, foreground, vector_chrome));
             ui_items.push(UIItem {
                 x: border.left.get() as usize
                     + padding_left as usize
@@ -186,6 +748,7 @@ impl crate::TermWindow {
                     + split.top * cell_height as usize,
                 height: cell_height as usize,
                 item_type: UIItemType::Split(split.clone()),
+                tooltip,
             });
         }
 
@@ -201,12 +764,7 @@ impl crate::TermWindow {
 
         match (self.window_background.is_empty(), self.allow_images) {
             (false, AllowImage::Yes | AllowImage::Scale(_)) => {
-                let bg_color = self
-                    .palette
-                    .as_ref()
-                    .map(|palette| palette.background)
-                    .unwrap_or_else(|| config::TermConfig::new().color_palette().background)
-                    .to_linear();
+                let bg_color = self.window_palette().background.to_linear();
                 let top = panes
                     .iter()
                     .find(|p| p.is_active)
@@ -235,43 +793,40 @@ impl crate::TermWindow {
         let background = if panes.len() == 1 {
             panes[0].pane.palette().background
         } else {
-            self.palette
-                .as_ref()
-                .map(|palette| palette.background)
-                .unwrap_or_else(|| config::TermConfig::new().color_palette().background)
+            self.window_palette().background
         }
-        .to_linear()
-        .mul_alpha(1.0);
+        .to_linear();
 
-        let rect: RectF = euclid::rect(
-            0.0,
-            0.0,
+        Ok(vec![window_background_fill(
+            background,
+            self.window_background_opacity(),
             self.dimensions.pixel_width as f32,
             self.dimensions.pixel_height as f32,
-        );
-
-        Ok(vec![RenderCommand::FillRect {
-            layer: 0,
-            zindex: 0,
-            rect,
-            color: background,
-            hsv: None,
-        }])
+        )])
     }
 
     pub fn describe_pane(&self, pos: &PositionedPane) -> anyhow::Result<PaneFrame> {
         let pane_id = pos.pane.pane_id();
         let current_viewport = self.get_viewport(pane_id);
         let snapshot = pos.pane.snapshot_for_render(current_viewport);
-        let cache_key = self.pane_describe_cache_key(pane_id, pos, snapshot.content_hash());
+        let cache_key = self.pane_describe_cache_key(pane_id, pos, &snapshot);
         self.describe_pane_with_snapshot(pos, snapshot, cache_key)
     }
 
+    /// The `pane_hash_salt` for the current state of the window
+    pub(crate) fn pane_hash_salt(&self) -> u64 {
+        pane_hash_salt(
+            self.config.palette_generation(),
+            self.shape_generation,
+            self.config.generation(),
+        )
+    }
+
     pub(crate) fn pane_describe_cache_key(
         &self,
         pane_id: PaneId,
         pos: &PositionedPane,
-        terminal_hash: u64,
+        snapshot: &PaneRenderSnapshot,
     ) -> u64 {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
@@ -289,12 +844,35 @@ impl crate::TermWindow {
         }
 
         let mut key_hasher = DefaultHasher::new();
-        terminal_hash.hash(&mut key_hasher);
+        snapshot_cache_key(snapshot).hash(&mut key_hasher);
+        // Overscrolling moves the lines down without changing them
+        overscrolled_rows(
+            self.get_viewport(pane_id),
+            snapshot.first_visible_row(),
+            snapshot.visible_lines().len(),
+            snapshot.dimensions().viewport_rows,
+        )
+        .hash(&mut key_hasher);
+        self.pane_hash_salt().hash(&mut key_hasher);
+        // The background of the pane is drawn at the window opacity
+        self.window_background_opacity()
+            .to_bits()
+            .hash(&mut key_hasher);
         pos.left.hash(&mut key_hasher);
         pos.top.hash(&mut key_hasher);
         pos.width.hash(&mut key_hasher);
         pos.height.hash(&mut key_hasher);
         pos.is_active.hash(&mut key_hasher);
+        // Cached frames have been moved by the content alignment offset
+        let (align_x, align_y) = self.content_alignment_offset();
+        align_x.to_bits().hash(&mut key_hasher);
+        align_y.to_bits().hash(&mut key_hasher);
+        // The focus of the window decides how the cursor is drawn
+        self.focused.is_some().hash(&mut key_hasher);
+        self.pane_state(pane_id)
+            .font_scale
+            .map(f64::to_bits)
+            .hash(&mut key_hasher);
 
         {
             let sel = self.selection(pane_id);
@@ -308,9 +886,73 @@ impl crate::TermWindow {
             sel.rectangular.hash(&mut key_hasher);
         }
 
+        // Hover styling is not part of the line commands, so the pane
+        // must be re-described when the highlighted link changes.
+        self.current_highlight
+            .as_ref()
+            .map(|link| Arc::as_ptr(link) as usize)
+            .hash(&mut key_hasher);
+        // Likewise the highlighted matches, which are found afresh
+        // whenever the pane is described
+        self.pane_highlight_pattern(pane_id)
+            .map(|highlight| highlight.pattern.clone())
+            .hash(&mut key_hasher);
+
         key_hasher.finish()
     }
 
+    /// Returns the area covered by the background of the pane, which
+    /// extends into the padding for panes at the edge of the window.
+    /// The pane is moved by the content alignment offset once it has
+    /// been described, so the edges of the window are offset to match.
+    fn pane_background_rect(&self, pos: &PositionedPane, top_pixel_y: f32) -> RectF {
+        let (padding_left, padding_top) = self.padding_left_top();
+        let (align_x, align_y) = self.content_alignment_offset();
+        let border = self.get_os_border();
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+
+        let (x, width_delta) = if pos.left == 0 {
+            (
+                -align_x,
+                align_x + padding_left + border.left.get() as f32 + (cell_width / 2.0),
+            )
+        } else {
+            (
+                padding_left + border.left.get() as f32 - (cell_width / 2.0)
+                    + (pos.left as f32 * cell_width),
+                cell_width,
+            )
+        };
+
+        let (y, height_delta) = if pos.top == 0 {
+            (
+                (top_pixel_y - padding_top - align_y),
+                align_y + padding_top + (cell_height / 2.0),
+            )
+        } else {
+            (
+                top_pixel_y + (pos.top as f32 * cell_height) - (cell_height / 2.0),
+                cell_height,
+            )
+        };
+
+        euclid::rect(
+            x,
+            y,
+            if pos.left + pos.width >= self.terminal_size.cols as usize {
+                self.dimensions.pixel_width as f32 - align_x - x
+            } else {
+                (pos.width as f32 * cell_width) + width_delta
+            },
+            if pos.top + pos.height >= self.terminal_size.rows as usize {
+                self.dimensions.pixel_height as f32 - align_y - y
+            } else {
+                (pos.height as f32 * cell_height) + height_delta
+            },
+        )
+    }
+
     pub fn describe_pane_with_snapshot(
         &self,
         pos: &PositionedPane,
@@ -319,6 +961,7 @@ impl crate::TermWindow {
     ) -> anyhow::Result<PaneFrame> {
         let config = &self.config;
         let pane_id = pos.pane.pane_id();
+        let pane_font = self.pane_font(pane_id);
 
         let (padding_left, padding_top) = self.padding_left_top();
         let tab_bar_height = if self.show_tab_bar {
@@ -340,7 +983,6 @@ impl crate::TermWindow {
 
         let gl_state = self.render_state.as_ref().unwrap();
         let white_space = gl_state.util_sprites.white_space.texture_coords();
-        let filled_box = gl_state.util_sprites.filled_box.texture_coords();
 
         let window_is_transparent = !self.window_background.is_empty();
 
@@ -354,68 +996,21 @@ impl crate::TermWindow {
                 config.text().text_background_opacity
             });
 
-        let cell_width = self.render_metrics.cell_size.width as f32;
-        let cell_height = self.render_metrics.cell_size.height as f32;
-
-        let background_rect = {
-            let (x, width_delta) = if pos.left == 0 {
-                (
-                    0.0,
-                    padding_left + border.left.get() as f32 + (cell_width / 2.0),
-                )
-            } else {
-                (
-                    padding_left + border.left.get() as f32 - (cell_width / 2.0)
-                        + (pos.left as f32 * cell_width),
-                    cell_width,
-                )
-            };
-
-            let (y, height_delta) = if pos.top == 0 {
-                ((top_pixel_y - padding_top), padding_top + (cell_height / 2.0))
-            } else {
-                (
-                    top_pixel_y + (pos.top as f32 * cell_height) - (cell_height / 2.0),
-                    cell_height,
-                )
-            };
-
-            euclid::rect(
-                x,
-                y,
-                if pos.left + pos.width >= self.terminal_size.cols as usize {
-                    self.dimensions.pixel_width as f32 - x
-                } else {
-                    (pos.width as f32 * cell_width) + width_delta
-                },
-                if pos.top + pos.height >= self.terminal_size.rows as usize {
-                    self.dimensions.pixel_height as f32 - y
-                } else {
-                    (pos.height as f32 * cell_height) + height_delta
-                },
-            )
-        };
-
-        let inactive_hsv = if pos.is_active {
-            None
-        } else {
-            Some(CmdHsbTransform {
-                hue: config.color_config().inactive_pane_hsb.hue,
-                saturation: config.color_config().inactive_pane_hsb.saturation,
-                brightness: config.color_config().inactive_pane_hsb.brightness,
-            })
-        };
+        let background_rect = self.pane_background_rect(pos, top_pixel_y);
 
         let mut commands = Vec::new();
         let mut ui_items = Vec::new();
 
         if self.window_background.is_empty() {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: 0,
+                layer: LayerId::background(),
                 rect: background_rect,
-                color: snapshot.palette().background.to_linear().mul_alpha(1.0),
-                hsv: inactive_hsv.clone(),
+                color: snapshot
+                    .palette()
+                    .background
+                    .to_linear()
+                    .mul_alpha(self.window_background_opacity()),
+                hsv: None,
             });
         }
 
@@ -435,7 +1030,12 @@ impl crate::TermWindow {
             let background = if window_is_transparent {
                 LinearRgba::with_components(r, g, b, intensity)
             } else {
-                let (r1, g1, b1, a) = snapshot.palette().background.to_linear().mul_alpha(1.0).tuple();
+                let (r1, g1, b1, a) = snapshot
+                    .palette()
+                    .background
+                    .to_linear()
+                    .mul_alpha(self.window_background_opacity())
+                    .tuple();
                 LinearRgba::with_components(
                     r1 + (r - r1) * intensity,
                     g1 + (g - g1) * intensity,
@@ -445,11 +1045,10 @@ impl crate::TermWindow {
             };
 
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: 0,
+                layer: LayerId::background(),
                 rect: background_rect,
                 color: background,
-                hsv: inactive_hsv.clone(),
+                hsv: None,
             });
         }
 
@@ -470,38 +1069,51 @@ impl crate::TermWindow {
             let color = snapshot.palette().scrollbar_thumb.to_linear();
             let padding = self.effective_right_padding(config) as f32;
             let thumb_x = self.dimensions.pixel_width - padding as usize - border.right.get();
-
-            ui_items.push(UIItem {
-                x: thumb_x,
-                width: padding as usize,
-                y: thumb_y_offset,
-                height: info.top,
-                item_type: UIItemType::AboveScrollThumb,
-            });
-            ui_items.push(UIItem {
-                x: thumb_x,
-                width: padding as usize,
-                y: abs_thumb_top,
-                height: thumb_size,
-                item_type: UIItemType::ScrollThumb,
-            });
-            ui_items.push(UIItem {
-                x: thumb_x,
-                width: padding as usize,
-                y: abs_thumb_top + thumb_size,
-                height: self
-                    .dimensions
-                    .pixel_height
-                    .saturating_sub(abs_thumb_top + thumb_size),
-                item_type: UIItemType::BelowScrollThumb,
-            });
+            // The scroll bar stays at the edge of the window rather than
+            // moving with the content, so it is described offset by the
+            // opposite of the content alignment
+            let (align_x, align_y) = self.content_alignment_offset();
+
+            let thumb_items = [
+                UIItem {
+                    x: thumb_x,
+                    width: padding as usize,
+                    y: thumb_y_offset,
+                    height: info.top,
+                    item_type: UIItemType::AboveScrollThumb,
+                    tooltip: None,
+                },
+                UIItem {
+                    x: thumb_x,
+                    width: padding as usize,
+                    y: abs_thumb_top,
+                    height: thumb_size,
+                    item_type: UIItemType::ScrollThumb,
+                    tooltip: None,
+                },
+                UIItem {
+                    x: thumb_x,
+                    width: padding as usize,
+                    y: abs_thumb_top + thumb_size,
+                    height: self
+                        .dimensions
+                        .pixel_height
+                        .saturating_sub(abs_thumb_top + thumb_size),
+                    item_type: UIItemType::BelowScrollThumb,
+                    tooltip: None,
+                },
+            ];
+            ui_items.extend(
+                thumb_items
+                    .into_iter()
+                    .map(|item| item.translated(-align_x, -align_y)),
+            );
 
             commands.push(RenderCommand::FillRect {
-                layer: 2,
-                zindex: 0,
+                layer: LayerId::overlay(),
                 rect: euclid::rect(
-                    thumb_x as f32,
-                    abs_thumb_top as f32,
+                    thumb_x as f32 - align_x,
+                    abs_thumb_top as f32 - align_y,
                     padding,
                     thumb_size as f32,
                 ),
@@ -519,11 +1131,7 @@ impl crate::TermWindow {
         let selection_bg = snapshot.palette().selection_bg.to_linear();
         let cursor_fg = snapshot.palette().cursor_fg.to_linear();
         let cursor_bg = snapshot.palette().cursor_bg.to_linear();
-        let global_palette = self
-            .palette
-            .as_ref()
-            .cloned()
-            .unwrap_or_else(|| TermConfig::new().color_palette());
+        let global_palette = self.window_palette();
         let cursor_is_default_color =
             snapshot.palette().cursor_fg == global_palette.cursor_fg
                 && snapshot.palette().cursor_bg == global_palette.cursor_bg;
@@ -535,11 +1143,15 @@ impl crate::TermWindow {
             selrange: Option<SelectionRange>,
             rectangular: bool,
             dims: RenderableDimensions,
+            /// The top left corner of the pane
             top_pixel_y: f32,
             left_pixel_x: f32,
+            pane_font: Option<&'a PaneFont>,
+            render_metrics: RenderMetrics,
             pos: &'a PositionedPane,
             cursor: &'a StableCursorPosition,
             palette: &'a ColorPalette,
+            palette_hash: u64,
             default_bg: LinearRgba,
             cursor_border_color: LinearRgba,
             selection_fg: LinearRgba,
@@ -549,7 +1161,6 @@ impl crate::TermWindow {
             foreground: LinearRgba,
             cursor_is_default_color: bool,
             white_space: TextureRect,
-            filled_box: TextureRect,
             window_is_transparent: bool,
             commands: Vec<RenderCommand>,
             line_cache_hits: usize,
@@ -569,23 +1180,42 @@ impl crate::TermWindow {
                     .map_or(0..0, |sel| sel.cols_for_row(stable_row, self.rectangular));
                 let selection = selrange.start..selrange.end.min(self.dims.cols);
 
+                let mut flags = LineQuadFlags::empty();
+                flags.set(LineQuadFlags::REVERSE_VIDEO, self.dims.reverse_video);
+
                 let (cursor, composing, password_input) = if self.cursor.y == stable_row {
+                    // The dimming of an inactive pane is applied when its
+                    // commands are executed, so being active only changes
+                    // how the cursor row is drawn, and the other lines
+                    // stay cached as focus moves between panes
+                    flags.set(LineQuadFlags::PANE_IS_ACTIVE, self.pos.is_active);
+                    flags.set(
+                        LineQuadFlags::DEAD_KEY_OR_LEADER,
+                        self.term_window.dead_key_status != DeadKeyStatus::None
+                            || self.term_window.leader_is_active(),
+                    );
+                    flags.set(
+                        LineQuadFlags::CURSOR_IS_DEFAULT_COLOR,
+                        self.cursor_is_default_color,
+                    );
+                    flags.set(
+                        LineQuadFlags::WINDOW_IS_FOCUSED,
+                        self.term_window.focused.is_some(),
+                    );
                     (
-                        Some(CursorProperties {
+                        Some(Box::new(CursorProperties {
                             position: StableCursorPosition {
                                 y: 0,
                                 ..*self.cursor
                             },
-                            dead_key_or_leader: self.term_window.dead_key_status
-                                != DeadKeyStatus::None
-                                || self.term_window.leader_is_active(),
                             cursor_fg: self.cursor_fg,
                             cursor_bg: self.cursor_bg,
                             cursor_border_color: self.cursor_border_color,
-                            cursor_is_default_color: self.cursor_is_default_color,
-                        }),
+                        })),
                         match (self.pos.is_active, &self.term_window.dead_key_status) {
-                            (true, DeadKeyStatus::Composing(composing)) => Some(composing.to_string()),
+                            (true, DeadKeyStatus::Composing(composing)) => {
+                                Some(self.term_window.intern_composing(composing))
+                            }
                             _ => None,
                         },
                         if self.term_window.config.terminal_features().detect_password_input {
@@ -605,47 +1235,41 @@ impl crate::TermWindow {
                 } else {
                     (None, None, false)
                 };
+                flags.set(LineQuadFlags::PASSWORD_INPUT, password_input);
 
                 let shape_hash = self.term_window.shape_hash_for_line(line);
-                let quad_key = LineQuadCacheKey {
-                    pane_id: self.pos.pane.pane_id(),
-                    pane_width: self.pos.width,
-                    pane_left: self.pos.left,
-                    password_input,
-                    pane_is_active: self.pos.is_active,
+                let line_origin = PointF::new(
+                    self.left_pixel_x,
+                    self.top_pixel_y
+                        + line_idx as f32 * self.render_metrics.cell_size.height as f32,
+                );
+                let font_scale = self.pane_font.map_or(1.0, |font| font.scale).to_bits();
+                let quad_key = LineQuadCacheKey::new(LineQuadKeyFields {
                     config_generation: self.term_window.config.generation(),
                     shape_generation: self.term_window.shape_generation,
                     quad_generation: self.term_window.quad_generation,
-                    composing: composing.clone(),
-                    selection: selection.clone(),
-                    cursor,
                     shape_hash,
-                    top_pixel_y: NotNan::new(self.top_pixel_y).unwrap()
-                        + (line_idx + self.pos.top) as f32
-                            * self.term_window.render_metrics.cell_size.height as f32,
-                    left_pixel_x: NotNan::new(self.left_pixel_x).unwrap(),
-                    phys_line_idx: line_idx,
-                    reverse_video: self.dims.reverse_video,
-                };
+                    palette_hash: self.palette_hash,
+                    font_scale,
+                    pane_width: self.pos.width as u32,
+                    phys_line_idx: line_idx as u32,
+                    selection: selection.start as u32..selection.end as u32,
+                    flags,
+                    composing,
+                    cursor_pane: cursor.as_ref().map(|_| self.pos.pane.pane_id()),
+                    cursor,
+                });
 
                 let seed = {
                     let mut cache = self.term_window.line_command_cache.borrow_mut();
                     match cache.get(&quad_key) {
                         Some(cached) => {
                             let expired = cached.expires.map(|i| Instant::now() >= i).unwrap_or(false);
-                            let hover_changed = if cached.invalidate_on_hover_change {
-                                !same_hyperlink(
-                                    cached.current_highlight.as_ref(),
-                                    self.term_window.current_highlight.as_ref(),
-                                )
-                            } else {
-                                false
-                            };
-                            if expired || hover_changed {
+                            if expired {
                                 LineSeed::Fresh
                             } else {
                                 self.term_window.update_next_frame_time(cached.expires);
-                                LineSeed::Cached(Arc::clone(&cached.commands))
+                                LineSeed::Cached(cached.commands.clone())
                             }
                         }
                         None => LineSeed::Fresh,
@@ -656,7 +1280,7 @@ impl crate::TermWindow {
                 match seed {
                     LineSeed::Cached(commands) => {
                         self.line_cache_hits += 1;
-                        self.commands.extend_from_slice(&commands);
+                        place_line_commands(&mut self.commands, &commands, line_origin);
                         return Ok(());
                     }
                     LineSeed::Fresh => {}
@@ -665,10 +1289,11 @@ impl crate::TermWindow {
                 let next_due = self.term_window.has_animation.borrow_mut().take();
                 let shape_key = LineToEleShapeCacheKey {
                     shape_hash,
-                    shape_generation: quad_key.shape_generation,
+                    shape_generation: self.term_window.shape_generation,
+                    font_scale,
                     composing: if self.cursor.y == stable_row && self.pos.is_active {
                         if let DeadKeyStatus::Composing(composing) = &self.term_window.dead_key_status {
-                            Some((self.cursor.x, composing.to_string()))
+                            Some((self.cursor.x, composing.text.clone()))
                         } else {
                             None
                         }
@@ -677,16 +1302,13 @@ impl crate::TermWindow {
                     },
                 };
 
-                let (line_commands, line_result): (
-                    Vec<RenderCommand>,
-                    RenderScreenLineResult,
-                ) = self
+                let line_commands = self
                     .term_window
                     .describe_screen_line(RenderScreenLineParams {
-                        top_pixel_y: *quad_key.top_pixel_y,
-                        left_pixel_x: self.left_pixel_x,
+                        top_pixel_y: 0.,
+                        left_pixel_x: 0.,
                         pixel_width: self.dims.cols as f32
-                            * self.term_window.render_metrics.cell_size.width as f32,
+                            * self.render_metrics.cell_size.width as f32,
                         stable_line_idx: Some(stable_row),
                         line,
                         selection,
@@ -696,7 +1318,6 @@ impl crate::TermWindow {
                         config: &self.term_window.config,
                         pane: Some(&self.pos.pane),
                         white_space: self.white_space,
-                        filled_box: self.filled_box,
                         cursor_border_color: self.cursor_border_color,
                         foreground: self.foreground,
                         is_active: self.pos.is_active,
@@ -709,12 +1330,13 @@ impl crate::TermWindow {
                         default_bg: self.default_bg,
                         font: None,
                         style: None,
+                        pane_font: self.pane_font,
                         use_pixel_positioning: self
                             .term_window
                             .config
                             .text()
                             .experimental_pixel_positioning,
-                        render_metrics: self.term_window.render_metrics,
+                        render_metrics: self.render_metrics,
                         shape_key: Some(shape_key),
                         password_input,
                     })
@@ -722,7 +1344,7 @@ impl crate::TermWindow {
 
                 let expires = self.term_window.has_animation.borrow().as_ref().cloned();
                 self.term_window.update_next_frame_time(next_due);
-                let line_commands: Arc<[RenderCommand]> = line_commands.into();
+                let line_commands = self.term_window.intern_line_commands(line_commands);
 
                 self.term_window
                     .line_command_cache
@@ -731,17 +1353,11 @@ impl crate::TermWindow {
                         quad_key,
                         LineCommandCacheValue {
                             expires,
-                            commands: Arc::clone(&line_commands),
-                            invalidate_on_hover_change: line_result.invalidate_on_hover_change,
-                            current_highlight: if line_result.invalidate_on_hover_change {
-                                self.term_window.current_highlight.clone()
-                            } else {
-                                None
-                            },
+                            commands: line_commands.clone(),
                         },
                     );
 
-                self.commands.extend_from_slice(&line_commands);
+                place_line_commands(&mut self.commands, &line_commands, line_origin);
                 Ok(())
             }
 
@@ -765,19 +1381,40 @@ impl crate::TermWindow {
             }
         }
 
+        // The pane is placed by the layout, in window cells, but its
+        // lines are sized by its own font
         let left_pixel_x = padding_left
             + border.left.get() as f32
             + (pos.left as f32 * self.render_metrics.cell_size.width as f32);
+        let pane_top_pixel_y =
+            top_pixel_y + (pos.top as f32 * self.render_metrics.cell_size.height as f32);
+        let pane_metrics = pane_font
+            .as_ref()
+            .map_or(self.render_metrics, |font| font.render_metrics);
+        // A viewport that is overscrolled past the top of the scrollback
+        // leaves rows of the pane background above the first line
+        let (blank_rows, line_count) = overscrolled_rows(
+            self.get_viewport(pane_id),
+            snapshot.first_visible_row(),
+            snapshot.visible_lines().len(),
+            dims.viewport_rows,
+        );
+        let visible_lines = &snapshot.visible_lines()[..line_count];
+        let lines_top_pixel_y =
+            pane_top_pixel_y + blank_rows as f32 * pane_metrics.cell_size.height as f32;
         let mut line_describer = LineDescriber {
             term_window: self,
             selrange,
             rectangular,
             dims,
-            top_pixel_y,
+            top_pixel_y: lines_top_pixel_y,
             left_pixel_x,
+            pane_font: pane_font.as_deref(),
+            render_metrics: pane_metrics,
             pos,
             cursor: &cursor,
             palette: snapshot.palette(),
+            palette_hash: palette_hash(snapshot.palette()),
             default_bg,
             cursor_border_color,
             selection_fg,
@@ -787,7 +1424,6 @@ impl crate::TermWindow {
             foreground,
             cursor_is_default_color,
             white_space,
-            filled_box,
             window_is_transparent,
             commands: Vec::new(),
             line_cache_hits: 0,
@@ -795,18 +1431,43 @@ impl crate::TermWindow {
         };
 
         line_describer
-            .describe_lines(snapshot.first_visible_row(), snapshot.visible_lines())
+            .describe_lines(snapshot.first_visible_row(), visible_lines)
             .context("error while describing pane lines")?;
         metrics::histogram!("gui.describe.line_cache_hit_rate").record(line_describer.line_cache_hit_rate());
 
         commands.append(&mut line_describer.commands);
+        commands.extend(self.describe_hyperlink_hover(
+            visible_lines,
+            snapshot.palette(),
+            &pane_metrics,
+            lines_top_pixel_y,
+            left_pixel_x,
+        ));
+        commands.extend(self.describe_pane_highlight(
+            pane_id,
+            &snapshot,
+            line_count,
+            &pane_metrics,
+            lines_top_pixel_y,
+            left_pixel_x,
+        ));
+        commands.extend(
+            self.describe_pane_images(
+                visible_lines,
+                &pane_metrics,
+                lines_top_pixel_y,
+                left_pixel_x,
+                background_rect,
+            )
+            .context("error while describing pane images")?,
+        );
         // DIAGNOSTIC: clip_to_rect disabled to isolate rendering bug
         // let commands: Vec<RenderCommand> = commands
         //     .into_iter()
         //     .map(|cmd| cmd.clip_to_rect(&background_rect))
         //     .filter(|cmd| !matches!(cmd, RenderCommand::Nop))
         //     .collect();
-        let command_hash = RenderCommand::content_hash(&commands);
+        let command_hash = RenderCommand::content_hash_with_salt(&commands, self.pane_hash_salt());
         let commands: Arc<[RenderCommand]> = commands.into();
 
         Ok(PaneFrame {
@@ -814,39 +1475,364 @@ impl crate::TermWindow {
             is_active: pos.is_active,
             bounds: background_rect,
             command_hash,
-            cache_key,
+            cache_key: Some(cache_key),
             commands,
             ui_items,
             last_execution_stats: None,
             skip_streak: 0,
+            hsv: None,
+        })
+    }
+
+    /// Describes the placeholder for a pane that could not be described:
+    /// the pane background with `err` on its first line, drawn in the
+    /// same way as the retro tab bar.
+    pub(crate) fn describe_pane_placeholder(
+        &self,
+        pos: &PositionedPane,
+        err: &anyhow::Error,
+    ) -> PaneFrame {
+        let palette = self.window_palette();
+        let (padding_left, padding_top) = self.padding_left_top();
+        let border = self.get_os_border();
+        // Computing the tab bar height may be what failed, in which
+        // case the placeholder overlaps the tab bar
+        let top_bar_height = if self.show_tab_bar && !self.config.tab_bar().tab_bar_at_bottom {
+            self.tab_bar_pixel_height().unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+        let bounds = self.pane_background_rect(pos, top_pixel_y);
+
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let message = self
+            .describe_error_line(
+                &pane_error_message(err),
+                padding_left + border.left.get() as f32 + pos.left as f32 * cell_width,
+                top_pixel_y + pos.top as f32 * cell_height,
+                pos.width,
+                &palette,
+            )
+            .unwrap_or_else(|line_err| {
+                // Still cover the pane, even if the message can't be shown
+                log::debug!(
+                    "pane {}: unable to describe the error placeholder: {line_err:#}",
+                    pos.pane.pane_id()
+                );
+                vec![]
+            });
+
+        placeholder_pane_frame(
+            pos.pane.pane_id(),
+            pos.is_active,
+            bounds,
+            palette
+                .background
+                .to_linear()
+                .mul_alpha(self.window_background_opacity()),
+            message,
+        )
+    }
+
+    /// Describes the static content shown in place of a window whose
+    /// rendering has been disabled: the window background with
+    /// `message` on its first line
+    pub(crate) fn describe_window_error(&self, message: &str) -> Vec<RenderCommand> {
+        let palette = self.window_palette();
+        let (padding_left, padding_top) = self.padding_left_top();
+        let border = self.get_os_border();
+        let width = self.dimensions.pixel_width as f32;
+        let height = self.dimensions.pixel_height as f32;
+        let cols = self.terminal_size.cols.max(1);
+
+        let mut commands = vec![window_background_fill(
+            palette.background.to_linear(),
+            self.window_background_opacity(),
+            width,
+            height,
+        )];
+        // Shaping the message may itself panic, as that is what
+        // rendering the window does; show just the background then
+        match catch_panic(|| {
+            self.describe_error_line(
+                message,
+                padding_left + border.left.get() as f32,
+                padding_top + border.top.get() as f32,
+                cols,
+                &palette,
+            )
+        }) {
+            Ok(line) => commands.extend(line),
+            Err(err) => log::debug!("unable to describe the window error: {err:#}"),
+        }
+        commands
+    }
+
+    /// Returns the palette for the window as a whole, as opposed to
+    /// that of any particular pane
+    pub(crate) fn window_palette(&self) -> Arc<ColorPalette> {
+        self.palette
+            .clone()
+            .unwrap_or_else(|| self.config.color_palette())
+    }
+
+    /// Describes `message` as a line of `cols` cells at the given
+    /// position, in the palette's red
+    fn describe_error_line(
+        &self,
+        message: &str,
+        left_pixel_x: f32,
+        top_pixel_y: f32,
+        cols: usize,
+        palette: &ColorPalette,
+    ) -> anyhow::Result<Vec<RenderCommand>> {
+        let mut attrs = CellAttributes::default();
+        attrs.set_foreground(ColorAttribute::PaletteIndex(1));
+        let mut line = Line::from_text(message, &attrs, 0, None);
+        line.resize(cols, 0);
+
+        let window_is_transparent = !self.window_background.is_empty();
+        let gl_state = self.render_state.as_ref().unwrap();
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+        let default_bg = palette
+            .resolve_bg(ColorAttribute::Default)
+            .to_linear()
+            .mul_alpha(if window_is_transparent {
+                0.0
+            } else {
+                self.config.text().text_background_opacity
+            });
+        let cursor = StableCursorPosition::default();
+
+        self.describe_screen_line(RenderScreenLineParams {
+            top_pixel_y,
+            left_pixel_x,
+            pixel_width: cols as f32 * self.render_metrics.cell_size.width as f32,
+            stable_line_idx: None,
+            line: &line,
+            selection: 0..0,
+            cursor: &cursor,
+            palette,
+            dims: &RenderableDimensions {
+                cols,
+                physical_top: 0,
+                scrollback_rows: 0,
+                scrollback_top: 0,
+                viewport_rows: 1,
+                dpi: self.terminal_size.dpi,
+                pixel_height: self.render_metrics.cell_size.height as usize,
+                pixel_width: self.terminal_size.pixel_width,
+                reverse_video: false,
+            },
+            config: &self.config,
+            cursor_border_color: LinearRgba::default(),
+            foreground: palette.foreground.to_linear(),
+            pane: None,
+            is_active: true,
+            selection_fg: LinearRgba::default(),
+            selection_bg: LinearRgba::default(),
+            cursor_fg: LinearRgba::default(),
+            cursor_bg: LinearRgba::default(),
+            cursor_is_default_color: true,
+            white_space,
+            window_is_transparent,
+            default_bg,
+            style: None,
+            font: None,
+            pane_font: None,
+            use_pixel_positioning: self.config.text().experimental_pixel_positioning,
+            render_metrics: self.render_metrics,
+            shape_key: None,
+            password_input: false,
         })
     }
 
+    /// Emits the hover underline for the highlighted hyperlink as
+    /// overlay rects above the line content, so that moving the mouse
+    /// never invalidates the cached line commands.
+    fn describe_hyperlink_hover(
+        &self,
+        lines: &[Line],
+        palette: &ColorPalette,
+        metrics: &RenderMetrics,
+        top_pixel_y: f32,
+        left_pixel_x: f32,
+    ) -> Vec<RenderCommand> {
+        let spans = hyperlink_hover_spans(lines, self.current_highlight.as_ref());
+        if spans.is_empty() {
+            return vec![];
+        }
+
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+        let underline_height = metrics.underline_height as f32;
+
+        spans
+            .into_iter()
+            .filter_map(|span| {
+                let cell = lines[span.line_idx].get_cell(span.cols.start)?;
+                let color = hyperlink_hover_color(cell.attrs(), palette);
+                let row = if span.underlined {
+                    metrics.descender_plus_two as f32
+                } else {
+                    metrics.descender_row as f32
+                };
+                let line_top = top_pixel_y + span.line_idx as f32 * cell_height;
+
+                Some(RenderCommand::FillRect {
+                    layer: LayerId::overlay().with_zindex(1),
+                    rect: euclid::rect(
+                        left_pixel_x + span.cols.start as f32 * cell_width,
+                        line_top + row,
+                        span.cols.len() as f32 * cell_width,
+                        underline_height,
+                    ),
+                    color,
+                    hsv: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Emits the highlight for the matches of the pane's highlight
+    /// pattern in the first `line_count` of its visible lines.  The
+    /// matches are kept out of the
+    /// line commands so that changing the pattern never invalidates
+    /// the cached lines.
+    fn describe_pane_highlight(
+        &self,
+        pane_id: PaneId,
+        snapshot: &PaneRenderSnapshot,
+        line_count: usize,
+        metrics: &RenderMetrics,
+        top_pixel_y: f32,
+        left_pixel_x: f32,
+    ) -> Vec<RenderCommand> {
+        let Some(highlight) = self.pane_highlight_pattern(pane_id) else {
+            return vec![];
+        };
+        let lines = &snapshot.visible_lines()[..line_count];
+        let first_row = snapshot.first_visible_row();
+        let results = search_lines(&highlight.compiled, lines, first_row);
+        let spans =
+            pane_highlight_spans(&results, first_row, lines.len(), snapshot.dimensions().cols);
+        if spans.is_empty() {
+            return vec![];
+        }
+
+        let color = self
+            .config
+            .color_config()
+            .resolved_palette
+            .pane_highlight_bg
+            .as_deref()
+            .map_or(DEFAULT_PANE_HIGHLIGHT_BG, |color| color.to_linear());
+        pane_highlight_commands(
+            &spans,
+            metrics.cell_size.width as f32,
+            metrics.cell_size.height as f32,
+            top_pixel_y,
+            left_pixel_x,
+            color,
+        )
+    }
+
+    /// Emits the images of the cells of `lines`: those with a negative
+    /// z-index beneath the glyphs and the others above them.  They are
+    /// kept out of the line commands, which are placed relative to
+    /// their line, so that they can be clipped to the `clip` rect of
+    /// the pane as a whole.
+    fn describe_pane_images(
+        &self,
+        lines: &[Line],
+        metrics: &RenderMetrics,
+        top_pixel_y: f32,
+        left_pixel_x: f32,
+        clip: RectF,
+    ) -> anyhow::Result<Option<RenderCommand>> {
+        if self.allow_images == AllowImage::No {
+            return Ok(None);
+        }
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+        let padding =
+            (metrics.cell_size.height.max(metrics.cell_size.width) as usize).next_power_of_two();
+
+        let mut quads = vec![];
+        for (line_idx, line) in lines.iter().enumerate() {
+            for cell in line.visible_cells() {
+                let Some(images) = cell.attrs().images() else {
+                    continue;
+                };
+                let cell_rect = euclid::rect(
+                    left_pixel_x + cell.cell_index() as f32 * cell_width,
+                    top_pixel_y + line_idx as f32 * cell_height,
+                    cell_width,
+                    cell_height,
+                );
+                for image in images {
+                    let (sprite, next_due, _load_state) = gl_state
+                        .glyph_cache
+                        .borrow_mut()
+                        .cached_image(image.image_data(), Some(padding), self.allow_images)
+                        .context("cached_image")?;
+                    self.update_next_frame_time(next_due);
+
+                    let sprite_rect = euclid::rect(
+                        sprite.coords.origin.x as f32,
+                        sprite.coords.origin.y as f32,
+                        sprite.coords.size.width as f32,
+                        sprite.coords.size.height as f32,
+                    );
+                    let atlas_size = (
+                        sprite.texture.width() as f32,
+                        sprite.texture.height() as f32,
+                    );
+                    let (position, texture) =
+                        image_cell_quad(&image, cell_rect, sprite_rect, atlas_size);
+                    quads.push(RenderCommand::DrawQuad {
+                        layer: if image.z_index() < 0 {
+                            LayerId::background()
+                        } else {
+                            LayerId::overlay()
+                        },
+                        position,
+                        texture,
+                        fg_color: LinearRgba::with_components(1.0, 1.0, 1.0, 1.0),
+                        alt_color: None,
+                        hsv: None,
+                        mode: QuadMode::ColorEmoji,
+                    });
+                }
+            }
+        }
+
+        Ok(pane_image_commands(quads, clip))
+    }
+
     pub fn describe_tab_bar(&self) -> anyhow::Result<(Vec<RenderCommand>, Vec<UIItem>)> {
         if self.config.tab_bar().use_fancy_tab_bar {
             if let Some(computed) = self.fancy_tab_bar.as_ref() {
-                let ui_items = computed.ui_items();
+                let mut ui_items = computed.ui_items();
+                self.add_tab_tooltips(&mut ui_items);
                 let commands = self.describe_element(computed, None)?;
                 return Ok((commands, ui_items));
             }
 
-            let palette = self
-                .palette
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| TermConfig::new().color_palette());
+            let palette = self.window_palette();
             let computed = self.build_fancy_tab_bar(&palette)?;
-            let ui_items = computed.ui_items();
+            let mut ui_items = computed.ui_items();
+            self.add_tab_tooltips(&mut ui_items);
             let commands = self.describe_element(&computed, None)?;
             return Ok((commands, ui_items));
         }
 
         let border = self.get_os_border();
-        let palette = self
-            .palette
-            .as_ref()
-            .cloned()
-            .unwrap_or_else(|| TermConfig::new().color_palette());
+        let palette = self.window_palette();
         let tab_bar_height = self.tab_bar_pixel_height()?;
         let tab_bar_y = if self.config.tab_bar().tab_bar_at_bottom {
             ((self.dimensions.pixel_height as f32) - (tab_bar_height + border.bottom.get() as f32))
@@ -855,16 +1841,16 @@ impl crate::TermWindow {
             border.top.get() as f32
         };
 
-        let ui_items = self.tab_bar.compute_ui_items(
+        let mut ui_items = self.tab_bar.compute_ui_items(
             tab_bar_y as usize,
             self.render_metrics.cell_size.height as usize,
             self.render_metrics.cell_size.width as usize,
         );
+        self.add_tab_tooltips(&mut ui_items);
 
         let window_is_transparent = !self.window_background.is_empty();
         let gl_state = self.render_state.as_ref().unwrap();
         let white_space = gl_state.util_sprites.white_space.texture_coords();
-        let filled_box = gl_state.util_sprites.filled_box.texture_coords();
         let default_bg = palette
             .resolve_bg(ColorAttribute::Default)
             .to_linear()
@@ -875,48 +1861,47 @@ impl crate::TermWindow {
             });
         let cursor = StableCursorPosition::default();
 
-        let (commands, _result): (Vec<RenderCommand>, RenderScreenLineResult) =
-            self.describe_screen_line(RenderScreenLineParams {
-                top_pixel_y: tab_bar_y,
-                left_pixel_x: 0.0,
-                pixel_width: self.dimensions.pixel_width as f32,
-                stable_line_idx: None,
-                line: self.tab_bar.line(),
-                selection: 0..0,
-                cursor: &cursor,
-                palette: &palette,
-                dims: &RenderableDimensions {
-                    cols: self.dimensions.pixel_width / self.render_metrics.cell_size.width as usize,
-                    physical_top: 0,
-                    scrollback_rows: 0,
-                    scrollback_top: 0,
-                    viewport_rows: 1,
-                    dpi: self.terminal_size.dpi,
-                    pixel_height: self.render_metrics.cell_size.height as usize,
-                    pixel_width: self.terminal_size.pixel_width,
-                    reverse_video: false,
-                },
-                config: &self.config,
-                cursor_border_color: LinearRgba::default(),
-                foreground: palette.foreground.to_linear(),
-                pane: None,
-                is_active: true,
-                selection_fg: LinearRgba::default(),
-                selection_bg: LinearRgba::default(),
-                cursor_fg: LinearRgba::default(),
-                cursor_bg: LinearRgba::default(),
-                cursor_is_default_color: true,
-                white_space,
-                filled_box,
-                window_is_transparent,
-                default_bg,
-                style: None,
-                font: None,
-                use_pixel_positioning: self.config.text().experimental_pixel_positioning,
-                render_metrics: self.render_metrics,
-                shape_key: None,
-                password_input: false,
-            })?;
+        let commands = self.describe_screen_line(RenderScreenLineParams {
+            top_pixel_y: tab_bar_y,
+            left_pixel_x: 0.0,
+            pixel_width: self.dimensions.pixel_width as f32,
+            stable_line_idx: None,
+            line: self.tab_bar.line(),
+            selection: 0..0,
+            cursor: &cursor,
+            palette: &palette,
+            dims: &RenderableDimensions {
+                cols: self.dimensions.pixel_width / self.render_metrics.cell_size.width as usize,
+                physical_top: 0,
+                scrollback_rows: 0,
+                scrollback_top: 0,
+                viewport_rows: 1,
+                dpi: self.terminal_size.dpi,
+                pixel_height: self.render_metrics.cell_size.height as usize,
+                pixel_width: self.terminal_size.pixel_width,
+                reverse_video: false,
+            },
+            config: &self.config,
+            cursor_border_color: LinearRgba::default(),
+            foreground: palette.foreground.to_linear(),
+            pane: None,
+            is_active: true,
+            selection_fg: LinearRgba::default(),
+            selection_bg: LinearRgba::default(),
+            cursor_fg: LinearRgba::default(),
+            cursor_bg: LinearRgba::default(),
+            cursor_is_default_color: true,
+            white_space,
+            window_is_transparent,
+            default_bg,
+            style: None,
+            font: None,
+            pane_font: None,
+            use_pixel_positioning: self.config.text().experimental_pixel_positioning,
+            render_metrics: self.render_metrics,
+            shape_key: None,
+            password_input: false,
+        })?;
 
         Ok((commands, ui_items))
     }
@@ -926,7 +1911,21 @@ impl crate::TermWindow {
         let mut ui_items = Vec::new();
 
         if let Some(modal) = self.get_modal() {
-            for computed in modal.computed_element(self)?.iter() {
+            let computed = modal.computed_element(self)?;
+            if let Some(effect) = &self.config.gpu().webgpu_modal_effect {
+                // Let the effect apply to what is behind the modal
+                let region = computed
+                    .iter()
+                    .map(|computed| computed.bounds)
+                    .reduce(|a, b| a.union(&b));
+                if let Some(region) = region {
+                    commands.push(RenderCommand::BeginPostProcess {
+                        region: Some(region),
+                        effect: Some(effect.clone()),
+                    });
+                }
+            }
+            for computed in computed.iter() {
                 let mut element_ui_items = computed.ui_items();
                 let mut element_commands = self.describe_element(computed, None)?;
                 commands.append(&mut element_commands);
@@ -938,3 +1937,687 @@ impl crate::TermWindow {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_command::dsl::assert_commands_match;
+    use config::TermConfig;
+
+    fn described(pane_id: PaneId) -> PaneFrame {
+        let commands = vec![RenderCommand::Nop];
+        PaneFrame {
+            pane_id,
+            is_active: false,
+            bounds: euclid::rect(0., 0., 80., 16.),
+            command_hash: RenderCommand::content_hash(&commands),
+            cache_key: Some(pane_id as u64),
+            commands: commands.into(),
+            ui_items: vec![],
+            last_execution_stats: None,
+            skip_streak: 3,
+            hsv: None,
+        }
+    }
+
+    #[test]
+    fn overscrolled_viewport_leaves_blank_rows() {
+        // Not scrolled, or scrolled within the scrollback
+        assert_eq!(overscrolled_rows(None, 100, 24, 24), (0, 24));
+        assert_eq!(overscrolled_rows(Some(50), 50, 24, 24), (0, 24));
+        // Two rows past the top of the scrollback, which starts at
+        // row 0: the last two lines are pushed out of the pane
+        assert_eq!(overscrolled_rows(Some(-2), 0, 24, 24), (2, 22));
+        // A scrollback shorter than the pane
+        assert_eq!(overscrolled_rows(Some(-3), 0, 10, 24), (3, 10));
+        assert_eq!(overscrolled_rows(Some(-30), 0, 24, 24), (24, 0));
+    }
+
+    #[test]
+    fn split_command_follows_the_split() {
+        let color = LinearRgba(1., 1., 1., 1.);
+        let rect = euclid::rect(10., 0., 2., 40.);
+        assert_commands_match(
+            "fill layer=0/2 rect=10,0,2,40 color=#ffffffff",
+            &[split_command(rect, color, false)],
+        );
+        assert_commands_match(
+            "
+            line layer=0/2 from=11,0 to=11,40 width=2 color=#ffffffff
+            line layer=0/2 from=0,20.5 to=80,20.5 width=1 color=#ffffffff
+            ",
+            &[
+                split_command(rect, color, true),
+                split_command(euclid::rect(0., 20., 80., 1.), color, true),
+            ],
+        );
+    }
+
+    /// Describes panes 1 to 3, of which `failing` fails
+    fn describe_panes(failures: &RefCell<DescribeFailures>, failing: PaneId) -> Vec<PaneFrame> {
+        [1, 2, 3]
+            .iter()
+            .map(|&pane_id| {
+                describe_or_placeholder(
+                    pane_id,
+                    failures,
+                    || {
+                        if pane_id == failing {
+                            Err(anyhow::anyhow!("no lines available")
+                                .context("error while describing pane lines"))
+                        } else {
+                            Ok(described(pane_id))
+                        }
+                    },
+                    |err| {
+                        assert_eq!(
+                            pane_error_message(err),
+                            "Unable to render this pane: \
+                             error while describing pane lines: no lines available"
+                        );
+                        placeholder_pane_frame(
+                            pane_id,
+                            false,
+                            euclid::rect(0., 16., 80., 16.),
+                            LinearRgba(0., 0., 0., 1.),
+                            vec![RenderCommand::SetClipRect(None)],
+                        )
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn failing_pane_is_replaced_by_placeholder() {
+        let failures = RefCell::new(DescribeFailures::default());
+        let frames = describe_panes(&failures, 2);
+
+        let ids: Vec<PaneId> = frames.iter().map(|frame| frame.pane_id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(frames[0].cache_key, Some(1));
+        assert_eq!(frames[2].cache_key, Some(3));
+
+        let placeholder = &frames[1];
+        assert_eq!(placeholder.cache_key, None);
+        assert_eq!(placeholder.skip_streak, 0);
+        assert_commands_match(
+            "
+            fill layer=0/0 rect=0,16,80,16 color=#000000ff
+            clip none
+            ",
+            &placeholder.commands,
+        );
+        assert_eq!(
+            placeholder.command_hash,
+            RenderCommand::content_hash(&placeholder.commands)
+        );
+    }
+
+    #[test]
+    fn describe_failures_are_logged_once() {
+        let failures = RefCell::new(DescribeFailures::default());
+        describe_panes(&failures, 2);
+        describe_panes(&failures, 2);
+        let mut failures = failures.into_inner();
+        let message = "error while describing pane lines: no lines available";
+        assert_eq!(failures.logged.len(), 1);
+        assert!(!failures.is_new(2, message));
+        // The same error from another pane is reported separately
+        assert!(failures.is_new(3, message));
+
+        failures.retain_panes(&[1, 3]);
+        assert_eq!(failures.logged.len(), 1);
+        assert!(failures.is_new(2, message));
+    }
+
+    #[test]
+    fn panicking_pane_is_replaced_by_placeholder() {
+        let failures = RefCell::new(DescribeFailures::default());
+        let frames: Vec<PaneFrame> = [1, 2, 3]
+            .iter()
+            .map(|&pane_id| {
+                describe_or_placeholder(
+                    pane_id,
+                    &failures,
+                    || {
+                        if pane_id == 2 {
+                            panic!("line {} out of range", 7);
+                        }
+                        Ok(described(pane_id))
+                    },
+                    |err| {
+                        assert!(err.root_cause().downcast_ref::<Panicked>().is_some());
+                        assert_eq!(
+                            pane_error_message(err),
+                            "Unable to render this pane: panicked: line 7 out of range"
+                        );
+                        placeholder_pane_frame(
+                            pane_id,
+                            false,
+                            euclid::rect(0., 16., 80., 16.),
+                            LinearRgba(0., 0., 0., 1.),
+                            vec![],
+                        )
+                    },
+                )
+            })
+            .collect();
+
+        assert_eq!(frames[0].cache_key, Some(1));
+        assert_eq!(frames[1].cache_key, None);
+        assert_eq!(frames[2].cache_key, Some(3));
+        // The failures were not left borrowed by the unwinding
+        assert_eq!(failures.borrow().logged.len(), 1);
+    }
+
+    #[test]
+    fn repeated_paint_panics_disable_only_that_window() {
+        fn paint(panics: &mut PaintPanics, panic: bool) -> bool {
+            let result = catch_panic(|| {
+                if panic {
+                    panic!("paint exploded");
+                }
+                Ok(())
+            });
+            panics.record(&result)
+        }
+
+        let mut failing = PaintPanics::default();
+        let mut healthy = PaintPanics::default();
+
+        // A paint that succeeds resets the count
+        for _ in 1..MAX_CONSECUTIVE_PAINT_PANICS {
+            assert!(paint(&mut failing, true));
+        }
+        assert!(!paint(&mut failing, false));
+        assert_eq!(failing.disabled(), None);
+
+        for _ in 0..MAX_CONSECUTIVE_PAINT_PANICS {
+            assert!(paint(&mut failing, true));
+            assert!(!paint(&mut healthy, false));
+        }
+        assert_eq!(
+            failing.disabled(),
+            Some(
+                "Rendering of this window has been disabled \
+                 because it failed repeatedly: panicked: paint exploded"
+            )
+        );
+        assert_eq!(healthy.disabled(), None);
+
+        // Errors other than panics don't count
+        let mut erroring = PaintPanics::default();
+        for _ in 0..MAX_CONSECUTIVE_PAINT_PANICS {
+            assert!(!erroring.record::<()>(&Err(anyhow::anyhow!("out of texture space"))));
+        }
+        assert_eq!(erroring.disabled(), None);
+    }
+
+    #[test]
+    fn pane_error_message_is_one_line() {
+        let err = anyhow::anyhow!("first\nsecond").context("outer");
+        assert_eq!(
+            pane_error_message(&err),
+            "Unable to render this pane: outer: first second"
+        );
+    }
+
+    #[test]
+    fn window_opacity_reaches_the_background_fill() {
+        use crate::termwindow::opacity::WindowBackgroundOverride;
+
+        let mut state = WindowBackgroundOverride::default();
+        state.adjust_opacity(-0.25);
+        let background = LinearRgba::with_components(0.1, 0.2, 0.3, 1.0);
+        match window_background_fill(background, state.opacity(), 80., 40.) {
+            RenderCommand::FillRect { rect, color, .. } => {
+                assert_eq!(rect, euclid::rect(0., 0., 80., 40.));
+                assert_eq!(color, LinearRgba::with_components(0.1, 0.2, 0.3, 0.75));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn pane_hash_salt_follows_each_generation() {
+        let salt = pane_hash_salt(1, 2, 3);
+        assert_eq!(salt, pane_hash_salt(1, 2, 3));
+        assert_ne!(salt, pane_hash_salt(2, 2, 3));
+        assert_ne!(salt, pane_hash_salt(1, 3, 3));
+        assert_ne!(salt, pane_hash_salt(1, 2, 4));
+        // The generations are not interchangeable
+        assert_ne!(salt, pane_hash_salt(3, 2, 1));
+    }
+
+    fn linked_line(text: &str, link: Option<&Arc<Hyperlink>>) -> Line {
+        let mut attrs = CellAttributes::default();
+        attrs.set_hyperlink(link.cloned());
+        Line::from_text(text, &attrs, 0, None)
+    }
+
+    #[test]
+    fn osc_background_change_invalidates_describe_cache() {
+        use phaedra_term::{Terminal, TerminalSize};
+
+        let mut term = Terminal::new(
+            TerminalSize::default(),
+            Arc::new(TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        let snapshot = |term: &Terminal| {
+            PaneRenderSnapshot::new(
+                vec![linked_line("same text", None)],
+                0,
+                StableCursorPosition::default(),
+                RenderableDimensions::default(),
+                term.current_seqno(),
+                String::new(),
+                false,
+                false,
+                term.palette(),
+                term.palette_generation(),
+            )
+        };
+
+        let before = snapshot_cache_key(&snapshot(&term));
+        assert_eq!(before, snapshot_cache_key(&snapshot(&term)));
+
+        term.advance_bytes(b"\x1b]11;#ff0000\x1b\\");
+        assert_ne!(before, snapshot_cache_key(&snapshot(&term)));
+    }
+
+    #[test]
+    fn pane_is_skipped_while_output_is_held() {
+        use mux::pane::HeldSnapshot;
+        use phaedra_term::{Terminal, TerminalSize};
+
+        // paint_pass reuses the prior frame of a pane, rather than
+        // describing it again, when its cache key is unchanged
+        let mut term = Terminal::new(
+            TerminalSize::default(),
+            Arc::new(TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        let held = HeldSnapshot::default();
+        let key = |term: &Terminal| {
+            let snapshot = held.resolve(None, term.is_output_held(), || {
+                PaneRenderSnapshot::new(
+                    term.screen().visible_lines(),
+                    0,
+                    StableCursorPosition::default(),
+                    RenderableDimensions::default(),
+                    term.current_seqno(),
+                    String::new(),
+                    false,
+                    false,
+                    term.palette(),
+                    term.palette_generation(),
+                )
+            });
+            snapshot_cache_key(&snapshot)
+        };
+
+        term.advance_bytes(b"before");
+        let before = key(&term);
+
+        term.advance_bytes(b"\x1b[?2026h\x1b[2Jpartial");
+        assert_eq!(key(&term), before);
+        term.advance_bytes(b" update");
+        assert_eq!(key(&term), before);
+
+        term.advance_bytes(b"\x1b[?2026l");
+        let after = key(&term);
+        assert_ne!(after, before);
+
+        // An update that is never ended is shown once it times out
+        term.advance_bytes(b"\x1b[?2026hagain");
+        assert_eq!(key(&term), after);
+        std::thread::sleep(phaedra_term::SYNCHRONIZED_OUTPUT_TIMEOUT);
+        assert_ne!(key(&term), after);
+    }
+
+    #[test]
+    fn frozen_pane_reuses_its_frame_until_unfrozen() {
+        use phaedra_term::{Terminal, TerminalSize};
+
+        let mut term = Terminal::new(
+            TerminalSize::default(),
+            Arc::new(TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        let key = |term: &Terminal| {
+            snapshot_cache_key(&PaneRenderSnapshot::new(
+                term.screen().visible_lines(),
+                0,
+                StableCursorPosition::default(),
+                RenderableDimensions::default(),
+                term.current_seqno(),
+                String::new(),
+                false,
+                false,
+                term.palette(),
+                term.palette_generation(),
+            ))
+        };
+
+        term.advance_bytes(b"before");
+        let mut frame = described(1);
+        frame.cache_key = Some(key(&term));
+        let mut frames = HashMap::from([(1, frame)]);
+        let mut frozen = FrozenPanes::default();
+
+        assert!(frozen.set(1, true, &mut frames));
+        assert!(!frozen.set(1, true, &mut frames));
+        assert!(frozen.contains(1));
+
+        // The frame is reused while the pane is frozen, even though its
+        // content has changed since the frame was described
+        term.advance_bytes(b" after");
+        let changed = key(&term);
+        let reused = reusable_pane_frame(frames.get(&1), changed, frozen.contains(1));
+        assert_eq!(reused.map(|frame| frame.pane_id), Some(1));
+        assert!(reusable_pane_frame(frames.get(&1), changed, false).is_none());
+
+        // Unfreezing discards the frame, so the pane is described again
+        assert!(frozen.set(1, false, &mut frames));
+        assert!(!frozen.contains(1));
+        assert!(frames.get(&1).is_none());
+        assert!(reusable_pane_frame(frames.get(&1), changed, frozen.contains(1)).is_none());
+        assert!(!frozen.set(1, false, &mut frames));
+    }
+
+    #[test]
+    fn frozen_pane_indicator_is_inside_the_pane() {
+        let bounds = euclid::rect(100., 50., 200., 100.);
+        let color = LinearRgba(1., 1., 1., 1.);
+        match frozen_pane_indicator(bounds, 4., color) {
+            RenderCommand::FillRect { rect, .. } => {
+                assert_eq!(rect, euclid::rect(292., 54., 4., 4.));
+                assert!(bounds.contains_rect(&rect));
+            }
+            command => panic!("unexpected {command:?}"),
+        }
+    }
+
+    #[test]
+    fn broadcast_pane_border_is_inside_the_pane() {
+        let bounds = euclid::rect(100., 50., 200., 100.);
+        let color = LinearRgba(1., 1., 1., 1.);
+        let rects: Vec<RectF> = broadcast_pane_border(bounds, 2., color)
+            .iter()
+            .map(|command| match command {
+                RenderCommand::FillRect { rect, .. } => *rect,
+                command => panic!("unexpected {command:?}"),
+            })
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                euclid::rect(100., 50., 200., 2.),
+                euclid::rect(100., 148., 200., 2.),
+                euclid::rect(100., 50., 2., 100.),
+                euclid::rect(298., 50., 2., 100.),
+            ]
+        );
+        assert!(rects.iter().all(|rect| bounds.contains_rect(rect)));
+    }
+
+    #[test]
+    fn hover_spans_follow_wrapped_link() {
+        let link = Arc::new(Hyperlink::new("https://example.com"));
+        let mut first = linked_line("see ", None);
+        first.append_line(linked_line("https://exa", Some(&link)), 0);
+        let mut second = linked_line("mple.com", Some(&link));
+        second.append_line(linked_line(" ok", None), 0);
+
+        let spans = hyperlink_hover_spans(&[first, second], Some(&link));
+        assert_eq!(
+            spans,
+            vec![
+                HyperlinkHoverSpan {
+                    line_idx: 0,
+                    cols: 4..15,
+                    underlined: false,
+                },
+                HyperlinkHoverSpan {
+                    line_idx: 1,
+                    cols: 0..8,
+                    underlined: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hover_spans_require_highlighted_link() {
+        let link = Arc::new(Hyperlink::new("https://example.com"));
+        let other = Arc::new(Hyperlink::new("https://example.com"));
+        let lines = [linked_line("https://example.com", Some(&link))];
+
+        assert!(hyperlink_hover_spans(&lines, None).is_empty());
+        assert!(hyperlink_hover_spans(&lines, Some(&other)).is_empty());
+        assert_eq!(hyperlink_hover_spans(&lines, Some(&link)).len(), 1);
+    }
+
+    #[test]
+    fn hover_follows_hyperlink_rule_style() {
+        use termwiz::color::RgbColor;
+        use termwiz::hyperlink::HyperlinkStyle;
+
+        let palette = ColorPalette::default();
+        let plain = Arc::new(Hyperlink::new_implicit("https://example.com"));
+        let styled = Arc::new(Hyperlink::new_implicit_with_style(
+            "https://example.com",
+            Some(HyperlinkStyle {
+                underline: Some(Underline::Dotted),
+                hover_color: Some(RgbColor::new_8bpc(255, 0, 0)),
+                ..Default::default()
+            }),
+        ));
+
+        let lines = [linked_line("https://example.com", Some(&plain))];
+        let cell = lines[0].get_cell(0).unwrap();
+        assert_eq!(
+            hyperlink_hover_color(cell.attrs(), &palette),
+            palette.foreground.to_linear()
+        );
+        assert!(!hyperlink_hover_spans(&lines, Some(&plain))[0].underlined);
+
+        // The hover line is drawn below the underline that the rule
+        // asked for, in the rule's hover color
+        let lines = [linked_line("https://example.com", Some(&styled))];
+        let cell = lines[0].get_cell(0).unwrap();
+        assert_eq!(
+            hyperlink_hover_color(cell.attrs(), &palette),
+            termwiz::color::SrgbaTuple(1., 0., 0., 1.).to_linear()
+        );
+        assert!(hyperlink_hover_spans(&lines, Some(&styled))[0].underlined);
+    }
+
+    #[test]
+    fn pane_highlight_spans_follow_wrapped_match() {
+        let mut first = Line::from_text("hello wor", &CellAttributes::default(), 0, None);
+        first.set_last_cell_was_wrapped(true, 0);
+        let second = Line::from_text("ld", &CellAttributes::default(), 0, None);
+        let pattern = mux::search::CompiledPattern::new(&mux::pane::Pattern::CaseSensitiveString(
+            "world".to_string(),
+        ))
+        .unwrap();
+        let results = search_lines(&pattern, &[first, second], 10);
+
+        assert_eq!(
+            pane_highlight_spans(&results, 10, 2, 9),
+            vec![
+                PaneHighlightSpan {
+                    line_idx: 0,
+                    cols: 6..9,
+                },
+                PaneHighlightSpan {
+                    line_idx: 1,
+                    cols: 0..2,
+                },
+            ]
+        );
+
+        // Only the visible part of a match that extends beyond the
+        // viewport is highlighted
+        assert_eq!(
+            pane_highlight_spans(&results, 11, 1, 9),
+            vec![PaneHighlightSpan {
+                line_idx: 0,
+                cols: 0..2,
+            }]
+        );
+        assert_eq!(
+            pane_highlight_spans(&results, 9, 2, 9),
+            vec![PaneHighlightSpan {
+                line_idx: 1,
+                cols: 6..9,
+            }]
+        );
+    }
+
+    #[test]
+    fn pane_highlight_is_between_cell_background_and_glyphs() {
+        let cell_bg = LinearRgba(0., 0., 1., 1.);
+        let highlight = LinearRgba(1., 1., 0., 0.5);
+        let spans = vec![PaneHighlightSpan {
+            line_idx: 1,
+            cols: 2..5,
+        }];
+        let highlight_commands = pane_highlight_commands(&spans, 8., 16., 100., 10., highlight);
+        assert_eq!(
+            highlight_commands,
+            vec![RenderCommand::FillRect {
+                layer: LayerId::background(),
+                rect: euclid::rect(26., 116., 24., 16.),
+                color: highlight,
+                hsv: None,
+            }]
+        );
+
+        // The highlight follows the line commands, so it lands in the
+        // same buffer as the cell background but after it, while the
+        // glyphs are drawn from the text buffer above both
+        let mut commands = vec![RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(10., 100., 80., 32.),
+            color: cell_bg,
+            hsv: None,
+        }];
+        commands.extend(highlight_commands);
+        let buffers = crate::execute_render::execute_commands_dry_run(&commands, 0., 0.).unwrap();
+        assert_eq!(buffers.len(), 1);
+        let (layer, vertices) = &buffers[0];
+        assert_eq!(*layer, LayerId::background());
+        assert!(LayerId::background() < LayerId::text());
+        assert_eq!(vertices.len(), 8);
+        assert_eq!(vertices[0].fg_color, <[f32; 4]>::from(cell_bg));
+        assert_eq!(vertices[4].fg_color, <[f32; 4]>::from(highlight));
+    }
+
+    fn texture_tuple(texture: &TextureCoords) -> (f32, f32, f32, f32) {
+        (texture.left, texture.top, texture.right, texture.bottom)
+    }
+
+    /// The cell of a placement one column wide and four rows tall that
+    /// shows its `row`th row
+    fn placement_cell(row: usize) -> ImageCell {
+        let data = Arc::new(termwiz::image::ImageData::with_data(
+            termwiz::image::ImageDataType::new_single_frame(1, 1, vec![0; 4]),
+        ));
+        ImageCell::new(
+            termwiz::image::TextureCoordinate::new_f32(0., row as f32 / 4.),
+            termwiz::image::TextureCoordinate::new_f32(1., (row + 1) as f32 / 4.),
+            data,
+        )
+    }
+
+    #[test]
+    fn image_cell_maps_onto_its_rows_of_the_sprite() {
+        // The viewport starts two rows into the placement, so its first
+        // visible cell shows the third quarter of the image
+        let cell = placement_cell(2);
+        let cell_rect = euclid::rect(10., 100., 8., 16.);
+        let (position, texture) = image_cell_quad(
+            &cell,
+            cell_rect,
+            euclid::rect(16., 32., 64., 128.),
+            (256., 256.),
+        );
+        assert_eq!(position, cell_rect);
+        assert_eq!(
+            texture_tuple(&texture),
+            (16. / 256., 96. / 256., 80. / 256., 128. / 256.)
+        );
+
+        // A sprite that was downsampled to fit into the atlas yields
+        // the same part of the image
+        let (_, texture) = image_cell_quad(
+            &cell,
+            cell_rect,
+            euclid::rect(16., 32., 32., 64.),
+            (256., 256.),
+        );
+        assert_eq!(
+            texture_tuple(&texture),
+            (16. / 256., 64. / 256., 48. / 256., 80. / 256.)
+        );
+    }
+
+    #[test]
+    fn image_quads_are_clipped_to_the_pane() {
+        let clip = euclid::rect(0., 100., 80., 64.);
+        let quad = |row: usize, top: f32| {
+            let (position, texture) = image_cell_quad(
+                &placement_cell(row),
+                euclid::rect(8., top, 8., 16.),
+                euclid::rect(0., 0., 64., 64.),
+                (64., 64.),
+            );
+            RenderCommand::DrawQuad {
+                layer: LayerId::overlay(),
+                position,
+                texture,
+                fg_color: LinearRgba::with_components(1., 1., 1., 1.),
+                alt_color: None,
+                hsv: None,
+                mode: QuadMode::ColorEmoji,
+            }
+        };
+
+        // The placement starts above the pane: its first row is wholly
+        // outside of it, and only the lower half of its second is inside
+        let batch =
+            pane_image_commands(vec![quad(0, 76.), quad(1, 92.), quad(2, 108.)], clip).unwrap();
+        let RenderCommand::Batch(commands) = batch else {
+            panic!("expected a batch, got {batch:?}");
+        };
+        assert_eq!(commands.len(), 4);
+        assert!(matches!(commands[0], RenderCommand::SetClipRect(Some(rect)) if rect == clip));
+        assert!(matches!(commands[3], RenderCommand::SetClipRect(None)));
+
+        let RenderCommand::DrawQuad {
+            position, texture, ..
+        } = &commands[1]
+        else {
+            panic!("expected a quad, got {:?}", commands[1]);
+        };
+        assert_eq!(*position, euclid::rect(8., 100., 8., 8.));
+        assert_eq!(texture_tuple(texture), (0., 0.375, 1., 0.5));
+
+        let RenderCommand::DrawQuad { texture, .. } = &commands[2] else {
+            panic!("expected a quad, got {:?}", commands[2]);
+        };
+        assert_eq!(texture_tuple(texture), (0., 0.5, 1., 0.75));
+
+        assert!(pane_image_commands(vec![quad(0, 0.)], clip).is_none());
+    }
+}
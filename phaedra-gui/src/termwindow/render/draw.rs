@@ -1,24 +1,35 @@
+use crate::termwindow::gpu_recovery::GpuDeviceLost;
 use crate::termwindow::webgpu::{PostProcessUniform, ShaderUniform};
-use crate::render_plan::quad_count_for_snapshot;
+use crate::render_command::LayerId;
+use crate::render_plan::{quad_count_for_snapshot, PostProcessRegion, ScissorRect};
+use crate::renderstate::SubmissionFence;
+use ::window::color::LinearRgba;
 use config::observers::*;
+use std::rc::Rc;
 
 const INDICES_PER_QUAD: usize = 6;
 
 fn quad_range_for_section(
     range: &crate::render_plan::QuadRange,
-    zindex: i8,
-    sub_idx: usize,
+    layer: LayerId,
 ) -> Option<(usize, usize)> {
-    let start_quad = quad_count_for_snapshot(&range.start, zindex, sub_idx);
-    let end_quad = quad_count_for_snapshot(&range.end, zindex, sub_idx);
+    let start_quad = quad_count_for_snapshot(&range.start, layer);
+    let end_quad = quad_count_for_snapshot(&range.end, layer);
     (end_quad > start_quad).then_some((start_quad, end_quad))
 }
 
+fn draw_quads(render_pass: &mut wgpu::RenderPass<'_>, (start_quad, end_quad): (usize, usize)) {
+    render_pass.draw_indexed(
+        (start_quad * INDICES_PER_QUAD) as u32..(end_quad * INDICES_PER_QUAD) as u32,
+        0,
+        0..1,
+    );
+}
+
 fn draw_layer_sections(
     render_pass: &mut wgpu::RenderPass<'_>,
     render_plan: &crate::render_plan::RenderPlan,
-    zindex: i8,
-    sub_idx: usize,
+    layer: LayerId,
     fallback_index_count: usize,
     current_vertex_buffer: &wgpu::Buffer,
     previous_frame: Option<&crate::renderstate::FrameBuffers>,
@@ -27,9 +38,27 @@ fn draw_layer_sections(
     let mut has_range = false;
     let mut sections_drawn = 0usize;
     let mut sections_skipped = 0usize;
+    let mut scissor_changes = 0usize;
+    let discarded = render_plan.discarded_quads(layer);
+    let full_viewport = ScissorRect {
+        x: 0,
+        y: 0,
+        width: render_plan.viewport_width,
+        height: render_plan.viewport_height,
+    };
+    let mut current_scissor: Option<&ScissorRect> = None;
+    // Quads from the current vertex buffer that are yet to be drawn;
+    // consecutive sections whose quads follow on from one another are
+    // drawn together
+    let mut pending: Option<(usize, usize)> = None;
 
-    for (section_idx, section) in render_plan.sections.iter().enumerate() {
-        let current_range = quad_range_for_section(&section.quad_range, zindex, sub_idx);
+    let batches = render_plan.batches();
+    for section_idx in batches
+        .iter()
+        .flat_map(|batch| batch.sections.iter().copied())
+    {
+        let section = &render_plan.sections[section_idx];
+        let current_range = quad_range_for_section(&section.quad_range, layer);
         if current_range.is_some() {
             has_range = true;
         }
@@ -37,8 +66,8 @@ fn draw_layer_sections(
         let mut use_previous_frame = false;
         let range = if section.skippable {
             if let Some(previous_frame) = previous_frame {
-                if let Some(range) = previous_frame.section_quad_range(section_idx, zindex, sub_idx) {
-                    if previous_frame.buffer(zindex, sub_idx).is_some() {
+                if let Some(range) = previous_frame.section_quad_range(section_idx, layer) {
+                    if previous_frame.buffer(layer).is_some() {
                         use_previous_frame = true;
                         sections_skipped += 1;
                         Some(range)
@@ -61,47 +90,192 @@ fn draw_layer_sections(
             }
             continue;
         };
+        let start_quad = start_quad.max(discarded);
+        if start_quad >= end_quad {
+            continue;
+        }
 
-        if let Some(scissor) = &section.scissor {
-            if scissor.width == 0 || scissor.height == 0 {
-                continue;
+        let scissor = section.scissor.as_ref().unwrap_or(&full_viewport);
+        if scissor.width == 0 || scissor.height == 0 {
+            continue;
+        }
+        if current_scissor != Some(scissor) {
+            if let Some(range) = pending.take() {
+                draw_quads(render_pass, range);
             }
             render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height);
-        } else if render_plan.viewport_width > 0 && render_plan.viewport_height > 0 {
-            render_pass.set_scissor_rect(0, 0, render_plan.viewport_width, render_plan.viewport_height);
-        } else {
-            continue;
+            current_scissor = Some(scissor);
+            scissor_changes += 1;
         }
 
         if use_previous_frame {
-            if let Some(previous_buffer) = previous_frame.and_then(|frame| frame.buffer(zindex, sub_idx))
-            {
-                render_pass.set_vertex_buffer(0, previous_buffer.slice(..));
-            } else {
+            let Some(previous_buffer) = previous_frame.and_then(|frame| frame.buffer(layer)) else {
                 continue;
+            };
+            if let Some(range) = pending.take() {
+                draw_quads(render_pass, range);
             }
+            render_pass.set_vertex_buffer(0, previous_buffer.slice(..));
+            draw_quads(render_pass, (start_quad, end_quad));
+            render_pass.set_vertex_buffer(0, current_vertex_buffer.slice(..));
+        } else {
+            pending = match pending {
+                Some((start, end)) if end == start_quad => Some((start, end_quad)),
+                Some(range) => {
+                    draw_quads(render_pass, range);
+                    Some((start_quad, end_quad))
+                }
+                None => Some((start_quad, end_quad)),
+            };
         }
-
-        render_pass.draw_indexed(
-            (start_quad * INDICES_PER_QUAD) as u32..(end_quad * INDICES_PER_QUAD) as u32,
-            0,
-            0..1,
-        );
         sections_drawn += 1;
         drew = true;
-
-        if use_previous_frame {
-            render_pass.set_vertex_buffer(0, current_vertex_buffer.slice(..));
-        }
+    }
+    if let Some(range) = pending.take() {
+        draw_quads(render_pass, range);
     }
 
-    if !drew && !has_range && fallback_index_count > 0 {
-        render_pass.draw_indexed(0..fallback_index_count as u32, 0, 0..1);
+    let fallback_start = discarded * INDICES_PER_QUAD;
+    if !drew && !has_range && fallback_index_count > fallback_start {
+        render_pass.draw_indexed(fallback_start as u32..fallback_index_count as u32, 0, 0..1);
         sections_drawn += 1;
     }
 
     metrics::histogram!("gui.draw.sections_drawn").record(sections_drawn as f64);
     metrics::histogram!("gui.draw.sections_skipped").record(sections_skipped as f64);
+    metrics::histogram!("gui.draw.scissor_changes").record(scissor_changes as f64);
+}
+
+/// Convert a clear color into the form expected by the surface: its
+/// components are multiplied by alpha when the compositor expects
+/// premultiplied values, and passed through otherwise
+fn surface_clear_color(color: LinearRgba, alpha_mode: wgpu::CompositeAlphaMode) -> wgpu::Color {
+    let LinearRgba(r, g, b, a) = color;
+    let scale = match alpha_mode {
+        wgpu::CompositeAlphaMode::PreMultiplied => a,
+        _ => 1.,
+    };
+    wgpu::Color {
+        r: (r * scale) as f64,
+        g: (g * scale) as f64,
+        b: (b * scale) as f64,
+        a: a as f64,
+    }
+}
+
+/// The load op for the first render pass of a frame: a frame that
+/// starts with a `RenderCommand::Clear` is cleared to its color, and
+/// any other frame is drawn over the existing contents of the target
+fn frame_load_op(
+    clear_color: Option<LinearRgba>,
+    alpha_mode: wgpu::CompositeAlphaMode,
+) -> wgpu::LoadOp<wgpu::Color> {
+    match clear_color {
+        Some(color) => wgpu::LoadOp::Clear(surface_clear_color(color, alpha_mode)),
+        None => wgpu::LoadOp::Load,
+    }
+}
+
+/// One draw of the post-process pass: a full-screen triangle sampling
+/// the intermediate texture with `pipeline`, limited to `scissor`, or
+/// to the whole target when that is None
+#[derive(Debug, PartialEq)]
+struct PostProcessDraw<'a, P> {
+    pipeline: &'a P,
+    scissor: Option<ScissorRect>,
+}
+
+/// Returns the shader that post-processes the frame unless a request
+/// confines it to a region: the configured `webgpu_shader`, which reads
+/// the bell intensity from its uniform, or otherwise the built-in
+/// visual bell, for just as long as a bell targeting PostProcess rings
+fn default_postprocess<'a, P>(
+    configured: Option<&'a P>,
+    visual_bell: Option<&'a P>,
+    bell_intensity: f32,
+) -> Option<&'a P> {
+    configured.or_else(|| visual_bell.filter(|_| bell_intensity > 0.))
+}
+
+/// Decide how the post-process pass composes the surface.  When the
+/// default shader is loaded and no request confines it to a region, it
+/// covers the whole frame; otherwise the frame is copied through
+/// unmodified and each requested region is drawn over that copy with
+/// its shader.  Requests for effects that are not loaded are ignored.
+fn plan_postprocess_draws<'a, P>(
+    default: Option<&'a P>,
+    copy: &'a P,
+    effect: impl Fn(&str) -> Option<&'a P>,
+    regions: &[PostProcessRegion],
+) -> Vec<PostProcessDraw<'a, P>> {
+    let mut default_requests = regions.iter().filter(|region| region.effect.is_none());
+    let default_is_scoped = default_requests.clone().next().is_some()
+        && default_requests.all(|region| region.scissor.is_some());
+    let base = default.filter(|_| !default_is_scoped);
+
+    let mut draws = vec![PostProcessDraw {
+        pipeline: base.unwrap_or(copy),
+        scissor: None,
+    }];
+
+    for region in regions {
+        let pipeline = match &region.effect {
+            None if base.is_none() => default,
+            None => None,
+            Some(name) => {
+                let pipeline = effect(name);
+                if pipeline.is_none() {
+                    log::trace!("post-process effect {name} is not loaded");
+                }
+                pipeline
+            }
+        };
+        if let Some(pipeline) = pipeline {
+            draws.push(PostProcessDraw {
+                pipeline,
+                scissor: region.scissor.clone(),
+            });
+        }
+    }
+
+    draws
+}
+
+fn encode_postprocess_draws(
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    bind_group: &wgpu::BindGroup,
+    draws: &[PostProcessDraw<'_, wgpu::RenderPipeline>],
+    width: u32,
+    height: u32,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("PostProcess Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    render_pass.set_bind_group(0, bind_group, &[]);
+    for draw in draws {
+        match &draw.scissor {
+            Some(scissor) => {
+                render_pass.set_scissor_rect(scissor.x, scissor.y, scissor.width, scissor.height)
+            }
+            None => render_pass.set_scissor_rect(0, 0, width, height),
+        }
+        render_pass.set_pipeline(draw.pipeline);
+        // Draw a full-screen triangle (3 vertices, no vertex buffer needed)
+        render_pass.draw(0..3, 0..1);
+    }
 }
 
 impl crate::TermWindow {
@@ -116,7 +290,28 @@ impl crate::TermWindow {
         let render_state = self.render_state.as_ref().unwrap();
         let render_plan = self.render_plan.as_ref();
 
-        let has_postprocess = webgpu.has_postprocess();
+        let post_process_regions = render_plan.map_or(&[][..], |plan| &plan.post_process);
+        let bell_intensity = render_plan.map_or(0., |plan| plan.visual_bell_intensity);
+        if bell_intensity > 0. && !webgpu.has_postprocess() {
+            if let Err(err) = webgpu.ensure_visual_bell_pipeline() {
+                log::error!("Failed to compile the visual bell shader: {:#}", err);
+            }
+        }
+        let has_default_postprocess = default_postprocess(
+            webgpu.postprocess_pipeline.borrow().as_ref(),
+            webgpu.visual_bell_pipeline.borrow().as_ref(),
+            bell_intensity,
+        )
+        .is_some();
+        let has_postprocess = has_default_postprocess || {
+            let effects = webgpu.postprocess_effects.borrow();
+            post_process_regions.iter().any(|region| {
+                region
+                    .effect
+                    .as_ref()
+                    .map_or(false, |name| effects.contains_key(name))
+            })
+        };
         let width = self.dimensions.pixel_width as u32;
         let height = self.dimensions.pixel_height as u32;
 
@@ -128,7 +323,17 @@ impl crate::TermWindow {
             webgpu.ensure_intermediate_texture(width, height);
         }
 
-        let output = webgpu.surface.get_current_texture()?;
+        if webgpu.is_device_lost() {
+            return Err(GpuDeviceLost.into());
+        }
+        let output = match webgpu.surface.get_current_texture() {
+            Ok(output) => output,
+            // The surface can only be recreated along with everything else
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::OutOfMemory) => {
+                return Err(GpuDeviceLost.into())
+            }
+            Err(err) => return Err(err.into()),
+        };
         let surface_view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -187,6 +392,10 @@ impl crate::TermWindow {
             });
 
         let mut cleared = false;
+        let first_load_op = frame_load_op(
+            render_plan.and_then(|plan| plan.clear_color),
+            webgpu.config.borrow().alpha_mode,
+        );
         let mut next_frame_buffers = crate::renderstate::FrameBuffers::default();
         let foreground_text_hsb = self.config.color_config().foreground_text_hsb;
         let foreground_text_hsb = [
@@ -206,10 +415,14 @@ impl crate::TermWindow {
         )
         .to_arrays_transposed();
 
+        // The (layer, vertex buffer, slot) triples read by this frame
+        let mut slots_in_use = vec![];
+
         // First pass: render terminal content to render target
         for layer in render_state.layers.borrow().iter() {
             for idx in 0..3 {
                 let vb = &layer.vb.borrow()[idx];
+                slots_in_use.push((Rc::clone(layer), idx, *vb.index.borrow()));
                 let (vertex_count, index_count) = vb.vertex_index_count();
                 let uniforms;
                 if vertex_count > 0 {
@@ -224,12 +437,7 @@ impl crate::TermWindow {
                                     load: if cleared {
                                         wgpu::LoadOp::Load
                                     } else {
-                                        wgpu::LoadOp::Clear(wgpu::Color {
-                                            r: 0.,
-                                            g: 0.,
-                                            b: 0.,
-                                            a: 0.,
-                                        })
+                                        first_load_op
                                     },
                                     store: wgpu::StoreOp::Store,
                                 },
@@ -260,8 +468,7 @@ impl crate::TermWindow {
                             draw_layer_sections(
                                 &mut render_pass,
                                 render_plan,
-                                layer.zindex(),
-                                idx,
+                                layer.layer_id(idx),
                                 index_count,
                                 &vertex_buffer,
                                 previous_frame.as_ref(),
@@ -274,7 +481,7 @@ impl crate::TermWindow {
 
                     next_frame_buffers
                         .buffers
-                        .push((layer.zindex(), idx, vertex_buffer));
+                        .push((layer.layer_id(idx), vertex_buffer));
                 }
 
                 vb.next_index();
@@ -295,17 +502,514 @@ impl crate::TermWindow {
             let postprocess_uniform = webgpu.create_postprocess_uniform(PostProcessUniform {
                 resolution: [width as f32, height as f32],
                 time: self.created.elapsed().as_secs_f32(),
-                _padding: 0.0,
+                bell_intensity,
             });
 
             let pipeline = webgpu.postprocess_pipeline.borrow();
+            let visual_bell = webgpu.visual_bell_pipeline.borrow();
+            let effects = webgpu.postprocess_effects.borrow();
+            let draws = plan_postprocess_draws(
+                default_postprocess(pipeline.as_ref(), visual_bell.as_ref(), bell_intensity),
+                &webgpu.postprocess_copy_pipeline,
+                |name| effects.get(name),
+                post_process_regions,
+            );
+            encode_postprocess_draws(
+                &mut encoder,
+                &surface_view,
+                &postprocess_uniform,
+                &draws,
+                width,
+                height,
+            );
+        }
+
+        // submit will accept anything that implements IntoIter
+        let fence = SubmissionFence::submit(&webgpu.queue, std::iter::once(encoder.finish()));
+        for (layer, idx, slot) in slots_in_use {
+            layer.vb.borrow()[idx].mark_in_flight(slot, &fence);
+        }
+        output.present();
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::termwindow::webgpu::{
+        compile_postprocess_shader, create_postprocess_bind_group,
+        create_postprocess_bind_group_layout, create_postprocess_pipeline,
+    };
+
+    const COPY: u8 = 0;
+    const DEFAULT: u8 = 1;
+    const BLUR: u8 = 2;
+
+    fn scissor(x: u32) -> Option<ScissorRect> {
+        Some(ScissorRect {
+            x,
+            y: 0,
+            width: 10,
+            height: 10,
+        })
+    }
+
+    fn region(scissor: Option<ScissorRect>, effect: Option<&str>) -> PostProcessRegion {
+        PostProcessRegion {
+            scissor,
+            effect: effect.map(|s| s.to_string()),
+        }
+    }
+
+    fn plan(default: Option<&u8>, regions: &[PostProcessRegion]) -> Vec<(u8, Option<ScissorRect>)> {
+        plan_postprocess_draws(
+            default,
+            &COPY,
+            |name| (name == "blur").then_some(&BLUR),
+            regions,
+        )
+        .into_iter()
+        .map(|draw| (*draw.pipeline, draw.scissor))
+        .collect()
+    }
+
+    #[test]
+    fn frame_clear_maps_to_load_op() {
+        use crate::render_command::RenderCommand;
+        use crate::render_plan::RenderPlan;
+        use wgpu::CompositeAlphaMode;
+
+        let mut render_plan = RenderPlan::new(10, 10);
+        render_plan.record(&[RenderCommand::Clear {
+            color: LinearRgba(0.5, 0.25, 1., 0.5),
+            layer: None,
+        }]);
+        assert_eq!(
+            frame_load_op(render_plan.clear_color, CompositeAlphaMode::PreMultiplied),
+            wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.25,
+                g: 0.125,
+                b: 0.5,
+                a: 0.5,
+            })
+        );
+        assert_eq!(
+            frame_load_op(render_plan.clear_color, CompositeAlphaMode::PostMultiplied),
+            wgpu::LoadOp::Clear(wgpu::Color {
+                r: 0.5,
+                g: 0.25,
+                b: 1.,
+                a: 0.5,
+            })
+        );
+
+        let mut render_plan = RenderPlan::new(10, 10);
+        render_plan.record(&[RenderCommand::Nop]);
+        assert_eq!(
+            frame_load_op(render_plan.clear_color, CompositeAlphaMode::PreMultiplied),
+            wgpu::LoadOp::Load
+        );
+    }
+
+    #[test]
+    fn default_shader_covers_the_frame_unless_scoped() {
+        assert_eq!(plan(Some(&DEFAULT), &[]), vec![(DEFAULT, None)]);
+        assert_eq!(
+            plan(Some(&DEFAULT), &[region(scissor(1), None)]),
+            vec![(COPY, None), (DEFAULT, scissor(1))]
+        );
+        assert_eq!(
+            plan(
+                Some(&DEFAULT),
+                &[region(scissor(1), None), region(None, None)]
+            ),
+            vec![(DEFAULT, None)]
+        );
+        assert_eq!(plan(None, &[region(scissor(1), None)]), vec![(COPY, None)]);
+    }
+
+    #[test]
+    fn named_effects_are_drawn_over_the_base() {
+        assert_eq!(
+            plan(
+                None,
+                &[
+                    region(scissor(1), Some("blur")),
+                    region(scissor(2), Some("missing")),
+                    region(None, Some("blur")),
+                ]
+            ),
+            vec![(COPY, None), (BLUR, scissor(1)), (BLUR, None)]
+        );
+        assert_eq!(
+            plan(Some(&DEFAULT), &[region(scissor(1), Some("blur"))]),
+            vec![(DEFAULT, None), (BLUR, scissor(1))]
+        );
+    }
+
+    #[test]
+    fn visual_bell_pass_runs_while_ringing() {
+        const BELL: u8 = 3;
+
+        // Without a configured shader, a ringing bell adds a pass over
+        // the whole frame, which goes away once the bell has faded out
+        assert_eq!(
+            plan(default_postprocess(None, Some(&BELL), 0.5), &[]),
+            vec![(BELL, None)]
+        );
+        assert_eq!(default_postprocess(None, Some(&BELL), 0.), None);
+        assert_eq!(
+            plan(
+                default_postprocess(None, Some(&BELL), 0.5),
+                &[region(scissor(1), Some("blur"))]
+            ),
+            vec![(BELL, None), (BLUR, scissor(1))]
+        );
+
+        // A configured shader is given the intensity instead
+        assert_eq!(
+            default_postprocess(Some(&DEFAULT), Some(&BELL), 0.5),
+            Some(&DEFAULT)
+        );
+        assert_eq!(
+            default_postprocess(Some(&DEFAULT), None, 0.),
+            Some(&DEFAULT)
+        );
+    }
+
+    #[test]
+    fn visual_bell_shader_is_valid() {
+        config::shader_validation::validate_postprocess_shader_source(
+            include_str!("../../postprocess_bell.wgsl"),
+            std::path::Path::new("postprocess_bell.wgsl"),
+        )
+        .unwrap();
+    }
+
+    fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = futures::executor::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        )
+        .ok()?;
+        futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        }))
+        .ok()
+    }
+
+    #[test]
+    fn pixels_outside_the_region_are_copied_through() {
+        const SIZE: u32 = 8;
+        const RED: [u8; 4] = [255, 0, 0, 255];
+        const GREEN: [u8; 4] = [0, 255, 0, 255];
+
+        let Some((device, queue)) = headless_device() else {
+            // No usable adapter on this machine; there is nothing to check
+            return;
+        };
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let make_texture = |usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: SIZE,
+                    height: SIZE,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+
+        let input =
+            make_texture(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST);
+        queue.write_texture(
+            input.as_image_copy(),
+            &RED.repeat((SIZE * SIZE) as usize),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE * 4),
+                rows_per_image: None,
+            },
+            input.size(),
+        );
+        let output =
+            make_texture(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC);
+
+        let copy_source = include_str!("../../postprocess_copy.wgsl");
+        let green_source = copy_source.replace(
+            "return textureSample(input_texture, input_sampler, in.uv);",
+            "return vec4<f32>(0.0, 1.0, 0.0, 1.0);",
+        );
+        let layout = create_postprocess_bind_group_layout(&device);
+        let copy = create_postprocess_pipeline(
+            &device,
+            &layout,
+            &compile_postprocess_shader(&device, "copy", copy_source).unwrap(),
+            format,
+        );
+        let green = create_postprocess_pipeline(
+            &device,
+            &layout,
+            &compile_postprocess_shader(&device, "green", &green_source).unwrap(),
+            format,
+        );
+        let bind_group = create_postprocess_bind_group(
+            &device,
+            &layout,
+            PostProcessUniform {
+                resolution: [SIZE as f32, SIZE as f32],
+                time: 0.,
+                bell_intensity: 0.,
+            },
+            &input.create_view(&wgpu::TextureViewDescriptor::default()),
+            &device.create_sampler(&wgpu::SamplerDescriptor::default()),
+        );
+
+        let draws = plan_postprocess_draws(
+            None,
+            &copy,
+            |name| (name == "green").then_some(&green),
+            &[region(
+                Some(ScissorRect {
+                    x: 2,
+                    y: 2,
+                    width: 4,
+                    height: 4,
+                }),
+                Some("green"),
+            )],
+        );
+        assert_eq!(draws.len(), 2);
+
+        let bytes_per_row = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (bytes_per_row * SIZE) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encode_postprocess_draws(
+            &mut encoder,
+            &output.create_view(&wgpu::TextureViewDescriptor::default()),
+            &bind_group,
+            &draws,
+            SIZE,
+            SIZE,
+        );
+        encoder.copy_texture_to_buffer(
+            output.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            output.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let data = readback.slice(..).get_mapped_range();
+
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let offset = (y * bytes_per_row + x * 4) as usize;
+                let inside = (2..6).contains(&x) && (2..6).contains(&y);
+                assert_eq!(
+                    data[offset..offset + 4],
+                    if inside { GREEN } else { RED },
+                    "pixel {x},{y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn solid_color_quads_ignore_the_atlas() {
+        use crate::execute_render::execute_commands_dry_run;
+        use crate::quad::{VERTICES_PER_CELL, V_BOT_LEFT, V_BOT_RIGHT, V_TOP_LEFT, V_TOP_RIGHT};
+        use crate::render_command::{QuadMode, RenderCommand, TextureCoords};
+        use crate::termwindow::webgpu::{
+            create_render_pipeline, create_shader_uniform_bind_group_layout,
+            create_texture_bind_group_layout,
+        };
+        use wgpu::util::DeviceExt;
+
+        const SIZE: u32 = 8;
+        const GREEN: [u8; 4] = [0, 255, 0, 255];
+        const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+        let Some((device, queue)) = headless_device() else {
+            return;
+        };
+
+        // The left half is a solid color; the right half is a glyph of
+        // the same color whose texture coordinates are the same as
+        // those of the FillRect.  The atlas is entirely transparent, so
+        // only the glyph depends on what is sampled from it.
+        let green = LinearRgba(0., 1., 0., 1.);
+        let commands = vec![
+            RenderCommand::FillRect {
+                layer: LayerId::background(),
+                rect: euclid::rect(0., 0., 4., 8.),
+                color: green,
+                hsv: None,
+            },
+            RenderCommand::DrawQuad {
+                layer: LayerId::background(),
+                position: euclid::rect(4., 0., 4., 8.),
+                texture: TextureCoords {
+                    left: 0.,
+                    top: 0.,
+                    right: 0.,
+                    bottom: 0.,
+                },
+                fg_color: green,
+                alt_color: None,
+                hsv: None,
+                mode: QuadMode::Glyph,
+            },
+        ];
+        let half = SIZE as f32 / 2.;
+        let vertices = execute_commands_dry_run(&commands, half, half).unwrap();
+        let vertices = &vertices[0].1;
+        let indices: Vec<u32> = (0..(vertices.len() / VERTICES_PER_CELL) as u32)
+            .flat_map(|quad| {
+                let idx = quad * VERTICES_PER_CELL as u32;
+                vec![
+                    V_TOP_LEFT,
+                    V_TOP_RIGHT,
+                    V_BOT_LEFT,
+                    V_TOP_RIGHT,
+                    V_BOT_LEFT,
+                    V_BOT_RIGHT,
+                ]
+                .into_iter()
+                .map(move |v| idx + v as u32)
+            })
+            .collect();
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let texture = |size, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let atlas = texture(
+            1,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        queue.write_texture(
+            atlas.as_image_copy(),
+            &CLEAR,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: None,
+            },
+            atlas.size(),
+        );
+        let output = texture(
+            SIZE,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+
+        let uniform_layout = create_shader_uniform_bind_group_layout(&device);
+        let texture_layout = create_texture_bind_group_layout(&device);
+        let pipeline = create_render_pipeline(&device, &uniform_layout, &texture_layout, format);
+
+        let uniform = ShaderUniform {
+            foreground_text_hsb: [1., 1., 1.],
+            milliseconds: 0,
+            projection: euclid::Transform3D::<f32, f32, f32>::ortho(
+                -half, half, half, -half, -1.0, 1.0,
+            )
+            .to_arrays_transposed(),
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let uniforms = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: None,
+        });
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let atlas_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: None,
+        });
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let bytes_per_row = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (bytes_per_row * SIZE) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("PostProcess Pass"),
+                label: None,
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &surface_view,
+                    view: &output.create_view(&wgpu::TextureViewDescriptor::default()),
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
@@ -313,18 +1017,41 @@ impl crate::TermWindow {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
-
-            render_pass.set_pipeline(pipeline.as_ref().unwrap());
-            render_pass.set_bind_group(0, &postprocess_uniform, &[]);
-            // Draw a full-screen triangle (3 vertices, no vertex buffer needed)
-            render_pass.draw(0..3, 0..1);
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &uniforms, &[]);
+            render_pass.set_bind_group(1, &atlas_bind_group, &[]);
+            render_pass.set_bind_group(2, &atlas_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
         }
+        encoder.copy_texture_to_buffer(
+            output.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            output.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
 
-        // submit will accept anything that implements IntoIter
-        webgpu.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::PollType::Wait).unwrap();
+        let data = readback.slice(..).get_mapped_range();
 
-        Ok(())
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let offset = (y * bytes_per_row + x * 4) as usize;
+                assert_eq!(
+                    data[offset..offset + 4],
+                    if x < SIZE / 2 { GREEN } else { CLEAR },
+                    "pixel {x},{y}"
+                );
+            }
+        }
     }
-
 }
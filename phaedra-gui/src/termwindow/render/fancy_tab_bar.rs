@@ -1,5 +1,6 @@
 use crate::customglyph::*;
 use config::observers::*;
+use crate::render_command::LayerId;
 use crate::tabbar::{TabBarItem, TabEntry};
 use crate::termwindow::box_model::*;
 use crate::termwindow::render::corners::*;
@@ -113,8 +114,14 @@ impl crate::TermWindow {
             let active_tab = colors.active_tab();
 
             match item.item {
-                TabBarItem::RightStatus | TabBarItem::LeftStatus | TabBarItem::None => element
-                    .item_type(UIItemType::TabBar(TabBarItem::None))
+                TabBarItem::RightStatus
+                | TabBarItem::LeftStatus
+                | TabBarItem::None
+                | TabBarItem::StatusSegment(_) => element
+                    .item_type(match item.item {
+                        TabBarItem::StatusSegment(index) => UIItemType::StatusSegment(index),
+                        _ => UIItemType::TabBar(TabBarItem::None),
+                    })
                     .line_height(Some(1.75))
                     .margin(BoxDimension {
                         left: Dimension::Cells(0.),
@@ -329,7 +336,12 @@ impl crate::TermWindow {
         for item in items {
             match item.item {
                 TabBarItem::LeftStatus => left_status.push(item_to_elem(item)),
-                TabBarItem::None | TabBarItem::RightStatus => right_eles.push(item_to_elem(item)),
+                TabBarItem::StatusSegment(index) if index < self.status_segments.left.len() => {
+                    left_status.push(item_to_elem(item))
+                }
+                TabBarItem::None | TabBarItem::RightStatus | TabBarItem::StatusSegment(_) => {
+                    right_eles.push(item_to_elem(item))
+                }
                 TabBarItem::WindowButton(_) => {
                     if self.config.window_config().integrated_title_button_alignment
                         == IntegratedTitleButtonAlignment::Left
@@ -441,7 +453,7 @@ impl crate::TermWindow {
                 ),
                 metrics: &metrics,
                 gl_state: self.render_state.as_ref().unwrap(),
-                zindex: 10,
+                zindex: LayerId::chrome().zindex(),
             },
             &tabs,
         )?;
@@ -7,30 +7,37 @@ use crate::quad::{
 };
 use crate::render_command::RenderCommand;
 use crate::shapecache::*;
+use crate::termwindow::pane_font::PaneFont;
 use crate::termwindow::render::paint::AllowImage;
 use crate::termwindow::{BorrowedShapeCacheKey, RenderState, ShapedInfo, TermWindowNotif};
 use crate::utilsprites::RenderMetrics;
 use ::window::bitmaps::{TextureCoord, TextureRect, TextureSize};
-use ::window::{DeadKeyStatus, PointF, RectF, SizeF, WindowOps};
+use ::window::{
+    Composition, Connection, ConnectionOps, DeadKeyStatus, PointF, RectF, SizeF, WindowOps,
+};
 use anyhow::{anyhow, Context};
 use config::{
     BoldBrightening, ConfigHandle, DimensionContext, HorizontalWindowContentAlignment, TextStyle,
-    VerticalWindowContentAlignment, VisualBellTarget,
+    UnfocusedCursorStyle, VerticalWindowContentAlignment, VisualBell, VisualBellTarget,
+    WindowContentAlignment,
 };
 use euclid::num::Zero;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use ordered_float::NotNan;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::time::Instant;
 use termwiz::cellcluster::CellCluster;
-use termwiz::hyperlink::Hyperlink;
+use termwiz::cell::Underline;
+use termwiz::hyperlink::{Hyperlink, HyperlinkStyle};
 use termwiz::surface::{CursorShape, CursorVisibility, SequenceNo};
 use phaedra_font::shaper::PresentationWidth;
 use phaedra_font::units::{IntPixelLength, PixelLength};
-use phaedra_font::{ClearShapeCache, GlyphInfo, LoadedFont};
+use phaedra_font::{ClearShapeCache, FontConfiguration, GlyphInfo, LoadedFont};
+use phaedra_hash_cons::{Hc, HcSlice, InternTable};
 use phaedra_term::color::{ColorAttribute, ColorPalette};
 use phaedra_term::{CellAttributes, Line, StableRowIndex};
 use window::color::LinearRgba;
@@ -42,10 +49,20 @@ pub mod draw;
 pub mod fancy_tab_bar;
 pub mod paint;
 pub mod pane;
+pub mod preedit;
 pub mod screen_line;
 pub mod tab_bar;
 pub mod window_buttons;
 
+/// Sprites that have not been used in this many frames may be evicted
+/// from the glyph atlas when it fills up.
+/// Sprites served from the shape and line caches don't touch the atlas,
+/// so this mostly protects glyphs from content that is changing.
+const ATLAS_RECLAIM_MAX_AGE: u64 = 60;
+
+/// The number of distinct compositions kept by `intern_composing`
+const MAX_INTERNED_COMPOSING: usize = 32;
+
 /// The data that we associate with a line; we use this to cache it shape hash
 #[derive(Debug)]
 pub struct CachedLineState {
@@ -54,41 +71,108 @@ pub struct CachedLineState {
     pub shape_hash: [u8; 16],
 }
 
-#[derive(Debug, Hash, Clone, PartialEq, Eq)]
+/// Identifies the commands that describe a line.  The commands are
+/// positioned relative to the top-left corner of the line and are
+/// translated into place when they are emitted, so the key excludes
+/// the identity and position of the pane: splits showing the same
+/// content share their entries.
+///
+/// A key is made and looked up for every visible line of every frame,
+/// so it is kept small and its hash is computed just once, when it is
+/// made; hashing the key only feeds that value to the hasher.
+#[derive(Debug, Clone)]
 pub struct LineQuadCacheKey {
+    hash: u64,
+    fields: LineQuadKeyFields,
+}
+
+impl LineQuadCacheKey {
+    pub fn new(fields: LineQuadKeyFields) -> Self {
+        let mut hasher = DefaultHasher::new();
+        fields.hash(&mut hasher);
+        Self {
+            hash: hasher.finish(),
+            fields,
+        }
+    }
+}
+
+impl Hash for LineQuadCacheKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl PartialEq for LineQuadCacheKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.fields == other.fields
+    }
+}
+
+impl Eq for LineQuadCacheKey {}
+
+#[derive(Debug, Hash, Clone, PartialEq, Eq)]
+pub struct LineQuadKeyFields {
     pub config_generation: usize,
     pub shape_generation: usize,
     pub quad_generation: usize,
-    pub pane_width: usize,
-    pub pane_left: usize,
-    /// Only set if cursor.y == stable_row
-    pub composing: Option<String>,
-    pub selection: Range<usize>,
     pub shape_hash: [u8; 16],
-    pub top_pixel_y: NotNan<f32>,
-    pub left_pixel_x: NotNan<f32>,
-    pub phys_line_idx: usize,
-    pub pane_id: PaneId,
-    pub pane_is_active: bool,
+    pub palette_hash: u64,
+    /// The bits of the font scale of the pane, which is 1.0 unless
+    /// the pane has its own font scale
+    pub font_scale: u64,
+    pub pane_width: u32,
+    pub phys_line_idx: u32,
+    pub selection: Range<u32>,
+    pub flags: LineQuadFlags,
+    /// Only set if cursor.y == stable_row
+    pub composing: Option<Hc<Composition>>,
     /// A cursor position with the y value fixed at 0.
-    /// Only is_some() if the y value matches this row.
-    pub cursor: Option<CursorProperties>,
-    pub reverse_video: bool,
-    pub password_input: bool,
+    /// Only is_some() if the y value matches this row, so it is boxed
+    /// to keep the keys of all of the other rows small.
+    pub cursor: Option<Box<CursorProperties>>,
+    /// The pane that owns the cursor; the cursor is rendered from
+    /// per-pane state, so that row is never shared with another pane.
+    /// Only is_some() if `cursor` is.
+    pub cursor_pane: Option<PaneId>,
+}
+
+bitflags::bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct LineQuadFlags: u8 {
+        const PANE_IS_ACTIVE = 1<<0;
+        const REVERSE_VIDEO = 1<<1;
+        const PASSWORD_INPUT = 1<<2;
+        /// The cursor is on this row, and a dead key or the leader
+        /// is active
+        const DEAD_KEY_OR_LEADER = 1<<3;
+        /// The cursor is on this row, in its default colors
+        const CURSOR_IS_DEFAULT_COLOR = 1<<4;
+        /// The cursor is on this row, and the window has the focus
+        const WINDOW_IS_FOCUSED = 1<<5;
+    }
 }
 
 pub struct LineCommandCacheValue {
     pub expires: Option<Instant>,
-    pub commands: Arc<[RenderCommand]>,
-    pub current_highlight: Option<Arc<Hyperlink>>,
-    pub invalidate_on_hover_change: bool,
+    pub commands: HcSlice<RenderCommand>,
 }
 
 pub enum LineSeed {
-    Cached(Arc<[RenderCommand]>),
+    Cached(HcSlice<RenderCommand>),
     Fresh,
 }
 
+/// Append `commands`, which were described relative to the top-left
+/// corner of a line, to `out` with that corner moved to `origin`
+pub fn place_line_commands(
+    out: &mut Vec<RenderCommand>,
+    commands: &[RenderCommand],
+    origin: PointF,
+) {
+    out.extend(commands.iter().map(|cmd| cmd.translate(origin.x, origin.y)));
+}
+
 pub struct LineToElementParams<'a> {
     pub line: &'a Line,
     pub config: &'a ConfigHandle,
@@ -96,6 +180,9 @@ pub struct LineToElementParams<'a> {
     pub window_is_transparent: bool,
     pub reverse_video: bool,
     pub shape_key: &'a Option<LineToEleShapeCacheKey>,
+    /// Used for decorations of text rendered from the primary font
+    pub render_metrics: &'a RenderMetrics,
+    pub pane_font: Option<&'a PaneFont>,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -103,19 +190,21 @@ pub struct LineToEleShapeCacheKey {
     pub shape_hash: [u8; 16],
     pub composing: Option<(usize, String)>,
     pub shape_generation: usize,
+    /// As for LineQuadCacheKey::font_scale
+    pub font_scale: u64,
 }
 
 pub struct LineToElementShapeItem {
     pub expires: Option<Instant>,
     pub shaped: Rc<Vec<LineToElementShape>>,
-    // Only set if the line contains any hyperlinks, so
-    // that we can invalidate when it changes
-    pub current_highlight: Option<Arc<Hyperlink>>,
-    pub invalidate_on_hover_change: bool,
 }
 
 pub struct LineToElementShape {
     pub underline_tex_rect: TextureRect,
+    /// Replacement underline/strikethrough sprites for the cells of
+    /// this cluster that were rendered from a fallback font with
+    /// different metrics; cell ranges are relative to the cluster
+    pub fallback_underlines: Vec<(Range<usize>, TextureRect)>,
     pub fg_color: LinearRgba,
     pub bg_color: LinearRgba,
     pub underline_color: LinearRgba,
@@ -125,10 +214,6 @@ pub struct LineToElementShape {
     pub cluster: CellCluster,
 }
 
-pub struct RenderScreenLineResult {
-    pub invalidate_on_hover_change: bool,
-}
-
 pub struct RenderScreenLineParams<'a> {
     /// zero-based offset from top of the window viewport to the line that
     /// needs to be rendered, measured in pixels
@@ -147,7 +232,6 @@ pub struct RenderScreenLineParams<'a> {
     pub pane: Option<&'a Arc<dyn Pane>>,
 
     pub white_space: TextureRect,
-    pub filled_box: TextureRect,
 
     pub cursor_border_color: LinearRgba,
     pub foreground: LinearRgba,
@@ -166,6 +250,9 @@ pub struct RenderScreenLineParams<'a> {
     /// the resolved title font
     pub font: Option<Rc<LoadedFont>>,
     pub style: Option<&'a TextStyle>,
+    /// The fonts of a pane that has its own font scale; the line
+    /// is shaped with these rather than the window fonts
+    pub pane_font: Option<&'a PaneFont>,
 
     /// If true, use the shaper-determined pixel positions,
     /// rather than using monospace cell based positions.
@@ -176,11 +263,11 @@ pub struct RenderScreenLineParams<'a> {
     pub password_input: bool,
 }
 
+/// The parts of the cursor that affect how its row is described.
+/// Its boolean properties are carried by `LineQuadFlags`.
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct CursorProperties {
     pub position: StableCursorPosition,
-    pub dead_key_or_leader: bool,
-    pub cursor_is_default_color: bool,
     pub cursor_fg: LinearRgba,
     pub cursor_bg: LinearRgba,
     pub cursor_border_color: LinearRgba,
@@ -213,7 +300,19 @@ pub struct ComputeCellFgBgResult {
     pub cursor_border_color: LinearRgba,
     pub cursor_border_color_alt: LinearRgba,
     pub cursor_border_mix: f32,
-    pub cursor_shape: Option<CursorShape>,
+    pub cursor_style: Option<CursorRenderStyle>,
+}
+
+/// How the cursor is drawn, having taken the focus into account
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CursorRenderStyle {
+    /// A sprite of this shape; `CursorShape::Default` is a filled block
+    Shape(CursorShape),
+    /// An outline of the cell, drawn with the cursor border color
+    Hollow,
+    /// A sprite of this shape, whose color has had its alpha scaled
+    /// by `alpha`
+    Dimmed { shape: CursorShape, alpha: f32 },
 }
 
 /// Basic cache of computed data from prior cluster to avoid doing the same
@@ -235,6 +334,16 @@ impl crate::TermWindow {
         }
     }
 
+    /// Whether animations should play, per `animation_enabled`.  Each
+    /// site that eases something or schedules frames to animate it
+    /// consults this, and shows the final state at once when it is false.
+    pub fn animations_enabled(&self) -> bool {
+        self.config
+            .window_config()
+            .animation_enabled
+            .resolve(|| Connection::get().map_or(false, |conn| conn.prefers_reduced_motion()))
+    }
+
     fn get_intensity_if_bell_target_ringing(
         &self,
         pane: &Arc<dyn Pane>,
@@ -244,24 +353,24 @@ impl crate::TermWindow {
         let mut per_pane = self.pane_state(pane.pane_id());
         if let Some(ringing) = per_pane.bell_start {
             if config.bell().visual_bell.target == target {
-                let mut color_ease = ColorEase::new(
-                    config.bell().visual_bell.fade_in_duration_ms,
-                    config.bell().visual_bell.fade_in_function,
-                    config.bell().visual_bell.fade_out_duration_ms,
-                    config.bell().visual_bell.fade_out_function,
-                    Some(ringing),
-                );
-
-                let intensity = color_ease.intensity_one_shot();
-
-                match intensity {
+                match bell_frame_intensity(
+                    &config.bell().visual_bell,
+                    ringing,
+                    self.animations_enabled(),
+                ) {
                     None => {
                         per_pane.bell_start.take();
                     }
-                    Some((intensity, next)) => {
+                    Some((intensity, Some(next))) => {
                         self.update_next_frame_time(Some(next));
                         return Some(intensity);
                     }
+                    Some((intensity, None)) => {
+                        // Clear the bell in the next frame
+                        per_pane.bell_start.take();
+                        self.update_next_frame_time(Some(Instant::now()));
+                        return Some(intensity);
+                    }
                 }
             }
         }
@@ -278,14 +387,13 @@ impl crate::TermWindow {
         let mut quad = layers.allocate(layer_num)?;
         let left_offset = self.dimensions.pixel_width as f32 / 2.;
         let top_offset = self.dimensions.pixel_height as f32 / 2.;
-        let gl_state = self.render_state.as_ref().unwrap();
         quad.set_position(
             rect.min_x() as f32 - left_offset,
             rect.min_y() as f32 - top_offset,
             rect.max_x() as f32 - left_offset,
             rect.max_y() as f32 - top_offset,
         );
-        quad.set_texture(gl_state.util_sprites.filled_box.texture_coords());
+        quad.set_texture_discrete(0., 0., 0., 0.);
         quad.set_is_background();
         quad.set_fg_color(color);
         quad.set_hsv(None);
@@ -344,30 +452,55 @@ impl crate::TermWindow {
             })
     }
 
+    /// Returns the left and top padding, which is where the cells are
+    /// placed when describing the window.  The commands and ui items of
+    /// the panes and the chrome around them are then moved by
+    /// `content_alignment_offset` when the frame is painted.
     pub fn padding_left_top(&self) -> (f32, f32) {
+        let (padding_left, padding_top, _, _) = self.padding_and_gaps();
+        (padding_left, padding_top)
+    }
+
+    /// Returns where the cells are placed in the window: the padding
+    /// plus the content alignment offset.  This is used to map window
+    /// coordinates, such as those of the mouse, to cells.
+    pub fn aligned_padding_left_top(&self) -> (f32, f32) {
+        let (padding_left, padding_top) = self.padding_left_top();
+        let (dx, dy) = self.content_alignment_offset();
+        (padding_left + dx, padding_top + dy)
+    }
+
+    /// Returns the offset by which the content of the window is moved
+    /// to place it according to `window_content_alignment`
+    pub fn content_alignment_offset(&self) -> (f32, f32) {
+        let (_, _, horizontal_gap, vertical_gap) = self.padding_and_gaps();
+        window_content_offset(
+            self.config.window_config().window_content_alignment,
+            horizontal_gap,
+            vertical_gap,
+        )
+    }
+
+    /// Returns the left and top padding, and the pixels that are left
+    /// over horizontally and vertically because the window isn't an
+    /// exact multiple of the cell size
+    fn padding_and_gaps(&self) -> (f32, f32, f32, f32) {
         let h_context = DimensionContext {
             dpi: self.dimensions.dpi as f32,
-            pixel_max: self.terminal_size.pixel_width as f32,
+            pixel_max: self.dimensions.pixel_width as f32,
             pixel_cell: self.render_metrics.cell_size.width as f32,
         };
         let v_context = DimensionContext {
             dpi: self.dimensions.dpi as f32,
-            pixel_max: self.terminal_size.pixel_height as f32,
+            pixel_max: self.dimensions.pixel_height as f32,
             pixel_cell: self.render_metrics.cell_size.height as f32,
         };
 
-        let padding_left = self
-            .config
-            .window_config().window_padding
-            .left
-            .evaluate_as_pixels(h_context);
-        let padding_right = self.config.window_config().window_padding.right;
-        let padding_top = self.config.window_config().window_padding.top.evaluate_as_pixels(v_context);
-        let padding_bottom = self
-            .config
-            .window_config().window_padding
-            .bottom
-            .evaluate_as_pixels(v_context);
+        let padding = self.window_padding();
+        let padding_left = padding.left.evaluate_as_pixels(h_context);
+        let padding_right = padding.right;
+        let padding_top = padding.top.evaluate_as_pixels(v_context);
+        let padding_bottom = padding.bottom.evaluate_as_pixels(v_context);
 
         let horizontal_gap = self.dimensions.pixel_width as f32
             - self.terminal_size.pixel_width as f32
@@ -386,18 +519,8 @@ impl crate::TermWindow {
             } else {
                 0.
             };
-        let left_gap = match self.config.window_config().window_content_alignment.horizontal {
-            HorizontalWindowContentAlignment::Left => 0.,
-            HorizontalWindowContentAlignment::Center => (horizontal_gap / 2.).round(),
-            HorizontalWindowContentAlignment::Right => horizontal_gap,
-        };
-        let top_gap = match self.config.window_config().window_content_alignment.vertical {
-            VerticalWindowContentAlignment::Top => 0.,
-            VerticalWindowContentAlignment::Center => (vertical_gap / 2.).round(),
-            VerticalWindowContentAlignment::Bottom => vertical_gap,
-        };
 
-        (padding_left + left_gap, padding_top + top_gap)
+        (padding_left, padding_top, horizontal_gap, vertical_gap)
     }
 
     fn resolve_lock_glyph(
@@ -405,13 +528,15 @@ impl crate::TermWindow {
         style: &TextStyle,
         attrs: &CellAttributes,
         font: Option<&Rc<LoadedFont>>,
+        fonts: &Rc<FontConfiguration>,
         gl_state: &RenderState,
         metrics: &RenderMetrics,
     ) -> anyhow::Result<Rc<CachedGlyph>> {
         let fa_lock = "\u{f023}";
         let line = Line::from_text(fa_lock, attrs, 0, None);
         let cluster = line.cluster(None);
-        let shape_info = self.cached_cluster_shape(style, &cluster[0], gl_state, font, metrics)?;
+        let shape_info =
+            self.cached_cluster_shape(style, &cluster[0], gl_state, font, fonts, metrics)?;
         Ok(Rc::clone(&shape_info[0].glyph))
     }
 
@@ -545,7 +670,7 @@ impl crate::TermWindow {
                 let (fg_color, bg_color) = if self.use_reverse_video_cursor(&params) {
                     (params.bg_color, params.fg_color)
                 } else {
-                    (params.cursor_fg, params.cursor_bg)
+                    self.resolve_cursor_colors(&params, params.cursor_fg)
                 };
 
                 let fg_color = self.ensure_min_contrast(fg_color, bg_color);
@@ -580,7 +705,7 @@ impl crate::TermWindow {
                 let (fg_color, bg_color) = if self.use_reverse_video_cursor(&params) {
                     (params.bg_color, params.fg_color)
                 } else {
-                    (params.cursor_fg, params.cursor_bg)
+                    self.resolve_cursor_colors(&params, params.cursor_fg)
                 };
 
                 let fg_color = self.ensure_min_contrast(fg_color, bg_color);
@@ -599,7 +724,7 @@ impl crate::TermWindow {
                     bg_color,
                     bg_color_alt: bg_color,
                     bg_color_mix: 0.,
-                    cursor_shape: Some(CursorShape::Default),
+                    cursor_style: Some(CursorRenderStyle::Shape(CursorShape::Default)),
                     cursor_border_color: color,
                     cursor_border_color_alt: color,
                     cursor_border_mix: 0.,
@@ -619,10 +744,18 @@ impl crate::TermWindow {
         };
 
         let focused_and_active = self.focused.is_some() && params.is_active_pane;
+        let cursor_style = resolve_cursor_render_style(
+            focused_and_active,
+            cursor_shape,
+            &params.config.cursor().unfocused_cursor_style,
+        );
+        // The cell is drawn in the cursor colors unless the cursor has
+        // been made hollow or dimmed for want of focus
+        let uses_cursor_colors = matches!(cursor_style, CursorRenderStyle::Shape(_));
 
         let (fg_color, bg_color, cursor_bg) = match (
             params.selected,
-            focused_and_active,
+            uses_cursor_colors,
             cursor_shape,
             visibility,
         ) {
@@ -642,11 +775,11 @@ impl crate::TermWindow {
                 if self.use_reverse_video_cursor(&params) {
                     (params.bg_color, params.fg_color, params.fg_color)
                 } else {
-                    (
+                    let (fg_color, cursor_bg) = self.resolve_cursor_colors(
+                        &params,
                         params.cursor_fg.when_fully_transparent(params.fg_color),
-                        params.cursor_bg,
-                        params.cursor_bg,
-                    )
+                    );
+                    (fg_color, cursor_bg, cursor_bg)
                 }
             }
             (
@@ -665,16 +798,29 @@ impl crate::TermWindow {
                 }
             }
             // Normally, render the cell as configured (or if the window is unfocused)
-            _ => (params.fg_color, params.bg_color, params.cursor_border_color),
+            _ => match cursor_style {
+                CursorRenderStyle::Dimmed { alpha, .. } => (
+                    params.fg_color,
+                    params.bg_color,
+                    params.cursor_bg.mul_alpha(alpha),
+                ),
+                _ => (params.fg_color, params.bg_color, params.cursor_border_color),
+            },
         };
 
         let fg_color = self.ensure_min_contrast(fg_color, bg_color);
 
         let blinking = params.cursor.is_some()
             && params.is_active_pane
-            && cursor_shape.is_blinking()
-            && params.config.cursor().cursor_blink_rate != 0
-            && self.focused.is_some();
+            && cursor_blinks(
+                cursor_shape,
+                params.config.cursor().cursor_blink_rate,
+                self.animations_enabled(),
+            )
+            && self.focused.is_some()
+            && !params
+                .pane
+                .map_or(false, |pane| self.is_pane_render_frozen(pane.pane_id()));
 
         let mut fg_color_alt = fg_color;
         let bg_color_alt = bg_color;
@@ -712,22 +858,30 @@ impl crate::TermWindow {
             cursor_border_color: cursor_bg,
             cursor_border_color_alt,
             cursor_border_mix,
-            cursor_shape: if visibility == CursorVisibility::Visible {
-                match cursor_shape {
-                    CursorShape::BlinkingBlock | CursorShape::SteadyBlock if focused_and_active => {
-                        Some(CursorShape::Default)
-                    }
-                    // When not focused, convert bar to block to make it more visually
-                    // distinct from the focused bar in another pane
-                    _shape if !focused_and_active => Some(CursorShape::SteadyBlock),
-                    shape => Some(shape),
-                }
+            cursor_style: if visibility == CursorVisibility::Visible {
+                Some(cursor_style)
             } else {
                 None
             },
         }
     }
 
+    /// Returns the (text, block) colors for a block cursor drawn with
+    /// `cursor_fg` over the configured cursor_bg
+    fn resolve_cursor_colors(
+        &self,
+        params: &ComputeCellFgBgParams,
+        cursor_fg: LinearRgba,
+    ) -> (LinearRgba, LinearRgba) {
+        resolve_cursor_colors(
+            cursor_fg,
+            params.cursor_bg,
+            params.fg_color,
+            params.bg_color,
+            self.config.cursor().reverse_video_cursor_min_contrast,
+        )
+    }
+
     fn use_reverse_video_cursor(&self, params: &ComputeCellFgBgParams) -> bool {
         self.config.cursor().force_reverse_video_cursor
             && params.cursor_is_default_color
@@ -790,12 +944,14 @@ impl crate::TermWindow {
         cluster: &CellCluster,
         gl_state: &RenderState,
         font: Option<&Rc<LoadedFont>>,
+        fonts: &Rc<FontConfiguration>,
         metrics: &RenderMetrics,
     ) -> anyhow::Result<Rc<Vec<ShapedInfo>>> {
         let shape_resolve_start = Instant::now();
         let key = BorrowedShapeCacheKey {
             style,
             text: &cluster.text,
+            font_scale: fonts.get_font_scale().to_bits(),
         };
         let glyph_info = match self.lookup_cached_shape(&key) {
             Some(Ok(info)) => info,
@@ -803,7 +959,7 @@ impl crate::TermWindow {
             None => {
                 let font = match font {
                     Some(f) => Rc::clone(f),
-                    None => self.fonts.resolve_font(style)?,
+                    None => fonts.resolve_font(style)?,
                 };
                 let window = self.window.as_ref().unwrap().clone();
 
@@ -865,11 +1021,35 @@ impl crate::TermWindow {
         }
     }
 
+    /// Evict sprites that haven't been used recently from the glyph
+    /// atlas, along with the caches that may reference them.
+    /// Returns true if any space was reclaimed.
+    pub fn reclaim_texture_atlas(&mut self) -> bool {
+        let evicted = match self.render_state.as_ref() {
+            Some(render_state) => render_state
+                .glyph_cache
+                .borrow_mut()
+                .reclaim(ATLAS_RECLAIM_MAX_AGE),
+            None => 0,
+        };
+        if evicted == 0 {
+            return false;
+        }
+        log::trace!("evicted {evicted} sprites from the texture atlas");
+        self.shape_generation += 1;
+        self.shape_cache.borrow_mut().clear();
+        self.line_to_ele_shape_cache.borrow_mut().clear();
+        self.line_command_cache.borrow_mut().clear();
+        self.line_command_interner.borrow_mut().purge_unused();
+        true
+    }
+
     pub fn recreate_texture_atlas(&mut self, size: Option<usize>) -> anyhow::Result<()> {
         self.shape_generation += 1;
         self.shape_cache.borrow_mut().clear();
         self.line_to_ele_shape_cache.borrow_mut().clear();
         self.line_command_cache.borrow_mut().clear();
+        self.line_command_interner.borrow_mut().purge_unused();
         if let Some(render_state) = self.render_state.as_mut() {
             render_state.recreate_texture_atlas(&self.fonts, &self.render_metrics, size)?;
         }
@@ -909,6 +1089,29 @@ impl crate::TermWindow {
         self.line_state_cache.borrow_mut().put(id, state);
         shape_hash
     }
+
+    /// Returns the shared copy of `commands`.  Slices are freed once the
+    /// line command cache no longer references them; we sweep for those
+    /// when the table grows well beyond the capacity of the cache.
+    fn intern_line_commands(&self, commands: Vec<RenderCommand>) -> HcSlice<RenderCommand> {
+        let mut interner = self.line_command_interner.borrow_mut();
+        if interner.len() > 2 * self.line_command_cache.borrow().capacity() {
+            interner.purge_unused();
+        }
+        interner.intern_slice(commands)
+    }
+
+    /// Returns the shared copy of the composition.  Rather than
+    /// holding every composition in the life of the window, the
+    /// table is started afresh once it holds more than a few; handles
+    /// from the old table remain valid and compare equal by value.
+    fn intern_composing(&self, composing: &Composition) -> Hc<Composition> {
+        let mut interner = self.composing_interner.borrow_mut();
+        if interner.len() >= MAX_INTERNED_COMPOSING {
+            *interner = InternTable::default();
+        }
+        interner.intern(composing.clone())
+    }
 }
 
 fn resolve_fg_color_attr(
@@ -945,6 +1148,98 @@ fn resolve_fg_color_attr(
     .to_linear()
 }
 
+/// Returns the style that the hyperlink rule that produced the link
+/// of a cell asked for, if any
+pub(crate) fn hyperlink_style(attrs: &CellAttributes) -> Option<&HyperlinkStyle> {
+    attrs.hyperlink().and_then(|link| link.style())
+}
+
+/// Returns how a cell is underlined, which the style of a hyperlink
+/// rule may override for the cells that it links
+pub(crate) fn effective_underline(attrs: &CellAttributes) -> Underline {
+    hyperlink_style(attrs)
+        .and_then(|style| style.underline)
+        .unwrap_or_else(|| attrs.underline())
+}
+
+/// Returns the color of the underline of a cell whose text is drawn in
+/// `fg_color`.  SGR 58 sets a color of its own, which is resolved in the
+/// same way as a foreground color; otherwise, including after SGR 59,
+/// the underline follows the text.  The style of a hyperlink rule takes
+/// precedence over both.
+fn resolve_underline_color(
+    attrs: &CellAttributes,
+    fg_color: LinearRgba,
+    palette: &ColorPalette,
+    config: &ConfigHandle,
+    style: &config::TextStyle,
+) -> LinearRgba {
+    if let Some(color) = hyperlink_style(attrs).and_then(|style| style.underline_color) {
+        return color.to_linear_tuple_rgba();
+    }
+    match attrs.underline_color() {
+        ColorAttribute::Default => fg_color,
+        c => resolve_fg_color_attr(attrs, c, palette, config, style),
+    }
+}
+
+/// Returns the intensity of a visual bell that started ringing at
+/// `ringing`, and when that next changes, or None once it has faded out
+fn visual_bell_intensity(bell: &VisualBell, ringing: Instant) -> Option<(f32, Instant)> {
+    ColorEase::new(
+        bell.fade_in_duration_ms,
+        bell.fade_in_function,
+        bell.fade_out_duration_ms,
+        bell.fade_out_function,
+        Some(ringing),
+    )
+    .intensity_one_shot()
+}
+
+/// Returns the intensity of a visual bell that started ringing at
+/// `ringing`, and when that next changes, or None once it has faded
+/// out.  Without animation the bell is shown at full intensity with no
+/// next change, meaning that it is to be cleared after this frame.
+fn bell_frame_intensity(
+    bell: &VisualBell,
+    ringing: Instant,
+    animations_enabled: bool,
+) -> Option<(f32, Option<Instant>)> {
+    if !animations_enabled {
+        return Some((1.0, None));
+    }
+    visual_bell_intensity(bell, ringing).map(|(intensity, next)| (intensity, Some(next)))
+}
+
+/// Whether a cursor of `shape` blinks, rather than being drawn steadily
+fn cursor_blinks(shape: CursorShape, blink_rate: u64, animations_enabled: bool) -> bool {
+    shape.is_blinking() && blink_rate != 0 && animations_enabled
+}
+
+/// Returns the offset that places the content of the window according
+/// to `alignment`, given the pixels that are left over horizontally and
+/// vertically.  The offset is rounded to whole pixels, so that an odd
+/// number of leftover pixels doesn't place the cells between pixels.
+pub fn window_content_offset(
+    alignment: WindowContentAlignment,
+    horizontal_gap: f32,
+    vertical_gap: f32,
+) -> (f32, f32) {
+    let horizontal_gap = horizontal_gap.max(0.);
+    let vertical_gap = vertical_gap.max(0.);
+    let dx = match alignment.horizontal {
+        HorizontalWindowContentAlignment::Left => 0.,
+        HorizontalWindowContentAlignment::Center => horizontal_gap / 2.,
+        HorizontalWindowContentAlignment::Right => horizontal_gap,
+    };
+    let dy = match alignment.vertical {
+        VerticalWindowContentAlignment::Top => 0.,
+        VerticalWindowContentAlignment::Center => vertical_gap / 2.,
+        VerticalWindowContentAlignment::Bottom => vertical_gap,
+    };
+    (dx.round(), dy.round())
+}
+
 fn update_next_frame_time(storage: &mut Option<Instant>, next_due: Option<Instant>) {
     if let Some(next_due) = next_due {
         match storage.take() {
@@ -961,9 +1256,619 @@ fn update_next_frame_time(storage: &mut Option<Instant>, next_due: Option<Instan
     }
 }
 
+/// Choose the color of the text drawn over a block cursor.
+/// `text` is used if its contrast with `block` is at least
+/// `min_contrast`; otherwise the first of the cell foreground, the
+/// cell background, black and white that meets it is used instead.
+/// If none of them do, the one with the highest contrast wins.
+/// Returns the (text, block) colors.
+pub fn resolve_cursor_colors(
+    text: LinearRgba,
+    block: LinearRgba,
+    cell_fg: LinearRgba,
+    cell_bg: LinearRgba,
+    min_contrast: f32,
+) -> (LinearRgba, LinearRgba) {
+    let candidates = [
+        text,
+        cell_fg,
+        cell_bg,
+        LinearRgba::with_components(0., 0., 0., 1.),
+        LinearRgba::with_components(1., 1., 1., 1.),
+    ];
+
+    let text = candidates
+        .iter()
+        .find(|color| color.contrast_ratio(&block) >= min_contrast)
+        .or_else(|| {
+            candidates.iter().max_by(|a, b| {
+                a.contrast_ratio(&block)
+                    .total_cmp(&b.contrast_ratio(&block))
+            })
+        })
+        .copied()
+        .unwrap_or(text);
+
+    (text, block)
+}
+
+/// Decide how to draw a cursor of `shape`.  `focused` is true when the
+/// window has the focus and the cursor belongs to the active pane;
+/// otherwise `unfocused` applies.
+pub fn resolve_cursor_render_style(
+    focused: bool,
+    shape: CursorShape,
+    unfocused: &UnfocusedCursorStyle,
+) -> CursorRenderStyle {
+    let focused_shape = match shape {
+        CursorShape::BlinkingBlock | CursorShape::SteadyBlock => CursorShape::Default,
+        shape => shape,
+    };
+    match (focused, unfocused) {
+        (true, _) | (false, UnfocusedCursorStyle::Unchanged) => {
+            CursorRenderStyle::Shape(focused_shape)
+        }
+        // A bar is outlined as a block too, which makes it more visually
+        // distinct from the focused bar in another pane
+        (false, UnfocusedCursorStyle::Hollow) => CursorRenderStyle::Hollow,
+        (false, UnfocusedCursorStyle::Dimmed { alpha }) => CursorRenderStyle::Dimmed {
+            shape: focused_shape,
+            alpha: alpha.clamp(0., 1.),
+        },
+    }
+}
+
+/// Returns the top, bottom, left and right edges of a hollow cursor
+/// drawn `thickness` pixels thick just inside `cell`
+pub fn hollow_cursor_rects(
+    cell: crate::render_command::RectF,
+    thickness: f32,
+) -> [crate::render_command::RectF; 4] {
+    let thickness = thickness
+        .max(1.)
+        .min(cell.width() / 2.)
+        .min(cell.height() / 2.);
+    [
+        euclid::rect(cell.min_x(), cell.min_y(), cell.width(), thickness),
+        euclid::rect(
+            cell.min_x(),
+            cell.max_y() - thickness,
+            cell.width(),
+            thickness,
+        ),
+        euclid::rect(cell.min_x(), cell.min_y(), thickness, cell.height()),
+        euclid::rect(
+            cell.max_x() - thickness,
+            cell.min_y(),
+            thickness,
+            cell.height(),
+        ),
+    ]
+}
+
 fn same_hyperlink(a: Option<&Arc<Hyperlink>>, b: Option<&Arc<Hyperlink>>) -> bool {
     match (a, b) {
         (Some(a), Some(b)) => Arc::ptr_eq(a, b),
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render_command::LayerId;
+
+    fn gray(level: f32) -> LinearRgba {
+        LinearRgba::with_components(level, level, level, 1.)
+    }
+
+    #[test]
+    fn readable_cursor_text_is_kept() {
+        let (text, block) = resolve_cursor_colors(gray(0.), gray(1.), gray(0.5), gray(0.), 2.5);
+        assert_eq!(text, gray(0.));
+        assert_eq!(block, gray(1.));
+    }
+
+    #[test]
+    fn dark_on_dark_falls_back() {
+        // The cell foreground is as dark as the cursor, but the
+        // cell background is light enough to read
+        let (text, block) =
+            resolve_cursor_colors(gray(0.02), gray(0.03), gray(0.02), gray(0.8), 2.5);
+        assert_eq!(text, gray(0.8));
+        assert_eq!(block, gray(0.03));
+
+        // Nothing from the cell is usable, so white is chosen
+        let (text, _) = resolve_cursor_colors(gray(0.02), gray(0.03), gray(0.02), gray(0.), 2.5);
+        assert_eq!(text, gray(1.));
+    }
+
+    #[test]
+    fn light_on_light_falls_back() {
+        let (text, block) = resolve_cursor_colors(gray(0.9), gray(0.95), gray(0.1), gray(0.9), 2.5);
+        assert_eq!(text, gray(0.1));
+        assert_eq!(block, gray(0.95));
+
+        let (text, _) = resolve_cursor_colors(gray(0.9), gray(0.95), gray(0.9), gray(1.), 2.5);
+        assert_eq!(text, gray(0.));
+    }
+
+    /// Describes a line of `cols` cells the way that describe_screen_line
+    /// does: a background for each cell, a glyph for most of them and a
+    /// clip rect around the line, all positioned from `origin`
+    fn describe_cells(origin: PointF, cols: usize) -> Vec<RenderCommand> {
+        use crate::render_command::{QuadMode, TextureCoords};
+
+        let (width, height) = (8., 16.);
+        let line_rect = euclid::rect(origin.x, origin.y, cols as f32 * width, height);
+        let mut commands = vec![RenderCommand::SetClipRect(Some(line_rect))];
+        let mut glyphs = vec![];
+        for col in 0..cols {
+            let rect = euclid::rect(origin.x + col as f32 * width, origin.y, width, height);
+            commands.push(RenderCommand::FillRect {
+                layer: LayerId::background(),
+                rect,
+                color: gray(0.1),
+                hsv: None,
+            });
+            if col % 3 != 0 {
+                glyphs.push(RenderCommand::DrawQuad {
+                    layer: LayerId::text(),
+                    position: rect,
+                    texture: TextureCoords {
+                        left: 0.,
+                        top: 0.,
+                        right: 0.5,
+                        bottom: 0.5,
+                    },
+                    fg_color: gray(0.9),
+                    alt_color: None,
+                    hsv: None,
+                    mode: QuadMode::Glyph,
+                });
+            }
+        }
+        commands.push(RenderCommand::Batch(glyphs));
+        commands.push(RenderCommand::SetClipRect(None));
+        commands
+    }
+
+    #[test]
+    fn shared_line_commands_match_unshared() {
+        let mut interner = phaedra_hash_cons::SliceInternTable::default();
+        let left = interner.intern_slice(describe_cells(PointF::new(0., 0.), 10));
+        let right = interner.intern_slice(describe_cells(PointF::new(0., 0.), 10));
+        // Two splits showing the same line share one copy of its commands
+        assert_eq!(left.as_ptr(), right.as_ptr());
+        assert_eq!(interner.len(), 1);
+
+        for origin in [PointF::new(2., 48.), PointF::new(402., 48.)] {
+            let mut placed = vec![];
+            place_line_commands(&mut placed, &left, origin);
+            assert_eq!(placed, describe_cells(origin, 10));
+        }
+    }
+
+    #[test]
+    fn aligned_quads_and_ui_items_move_together() {
+        use crate::frame::PaneFrame;
+        use crate::termwindow::{UIItem, UIItemType};
+
+        let alignment = |horizontal, vertical| WindowContentAlignment {
+            horizontal,
+            vertical,
+        };
+        // 7 pixels are left over horizontally and 5 vertically
+        let (h_gap, v_gap) = (7., 5.);
+        let cases = [
+            (
+                alignment(
+                    HorizontalWindowContentAlignment::Center,
+                    VerticalWindowContentAlignment::Center,
+                ),
+                (4., 3.),
+            ),
+            (
+                alignment(
+                    HorizontalWindowContentAlignment::Right,
+                    VerticalWindowContentAlignment::Top,
+                ),
+                (7., 0.),
+            ),
+            (
+                alignment(
+                    HorizontalWindowContentAlignment::Left,
+                    VerticalWindowContentAlignment::Bottom,
+                ),
+                (0., 5.),
+            ),
+        ];
+
+        for (alignment, expected) in cases {
+            let (dx, dy) = window_content_offset(alignment, h_gap, v_gap);
+            assert_eq!((dx, dy), expected);
+
+            let commands = describe_cells(PointF::new(2., 48.), 4);
+            let frame = PaneFrame {
+                pane_id: 0,
+                is_active: true,
+                bounds: euclid::rect(0., 40., 36., 32.),
+                command_hash: RenderCommand::content_hash_with_salt(&commands, 9),
+                cache_key: Some(1),
+                commands: commands.into(),
+                ui_items: vec![UIItem {
+                    x: 2,
+                    y: 48,
+                    width: 8,
+                    height: 16,
+                    item_type: UIItemType::ScrollThumb,
+                    tooltip: None,
+                }],
+                last_execution_stats: None,
+                skip_streak: 0,
+                hsv: None,
+            }
+            .aligned(dx, dy, 9);
+
+            let moved = describe_cells(PointF::new(2. + dx, 48. + dy), 4);
+            assert_eq!(&*frame.commands, &moved[..]);
+            assert_eq!(
+                frame.command_hash,
+                RenderCommand::content_hash_with_salt(&moved, 9)
+            );
+            assert_eq!(frame.bounds, euclid::rect(dx, 40. + dy, 36., 32.));
+            // The first cell and the item over it are still in the same place
+            let item = &frame.ui_items[0];
+            assert_eq!((item.x as f32, item.y as f32), (2. + dx, 48. + dy));
+        }
+
+        // A window smaller than its cells isn't moved
+        assert_eq!(
+            window_content_offset(
+                alignment(
+                    HorizontalWindowContentAlignment::Right,
+                    VerticalWindowContentAlignment::Bottom,
+                ),
+                -3.,
+                -1.,
+            ),
+            (0., 0.)
+        );
+    }
+
+    #[test]
+    fn underline_color_follows_sgr_58_and_59() {
+        use phaedra_term::{Terminal, TerminalSize, Underline};
+
+        config::use_test_configuration();
+        let config = config::configuration();
+        let style = config::TextStyle::default();
+        let mut term = Terminal::new(
+            TerminalSize::default(),
+            Arc::new(config::TermConfig::new()),
+            "phaedra",
+            "test",
+            Box::new(Vec::new()),
+        );
+        term.advance_bytes(b"\x1b[4:3;58:2::255:0:0ma\x1b[4:4;59mb\x1b[4:5;58;5;2mc");
+
+        let lines = term.screen().visible_lines();
+        let line = &lines[0];
+        let palette = term.palette();
+        let fg = gray(0.5);
+        let underline = |col: usize| {
+            let attrs = line.get_cell(col).unwrap().attrs().clone();
+            let color = resolve_underline_color(&attrs, fg, &palette, &config, &style);
+            (attrs.underline(), color)
+        };
+
+        assert_eq!(
+            underline(0),
+            (
+                Underline::Curly,
+                termwiz::color::SrgbaTuple(1., 0., 0., 1.).to_linear()
+            )
+        );
+        assert_eq!(underline(1), (Underline::Dotted, fg));
+        let green = palette.resolve_fg(ColorAttribute::PaletteIndex(2));
+        assert_eq!(underline(2), (Underline::Dashed, green.to_linear()));
+    }
+
+    #[test]
+    fn hyperlink_rule_style_overrides_underline() {
+        use termwiz::color::RgbColor;
+        use termwiz::hyperlink::Rule;
+
+        config::use_test_configuration();
+        let config = config::configuration();
+        let style = config::TextStyle::default();
+        let palette = ColorPalette::default();
+        let fg = gray(0.5);
+
+        let mut rule = Rule::new(r"\bT\d+\b", "https://example.com/$0").unwrap();
+        rule.style = Some(HyperlinkStyle {
+            underline: Some(Underline::Double),
+            underline_color: Some(RgbColor::new_8bpc(0, 0, 255)),
+            ..Default::default()
+        });
+        let mut line = Line::from_text("see T42", &CellAttributes::default(), 0, None);
+        line.scan_and_create_hyperlinks(&[rule]);
+
+        let plain = line.get_cell(0).unwrap().attrs().clone();
+        assert_eq!(effective_underline(&plain), Underline::None);
+        assert_eq!(
+            resolve_underline_color(&plain, fg, &palette, &config, &style),
+            fg
+        );
+
+        let linked = line.get_cell(4).unwrap().attrs().clone();
+        assert_eq!(effective_underline(&linked), Underline::Double);
+        assert_eq!(
+            resolve_underline_color(&linked, fg, &palette, &config, &style),
+            termwiz::color::SrgbaTuple(0., 0., 1., 1.).to_linear()
+        );
+    }
+
+    #[test]
+    fn visual_bell_fades_out() {
+        use config::EasingFunction;
+        use std::time::Duration;
+
+        config::use_test_configuration();
+        let bell = VisualBell {
+            fade_in_duration_ms: 0,
+            fade_in_function: EasingFunction::Linear,
+            fade_out_duration_ms: 6_000,
+            fade_out_function: EasingFunction::Linear,
+            target: VisualBellTarget::PostProcess,
+        };
+
+        let now = Instant::now();
+        let (intensity, next) = visual_bell_intensity(&bell, now).unwrap();
+        assert!(intensity > 0.99, "{intensity}");
+        assert!(next > now);
+
+        let ringing = now - Duration::from_millis(4_500);
+        let (intensity, _) = visual_bell_intensity(&bell, ringing).unwrap();
+        assert!(intensity > 0. && intensity < 0.5, "{intensity}");
+
+        let ringing = now - Duration::from_millis(6_100);
+        assert_eq!(visual_bell_intensity(&bell, ringing), None);
+    }
+
+    #[test]
+    fn visual_bell_without_animation_shows_one_frame() {
+        use config::{AnimationEnabled, EasingFunction};
+
+        let bell = VisualBell {
+            fade_in_duration_ms: 100,
+            fade_in_function: EasingFunction::Linear,
+            fade_out_duration_ms: 6_000,
+            fade_out_function: EasingFunction::Linear,
+            target: VisualBellTarget::BackgroundColor,
+        };
+        let animate = AnimationEnabled::Never.resolve(|| false);
+        assert_eq!(
+            bell_frame_intensity(&bell, Instant::now(), animate),
+            Some((1.0, None))
+        );
+    }
+
+    #[test]
+    fn cursor_without_animation_is_steady() {
+        use config::AnimationEnabled;
+
+        assert!(cursor_blinks(CursorShape::BlinkingBlock, 800, true));
+        assert!(!cursor_blinks(CursorShape::SteadyBlock, 800, true));
+        assert!(!cursor_blinks(CursorShape::BlinkingBar, 0, true));
+        let animate = AnimationEnabled::Never.resolve(|| false);
+        assert!(!cursor_blinks(CursorShape::BlinkingBlock, 800, animate));
+    }
+
+    #[test]
+    fn unreachable_threshold_picks_highest_contrast() {
+        let (text, _) = resolve_cursor_colors(gray(0.5), gray(0.9), gray(0.6), gray(0.7), 100.);
+        assert_eq!(text, gray(0.));
+    }
+
+    fn key_fields(phys_line_idx: u32) -> LineQuadKeyFields {
+        LineQuadKeyFields {
+            config_generation: 1,
+            shape_generation: 2,
+            quad_generation: 3,
+            shape_hash: [phys_line_idx as u8; 16],
+            palette_hash: 0x1234,
+            font_scale: 1f64.to_bits(),
+            pane_width: 80,
+            phys_line_idx,
+            selection: 0..0,
+            flags: LineQuadFlags::PANE_IS_ACTIVE,
+            composing: None,
+            cursor: None,
+            cursor_pane: None,
+        }
+    }
+
+    fn key_hash(key: &LineQuadCacheKey) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn line_quad_keys_compare_by_their_fields() {
+        let key = LineQuadCacheKey::new(key_fields(4));
+        assert_eq!(key, LineQuadCacheKey::new(key_fields(4)));
+        assert_eq!(
+            key_hash(&key),
+            key_hash(&LineQuadCacheKey::new(key_fields(4)))
+        );
+        assert_ne!(key, LineQuadCacheKey::new(key_fields(5)));
+
+        let mut inactive = key_fields(4);
+        inactive.flags.remove(LineQuadFlags::PANE_IS_ACTIVE);
+        assert_ne!(key, LineQuadCacheKey::new(inactive));
+
+        let mut selected = key_fields(4);
+        selected.selection = 2..5;
+        assert_ne!(key, LineQuadCacheKey::new(selected));
+
+        let mut focused = key_fields(4);
+        focused.flags.insert(LineQuadFlags::WINDOW_IS_FOCUSED);
+        assert_ne!(key, LineQuadCacheKey::new(focused));
+    }
+
+    #[test]
+    fn unfocused_cursor_style_is_resolved() {
+        use CursorRenderStyle::*;
+
+        for style in [
+            UnfocusedCursorStyle::Hollow,
+            UnfocusedCursorStyle::Dimmed { alpha: 0.5 },
+            UnfocusedCursorStyle::Unchanged,
+        ] {
+            // Focus always wins, and a block is filled
+            assert_eq!(
+                resolve_cursor_render_style(true, CursorShape::BlinkingBlock, &style),
+                Shape(CursorShape::Default)
+            );
+            assert_eq!(
+                resolve_cursor_render_style(true, CursorShape::SteadyBar, &style),
+                Shape(CursorShape::SteadyBar)
+            );
+        }
+
+        let hollow = UnfocusedCursorStyle::Hollow;
+        assert_eq!(
+            resolve_cursor_render_style(false, CursorShape::SteadyBlock, &hollow),
+            Hollow
+        );
+        assert_eq!(
+            resolve_cursor_render_style(false, CursorShape::BlinkingBar, &hollow),
+            Hollow
+        );
+
+        assert_eq!(
+            resolve_cursor_render_style(
+                false,
+                CursorShape::SteadyBlock,
+                &UnfocusedCursorStyle::Dimmed { alpha: 0.5 }
+            ),
+            Dimmed {
+                shape: CursorShape::Default,
+                alpha: 0.5
+            }
+        );
+        assert_eq!(
+            resolve_cursor_render_style(
+                false,
+                CursorShape::SteadyUnderline,
+                &UnfocusedCursorStyle::Dimmed { alpha: 3. }
+            ),
+            Dimmed {
+                shape: CursorShape::SteadyUnderline,
+                alpha: 1.
+            }
+        );
+
+        assert_eq!(
+            resolve_cursor_render_style(
+                false,
+                CursorShape::SteadyBlock,
+                &UnfocusedCursorStyle::Unchanged
+            ),
+            Shape(CursorShape::Default)
+        );
+    }
+
+    #[test]
+    fn hollow_cursor_outlines_the_cell() {
+        let cell = euclid::rect(10., 20., 8., 16.);
+        assert_eq!(
+            hollow_cursor_rects(cell, 2.),
+            [
+                euclid::rect(10., 20., 8., 2.),
+                euclid::rect(10., 34., 8., 2.),
+                euclid::rect(10., 20., 2., 16.),
+                euclid::rect(16., 20., 2., 16.),
+            ]
+        );
+
+        // The edges are at least a pixel thick, and never more than
+        // half of the cell
+        assert_eq!(hollow_cursor_rects(cell, 0.)[0].height(), 1.);
+        assert_eq!(hollow_cursor_rects(cell, 10.)[2].width(), 4.);
+    }
+
+    #[test]
+    fn composing_text_from_another_table_matches() {
+        let composing = |table: &mut InternTable<Composition>, text: &str| {
+            let mut fields = key_fields(0);
+            fields.composing = Some(table.intern(Composition::new(text)));
+            LineQuadCacheKey::new(fields)
+        };
+        let mut first = InternTable::default();
+        let mut second = InternTable::default();
+        // As when the composing table has been started afresh
+        assert_eq!(composing(&mut first, "ka"), composing(&mut second, "ka"));
+        assert_ne!(composing(&mut first, "ka"), composing(&mut first, "kan"));
+        assert_ne!(
+            composing(&mut first, "ka"),
+            LineQuadCacheKey::new(key_fields(0))
+        );
+    }
+
+    #[test]
+    fn composing_segments_are_part_of_the_key() {
+        use ::window::{PreeditSegment, PreeditUnderline};
+        let mut table = InternTable::default();
+        let mut composing = |underline| {
+            let mut fields = key_fields(0);
+            fields.composing = Some(table.intern(Composition {
+                text: "kanji".to_string(),
+                segments: vec![PreeditSegment {
+                    range: 0..5,
+                    underline,
+                }],
+                caret: Some(0),
+            }));
+            LineQuadCacheKey::new(fields)
+        };
+        // Converting a clause changes its underline but not the text
+        assert_ne!(
+            composing(PreeditUnderline::Thin),
+            composing(PreeditUnderline::Thick)
+        );
+    }
+
+    #[test]
+    fn bench_line_quad_key_probe() {
+        config::use_test_configuration();
+
+        benchmarking::warm_up();
+
+        for &rows in &[100u32, 1000, 10_000] {
+            let bench_result = benchmarking::measure_function(move |measurer| {
+                let config = config::configuration();
+                let mut cache = lfucache::LfuCache::new(
+                    "line_command_cache.hit.rate",
+                    "line_command_cache.miss.rate",
+                    |config| config.cache().line_quad_cache_size,
+                    &config,
+                );
+                for row in 0..rows {
+                    cache.put(LineQuadCacheKey::new(key_fields(row)), row);
+                }
+
+                measurer.measure(|| {
+                    for row in 0..rows {
+                        let key = LineQuadCacheKey::new(key_fields(row));
+                        let _hit = cache.get(&key);
+                    }
+                });
+            })
+            .unwrap();
+            println!("{}: {:?}", rows, bench_result.elapsed());
+        }
+    }
+}
@@ -1,16 +1,37 @@
+use crate::termwindow::gpu_recovery::GpuDeviceLost;
+use crate::termwindow::keyevent::format_key_table_chip;
+use crate::colorease::Transition;
+use crate::termwindow::render::describe::{
+    broadcast_pane_border, catch_panic, describe_or_placeholder, frozen_pane_indicator,
+    palette_hash, reusable_pane_frame, snapshot_cache_key,
+};
 use crate::termwindow::TermWindowNotif;
-use crate::execute_render::{execute_commands, execute_commands_with_history};
+use crate::diagnostics;
+use crate::execute_render::{
+    execute_commands, execute_commands_with_history, execute_commands_with_hsv,
+};
+use crate::frame::{align_commands, align_ui_items};
+use crate::frame_budget::FrameStage;
+use crate::frame_recording::{self, FrameCommands};
+use crate::render_command::{HsbTransform as CmdHsbTransform, RenderCommand};
+use crate::render_diagnostics::{elapsed_us, render_diag, section};
 use crate::render_plan::{
     quad_count_for_snapshot, snapshot_layers, CofreeContext, QuadRange, RenderPlan, RenderSection,
     ScissorRect, SectionOutcome,
 };
 use config::observers::*;
+use config::VisualBellTarget;
 use mux::pane::TerminalView;
+use mux::tab::PositionedPane;
 use ::window::bitmaps::atlas::OutOfTextureSpace;
+use ::window::color::LinearRgba;
 use ::window::WindowOps;
 use smol::Timer;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use phaedra_font::ClearShapeCache;
+use phaedra_frame_recording::FrameInfo;
+use phaedra_render_diagnostics::PaneTrace;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllowImage {
@@ -24,12 +45,8 @@ fn frame_has_buffers_for_range(
     quad_range: &QuadRange,
 ) -> bool {
     quad_range.end.iter().all(|end_snapshot| {
-        let start_quad =
-            quad_count_for_snapshot(&quad_range.start, end_snapshot.zindex, end_snapshot.sub_idx);
-        end_snapshot.quad_count <= start_quad
-            || frame
-                .buffer(end_snapshot.zindex, end_snapshot.sub_idx)
-                .is_some()
+        let start_quad = quad_count_for_snapshot(&quad_range.start, end_snapshot.layer);
+        end_snapshot.quad_count <= start_quad || frame.buffer(end_snapshot.layer).is_some()
     })
 }
 
@@ -38,14 +55,13 @@ fn advance_quad_counts_for_range(
     quad_range: &QuadRange,
 ) -> anyhow::Result<()> {
     for end_snapshot in &quad_range.end {
-        let start_quad =
-            quad_count_for_snapshot(&quad_range.start, end_snapshot.zindex, end_snapshot.sub_idx);
+        let start_quad = quad_count_for_snapshot(&quad_range.start, end_snapshot.layer);
         if end_snapshot.quad_count <= start_quad {
             continue;
         }
         let quad_delta = end_snapshot.quad_count - start_quad;
-        let render_layer = render_state.layer_for_zindex(end_snapshot.zindex)?;
-        render_layer.vb.borrow()[end_snapshot.sub_idx].advance_quad_count(quad_delta);
+        let render_layer = render_state.layer_for_zindex(end_snapshot.layer.zindex())?;
+        render_layer.vb.borrow()[end_snapshot.layer.sub_layer()].advance_quad_count(quad_delta);
     }
     Ok(())
 }
@@ -59,7 +75,21 @@ impl crate::TermWindow {
         // Start with the assumption that we should allow images to render
         self.allow_images = AllowImage::Yes;
 
+        // Keep the key table chip in the tab bar current; the stack
+        // schedules the next frame while any activation has a timeout
+        if self.config.key_input().show_active_key_table {
+            let stack = self.key_table_stack();
+            if format_key_table_chip(&stack) != self.key_table_chip {
+                self.update_title_impl();
+            }
+        }
+
+        self.update_tooltip();
+
         let start = Instant::now();
+        self.advance_overscroll(start);
+        self.render_diagnostics.begin_frame(&self.config, self.mux_window_id);
+        self.frame_timings.clear();
 
         {
             let diff = start.duration_since(self.last_fps_check_time);
@@ -71,8 +101,31 @@ impl crate::TermWindow {
             }
         }
 
+        if let Some(render_state) = self.render_state.as_ref() {
+            render_state.glyph_cache.borrow_mut().begin_frame();
+        }
+        let mut reclaimed = false;
+
         'pass: for pass in 0.. {
-            match self.paint_pass() {
+            if let Some(message) = self.paint_panics.disabled() {
+                let message = message.to_string();
+                if let Err(err) = self.paint_disabled(&message) {
+                    log::error!("paint_disabled failed: {:#}", err);
+                }
+                break 'pass;
+            }
+            let result = catch_panic(|| self.paint_pass());
+            if self.paint_panics.record(&result) {
+                // The pass was abandoned part way through; don't let
+                // the next one build on anything that it left behind
+                self.invalidate_fancy_tab_bar();
+                self.invalidate_modal();
+                self.prev_pane_frames.clear();
+                if let Some(render_state) = self.render_state.as_ref() {
+                    *render_state.prev_frame_buffers.borrow_mut() = None;
+                }
+            }
+            match result {
                 Ok(_) => match self.render_state.as_mut().unwrap().allocated_more_quads() {
                     Ok(allocated) => {
                         if !allocated {
@@ -96,7 +149,14 @@ impl crate::TermWindow {
                         current_size,
                     }) = err.root_cause().downcast_ref::<OutOfTextureSpace>()
                     {
-                        let result = if pass == 0 {
+                        let atlas_start = Instant::now();
+                        let result = if pass == 0 && self.reclaim_texture_atlas() {
+                            // Space held by stale sprites was released;
+                            // try again without discarding the rest
+                            log::trace!("reclaimed texture atlas space");
+                            reclaimed = true;
+                            Ok(())
+                        } else if pass == 0 || (pass == 1 && reclaimed) {
                             // Let's try clearing out the atlas and trying again
                             // self.clear_texture_atlas()
                             log::trace!("recreate_texture_atlas");
@@ -105,6 +165,7 @@ impl crate::TermWindow {
                             log::trace!("grow texture atlas to {}", size);
                             self.recreate_texture_atlas(Some(size))
                         };
+                        self.frame_timings.record_since(FrameStage::AtlasRebuild, atlas_start);
                         self.invalidate_fancy_tab_bar();
                         self.invalidate_modal();
                         self.prev_pane_frames.clear();
@@ -150,8 +211,22 @@ impl crate::TermWindow {
         }
         log::debug!("paint_impl before call_draw elapsed={:?}", start.elapsed());
 
-        self.call_draw().ok();
+        let draw_start = Instant::now();
+        if let Err(err) = self.call_draw() {
+            if err.root_cause().downcast_ref::<GpuDeviceLost>().is_some() {
+                self.gpu_device_lost();
+            }
+        }
+        self.frame_timings.record_since(FrameStage::Draw, draw_start);
         self.last_frame_duration = start.elapsed();
+        if let Some(warning) = self.frame_budget.check(
+            self.config.runtime().frame_time_warning_ms,
+            self.last_frame_duration,
+            &self.frame_timings,
+            Instant::now(),
+        ) {
+            log::warn!("window {}: {warning}", self.mux_window_id);
+        }
         log::debug!(
             "paint_impl elapsed={:?}, fps={}",
             self.last_frame_duration,
@@ -159,10 +234,23 @@ impl crate::TermWindow {
         );
         metrics::histogram!("gui.paint.impl").record(self.last_frame_duration);
         metrics::histogram!("gui.paint.impl.rate").record(1.);
+        self.render_diagnostics.end_frame(self.last_frame_duration);
+        frame_recording::record_frame(
+            &self.config,
+            FrameInfo {
+                window_id: self.mux_window_id,
+                pixel_width: self.dimensions.pixel_width,
+                pixel_height: self.dimensions.pixel_height,
+                config_generation: self.config.generation(),
+            },
+            self.recorded_frame.take(),
+        );
+
+        self.cache_registry.frame(self, self.config.cache());
 
         // Schedule continuous rendering for animated shaders
         if let Some(ref webgpu) = self.webgpu {
-            if webgpu.has_postprocess() {
+            if webgpu.has_postprocess() && self.animations_enabled() {
                 let fps = self.config.gpu().webgpu_shader_fps;
                 if fps > 0 {
                     let frame_interval = Duration::from_millis(1000 / fps as u64);
@@ -209,15 +297,45 @@ impl crate::TermWindow {
         }
     }
 
+    /// Paints the static error shown in place of the window's content
+    /// once its rendering has been disabled
+    fn paint_disabled(&mut self, message: &str) -> anyhow::Result<()> {
+        let gl_state = self.render_state.as_ref().unwrap();
+        for layer in gl_state.layers.borrow().iter() {
+            layer.clear_quad_allocation();
+        }
+        gl_state.layer_clears.borrow_mut().clear();
+        self.ui_items.clear();
+        self.render_plan = None;
+        self.recorded_frame = None;
+
+        let commands = self.describe_window_error(message);
+        let render_state = self.render_state.as_ref().unwrap();
+        execute_commands(
+            &commands,
+            render_state,
+            self.dimensions.pixel_width as f32 / 2.0,
+            self.dimensions.pixel_height as f32 / 2.0,
+        )?;
+        Ok(())
+    }
+
     pub fn paint_pass(&mut self) -> anyhow::Result<()> {
         {
             let gl_state = self.render_state.as_ref().unwrap();
             for layer in gl_state.layers.borrow().iter() {
                 layer.clear_quad_allocation();
             }
+            gl_state.layer_clears.borrow_mut().clear();
         }
         self.ui_items.clear();
         self.render_plan = None;
+        self.recorded_frame = None;
+        // Only the final pass of a frame is of interest
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.panes.clear();
+            trace.chrome.clear();
+        });
 
         let panes = self.get_panes_to_render();
         let focused = self.focused.is_some();
@@ -240,19 +358,26 @@ impl crate::TermWindow {
         let top_offset = pixel_dims.1 / 2.0;
         let viewport_width = pixel_dims.0.max(0.0) as u32;
         let viewport_height = pixel_dims.1.max(0.0) as u32;
-        let filled_box = render_state.util_sprites.filled_box.texture_coords();
         let mut plan = RenderPlan::new(viewport_width, viewport_height);
         let mut ui_items = Vec::new();
-
-        let background = self.describe_window_background(&panes)?;
+        // The panes and the chrome around them are described as though
+        // aligned to the top left, and are then moved into place
+        let (align_x, align_y) = self.content_alignment_offset();
+
+        // Start from a transparent frame, so that the window background
+        // is composited according to its opacity
+        let mut background = vec![RenderCommand::Clear {
+            color: LinearRgba(0., 0., 0., 0.),
+            layer: None,
+        }];
+        background.extend(self.describe_window_background(&panes)?);
         let background_start = snapshot_layers(render_state);
-        execute_commands(
-            &background,
-            render_state,
-            left_offset,
-            top_offset,
-            &filled_box,
-        )?;
+        execute_commands(&background, render_state, left_offset, top_offset)?;
+        let mut recorded = frame_recording::is_recording(&self.config).then(|| FrameCommands {
+            background: background.clone(),
+            ..FrameCommands::default()
+        });
+        plan.record(&background);
         let background_end = snapshot_layers(render_state);
         plan.sections.push(RenderSection {
             scissor: None,
@@ -269,18 +394,43 @@ impl crate::TermWindow {
         let previous_frame = render_state.prev_frame_buffers.borrow();
         let current_pane_order: Vec<_> = panes.iter().map(|pane| pane.pane.pane_id()).collect();
         let mut pane_skip_chain_valid = self.prev_pane_order == current_pane_order;
+        self.describe_failures
+            .borrow_mut()
+            .retain_panes(&current_pane_order);
+        self.pane_fades
+            .borrow_mut()
+            .retain(|pane_id, _| current_pane_order.contains(pane_id));
         let mut cofree = CofreeContext::new();
 
         for pos in &panes {
             let pane_id = pos.pane.pane_id();
             let snapshot = pos.pane.snapshot_for_render(self.get_viewport(pane_id));
-            let terminal_hash = snapshot.content_hash();
-            let cache_key = self.pane_describe_cache_key(pane_id, pos, terminal_hash);
+            let cache_key = self.pane_describe_cache_key(pane_id, pos, &snapshot);
             let prior = self.prev_pane_frames.get(&pane_id);
             let prior_skip_streak = prior.map_or(0, |frame| frame.skip_streak);
+            render_diag!(self.render_diagnostics, |trace| {
+                trace.panes.push(PaneTrace {
+                    pane_id,
+                    cache_key,
+                    terminal_hash: snapshot_cache_key(&snapshot),
+                    config_generation: self.config.generation(),
+                    shape_generation: self.shape_generation,
+                    palette_hash: palette_hash(snapshot.palette()),
+                    described: false,
+                    skipped: false,
+                    commands: 0,
+                    quads: 0,
+                    describe_us: 0,
+                    execute_us: 0,
+                })
+            });
 
-            let (mut pane_frame, candidate_skippable) = match prior {
-                Some(cached) if cached.cache_key == cache_key => {
+            let describe_start = self.render_diagnostics.start_timer();
+            let stage_start = Instant::now();
+            let frozen = self.is_pane_render_frozen(pane_id);
+            let reusable = reusable_pane_frame(prior, cache_key, frozen);
+            let (mut pane_frame, candidate_skippable) = match reusable {
+                Some(cached) => {
                     let mut frame = cached.clone();
                     frame.skip_streak = prior_skip_streak.saturating_add(1);
                     log::trace!(
@@ -296,11 +446,27 @@ impl crate::TermWindow {
                         prior_skip_streak,
                         cofree.skip_streak
                     );
-                    (self.describe_pane_with_snapshot(pos, snapshot, cache_key)?, false)
+                    let frame = describe_or_placeholder(
+                        pane_id,
+                        &self.describe_failures,
+                        || self.describe_pane_with_snapshot(pos, snapshot, cache_key),
+                        |err| self.describe_pane_placeholder(pos, err),
+                    );
+                    (
+                        frame.aligned(align_x, align_y, self.pane_hash_salt()),
+                        false,
+                    )
                 }
             };
+            self.frame_timings.record_since(FrameStage::DescribePane(pane_id), stage_start);
+            let describe_us = elapsed_us(describe_start);
 
-            let prior_quad_range = if candidate_skippable && pane_skip_chain_valid {
+            // The quads of the prior frame can only be reused if they
+            // were executed with the same transform
+            let pane_hsv = self.pane_hsv(pos);
+            let same_hsv = prior.map_or(false, |frame| frame.hsv == pane_hsv);
+
+            let prior_quad_range = if candidate_skippable && pane_skip_chain_valid && same_hsv {
                 previous_frame
                     .as_ref()
                     .and_then(|frame| frame.section_ranges.get(plan.sections.len()).cloned())
@@ -315,16 +481,18 @@ impl crate::TermWindow {
             };
 
             let pane_start = snapshot_layers(render_state);
+            let execute_start = self.render_diagnostics.start_timer();
+            let stage_start = Instant::now();
             let outcome = if let Some(prior_quad_range) = prior_quad_range.as_ref() {
                 advance_quad_counts_for_range(render_state, prior_quad_range)?;
                 SectionOutcome::Skipped
             } else if candidate_skippable {
-                execute_commands(
+                execute_commands_with_hsv(
                     &pane_frame.commands,
                     render_state,
                     left_offset,
                     top_offset,
-                    &filled_box,
+                    pane_hsv.as_ref(),
                 )?;
                 SectionOutcome::Executed {
                     stats: pane_frame.last_execution_stats.unwrap_or_default(),
@@ -335,15 +503,34 @@ impl crate::TermWindow {
                     render_state,
                     left_offset,
                     top_offset,
-                    &filled_box,
+                    pane_hsv.as_ref(),
                 )?;
                 let stats = history.stats();
                 pane_frame.last_execution_stats = Some(stats);
                 pane_frame.skip_streak = 0;
                 SectionOutcome::Executed { stats }
             };
+            self.frame_timings.record_since(FrameStage::ExecutePane(pane_id), stage_start);
             let pane_end = snapshot_layers(render_state);
+            pane_frame.hsv = pane_hsv;
+            plan.record(&pane_frame.commands);
             let skippable = prior_quad_range.is_some();
+            render_diag!(self.render_diagnostics, |trace| {
+                if let Some(pane) = trace.panes.last_mut() {
+                    pane.described = !candidate_skippable;
+                    pane.skipped = skippable;
+                    pane.commands = pane_frame.commands.len();
+                    pane.quads = pane_end
+                        .iter()
+                        .map(|end| {
+                            end.quad_count
+                                .saturating_sub(quad_count_for_snapshot(&pane_start, end.layer))
+                        })
+                        .sum();
+                    pane.describe_us = describe_us;
+                    pane.execute_us = elapsed_us(execute_start);
+                }
+            });
             if !skippable {
                 pane_skip_chain_valid = false;
             }
@@ -365,9 +552,36 @@ impl crate::TermWindow {
             });
 
             ui_items.extend(pane_frame.ui_items.iter().cloned());
+            if let Some(recorded) = recorded.as_mut() {
+                // A recording is replayed without the execute-time
+                // transform, so it is applied to the recorded commands
+                let (command_hash, commands) = match pane_frame.hsv.as_ref() {
+                    Some(hsv) => {
+                        let commands: Arc<[RenderCommand]> = pane_frame
+                            .commands
+                            .iter()
+                            .map(|cmd| cmd.clone().with_hsv(hsv))
+                            .collect();
+                        (RenderCommand::content_hash(&commands), commands)
+                    }
+                    None => (pane_frame.command_hash, Arc::clone(&pane_frame.commands)),
+                };
+                recorded.panes.push((pane_id, command_hash, commands));
+            }
             new_pane_frames.insert(pane_id, pane_frame);
         }
 
+        plan.visual_bell_intensity = panes
+            .iter()
+            .filter_map(|pos| {
+                self.get_intensity_if_bell_target_ringing(
+                    &pos.pane,
+                    &self.config,
+                    VisualBellTarget::PostProcess,
+                )
+            })
+            .fold(0., f32::max);
+
         let chrono_skip_streak_max = cofree
             .prior_outcomes
             .iter()
@@ -385,19 +599,27 @@ impl crate::TermWindow {
         metrics::histogram!("gui.chrono.skip_rate").record(cofree.skip_rate());
 
         let chrome_start = snapshot_layers(render_state);
+        let chrome_timer = Instant::now();
+        let mut tab_bar_describe = Duration::ZERO;
 
+        let section_start = self.render_diagnostics.start_timer();
         if self.show_tab_bar {
+            let describe_start = Instant::now();
             let (tab_bar, tab_bar_ui_items) = self.describe_tab_bar()?;
-            execute_commands(
-                &tab_bar,
-                render_state,
-                left_offset,
-                top_offset,
-                &filled_box,
-            )?;
+            tab_bar_describe = describe_start.elapsed();
+            self.frame_timings.record(FrameStage::DescribeTabBar, tab_bar_describe);
+            execute_commands(&tab_bar, render_state, left_offset, top_offset)?;
+            plan.record(&tab_bar);
+            if let Some(recorded) = recorded.as_mut() {
+                recorded.chrome.extend_from_slice(&tab_bar);
+            }
             ui_items.extend(tab_bar_ui_items);
+            render_diag!(self.render_diagnostics, |trace| {
+                trace.chrome.push(section("tab_bar", section_start))
+            });
         }
 
+        let section_start = self.render_diagnostics.start_timer();
         if let Some(pane) = self.get_active_pane_or_overlay() {
             let splits = {
                 let mux = mux::Mux::get();
@@ -415,37 +637,119 @@ impl crate::TermWindow {
             };
 
             for split in &splits {
-                let (commands, items) = self.describe_split(split, &pane);
-                execute_commands(
-                    &commands,
-                    render_state,
-                    left_offset,
-                    top_offset,
-                    &filled_box,
-                )?;
-                ui_items.extend(items);
+                let tooltip = self.split_tooltip(split, &panes);
+                let (commands, items) = self.describe_split(split, &pane, tooltip);
+                let commands = align_commands(commands, align_x, align_y);
+                execute_commands(&commands, render_state, left_offset, top_offset)?;
+                plan.record(&commands);
+                if let Some(recorded) = recorded.as_mut() {
+                    recorded.chrome.extend_from_slice(&commands);
+                }
+                ui_items.extend(align_ui_items(items, align_x, align_y));
             }
         }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("splits", section_start))
+        });
 
+        let section_start = self.render_diagnostics.start_timer();
+        if self.config.window().frozen_pane_indicator {
+            let size = (self.render_metrics.cell_size.width as f32 / 2.).max(2.);
+            for pos in &panes {
+                let pane_id = pos.pane.pane_id();
+                let frame = match new_pane_frames.get(&pane_id) {
+                    Some(frame) if self.is_pane_render_frozen(pane_id) => frame,
+                    _ => continue,
+                };
+                let color = pos.pane.palette().split.to_linear();
+                let commands = [frozen_pane_indicator(frame.bounds, size, color)];
+                execute_commands(&commands, render_state, left_offset, top_offset)?;
+                plan.record(&commands);
+                if let Some(recorded) = recorded.as_mut() {
+                    recorded.chrome.extend_from_slice(&commands);
+                }
+            }
+        }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("frozen_panes", section_start))
+        });
+
+        let section_start = self.render_diagnostics.start_timer();
+        let thickness = (self.render_metrics.underline_height as f32 * 2.).max(2.);
+        for pos in &panes {
+            let pane_id = pos.pane.pane_id();
+            let frame = match new_pane_frames.get(&pane_id) {
+                Some(frame) if self.is_pane_broadcasting(pane_id) => frame,
+                _ => continue,
+            };
+            let color = self
+                .config
+                .color_config()
+                .resolved_palette
+                .visual_bell
+                .map(|c| c.to_linear())
+                .unwrap_or_else(|| pos.pane.palette().foreground.to_linear());
+            let commands = broadcast_pane_border(frame.bounds, thickness, color);
+            execute_commands(&commands, render_state, left_offset, top_offset)?;
+            plan.record(&commands);
+            if let Some(recorded) = recorded.as_mut() {
+                recorded.chrome.extend_from_slice(&commands);
+            }
+        }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("broadcast_panes", section_start))
+        });
+
+        let section_start = self.render_diagnostics.start_timer();
         let borders = self.describe_window_borders();
-        execute_commands(
-            &borders,
-            render_state,
-            left_offset,
-            top_offset,
-            &filled_box,
-        )?;
+        execute_commands(&borders, render_state, left_offset, top_offset)?;
+        plan.record(&borders);
+        if let Some(recorded) = recorded.as_mut() {
+            recorded.chrome.extend_from_slice(&borders);
+        }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("borders", section_start))
+        });
 
+        let section_start = self.render_diagnostics.start_timer();
         let (modal, modal_ui_items) = self.describe_modal()?;
-        execute_commands(
-            &modal,
-            render_state,
-            left_offset,
-            top_offset,
-            &filled_box,
-        )?;
-        ui_items.extend(modal_ui_items);
+        let modal = align_commands(modal, align_x, align_y);
+        execute_commands(&modal, render_state, left_offset, top_offset)?;
+        plan.record(&modal);
+        if let Some(recorded) = recorded.as_mut() {
+            recorded.chrome.extend_from_slice(&modal);
+        }
+        ui_items.extend(align_ui_items(modal_ui_items, align_x, align_y));
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("modal", section_start))
+        });
+
+        let section_start = self.render_diagnostics.start_timer();
+        let tooltip = self.describe_tooltip()?;
+        execute_commands(&tooltip, render_state, left_offset, top_offset)?;
+        plan.record(&tooltip);
+        if let Some(recorded) = recorded.as_mut() {
+            recorded.chrome.extend_from_slice(&tooltip);
+        }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("tooltip", section_start))
+        });
 
+        let section_start = self.render_diagnostics.start_timer();
+        let tab_drag = self.describe_tab_drag()?;
+        execute_commands(&tab_drag, render_state, left_offset, top_offset)?;
+        plan.record(&tab_drag);
+        if let Some(recorded) = recorded.as_mut() {
+            recorded.chrome.extend_from_slice(&tab_drag);
+        }
+        render_diag!(self.render_diagnostics, |trace| {
+            trace.chrome.push(section("tab_drag", section_start))
+        });
+
+        self.frame_timings.record(
+            FrameStage::ExecuteChrome,
+            chrome_timer.elapsed().saturating_sub(tab_bar_describe),
+        );
         let chrome_end = snapshot_layers(render_state);
         plan.sections.push(RenderSection {
             scissor: None,
@@ -471,11 +775,55 @@ impl crate::TermWindow {
             0.0
         });
 
+        if self.config.gpu().experimental_batch_render_sections {
+            plan.optimize();
+        }
+        plan.layer_clears = render_state.layer_clears.take();
+        diagnostics::record_frame(&plan, self.opengl_info.as_deref());
         self.render_plan = Some(plan);
         self.prev_pane_frames = new_pane_frames;
         self.prev_pane_order = current_pane_order;
-        self.ui_items = ui_items;
+        self.ui_items.set(ui_items);
+        self.publish_tab_drop_target();
+        self.recorded_frame = recorded;
 
         Ok(())
     }
+
+    /// The hsb transform to execute the commands of the pane at `pos`
+    /// with: none for the active pane and `inactive_pane_hsb` for the
+    /// others, easing from one to the other over
+    /// `inactive_pane_fade_duration_ms` as the pane gains or loses focus
+    fn pane_hsv(&self, pos: &PositionedPane) -> Option<CmdHsbTransform> {
+        let color_config = self.config.color_config();
+        let target = if pos.is_active {
+            CmdHsbTransform::IDENTITY
+        } else {
+            let hsb = color_config.inactive_pane_hsb;
+            CmdHsbTransform {
+                hue: hsb.hue,
+                saturation: hsb.saturation,
+                brightness: hsb.brightness,
+            }
+        };
+
+        let mut fades = self.pane_fades.borrow_mut();
+        let fade = fades
+            .entry(pos.pane.pane_id())
+            .or_insert_with(|| Transition::settled(target.clone()));
+        let duration_ms = if self.animations_enabled() {
+            color_config.inactive_pane_fade_duration_ms
+        } else {
+            0
+        };
+        fade.retarget(
+            target,
+            duration_ms,
+            color_config.inactive_pane_fade_function,
+        );
+        let (hsv, next_due) = fade.value();
+        self.update_next_frame_time(next_due);
+
+        (hsv != CmdHsbTransform::IDENTITY).then_some(hsv)
+    }
 }
@@ -6,7 +6,7 @@ impl crate::TermWindow {
     pub fn build_pane(&mut self, pos: &PositionedPane) -> anyhow::Result<ComputedElement> {
         let cell_width = self.render_metrics.cell_size.width as f32;
         let cell_height = self.render_metrics.cell_size.height as f32;
-        let (padding_left, padding_top) = self.padding_left_top();
+        let (padding_left, padding_top) = self.aligned_padding_left_top();
         let tab_bar_height = if self.show_tab_bar {
             self.tab_bar_pixel_height()?
         } else {
@@ -81,7 +81,11 @@ impl crate::TermWindow {
             colors: ElementColors {
                 border: BorderColor::default(),
                 bg: if self.window_background.is_empty() {
-                    palette.background.to_linear().mul_alpha(1.0).into()
+                    palette
+                        .background
+                        .to_linear()
+                        .mul_alpha(self.window_background_opacity())
+                        .into()
                 } else {
                     InheritableColor::Inherited
                 },
@@ -0,0 +1,200 @@
+//! The geometry of text that is being composed by an input method:
+//! the underlines that mark its clauses, and the rectangle that the
+//! candidate window of the input method is placed against.
+use crate::render_command::RectF;
+use ::window::{Composition, Point, PreeditUnderline, Rect, Size};
+use std::ops::Range;
+use termwiz::cell::unicode_column_width;
+
+/// The space left between the underlines of adjacent clauses, so that
+/// they read as separate clauses rather than one long underline
+const CLAUSE_GAP: f32 = 1.0;
+
+/// Returns the columns, relative to the start of the composition,
+/// that the `range` of its text occupies
+fn columns(text: &str, range: &Range<usize>) -> Range<usize> {
+    let start = unicode_column_width(text.get(..range.start).unwrap_or(text), None);
+    let width = unicode_column_width(text.get(range.clone()).unwrap_or(""), None);
+    start..start + width
+}
+
+/// Computes the underlines for the clauses of `composition`, which is
+/// drawn starting at `left` on the line whose top is at `top`.
+/// Clauses being converted are underlined twice as thickly as the rest.
+pub fn underline_rects(
+    composition: &Composition,
+    left: f32,
+    top: f32,
+    cell_width: f32,
+    cell_height: f32,
+    underline_height: f32,
+) -> Vec<RectF> {
+    composition
+        .segments
+        .iter()
+        .filter_map(|segment| {
+            let cols = columns(&composition.text, &segment.range);
+            if cols.is_empty() {
+                return None;
+            }
+            let thickness = match segment.underline {
+                PreeditUnderline::Thin => underline_height,
+                PreeditUnderline::Thick => underline_height * 2.,
+            };
+            let mut width = cols.len() as f32 * cell_width;
+            if composition.segments.len() > 1 && width > CLAUSE_GAP {
+                width -= CLAUSE_GAP;
+            }
+            Some(euclid::rect(
+                left + cols.start as f32 * cell_width,
+                top + cell_height - thickness,
+                width,
+                thickness,
+            ))
+        })
+        .collect()
+}
+
+/// Computes the rectangle, in pixels relative to the window, that the
+/// candidate window should be placed against when the cursor is at
+/// `column` and `row` of the cell grid that begins at `origin`.
+/// While composing, that is the clause being converted, or else the
+/// input method caret, rather than the start of the composition.
+pub fn caret_rect(
+    column: isize,
+    row: isize,
+    composition: Option<&Composition>,
+    cell_size: Size,
+    origin: Point,
+) -> Rect {
+    let cols = composition
+        .map(|composition| columns(&composition.text, &composition.active_range()))
+        .unwrap_or(0..0);
+    Rect::new(
+        Point::new(
+            origin.x + (column.max(0) + cols.start as isize) * cell_size.width,
+            origin.y + row.max(0) * cell_size.height,
+        ),
+        Size::new(
+            cols.len().max(1) as isize * cell_size.width,
+            cell_size.height,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::window::PreeditSegment;
+
+    fn segment(range: Range<usize>, underline: PreeditUnderline) -> PreeditSegment {
+        PreeditSegment { range, underline }
+    }
+
+    fn rects(composition: &Composition) -> Vec<(f32, f32, f32, f32)> {
+        underline_rects(composition, 100., 20., 10., 16., 1.)
+            .into_iter()
+            .map(|r| (r.min_x(), r.min_y(), r.width(), r.height()))
+            .collect()
+    }
+
+    #[test]
+    fn single_clause() {
+        let composition = Composition {
+            text: "kan".to_string(),
+            segments: vec![segment(0..3, PreeditUnderline::Thin)],
+            caret: Some(3),
+        };
+        assert_eq!(rects(&composition), vec![(100., 35., 30., 1.)]);
+    }
+
+    #[test]
+    fn dead_key_has_no_underline() {
+        assert_eq!(rects(&Composition::new("`")), vec![]);
+    }
+
+    #[test]
+    fn converted_clause_is_thicker() {
+        // Each of these is 3 bytes and 2 cells wide
+        let composition = Composition {
+            text: "漢字変換".to_string(),
+            segments: vec![
+                segment(0..6, PreeditUnderline::Thick),
+                segment(6..12, PreeditUnderline::Thin),
+            ],
+            caret: Some(0),
+        };
+        assert_eq!(
+            rects(&composition),
+            vec![(100., 34., 39., 2.), (140., 35., 39., 1.)]
+        );
+    }
+
+    #[test]
+    fn empty_clauses_are_skipped() {
+        let composition = Composition {
+            text: "ka".to_string(),
+            segments: vec![
+                segment(0..0, PreeditUnderline::Thick),
+                segment(0..2, PreeditUnderline::Thin),
+            ],
+            caret: None,
+        };
+        assert_eq!(rects(&composition), vec![(100., 35., 19., 1.)]);
+    }
+
+    fn caret(column: isize, row: isize, composition: Option<&Composition>) -> Rect {
+        caret_rect(
+            column,
+            row,
+            composition,
+            Size::new(10, 16),
+            Point::new(5, 30),
+        )
+    }
+
+    #[test]
+    fn caret_without_composition() {
+        assert_eq!(
+            caret(3, 2, None),
+            Rect::new(Point::new(35, 62), Size::new(10, 16))
+        );
+        // A cursor that is scrolled out of view is clamped to the grid
+        assert_eq!(
+            caret(-1, -4, None),
+            Rect::new(Point::new(5, 30), Size::new(10, 16))
+        );
+    }
+
+    #[test]
+    fn caret_follows_composition() {
+        let mut composition = Composition {
+            text: "漢字へんかん".to_string(),
+            segments: vec![
+                segment(0..6, PreeditUnderline::Thin),
+                segment(6..18, PreeditUnderline::Thick),
+            ],
+            caret: Some(6),
+        };
+        // The clause being converted
+        assert_eq!(
+            caret(3, 2, Some(&composition)),
+            Rect::new(Point::new(75, 62), Size::new(80, 16))
+        );
+
+        // Without a clause being converted, the caret
+        composition.segments[1].underline = PreeditUnderline::Thin;
+        composition.caret = Some(9);
+        assert_eq!(
+            caret(3, 2, Some(&composition)),
+            Rect::new(Point::new(95, 62), Size::new(10, 16))
+        );
+
+        // Without a caret, the start of the composition
+        composition.caret = None;
+        assert_eq!(
+            caret(3, 2, Some(&composition)),
+            Rect::new(Point::new(35, 62), Size::new(10, 16))
+        );
+    }
+}
@@ -1,21 +1,25 @@
 use crate::render_command::{
-    HsbTransform as CmdHsbTransform, QuadMode, RectF as CmdRectF, RenderCommand,
+    HsbTransform as CmdHsbTransform, LayerId, QuadMode, RectF as CmdRectF, RenderCommand,
     TextureCoords as CmdTextureCoords,
 };
 use config::observers::*;
+use crate::shapecache::{font_runs, ShapedInfo};
 use crate::termwindow::render::{
-    resolve_fg_color_attr, same_hyperlink, update_next_frame_time, ClusterStyleCache,
-    ComputeCellFgBgParams, ComputeCellFgBgResult, LineToElementParams, LineToElementShape,
-    RenderScreenLineParams, RenderScreenLineResult,
+    effective_underline, hollow_cursor_rects, preedit, resolve_fg_color_attr,
+    resolve_underline_color, update_next_frame_time, ClusterStyleCache, ComputeCellFgBgParams,
+    ComputeCellFgBgResult, CursorRenderStyle, LineToElementParams, LineToElementShape,
+    RenderScreenLineParams,
 };
 use crate::termwindow::LineToElementShapeItem;
+use ::window::bitmaps::TextureRect;
 use ::window::DeadKeyStatus;
 use anyhow::Context;
-use config::{HsbTransform, TextStyle};
+use config::{DimensionContext, TextStyle};
 use std::ops::Range;
 use std::rc::Rc;
 use std::time::Instant;
 use termwiz::cell::{unicode_column_width, Blink};
+use termwiz::cellcluster::CellCluster;
 use termwiz::color::LinearRgba;
 use termwiz::surface::CursorShape;
 use phaedra_bidi::Direction;
@@ -26,7 +30,7 @@ impl crate::TermWindow {
     pub fn describe_screen_line(
         &self,
         params: RenderScreenLineParams,
-    ) -> anyhow::Result<(Vec<RenderCommand>, RenderScreenLineResult)> {
+    ) -> anyhow::Result<Vec<RenderCommand>> {
         fn cmd_rect(rect: CmdRectF) -> CmdRectF {
             CmdRectF::new(
                 euclid::point2(rect.min_x(), rect.min_y()),
@@ -51,33 +55,14 @@ impl crate::TermWindow {
             }
         }
 
-        let cmd_hsv = |hsv: Option<HsbTransform>| {
-            hsv.map(|h| CmdHsbTransform {
-                hue: h.hue,
-                saturation: h.saturation,
-                brightness: h.brightness,
-            })
-        };
-
         if params.line.is_double_height_bottom() {
-            return Ok((
-                vec![],
-                RenderScreenLineResult {
-                    invalidate_on_hover_change: false,
-                },
-            ));
+            return Ok(vec![]);
         }
 
         let gl_state = self.render_state.as_ref().unwrap();
 
         let num_cols = params.dims.cols;
 
-        let hsv = if params.is_active {
-            None
-        } else {
-            Some(params.config.color_config().inactive_pane_hsb)
-        };
-
         let width_scale = if !params.line.is_single_width() {
             2.0
         } else {
@@ -120,7 +105,7 @@ impl crate::TermWindow {
         let direction = bidi_direction.direction();
 
         if let Some(composing) = composing {
-            composition_width = unicode_column_width(composing, None);
+            composition_width = unicode_column_width(&composing.text, None);
         }
 
         let cursor_cell = if params.stable_line_idx == Some(params.cursor.y) {
@@ -141,27 +126,16 @@ impl crate::TermWindow {
             ..params.left_pixel_x + cursor_range.end as f32 * cell_width;
 
         let mut shaped = None;
-        let mut invalidate_on_hover_change = false;
 
         if let Some(shape_key) = &params.shape_key {
             let mut cache = self.line_to_ele_shape_cache.borrow_mut();
             if let Some(entry) = cache.get(shape_key) {
                 let expired = entry.expires.map(|i| Instant::now() >= i).unwrap_or(false);
-                let hover_changed = if entry.invalidate_on_hover_change {
-                    !same_hyperlink(
-                        entry.current_highlight.as_ref(),
-                        self.current_highlight.as_ref(),
-                    )
-                } else {
-                    false
-                };
 
-                if !expired && !hover_changed {
+                if !expired {
                     self.update_next_frame_time(entry.expires);
                     shaped.replace(Rc::clone(&entry.shaped));
                 }
-
-                invalidate_on_hover_change = entry.invalidate_on_hover_change;
             }
         }
 
@@ -175,11 +149,11 @@ impl crate::TermWindow {
                 window_is_transparent: params.window_is_transparent,
                 reverse_video: params.dims.reverse_video,
                 shape_key: &params.shape_key,
+                render_metrics: &params.render_metrics,
+                pane_font: params.pane_font,
             };
 
-            let (shaped, invalidate_on_hover) = self.build_line_element_shape(params)?;
-            invalidate_on_hover_change = invalidate_on_hover;
-            shaped
+            self.build_line_element_shape(params)?
         };
 
         let bounding_rect = euclid::rect(
@@ -200,8 +174,7 @@ impl crate::TermWindow {
 
         if params.dims.reverse_video {
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: 0,
+                layer: LayerId::background(),
                 rect: cmd_rect(euclid::rect(
                     params.left_pixel_x,
                     params.top_pixel_y,
@@ -209,7 +182,7 @@ impl crate::TermWindow {
                     cell_height,
                 )),
                 color: params.foreground,
-                hsv: cmd_hsv(hsv),
+                hsv: None,
             });
         }
 
@@ -268,17 +241,21 @@ impl crate::TermWindow {
                 let rect = euclid::rect(x, params.top_pixel_y, width, cell_height);
                 if let Some(rect) = rect.intersection(&bounding_rect) {
                     commands.push(RenderCommand::FillRect {
-                        layer: 0,
-                        zindex: 0,
+                        layer: LayerId::background(),
                         rect: cmd_rect(rect),
                         color: bg_color,
-                        hsv: cmd_hsv(hsv),
+                        hsv: None,
                     });
                 }
             }
 
             if item.underline_tex_rect != params.white_space {
                 for i in 0..cluster_width {
+                    let underline_tex_rect = item
+                        .fallback_underlines
+                        .iter()
+                        .find(|(cells, _)| cells.contains(&i))
+                        .map_or(item.underline_tex_rect, |(_, rect)| *rect);
                     let x = params.left_pixel_x
                         + if params.use_pixel_positioning {
                             item.x_pos
@@ -288,13 +265,12 @@ impl crate::TermWindow {
                         };
 
                     commands.push(RenderCommand::DrawQuad {
-                        layer: 0,
-                        zindex: 0,
+                        layer: LayerId::background(),
                         position: cmd_rect(euclid::rect(x, params.top_pixel_y, cell_width, cell_height)),
-                        texture: cmd_texture_coords(item.underline_tex_rect),
+                        texture: cmd_texture_coords(underline_tex_rect),
                         fg_color: item.underline_color,
                         alt_color: None,
-                        hsv: cmd_hsv(hsv),
+                        hsv: None,
                         mode: QuadMode::Glyph,
                     });
                 }
@@ -305,11 +281,10 @@ impl crate::TermWindow {
             let start = params.left_pixel_x + (params.selection.start as f32 * cell_width);
             let width = (params.selection.end - params.selection.start) as f32 * cell_width;
             commands.push(RenderCommand::FillRect {
-                layer: 0,
-                zindex: 0,
+                layer: LayerId::background(),
                 rect: cmd_rect(euclid::rect(start, params.top_pixel_y, width, cell_height)),
                 color: params.selection_bg,
-                hsv: cmd_hsv(hsv),
+                hsv: None,
             });
 
             start..start + width
@@ -337,7 +312,8 @@ impl crate::TermWindow {
             };
 
             let ComputeCellFgBgResult {
-                cursor_shape,
+                fg_color: cursor_text_color,
+                cursor_style,
                 cursor_border_color,
                 cursor_border_color_alt,
                 cursor_border_mix,
@@ -360,10 +336,43 @@ impl crate::TermWindow {
             let pos_x = params.left_pixel_x
                 + (phys(params.cursor.x, num_cols, direction) as f32 * cell_width);
 
-            if let Some(shape) = cursor_shape {
+            if cursor_style == Some(CursorRenderStyle::Hollow) && !params.password_input {
+                let cursor_width = (cursor_range.end - cursor_range.start) as f32 * cell_width;
+                // As for the outline sprite, the edges are as thick as
+                // an underline unless cursor_thickness says otherwise
+                let underline_height = params.render_metrics.underline_height as f32;
+                let thickness = match &params.config.cursor().cursor_thickness {
+                    Some(d) => d
+                        .evaluate_as_pixels(DimensionContext {
+                            dpi: self.fonts.get_dpi() as f32,
+                            pixel_max: underline_height,
+                            pixel_cell: cell_height,
+                        })
+                        .floor(),
+                    None => underline_height,
+                };
+                for rect in hollow_cursor_rects(
+                    euclid::rect(pos_x, params.top_pixel_y, cursor_width, cell_height),
+                    thickness,
+                ) {
+                    commands.push(RenderCommand::FillRect {
+                        layer: LayerId::background(),
+                        rect,
+                        color: cursor_border_color,
+                        hsv: None,
+                    });
+                }
+            } else if let Some(style) = cursor_style {
+                let shape = match style {
+                    CursorRenderStyle::Shape(shape) | CursorRenderStyle::Dimmed { shape, .. } => {
+                        shape
+                    }
+                    // The password lock glyph takes the place of the outline
+                    CursorRenderStyle::Hollow => CursorShape::SteadyBlock,
+                };
                 let cursor_layer = match shape {
-                    CursorShape::BlinkingBar | CursorShape::SteadyBar => 2,
-                    _ => 0,
+                    CursorShape::BlinkingBar | CursorShape::SteadyBar => LayerId::overlay(),
+                    _ => LayerId::background(),
                 };
 
                 let mut draw_basic = true;
@@ -381,6 +390,7 @@ impl crate::TermWindow {
                             &TextStyle::default(),
                             &attrs,
                             params.font.as_ref(),
+                            params.pane_font.map_or(&self.fonts, |font| &font.fonts),
                             gl_state,
                             &params.render_metrics,
                         )
@@ -424,20 +434,37 @@ impl crate::TermWindow {
                 if let (Some(position), Some(texture)) = (cursor_position, cursor_texture) {
                     commands.push(RenderCommand::DrawQuad {
                         layer: cursor_layer,
-                        zindex: 0,
                         position,
                         texture,
                         fg_color: cursor_border_color,
                         alt_color: cmd_alt_color(cursor_border_color_alt, cursor_border_mix),
-                        hsv: cmd_hsv(hsv),
+                        hsv: None,
                         mode: QuadMode::Glyph,
                     });
                 }
             }
-        }
 
-        let mut visual_cell_idx = 0;
+            if let Some(composing) = composing {
+                for rect in preedit::underline_rects(
+                    composing,
+                    pos_x,
+                    params.top_pixel_y,
+                    cell_width,
+                    cell_height,
+                    params.render_metrics.underline_height as f32,
+                ) {
+                    commands.push(RenderCommand::FillRect {
+                        layer: LayerId::overlay(),
+                        rect,
+                        color: cursor_text_color,
+                        hsv: None,
+                    });
+                }
+            }
+        }
 
+        // The images of the cells are described for the whole pane by
+        // describe_pane_images, so that they can be clipped to the pane
         let mut cluster_x_pos = match direction {
             Direction::LeftToRight => 0.,
             Direction::RightToLeft => params.pixel_width,
@@ -446,7 +473,6 @@ impl crate::TermWindow {
         for item in shaped.iter() {
             let cluster = &item.cluster;
             let glyph_info = &item.glyph_info;
-            let images = cluster.attrs.images().unwrap_or_else(|| vec![]);
             let valign_adjust = match cluster.attrs.vertical_align() {
                 termwiz::cell::VerticalAlign::BaseLine => 0.,
                 termwiz::cell::VerticalAlign::SuperScript => {
@@ -475,14 +501,6 @@ impl crate::TermWindow {
                     break;
                 }
 
-                for glyph_idx in 0..info.pos.num_cells as usize {
-                    for img in &images {
-                        if img.z_index() < 0 {
-                            let _ = (img, glyph_idx);
-                        }
-                    }
-                }
-
                 {
                     let mut texture = glyph.texture.as_ref().cloned();
 
@@ -607,18 +625,16 @@ impl crate::TermWindow {
 
                             let texture_rect = texture.texture.to_texture_coords(pixel_rect);
                             let quad_hsv = if glyph.brightness_adjust != 1.0 {
-                                let hsv = hsv.unwrap_or_else(|| HsbTransform::default());
-                                Some(HsbTransform {
-                                    brightness: hsv.brightness * glyph.brightness_adjust,
-                                    ..hsv
+                                Some(CmdHsbTransform {
+                                    brightness: glyph.brightness_adjust,
+                                    ..CmdHsbTransform::IDENTITY
                                 })
                             } else {
-                                hsv
+                                None
                             };
 
                             commands.push(RenderCommand::DrawQuad {
-                                layer: 1,
-                                zindex: 0,
+                                layer: LayerId::text(),
                                 position: cmd_rect(euclid::rect(
                                     range.start,
                                     params.top_pixel_y + top,
@@ -628,25 +644,13 @@ impl crate::TermWindow {
                                 texture: cmd_texture_coords(texture_rect),
                                 fg_color: glyph_color,
                                 alt_color: cmd_alt_color(fg_color_alt, fg_color_mix),
-                                hsv: cmd_hsv(quad_hsv),
-                                mode: if glyph.has_color {
-                                    QuadMode::ColorEmoji
-                                } else {
-                                    QuadMode::Glyph
-                                },
+                                hsv: quad_hsv,
+                                mode: info.quad_mode(),
                             });
                         }
                     }
                 }
 
-                for glyph_idx in 0..info.pos.num_cells as usize {
-                    for img in &images {
-                        if img.z_index() >= 0 {
-                            let _ = (img, visual_cell_idx + glyph_idx, item.fg_color);
-                        }
-                    }
-                }
-                visual_cell_idx += info.pos.num_cells as usize;
                 cluster_x_pos += if params.use_pixel_positioning {
                     glyph.x_advance.get() as f32 * width_scale
                 } else {
@@ -668,18 +672,13 @@ impl crate::TermWindow {
 
         metrics::histogram!("describe_screen_line").record(start.elapsed());
 
-        Ok((
-            commands,
-            RenderScreenLineResult {
-                invalidate_on_hover_change,
-            },
-        ))
+        Ok(commands)
     }
 
     fn build_line_element_shape(
         &self,
         params: LineToElementParams,
-    ) -> anyhow::Result<(Rc<Vec<LineToElementShape>>, bool)> {
+    ) -> anyhow::Result<Rc<Vec<LineToElementShape>>> {
         let (bidi_enabled, bidi_direction) = params.line.bidi_info();
         let bidi_hint = if bidi_enabled {
             Some(bidi_direction)
@@ -699,33 +698,33 @@ impl crate::TermWindow {
         };
 
         let gl_state = self.render_state.as_ref().unwrap();
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+        let (fonts, shape_metrics) = match params.pane_font {
+            Some(font) => (&font.fonts, &font.render_metrics),
+            None => (&self.fonts, &self.render_metrics),
+        };
         let mut shaped = vec![];
         let mut last_style = None;
         let mut x_pos = 0.;
         let mut expires = None;
-        let mut invalidate_on_hover_change = false;
 
         for cluster in &cell_clusters {
             if !matches!(last_style.as_ref(), Some(ClusterStyleCache{attrs,..}) if *attrs == &cluster.attrs)
             {
                 let attrs = &cluster.attrs;
-                let style = self.fonts.match_style(params.config, attrs);
-                let hyperlink = attrs.hyperlink();
-                let is_highlited_hyperlink =
-                    same_hyperlink(hyperlink, self.current_highlight.as_ref());
-                if hyperlink.is_some() {
-                    invalidate_on_hover_change = true;
-                }
-                // underline and strikethrough
+                let style = fonts.match_style(params.config, attrs);
+                // underline and strikethrough.  Hyperlink hover styling is
+                // emitted separately by describe_hyperlink_hover so that the
+                // shaped line stays valid while the mouse moves.
                 let underline_tex_rect = gl_state
                     .glyph_cache
                     .borrow_mut()
                     .cached_line_sprite(
-                        is_highlited_hyperlink,
+                        false,
                         attrs.strikethrough(),
-                        attrs.underline(),
+                        effective_underline(attrs),
                         attrs.overline(),
-                        &self.render_metrics,
+                        params.render_metrics,
                     )?
                     .texture_coords();
                 let bg_is_default = attrs.background() == ColorAttribute::Default;
@@ -764,7 +763,7 @@ impl crate::TermWindow {
                         )),
                     };
                     if let Some((blink_rate, mut colorease)) = blink_rate {
-                        if blink_rate != 0 {
+                        if blink_rate != 0 && self.animations_enabled() {
                             let (intensity, next) = colorease.intensity_continuous();
 
                             let (r1, g1, b1, a) = bg.tuple();
@@ -785,10 +784,13 @@ impl crate::TermWindow {
                 };
 
                 let glyph_color = fg_color;
-                let underline_color = match attrs.underline_color() {
-                    ColorAttribute::Default => fg_color,
-                    c => resolve_fg_color_attr(&attrs, c, &params.palette, &params.config, style),
-                };
+                let underline_color = resolve_underline_color(
+                    &attrs,
+                    fg_color,
+                    &params.palette,
+                    &params.config,
+                    style,
+                );
 
                 let (bg_r, bg_g, bg_b, _) = bg_color.tuple();
                 let bg_color = LinearRgba::with_components(
@@ -819,15 +821,23 @@ impl crate::TermWindow {
                 &cluster,
                 &gl_state,
                 None,
-                &self.render_metrics,
+                fonts,
+                shape_metrics,
             )?;
             let pixel_width = glyph_info
                 .iter()
                 .map(|info| info.glyph.x_advance.get() as f32)
                 .sum();
 
+            let fallback_underlines = if style_params.underline_tex_rect != white_space {
+                self.fallback_underlines(&params, &style_params, &cluster, &glyph_info)?
+            } else {
+                vec![]
+            };
+
             shaped.push(LineToElementShape {
                 underline_tex_rect: style_params.underline_tex_rect,
+                fallback_underlines,
                 bg_color: style_params.bg_color,
                 fg_color: style_params.fg_color,
                 underline_color: style_params.underline_color,
@@ -848,16 +858,72 @@ impl crate::TermWindow {
                 LineToElementShapeItem {
                     expires,
                     shaped: Rc::clone(&shaped),
-                    invalidate_on_hover_change,
-                    current_highlight: if invalidate_on_hover_change {
-                        self.current_highlight.clone()
-                    } else {
-                        None
-                    },
                 },
             );
         }
 
-        Ok((shaped, invalidate_on_hover_change))
+        Ok(shaped)
+    }
+
+    /// Compute the underline/strikethrough sprites for the portions of
+    /// a cluster that were rendered from a fallback font, using the
+    /// decoration metrics of that font rather than those of the primary.
+    fn fallback_underlines(
+        &self,
+        params: &LineToElementParams,
+        style_params: &ClusterStyleCache,
+        cluster: &CellCluster,
+        glyph_info: &[ShapedInfo],
+    ) -> anyhow::Result<Vec<(Range<usize>, TextureRect)>> {
+        let runs = font_runs(
+            glyph_info,
+            cluster.width,
+            cluster.direction == Direction::RightToLeft,
+        );
+        if runs.iter().all(|run| run.font_idx == 0) {
+            return Ok(vec![]);
+        }
+
+        let font = params
+            .pane_font
+            .map_or(&self.fonts, |font| &font.fonts)
+            .resolve_font(style_params.style)?;
+        let gl_state = self.render_state.as_ref().unwrap();
+        let attrs = style_params.attrs;
+        let mut result = vec![];
+
+        for run in runs {
+            if run.font_idx == 0 {
+                continue;
+            }
+            let font_metrics = match font.metrics_for_idx(run.font_idx) {
+                Ok(m) => m,
+                Err(err) => {
+                    log::trace!("no metrics for fallback font {}: {:#}", run.font_idx, err);
+                    continue;
+                }
+            };
+            let metrics = params.render_metrics.with_run_font_metrics(
+                &font_metrics,
+                run.scale,
+                params.config.text(),
+            );
+            let tex_rect = gl_state
+                .glyph_cache
+                .borrow_mut()
+                .cached_line_sprite(
+                    false,
+                    attrs.strikethrough(),
+                    effective_underline(attrs),
+                    attrs.overline(),
+                    &metrics,
+                )?
+                .texture_coords();
+            if tex_rect != style_params.underline_tex_rect {
+                result.push((run.cells, tex_rect));
+            }
+        }
+
+        Ok(result)
     }
 }
@@ -2,7 +2,8 @@ use crate::resize_increment_calculator::ResizeIncrementCalculator;
 use config::observers::*;
 use crate::utilsprites::RenderMetrics;
 use ::window::{Dimensions, ResizeIncrement, Window, WindowOps, WindowState};
-use config::{ConfigHandle, DimensionContext};
+use config::keyassignment::WindowPaddingOverride;
+use config::{ConfigHandle, DimensionContext, WindowPadding};
 use mux::Mux;
 use std::rc::Rc;
 use phaedra_font::FontConfiguration;
@@ -163,6 +164,7 @@ impl super::TermWindow {
         // change to the tab size.
 
         let config = &self.config;
+        let padding = self.window_padding();
 
         let tab_bar_height = if self.show_tab_bar {
             self.tab_bar_pixel_height().unwrap_or(0.)
@@ -188,19 +190,18 @@ impl super::TermWindow {
 
             let h_context = DimensionContext {
                 dpi: dimensions.dpi as f32,
-                pixel_max: size.pixel_width as f32,
+                pixel_max: dimensions.pixel_width as f32,
                 pixel_cell: self.render_metrics.cell_size.width as f32,
             };
             let v_context = DimensionContext {
                 dpi: dimensions.dpi as f32,
-                pixel_max: size.pixel_height as f32,
+                pixel_max: dimensions.pixel_height as f32,
                 pixel_cell: self.render_metrics.cell_size.height as f32,
             };
-            let padding_left = config.window_config().window_padding.left.evaluate_as_pixels(h_context) as usize;
-            let padding_top = config.window_config().window_padding.top.evaluate_as_pixels(v_context) as usize;
-            let padding_bottom =
-                config.window_config().window_padding.bottom.evaluate_as_pixels(v_context) as usize;
-            let padding_right = effective_right_padding(&config, h_context);
+            let padding_left = padding.left.evaluate_as_pixels(h_context) as usize;
+            let padding_top = padding.top.evaluate_as_pixels(v_context) as usize;
+            let padding_bottom = padding.bottom.evaluate_as_pixels(v_context) as usize;
+            let padding_right = effective_right_padding(&config, &padding, h_context);
 
             let pixel_height = (rows * self.render_metrics.cell_size.height as usize)
                 + (padding_top + padding_bottom)
@@ -234,19 +235,18 @@ impl super::TermWindow {
 
             let h_context = DimensionContext {
                 dpi: dimensions.dpi as f32,
-                pixel_max: self.terminal_size.pixel_width as f32,
+                pixel_max: dimensions.pixel_width as f32,
                 pixel_cell: self.render_metrics.cell_size.width as f32,
             };
             let v_context = DimensionContext {
                 dpi: dimensions.dpi as f32,
-                pixel_max: self.terminal_size.pixel_height as f32,
+                pixel_max: dimensions.pixel_height as f32,
                 pixel_cell: self.render_metrics.cell_size.height as f32,
             };
-            let padding_left = config.window_config().window_padding.left.evaluate_as_pixels(h_context) as usize;
-            let padding_top = config.window_config().window_padding.top.evaluate_as_pixels(v_context) as usize;
-            let padding_bottom =
-                config.window_config().window_padding.bottom.evaluate_as_pixels(v_context) as usize;
-            let padding_right = effective_right_padding(&config, h_context);
+            let padding_left = padding.left.evaluate_as_pixels(h_context) as usize;
+            let padding_top = padding.top.evaluate_as_pixels(v_context) as usize;
+            let padding_bottom = padding.bottom.evaluate_as_pixels(v_context) as usize;
+            let padding_right = effective_right_padding(&config, &padding, h_context);
 
             let avail_width = dimensions.pixel_width.saturating_sub(
                 (padding_left + padding_right) as usize
@@ -506,14 +506,15 @@ impl super::TermWindow {
             pixel_max: self.dimensions.pixel_height as f32,
             pixel_cell: render_metrics.cell_size.height as f32,
         };
-        let padding_left = config.window_config().window_padding.left.evaluate_as_pixels(h_context) as usize;
-        let padding_top = config.window_config().window_padding.top.evaluate_as_pixels(v_context) as usize;
-        let padding_bottom = config.window_config().window_padding.bottom.evaluate_as_pixels(v_context) as usize;
+        let padding = self.window_padding();
+        let padding_left = padding.left.evaluate_as_pixels(h_context) as usize;
+        let padding_top = padding.top.evaluate_as_pixels(v_context) as usize;
+        let padding_bottom = padding.bottom.evaluate_as_pixels(v_context) as usize;
 
         let dimensions = Dimensions {
             pixel_width: ((terminal_size.cols as usize * render_metrics.cell_size.width as usize)
                 + padding_left
-                + effective_right_padding(&config, h_context)),
+                + effective_right_padding(&config, &padding, h_context)),
             pixel_height: ((terminal_size.rows as usize * render_metrics.cell_size.height as usize)
                 + padding_top
                 + padding_bottom) as usize
@@ -544,9 +545,46 @@ impl super::TermWindow {
         self.set_window_size(size, window)
     }
 
+    /// Returns the padding of this window: the configured
+    /// `window_padding`, with the sides that have been changed
+    /// by `SetWindowPadding` replaced
+    pub fn window_padding(&self) -> WindowPadding {
+        self.config
+            .window_config()
+            .window_padding
+            .with_override(&self.window_padding_override)
+    }
+
+    /// Overrides the configured padding for the sides that `padding`
+    /// specifies, until `reset_window_padding` is called
+    pub fn set_window_padding(&mut self, padding: &WindowPaddingOverride) {
+        let current = &mut self.window_padding_override;
+        current.left = padding.left.or(current.left);
+        current.right = padding.right.or(current.right);
+        current.top = padding.top.or(current.top);
+        current.bottom = padding.bottom.or(current.bottom);
+        self.apply_window_padding();
+    }
+
+    pub fn reset_window_padding(&mut self) {
+        self.window_padding_override = WindowPaddingOverride::default();
+        self.apply_window_padding();
+    }
+
+    /// Lays out the panes again for the current padding, keeping the
+    /// size of the window
+    fn apply_window_padding(&mut self) {
+        if let Some(window) = self.window.clone() {
+            let dimensions = self.dimensions;
+            self.apply_dimensions(&dimensions, None, &window);
+            window.invalidate();
+        }
+    }
+
     pub fn effective_right_padding(&self, config: &ConfigHandle) -> usize {
         effective_right_padding(
             config,
+            &self.window_padding(),
             DimensionContext {
                 pixel_cell: self.render_metrics.cell_size.width as f32,
                 dpi: self.dimensions.dpi as f32,
@@ -560,10 +598,14 @@ impl super::TermWindow {
 /// This is needed because the default is 0, but if the user has
 /// enabled the scroll bar then they will expect it to have a reasonable
 /// size unless they've specified differently.
-pub fn effective_right_padding(config: &ConfigHandle, context: DimensionContext) -> usize {
-    if config.scroll().enable_scroll_bar && config.window_config().window_padding.right.is_zero() {
+pub fn effective_right_padding(
+    config: &ConfigHandle,
+    padding: &WindowPadding,
+    context: DimensionContext,
+) -> usize {
+    if config.scroll().enable_scroll_bar && padding.right.is_zero() {
         context.pixel_cell as usize
     } else {
-        config.window_config().window_padding.right.evaluate_as_pixels(context) as usize
+        padding.right.evaluate_as_pixels(context) as usize
     }
 }
@@ -240,6 +240,27 @@ impl super::TermWindow {
         self.window.as_ref().unwrap().invalidate();
     }
 
+    /// Selects the output of the most recent command that produced some
+    pub fn select_last_command_output(&mut self, pane: &Arc<dyn Pane>) {
+        let output = match self
+            .get_last_command_with_output(pane)
+            .and_then(|command| command.output)
+        {
+            Some(output) => output,
+            None => return,
+        };
+        let selection_range = SelectionRange {
+            start: SelectionCoordinate::x_y(output.start_x, output.start_y),
+            end: SelectionCoordinate::x_y(output.end_x, output.end_y),
+        };
+
+        self.selection(pane.pane_id()).origin = Some(selection_range.start);
+        self.selection(pane.pane_id()).range = Some(selection_range);
+        self.selection(pane.pane_id()).rectangular = false;
+        self.selection(pane.pane_id()).seqno = pane.get_current_seqno();
+        self.window.as_ref().unwrap().invalidate();
+    }
+
     pub fn select_text_at_mouse_cursor(&mut self, mode: SelectionMode, pane: &Arc<dyn Pane>) {
         let (x, y) = match self.pane_state(pane.pane_id()).mouse_terminal_coords {
             Some(coords) => (coords.0.column, coords.1),
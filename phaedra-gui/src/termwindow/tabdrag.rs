@@ -0,0 +1,465 @@
+//! Dragging tabs by the tab bar: dropping a tab back onto its own tab
+//! bar reorders it, dropping it onto the tab bar of another window
+//! moves it there, and dropping it outside of every window detaches it
+//! into a window of its own.
+//!
+//! Windows can't see each other's tab bars, so each one publishes
+//! where its tab bar is on screen through the frontend, and the window
+//! that the drag started in resolves the drop against all of them.
+use crate::frontend::front_end;
+use crate::render_command::RenderCommand;
+use crate::tabbar::TabBarItem;
+use crate::termwindow::{TermWindow, UIItem, UIItemType};
+use ::window::{MouseEvent, ScreenPoint, ScreenRect, WindowOps};
+use anyhow::anyhow;
+use config::keyassignment::TabIndex;
+use config::{Dimension, GeometryOrigin, GuiPosition};
+use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How far the mouse has to move, in pixels, with the button held
+/// before pressing a tab turns into dragging it
+const DRAG_THRESHOLD: isize = 8;
+
+/// The tab bar of a window, in screen coordinates
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabDropTarget {
+    pub mux_window_id: MuxWindowId,
+    pub window: ScreenRect,
+    pub tab_bar: ScreenRect,
+    /// The horizontal extent of each tab, in tab order
+    pub tabs: Vec<Range<isize>>,
+}
+
+impl TabDropTarget {
+    /// The index that a tab dropped at `x` is inserted at; that is,
+    /// the number of tabs whose middle is to the left of `x`
+    fn insertion_index(&self, x: isize) -> usize {
+        self.tabs
+            .iter()
+            .filter(|tab| (tab.start + tab.end) / 2 < x)
+            .count()
+    }
+}
+
+/// Where a dragged tab ends up
+#[derive(Clone, Debug, PartialEq)]
+pub enum TabDrop {
+    /// At this index in the tab bar that it was dragged from
+    Reorder(usize),
+    /// At this index in the tab bar of another window
+    MoveToWindow {
+        mux_window_id: MuxWindowId,
+        index: usize,
+    },
+    /// In a new window with its top left corner here
+    Detach(ScreenPoint),
+    /// Over a window, but not its tab bar, so it stays where it was
+    Cancel,
+}
+
+/// A tab that was pressed in the tab bar.  It becomes a drag once the
+/// mouse has moved far enough, and stays one until it is dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabDrag {
+    pub tab_idx: usize,
+    start: ScreenPoint,
+    /// Where the tab was grabbed, relative to its top left corner
+    grab: (isize, isize),
+    position: ScreenPoint,
+    dragging: bool,
+}
+
+impl TabDrag {
+    pub fn new(tab_idx: usize, start: ScreenPoint, grab: (isize, isize)) -> Self {
+        Self {
+            tab_idx,
+            start,
+            grab,
+            position: start,
+            dragging: false,
+        }
+    }
+
+    /// Follows the mouse to `position`, and returns true if the tab
+    /// is being dragged
+    pub fn motion(&mut self, position: ScreenPoint) -> bool {
+        self.position = position;
+        if !self.dragging {
+            let delta = position - self.start;
+            self.dragging = delta.x.abs() > DRAG_THRESHOLD || delta.y.abs() > DRAG_THRESHOLD;
+        }
+        self.dragging
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// Where the top left corner of the tab is, with the point that
+    /// was grabbed under the mouse
+    pub fn tab_origin(&self) -> ScreenPoint {
+        ScreenPoint::new(self.position.x - self.grab.0, self.position.y - self.grab.1)
+    }
+
+    /// Resolves dropping the tab at the current mouse position, given
+    /// the tab bars of all the windows.  Returns None if the tab was
+    /// only pressed, and not dragged.
+    pub fn drop_at(&self, own: MuxWindowId, targets: &[TabDropTarget]) -> Option<TabDrop> {
+        if !self.dragging {
+            return None;
+        }
+        let point = self.position;
+        if let Some(target) = targets.iter().find(|t| t.tab_bar.contains(point)) {
+            let index = target.insertion_index(point.x);
+            if target.mux_window_id == own {
+                // The tab is no longer to the left of its old position
+                // once it has been taken out of the tab bar
+                let index = if index > self.tab_idx {
+                    index - 1
+                } else {
+                    index
+                };
+                return Some(TabDrop::Reorder(index));
+            }
+            return Some(TabDrop::MoveToWindow {
+                mux_window_id: target.mux_window_id,
+                index,
+            });
+        }
+        if targets.iter().any(|t| t.window.contains(point)) {
+            return Some(TabDrop::Cancel);
+        }
+        Some(TabDrop::Detach(self.tab_origin()))
+    }
+}
+
+impl TermWindow {
+    /// Describes where the tab bar of this window is on screen, if
+    /// it is showing and the window has seen the mouse
+    pub fn tab_drop_target(&self) -> Option<TabDropTarget> {
+        let origin = self.window_origin?;
+        if !self.show_tab_bar {
+            return None;
+        }
+
+        let mut tab_bar: Option<ScreenRect> = None;
+        let mut tabs: BTreeMap<usize, Range<isize>> = BTreeMap::new();
+        for item in self.ui_items.iter() {
+            match item.item_type {
+                UIItemType::TabBar(_) | UIItemType::CloseTab(_) | UIItemType::StatusSegment(_) => {}
+                UIItemType::AboveScrollThumb
+                | UIItemType::ScrollThumb
+                | UIItemType::BelowScrollThumb
+                | UIItemType::Split(_) => continue,
+            }
+            let rect = ScreenRect::new(
+                ScreenPoint::new(origin.x + item.x as isize, origin.y + item.y as isize),
+                euclid::size2(item.width as isize, item.height as isize),
+            );
+            tab_bar = Some(tab_bar.map_or(rect, |bar| bar.union(&rect)));
+            if let UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) = item.item_type {
+                let x = rect.min_x()..rect.max_x();
+                tabs.entry(tab_idx)
+                    .and_modify(|tab| {
+                        tab.start = tab.start.min(x.start);
+                        tab.end = tab.end.max(x.end);
+                    })
+                    .or_insert(x);
+            }
+        }
+
+        Some(TabDropTarget {
+            mux_window_id: self.mux_window_id,
+            window: ScreenRect::new(
+                origin,
+                euclid::size2(
+                    self.dimensions.pixel_width as isize,
+                    self.dimensions.pixel_height as isize,
+                ),
+            ),
+            tab_bar: tab_bar?,
+            tabs: tabs.into_values().collect(),
+        })
+    }
+
+    pub fn publish_tab_drop_target(&self) {
+        if let Some(window) = self.window.as_ref() {
+            front_end().set_tab_drop_target(window, self.tab_drop_target());
+        }
+    }
+
+    /// Infers the position of the window from a mouse event, which
+    /// carries both window and screen coordinates
+    pub fn update_window_origin(&mut self, event: &MouseEvent) {
+        let origin = ScreenPoint::new(
+            event.screen_coords.x - event.coords.x,
+            event.screen_coords.y - event.coords.y,
+        );
+        if self.window_origin != Some(origin) {
+            self.window_origin.replace(origin);
+            self.publish_tab_drop_target();
+        }
+    }
+
+    /// Called when the left button is pressed on the tab `item`
+    pub fn start_tab_drag(&mut self, item: UIItem, tab_idx: usize, event: MouseEvent) {
+        let grab = (
+            event.coords.x - item.x as isize,
+            event.coords.y - item.y as isize,
+        );
+        self.tab_drag = Some(TabDrag::new(tab_idx, event.screen_coords, grab));
+        self.dragging.replace((item, event));
+    }
+
+    pub fn drag_tab(
+        &mut self,
+        item: UIItem,
+        start_event: MouseEvent,
+        event: MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        if let Some(drag) = self.tab_drag.as_mut() {
+            if drag.motion(event.screen_coords) {
+                self.tooltip = None;
+                context.invalidate();
+            }
+        }
+        self.dragging.replace((item, start_event));
+    }
+
+    /// Drops the tab that is being dragged, if any.  Returns false if
+    /// the tab was pressed but never dragged, so that the release is
+    /// handled as the end of a click.
+    pub fn drop_tab(&mut self, context: &dyn WindowOps) -> bool {
+        let Some(drag) = self.tab_drag.take() else {
+            return false;
+        };
+        let targets = front_end().tab_drop_targets();
+        let Some(drop) = drag.drop_at(self.mux_window_id, &targets) else {
+            return false;
+        };
+        if let Err(err) = self.perform_tab_drop(&drag, drop, context) {
+            log::error!("while dropping tab {}: {:#}", drag.tab_idx, err);
+        }
+        context.invalidate();
+        true
+    }
+
+    fn perform_tab_drop(
+        &mut self,
+        drag: &TabDrag,
+        drop: TabDrop,
+        context: &dyn WindowOps,
+    ) -> anyhow::Result<()> {
+        let mux = Mux::get();
+        let (tab_id, tab_count, workspace) = {
+            let window = mux
+                .get_window(self.mux_window_id)
+                .ok_or_else(|| anyhow!("no such window"))?;
+            let tab = window
+                .get_by_idx(drag.tab_idx)
+                .ok_or_else(|| anyhow!("no tab at index {}", drag.tab_idx))?;
+            (
+                tab.tab_id(),
+                window.len(),
+                window.get_workspace().to_string(),
+            )
+        };
+
+        match drop {
+            TabDrop::Cancel => {}
+            TabDrop::Reorder(index) => {
+                self.activate_tab(TabIndex::absolute(drag.tab_idx))?;
+                self.move_tab(index)?;
+            }
+            TabDrop::MoveToWindow {
+                mux_window_id,
+                index,
+            } => {
+                mux.move_tab_to_window(tab_id, mux_window_id, Some(index))?;
+                if let Some(gui_win) = front_end().gui_window_for_mux_window(mux_window_id) {
+                    gui_win.window.focus();
+                }
+            }
+            TabDrop::Detach(tab_origin) => {
+                // Place the window so that its first tab is where the
+                // tab was dropped
+                let offset = self
+                    .tab_drop_target()
+                    .and_then(|target| {
+                        let first = target.tabs.first()?;
+                        Some((
+                            first.start - target.window.min_x(),
+                            target.tab_bar.min_y() - target.window.min_y(),
+                        ))
+                    })
+                    .unwrap_or((0, 0));
+                let top_left = ScreenPoint::new(tab_origin.x - offset.0, tab_origin.y - offset.1);
+
+                if tab_count == 1 {
+                    // Detaching the only tab is the same as moving
+                    // the window
+                    context.set_window_position(top_left);
+                    return Ok(());
+                }
+
+                let position = GuiPosition {
+                    x: Dimension::Pixels(top_left.x as f32),
+                    y: Dimension::Pixels(top_left.y as f32),
+                    origin: GeometryOrigin::ScreenCoordinateSystem,
+                };
+                let window_builder = mux.new_empty_window(Some(workspace), Some(position));
+                mux.move_tab_to_window(tab_id, *window_builder, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes the tab that is being dragged, under the mouse
+    pub fn describe_tab_drag(&self) -> anyhow::Result<Vec<RenderCommand>> {
+        let (Some(drag), Some(origin)) = (self.tab_drag.as_ref(), self.window_origin) else {
+            return Ok(vec![]);
+        };
+        if !drag.is_dragging() {
+            return Ok(vec![]);
+        }
+
+        let mux = Mux::get();
+        let tab = mux
+            .get_window(self.mux_window_id)
+            .and_then(|window| window.get_by_idx(drag.tab_idx).map(Arc::clone));
+        let Some(tab) = tab else {
+            return Ok(vec![]);
+        };
+        let mut title = tab.get_title();
+        if title.is_empty() {
+            title = tab
+                .get_active_pane()
+                .map(|pane| pane.get_title())
+                .unwrap_or_default();
+        }
+
+        let tab_origin = drag.tab_origin();
+        let x = (tab_origin.x - origin.x) as f32;
+        let y = (tab_origin.y - origin.y) as f32;
+        self.describe_label(&[title], |width, height| euclid::rect(x, y, width, height))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rect(x: isize, y: isize, width: isize, height: isize) -> ScreenRect {
+        euclid::rect(x, y, width, height)
+    }
+
+    /// Two windows side by side, each with a 30 pixel tab bar along
+    /// the top: the first has three tabs and the second has two
+    fn targets() -> Vec<TabDropTarget> {
+        vec![
+            TabDropTarget {
+                mux_window_id: 1,
+                window: rect(0, 0, 800, 600),
+                tab_bar: rect(0, 0, 800, 30),
+                tabs: vec![0..100, 100..200, 200..300],
+            },
+            TabDropTarget {
+                mux_window_id: 2,
+                window: rect(1000, 0, 800, 600),
+                tab_bar: rect(1000, 0, 800, 30),
+                tabs: vec![1000..1100, 1100..1200],
+            },
+        ]
+    }
+
+    /// Presses the tab at `tab_idx` in the first window 10 pixels in
+    /// from its top left corner, and then drags it to each point
+    fn drag(tab_idx: usize, points: &[(isize, isize)]) -> TabDrag {
+        let start = ScreenPoint::new(tab_idx as isize * 100 + 10, 10);
+        let mut drag = TabDrag::new(tab_idx, start, (10, 10));
+        for &(x, y) in points {
+            drag.motion(ScreenPoint::new(x, y));
+        }
+        drag
+    }
+
+    #[test]
+    fn small_movements_are_clicks() {
+        let drag = drag(1, &[(112, 14), (118, 2)]);
+        assert!(!drag.is_dragging());
+        assert_eq!(drag.drop_at(1, &targets()), None);
+    }
+
+    #[test]
+    fn dragging_continues_once_started() {
+        let mut drag = drag(1, &[(130, 10)]);
+        assert!(drag.is_dragging());
+        // Coming back to where it started doesn't make it a click
+        assert!(drag.motion(ScreenPoint::new(110, 10)));
+        assert_eq!(drag.drop_at(1, &targets()), Some(TabDrop::Reorder(1)));
+    }
+
+    #[test]
+    fn reorders_within_its_own_tab_bar() {
+        assert_eq!(
+            drag(0, &[(260, 10)]).drop_at(1, &targets()),
+            Some(TabDrop::Reorder(2))
+        );
+        assert_eq!(
+            drag(0, &[(140, 10)]).drop_at(1, &targets()),
+            Some(TabDrop::Reorder(0))
+        );
+        assert_eq!(
+            drag(2, &[(20, 10)]).drop_at(1, &targets()),
+            Some(TabDrop::Reorder(0))
+        );
+        // Past the last tab, over the rest of the tab bar
+        assert_eq!(
+            drag(0, &[(700, 10)]).drop_at(1, &targets()),
+            Some(TabDrop::Reorder(2))
+        );
+    }
+
+    #[test]
+    fn moves_to_another_tab_bar() {
+        assert_eq!(
+            drag(0, &[(1120, 20)]).drop_at(1, &targets()),
+            Some(TabDrop::MoveToWindow {
+                mux_window_id: 2,
+                index: 1
+            })
+        );
+        assert_eq!(
+            drag(0, &[(1600, 20)]).drop_at(1, &targets()),
+            Some(TabDrop::MoveToWindow {
+                mux_window_id: 2,
+                index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn cancelled_over_the_body_of_a_window() {
+        assert_eq!(
+            drag(0, &[(400, 300)]).drop_at(1, &targets()),
+            Some(TabDrop::Cancel)
+        );
+        assert_eq!(
+            drag(0, &[(1400, 300)]).drop_at(1, &targets()),
+            Some(TabDrop::Cancel)
+        );
+    }
+
+    #[test]
+    fn detached_outside_of_every_window() {
+        assert_eq!(
+            drag(1, &[(900, 700)]).drop_at(1, &targets()),
+            Some(TabDrop::Detach(ScreenPoint::new(890, 690)))
+        );
+    }
+}
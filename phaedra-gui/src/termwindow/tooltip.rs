@@ -0,0 +1,478 @@
+//! Tooltips for the tabs in the tab bar and the dividers between panes.
+//! Their text comes from `tab_bar.tooltip_format` and
+//! `pane_tooltip_format`, and appears once the mouse has rested on the
+//! item for `tooltip_delay_ms`.
+use crate::render_command::{LayerId, RectF, RenderCommand};
+use crate::tabbar::TabBarItem;
+use crate::termwindow::render::RenderScreenLineParams;
+use crate::termwindow::{TermWindow, UIItem, UIItemType};
+use ::window::{MouseEvent, WindowOps};
+use anyhow::bail;
+use config::spawn_template::expand_template;
+use mux::pane::{CachePolicy, Pane, PaneId};
+use mux::renderable::{RenderableDimensions, StableCursorPosition};
+use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
+use mux::Mux;
+use phaedra_term::{CellAttributes, Line};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Tooltips are drawn above the modal layer
+const TOOLTIP_ZINDEX: i8 = 20;
+
+/// What the variables in a tooltip template refer to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TooltipTarget {
+    /// The tab at this index in the window, and its active pane
+    Tab(usize),
+    Pane(PaneId),
+}
+
+/// The tooltip of a UIItem.  The template is only expanded once the
+/// tooltip is due to be shown, as finding the foreground process of
+/// a pane isn't free.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TooltipSpec {
+    pub template: String,
+    pub target: TooltipTarget,
+}
+
+/// The values that a tooltip template can refer to
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TooltipContext {
+    pub pane_id: Option<PaneId>,
+    pub pane_title: String,
+    pub cwd: String,
+    pub process: String,
+    pub domain: String,
+    pub tab_index: Option<usize>,
+    pub tab_title: String,
+}
+
+impl TooltipContext {
+    fn new(tab: Option<(usize, String)>, pane: Option<&Arc<dyn Pane>>) -> Self {
+        let mut context = Self::default();
+        if let Some((tab_index, tab_title)) = tab {
+            context.tab_index = Some(tab_index);
+            context.tab_title = tab_title;
+        }
+        if let Some(pane) = pane {
+            context.pane_id = Some(pane.pane_id());
+            context.pane_title = pane.get_title();
+            context.cwd = pane
+                .get_current_working_dir(CachePolicy::AllowStale)
+                .map(|url| match url.to_file_path() {
+                    Ok(path) => path.display().to_string(),
+                    Err(_) => url.path().to_string(),
+                })
+                .unwrap_or_default();
+            context.process = pane
+                .get_foreground_process_name(CachePolicy::AllowStale)
+                .unwrap_or_default();
+            context.domain = Mux::get()
+                .get_domain(pane.domain_id())
+                .map(|domain| domain.domain_name().to_string())
+                .unwrap_or_default();
+        }
+        context
+    }
+
+    /// Expands the `${name}` references in `template`, where `name` is
+    /// one of the fields of this struct.  Values that aren't known,
+    /// such as the cwd of a pane that hasn't reported one, expand to
+    /// the empty string.
+    pub fn expand(&self, template: &str) -> anyhow::Result<String> {
+        expand_template(template, |name| self.lookup(name))
+    }
+
+    fn lookup(&self, name: &str) -> anyhow::Result<String> {
+        Ok(match name {
+            "pane_id" => self.pane_id.map(|id| id.to_string()).unwrap_or_default(),
+            "pane_title" => self.pane_title.clone(),
+            "cwd" => self.cwd.clone(),
+            "process" => self.process.clone(),
+            "domain" => self.domain.clone(),
+            "tab_index" => self
+                .tab_index
+                .map(|idx| idx.to_string())
+                .unwrap_or_default(),
+            "tab_title" => self.tab_title.clone(),
+            name => bail!(
+                "${{{name}}} is not known. Use one of ${{pane_title}}, ${{cwd}}, \
+                 ${{process}}, ${{domain}}, ${{pane_id}}, ${{tab_title}} or ${{tab_index}}"
+            ),
+        })
+    }
+}
+
+/// The tooltip for the item under the mouse
+#[derive(Debug)]
+pub struct TooltipHover {
+    spec: TooltipSpec,
+    /// Where the mouse entered the item
+    anchor: (isize, isize),
+    since: Instant,
+    /// The expanded template, split into lines, once the tooltip is shown
+    lines: Option<Vec<String>>,
+}
+
+/// Returns the bounds of a tooltip of `width` by `height` pixels for the
+/// mouse at `cursor`.  The tooltip goes `gap` pixels below the mouse,
+/// or above it if there is no room below, and is moved left when it
+/// would extend past the right edge.  A tooltip larger than the window
+/// is aligned with its top left corner.
+pub fn tooltip_bounds(
+    cursor: (f32, f32),
+    width: f32,
+    height: f32,
+    window_width: f32,
+    window_height: f32,
+    gap: f32,
+) -> RectF {
+    let (cursor_x, cursor_y) = cursor;
+    let x = cursor_x.min(window_width - width).max(0.);
+    let below = cursor_y + gap;
+    let y = if below + height <= window_height {
+        below
+    } else {
+        (cursor_y - gap - height)
+            .min(window_height - height)
+            .max(0.)
+    };
+    euclid::rect(x, y, width, height)
+}
+
+/// Returns the pane that is to the left of, or above, `split`.  Where
+/// several panes share that side, this is the first of them.
+pub fn pane_before_split(split: &PositionedSplit, panes: &[PositionedPane]) -> Option<PaneId> {
+    panes
+        .iter()
+        .find(|pos| match split.direction {
+            SplitDirection::Horizontal => {
+                pos.left + pos.width == split.left
+                    && (pos.top..pos.top + pos.height).contains(&split.top)
+            }
+            SplitDirection::Vertical => {
+                pos.top + pos.height == split.top
+                    && (pos.left..pos.left + pos.width).contains(&split.left)
+            }
+        })
+        .map(|pos| pos.pane.pane_id())
+}
+
+impl TermWindow {
+    /// Attaches a tooltip to each of the tabs in `items`, if
+    /// `tab_bar.tooltip_format` is set
+    pub fn add_tab_tooltips(&self, items: &mut [UIItem]) {
+        let Some(template) = &self.config.tab_bar().tooltip_format else {
+            return;
+        };
+        for item in items {
+            if let UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) = item.item_type {
+                item.tooltip = Some(TooltipSpec {
+                    template: template.clone(),
+                    target: TooltipTarget::Tab(tab_idx),
+                });
+            }
+        }
+    }
+
+    /// Returns the tooltip for the divider `split`, if
+    /// `pane_tooltip_format` is set, which describes the pane
+    /// before the divider
+    pub fn split_tooltip(
+        &self,
+        split: &PositionedSplit,
+        panes: &[PositionedPane],
+    ) -> Option<TooltipSpec> {
+        let template = self.config.window().pane_tooltip_format.as_ref()?;
+        Some(TooltipSpec {
+            template: template.clone(),
+            target: TooltipTarget::Pane(pane_before_split(split, panes)?),
+        })
+    }
+
+    /// Starts the hover delay when the mouse moves onto an item with
+    /// a tooltip, and hides the tooltip when it leaves
+    pub fn update_tooltip_hover(
+        &mut self,
+        item: Option<&UIItem>,
+        event: &MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        let spec = item.and_then(|item| item.tooltip.as_ref());
+        if let (Some(hover), Some(spec)) = (&self.tooltip, spec) {
+            if hover.spec == *spec {
+                return;
+            }
+        }
+        if self
+            .tooltip
+            .take()
+            .map_or(false, |hover| hover.lines.is_some())
+        {
+            context.invalidate();
+        }
+        self.tooltip = spec.map(|spec| TooltipHover {
+            spec: spec.clone(),
+            anchor: (event.coords.x, event.coords.y),
+            since: Instant::now(),
+            lines: None,
+        });
+    }
+
+    pub fn hide_tooltip(&mut self) {
+        self.tooltip = None;
+    }
+
+    fn expand_tooltip(&self, spec: &TooltipSpec) -> anyhow::Result<String> {
+        let mux = Mux::get();
+        let (tab, pane) = match spec.target {
+            TooltipTarget::Tab(tab_idx) => {
+                let tab = mux
+                    .get_window(self.mux_window_id)
+                    .and_then(|window| window.get_by_idx(tab_idx).map(Arc::clone));
+                match tab {
+                    Some(tab) => (Some((tab_idx, tab.get_title())), tab.get_active_pane()),
+                    None => (None, None),
+                }
+            }
+            TooltipTarget::Pane(pane_id) => {
+                let tab = mux.get_window(self.mux_window_id).and_then(|window| {
+                    let tab_idx = window.get_active_idx();
+                    window
+                        .get_by_idx(tab_idx)
+                        .map(|tab| (tab_idx, tab.get_title()))
+                });
+                (tab, mux.get_pane(pane_id))
+            }
+        };
+        TooltipContext::new(tab, pane.as_ref()).expand(&spec.template)
+    }
+
+    /// Expands the tooltip once the mouse has rested on its item for
+    /// long enough; until then, schedules a frame for when it is due
+    pub fn update_tooltip(&mut self) {
+        let Some(hover) = self.tooltip.as_ref() else {
+            return;
+        };
+        if hover.lines.is_some() {
+            return;
+        }
+        let due = hover.since + Duration::from_millis(self.config.window().tooltip_delay_ms);
+        if Instant::now() < due {
+            self.update_next_frame_time(Some(due));
+            return;
+        }
+        let lines = match self.expand_tooltip(&hover.spec) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(err) => {
+                log::error!("tooltip: {err:#}");
+                vec![]
+            }
+        };
+        if let Some(hover) = self.tooltip.as_mut() {
+            hover.lines = Some(lines);
+        }
+    }
+
+    /// Describes the tooltip near the mouse, if it is due to be shown
+    pub fn describe_tooltip(&self) -> anyhow::Result<Vec<RenderCommand>> {
+        let Some(hover) = self.tooltip.as_ref() else {
+            return Ok(vec![]);
+        };
+        let texts = hover.lines.as_deref().unwrap_or_default();
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        self.describe_label(texts, |width, height| {
+            tooltip_bounds(
+                (hover.anchor.0 as f32, hover.anchor.1 as f32),
+                width,
+                height,
+                self.dimensions.pixel_width as f32,
+                self.dimensions.pixel_height as f32,
+                cell_height,
+            )
+        })
+    }
+
+    /// Describes a box holding `texts`, one per line, in the inverse
+    /// of the window colors and above the modal layer.  `place` is
+    /// given the width and height of the box and returns its bounds.
+    pub fn describe_label(
+        &self,
+        texts: &[String],
+        place: impl FnOnce(f32, f32) -> RectF,
+    ) -> anyhow::Result<Vec<RenderCommand>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut palette = (*self.window_palette()).clone();
+        std::mem::swap(&mut palette.foreground, &mut palette.background);
+        let background = palette.background.to_linear();
+
+        let attrs = CellAttributes::default();
+        let lines: Vec<Line> = texts
+            .iter()
+            .map(|text| Line::from_text(text, &attrs, 0, None))
+            .collect();
+        let cols = lines
+            .iter()
+            .map(|line| line.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let padding = (cell_width / 2.).ceil();
+        let bounds = place(
+            cols as f32 * cell_width + 2. * padding,
+            lines.len() as f32 * cell_height + 2. * padding,
+        );
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+        let cursor = StableCursorPosition::default();
+
+        let mut commands = vec![RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: bounds,
+            color: background,
+            hsv: None,
+        }];
+        for (idx, line) in lines.iter().enumerate() {
+            commands.extend(self.describe_screen_line(RenderScreenLineParams {
+                top_pixel_y: bounds.min_y() + padding + idx as f32 * cell_height,
+                left_pixel_x: bounds.min_x() + padding,
+                pixel_width: cols as f32 * cell_width,
+                stable_line_idx: None,
+                line,
+                selection: 0..0,
+                cursor: &cursor,
+                palette: &palette,
+                dims: &RenderableDimensions {
+                    cols,
+                    physical_top: 0,
+                    scrollback_rows: 0,
+                    scrollback_top: 0,
+                    viewport_rows: 1,
+                    dpi: self.terminal_size.dpi,
+                    pixel_height: self.render_metrics.cell_size.height as usize,
+                    pixel_width: self.terminal_size.pixel_width,
+                    reverse_video: false,
+                },
+                config: &self.config,
+                cursor_border_color: Default::default(),
+                foreground: palette.foreground.to_linear(),
+                pane: None,
+                is_active: true,
+                selection_fg: Default::default(),
+                selection_bg: Default::default(),
+                cursor_fg: Default::default(),
+                cursor_bg: Default::default(),
+                cursor_is_default_color: true,
+                white_space,
+                window_is_transparent: false,
+                default_bg: background,
+                style: None,
+                font: None,
+                pane_font: None,
+                use_pixel_positioning: self.config.text().experimental_pixel_positioning,
+                render_metrics: self.render_metrics,
+                shape_key: None,
+                password_input: false,
+            })?);
+        }
+
+        Ok(commands
+            .into_iter()
+            .map(|command| command.with_zindex(TOOLTIP_ZINDEX))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context() -> TooltipContext {
+        TooltipContext {
+            pane_id: Some(3),
+            pane_title: "vim".to_string(),
+            cwd: "/home/user/project".to_string(),
+            process: "nvim".to_string(),
+            domain: "local".to_string(),
+            tab_index: Some(1),
+            tab_title: "editor".to_string(),
+        }
+    }
+
+    #[test]
+    fn expands_variables() {
+        assert_eq!(
+            context()
+                .expand("${pane_title} (${process}) in ${cwd}\n${domain}, pane ${pane_id}")
+                .unwrap(),
+            "vim (nvim) in /home/user/project\nlocal, pane 3"
+        );
+        assert_eq!(
+            context().expand("tab ${tab_index}: ${tab_title}").unwrap(),
+            "tab 1: editor"
+        );
+        assert_eq!(
+            context().expand("$${cwd} costs $5").unwrap(),
+            "${cwd} costs $5"
+        );
+    }
+
+    #[test]
+    fn unknown_values_are_empty() {
+        assert_eq!(
+            TooltipContext::default()
+                .expand("[${cwd}] [${pane_id}] [${tab_index}]")
+                .unwrap(),
+            "[] [] []"
+        );
+    }
+
+    #[test]
+    fn unknown_variables_are_errors() {
+        let err = context().expand("${pane_cwd}").unwrap_err().to_string();
+        assert!(err.contains("${pane_cwd} is not known"), "{err}");
+        assert!(context().expand("${cwd").is_err());
+    }
+
+    fn bounds(cursor: (f32, f32), width: f32, height: f32) -> (f32, f32) {
+        let rect = tooltip_bounds(cursor, width, height, 800., 600., 20.);
+        assert_eq!((rect.width(), rect.height()), (width, height));
+        (rect.min_x(), rect.min_y())
+    }
+
+    #[test]
+    fn placed_below_the_mouse() {
+        assert_eq!(bounds((100., 100.), 200., 50.), (100., 120.));
+        assert_eq!(bounds((0., 0.), 200., 50.), (0., 20.));
+    }
+
+    #[test]
+    fn kept_inside_the_right_edge() {
+        assert_eq!(bounds((700., 100.), 200., 50.), (600., 120.));
+        assert_eq!(bounds((800., 100.), 200., 50.), (600., 120.));
+    }
+
+    #[test]
+    fn flipped_above_the_mouse_at_the_bottom_edge() {
+        assert_eq!(bounds((100., 560.), 200., 50.), (100., 490.));
+        assert_eq!(bounds((790., 599.), 200., 50.), (600., 529.));
+    }
+
+    #[test]
+    fn clamped_when_there_is_no_room_above_or_below() {
+        // Neither above nor below the mouse fits, so it is moved down
+        // from above the mouse until it is inside the window
+        assert_eq!(bounds((100., 300.), 200., 580.), (100., 0.));
+        // Larger than the window, so it starts at the top left
+        assert_eq!(bounds((500., 300.), 1000., 700.), (0., 0.));
+    }
+}
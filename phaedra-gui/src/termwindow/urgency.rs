@@ -0,0 +1,97 @@
+//! Decides when a bell sets the urgency hint of its window, so that a
+//! bell in a window on another workspace can be noticed.
+use config::{AudibleBell, BellConfig, NotificationHandling};
+
+/// Returns true if a bell that rang in a window should set the urgency
+/// hint of that window.  Only a bell that can be heard or seen does so,
+/// and only while the window isn't focused.  `notification_handling`
+/// suppresses the hint only when it is `NeverShow`: the pane that rang
+/// is in a window that isn't focused, so it is neither the focused pane
+/// nor in the focused tab or window.
+pub fn bell_sets_urgency_hint(
+    bell: &BellConfig,
+    notification_handling: NotificationHandling,
+    window_focused: bool,
+) -> bool {
+    if !bell.set_urgency_hint || window_focused {
+        return false;
+    }
+    if notification_handling == NotificationHandling::NeverShow {
+        return false;
+    }
+    let audible = matches!(bell.audible_bell, AudibleBell::SystemBeep);
+    let visual = bell.visual_bell.fade_in_duration_ms + bell.visual_bell.fade_out_duration_ms > 0;
+    audible || visual
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_unfocused_windows_are_marked_urgent() {
+        let bell = BellConfig::default();
+        assert!(bell.set_urgency_hint);
+        assert!(bell_sets_urgency_hint(
+            &bell,
+            NotificationHandling::AlwaysShow,
+            false
+        ));
+        assert!(!bell_sets_urgency_hint(
+            &bell,
+            NotificationHandling::AlwaysShow,
+            true
+        ));
+
+        let disabled = BellConfig {
+            set_urgency_hint: false,
+            ..BellConfig::default()
+        };
+        assert!(!bell_sets_urgency_hint(
+            &disabled,
+            NotificationHandling::AlwaysShow,
+            false
+        ));
+    }
+
+    #[test]
+    fn notification_handling_is_respected() {
+        let bell = BellConfig::default();
+        for handling in [
+            NotificationHandling::AlwaysShow,
+            NotificationHandling::SuppressFromFocusedPane,
+            NotificationHandling::SuppressFromFocusedTab,
+            NotificationHandling::SuppressFromFocusedWindow,
+        ] {
+            assert!(
+                bell_sets_urgency_hint(&bell, handling, false),
+                "{handling:?}"
+            );
+        }
+        assert!(!bell_sets_urgency_hint(
+            &bell,
+            NotificationHandling::NeverShow,
+            false
+        ));
+    }
+
+    #[test]
+    fn silent_invisible_bell_is_not_urgent() {
+        let mut bell = BellConfig {
+            audible_bell: AudibleBell::Disabled,
+            ..BellConfig::default()
+        };
+        assert!(!bell_sets_urgency_hint(
+            &bell,
+            NotificationHandling::AlwaysShow,
+            false
+        ));
+
+        bell.visual_bell.fade_out_duration_ms = 150;
+        assert!(bell_sets_urgency_hint(
+            &bell,
+            NotificationHandling::AlwaysShow,
+            false
+        ));
+    }
+}
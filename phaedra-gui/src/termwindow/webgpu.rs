@@ -3,6 +3,8 @@ use config::observers::*;
 use anyhow::anyhow;
 use config::{ConfigHandle, GpuInfo, WebGpuPowerPreference};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 use window::bitmaps::Texture2d;
@@ -27,7 +29,9 @@ pub struct ShaderUniform {
 pub struct PostProcessUniform {
     pub resolution: [f32; 2],
     pub time: f32,
-    pub _padding: f32,
+    /// The intensity of a ringing visual bell whose target is
+    /// PostProcess, from 0.0 to 1.0
+    pub bell_intensity: f32,
 }
 
 pub struct WebGpuState {
@@ -46,9 +50,19 @@ pub struct WebGpuState {
     pub handle: RawHandlePair,
     // Post-processing support
     pub postprocess_pipeline: RefCell<Option<wgpu::RenderPipeline>>,
-    pub postprocess_bind_group_layout: RefCell<Option<wgpu::BindGroupLayout>>,
+    /// Named effects that can be requested for part of the frame
+    /// via `RenderCommand::BeginPostProcess`
+    pub postprocess_effects: RefCell<HashMap<String, wgpu::RenderPipeline>>,
+    /// Copies the intermediate texture through unmodified
+    pub postprocess_copy_pipeline: wgpu::RenderPipeline,
+    /// The built-in visual bell flash, compiled the first time that it
+    /// is needed; see `ensure_visual_bell_pipeline`
+    pub visual_bell_pipeline: RefCell<Option<wgpu::RenderPipeline>>,
+    pub postprocess_bind_group_layout: wgpu::BindGroupLayout,
     pub postprocess_intermediate_texture: RefCell<Option<wgpu::Texture>>,
     pub postprocess_sampler: wgpu::Sampler,
+    /// Set by the device lost callback
+    device_lost: Arc<AtomicBool>,
 }
 
 pub struct RawHandlePair {
@@ -183,11 +197,19 @@ impl WebGpuTexture {
 }
 
 pub fn adapter_info_to_gpu_info(info: wgpu::AdapterInfo) -> GpuInfo {
+    let device_type = format!("{:?}", info.device_type);
+    let is_software = GpuInfo::detect_software(
+        &info.name,
+        Some(info.driver.as_str()).filter(|d| !d.is_empty()),
+        &device_type,
+    );
     GpuInfo {
+        is_software,
+        surface_compatible: None,
         name: info.name,
         vendor: Some(info.vendor),
         device: Some(info.device),
-        device_type: format!("{:?}", info.device_type),
+        device_type,
         driver: if info.driver.is_empty() {
             None
         } else {
@@ -211,31 +233,353 @@ fn compute_compatibility_list(
         .enumerate_adapters(backends)
         .into_iter()
         .map(|a| {
-            let info = adapter_info_to_gpu_info(a.get_info());
-            let compatible = a.is_surface_supported(&surface);
-            format!(
-                "{}, compatible={}",
-                info.to_string(),
-                if compatible { "yes" } else { "NO" }
-            )
+            let mut info = adapter_info_to_gpu_info(a.get_info());
+            info.surface_compatible = Some(a.is_surface_supported(&surface));
+            info.to_string()
         })
         .collect()
 }
 
+/// Enumerates the adapters that wgpu can see for each backend,
+/// without requiring a window.  As there is no surface to test
+/// against, surface compatibility is reported as unknown.
+pub fn enumerate_adapters_standalone() -> Vec<GpuInfo> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    [
+        wgpu::Backends::VULKAN,
+        wgpu::Backends::METAL,
+        wgpu::Backends::DX12,
+        wgpu::Backends::GL,
+    ]
+    .into_iter()
+    .flat_map(|backend| instance.enumerate_adapters(backend))
+    .map(|adapter| adapter_info_to_gpu_info(adapter.get_info()))
+    .collect()
+}
+
+pub(crate) fn compile_postprocess_shader(
+    device: &wgpu::Device,
+    label: &str,
+    shader_source: &str,
+) -> anyhow::Result<wgpu::ShaderModule> {
+    // wgpu will validate and log any shader errors
+    // Using catch_unwind to prevent panics from crashing the terminal
+    let shader_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        })
+    }));
+
+    match shader_result {
+        Ok(s) => Ok(s),
+        Err(e) => {
+            let msg = if let Some(s) = e.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = e.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "Unknown shader compilation error".to_string()
+            };
+            log::error!("WebGPU shader compilation failed: {}", msg);
+            Err(anyhow!("Shader compilation failed: {}", msg))
+        }
+    }
+}
+
+pub(crate) fn create_shader_uniform_bind_group_layout(
+    device: &wgpu::Device,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+        label: Some("ShaderUniform bind group layout"),
+    })
+}
+
+pub(crate) fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some("texture bind group layout"),
+    })
+}
+
+/// Creates the pipeline that draws the quads in the render layers,
+/// with the uniforms in group 0 and the linear and nearest atlas
+/// textures in groups 1 and 2
+pub(crate) fn create_render_pipeline(
+    device: &wgpu::Device,
+    shader_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    texture_bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(wgpu::include_wgsl!("../shader.wgsl"));
+    let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts: &[
+            shader_uniform_bind_group_layout,
+            texture_bind_group_layout,
+            texture_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Render Pipeline"),
+        layout: Some(&render_pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[Vertex::desc()],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+pub(crate) fn create_postprocess_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("PostProcess Bind Group Layout"),
+        entries: &[
+            // Uniform buffer
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Input texture
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            // Sampler
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    })
+}
+
+pub(crate) fn create_postprocess_pipeline(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("PostProcess Pipeline Layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("PostProcess Pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[], // Full-screen triangle doesn't need vertex buffers
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None,
+    })
+}
+
+pub(crate) fn create_postprocess_bind_group(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    uniform: PostProcessUniform,
+    texture_view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("PostProcess Uniform Buffer"),
+        contents: bytemuck::cast_slice(&[uniform]),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("PostProcess Bind Group"),
+    })
+}
+
+/// Load the post-processing shader and effects named by the configuration.
+/// Problems are logged, leaving the frame without post-processing.
+pub fn load_postprocess_shaders(webgpu: &WebGpuState, config: &ConfigHandle) {
+    if let Some(shader_path) = &config.gpu().webgpu_shader {
+        match std::fs::read_to_string(shader_path) {
+            Ok(shader_source) => {
+                if let Err(e) = webgpu.load_postprocess_shader(&shader_source) {
+                    log::error!("Failed to load WebGPU shader from {:?}: {}", shader_path, e);
+                }
+            }
+            Err(e) => {
+                log::error!("Failed to read WebGPU shader file {:?}: {}", shader_path, e);
+            }
+        }
+    }
+    for (name, shader_path) in &config.gpu().webgpu_shader_effects {
+        match std::fs::read_to_string(shader_path) {
+            Ok(shader_source) => {
+                if let Err(e) = webgpu.load_postprocess_effect(name, &shader_source) {
+                    log::error!(
+                        "Failed to load WebGPU effect {} from {:?}: {}",
+                        name,
+                        shader_path,
+                        e
+                    );
+                }
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to read WebGPU effect {} file {:?}: {}",
+                    name,
+                    shader_path,
+                    e
+                );
+            }
+        }
+    }
+}
+
 impl WebGpuState {
     pub async fn new(
         window: &Window,
         dimensions: Dimensions,
         config: &ConfigHandle,
+    ) -> anyhow::Result<Self> {
+        let force_fallback_adapter = config.gpu().webgpu_force_fallback_adapter;
+        Self::new_with_adapter(window, dimensions, config, force_fallback_adapter).await
+    }
+
+    /// Like `new`, but `force_fallback_adapter` overrides
+    /// `gpu.webgpu_force_fallback_adapter`; when it is set, the
+    /// preferred adapter is not considered
+    pub async fn new_with_adapter(
+        window: &Window,
+        dimensions: Dimensions,
+        config: &ConfigHandle,
+        force_fallback_adapter: bool,
     ) -> anyhow::Result<Self> {
         let handle = RawHandlePair::new(window);
-        Self::new_impl(handle, dimensions, config).await
+        Self::new_impl(handle, dimensions, config, force_fallback_adapter).await
     }
 
     pub async fn new_impl(
         handle: RawHandlePair,
         dimensions: Dimensions,
         config: &ConfigHandle,
+        force_fallback_adapter: bool,
     ) -> anyhow::Result<Self> {
         let backends = wgpu::Backends::all();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
@@ -248,7 +592,12 @@ impl WebGpuState {
 
         let mut adapter: Option<wgpu::Adapter> = None;
 
-        if let Some(preference) = &config.gpu().webgpu_preferred_adapter {
+        let preferred_adapter = if force_fallback_adapter {
+            None
+        } else {
+            config.gpu().webgpu_preferred_adapter.as_ref()
+        };
+        if let Some(preference) = preferred_adapter {
             for a in instance.enumerate_adapters(backends) {
                 if !a.is_surface_supported(&surface) {
                     let info = adapter_info_to_gpu_info(a.get_info());
@@ -312,7 +661,7 @@ impl WebGpuState {
                             WebGpuPowerPreference::LowPower => wgpu::PowerPreference::LowPower,
                         },
                         compatible_surface: Some(&surface),
-                        force_fallback_adapter: config.gpu().webgpu_force_fallback_adapter,
+                        force_fallback_adapter,
                     })
                     .await?,
             );
@@ -352,6 +701,19 @@ impl WebGpuState {
 
         let queue = Arc::new(queue);
 
+        let device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let device_lost = Arc::clone(&device_lost);
+            move |reason, message| match reason {
+                // We dropped the device ourselves
+                wgpu::DeviceLostReason::Destroyed => {}
+                _ => {
+                    log::error!("GPU device lost ({:?}): {}", reason, message);
+                    device_lost.store(true, Ordering::SeqCst);
+                }
+            }
+        });
+
         // Explicitly request an SRGB format, if available
         let pref_format_srgb = caps.formats[0].add_srgb_suffix();
         let format = if caps.formats.contains(&pref_format_srgb) {
@@ -397,22 +759,7 @@ impl WebGpuState {
         };
         surface.configure(&device, &config);
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("../shader.wgsl"));
-
-        let shader_uniform_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("ShaderUniform bind group layout"),
-            });
+        let shader_uniform_bind_group_layout = create_shader_uniform_bind_group_layout(&device);
 
         let texture_nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
@@ -433,78 +780,13 @@ impl WebGpuState {
             ..Default::default()
         });
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture bind group layout"),
-            });
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &shader_uniform_bind_group_layout,
-                    &texture_bind_group_layout,
-                    &texture_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let texture_bind_group_layout = create_texture_bind_group_layout(&device);
+        let render_pipeline = create_render_pipeline(
+            &device,
+            &shader_uniform_bind_group_layout,
+            &texture_bind_group_layout,
+            config.format,
+        );
 
         // Create post-processing sampler
         let postprocess_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -517,6 +799,14 @@ impl WebGpuState {
             ..Default::default()
         });
 
+        let postprocess_bind_group_layout = create_postprocess_bind_group_layout(&device);
+        let postprocess_copy_pipeline = create_postprocess_pipeline(
+            &device,
+            &postprocess_bind_group_layout,
+            &device.create_shader_module(wgpu::include_wgsl!("../postprocess_copy.wgsl")),
+            format,
+        );
+
         Ok(Self {
             adapter_info,
             downlevel_caps,
@@ -532,9 +822,13 @@ impl WebGpuState {
             texture_nearest_sampler,
             texture_linear_sampler,
             postprocess_pipeline: RefCell::new(None),
-            postprocess_bind_group_layout: RefCell::new(None),
+            postprocess_effects: RefCell::new(HashMap::new()),
+            postprocess_copy_pipeline,
+            visual_bell_pipeline: RefCell::new(None),
+            postprocess_bind_group_layout,
             postprocess_intermediate_texture: RefCell::new(None),
             postprocess_sampler,
+            device_lost,
         })
     }
 
@@ -557,37 +851,17 @@ impl WebGpuState {
     }
 
     pub fn create_postprocess_uniform(&self, uniform: PostProcessUniform) -> wgpu::BindGroup {
-        let buffer = self
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("PostProcess Uniform Buffer"),
-                contents: bytemuck::cast_slice(&[uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
-
         let intermediate_texture = self.postprocess_intermediate_texture.borrow();
         let texture = intermediate_texture.as_ref().expect("intermediate texture must exist");
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let bind_group_layout = self.postprocess_bind_group_layout.borrow();
-        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: bind_group_layout.as_ref().unwrap(),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&self.postprocess_sampler),
-                },
-            ],
-            label: Some("PostProcess Bind Group"),
-        })
+        create_postprocess_bind_group(
+            &self.device,
+            &self.postprocess_bind_group_layout,
+            uniform,
+            &texture_view,
+            &self.postprocess_sampler,
+        )
     }
 
     pub fn ensure_intermediate_texture(&self, width: u32, height: u32) {
@@ -653,120 +927,75 @@ impl WebGpuState {
 
     /// Load a custom post-processing shader from the given WGSL source code
     pub fn load_postprocess_shader(&self, shader_source: &str) -> anyhow::Result<()> {
-        // wgpu will validate and log any shader errors
-        // Using catch_unwind to prevent panics from crashing the terminal
-        let shader_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Custom PostProcess Shader"),
-                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
-            })
-        }));
-
-        let shader = match shader_result {
-            Ok(s) => s,
-            Err(e) => {
-                let msg = if let Some(s) = e.downcast_ref::<&str>() {
-                    s.to_string()
-                } else if let Some(s) = e.downcast_ref::<String>() {
-                    s.clone()
-                } else {
-                    "Unknown shader compilation error".to_string()
-                };
-                log::error!("WebGPU shader compilation failed: {}", msg);
-                return Err(anyhow!("Shader compilation failed: {}", msg));
-            }
-        };
+        let shader =
+            compile_postprocess_shader(&self.device, "Custom PostProcess Shader", shader_source)?;
+        let format = self.config.borrow().format;
+        let pipeline = create_postprocess_pipeline(
+            &self.device,
+            &self.postprocess_bind_group_layout,
+            &shader,
+            format,
+        );
 
-        // Create bind group layout for post-processing
-        let bind_group_layout = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("PostProcess Bind Group Layout"),
-            entries: &[
-                // Uniform buffer
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                },
-                // Input texture
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                // Sampler
-                wgpu::BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        *self.postprocess_pipeline.borrow_mut() = Some(pipeline);
 
-        let pipeline_layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("PostProcess Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
+        log::info!("Loaded custom post-processing shader");
+        Ok(())
+    }
 
+    /// Load a named post-processing effect from the given WGSL source
+    /// code.  It uses the same bindings as `load_postprocess_shader`.
+    pub fn load_postprocess_effect(&self, name: &str, shader_source: &str) -> anyhow::Result<()> {
+        let shader = compile_postprocess_shader(
+            &self.device,
+            &format!("PostProcess Effect {name}"),
+            shader_source,
+        )?;
         let format = self.config.borrow().format;
+        let pipeline = create_postprocess_pipeline(
+            &self.device,
+            &self.postprocess_bind_group_layout,
+            &shader,
+            format,
+        );
 
-        let pipeline = self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("PostProcess Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[], // Full-screen triangle doesn't need vertex buffers
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        self.postprocess_effects
+            .borrow_mut()
+            .insert(name.to_string(), pipeline);
 
-        *self.postprocess_bind_group_layout.borrow_mut() = Some(bind_group_layout);
-        *self.postprocess_pipeline.borrow_mut() = Some(pipeline);
+        log::info!("Loaded post-processing effect {name}");
+        Ok(())
+    }
 
-        log::info!("Loaded custom post-processing shader");
+    /// Compiles the built-in visual bell shader, unless that has
+    /// already been done
+    pub fn ensure_visual_bell_pipeline(&self) -> anyhow::Result<()> {
+        if self.visual_bell_pipeline.borrow().is_some() {
+            return Ok(());
+        }
+        let shader = compile_postprocess_shader(
+            &self.device,
+            "Visual Bell PostProcess Shader",
+            include_str!("../postprocess_bell.wgsl"),
+        )?;
+        let format = self.config.borrow().format;
+        let pipeline = create_postprocess_pipeline(
+            &self.device,
+            &self.postprocess_bind_group_layout,
+            &shader,
+            format,
+        );
+        *self.visual_bell_pipeline.borrow_mut() = Some(pipeline);
         Ok(())
     }
 
     /// Check if post-processing is enabled
+    /// Returns true once the device has been lost; everything created
+    /// from it must then be recreated with a new WebGpuState
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::SeqCst)
+    }
+
     pub fn has_postprocess(&self) -> bool {
         self.postprocess_pipeline.borrow().is_some()
     }
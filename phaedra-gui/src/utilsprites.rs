@@ -5,7 +5,7 @@ use ::window::bitmaps::{BitmapImage, Image};
 use ::window::color::SrgbaPixel;
 use ::window::{Point, Rect, Size};
 use anyhow::Context;
-use config::DimensionContext;
+use config::{Dimension, DimensionContext, TextConfig};
 use std::rc::Rc;
 use phaedra_font::units::*;
 use phaedra_font::{FontConfiguration, FontMetrics};
@@ -17,9 +17,29 @@ pub struct RenderMetrics {
     pub descender_plus_two: IntPixelLength,
     pub underline_height: IntPixelLength,
     pub strike_row: IntPixelLength,
+    /// The row of the top of the overline, relative to the top of the cell
+    pub overline_row: IntPixelLength,
     pub cell_size: Size,
 }
 
+/// Evaluates a strikethrough or overline position that was set in the
+/// text config to the row, relative to the top of the cell, that the
+/// line is drawn at.  `pixel_max` is what a percentage is relative to.
+pub fn decoration_row(
+    position: &Dimension,
+    dpi: f32,
+    pixel_max: f32,
+    cell_height: IntPixelLength,
+) -> IntPixelLength {
+    position
+        .evaluate_as_pixels(DimensionContext {
+            dpi,
+            pixel_max,
+            pixel_cell: cell_height as f32,
+        })
+        .round() as isize
+}
+
 impl RenderMetrics {
     pub fn with_font_metrics(metrics: &FontMetrics) -> Self {
         let (cell_height, cell_width) = (
@@ -40,6 +60,7 @@ impl RenderMetrics {
             descender_row,
             descender_plus_two,
             strike_row,
+            overline_row: 0,
             cell_size: Size::new(cell_width as isize, cell_height as isize),
             underline_height,
         }
@@ -59,6 +80,7 @@ impl RenderMetrics {
             descender_plus_two: self.descender_plus_two - adjust,
             underline_height: self.underline_height,
             strike_row: self.strike_row,
+            overline_row: self.overline_row,
             cell_size: size,
         }
     }
@@ -69,6 +91,56 @@ impl RenderMetrics {
         scaled
     }
 
+    /// Returns a copy of these metrics with the underline, strikethrough
+    /// and overline placement derived from `font`, the metrics of the font
+    /// that was actually used to render a run of text, rather than the
+    /// primary font.  `scale` is the factor that was applied to that font's
+    /// glyphs to fit them into the cell.  The cell size and baseline are
+    /// shared with the primary font, and any values that were explicitly
+    /// set in the text config are retained.
+    pub fn with_run_font_metrics(&self, font: &FontMetrics, scale: f64, text: &TextConfig) -> Self {
+        let cell_height = self.cell_size.height;
+        let underline_height = match &text.underline_thickness {
+            Some(_) => self.underline_height,
+            None => (font.underline_thickness.get() * scale).round().max(1.) as isize,
+        };
+
+        let descender_row = match &text.underline_position {
+            Some(_) => self.descender_row,
+            None => {
+                let baseline = cell_height as f64 + self.descender.get();
+                (baseline - font.underline_position.get() * scale) as isize
+            }
+        };
+        let descender_plus_two =
+            (2 * underline_height + descender_row).min(cell_height - underline_height);
+
+        let strike_row = match &text.strikethrough_position {
+            Some(_) => self.strike_row,
+            None => self.strike_row + (descender_row - self.descender_row) / 2,
+        };
+
+        // The overline sits on top of the run's ascent, which is the
+        // primary font's ascent unless the run font is taller than it
+        let overline_row = match &text.overline_position {
+            Some(_) => self.overline_row,
+            None => {
+                let baseline = cell_height as f64 + self.descender.get();
+                let ascent = (font.cell_height + font.descender).get() * scale;
+                (baseline - ascent).max(0.).min(self.overline_row as f64) as isize
+            }
+        };
+
+        Self {
+            descender_row,
+            descender_plus_two,
+            underline_height,
+            strike_row,
+            overline_row,
+            ..*self
+        }
+    }
+
     pub fn new(fonts: &Rc<FontConfiguration>) -> anyhow::Result<Self> {
         let metrics = fonts
             .default_font_metrics()
@@ -116,13 +188,24 @@ impl RenderMetrics {
                 ((cell_height as f64 + (metrics.descender.get() - underline_position)) / 2.)
                     as isize
             }
-            Some(d) => d
-                .evaluate_as_pixels(DimensionContext {
-                    dpi: fonts.get_dpi() as f32,
-                    pixel_max: descender_row as f32 / 2.,
-                    pixel_cell: cell_height as f32,
-                })
-                .round() as isize,
+            Some(d) => decoration_row(
+                d,
+                fonts.get_dpi() as f32,
+                descender_row as f32 / 2.,
+                cell_height as isize,
+            ),
+        };
+        // By default the overline is drawn at the top of the primary
+        // font's cell, which is below the top of our cell when the
+        // line_height has been increased
+        let overline_row = match &config.text().overline_position {
+            None => line_height_y_adjust as isize,
+            Some(d) => decoration_row(
+                d,
+                fonts.get_dpi() as f32,
+                metrics.cell_height.get() as f32,
+                cell_height as isize,
+            ),
         };
 
         Ok(Self {
@@ -130,6 +213,7 @@ impl RenderMetrics {
             descender_row,
             descender_plus_two,
             strike_row,
+            overline_row,
             cell_size: Size::new(cell_width as isize, cell_height as isize),
             underline_height,
         })
@@ -138,33 +222,119 @@ impl RenderMetrics {
 
 pub struct UtilSprites {
     pub white_space: Sprite,
-    pub filled_box: Sprite,
+    /// The top left quarter of a filled circle; see `rounded_corner_image`
+    pub rounded_corner: Sprite,
 }
 
 impl UtilSprites {
+    /// Allocates the utility sprites, which must be the first sprites
+    /// allocated in a fresh atlas.  The atlas allocator is deterministic,
+    /// so they then occupy the same reserved region, at the same
+    /// coordinates, each time an atlas of the same size is rebuilt for
+    /// the same metrics.
     pub fn new(
         glyph_cache: &mut GlyphCache,
         metrics: &RenderMetrics,
     ) -> Result<Self, OutOfTextureSpace> {
+        debug_assert!(
+            glyph_cache.atlas.is_empty(),
+            "utility sprites must be allocated before any other sprite"
+        );
         let mut buffer = Image::new(
             metrics.cell_size.width as usize,
             metrics.cell_size.height as usize,
         );
 
         let black = SrgbaPixel::rgba(0, 0, 0, 0);
-        let white = SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff);
 
         let cell_rect = Rect::new(Point::new(0, 0), metrics.cell_size);
 
-        buffer.clear_rect(cell_rect, white);
-        let filled_box = glyph_cache.atlas.allocate(&buffer)?;
-
         buffer.clear_rect(cell_rect, black);
         let white_space = glyph_cache.atlas.allocate(&buffer)?;
 
+        // This is held outside of the glyph cache, so it must
+        // survive any eviction
+        glyph_cache.atlas.pin(&white_space);
+
+        let rounded_corner = glyph_cache
+            .atlas
+            .allocate(&rounded_corner_image(metrics.cell_size.height as usize / 2))?;
+        glyph_cache.atlas.pin(&rounded_corner);
+
         Ok(Self {
             white_space,
-            filled_box,
+            rounded_corner,
         })
     }
 }
+
+/// Draws the top left quarter of a circle of radius `size`, centered
+/// on the bottom right corner of the image, for sampling by the corners
+/// of a `RenderCommand::FillRoundedRect`.  The alpha of each pixel is
+/// its coverage, estimated from the signed distance of the center of
+/// the pixel to the edge of the circle, so that the edge is smooth.
+/// The atlas is sampled without filtering, so the size is chosen to be
+/// close to that of the corners of the UI chrome, which are typically
+/// half a cell.
+fn rounded_corner_image(size: usize) -> Image {
+    let size = size.max(4);
+    let radius = size as f32;
+    let mut image = Image::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let dx = radius - (x as f32 + 0.5);
+            let dy = radius - (y as f32 + 0.5);
+            let distance = (dx * dx + dy * dy).sqrt() - radius;
+            let alpha = ((0.5 - distance).max(0.).min(1.) * 255.).round() as u8;
+            *image.pixel_mut(x, y) = SrgbaPixel::rgba(alpha, alpha, alpha, alpha).as_srgba32();
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metrics() -> RenderMetrics {
+        RenderMetrics {
+            descender: PixelLength::new(3.),
+            descender_row: 13,
+            descender_plus_two: 15,
+            underline_height: 1,
+            strike_row: 8,
+            overline_row: 0,
+            cell_size: Size::new(8, 18),
+        }
+    }
+
+    #[test]
+    fn rebuilds_reuse_util_sprite_coords() {
+        config::use_test_configuration();
+        let config = config::configuration();
+        let fonts = Rc::new(
+            FontConfiguration::new(
+                None,
+                config
+                    .font_config()
+                    .dpi
+                    .unwrap_or_else(|| ::window::default_dpi()) as usize,
+            )
+            .unwrap(),
+        );
+        let metrics = metrics();
+
+        let rebuild = || {
+            let mut glyph_cache = GlyphCache::new_in_memory(&fonts, 256).unwrap();
+            let sprites = UtilSprites::new(&mut glyph_cache, &metrics).unwrap();
+            // Sprites allocated after the util sprites don't disturb them
+            glyph_cache.atlas.allocate(&Image::new(30, 40)).unwrap();
+            sprites
+        };
+        let first = rebuild();
+        let second = rebuild();
+        assert_eq!(first.white_space.coords, second.white_space.coords);
+        assert_eq!(first.rounded_corner.coords, second.rounded_corner.coords);
+        assert_ne!(first.white_space.coords, first.rounded_corner.coords);
+    }
+}
@@ -1,16 +1,19 @@
-use std::collections::hash_map::DefaultHasher;
+use phaedra_stable_hash::stable_hash;
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::sync::Arc;
 
+pub use phaedra_stable_hash::HASH_VERSION;
+
+/// Hashes are computed with a stable hasher so that `hash_value`
+/// is consistent across process runs
 fn compute_hash<T>(value: &T) -> u64
 where
     T: Hash + ?Sized,
 {
-    let mut hasher = DefaultHasher::new();
-    value.hash(&mut hasher);
-    hasher.finish()
+    stable_hash(value)
 }
 
 #[derive(Clone)]
@@ -23,6 +26,32 @@ impl<T: Hash + Eq> Hc<T> {
     pub fn hash_value(&self) -> u64 {
         self.hash
     }
+
+    /// Returns a key that identifies this handle by the address of the
+    /// value it points to, rather than by the value itself.
+    /// See `HcKey` for the conditions under which that is meaningful.
+    pub fn ptr_key(&self) -> HcKey {
+        HcKey {
+            addr: Arc::as_ptr(&self.value) as *const () as usize,
+        }
+    }
+}
+
+/// An opaque, cheaply copied and compared key derived from an `Hc`.
+///
+/// Two keys are equal only if they were obtained from handles that
+/// share the same allocation.  Within a single `InternTable` equal
+/// values always share an allocation, so keys taken from handles
+/// returned by that table compare just as the values do, without
+/// touching the values.
+///
+/// Keys from different tables must not be compared: equal values that
+/// were interned in different tables have different keys.  Since the
+/// table holds a reference to every value it has interned, a key stays
+/// unique for as long as the table that produced it is alive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HcKey {
+    addr: usize,
 }
 
 impl<T: Hash + Eq + std::fmt::Debug> std::fmt::Debug for Hc<T> {
@@ -62,30 +91,126 @@ impl<T: Hash + Eq> Deref for Hc<T> {
     }
 }
 
-#[derive(Default)]
+/// Holds a single shared copy of each distinct value interned in it.
+///
+/// As with `HashMap`, values may be looked up by a borrowed form `Q`
+/// of `T`, such as `str` for `String`, so that a value which is
+/// already present can be found without constructing an owned copy.
+/// Doing so requires that the borrowed and owned forms of a value
+/// hash identically, ie: that `Hash` for `Q` feeds the hasher exactly
+/// what `Hash` for `T` does, as it does for `String` and `str`.
+/// Otherwise a borrowed lookup won't find the owned value.
 pub struct InternTable<T: Hash + Eq> {
     entries: HashMap<u64, Vec<Arc<T>>>,
+    len: usize,
 }
 
-impl<T: Hash + Eq> InternTable<T> {
-    pub fn intern(&mut self, value: T) -> Hc<T> {
-        let hash = compute_hash(&value);
-        if let Some(bucket) = self.entries.get(&hash) {
-            if let Some(existing) = bucket.iter().find(|existing| existing.as_ref() == &value) {
-                return Hc {
-                    value: Arc::clone(existing),
-                    hash,
-                };
-            }
+// Not derived, as that would require `T: Default`
+impl<T: Hash + Eq> Default for InternTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            len: 0,
         }
+    }
+}
 
+impl<T: Hash + Eq> InternTable<T> {
+    fn lookup<Q>(&self, hash: u64, value: &Q) -> Option<Hc<T>>
+    where
+        T: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let bucket = self.entries.get(&hash)?;
+        let existing = bucket
+            .iter()
+            .find(|existing| <T as Borrow<Q>>::borrow(existing) == value)?;
+        Some(Hc {
+            value: Arc::clone(existing),
+            hash,
+        })
+    }
+
+    fn insert(&mut self, hash: u64, value: T) -> Hc<T> {
         let value = Arc::new(value);
         self.entries
             .entry(hash)
             .or_default()
             .push(Arc::clone(&value));
+        self.len += 1;
         Hc { value, hash }
     }
+
+    pub fn intern(&mut self, value: T) -> Hc<T> {
+        let hash = compute_hash(&value);
+        if let Some(existing) = self.lookup(hash, &value) {
+            return existing;
+        }
+        self.insert(hash, value)
+    }
+
+    /// Returns the handle for the value equal to `value`, if the table
+    /// holds one.  `value` may be any borrowed form of `T` that hashes
+    /// identically to it; see `InternTable`.
+    pub fn get<Q>(&self, value: &Q) -> Option<Hc<T>>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.lookup(compute_hash(value), value)
+    }
+
+    /// Like `intern`, but takes a borrowed form of `T`, which is only
+    /// converted to an owned `T` if the table doesn't already hold an
+    /// equal value.  `value` must hash identically to the owned value;
+    /// see `InternTable`.
+    pub fn intern_ref<Q>(&mut self, value: &Q) -> Hc<T>
+    where
+        T: Borrow<Q> + for<'a> From<&'a Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = compute_hash(value);
+        if let Some(existing) = self.lookup(hash, value) {
+            return existing;
+        }
+        let value = T::from(value);
+        debug_assert_eq!(
+            compute_hash(&value),
+            hash,
+            "the borrowed form of a value must hash identically to the owned value"
+        );
+        self.insert(hash, value)
+    }
+
+    /// Returns the handle that this table holds for the value of `hc`,
+    /// adopting `hc` as that handle if the table has no equal value.
+    /// `hc` may have come from another table; the result is canonical
+    /// for this one, so its `ptr_key` may be compared with the keys of
+    /// any other handle returned by this table.
+    pub fn intern_hc(&mut self, hc: &Hc<T>) -> Hc<T> {
+        if let Some(existing) = self.lookup(hc.hash, &hc.value) {
+            return existing;
+        }
+
+        self.entries
+            .entry(hc.hash)
+            .or_default()
+            .push(Arc::clone(&hc.value));
+        self.len += 1;
+        Hc {
+            value: Arc::clone(&hc.value),
+            hash: hc.hash,
+        }
+    }
+
+    /// Returns the number of distinct values held by the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 }
 
 #[derive(Clone)]
@@ -137,31 +262,372 @@ impl<T: Hash + Eq> Deref for HcSlice<T> {
     }
 }
 
-#[derive(Default)]
 pub struct SliceInternTable<T: Hash + Eq> {
     entries: HashMap<u64, Vec<Arc<[T]>>>,
+    len: usize,
 }
 
-impl<T: Hash + Eq> SliceInternTable<T> {
-    pub fn intern_slice(&mut self, values: Vec<T>) -> HcSlice<T> {
-        let hash = compute_hash(values.as_slice());
-        if let Some(bucket) = self.entries.get(&hash) {
-            if let Some(existing) = bucket
-                .iter()
-                .find(|existing| existing.as_ref() == values.as_slice())
-            {
-                return HcSlice {
-                    values: Arc::clone(existing),
-                    hash,
-                };
-            }
+impl<T: Hash + Eq> Default for SliceInternTable<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            len: 0,
         }
+    }
+}
+
+impl<T: Hash + Eq> SliceInternTable<T> {
+    fn lookup(&self, hash: u64, values: &[T]) -> Option<HcSlice<T>> {
+        let bucket = self.entries.get(&hash)?;
+        let existing = bucket.iter().find(|existing| existing.as_ref() == values)?;
+        Some(HcSlice {
+            values: Arc::clone(existing),
+            hash,
+        })
+    }
 
-        let values: Arc<[T]> = values.into();
+    fn insert(&mut self, hash: u64, values: Arc<[T]>) -> HcSlice<T> {
         self.entries
             .entry(hash)
             .or_default()
             .push(Arc::clone(&values));
+        self.len += 1;
         HcSlice { values, hash }
     }
+
+    pub fn intern_slice(&mut self, values: Vec<T>) -> HcSlice<T> {
+        // A Vec hashes as its slice does, so this is the same hash
+        // that `get_slice` and `intern_slice_ref` compute
+        let hash = compute_hash(values.as_slice());
+        if let Some(existing) = self.lookup(hash, &values) {
+            return existing;
+        }
+        self.insert(hash, values.into())
+    }
+
+    /// Returns the handle for the slice equal to `values`, if the table
+    /// holds one
+    pub fn get_slice(&self, values: &[T]) -> Option<HcSlice<T>> {
+        self.lookup(compute_hash(values), values)
+    }
+
+    /// Like `intern_slice`, but takes the values by reference, and only
+    /// copies them if the table doesn't already hold an equal slice
+    pub fn intern_slice_ref(&mut self, values: &[T]) -> HcSlice<T>
+    where
+        T: Clone,
+    {
+        let hash = compute_hash(values);
+        if let Some(existing) = self.lookup(hash, values) {
+            return existing;
+        }
+        self.insert(hash, values.into())
+    }
+
+    /// Experimental: splits `values` into chunks of `chunk` values, the
+    /// last of which may be shorter, and interns each of them separately.
+    /// Sequences that differ only in a few places then share the chunks
+    /// that they have in common, rather than each holding a whole copy.
+    /// `ChunkedSlice` presents the result as a single sequence.
+    pub fn intern_chunked(&mut self, values: Vec<T>, chunk: usize) -> Vec<HcSlice<T>> {
+        assert!(chunk > 0, "chunk size must be greater than zero");
+        let mut chunks = Vec::with_capacity(values.len().div_ceil(chunk));
+        let mut values = values.into_iter().peekable();
+        while values.peek().is_some() {
+            chunks.push(self.intern_slice(values.by_ref().take(chunk).collect()));
+        }
+        chunks
+    }
+
+    /// Returns the number of distinct slices held by the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the total number of values in the distinct slices held
+    /// by the table
+    pub fn value_count(&self) -> usize {
+        self.entries
+            .values()
+            .flat_map(|bucket| bucket.iter())
+            .map(|values| values.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Release the slices that are no longer referenced by any `HcSlice`
+    /// outside of the table.  A value interned after it has been purged
+    /// is newly allocated, so it won't share storage with any handle
+    /// that was dropped.
+    pub fn purge_unused(&mut self) {
+        self.entries.retain(|_, bucket| {
+            bucket.retain(|values| Arc::strong_count(values) > 1);
+            !bucket.is_empty()
+        });
+        self.len = self.entries.values().map(|bucket| bucket.len()).sum();
+    }
+}
+
+/// A sequence made of the chunks returned by
+/// `SliceInternTable::intern_chunked`.  The values are not copied into
+/// contiguous storage, so rather than dereferencing to a slice, this
+/// provides access to them by index and by iteration.
+#[derive(Clone)]
+pub struct ChunkedSlice<T: Hash + Eq> {
+    chunks: Vec<HcSlice<T>>,
+    len: usize,
+}
+
+impl<T: Hash + Eq> ChunkedSlice<T> {
+    pub fn new(chunks: Vec<HcSlice<T>>) -> Self {
+        let len = chunks.iter().map(|chunk| chunk.len()).sum();
+        Self { chunks, len }
+    }
+
+    /// Returns the total number of values in all of the chunks
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn chunks(&self) -> &[HcSlice<T>] {
+        &self.chunks
+    }
+
+    pub fn get(&self, mut index: usize) -> Option<&T> {
+        for chunk in &self.chunks {
+            if index < chunk.len() {
+                return Some(&chunk[index]);
+            }
+            index -= chunk.len();
+        }
+        None
+    }
+
+    /// Iterates the values of the chunks in order
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len);
+        for chunk in &self.chunks {
+            values.extend_from_slice(chunk);
+        }
+        values
+    }
+}
+
+impl<T: Hash + Eq> From<Vec<HcSlice<T>>> for ChunkedSlice<T> {
+    fn from(chunks: Vec<HcSlice<T>>) -> Self {
+        Self::new(chunks)
+    }
+}
+
+impl<T: Hash + Eq + std::fmt::Debug> std::fmt::Debug for ChunkedSlice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Hash + Eq> PartialEq for ChunkedSlice<T> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        if self.chunks == other.chunks {
+            return true;
+        }
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Hash + Eq> Eq for ChunkedSlice<T> {}
+
+impl<T: Hash + Eq> PartialEq<[T]> for ChunkedSlice<T> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.len == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hash_values_are_stable() {
+        let mut table = InternTable::default();
+        assert_eq!(
+            table.intern("hello".to_string()).hash_value(),
+            0xa9bd73cca220c59c
+        );
+
+        let mut slices = SliceInternTable::default();
+        let hc = slices.intern_slice(vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(hc.hash_value(), 0xafe6dd348dfb7270);
+    }
+
+    #[test]
+    fn borrowed_lookup_matches_owned_values() {
+        let mut table: InternTable<String> = InternTable::default();
+        assert_eq!(table.get("hello"), None);
+
+        // A miss constructs the owned value
+        let hello = table.intern_ref("hello");
+        assert_eq!(hello.as_str(), "hello");
+        assert_eq!(table.len(), 1);
+
+        // Borrowed and owned forms find the same value
+        assert_eq!(table.get("hello").unwrap().ptr_key(), hello.ptr_key());
+        assert_eq!(table.intern_ref("hello").ptr_key(), hello.ptr_key());
+        assert_eq!(table.intern("hello".to_string()).ptr_key(), hello.ptr_key());
+        assert_eq!(table.get("hello").unwrap().hash_value(), hello.hash_value());
+        assert_eq!(table.len(), 1);
+
+        let world = table.intern("world".to_string());
+        assert_eq!(table.get("world").unwrap().ptr_key(), world.ptr_key());
+        assert_eq!(table.get("other"), None);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn borrowed_slice_lookup_matches_owned_slices() {
+        let mut slices: SliceInternTable<String> = SliceInternTable::default();
+        let values = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(slices.get_slice(&values), None);
+
+        // A miss copies the values
+        let hc = slices.intern_slice_ref(&values);
+        assert_eq!(&*hc, values.as_slice());
+        assert_eq!(hc.hash_value(), 0xafe6dd348dfb7270);
+        assert_eq!(slices.len(), 1);
+
+        let found = slices.get_slice(&values).unwrap();
+        assert!(Arc::ptr_eq(&found.values, &hc.values));
+        let found = slices.intern_slice_ref(&values);
+        assert!(Arc::ptr_eq(&found.values, &hc.values));
+        let owned = slices.intern_slice(values.clone());
+        assert!(Arc::ptr_eq(&owned.values, &hc.values));
+        assert_eq!(slices.len(), 1);
+
+        assert_eq!(slices.get_slice(&values[..1]), None);
+        assert_eq!(slices.get_slice(&[]), None);
+        let empty = slices.intern_slice(vec![]);
+        assert!(Arc::ptr_eq(
+            &slices.get_slice(&[]).unwrap().values,
+            &empty.values
+        ));
+        assert_eq!(slices.len(), 2);
+    }
+
+    #[test]
+    fn unused_slices_are_purged() {
+        let mut slices = SliceInternTable::default();
+        let kept = slices.intern_slice(vec![1u32, 2]);
+        let dropped = slices.intern_slice(vec![3u32]);
+        let shared = slices.intern_slice(vec![1u32, 2]);
+        assert_eq!(slices.len(), 2);
+
+        drop(dropped);
+        slices.purge_unused();
+        assert_eq!(slices.len(), 1);
+        assert!(Arc::ptr_eq(&kept.values, &shared.values));
+
+        drop(kept);
+        slices.purge_unused();
+        assert_eq!(slices.len(), 1);
+        drop(shared);
+        slices.purge_unused();
+        assert!(slices.is_empty());
+    }
+
+    #[test]
+    fn chunked_view_equals_input() {
+        let mut slices = SliceInternTable::default();
+        for len in 0..12u32 {
+            let values: Vec<u32> = (0..len).collect();
+            for chunk in 1..6 {
+                let view = ChunkedSlice::new(slices.intern_chunked(values.clone(), chunk));
+                assert_eq!(view.len(), values.len());
+                assert!(view == *values.as_slice(), "{:?} != {:?}", view, values);
+                assert_eq!(view.to_vec(), values);
+                for (index, value) in values.iter().enumerate() {
+                    assert_eq!(view.get(index), Some(value));
+                }
+                assert_eq!(view.get(values.len()), None);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_are_shared() {
+        let mut slices = SliceInternTable::default();
+        let first = slices.intern_chunked(vec![1u32, 2, 3, 4, 5, 6, 7], 3);
+        let second = slices.intern_chunked(vec![1u32, 2, 3, 4, 5, 6, 8], 3);
+        assert_eq!(first.len(), 3);
+        assert!(Arc::ptr_eq(&first[0].values, &second[0].values));
+        assert!(Arc::ptr_eq(&first[1].values, &second[1].values));
+        assert_ne!(first[2], second[2]);
+        assert_eq!(slices.len(), 4);
+        assert_eq!(slices.value_count(), 8);
+
+        let first = ChunkedSlice::new(first);
+        let second = ChunkedSlice::new(second);
+        assert_ne!(first, second);
+        let third = ChunkedSlice::new(slices.intern_chunked(vec![1u32, 2, 3, 4, 5, 6, 7], 3));
+        assert_eq!(first, third);
+        assert_eq!(slices.len(), 4);
+    }
+
+    #[test]
+    fn ptr_keys_within_a_table() {
+        let mut table = InternTable::default();
+        let a = table.intern("a".to_string());
+        let a2 = table.intern("a".to_string());
+        let b = table.intern("b".to_string());
+        assert_eq!(a.ptr_key(), a2.ptr_key());
+        assert_ne!(a.ptr_key(), b.ptr_key());
+        assert_eq!(table.len(), 2);
+
+        let mut map = HashMap::new();
+        map.insert(a.ptr_key(), 1);
+        map.insert(b.ptr_key(), 2);
+        assert_eq!(map.get(&a2.ptr_key()), Some(&1));
+    }
+
+    #[test]
+    fn ptr_keys_differ_across_tables() {
+        let mut first = InternTable::default();
+        let mut second = InternTable::default();
+        let a = first.intern("a".to_string());
+        let other_a = second.intern("a".to_string());
+
+        // The values are equal, but the keys are not comparable
+        assert_eq!(a, other_a);
+        assert_ne!(a.ptr_key(), other_a.ptr_key());
+
+        // Canonicalizing through the first table makes them agree
+        assert_eq!(first.intern_hc(&other_a).ptr_key(), a.ptr_key());
+    }
+
+    #[test]
+    fn intern_hc_adopts_unknown_values() {
+        let mut first = InternTable::default();
+        let mut second = InternTable::default();
+        let b = first.intern("b".to_string());
+
+        let adopted = second.intern_hc(&b);
+        assert_eq!(adopted.ptr_key(), b.ptr_key());
+        assert_eq!(second.intern("b".to_string()).ptr_key(), b.ptr_key());
+        assert_eq!(second.intern_hc(&b).ptr_key(), b.ptr_key());
+    }
 }
@@ -0,0 +1,94 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use phaedra_hit_index::{linear_hit_test, HitIndex, HitRect};
+
+/// A window with `panes` panes side by side, each with a scroll bar,
+/// split dividers between them, and a tab bar with `tabs` tabs, each
+/// with a close button
+fn window_rects(panes: usize, tabs: usize) -> Vec<HitRect> {
+    let width = 2560;
+    let height = 1440;
+    let pane_width = width / panes;
+    let mut rects = vec![];
+    for pane in 0..panes {
+        let x = pane * pane_width;
+        let scroll_x = x + pane_width - 16;
+        rects.push(HitRect {
+            x: scroll_x,
+            y: 40,
+            width: 16,
+            height: 600,
+        });
+        rects.push(HitRect {
+            x: scroll_x,
+            y: 640,
+            width: 16,
+            height: 100,
+        });
+        rects.push(HitRect {
+            x: scroll_x,
+            y: 740,
+            width: 16,
+            height: height - 740,
+        });
+        if pane > 0 {
+            rects.push(HitRect {
+                x: x - 2,
+                y: 40,
+                width: 4,
+                height: height - 40,
+            });
+        }
+    }
+    let tab_width = width / tabs;
+    for tab in 0..tabs {
+        let x = tab * tab_width;
+        rects.push(HitRect {
+            x,
+            y: 0,
+            width: tab_width,
+            height: 40,
+        });
+        rects.push(HitRect {
+            x: x + tab_width - 20,
+            y: 10,
+            width: 16,
+            height: 16,
+        });
+    }
+    rects
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let points: Vec<(isize, isize)> = (0..64)
+        .map(|i| ((i * 397) % 2560, (i * 151) % 1440))
+        .collect();
+
+    for (panes, tabs) in [(2, 5), (16, 40), (64, 120)] {
+        let rects = window_rects(panes, tabs);
+        let name = format!("{}_rects", rects.len());
+
+        let mut group = c.benchmark_group("hit_test");
+        group.bench_with_input(BenchmarkId::new("linear", &name), &rects, |b, rects| {
+            b.iter(|| {
+                for &(x, y) in &points {
+                    black_box(linear_hit_test(rects, x, y));
+                }
+            })
+        });
+        let index = HitIndex::new(rects.clone());
+        group.bench_with_input(BenchmarkId::new("index", &name), &index, |b, index| {
+            b.iter(|| {
+                for &(x, y) in &points {
+                    black_box(index.hit_test(x, y));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("build", &name), &rects, |b, rects| {
+            b.iter(|| black_box(HitIndex::new(rects.clone())))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,253 @@
+//! A spatial index over the rectangles that make up the interactive
+//! parts of a window, such as tabs, close buttons, split dividers and
+//! scroll bars, so that finding the one under the mouse doesn't mean
+//! checking all of them.
+//!
+//! The index is a grid of square cells, each listing the rectangles
+//! that overlap it in the order in which they were added.  A hit test
+//! only checks the rectangles of the cell under the point, together
+//! with any rectangles that were too large to be worth placing in
+//! each of the cells that they cover.
+
+/// The size, in pixels, of a side of a grid cell
+const CELL_SIZE: usize = 64;
+
+/// Rectangles overlapping more cells than this are checked by every
+/// hit test rather than being placed in each of those cells
+const MAX_CELLS_PER_RECT: usize = 64;
+
+/// Rectangles extending beyond this many pixels are also checked by
+/// every hit test, so that a stray huge rectangle can't make the grid
+/// itself huge
+const MAX_EXTENT: usize = 1 << 14;
+
+/// A rectangle that includes both its left and right edges, and both
+/// its top and bottom edges, so that it is `width + 1` pixels wide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HitRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl HitRect {
+    pub fn contains(&self, x: isize, y: isize) -> bool {
+        x >= self.x as isize
+            && x <= self.x.saturating_add(self.width) as isize
+            && y >= self.y as isize
+            && y <= self.y.saturating_add(self.height) as isize
+    }
+
+    fn right(&self) -> usize {
+        self.x.saturating_add(self.width)
+    }
+
+    fn bottom(&self) -> usize {
+        self.y.saturating_add(self.height)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct HitIndex {
+    rects: Vec<HitRect>,
+    columns: usize,
+    rows: usize,
+    /// The rects overlapping each cell, in row major order, each
+    /// listed in ascending order
+    cells: Vec<Vec<u32>>,
+    /// The rects that aren't in `cells`, in ascending order
+    large: Vec<u32>,
+}
+
+impl HitIndex {
+    pub fn new(rects: Vec<HitRect>) -> Self {
+        let is_large = |rect: &HitRect| {
+            if rect.right() >= MAX_EXTENT || rect.bottom() >= MAX_EXTENT {
+                return true;
+            }
+            let columns = rect.right() / CELL_SIZE - rect.x / CELL_SIZE + 1;
+            let rows = rect.bottom() / CELL_SIZE - rect.y / CELL_SIZE + 1;
+            columns * rows > MAX_CELLS_PER_RECT
+        };
+
+        let mut columns = 0;
+        let mut rows = 0;
+        for rect in rects.iter().filter(|rect| !is_large(rect)) {
+            columns = columns.max(rect.right() / CELL_SIZE + 1);
+            rows = rows.max(rect.bottom() / CELL_SIZE + 1);
+        }
+
+        let mut cells = vec![vec![]; columns * rows];
+        let mut large = vec![];
+        for (idx, rect) in rects.iter().enumerate() {
+            let idx = idx as u32;
+            if is_large(rect) {
+                large.push(idx);
+                continue;
+            }
+            for row in rect.y / CELL_SIZE..=rect.bottom() / CELL_SIZE {
+                for col in rect.x / CELL_SIZE..=rect.right() / CELL_SIZE {
+                    cells[row * columns + col].push(idx);
+                }
+            }
+        }
+
+        Self {
+            rects,
+            columns,
+            rows,
+            cells,
+            large,
+        }
+    }
+
+    pub fn rects(&self) -> &[HitRect] {
+        &self.rects
+    }
+
+    /// Returns the index of the last of the rects that contains the
+    /// point, so that a rect added later takes priority over those
+    /// that it overlaps, just as it would if the rects were scanned
+    /// from last to first
+    pub fn hit_test(&self, x: isize, y: isize) -> Option<usize> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let last_hit = |candidates: &[u32]| {
+            candidates
+                .iter()
+                .rev()
+                .copied()
+                .find(|&idx| self.rects[idx as usize].contains(x, y))
+        };
+
+        let col = x as usize / CELL_SIZE;
+        let row = y as usize / CELL_SIZE;
+        let cell_hit = if col < self.columns && row < self.rows {
+            last_hit(&self.cells[row * self.columns + col])
+        } else {
+            None
+        };
+        let large_hit = last_hit(&self.large);
+
+        cell_hit.max(large_hit).map(|idx| idx as usize)
+    }
+}
+
+/// Finds the same rect as `HitIndex::hit_test`, by checking each of
+/// `rects` in turn
+pub fn linear_hit_test(rects: &[HitRect], x: isize, y: isize) -> Option<usize> {
+    rects.iter().rposition(|rect| rect.contains(x, y))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rect(x: usize, y: usize, width: usize, height: usize) -> HitRect {
+        HitRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn later_rects_win() {
+        // A scroll bar, with the thumb overlapping the region above it
+        let index = HitIndex::new(vec![
+            rect(1000, 0, 20, 300),
+            rect(1000, 100, 20, 50),
+            rect(1000, 140, 20, 160),
+        ]);
+        assert_eq!(index.hit_test(1010, 50), Some(0));
+        assert_eq!(index.hit_test(1010, 120), Some(1));
+        assert_eq!(index.hit_test(1010, 145), Some(2));
+        assert_eq!(index.hit_test(1010, 301), None);
+        assert_eq!(index.hit_test(999, 120), None);
+    }
+
+    #[test]
+    fn edges_are_inclusive() {
+        let index = HitIndex::new(vec![rect(64, 64, 64, 64)]);
+        for (x, y) in [(64, 64), (128, 64), (64, 128), (128, 128)] {
+            assert_eq!(index.hit_test(x, y), Some(0), "{x},{y}");
+        }
+        for (x, y) in [(63, 64), (129, 64), (64, 63), (64, 129), (-1, -1)] {
+            assert_eq!(index.hit_test(x, y), None, "{x},{y}");
+        }
+    }
+
+    #[test]
+    fn large_rects_keep_their_priority() {
+        // The first and last rects cover the whole window, so end up
+        // outside of the grid
+        let index = HitIndex::new(vec![
+            rect(0, 0, 2000, 1000),
+            rect(10, 10, 10, 10),
+            rect(0, 0, 2000, 1000),
+            rect(30, 10, 10, 10),
+            rect(usize::MAX - 5, 0, 10, 10),
+        ]);
+        assert_eq!(index.large, vec![0, 2, 4]);
+        assert_eq!(index.hit_test(15, 15), Some(2));
+        assert_eq!(index.hit_test(35, 15), Some(3));
+        assert_eq!(index.hit_test(500, 500), Some(2));
+    }
+
+    #[test]
+    fn empty() {
+        let index = HitIndex::new(vec![]);
+        assert_eq!(index.hit_test(0, 0), None);
+    }
+
+    /// Items shaped like those of a real window: rows of tabs, tall
+    /// thin scroll bars and dividers, and the occasional large region
+    fn random_rects(rng: &mut fastrand::Rng, count: usize) -> Vec<HitRect> {
+        (0..count)
+            .map(|_| match rng.u8(0..4) {
+                0 => rect(rng.usize(0..2000), rng.usize(0..40), rng.usize(0..200), 30),
+                1 => rect(
+                    rng.usize(0..2000),
+                    rng.usize(0..1200),
+                    15,
+                    rng.usize(0..1200),
+                ),
+                2 => rect(
+                    rng.usize(0..2000),
+                    rng.usize(0..1200),
+                    rng.usize(0..2000),
+                    rng.usize(0..1200),
+                ),
+                _ => rect(
+                    rng.usize(0..2000),
+                    rng.usize(0..1200),
+                    rng.usize(0..40),
+                    rng.usize(0..40),
+                ),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_linear_scan() {
+        let mut rng = fastrand::Rng::with_seed(0x5eed);
+        for count in [1, 5, 50, 500] {
+            for _ in 0..20 {
+                let rects = random_rects(&mut rng, count);
+                let index = HitIndex::new(rects.clone());
+                for _ in 0..500 {
+                    let x = rng.isize(-10..2500);
+                    let y = rng.isize(-10..1500);
+                    assert_eq!(
+                        index.hit_test(x, y),
+                        linear_hit_test(&rects, x, y),
+                        "{x},{y} in {rects:?}"
+                    );
+                }
+            }
+        }
+    }
+}
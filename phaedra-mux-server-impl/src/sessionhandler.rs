@@ -177,7 +177,7 @@ fn maybe_push_pane_changes(
                 sender.send(DecodedPdu {
                     pdu: Pdu::SetPalette(SetPalette {
                         pane_id: pane.pane_id(),
-                        palette: pane.palette(),
+                        palette: (*pane.palette()).clone(),
                     }),
                     serial: 0,
                 })?;
@@ -201,6 +201,9 @@ pub struct SessionHandler {
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
     client_id: Option<Arc<ClientId>>,
     proxy_client_id: Option<ClientId>,
+    /// Set when the client asked to attach read-only; requests
+    /// that would change the mux are rejected
+    read_only: bool,
 }
 
 impl Drop for SessionHandler {
@@ -219,6 +222,7 @@ impl SessionHandler {
             per_pane: HashMap::new(),
             client_id: None,
             proxy_client_id: None,
+            read_only: false,
         }
     }
 
@@ -249,6 +253,23 @@ impl SessionHandler {
         let sender = self.to_write_tx.clone();
         let serial = decoded.serial;
 
+        if self.read_only && decoded.pdu.access() == PduAccess::Write {
+            log::trace!(
+                "{} rejecting {} from read-only client",
+                serial,
+                decoded.pdu.pdu_name()
+            );
+            sender
+                .send(DecodedPdu {
+                    pdu: Pdu::PermissionDenied(PermissionDenied {
+                        pdu_name: decoded.pdu.pdu_name().to_string(),
+                    }),
+                    serial,
+                })
+                .ok();
+            return;
+        }
+
         if let Some(client_id) = &self.client_id {
             if decoded.pdu.is_user_input() {
                 Mux::get().client_had_input(client_id);
@@ -298,6 +319,7 @@ impl SessionHandler {
             Pdu::SetClientId(SetClientId {
                 mut client_id,
                 is_proxy,
+                read_only,
             }) => {
                 if is_proxy {
                     if self.proxy_client_id.is_none() {
@@ -318,6 +340,7 @@ impl SessionHandler {
                             format!("{} (via proxy pid {})", client_id.hostname, proxy_id.pid);
                     }
 
+                    self.read_only = read_only;
                     let client_id = Arc::new(client_id);
                     self.client_id.replace(client_id.clone());
                     spawn_into_main_thread(async move {
@@ -811,6 +834,30 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::CapturePaneRegion(CapturePaneRegion {
+                pane_id,
+                range,
+                include_styles,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let region = pane.capture_region(range, include_styles);
+                            Ok(Pdu::CapturePaneRegionResponse(CapturePaneRegionResponse {
+                                pane_id,
+                                region,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetImageCell(GetImageCell {
                 pane_id,
                 line_idx,
@@ -1010,6 +1057,8 @@ impl SessionHandler {
             | Pdu::MovePaneToNewTabResponse { .. }
             | Pdu::TabAddedToWindow { .. }
             | Pdu::GetPaneRenderableDimensionsResponse { .. }
+            | Pdu::PermissionDenied { .. }
+            | Pdu::CapturePaneRegionResponse { .. }
             | Pdu::ErrorResponse { .. } => {
                 send_response(Err(anyhow!("expected a request, got {:?}", decoded.pdu)))
             }
@@ -44,6 +44,16 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Override specific configuration values with JSON, which is
+    /// merged into the configuration without being evaluated as lua.
+    /// The name may be a dotted path, such as `font_config.font_size`
+    #[arg(
+        long = "config-json",
+        name = "name=json",
+        value_parser=clap::builder::ValueParser::new(name_equals_value),
+        number_of_values = 1)]
+    config_json_override: Vec<(String, String)>,
+
     /// Detach from the foreground and become a background process
     #[arg(long = "daemonize")]
     daemonize: bool,
@@ -94,6 +104,7 @@ fn run() -> anyhow::Result<()> {
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
+        &opts.config_json_override,
         opts.skip_config,
     )?;
 
@@ -147,6 +158,10 @@ fn run() -> anyhow::Result<()> {
             cmd.arg("--config");
             cmd.arg(&format!("{name}={value}"));
         }
+        for (name, value) in &opts.config_json_override {
+            cmd.arg("--config-json");
+            cmd.arg(&format!("{name}={value}"));
+        }
         if let Some(cwd) = opts.cwd {
             cmd.arg("--cwd");
             cmd.arg(cwd);
@@ -0,0 +1,23 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use phaedra_render_bench::{Stage, WORKLOADS};
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let frames: Vec<_> = WORKLOADS
+        .iter()
+        .map(|workload| (workload.name, workload.build_frame()))
+        .collect();
+
+    for stage in Stage::ALL.iter().copied() {
+        let mut group = c.benchmark_group(stage.name());
+        for (name, frame) in &frames {
+            group.throughput(Throughput::Elements(frame.command_count() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(name), frame, |b, frame| {
+                b.iter(|| black_box(stage.run(black_box(frame))))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,357 @@
+//! Synthetic workloads for the render command pipeline, and timings of
+//! the stages that process them.
+//!
+//! The frames built here are shaped like the output of describing a
+//! screen of text: for each line, a clip rect, a background for each
+//! cell and a batch of glyphs.  The `pipeline` benchmark measures each
+//! `Stage` with criterion; `phaedra-render-bench --profile-json` runs
+//! them with a fixed iteration count and prints the timings as JSON so
+//! that they can be tracked over time.
+//!
+//! Executing the commands into vertices (`execute_commands_dry_run`)
+//! is part of the phaedra-gui binary and can't be linked from here, so
+//! it isn't one of the stages.
+use phaedra_color_types::LinearRgba;
+use phaedra_hash_cons::{InternTable, SliceInternTable};
+use phaedra_render_command::{LayerId, QuadMode, RectF, RenderCommand, TextureCoords};
+use serde::Serialize;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+const CELL_WIDTH: f32 = 8.;
+const CELL_HEIGHT: f32 = 16.;
+
+/// A window of `cols` x `rows` cells, split into a grid of panes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Workload {
+    pub name: &'static str,
+    pub cols: usize,
+    pub rows: usize,
+    pub panes_across: usize,
+    pub panes_down: usize,
+}
+
+pub const WORKLOADS: [Workload; 4] = [
+    Workload {
+        name: "80x24",
+        cols: 80,
+        rows: 24,
+        panes_across: 1,
+        panes_down: 1,
+    },
+    Workload {
+        name: "200x60",
+        cols: 200,
+        rows: 60,
+        panes_across: 1,
+        panes_down: 1,
+    },
+    Workload {
+        name: "200x60 4 panes",
+        cols: 200,
+        rows: 60,
+        panes_across: 2,
+        panes_down: 2,
+    },
+    Workload {
+        name: "200x60 16 panes",
+        cols: 200,
+        rows: 60,
+        panes_across: 4,
+        panes_down: 4,
+    },
+];
+
+/// The commands for one pane, at their final position in the window
+#[derive(Debug, Clone)]
+pub struct Pane {
+    pub bounds: RectF,
+    pub commands: Vec<RenderCommand>,
+    /// The range of `commands` that describes each line
+    pub lines: Vec<Range<usize>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub panes: Vec<Pane>,
+}
+
+impl Frame {
+    /// Returns the number of commands in the frame, not counting
+    /// the contents of batches
+    pub fn command_count(&self) -> usize {
+        self.panes.iter().map(|pane| pane.commands.len()).sum()
+    }
+}
+
+impl Workload {
+    pub fn build_frame(&self) -> Frame {
+        let cols = self.cols / self.panes_across;
+        let rows = self.rows / self.panes_down;
+        let mut panes = vec![];
+        for down in 0..self.panes_down {
+            for across in 0..self.panes_across {
+                let left = (across * cols) as f32 * CELL_WIDTH;
+                let top = (down * rows) as f32 * CELL_HEIGHT;
+                panes.push(build_pane(panes.len(), left, top, cols, rows));
+            }
+        }
+        Frame { panes }
+    }
+}
+
+/// Builds the commands for a pane full of text.  `seed` varies the
+/// content so that panes don't all describe the same lines.
+fn build_pane(seed: usize, left: f32, top: f32, cols: usize, rows: usize) -> Pane {
+    let bounds = euclid::rect(
+        left,
+        top,
+        cols as f32 * CELL_WIDTH,
+        rows as f32 * CELL_HEIGHT,
+    );
+    let mut commands = vec![];
+    let mut lines = vec![];
+    for row in 0..rows {
+        let start = commands.len();
+        let y = top + row as f32 * CELL_HEIGHT;
+        commands.push(RenderCommand::SetClipRect(Some(euclid::rect(
+            left,
+            y,
+            bounds.width(),
+            CELL_HEIGHT,
+        ))));
+
+        let mut glyphs = vec![];
+        for col in 0..cols {
+            let rect = euclid::rect(left + col as f32 * CELL_WIDTH, y, CELL_WIDTH, CELL_HEIGHT);
+            let shade = ((seed + row + col / 8) % 4) as f32 / 16.;
+            commands.push(RenderCommand::FillRect {
+                layer: LayerId::background(),
+                rect,
+                color: LinearRgba(shade, shade, shade, 1.),
+                hsv: None,
+            });
+
+            let glyph = (seed * 31 + row * 7 + col * 3) % 97;
+            if glyph % 11 != 0 {
+                let u = glyph as f32 / 97.;
+                glyphs.push(RenderCommand::DrawQuad {
+                    layer: LayerId::text(),
+                    position: rect,
+                    texture: TextureCoords {
+                        left: u,
+                        top: 0.,
+                        right: u + 0.01,
+                        bottom: 0.02,
+                    },
+                    fg_color: LinearRgba(0.9, 0.9, 0.9, 1.),
+                    alt_color: None,
+                    hsv: None,
+                    mode: QuadMode::Glyph,
+                });
+            }
+        }
+        commands.push(RenderCommand::Batch(glyphs));
+        commands.push(RenderCommand::SetClipRect(None));
+        lines.push(start..commands.len());
+    }
+
+    // The cursor, in the first row
+    commands.push(RenderCommand::FillRect {
+        layer: LayerId::overlay(),
+        rect: euclid::rect(left, top, CELL_WIDTH, CELL_HEIGHT),
+        color: LinearRgba(0.5, 0.8, 0.5, 1.),
+        hsv: None,
+    });
+
+    Pane {
+        bounds,
+        commands,
+        lines,
+    }
+}
+
+/// A pass over the render commands of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// `RenderCommand::content_hash` of each pane
+    ContentHash,
+    /// Flattening the batches of each pane
+    Flatten,
+    /// `RenderCommand::partition_runs` of each pane
+    PartitionRuns,
+    /// `clip_to_rect` of each command to its pane, less a margin of
+    /// one cell, including the cost of cloning the commands
+    ClipToRect,
+    /// Interning each command in an empty `InternTable`
+    InternCommands,
+    /// Interning each line in an empty `SliceInternTable`, including
+    /// the cost of copying the commands of the line
+    InternLines,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::ContentHash,
+        Stage::Flatten,
+        Stage::PartitionRuns,
+        Stage::ClipToRect,
+        Stage::InternCommands,
+        Stage::InternLines,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::ContentHash => "content_hash",
+            Stage::Flatten => "flatten",
+            Stage::PartitionRuns => "partition_runs",
+            Stage::ClipToRect => "clip_to_rect",
+            Stage::InternCommands => "intern_commands",
+            Stage::InternLines => "intern_lines",
+        }
+    }
+
+    /// Runs the stage over `frame`.  The result depends on the work
+    /// that was done, so that it can't be optimized away.
+    pub fn run(self, frame: &Frame) -> u64 {
+        let mut result = 0u64;
+        for pane in &frame.panes {
+            result = result.wrapping_add(match self {
+                Stage::ContentHash => RenderCommand::content_hash(&pane.commands),
+                Stage::Flatten => {
+                    phaedra_render_command::bench::flatten(&pane.commands).len() as u64
+                }
+                Stage::PartitionRuns => RenderCommand::partition_runs(&pane.commands).len() as u64,
+                Stage::ClipToRect => {
+                    let clip = pane.bounds.inflate(-CELL_WIDTH, -CELL_HEIGHT);
+                    pane.commands
+                        .iter()
+                        .cloned()
+                        .map(|cmd| cmd.clip_to_rect(&clip))
+                        .filter(|cmd| !matches!(cmd, RenderCommand::Nop))
+                        .count() as u64
+                }
+                Stage::InternCommands => {
+                    let mut table = InternTable::default();
+                    phaedra_render_command::bench::flatten(&pane.commands)
+                        .into_iter()
+                        .map(|cmd| table.intern(cmd.clone()).hash_value())
+                        .fold(0, u64::wrapping_add)
+                }
+                Stage::InternLines => {
+                    let mut table = SliceInternTable::default();
+                    for line in &pane.lines {
+                        table.intern_slice(pane.commands[line.clone()].to_vec());
+                    }
+                    table.len() as u64
+                }
+            });
+        }
+        result
+    }
+}
+
+/// The time taken by one stage for one workload
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub workload: &'static str,
+    pub stage: Stage,
+    /// The number of commands in the frame
+    pub commands: usize,
+    pub iterations: usize,
+    pub mean_ns: u64,
+    pub min_ns: u64,
+    pub max_ns: u64,
+}
+
+/// The output of `--profile-json`
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    /// Incremented when the meaning of the timings changes, so that
+    /// trend tracking can tell when results are not comparable
+    pub version: u32,
+    pub timings: Vec<StageTiming>,
+}
+
+pub const PROFILE_VERSION: u32 = 1;
+
+/// Runs each stage `iterations` times for each workload
+pub fn profile(iterations: usize) -> Profile {
+    let iterations = iterations.max(1);
+    let mut timings = vec![];
+    for workload in &WORKLOADS {
+        let frame = workload.build_frame();
+        for stage in Stage::ALL.iter().copied() {
+            let mut total = Duration::default();
+            let mut min = Duration::MAX;
+            let mut max = Duration::default();
+            for _ in 0..iterations {
+                let start = Instant::now();
+                std::hint::black_box(stage.run(std::hint::black_box(&frame)));
+                let elapsed = start.elapsed();
+                total += elapsed;
+                min = min.min(elapsed);
+                max = max.max(elapsed);
+            }
+            timings.push(StageTiming {
+                workload: workload.name,
+                stage,
+                commands: frame.command_count(),
+                iterations,
+                mean_ns: (total / iterations as u32).as_nanos() as u64,
+                min_ns: min.as_nanos() as u64,
+                max_ns: max.as_nanos() as u64,
+            });
+        }
+    }
+    Profile {
+        version: PROFILE_VERSION,
+        timings,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn panes_tile_the_window() {
+        let frame = WORKLOADS[3].build_frame();
+        assert_eq!(frame.panes.len(), 16);
+        let area: f32 = frame.panes.iter().map(|pane| pane.bounds.area()).sum();
+        assert_eq!(area, 200. * CELL_WIDTH * 60. * CELL_HEIGHT);
+        for pane in &frame.panes {
+            assert_eq!(pane.lines.len(), 15);
+            assert_eq!(pane.lines.last().unwrap().end + 1, pane.commands.len());
+        }
+    }
+
+    #[test]
+    fn stages_do_work() {
+        let frame = WORKLOADS[0].build_frame();
+        let commands = frame.command_count() as u64;
+        assert_eq!(commands, 24 * (2 + 80 + 1) + 1);
+        assert!(Stage::Flatten.run(&frame) > commands);
+        // Each clip rect ends a run, and the cursor is in a run of its own
+        assert_eq!(Stage::PartitionRuns.run(&frame), 2 * 24 + 1);
+        assert_eq!(Stage::InternLines.run(&frame), 24);
+        // The margin removes the backgrounds of the cells around the
+        // edge of the pane, the glyph batches of the first and last
+        // lines, and the cursor
+        assert_eq!(
+            Stage::ClipToRect.run(&frame),
+            commands - (2 * 80 + 2 * 22) - 2 - 1
+        );
+    }
+
+    #[test]
+    fn profile_covers_each_stage_and_workload() {
+        let profile = profile(1);
+        assert_eq!(profile.timings.len(), WORKLOADS.len() * Stage::ALL.len());
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["version"], PROFILE_VERSION);
+        assert_eq!(json["timings"][0]["workload"], "80x24");
+        assert_eq!(json["timings"][0]["stage"], "content_hash");
+    }
+}
@@ -0,0 +1,42 @@
+use clap::Parser;
+use phaedra_render_bench::profile;
+
+#[derive(Debug, Parser)]
+#[command(about = "Time the stages of the render command pipeline for synthetic frames")]
+struct Opt {
+    /// Print the timings as a single line of JSON, for tracking
+    /// trends in CI, rather than as a table
+    #[arg(long)]
+    profile_json: bool,
+
+    /// How many times to run each stage for each workload
+    #[arg(long, default_value = "50")]
+    iterations: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opt::parse();
+    let profile = profile(opts.iterations);
+
+    if opts.profile_json {
+        println!("{}", serde_json::to_string(&profile)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<16} {:<16} {:>8} {:>12} {:>12} {:>12}",
+        "workload", "stage", "commands", "mean ns", "min ns", "max ns"
+    );
+    for timing in &profile.timings {
+        println!(
+            "{:<16} {:<16} {:>8} {:>12} {:>12} {:>12}",
+            timing.workload,
+            timing.stage.name(),
+            timing.commands,
+            timing.mean_ns,
+            timing.min_ns,
+            timing.max_ns
+        );
+    }
+    Ok(())
+}
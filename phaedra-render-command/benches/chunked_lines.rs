@@ -0,0 +1,95 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use phaedra_color_types::LinearRgba;
+use phaedra_hash_cons::{ChunkedSlice, HcSlice, SliceInternTable};
+use phaedra_render_command::{LayerId, RenderCommand};
+
+const COLS: usize = 100;
+const FRAMES: usize = 1000;
+const CHUNK: usize = 16;
+
+/// The commands for the line holding the cursor in frame `frame`: a
+/// background and glyph for each cell, with the cursor at the end of
+/// the line fading in and out.  Every frame produces a different line,
+/// but only the commands for the last cell differ.
+fn describe_line(frame: usize) -> Vec<RenderCommand> {
+    let mut commands = vec![];
+    for col in 0..COLS {
+        let rect = euclid::rect(col as f32 * 8., 0., 8., 16.);
+        let bg = if col == COLS - 1 {
+            let level = frame as f32 / FRAMES as f32;
+            LinearRgba(level, level, level, 1.)
+        } else {
+            LinearRgba(0.1, 0.1, 0.1, 1.)
+        };
+        commands.push(RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect,
+            color: bg,
+            hsv: None,
+        });
+        commands.push(RenderCommand::FillRect {
+            layer: LayerId::text(),
+            rect,
+            color: LinearRgba(0.9, 0.9, 0.9, 1.),
+            hsv: None,
+        });
+    }
+    commands
+}
+
+fn intern_whole(interner: &mut SliceInternTable<RenderCommand>) -> Vec<HcSlice<RenderCommand>> {
+    (0..FRAMES)
+        .map(|frame| interner.intern_slice(describe_line(frame)))
+        .collect()
+}
+
+fn intern_chunked(
+    interner: &mut SliceInternTable<RenderCommand>,
+) -> Vec<ChunkedSlice<RenderCommand>> {
+    (0..FRAMES)
+        .map(|frame| ChunkedSlice::new(interner.intern_chunked(describe_line(frame), CHUNK)))
+        .collect()
+}
+
+/// An estimate of the memory held by the table: the values themselves,
+/// plus the reference counts at the head of each allocation
+fn table_bytes(interner: &SliceInternTable<RenderCommand>) -> usize {
+    interner.value_count() * std::mem::size_of::<RenderCommand>()
+        + interner.len() * 2 * std::mem::size_of::<usize>()
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut whole = SliceInternTable::default();
+    let whole_lines = intern_whole(&mut whole);
+    let mut chunked = SliceInternTable::default();
+    let chunked_lines = intern_chunked(&mut chunked);
+    for (whole, chunked) in whole_lines.iter().zip(&chunked_lines) {
+        assert!(*chunked == **whole);
+    }
+
+    // Only the tables are compared; the handles held for each line
+    // are small next to the commands that they share
+    println!(
+        "{} lines of {} commands: {} bytes interned whole, {} bytes in chunks of {}",
+        FRAMES,
+        COLS * 2,
+        table_bytes(&whole),
+        table_bytes(&chunked),
+        CHUNK
+    );
+
+    c.bench_function("near-identical lines interned whole", |b| {
+        b.iter(|| black_box(intern_whole(&mut SliceInternTable::default())))
+    });
+    c.bench_function("near-identical lines interned in chunks", |b| {
+        b.iter(|| black_box(intern_chunked(&mut SliceInternTable::default())))
+    });
+
+    let line = ChunkedSlice::new(chunked.intern_chunked(describe_line(0), CHUNK));
+    c.bench_function("iterate chunked line", |b| {
+        b.iter(|| black_box(line.iter().count()))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use phaedra_color_types::LinearRgba;
+use phaedra_render_command::{LayerId, RenderCommand};
+use std::collections::BTreeMap;
+
+/// A frame resembling a full screen of text: a background fill for each
+/// cell and a glyph for most of them, spread over a few layers, with an
+/// occasional clip rect barrier
+fn build_frame(cells: usize) -> Vec<RenderCommand> {
+    let mut commands = vec![RenderCommand::Clear {
+        color: LinearRgba(0., 0., 0., 1.),
+        layer: None,
+    }];
+    for i in 0..cells {
+        let x = (i % 200) as f32 * 8.;
+        let y = (i / 200) as f32 * 16.;
+        commands.push(RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(x, y, 8., 16.),
+            color: LinearRgba(0.1, 0.1, 0.1, 1.),
+            hsv: None,
+        });
+        if i % 5 != 0 {
+            commands.push(RenderCommand::FillRect {
+                layer: LayerId::text(),
+                rect: euclid::rect(x, y, 8., 16.),
+                color: LinearRgba(0.9, 0.9, 0.9, 1.),
+                hsv: None,
+            });
+        }
+        if i % 2000 == 0 {
+            commands.push(RenderCommand::SetClipRect(None));
+        }
+    }
+    commands
+}
+
+fn quad_of(cmd: &RenderCommand) -> [f32; 4] {
+    match cmd {
+        RenderCommand::FillRect { rect, .. } | RenderCommand::DrawQuad { position: rect, .. } => {
+            [rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y()]
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Stands in for looking up and mapping the target buffer for every command
+fn fill_sequential(commands: &[RenderCommand]) -> BTreeMap<LayerId, Vec<[f32; 4]>> {
+    fn walk(commands: &[RenderCommand], buffers: &mut BTreeMap<LayerId, Vec<[f32; 4]>>) {
+        for cmd in commands {
+            match cmd {
+                RenderCommand::Batch(cmds) => walk(cmds, buffers),
+                cmd => {
+                    if let Some(key) = cmd.partition_key() {
+                        buffers.entry(key).or_default().push(quad_of(cmd));
+                    }
+                }
+            }
+        }
+    }
+    let mut buffers = BTreeMap::new();
+    walk(commands, &mut buffers);
+    buffers
+}
+
+/// Looks up each target buffer once per group
+fn fill_partitioned(commands: &[RenderCommand]) -> BTreeMap<LayerId, Vec<[f32; 4]>> {
+    let mut buffers: BTreeMap<LayerId, Vec<[f32; 4]>> = BTreeMap::new();
+    for run in RenderCommand::partition_runs(commands) {
+        for (key, group) in run.groups {
+            let buffer = buffers.entry(key).or_default();
+            buffer.extend(group.into_iter().map(quad_of));
+        }
+    }
+    buffers
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let frame = build_frame(200 * 60);
+    assert_eq!(fill_sequential(&frame), fill_partitioned(&frame));
+
+    c.bench_function("partition_runs 200x60", |b| {
+        b.iter(|| black_box(RenderCommand::partition_runs(black_box(&frame))))
+    });
+    c.bench_function("fill sequential 200x60", |b| {
+        b.iter(|| black_box(fill_sequential(black_box(&frame))))
+    });
+    c.bench_function("fill partitioned 200x60", |b| {
+        b.iter(|| black_box(fill_partitioned(black_box(&frame))))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
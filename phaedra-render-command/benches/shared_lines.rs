@@ -0,0 +1,103 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use phaedra_color_types::LinearRgba;
+use phaedra_hash_cons::{HcSlice, SliceInternTable};
+use phaedra_render_command::{LayerId, RenderCommand};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const COLS: usize = 100;
+const ROWS: usize = 60;
+
+/// The commands for one line of text: a background for each cell and
+/// a glyph for most of them, positioned from (left, top)
+fn describe_line(row: usize, left: f32, top: f32) -> Vec<RenderCommand> {
+    let mut commands = vec![];
+    for col in 0..COLS {
+        let rect = euclid::rect(left + col as f32 * 8., top, 8., 16.);
+        commands.push(RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect,
+            color: LinearRgba(0.1, 0.1, 0.1, 1.),
+            hsv: None,
+        });
+        if (row + col) % 5 != 0 {
+            commands.push(RenderCommand::FillRect {
+                layer: LayerId::text(),
+                rect,
+                color: LinearRgba(0.9, 0.9, 0.9, 1.),
+                hsv: None,
+            });
+        }
+    }
+    commands
+}
+
+/// The left edges of two side by side splits of the same pane
+const SPLITS: [f32; 2] = [0., COLS as f32 * 8. + 4.];
+
+/// Caches lines per pane, with the commands at their final position
+fn frame_unshared(cache: &mut HashMap<(usize, usize), Arc<[RenderCommand]>>) -> Vec<RenderCommand> {
+    let mut out = vec![];
+    for (pane, &left) in SPLITS.iter().enumerate() {
+        for row in 0..ROWS {
+            let commands = cache
+                .entry((pane, row))
+                .or_insert_with(|| describe_line(row, left, row as f32 * 16.).into());
+            out.extend_from_slice(commands);
+        }
+    }
+    out
+}
+
+/// Caches lines relative to their origin, so that both splits share
+/// them, and translates them into place
+fn frame_interned(
+    cache: &mut HashMap<usize, HcSlice<RenderCommand>>,
+    interner: &mut SliceInternTable<RenderCommand>,
+) -> Vec<RenderCommand> {
+    let mut out = vec![];
+    for &left in &SPLITS {
+        for row in 0..ROWS {
+            let commands = cache
+                .entry(row)
+                .or_insert_with(|| interner.intern_slice(describe_line(row, 0., 0.)));
+            let top = row as f32 * 16.;
+            out.extend(commands.iter().map(|cmd| cmd.translate(left, top)));
+        }
+    }
+    out
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    assert_eq!(
+        frame_unshared(&mut HashMap::new()),
+        frame_interned(&mut HashMap::new(), &mut SliceInternTable::default())
+    );
+
+    c.bench_function("two splits unshared, cold", |b| {
+        b.iter(|| black_box(frame_unshared(&mut HashMap::new())))
+    });
+    c.bench_function("two splits interned, cold", |b| {
+        b.iter(|| {
+            black_box(frame_interned(
+                &mut HashMap::new(),
+                &mut SliceInternTable::default(),
+            ))
+        })
+    });
+
+    let mut unshared = HashMap::new();
+    frame_unshared(&mut unshared);
+    c.bench_function("two splits unshared, cached", |b| {
+        b.iter(|| black_box(frame_unshared(&mut unshared)))
+    });
+    let mut interned = HashMap::new();
+    let mut interner = SliceInternTable::default();
+    frame_interned(&mut interned, &mut interner);
+    c.bench_function("two splits interned, cached", |b| {
+        b.iter(|| black_box(frame_interned(&mut interned, &mut interner)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,556 @@
+//! A compact textual form of render commands, for asserting in tests
+//! that a describe pass produced the expected frame without spelling
+//! out every field of every command.
+//!
+//! Each command is one line: its kind followed by `key=value` fields.
+//!
+//! ```text
+//! clear color=#000000ff
+//! fill layer=0/0 rect=0,16,80,16 color=#1e1e2eff
+//! rounded layer=10/0 rect=4,4,100,20 radius=6 color=#313244ff
+//! quad layer=0/1 rect=8,16,8,16 color=#cdd6f4ff mode=glyph
+//! line layer=0/2 from=0,20.5 to=80,20.5 width=1 color=#ffffffff
+//! clip rect=0,0,80,32
+//! clip none
+//! postprocess region=0,0,80,32 effect=crt
+//! ```
+//!
+//! Layers are written as `zindex/sub_layer` and rects as
+//! `x,y,width,height`.  Colors are the sRGB form of the command's color,
+//! as `#rrggbbaa`, and numbers are rounded to two decimal places.
+//! Batches are flattened and `Nop`s are omitted.  The texture of a quad
+//! is omitted, as it depends on where the glyph landed in the atlas.
+//! Blank lines, and lines that start with `//`, are ignored.
+//!
+//! `render_commands_to_dsl` produces the text for a sequence of
+//! commands, which can be pasted into a test as its expectation.
+use crate::{HsbTransform, LayerId, PointF, QuadMode, RectF, RenderCommand};
+use phaedra_color_types::LinearRgba;
+
+/// The pixel tolerance used by `assert_commands_match`
+pub const DEFAULT_TOLERANCE: f32 = 0.5;
+
+/// The fields whose values are geometry, and which are compared
+/// within the tolerance rather than exactly
+const GEOMETRY_FIELDS: &[&str] = &["rect", "region", "from", "to", "width", "radius"];
+
+const KINDS: &[&str] = &[
+    "clear",
+    "fill",
+    "rounded",
+    "quad",
+    "line",
+    "clip",
+    "postprocess",
+];
+
+/// A parsed line of the textual form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DslLine {
+    pub kind: String,
+    /// The fields, in the order that they were written
+    pub fields: Vec<(String, String)>,
+}
+
+impl DslLine {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns true if `other` is the same command as `self`, with its
+    /// geometry within `tolerance` pixels of that of `self`
+    pub fn matches(&self, other: &Self, tolerance: f32) -> bool {
+        if self.kind != other.kind || self.fields.len() != other.fields.len() {
+            return false;
+        }
+        self.fields.iter().all(|(key, value)| {
+            let Some(other_value) = other.get(key) else {
+                return false;
+            };
+            if GEOMETRY_FIELDS.contains(&key.as_str()) {
+                match (parse_numbers(value), parse_numbers(other_value)) {
+                    (Some(a), Some(b)) => {
+                        a.len() == b.len()
+                            && a.iter().zip(&b).all(|(a, b)| (a - b).abs() <= tolerance)
+                    }
+                    _ => false,
+                }
+            } else {
+                value == other_value
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for DslLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        for (key, value) in &self.fields {
+            if value.is_empty() {
+                // Such as the `none` of `clip none`
+                write!(f, " {key}")?;
+            } else {
+                write!(f, " {key}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_numbers(value: &str) -> Option<Vec<f32>> {
+    value.split(',').map(|n| n.parse().ok()).collect()
+}
+
+/// Parses the textual form, returning an error that names the line
+/// number of the first line that isn't valid
+pub fn parse_dsl(text: &str) -> Result<Vec<DslLine>, String> {
+    let mut lines = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let kind = tokens.next().expect("line is not empty");
+        if !KINDS.contains(&kind) {
+            return Err(format!(
+                "line {}: unknown command `{kind}`; expected one of {}",
+                idx + 1,
+                KINDS.join(", ")
+            ));
+        }
+        let mut parsed = DslLine {
+            kind: kind.to_string(),
+            fields: vec![],
+        };
+        for token in tokens {
+            if kind == "clip" && token == "none" {
+                parsed.fields.push(("none".to_string(), String::new()));
+                continue;
+            }
+            let Some((key, value)) = token.split_once('=') else {
+                return Err(format!(
+                    "line {}: expected key=value, found `{token}`",
+                    idx + 1
+                ));
+            };
+            if parsed.get(key).is_some() {
+                return Err(format!("line {}: `{key}` is given twice", idx + 1));
+            }
+            if GEOMETRY_FIELDS.contains(&key) && parse_numbers(value).is_none() {
+                return Err(format!(
+                    "line {}: `{key}` must be comma separated numbers, found `{value}`",
+                    idx + 1
+                ));
+            }
+            parsed.fields.push((key.to_string(), value.to_string()));
+        }
+        lines.push(parsed);
+    }
+    Ok(lines)
+}
+
+fn number(n: f32) -> String {
+    let n = (n * 100.).round() / 100.;
+    // Avoid printing -0
+    let n = if n == 0. { 0. } else { n };
+    format!("{n}")
+}
+
+fn rect(r: &RectF) -> String {
+    format!(
+        "{},{},{},{}",
+        number(r.origin.x),
+        number(r.origin.y),
+        number(r.size.width),
+        number(r.size.height)
+    )
+}
+
+fn point(p: &PointF) -> String {
+    format!("{},{}", number(p.x), number(p.y))
+}
+
+fn color(c: &LinearRgba) -> String {
+    let srgb = c.to_srgb();
+    let byte = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        byte(srgb.0),
+        byte(srgb.1),
+        byte(srgb.2),
+        byte(srgb.3)
+    )
+}
+
+fn layer(layer: &LayerId) -> String {
+    format!("{}/{}", layer.zindex(), layer.sub_layer())
+}
+
+fn hsv(fields: &mut Vec<(String, String)>, hsv: &Option<HsbTransform>) {
+    if let Some(hsv) = hsv {
+        fields.push((
+            "hsv".to_string(),
+            format!(
+                "{},{},{}",
+                number(hsv.hue),
+                number(hsv.saturation),
+                number(hsv.brightness)
+            ),
+        ));
+    }
+}
+
+fn mode(mode: &QuadMode) -> &'static str {
+    match mode {
+        QuadMode::Glyph => "glyph",
+        QuadMode::ColorEmoji => "color_emoji",
+        QuadMode::BackgroundImage => "background_image",
+        QuadMode::SolidColor => "solid_color",
+        QuadMode::GrayScale => "gray_scale",
+    }
+}
+
+fn describe(cmd: &RenderCommand, lines: &mut Vec<DslLine>) {
+    let mut fields = vec![];
+    let mut field = |key: &str, value: String| fields.push((key.to_string(), value));
+    let kind = match cmd {
+        RenderCommand::Batch(cmds) => {
+            for cmd in cmds {
+                describe(cmd, lines);
+            }
+            return;
+        }
+        RenderCommand::Nop => return,
+        RenderCommand::Clear {
+            color: c,
+            layer: None,
+        } => {
+            field("color", color(c));
+            "clear"
+        }
+        RenderCommand::Clear {
+            layer: Some(sub_layer),
+            ..
+        } => {
+            field("layer", sub_layer.to_string());
+            "clear"
+        }
+        RenderCommand::FillRect {
+            layer: l,
+            rect: r,
+            color: c,
+            hsv: h,
+        } => {
+            field("layer", layer(l));
+            field("rect", rect(r));
+            field("color", color(c));
+            hsv(&mut fields, h);
+            "fill"
+        }
+        RenderCommand::FillRoundedRect {
+            layer: l,
+            rect: r,
+            radius,
+            color: c,
+            hsv: h,
+        } => {
+            field("layer", layer(l));
+            field("rect", rect(r));
+            field("radius", number(*radius));
+            field("color", color(c));
+            hsv(&mut fields, h);
+            "rounded"
+        }
+        RenderCommand::DrawQuad {
+            layer: l,
+            position,
+            texture: _,
+            fg_color,
+            alt_color,
+            hsv: h,
+            mode: m,
+        } => {
+            field("layer", layer(l));
+            field("rect", rect(position));
+            field("color", color(fg_color));
+            field("mode", mode(m).to_string());
+            if let Some((alt, mix)) = alt_color {
+                field("alt", format!("{},{}", color(alt), number(*mix)));
+            }
+            hsv(&mut fields, h);
+            "quad"
+        }
+        RenderCommand::DrawLine {
+            layer: l,
+            from,
+            to,
+            width,
+            color: c,
+        } => {
+            field("layer", layer(l));
+            field("from", point(from));
+            field("to", point(to));
+            field("width", number(*width));
+            field("color", color(c));
+            "line"
+        }
+        RenderCommand::SetClipRect(Some(r)) => {
+            field("rect", rect(r));
+            "clip"
+        }
+        RenderCommand::SetClipRect(None) => {
+            field("none", String::new());
+            "clip"
+        }
+        RenderCommand::BeginPostProcess { region, effect } => {
+            if let Some(r) = region {
+                field("region", rect(r));
+            }
+            if let Some(effect) = effect {
+                field("effect", effect.clone());
+            }
+            "postprocess"
+        }
+    };
+    lines.push(DslLine {
+        kind: kind.to_string(),
+        fields,
+    });
+}
+
+fn to_lines(commands: &[RenderCommand]) -> Vec<DslLine> {
+    let mut lines = vec![];
+    for cmd in commands {
+        describe(cmd, &mut lines);
+    }
+    lines
+}
+
+/// Returns the textual form of `commands`, one line per command
+pub fn render_commands_to_dsl(commands: &[RenderCommand]) -> String {
+    let mut text = String::new();
+    for line in to_lines(commands) {
+        text.push_str(&line.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+/// Compares `commands` with the textual form `expected`, allowing the
+/// geometry to differ by up to `tolerance` pixels.  The error describes
+/// the first difference and includes the textual form of `commands`.
+pub fn compare_commands(
+    expected: &str,
+    commands: &[RenderCommand],
+    tolerance: f32,
+) -> Result<(), String> {
+    let expected = parse_dsl(expected).map_err(|err| format!("invalid expectation: {err}"))?;
+    let actual = to_lines(commands);
+
+    let mismatch = expected
+        .iter()
+        .zip(&actual)
+        .position(|(expected, actual)| !expected.matches(actual, tolerance));
+    let problem = match mismatch {
+        Some(idx) => format!(
+            "command {} differs:\n  expected: {}\n    actual: {}",
+            idx + 1,
+            expected[idx],
+            actual[idx]
+        ),
+        None if expected.len() != actual.len() => format!(
+            "expected {} commands, but there are {}",
+            expected.len(),
+            actual.len()
+        ),
+        None => return Ok(()),
+    };
+    Err(format!(
+        "{problem}\nthe commands are:\n{}",
+        render_commands_to_dsl(commands)
+    ))
+}
+
+/// Asserts that `commands` match the textual form `expected`, with the
+/// geometry within `DEFAULT_TOLERANCE` pixels
+#[track_caller]
+pub fn assert_commands_match(expected: &str, commands: &[RenderCommand]) {
+    assert_commands_match_within(expected, commands, DEFAULT_TOLERANCE);
+}
+
+/// Asserts that `commands` match the textual form `expected`, with the
+/// geometry within `tolerance` pixels
+#[track_caller]
+pub fn assert_commands_match_within(expected: &str, commands: &[RenderCommand], tolerance: f32) {
+    if let Err(err) = compare_commands(expected, commands, tolerance) {
+        panic!("{err}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::TextureCoords;
+
+    fn commands() -> Vec<RenderCommand> {
+        let white = LinearRgba(1., 1., 1., 1.);
+        vec![
+            RenderCommand::Clear {
+                color: LinearRgba(0., 0., 0., 1.),
+                layer: None,
+            },
+            RenderCommand::Batch(vec![
+                RenderCommand::FillRect {
+                    layer: LayerId::background(),
+                    rect: euclid::rect(0., 16., 80., 16.),
+                    color: LinearRgba(1., 0., 0., 0.5),
+                    hsv: None,
+                },
+                RenderCommand::Nop,
+                RenderCommand::FillRoundedRect {
+                    layer: LayerId::chrome(),
+                    rect: euclid::rect(4., 4., 100.333, 20.),
+                    radius: 6.,
+                    color: white,
+                    hsv: Some(HsbTransform {
+                        hue: 1.,
+                        saturation: 0.5,
+                        brightness: 0.25,
+                    }),
+                },
+            ]),
+            RenderCommand::DrawQuad {
+                layer: LayerId::text(),
+                position: euclid::rect(8., 16., 8., 16.),
+                texture: TextureCoords {
+                    left: 0.,
+                    top: 0.,
+                    right: 0.1,
+                    bottom: 0.1,
+                },
+                fg_color: white,
+                alt_color: Some((LinearRgba(0., 0., 0., 1.), 0.5)),
+                hsv: None,
+                mode: QuadMode::Glyph,
+            },
+            RenderCommand::DrawLine {
+                layer: LayerId::overlay(),
+                from: euclid::point2(0., 20.5),
+                to: euclid::point2(80., 20.5),
+                width: 1.,
+                color: white,
+            },
+            RenderCommand::SetClipRect(Some(euclid::rect(0., 0., 80., 32.))),
+            RenderCommand::SetClipRect(None),
+            RenderCommand::Clear {
+                color: white,
+                layer: Some(1),
+            },
+            RenderCommand::BeginPostProcess {
+                region: None,
+                effect: Some("crt".to_string()),
+            },
+        ]
+    }
+
+    const EXPECTED: &str = "\
+clear color=#000000ff
+fill layer=0/0 rect=0,16,80,16 color=#ff000080
+rounded layer=10/0 rect=4,4,100.33,20 radius=6 color=#ffffffff hsv=1,0.5,0.25
+quad layer=0/1 rect=8,16,8,16 color=#ffffffff mode=glyph alt=#000000ff,0.5
+line layer=0/2 from=0,20.5 to=80,20.5 width=1 color=#ffffffff
+clip rect=0,0,80,32
+clip none
+clear layer=1
+postprocess effect=crt
+";
+
+    #[test]
+    fn dsl_round_trips() {
+        let commands = commands();
+        let text = render_commands_to_dsl(&commands);
+        assert_eq!(text, EXPECTED);
+
+        let parsed = parse_dsl(&text).unwrap();
+        assert_eq!(parsed.len(), 9);
+        assert_eq!(parsed[6].kind, "clip");
+        assert_eq!(parsed[6].get("none"), Some(""));
+        let reprinted: Vec<String> = parsed.iter().map(|line| line.to_string()).collect();
+        assert_eq!(reprinted[1], EXPECTED.lines().nth(1).unwrap());
+
+        assert_eq!(compare_commands(&text, &commands, 0.), Ok(()));
+        assert_commands_match(
+            "
+            // Indentation, blank lines and comments are ignored
+            clear color=#000000ff
+
+            fill layer=0/0 rect=0,16,80,16 color=#ff000080
+            rounded layer=10/0 rect=4,4,100.33,20 radius=6 color=#ffffffff hsv=1,0.5,0.25
+            quad layer=0/1 rect=8,16,8,16 color=#ffffffff mode=glyph alt=#000000ff,0.5
+            line layer=0/2 from=0,20.5 to=80,20.5 width=1 color=#ffffffff
+            clip rect=0,0,80,32
+            clip none
+            clear layer=1
+            postprocess effect=crt
+            ",
+            &commands,
+        );
+    }
+
+    #[test]
+    fn geometry_is_compared_within_the_tolerance() {
+        let commands = vec![RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(10.4, 20., 30., 40.),
+            color: LinearRgba(0., 0., 0., 1.),
+            hsv: None,
+        }];
+        let expected = "fill layer=0/0 rect=10,20,30,40 color=#000000ff";
+        assert_eq!(compare_commands(expected, &commands, 0.5), Ok(()));
+        assert_commands_match(expected, &commands);
+
+        let err = compare_commands(expected, &commands, 0.1).unwrap_err();
+        assert!(err.starts_with("command 1 differs:"), "{err}");
+        assert!(
+            err.contains("actual: fill layer=0/0 rect=10.4,20,30,40"),
+            "{err}"
+        );
+
+        // Colors and other fields are compared exactly
+        let expected = "fill layer=0/0 rect=10,20,30,40 color=#000001ff";
+        assert!(compare_commands(expected, &commands, 10.).is_err());
+        let expected = "fill layer=0/1 rect=10,20,30,40 color=#000000ff";
+        assert!(compare_commands(expected, &commands, 10.).is_err());
+        let expected = "fill layer=0/0 rect=10,20,30 color=#000000ff";
+        assert!(compare_commands(expected, &commands, 10.).is_err());
+    }
+
+    #[test]
+    fn mismatched_counts_and_invalid_text_are_reported() {
+        let commands = commands();
+        let err = compare_commands("clear color=#000000ff", &commands, 0.).unwrap_err();
+        assert!(
+            err.starts_with("expected 1 commands, but there are 9"),
+            "{err}"
+        );
+        assert!(err.ends_with(EXPECTED), "{err}");
+
+        let err = parse_dsl("clear color=#000000ff\nsquare rect=0,0,1,1").unwrap_err();
+        assert!(err.starts_with("line 2: unknown command `square`"), "{err}");
+        let err = parse_dsl("fill layer").unwrap_err();
+        assert_eq!(err, "line 1: expected key=value, found `layer`");
+        let err = parse_dsl("fill layer=0/0 layer=0/1").unwrap_err();
+        assert_eq!(err, "line 1: `layer` is given twice");
+        let err = parse_dsl("fill rect=0,a,1,1").unwrap_err();
+        assert_eq!(
+            err,
+            "line 1: `rect` must be comma separated numbers, found `0,a,1,1`"
+        );
+        let err = compare_commands("fill rect=", &commands, 0.).unwrap_err();
+        assert!(err.starts_with("invalid expectation: line 1:"), "{err}");
+    }
+}
@@ -1,9 +1,13 @@
 use phaedra_color_types::LinearRgba;
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub type RectF = euclid::default::Rect<f32>;
 pub type PointF = euclid::default::Point2D<f32>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub enum QuadMode {
     Glyph,
     ColorEmoji,
@@ -12,14 +16,70 @@ pub enum QuadMode {
     GrayScale,
 }
 
-#[derive(Debug, Clone)]
+impl QuadMode {
+    fn stable_tag(&self) -> u8 {
+        match self {
+            Self::Glyph => 0,
+            Self::ColorEmoji => 1,
+            Self::BackgroundImage => 2,
+            Self::SolidColor => 3,
+            Self::GrayScale => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct HsbTransform {
     pub hue: f32,
     pub saturation: f32,
     pub brightness: f32,
 }
 
+impl HsbTransform {
+    /// The transform that leaves colors unchanged
+    pub const IDENTITY: Self = Self {
+        hue: 1.,
+        saturation: 1.,
+        brightness: 1.,
+    };
+
+    /// The transform that is equivalent to applying `self` and then
+    /// `other`.  The components multiply the hsv of a color, so this
+    /// is their product.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self {
+            hue: self.hue * other.hue,
+            saturation: self.saturation * other.saturation,
+            brightness: self.brightness * other.brightness,
+        }
+    }
+
+    /// Composes two optional transforms, where None leaves colors
+    /// unchanged
+    pub fn compose_opt(a: &Option<Self>, b: Option<&Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.compose(b)),
+            (Some(a), None) => Some(a.clone()),
+            (None, Some(b)) => Some(b.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Interpolates between `self`, at a `fraction` of 0.0, and
+    /// `other`, at 1.0
+    pub fn lerp(&self, other: &Self, fraction: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * fraction;
+        Self {
+            hue: lerp(self.hue, other.hue),
+            saturation: lerp(self.saturation, other.saturation),
+            brightness: lerp(self.brightness, other.brightness),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct TextureCoords {
     pub left: f32,
     pub top: f32,
@@ -27,21 +87,139 @@ pub struct TextureCoords {
     pub bottom: f32,
 }
 
+/// Identifies the quad buffer that a drawing command is written to:
+/// the zindex of its render layer, and which of the sub-layers of that
+/// render layer it is drawn into.  The ordering matches the order in
+/// which the layers are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct LayerId {
+    zindex: i8,
+    sub_layer: u8,
+}
+
+impl LayerId {
+    /// The number of sub-layers in each render layer
+    pub const SUB_LAYERS: usize = 3;
+    /// The zindex of the tab bar and the other window decorations
+    pub const CHROME_ZINDEX: i8 = 10;
+    /// The zindex of modal overlays, such as the command palette
+    pub const MODAL_ZINDEX: i8 = 100;
+
+    /// Returns the id of `sub_layer` of the render layer at `zindex`,
+    /// or None if there is no such sub-layer
+    pub const fn new(zindex: i8, sub_layer: usize) -> Option<Self> {
+        if sub_layer < Self::SUB_LAYERS {
+            Some(Self {
+                zindex,
+                sub_layer: sub_layer as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Solid fills and images beneath the text
+    pub const fn background() -> Self {
+        Self {
+            zindex: 0,
+            sub_layer: 0,
+        }
+    }
+
+    /// Glyphs
+    pub const fn text() -> Self {
+        Self {
+            zindex: 0,
+            sub_layer: 1,
+        }
+    }
+
+    /// Drawn over the text, such as the cursor and indicators
+    pub const fn overlay() -> Self {
+        Self {
+            zindex: 0,
+            sub_layer: 2,
+        }
+    }
+
+    /// The background of the tab bar and the window decorations
+    pub const fn chrome() -> Self {
+        Self::background().with_zindex(Self::CHROME_ZINDEX)
+    }
+
+    /// The background of modal overlays
+    pub const fn modal() -> Self {
+        Self::background().with_zindex(Self::MODAL_ZINDEX)
+    }
+
+    /// Returns the same sub-layer of the render layer at `zindex`
+    pub const fn with_zindex(self, zindex: i8) -> Self {
+        Self {
+            zindex,
+            sub_layer: self.sub_layer,
+        }
+    }
+
+    pub const fn zindex(self) -> i8 {
+        self.zindex
+    }
+
+    pub const fn sub_layer(self) -> usize {
+        self.sub_layer as usize
+    }
+
+    /// Hashes as the separate sub-layer and zindex fields that
+    /// preceded this type did, so that content hashes are unchanged
+    fn hash_stable(self, hasher: &mut impl std::hash::Hasher) {
+        use std::hash::Hash;
+        self.sub_layer().hash(hasher);
+        self.zindex.hash(hasher);
+    }
+}
+
+/// Interprets a raw sub-layer number as that sub-layer at zindex 0.
+/// This is only for code that hasn't moved to the named constructors;
+/// it panics if there is no such sub-layer.
+impl From<usize> for LayerId {
+    fn from(sub_layer: usize) -> Self {
+        match Self::new(0, sub_layer) {
+            Some(layer) => layer,
+            None => panic!("there is no sub-layer {sub_layer}"),
+        }
+    }
+}
+
+/// The drawing commands between two ordering barriers, grouped by the
+/// buffer that they target.  Commands within a group are in their
+/// original order.
+#[derive(Debug, Default)]
+pub struct PartitionRun<'a> {
+    pub groups: BTreeMap<LayerId, Vec<&'a RenderCommand>>,
+    /// The barrier command that ended this run, if any.  It must be
+    /// executed after all of the groups.
+    pub barrier: Option<&'a RenderCommand>,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub enum RenderCommand {
+    /// With `layer` None, clears the whole frame to `color`, discarding
+    /// everything drawn before it.  With `Some(layer)`, discards the
+    /// quads written so far to that sub-layer of every render layer,
+    /// leaving the rest of the frame intact; `color` is not used.
     Clear {
         color: LinearRgba,
+        layer: Option<usize>,
     },
     FillRect {
-        layer: usize,
-        zindex: i8,
+        layer: LayerId,
         rect: RectF,
         color: LinearRgba,
         hsv: Option<HsbTransform>,
     },
     DrawQuad {
-        layer: usize,
-        zindex: i8,
+        layer: LayerId,
         position: RectF,
         texture: TextureCoords,
         fg_color: LinearRgba,
@@ -49,8 +227,34 @@ pub enum RenderCommand {
         hsv: Option<HsbTransform>,
         mode: QuadMode,
     },
+    /// A straight line `width` pixels wide, centered on the segment
+    /// from `from` to `to`.  Unlike a FillRect, it need not be aligned
+    /// with the axes.
+    DrawLine {
+        layer: LayerId,
+        from: PointF,
+        to: PointF,
+        width: f32,
+        color: LinearRgba,
+    },
+    /// A solid rect whose corners are rounded to `radius`, which is
+    /// limited to half of the shorter side of `rect`
+    FillRoundedRect {
+        layer: LayerId,
+        rect: RectF,
+        radius: f32,
+        color: LinearRgba,
+        hsv: Option<HsbTransform>,
+    },
     SetClipRect(Option<RectF>),
-    BeginPostProcess,
+    /// Requests that the post-process pass be applied to `region`,
+    /// or to the whole frame when it is None.  `effect` names one of
+    /// the configured `webgpu_shader_effects`; None selects the
+    /// default `webgpu_shader`.
+    BeginPostProcess {
+        region: Option<RectF>,
+        effect: Option<String>,
+    },
     Batch(Vec<RenderCommand>),
     Nop,
 }
@@ -63,38 +267,79 @@ impl RenderCommand {
         f(self)
     }
 
+    /// Hash the content of a sequence of commands.  The result is stable
+    /// across process runs for a given `phaedra_stable_hash::HASH_VERSION`.
     pub fn content_hash(commands: &[Self]) -> u64 {
+        Self::content_hash_iter(commands)
+    }
+
+    /// The `content_hash` of the commands with `salt` hashed before
+    /// them, for callers that need the hash to change along with state
+    /// that the commands are drawn with but that isn't part of them,
+    /// such as the generation of the texture atlas.  Equal commands
+    /// with equal salts have equal hashes.
+    pub fn content_hash_with_salt(commands: &[Self], salt: u64) -> u64 {
+        use std::hash::Hasher;
+        let mut hasher = phaedra_stable_hash::StableHasher::new();
+        hasher.write_u64(salt);
+        for cmd in commands {
+            cmd.hash_command(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// The `content_hash` of the commands, taken in order, without
+    /// collecting them into a single slice
+    pub fn content_hash_iter<'a>(commands: impl IntoIterator<Item = &'a Self>) -> u64 {
         use std::hash::Hasher;
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        let mut hasher = phaedra_stable_hash::StableHasher::new();
         for cmd in commands {
             cmd.hash_command(&mut hasher);
         }
         hasher.finish()
     }
 
+    /// An explicit per-variant tag for hashing; unlike
+    /// `std::mem::discriminant`, its hashed form is not an
+    /// implementation detail of the compiler.
+    fn stable_tag(&self) -> u8 {
+        match self {
+            Self::Clear { .. } => 0,
+            Self::FillRect { .. } => 1,
+            Self::DrawQuad { .. } => 2,
+            Self::SetClipRect(_) => 3,
+            Self::BeginPostProcess { .. } => 4,
+            Self::Batch(_) => 5,
+            Self::Nop => 6,
+            Self::DrawLine { .. } => 7,
+            Self::FillRoundedRect { .. } => 8,
+        }
+    }
+
     fn hash_command(&self, hasher: &mut impl std::hash::Hasher) {
         use std::hash::Hash;
-        std::mem::discriminant(self).hash(hasher);
+        hasher.write_u8(self.stable_tag());
         match self {
-            Self::Clear { color } => {
+            Self::Clear { color, layer } => {
                 hash_linear_rgba(color, hasher);
+                layer.is_some().hash(hasher);
+                if let Some(layer) = layer {
+                    layer.hash(hasher);
+                }
             }
             Self::FillRect {
                 layer,
-                zindex,
                 rect,
                 color,
                 hsv,
             } => {
-                layer.hash(hasher);
-                zindex.hash(hasher);
+                layer.hash_stable(hasher);
                 hash_rectf(rect, hasher);
                 hash_linear_rgba(color, hasher);
                 hash_opt_hsb(hsv, hasher);
             }
             Self::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color,
@@ -102,8 +347,7 @@ impl RenderCommand {
                 hsv,
                 mode,
             } => {
-                layer.hash(hasher);
-                zindex.hash(hasher);
+                layer.hash_stable(hasher);
                 hash_rectf(position, hasher);
                 hash_texture_coords(texture, hasher);
                 hash_linear_rgba(fg_color, hasher);
@@ -113,7 +357,33 @@ impl RenderCommand {
                     mix.to_bits().hash(hasher);
                 }
                 hash_opt_hsb(hsv, hasher);
-                std::mem::discriminant(mode).hash(hasher);
+                hasher.write_u8(mode.stable_tag());
+            }
+            Self::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color,
+            } => {
+                layer.hash_stable(hasher);
+                hash_pointf(from, hasher);
+                hash_pointf(to, hasher);
+                width.to_bits().hash(hasher);
+                hash_linear_rgba(color, hasher);
+            }
+            Self::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color,
+                hsv,
+            } => {
+                layer.hash_stable(hasher);
+                hash_rectf(rect, hasher);
+                radius.to_bits().hash(hasher);
+                hash_linear_rgba(color, hasher);
+                hash_opt_hsb(hsv, hasher);
             }
             Self::Batch(cmds) => {
                 for cmd in cmds.iter() {
@@ -126,7 +396,17 @@ impl RenderCommand {
                     hash_rectf(r, hasher);
                 }
             }
-            Self::BeginPostProcess | Self::Nop => {}
+            Self::BeginPostProcess { region, effect } => {
+                region.is_some().hash(hasher);
+                if let Some(r) = region {
+                    hash_rectf(r, hasher);
+                }
+                effect.is_some().hash(hasher);
+                if let Some(effect) = effect {
+                    effect.as_str().hash(hasher);
+                }
+            }
+            Self::Nop => {}
         }
     }
 
@@ -135,25 +415,23 @@ impl RenderCommand {
         F: Fn(LinearRgba) -> LinearRgba,
     {
         match self {
-            RenderCommand::Clear { color } => RenderCommand::Clear { color: f(color) },
+            RenderCommand::Clear { color, layer } => RenderCommand::Clear {
+                color: f(color),
+                layer,
+            },
             RenderCommand::FillRect {
                 layer,
-                zindex,
                 rect,
                 color,
                 hsv,
-            } => {
-                RenderCommand::FillRect {
-                    layer,
-                    zindex,
-                    rect,
-                    color: f(color),
-                    hsv,
-                }
-            }
+            } => RenderCommand::FillRect {
+                layer,
+                rect,
+                color: f(color),
+                hsv,
+            },
             RenderCommand::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color,
@@ -162,7 +440,6 @@ impl RenderCommand {
                 mode,
             } => RenderCommand::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color: f(fg_color),
@@ -170,6 +447,32 @@ impl RenderCommand {
                 hsv,
                 mode,
             },
+            RenderCommand::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color,
+            } => RenderCommand::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color: f(color),
+            },
+            RenderCommand::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color,
+                hsv,
+            } => RenderCommand::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color: f(color),
+                hsv,
+            },
             RenderCommand::Batch(cmds) => {
                 RenderCommand::Batch(cmds.into_iter().map(|c| c.map_colors(f)).collect())
             }
@@ -181,14 +484,12 @@ impl RenderCommand {
         match self {
             RenderCommand::FillRect {
                 layer,
-                zindex,
                 rect,
                 color,
                 hsv,
             } => match rect.intersection(clip) {
                 Some(clipped) => RenderCommand::FillRect {
                     layer,
-                    zindex,
                     rect: clipped,
                     color,
                     hsv,
@@ -197,7 +498,6 @@ impl RenderCommand {
             },
             RenderCommand::DrawQuad {
                 layer,
-                zindex,
                 position,
                 texture,
                 fg_color,
@@ -230,7 +530,6 @@ impl RenderCommand {
 
                     RenderCommand::DrawQuad {
                         layer,
-                        zindex,
                         position: clipped_pos,
                         texture: clipped_texture,
                         fg_color,
@@ -241,6 +540,47 @@ impl RenderCommand {
                 }
                 None => RenderCommand::Nop,
             },
+            RenderCommand::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color,
+            } => match clip_segment(from, to, clip) {
+                Some((from, to)) => RenderCommand::DrawLine {
+                    layer,
+                    from,
+                    to,
+                    width,
+                    color,
+                },
+                None => RenderCommand::Nop,
+            },
+            RenderCommand::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color,
+                hsv,
+            } => match rect.intersection(clip) {
+                Some(clipped) if clipped == rect => RenderCommand::FillRoundedRect {
+                    layer,
+                    rect,
+                    radius,
+                    color,
+                    hsv,
+                },
+                // The corners of the visible part don't correspond to
+                // those of the rect, so fill it square rather than
+                // rounding corners that aren't there
+                Some(clipped) => RenderCommand::FillRect {
+                    layer,
+                    rect: clipped,
+                    color,
+                    hsv,
+                },
+                None => RenderCommand::Nop,
+            },
             RenderCommand::Batch(cmds) => {
                 let clipped: Vec<_> = cmds
                     .into_iter()
@@ -257,6 +597,226 @@ impl RenderCommand {
         }
     }
 
+    /// Returns a copy of this command with its geometry offset by
+    /// (dx, dy), so that commands described relative to some origin can
+    /// be placed at a position in the frame.
+    pub fn translate(&self, dx: f32, dy: f32) -> RenderCommand {
+        let offset = euclid::vec2(dx, dy);
+        match self {
+            RenderCommand::FillRect {
+                layer,
+                rect,
+                color,
+                hsv,
+            } => RenderCommand::FillRect {
+                layer: *layer,
+                rect: rect.translate(offset),
+                color: *color,
+                hsv: hsv.clone(),
+            },
+            RenderCommand::DrawQuad {
+                layer,
+                position,
+                texture,
+                fg_color,
+                alt_color,
+                hsv,
+                mode,
+            } => RenderCommand::DrawQuad {
+                layer: *layer,
+                position: position.translate(offset),
+                texture: texture.clone(),
+                fg_color: *fg_color,
+                alt_color: *alt_color,
+                hsv: hsv.clone(),
+                mode: mode.clone(),
+            },
+            RenderCommand::DrawLine {
+                layer,
+                from,
+                to,
+                width,
+                color,
+            } => RenderCommand::DrawLine {
+                layer: *layer,
+                from: *from + offset,
+                to: *to + offset,
+                width: *width,
+                color: *color,
+            },
+            RenderCommand::FillRoundedRect {
+                layer,
+                rect,
+                radius,
+                color,
+                hsv,
+            } => RenderCommand::FillRoundedRect {
+                layer: *layer,
+                rect: rect.translate(offset),
+                radius: *radius,
+                color: *color,
+                hsv: hsv.clone(),
+            },
+            RenderCommand::SetClipRect(rect) => {
+                RenderCommand::SetClipRect(rect.map(|r| r.translate(offset)))
+            }
+            RenderCommand::BeginPostProcess { region, effect } => RenderCommand::BeginPostProcess {
+                region: region.map(|r| r.translate(offset)),
+                effect: effect.clone(),
+            },
+            RenderCommand::Batch(cmds) => {
+                RenderCommand::Batch(cmds.iter().map(|c| c.translate(dx, dy)).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Returns this command with `f` applied to each of the rects that
+    /// place it in the frame: the rects that are filled, the positions of
+    /// quads, clip rects and post process regions.  The end points of a
+    /// line are mapped as empty rects at those points.
+    pub fn map_rects(mut self, f: &impl Fn(RectF) -> RectF) -> RenderCommand {
+        match &mut self {
+            RenderCommand::FillRect { rect, .. } | RenderCommand::FillRoundedRect { rect, .. } => {
+                *rect = f(*rect);
+            }
+            RenderCommand::DrawQuad { position, .. } => {
+                *position = f(*position);
+            }
+            RenderCommand::DrawLine { from, to, .. } => {
+                *from = f(RectF::new(*from, euclid::size2(0., 0.))).origin;
+                *to = f(RectF::new(*to, euclid::size2(0., 0.))).origin;
+            }
+            RenderCommand::SetClipRect(rect)
+            | RenderCommand::BeginPostProcess { region: rect, .. } => {
+                *rect = rect.map(f);
+            }
+            RenderCommand::Batch(cmds) => {
+                for cmd in cmds.iter_mut() {
+                    *cmd = std::mem::replace(cmd, RenderCommand::Nop).map_rects(f);
+                }
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Returns this command with `transform` applied after any hsb
+    /// transform of its own.  This is what executing it with
+    /// `transform` as the execute-time transform draws.  Lines have no
+    /// transform, so they are unchanged.
+    pub fn with_hsv(mut self, transform: &HsbTransform) -> RenderCommand {
+        match &mut self {
+            RenderCommand::FillRect { hsv, .. }
+            | RenderCommand::DrawQuad { hsv, .. }
+            | RenderCommand::FillRoundedRect { hsv, .. } => {
+                *hsv = HsbTransform::compose_opt(hsv, Some(transform));
+            }
+            RenderCommand::Batch(cmds) => {
+                for cmd in cmds.iter_mut() {
+                    *cmd = std::mem::replace(cmd, RenderCommand::Nop).with_hsv(transform);
+                }
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Returns this command with everything that it draws moved to the
+    /// render layer for `zindex`, so that content described at the
+    /// default zindex can be placed above the rest of the frame.
+    pub fn with_zindex(mut self, new_zindex: i8) -> RenderCommand {
+        match &mut self {
+            RenderCommand::FillRect { layer, .. }
+            | RenderCommand::DrawQuad { layer, .. }
+            | RenderCommand::DrawLine { layer, .. }
+            | RenderCommand::FillRoundedRect { layer, .. } => {
+                *layer = layer.with_zindex(new_zindex)
+            }
+            RenderCommand::Batch(cmds) => {
+                for cmd in cmds.iter_mut() {
+                    *cmd = std::mem::replace(cmd, RenderCommand::Nop).with_zindex(new_zindex);
+                }
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Returns the buffer that this command draws into, or None
+    /// if it doesn't emit a quad
+    pub fn partition_key(&self) -> Option<LayerId> {
+        match self {
+            Self::FillRect { layer, .. }
+            | Self::DrawQuad { layer, .. }
+            | Self::DrawLine { layer, .. }
+            | Self::FillRoundedRect { layer, .. } => Some(*layer),
+            _ => None,
+        }
+    }
+
+    /// Returns true for commands that affect the state in which
+    /// subsequent commands are executed, and that therefore cannot
+    /// be reordered with respect to drawing commands
+    pub fn is_barrier(&self) -> bool {
+        matches!(
+            self,
+            Self::Clear { .. } | Self::SetClipRect(_) | Self::BeginPostProcess { .. }
+        )
+    }
+
+    /// Group the drawing commands by their target buffer, preserving
+    /// their relative order within each group.  Batches are flattened
+    /// and Nops are discarded.  Barriers are not represented in the
+    /// result; use `partition_runs` when they need to be honored.
+    pub fn partition(commands: &[Self]) -> BTreeMap<LayerId, Vec<&Self>> {
+        let mut groups = BTreeMap::new();
+        for cmd in commands {
+            cmd.for_each_leaf(&mut |cmd| {
+                if let Some(key) = cmd.partition_key() {
+                    groups.entry(key).or_insert_with(Vec::new).push(cmd);
+                }
+            });
+        }
+        groups
+    }
+
+    /// Split the commands into runs separated by barriers, and
+    /// partition the drawing commands in each run by their target
+    /// buffer.  Executing each run's groups followed by its barrier
+    /// produces the same buffer contents as executing the commands
+    /// in sequence.
+    pub fn partition_runs(commands: &[Self]) -> Vec<PartitionRun<'_>> {
+        let mut runs = vec![];
+        let mut current = PartitionRun::default();
+        for cmd in commands {
+            cmd.for_each_leaf(&mut |cmd| {
+                if let Some(key) = cmd.partition_key() {
+                    current.groups.entry(key).or_insert_with(Vec::new).push(cmd);
+                } else if cmd.is_barrier() {
+                    current.barrier = Some(cmd);
+                    runs.push(std::mem::take(&mut current));
+                }
+            });
+        }
+        if !current.groups.is_empty() {
+            runs.push(current);
+        }
+        runs
+    }
+
+    /// Visit each command, descending into batches
+    fn for_each_leaf<'a>(&'a self, f: &mut impl FnMut(&'a Self)) {
+        match self {
+            Self::Batch(cmds) => {
+                for cmd in cmds {
+                    cmd.for_each_leaf(f);
+                }
+            }
+            _ => f(self),
+        }
+    }
+
     pub fn fold<T, F>(&self, init: T, f: &F) -> T
     where
         F: Fn(T, &RenderCommand) -> T,
@@ -268,6 +828,211 @@ impl RenderCommand {
     }
 }
 
+/// Hashes consistently with `content_hash`, so that commands can be
+/// used as keys, for example to intern identical command sequences
+impl std::hash::Hash for RenderCommand {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hash_command(state);
+    }
+}
+
+/// Feeds the content of render commands into a hasher of the caller's
+/// choosing, in the same way as `RenderCommand::content_hash`, so that
+/// other state can be hashed along with them without hashing the
+/// commands a second time.  The hasher is taken as a trait object so
+/// that this trait can itself be used as one.
+pub trait HashCommands {
+    fn hash_into(&self, hasher: &mut dyn std::hash::Hasher);
+}
+
+impl HashCommands for RenderCommand {
+    fn hash_into(&self, mut hasher: &mut dyn std::hash::Hasher) {
+        self.hash_command(&mut hasher);
+    }
+}
+
+/// Hashes the commands in order, so that finishing a
+/// `phaedra_stable_hash::StableHasher` fed only these commands gives
+/// their `content_hash`
+impl HashCommands for &[RenderCommand] {
+    fn hash_into(&self, mut hasher: &mut dyn std::hash::Hasher) {
+        for cmd in self.iter() {
+            cmd.hash_command(&mut hasher);
+        }
+    }
+}
+
+/// Commands are equal when their values are bitwise identical, which
+/// is consistent with the `Hash` impl, and makes this a true `Eq`
+/// despite the float fields.
+impl PartialEq for RenderCommand {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Clear {
+                    color: a,
+                    layer: layer_a,
+                },
+                Self::Clear {
+                    color: b,
+                    layer: layer_b,
+                },
+            ) => rgba_bits(a) == rgba_bits(b) && layer_a == layer_b,
+            (
+                Self::FillRect {
+                    layer,
+                    rect,
+                    color,
+                    hsv,
+                },
+                Self::FillRect {
+                    layer: o_layer,
+                    rect: o_rect,
+                    color: o_color,
+                    hsv: o_hsv,
+                },
+            ) => {
+                layer == o_layer
+                    && rect_bits(rect) == rect_bits(o_rect)
+                    && rgba_bits(color) == rgba_bits(o_color)
+                    && hsb_bits(hsv) == hsb_bits(o_hsv)
+            }
+            (
+                Self::DrawQuad {
+                    layer,
+                    position,
+                    texture,
+                    fg_color,
+                    alt_color,
+                    hsv,
+                    mode,
+                },
+                Self::DrawQuad {
+                    layer: o_layer,
+                    position: o_position,
+                    texture: o_texture,
+                    fg_color: o_fg_color,
+                    alt_color: o_alt_color,
+                    hsv: o_hsv,
+                    mode: o_mode,
+                },
+            ) => {
+                layer == o_layer
+                    && rect_bits(position) == rect_bits(o_position)
+                    && texture_bits(texture) == texture_bits(o_texture)
+                    && rgba_bits(fg_color) == rgba_bits(o_fg_color)
+                    && alt_color.map(|(c, mix)| (rgba_bits(&c), mix.to_bits()))
+                        == o_alt_color.map(|(c, mix)| (rgba_bits(&c), mix.to_bits()))
+                    && hsb_bits(hsv) == hsb_bits(o_hsv)
+                    && mode == o_mode
+            }
+            (
+                Self::DrawLine {
+                    layer,
+                    from,
+                    to,
+                    width,
+                    color,
+                },
+                Self::DrawLine {
+                    layer: o_layer,
+                    from: o_from,
+                    to: o_to,
+                    width: o_width,
+                    color: o_color,
+                },
+            ) => {
+                layer == o_layer
+                    && point_bits(from) == point_bits(o_from)
+                    && point_bits(to) == point_bits(o_to)
+                    && width.to_bits() == o_width.to_bits()
+                    && rgba_bits(color) == rgba_bits(o_color)
+            }
+            (
+                Self::FillRoundedRect {
+                    layer,
+                    rect,
+                    radius,
+                    color,
+                    hsv,
+                },
+                Self::FillRoundedRect {
+                    layer: o_layer,
+                    rect: o_rect,
+                    radius: o_radius,
+                    color: o_color,
+                    hsv: o_hsv,
+                },
+            ) => {
+                layer == o_layer
+                    && rect_bits(rect) == rect_bits(o_rect)
+                    && radius.to_bits() == o_radius.to_bits()
+                    && rgba_bits(color) == rgba_bits(o_color)
+                    && hsb_bits(hsv) == hsb_bits(o_hsv)
+            }
+            (Self::SetClipRect(a), Self::SetClipRect(b)) => {
+                a.as_ref().map(rect_bits) == b.as_ref().map(rect_bits)
+            }
+            (
+                Self::BeginPostProcess { region, effect },
+                Self::BeginPostProcess {
+                    region: o_region,
+                    effect: o_effect,
+                },
+            ) => {
+                region.as_ref().map(rect_bits) == o_region.as_ref().map(rect_bits)
+                    && effect == o_effect
+            }
+            (Self::Batch(a), Self::Batch(b)) => a == b,
+            (Self::Nop, Self::Nop) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RenderCommand {}
+
+fn rgba_bits(color: &LinearRgba) -> [u32; 4] {
+    [
+        color.0.to_bits(),
+        color.1.to_bits(),
+        color.2.to_bits(),
+        color.3.to_bits(),
+    ]
+}
+
+fn rect_bits(rect: &RectF) -> [u32; 4] {
+    [
+        rect.origin.x.to_bits(),
+        rect.origin.y.to_bits(),
+        rect.size.width.to_bits(),
+        rect.size.height.to_bits(),
+    ]
+}
+
+fn point_bits(point: &PointF) -> [u32; 2] {
+    [point.x.to_bits(), point.y.to_bits()]
+}
+
+fn texture_bits(texture: &TextureCoords) -> [u32; 4] {
+    [
+        texture.left.to_bits(),
+        texture.top.to_bits(),
+        texture.right.to_bits(),
+        texture.bottom.to_bits(),
+    ]
+}
+
+fn hsb_bits(hsv: &Option<HsbTransform>) -> Option<[u32; 3]> {
+    hsv.as_ref().map(|hsv| {
+        [
+            hsv.hue.to_bits(),
+            hsv.saturation.to_bits(),
+            hsv.brightness.to_bits(),
+        ]
+    })
+}
+
 fn hash_linear_rgba(color: &LinearRgba, hasher: &mut impl std::hash::Hasher) {
     use std::hash::Hash;
     color.0.to_bits().hash(hasher);
@@ -284,6 +1049,12 @@ fn hash_rectf(rect: &RectF, hasher: &mut impl std::hash::Hasher) {
     rect.size.height.to_bits().hash(hasher);
 }
 
+fn hash_pointf(point: &PointF, hasher: &mut impl std::hash::Hasher) {
+    use std::hash::Hash;
+    point.x.to_bits().hash(hasher);
+    point.y.to_bits().hash(hasher);
+}
+
 fn hash_texture_coords(texture: &TextureCoords, hasher: &mut impl std::hash::Hasher) {
     use std::hash::Hash;
     texture.left.to_bits().hash(hasher);
@@ -301,3 +1072,508 @@ fn hash_opt_hsb(hsv: &Option<HsbTransform>, hasher: &mut impl std::hash::Hasher)
         hsv.brightness.to_bits().hash(hasher);
     }
 }
+
+/// Clips the segment from `from` to `to` to `clip`, returning the
+/// part of it that lies within `clip`, if any
+fn clip_segment(from: PointF, to: PointF, clip: &RectF) -> Option<(PointF, PointF)> {
+    // Liang-Barsky: each edge of the clip rect limits the range of
+    // the parameter t, along from + t * (to - from), that is inside it
+    let delta = to - from;
+    let mut t0 = 0f32;
+    let mut t1 = 1f32;
+    for (p, q) in [
+        (-delta.x, from.x - clip.min_x()),
+        (delta.x, clip.max_x() - from.x),
+        (-delta.y, from.y - clip.min_y()),
+        (delta.y, clip.max_y() - from.y),
+    ]
+    .iter()
+    .copied()
+    {
+        if p == 0. {
+            // Parallel to this edge; either wholly inside or outside it
+            if q < 0. {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0. {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((from + delta * t0, from + delta * t1))
+}
+
+/// Internal passes of the pipeline, exposed so that they can be
+/// measured on their own by `phaedra-render-bench`
+/// A textual form of render commands for asserting on them in tests
+#[cfg(any(test, feature = "test-support"))]
+pub mod dsl;
+
+#[cfg(feature = "bench")]
+pub mod bench {
+    use super::RenderCommand;
+
+    /// Flattens batches into the sequence of commands that `partition`
+    /// and `partition_runs` operate on
+    pub fn flatten(commands: &[RenderCommand]) -> Vec<&RenderCommand> {
+        let mut leaves = vec![];
+        for cmd in commands {
+            cmd.for_each_leaf(&mut |cmd| leaves.push(cmd));
+        }
+        leaves
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // These pin content_hash to specific values so that accidental
+    // changes to the hashing scheme are caught; when one of them
+    // legitimately changes, phaedra_stable_hash::HASH_VERSION must be
+    // bumped.
+
+    fn id(zindex: i8, sub_layer: usize) -> LayerId {
+        LayerId::new(zindex, sub_layer).unwrap()
+    }
+
+    #[test]
+    fn layer_constructors_match_the_numbered_layers() {
+        assert_eq!(LayerId::background(), id(0, 0));
+        assert_eq!(LayerId::text(), id(0, 1));
+        assert_eq!(LayerId::overlay(), id(0, 2));
+        assert_eq!(LayerId::chrome(), id(10, 0));
+        assert_eq!(LayerId::modal(), id(100, 0));
+        assert_eq!(LayerId::text().with_zindex(-2), id(-2, 1));
+        assert_eq!(LayerId::overlay().sub_layer(), 2);
+        assert_eq!(LayerId::chrome().zindex(), 10);
+        assert_eq!(LayerId::from(1), LayerId::text());
+
+        assert_eq!(LayerId::new(0, LayerId::SUB_LAYERS), None);
+        // Layers are ordered as they are rendered
+        assert!(id(-1, 2) < LayerId::background());
+        assert!(LayerId::overlay() < LayerId::chrome());
+    }
+
+    #[test]
+    #[should_panic]
+    fn raw_layer_numbers_are_validated() {
+        let _ = LayerId::from(LayerId::SUB_LAYERS);
+    }
+
+    fn clear() -> RenderCommand {
+        RenderCommand::Clear {
+            color: LinearRgba(0., 0., 0., 1.),
+            layer: None,
+        }
+    }
+
+    fn fill_rect() -> RenderCommand {
+        RenderCommand::FillRect {
+            layer: LayerId::text().with_zindex(-2),
+            rect: euclid::rect(1., 2., 3., 4.),
+            color: LinearRgba(1., 0.5, 0.25, 1.),
+            hsv: None,
+        }
+    }
+
+    #[test]
+    fn content_hash_golden() {
+        assert_eq!(RenderCommand::content_hash(&[]), 0xcbf29ce484222325);
+        assert_eq!(RenderCommand::content_hash(&[clear()]), 0x230593af53978aea);
+        assert_eq!(
+            RenderCommand::content_hash(&[fill_rect()]),
+            0xa177a19b83513fd5
+        );
+        assert_eq!(
+            RenderCommand::content_hash(&[clear(), fill_rect()]),
+            0x25a11012e8fe3e30
+        );
+        assert_eq!(
+            RenderCommand::content_hash(&[RenderCommand::Nop]),
+            0xaf63bb4c8601b479
+        );
+    }
+
+    #[test]
+    fn batch_hashes_as_its_contents() {
+        assert_eq!(
+            RenderCommand::content_hash(&[RenderCommand::Batch(vec![clear(), fill_rect()])]),
+            0x93d57ba9eb04f71b
+        );
+    }
+
+    #[test]
+    fn salt_changes_the_hash() {
+        let commands = [clear(), fill_rect()];
+        let salted = RenderCommand::content_hash_with_salt(&commands, 1);
+        assert_ne!(salted, RenderCommand::content_hash_with_salt(&commands, 2));
+        assert_ne!(salted, RenderCommand::content_hash(&commands));
+        assert_eq!(
+            salted,
+            RenderCommand::content_hash_with_salt(&[clear(), fill_rect()], 1)
+        );
+        assert_ne!(
+            salted,
+            RenderCommand::content_hash_with_salt(&[fill_rect(), clear()], 1)
+        );
+    }
+
+    #[test]
+    fn hash_commands_through_trait_objects() {
+        use std::hash::Hasher;
+        let commands = vec![clear(), RenderCommand::Batch(vec![fill_rect()])];
+        let slice: &[RenderCommand] = &commands;
+        let hash = |sources: &[&dyn HashCommands]| {
+            let mut hasher = phaedra_stable_hash::StableHasher::new();
+            for source in sources {
+                source.hash_into(&mut hasher);
+            }
+            hasher.finish()
+        };
+        assert_eq!(hash(&[&slice]), RenderCommand::content_hash(&commands));
+        assert_eq!(
+            hash(&[&commands[0], &commands[1]]),
+            RenderCommand::content_hash(&commands)
+        );
+        let mut hasher = phaedra_stable_hash::StableHasher::new();
+        hasher.write_u64(7);
+        slice.hash_into(&mut hasher);
+        assert_eq!(
+            hasher.finish(),
+            RenderCommand::content_hash_with_salt(&commands, 7)
+        );
+    }
+
+    #[test]
+    fn post_process_region_and_effect_are_hashed() {
+        let post_process = |region: Option<RectF>, effect: Option<&str>| {
+            RenderCommand::content_hash(&[RenderCommand::BeginPostProcess {
+                region,
+                effect: effect.map(|s| s.to_string()),
+            }])
+        };
+        let whole = post_process(None, None);
+        assert_ne!(
+            whole,
+            post_process(Some(euclid::rect(0., 0., 1., 1.)), None)
+        );
+        assert_ne!(whole, post_process(None, Some("blur")));
+        assert_ne!(
+            post_process(None, Some("blur")),
+            post_process(None, Some("glow"))
+        );
+    }
+
+    #[test]
+    fn translate_offsets_geometry() {
+        let commands = vec![
+            clear(),
+            RenderCommand::Batch(vec![fill_rect()]),
+            RenderCommand::SetClipRect(Some(euclid::rect(0., 0., 10., 10.))),
+            RenderCommand::SetClipRect(None),
+        ];
+        let translated: Vec<_> = commands.iter().map(|c| c.translate(5., -1.)).collect();
+        assert_eq!(
+            translated,
+            vec![
+                clear(),
+                RenderCommand::Batch(vec![RenderCommand::FillRect {
+                    layer: LayerId::text().with_zindex(-2),
+                    rect: euclid::rect(6., 1., 3., 4.),
+                    color: LinearRgba(1., 0.5, 0.25, 1.),
+                    hsv: None,
+                }]),
+                RenderCommand::SetClipRect(Some(euclid::rect(5., -1., 10., 10.))),
+                RenderCommand::SetClipRect(None),
+            ]
+        );
+        let back: Vec<_> = translated.iter().map(|c| c.translate(-5., 1.)).collect();
+        assert_eq!(back, commands);
+    }
+
+    #[test]
+    fn map_rects_matches_translate() {
+        let commands = vec![
+            clear(),
+            RenderCommand::Batch(vec![fill_rect(), RenderCommand::SetClipRect(None)]),
+            RenderCommand::SetClipRect(Some(euclid::rect(0., 0., 10., 10.))),
+            RenderCommand::DrawLine {
+                layer: LayerId::overlay(),
+                from: euclid::point2(0., 1.),
+                to: euclid::point2(2., 3.),
+                width: 1.,
+                color: LinearRgba(1., 1., 1., 1.),
+            },
+        ];
+        let offset = euclid::vec2(3., 7.);
+        for cmd in commands {
+            assert_eq!(
+                cmd.clone().map_rects(&|r| r.translate(offset)),
+                cmd.translate(3., 7.)
+            );
+        }
+
+        let scaled = fill_rect().map_rects(&|r| r.scale(2., 2.));
+        assert!(
+            matches!(scaled, RenderCommand::FillRect { rect, .. } if rect == euclid::rect(2., 4., 6., 8.))
+        );
+    }
+
+    #[test]
+    fn with_zindex_moves_nested_commands() {
+        let raised = RenderCommand::Batch(vec![clear(), fill_rect()]).with_zindex(10);
+        assert_eq!(
+            raised,
+            RenderCommand::Batch(vec![
+                clear(),
+                RenderCommand::FillRect {
+                    layer: LayerId::text().with_zindex(10),
+                    rect: euclid::rect(1., 2., 3., 4.),
+                    color: LinearRgba(1., 0.5, 0.25, 1.),
+                    hsv: None,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn with_hsv_composes_with_the_command_transform() {
+        let dim = HsbTransform {
+            hue: 1.,
+            saturation: 0.5,
+            brightness: 0.8,
+        };
+        let tinted = RenderCommand::FillRect {
+            layer: LayerId::background(),
+            rect: euclid::rect(0., 0., 1., 1.),
+            color: LinearRgba(1., 1., 1., 1.),
+            hsv: Some(HsbTransform {
+                hue: 2.,
+                saturation: 1.,
+                brightness: 0.5,
+            }),
+        };
+        let dimmed =
+            RenderCommand::Batch(vec![fill_rect(), tinted.clone(), clear()]).with_hsv(&dim);
+        assert_eq!(
+            dimmed,
+            RenderCommand::Batch(vec![
+                RenderCommand::FillRect {
+                    layer: LayerId::text().with_zindex(-2),
+                    rect: euclid::rect(1., 2., 3., 4.),
+                    color: LinearRgba(1., 0.5, 0.25, 1.),
+                    hsv: Some(dim.clone()),
+                },
+                RenderCommand::FillRect {
+                    layer: LayerId::background(),
+                    rect: euclid::rect(0., 0., 1., 1.),
+                    color: LinearRgba(1., 1., 1., 1.),
+                    hsv: Some(HsbTransform {
+                        hue: 2.,
+                        saturation: 0.5,
+                        brightness: 0.4,
+                    }),
+                },
+                clear(),
+            ])
+        );
+        assert_eq!(tinted.clone().with_hsv(&HsbTransform::IDENTITY), tinted);
+    }
+
+    #[test]
+    fn equality_is_bitwise_and_consistent_with_hashing() {
+        assert_eq!(fill_rect(), fill_rect());
+        assert_ne!(fill_rect(), fill_rect().translate(0., 1.));
+        assert_ne!(clear(), RenderCommand::Nop);
+
+        let zero = RenderCommand::Clear {
+            color: LinearRgba(0., 0., 0., 1.),
+            layer: None,
+        };
+        let negative_zero = RenderCommand::Clear {
+            color: LinearRgba(-0., 0., 0., 1.),
+            layer: None,
+        };
+        assert_ne!(zero, negative_zero);
+        assert_ne!(
+            RenderCommand::content_hash(&[zero]),
+            RenderCommand::content_hash(&[negative_zero])
+        );
+    }
+
+    fn line(from: PointF, to: PointF) -> RenderCommand {
+        RenderCommand::DrawLine {
+            layer: LayerId::overlay(),
+            from,
+            to,
+            width: 1.5,
+            color: LinearRgba(1., 1., 1., 1.),
+        }
+    }
+
+    fn rounded_rect(rect: RectF) -> RenderCommand {
+        RenderCommand::FillRoundedRect {
+            layer: LayerId::background().with_zindex(1),
+            rect,
+            radius: 2.,
+            color: LinearRgba(0.5, 0.5, 0.5, 1.),
+            hsv: None,
+        }
+    }
+
+    #[test]
+    fn clipping_a_line_keeps_the_visible_segment() {
+        let clip = euclid::rect(0., 0., 10., 10.);
+        let diagonal = line(euclid::point2(-5., -5.), euclid::point2(15., 15.));
+        assert_eq!(
+            diagonal.clip_to_rect(&clip),
+            line(euclid::point2(0., 0.), euclid::point2(10., 10.))
+        );
+
+        let inside = line(euclid::point2(2., 2.), euclid::point2(8., 3.));
+        assert_eq!(inside.clone().clip_to_rect(&clip), inside);
+
+        let vertical = line(euclid::point2(5., -5.), euclid::point2(5., 15.));
+        assert_eq!(
+            vertical.clip_to_rect(&clip),
+            line(euclid::point2(5., 0.), euclid::point2(5., 10.))
+        );
+
+        let outside = line(euclid::point2(-5., 20.), euclid::point2(20., 20.));
+        assert_eq!(outside.clip_to_rect(&clip), RenderCommand::Nop);
+    }
+
+    #[test]
+    fn clipping_a_rounded_rect_falls_back_to_a_fill() {
+        let clip = euclid::rect(0., 0., 10., 10.);
+        let inside = rounded_rect(euclid::rect(1., 1., 4., 4.));
+        assert_eq!(inside.clone().clip_to_rect(&clip), inside);
+
+        assert_eq!(
+            rounded_rect(euclid::rect(5., 5., 10., 10.)).clip_to_rect(&clip),
+            RenderCommand::FillRect {
+                layer: LayerId::background().with_zindex(1),
+                rect: euclid::rect(5., 5., 5., 5.),
+                color: LinearRgba(0.5, 0.5, 0.5, 1.),
+                hsv: None,
+            }
+        );
+
+        assert_eq!(
+            rounded_rect(euclid::rect(20., 20., 4., 4.)).clip_to_rect(&clip),
+            RenderCommand::Nop
+        );
+    }
+
+    #[test]
+    fn lines_and_rounded_rects_are_distinct() {
+        let from = euclid::point2(0., 0.);
+        let to = euclid::point2(3., 4.);
+        let a = line(from, to);
+        let b = line(to, from);
+        assert_ne!(a, b);
+        assert_ne!(
+            RenderCommand::content_hash(&[a.clone()]),
+            RenderCommand::content_hash(&[b])
+        );
+        assert_eq!(
+            a.translate(1., 1.),
+            line(euclid::point2(1., 1.), euclid::point2(4., 5.))
+        );
+        assert_eq!(a.partition_key(), Some(LayerId::overlay()));
+
+        let rect = rounded_rect(euclid::rect(0., 0., 4., 4.));
+        let sharper = RenderCommand::FillRoundedRect {
+            layer: LayerId::background().with_zindex(1),
+            rect: euclid::rect(0., 0., 4., 4.),
+            radius: 1.,
+            color: LinearRgba(0.5, 0.5, 0.5, 1.),
+            hsv: None,
+        };
+        assert_ne!(rect, sharper);
+        assert_ne!(
+            RenderCommand::content_hash(&[rect]),
+            RenderCommand::content_hash(&[sharper])
+        );
+    }
+
+    fn fill(sub_layer: usize, zindex: i8, x: f32) -> RenderCommand {
+        RenderCommand::FillRect {
+            layer: id(zindex, sub_layer),
+            rect: euclid::rect(x, 0., 1., 1.),
+            color: LinearRgba(1., 1., 1., 1.),
+            hsv: None,
+        }
+    }
+
+    fn xs(cmds: &[&RenderCommand]) -> Vec<f32> {
+        cmds.iter()
+            .map(|cmd| match cmd {
+                RenderCommand::FillRect { rect, .. } => rect.origin.x,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partition_groups_by_buffer_in_order() {
+        let commands = vec![
+            fill(1, 0, 0.),
+            fill(0, 0, 1.),
+            RenderCommand::Batch(vec![fill(1, 0, 2.), RenderCommand::Nop, fill(0, 5, 3.)]),
+            fill(1, 0, 4.),
+            fill(0, -1, 5.),
+        ];
+        let groups = RenderCommand::partition(&commands);
+        let keys: Vec<LayerId> = groups.keys().copied().collect();
+        assert_eq!(keys, vec![id(-1, 0), id(0, 0), id(0, 1), id(5, 0)]);
+        assert_eq!(xs(&groups[&id(0, 1)]), vec![0., 2., 4.]);
+        assert_eq!(xs(&groups[&id(0, 0)]), vec![1.]);
+        assert_eq!(xs(&groups[&id(5, 0)]), vec![3.]);
+        assert_eq!(xs(&groups[&id(-1, 0)]), vec![5.]);
+    }
+
+    #[test]
+    fn partition_runs_are_split_at_barriers() {
+        let commands = vec![
+            fill(0, 0, 0.),
+            fill(1, 0, 1.),
+            RenderCommand::Batch(vec![fill(0, 0, 2.), RenderCommand::SetClipRect(None)]),
+            fill(0, 0, 3.),
+            RenderCommand::BeginPostProcess {
+                region: None,
+                effect: None,
+            },
+            RenderCommand::Nop,
+        ];
+        let runs = RenderCommand::partition_runs(&commands);
+        assert_eq!(runs.len(), 2);
+
+        assert_eq!(xs(&runs[0].groups[&id(0, 0)]), vec![0., 2.]);
+        assert_eq!(xs(&runs[0].groups[&id(0, 1)]), vec![1.]);
+        assert!(matches!(
+            runs[0].barrier,
+            Some(RenderCommand::SetClipRect(None))
+        ));
+
+        assert_eq!(runs[1].groups.len(), 1);
+        assert_eq!(xs(&runs[1].groups[&id(0, 0)]), vec![3.]);
+        assert!(matches!(
+            runs[1].barrier,
+            Some(RenderCommand::BeginPostProcess { .. })
+        ));
+
+        // A trailing run without a barrier is kept
+        let runs = RenderCommand::partition_runs(&commands[..2]);
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].barrier.is_none());
+    }
+}
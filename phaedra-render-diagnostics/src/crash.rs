@@ -0,0 +1,307 @@
+//! Reports written when the GUI panics or loses its GPU device.
+//!
+//! A report is a tar archive, in the `crash-reports` directory of the
+//! cache directory, that holds what was known about the render state
+//! at the time: the hashes of the most recent frames, a summary of the
+//! last render plan, the GPU adapter, the configuration generation and
+//! how the configuration differs from the defaults, and the most recent
+//! log lines.  `phaedra diag last-report` prints the path of the most
+//! recent one.
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Reports are named `crash-report-<timestamp>-<pid>.tar`
+pub const REPORT_FILE_PREFIX: &str = "crash-report-";
+pub const REPORT_FILE_EXTENSION: &str = "tar";
+
+/// The least time between two reports written by the same process
+pub const MIN_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+/// How many frame hashes and log lines a report holds
+pub const MAX_FRAME_HASHES: usize = 32;
+pub const MAX_LOG_LINES: usize = 200;
+
+/// The size limits of the files in a report.  A file that is larger
+/// is truncated: the log loses its oldest lines, and the others lose
+/// their end.
+const MAX_SUMMARY_BYTES: usize = 64 * 1024;
+const MAX_CONFIG_DIFF_BYTES: usize = 256 * 1024;
+const MAX_LOG_BYTES: usize = 512 * 1024;
+const TRUNCATED: &str = "[truncated]\n";
+
+/// Returns the directory that reports are written to
+pub fn report_dir(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("crash-reports")
+}
+
+pub fn report_file_name(timestamp: &str, pid: u32) -> String {
+    format!("{REPORT_FILE_PREFIX}{timestamp}-{pid}.{REPORT_FILE_EXTENSION}")
+}
+
+/// What went into a report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrashContext {
+    /// What prompted the report, such as the panic message
+    pub reason: String,
+    pub version: String,
+    /// When the report was made, in local time
+    pub timestamp: String,
+    /// The hashes of the most recently painted frames, oldest first
+    pub frame_hashes: Vec<u64>,
+    /// A summary of the render plan of the most recent frame
+    pub render_plan: Option<String>,
+    /// The GPU adapter and backend in use
+    pub adapter: Option<String>,
+    pub config_generation: usize,
+    /// The configuration options that differ from their defaults, as
+    /// `name = value` lines
+    pub config_diff: Vec<String>,
+    /// The most recent log lines, oldest first
+    pub log_lines: Vec<String>,
+}
+
+impl CrashContext {
+    /// Returns the files of the report, as names and contents, with
+    /// each file limited in size
+    pub fn report_files(&self) -> Vec<(&'static str, String)> {
+        let mut summary = String::new();
+        summary.push_str(&format!("reason: {}\n", self.reason));
+        summary.push_str(&format!("version: {}\n", self.version));
+        summary.push_str(&format!("time: {}\n", self.timestamp));
+        summary.push_str(&format!(
+            "adapter: {}\n",
+            self.adapter.as_deref().unwrap_or("unknown")
+        ));
+        summary.push_str(&format!("config generation: {}\n", self.config_generation));
+        summary.push_str(&format!(
+            "render plan: {}\n",
+            self.render_plan.as_deref().unwrap_or("none")
+        ));
+        summary.push_str("recent frame hashes, oldest first:\n");
+        let skip = self.frame_hashes.len().saturating_sub(MAX_FRAME_HASHES);
+        for hash in &self.frame_hashes[skip..] {
+            summary.push_str(&format!("  {hash:016x}\n"));
+        }
+
+        let mut config_diff = String::new();
+        for line in &self.config_diff {
+            config_diff.push_str(line);
+            config_diff.push('\n');
+        }
+
+        let mut log = String::new();
+        let skip = self.log_lines.len().saturating_sub(MAX_LOG_LINES);
+        for line in &self.log_lines[skip..] {
+            log.push_str(line);
+            log.push('\n');
+        }
+
+        vec![
+            ("summary.txt", truncate_end(summary, MAX_SUMMARY_BYTES)),
+            (
+                "config-diff.txt",
+                truncate_end(config_diff, MAX_CONFIG_DIFF_BYTES),
+            ),
+            ("log.txt", truncate_start(log, MAX_LOG_BYTES)),
+        ]
+    }
+}
+
+/// Limits `text` to `max` bytes by removing its end
+fn truncate_end(mut text: String, max: usize) -> String {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max.saturating_sub(TRUNCATED.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(TRUNCATED);
+    text
+}
+
+/// Limits `text` to `max` bytes by removing its start, keeping whole
+/// lines
+fn truncate_start(text: String, max: usize) -> String {
+    if text.len() <= max {
+        return text;
+    }
+    let mut start = text.len() - max.saturating_sub(TRUNCATED.len());
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = match text[start..].find('\n') {
+        Some(newline) => start + newline + 1,
+        None => text.len(),
+    };
+    format!("{TRUNCATED}{}", &text[start..])
+}
+
+/// Decides whether a report may be written, so that a process that
+/// panics repeatedly doesn't fill the disk
+#[derive(Debug, Default)]
+pub struct ReportThrottle {
+    last_report: Option<Instant>,
+}
+
+impl ReportThrottle {
+    /// Returns true, and counts a report as written at `now`, if no
+    /// report has been written within `MIN_REPORT_INTERVAL` of `now`
+    pub fn permit(&mut self, now: Instant) -> bool {
+        if let Some(last) = self.last_report {
+            if now.saturating_duration_since(last) < MIN_REPORT_INTERVAL {
+                return false;
+            }
+        }
+        self.last_report.replace(now);
+        true
+    }
+}
+
+/// Writes the report for `context` to `dir`, creating it if needed,
+/// and returns the path of the report
+pub fn write_report(
+    dir: &Path,
+    file_name: &str,
+    context: &CrashContext,
+) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = dir.join(file_name);
+    let file =
+        std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+    let mut builder = tar::Builder::new(file);
+    for (name, contents) in context.report_files() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, contents.as_bytes())
+            .with_context(|| format!("writing {name} to {}", path.display()))?;
+    }
+    builder
+        .into_inner()
+        .with_context(|| format!("finishing {}", path.display()))?;
+    Ok(path)
+}
+
+/// Returns the most recently written report in `dir`
+pub fn latest_report(dir: &Path) -> anyhow::Result<PathBuf> {
+    crate::latest_file(dir, REPORT_FILE_PREFIX, REPORT_FILE_EXTENSION)?
+        .ok_or_else(|| anyhow::anyhow!("no crash reports found in {}", dir.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    fn context() -> CrashContext {
+        CrashContext {
+            reason: "panic: index out of bounds".to_string(),
+            version: "20261015-000000-abcdef".to_string(),
+            timestamp: "2026-10-15 03:00:00".to_string(),
+            frame_hashes: (0..40).collect(),
+            render_plan: Some("4 sections in 2 batches".to_string()),
+            adapter: None,
+            config_generation: 3,
+            config_diff: vec!["font_size = 13".to_string()],
+            log_lines: (0..250).map(|n| format!("line {n}")).collect(),
+        }
+    }
+
+    #[test]
+    fn report_contents() {
+        let files = context().report_files();
+        let names: Vec<&str> = files.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["summary.txt", "config-diff.txt", "log.txt"]);
+
+        let summary = &files[0].1;
+        assert!(summary.starts_with(
+            "reason: panic: index out of bounds\n\
+             version: 20261015-000000-abcdef\n\
+             time: 2026-10-15 03:00:00\n\
+             adapter: unknown\n\
+             config generation: 3\n\
+             render plan: 4 sections in 2 batches\n\
+             recent frame hashes, oldest first:\n  0000000000000008\n"
+        ));
+        assert!(summary.ends_with("  0000000000000027\n"));
+        assert_eq!(summary.lines().count(), 7 + MAX_FRAME_HASHES);
+
+        assert_eq!(files[1].1, "font_size = 13\n");
+
+        // Only the most recent log lines are kept
+        let log = &files[2].1;
+        assert_eq!(log.lines().count(), MAX_LOG_LINES);
+        assert!(log.starts_with("line 50\n"));
+        assert!(log.ends_with("line 249\n"));
+    }
+
+    #[test]
+    fn files_are_limited_in_size() {
+        let mut context = context();
+        context.reason = "é".repeat(MAX_SUMMARY_BYTES);
+        context.log_lines = vec!["x".repeat(MAX_LOG_BYTES / 2); 3];
+        let files = context.report_files();
+
+        let summary = &files[0].1;
+        assert!(summary.len() <= MAX_SUMMARY_BYTES);
+        assert!(summary.ends_with(TRUNCATED));
+
+        // The log keeps its most recent whole lines
+        let log = &files[2].1;
+        assert!(log.len() <= MAX_LOG_BYTES);
+        assert_eq!(
+            log,
+            &format!("{TRUNCATED}{}\n", "x".repeat(MAX_LOG_BYTES / 2))
+        );
+    }
+
+    #[test]
+    fn reports_are_rate_limited() {
+        let mut throttle = ReportThrottle::default();
+        let start = Instant::now();
+        assert!(throttle.permit(start));
+        assert!(!throttle.permit(start + Duration::from_secs(59)));
+        assert!(throttle.permit(start + MIN_REPORT_INTERVAL));
+    }
+
+    #[test]
+    fn write_and_find_report() {
+        let dir = report_dir(
+            &std::env::temp_dir().join(format!("phaedra-crash-report-{}", std::process::id())),
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(latest_report(&dir).is_err());
+
+        let path =
+            write_report(&dir, &report_file_name("20261015-030000", 42), &context()).unwrap();
+        assert_eq!(
+            path.file_name().unwrap(),
+            "crash-report-20261015-030000-42.tar"
+        );
+        assert_eq!(latest_report(&dir).unwrap(), path);
+
+        let mut archive = tar::Archive::new(std::fs::File::open(&path).unwrap());
+        let mut found = vec![];
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            found.push((name, contents));
+        }
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            found[1],
+            (
+                "config-diff.txt".to_string(),
+                "font_size = 13\n".to_string()
+            )
+        );
+
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+//! Per-frame traces of the decisions made by the render pipeline.
+//!
+//! When `runtime.render_diagnostics` is enabled, the GUI appends one
+//! `FrameTrace` per painted frame, as a line of JSON, to a file in the
+//! runtime directory.  `phaedra diag render` reads the most recent
+//! frames back and summarizes them with `summarize`, which is mostly
+//! useful for answering "why did this pane get re-described?".
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub mod crash;
+
+/// Trace files are named `render-diagnostics-<pid>.jsonl`
+pub const TRACE_FILE_PREFIX: &str = "render-diagnostics-";
+pub const TRACE_FILE_EXTENSION: &str = "jsonl";
+
+/// Once a trace file grows beyond this size it is moved aside to
+/// `<name>.1`, replacing any prior one, and a new file is started.
+const MAX_TRACE_FILE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// What happened to one pane during a frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaneTrace {
+    pub pane_id: usize,
+    /// The describe cache key; the pane is re-described when this
+    /// differs from the key of the prior frame
+    pub cache_key: u64,
+    /// The components of the cache key that are most often the reason
+    /// that it changed.  The key also covers the pane position, the
+    /// selection and the hovered hyperlink.
+    pub terminal_hash: u64,
+    pub config_generation: usize,
+    pub shape_generation: usize,
+    pub palette_hash: u64,
+    /// true if the render commands for the pane were described afresh
+    /// rather than reused from the prior frame
+    pub described: bool,
+    /// true if the quads of the prior frame were reused, so that the
+    /// commands weren't executed at all
+    pub skipped: bool,
+    pub commands: usize,
+    /// The number of quads that the pane occupies in this frame
+    pub quads: usize,
+    pub describe_us: u64,
+    pub execute_us: u64,
+}
+
+/// The time spent describing and executing one of the sections of the
+/// window chrome, such as the tab bar
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionTrace {
+    pub name: String,
+    pub duration_us: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameTrace {
+    /// Counts the frames painted by the window since tracing began
+    pub frame: u64,
+    pub window_id: usize,
+    /// Milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    pub panes: Vec<PaneTrace>,
+    pub chrome: Vec<SectionTrace>,
+    pub total_us: u64,
+}
+
+impl FrameTrace {
+    pub fn to_json_line(&self) -> anyhow::Result<String> {
+        serde_json::to_string(self).context("serializing frame trace")
+    }
+
+    pub fn from_json_line(line: &str) -> anyhow::Result<Self> {
+        serde_json::from_str(line).context("parsing frame trace")
+    }
+}
+
+pub fn trace_file_name(pid: u32) -> String {
+    format!("{TRACE_FILE_PREFIX}{pid}.{TRACE_FILE_EXTENSION}")
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Appends frame traces to a trace file
+pub struct TraceWriter {
+    path: PathBuf,
+    file: BufWriter<File>,
+    bytes_written: u64,
+}
+
+impl TraceWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: BufWriter::new(file),
+            bytes_written: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `frame` and flushes it out, so that the file can be
+    /// read while the GUI is still running
+    pub fn write_frame(&mut self, frame: &FrameTrace) -> anyhow::Result<()> {
+        if self.bytes_written >= MAX_TRACE_FILE_BYTES {
+            self.file.flush()?;
+            let rotated = rotated_path(&self.path);
+            std::fs::rename(&self.path, &rotated).with_context(|| {
+                format!("renaming {} to {}", self.path.display(), rotated.display())
+            })?;
+            *self = Self::create(&self.path)?;
+        }
+        let line = frame.to_json_line()?;
+        writeln!(self.file, "{line}")?;
+        self.file.flush()?;
+        self.bytes_written += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Read the last `count` frames from the trace file at `path`, taking
+/// older frames from its rotated predecessor if necessary.  Lines that
+/// cannot be parsed, such as one that was still being written when the
+/// file was read, are ignored.
+pub fn read_last_frames(path: &Path, count: usize) -> anyhow::Result<Vec<FrameTrace>> {
+    let mut frames = VecDeque::with_capacity(count);
+    let rotated = rotated_path(path);
+    let sources = [rotated.as_path(), path];
+    for source in sources {
+        let file = match File::open(source) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && source != path => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("opening {}", source.display()));
+            }
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line.with_context(|| format!("reading {}", source.display()))?;
+            if let Ok(frame) = FrameTrace::from_json_line(&line) {
+                if frames.len() == count {
+                    frames.pop_front();
+                }
+                if count > 0 {
+                    frames.push_back(frame);
+                }
+            }
+        }
+    }
+    Ok(frames.into())
+}
+
+/// Returns the most recently modified trace file in `dir`
+pub fn latest_trace_file(dir: &Path) -> anyhow::Result<PathBuf> {
+    latest_file(dir, TRACE_FILE_PREFIX, TRACE_FILE_EXTENSION)?
+        .ok_or_else(|| anyhow::anyhow!("no render diagnostics found in {}", dir.display()))
+}
+
+/// Returns the most recently modified file in `dir` whose name has
+/// the given prefix and extension
+fn latest_file(dir: &Path, prefix: &str, extension: &str) -> anyhow::Result<Option<PathBuf>> {
+    let mut latest = None;
+    for entry in std::fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        let matches = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| {
+                name.starts_with(prefix)
+                    && path.extension().and_then(|ext| ext.to_str()) == Some(extension)
+            })
+            .unwrap_or(false);
+        if !matches {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        match &latest {
+            Some((when, _)) if *when >= modified => {}
+            _ => latest = Some((modified, path)),
+        }
+    }
+    Ok(latest.map(|(_, path)| path))
+}
+
+/// Why a pane was described afresh rather than reused from the prior
+/// frame.  When several of the cache key components changed at once,
+/// the first of these that applies is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DescribeReason {
+    /// The pane was not present in the prior traced frame
+    FirstSeen,
+    Config,
+    Shape,
+    Palette,
+    Terminal,
+    /// Something else that is part of the cache key changed, such as
+    /// the pane position, the selection or the hovered hyperlink
+    Other,
+}
+
+impl DescribeReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::FirstSeen => "first-seen",
+            Self::Config => "config",
+            Self::Shape => "shape",
+            Self::Palette => "palette",
+            Self::Terminal => "terminal",
+            Self::Other => "other",
+        }
+    }
+
+    fn between(prior: Option<&PaneTrace>, pane: &PaneTrace) -> Self {
+        match prior {
+            None => Self::FirstSeen,
+            Some(prior) if prior.config_generation != pane.config_generation => Self::Config,
+            Some(prior) if prior.shape_generation != pane.shape_generation => Self::Shape,
+            Some(prior) if prior.palette_hash != pane.palette_hash => Self::Palette,
+            Some(prior) if prior.terminal_hash != pane.terminal_hash => Self::Terminal,
+            Some(_) => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaneSummary {
+    pub pane_id: usize,
+    pub frames: usize,
+    pub described: usize,
+    pub skipped: usize,
+    pub reasons: BTreeMap<DescribeReason, usize>,
+    /// Averaged over the frames in which the pane was described
+    pub mean_describe_us: f64,
+    /// Averaged over the frames in which the pane was executed
+    pub mean_execute_us: f64,
+    pub max_quads: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionSummary {
+    pub name: String,
+    pub mean_us: f64,
+    pub max_us: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSummary {
+    pub frames: usize,
+    pub mean_frame_us: f64,
+    pub max_frame_us: u64,
+    /// Ordered by pane id
+    pub panes: Vec<PaneSummary>,
+    /// In the order in which the sections are painted
+    pub chrome: Vec<SectionSummary>,
+}
+
+fn mean(total: u64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+pub fn summarize(frames: &[FrameTrace]) -> RenderSummary {
+    #[derive(Default)]
+    struct PaneTotals {
+        frames: usize,
+        described: usize,
+        skipped: usize,
+        reasons: BTreeMap<DescribeReason, usize>,
+        describe_us: u64,
+        execute_us: u64,
+        max_quads: usize,
+    }
+
+    let mut panes: BTreeMap<usize, PaneTotals> = BTreeMap::new();
+    let mut prior_traces: BTreeMap<usize, &PaneTrace> = BTreeMap::new();
+    let mut chrome: Vec<(String, u64, usize, u64)> = vec![];
+
+    for frame in frames {
+        for pane in &frame.panes {
+            let totals = panes.entry(pane.pane_id).or_default();
+            totals.frames += 1;
+            totals.max_quads = totals.max_quads.max(pane.quads);
+            if pane.described {
+                totals.described += 1;
+                totals.describe_us += pane.describe_us;
+                let reason =
+                    DescribeReason::between(prior_traces.get(&pane.pane_id).copied(), pane);
+                *totals.reasons.entry(reason).or_insert(0) += 1;
+            }
+            if pane.skipped {
+                totals.skipped += 1;
+            } else {
+                totals.execute_us += pane.execute_us;
+            }
+            prior_traces.insert(pane.pane_id, pane);
+        }
+
+        for section in &frame.chrome {
+            match chrome.iter_mut().find(|(name, ..)| *name == section.name) {
+                Some((_, total, count, max)) => {
+                    *total += section.duration_us;
+                    *count += 1;
+                    *max = (*max).max(section.duration_us);
+                }
+                None => chrome.push((
+                    section.name.clone(),
+                    section.duration_us,
+                    1,
+                    section.duration_us,
+                )),
+            }
+        }
+    }
+
+    RenderSummary {
+        frames: frames.len(),
+        mean_frame_us: mean(frames.iter().map(|f| f.total_us).sum(), frames.len()),
+        max_frame_us: frames.iter().map(|f| f.total_us).max().unwrap_or(0),
+        panes: panes
+            .into_iter()
+            .map(|(pane_id, totals)| PaneSummary {
+                pane_id,
+                frames: totals.frames,
+                described: totals.described,
+                skipped: totals.skipped,
+                mean_describe_us: mean(totals.describe_us, totals.described),
+                mean_execute_us: mean(totals.execute_us, totals.frames - totals.skipped),
+                reasons: totals.reasons,
+                max_quads: totals.max_quads,
+            })
+            .collect(),
+        chrome: chrome
+            .into_iter()
+            .map(|(name, total, count, max_us)| SectionSummary {
+                name,
+                mean_us: mean(total, count),
+                max_us,
+            })
+            .collect(),
+    }
+}
+
+impl std::fmt::Display for RenderSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} frames, mean {:.0}us, max {}us",
+            self.frames, self.mean_frame_us, self.max_frame_us
+        )?;
+        writeln!(
+            f,
+            "{:>6} {:>7} {:>9} {:>8} {:>12} {:>12} {:>9}  describe reasons",
+            "pane", "frames", "described", "skipped", "describe(us)", "execute(us)", "max quads"
+        )?;
+        for pane in &self.panes {
+            let reasons = pane
+                .reasons
+                .iter()
+                .map(|(reason, count)| format!("{}={count}", reason.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:>6} {:>7} {:>9} {:>8} {:>12.0} {:>12.0} {:>9}  {}",
+                pane.pane_id,
+                pane.frames,
+                pane.described,
+                pane.skipped,
+                pane.mean_describe_us,
+                pane.mean_execute_us,
+                pane.max_quads,
+                reasons
+            )?;
+        }
+        if !self.chrome.is_empty() {
+            writeln!(f, "chrome:")?;
+            for section in &self.chrome {
+                writeln!(
+                    f,
+                    "  {:<10} mean {:.0}us, max {}us",
+                    section.name, section.mean_us, section.max_us
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pane(pane_id: usize, terminal_hash: u64, described: bool, skipped: bool) -> PaneTrace {
+        PaneTrace {
+            pane_id,
+            cache_key: terminal_hash,
+            terminal_hash,
+            config_generation: 1,
+            shape_generation: 1,
+            palette_hash: 7,
+            described,
+            skipped,
+            commands: 10,
+            quads: 100,
+            describe_us: if described { 300 } else { 0 },
+            execute_us: if skipped { 0 } else { 50 },
+        }
+    }
+
+    fn frame(frame: u64, panes: Vec<PaneTrace>) -> FrameTrace {
+        FrameTrace {
+            frame,
+            window_id: 0,
+            timestamp_ms: 1_700_000_000_000 + frame,
+            panes,
+            chrome: vec![
+                SectionTrace {
+                    name: "tab_bar".to_string(),
+                    duration_us: 10 * frame,
+                },
+                SectionTrace {
+                    name: "modal".to_string(),
+                    duration_us: 1,
+                },
+            ],
+            total_us: 1000 + 2 * frame,
+        }
+    }
+
+    #[test]
+    fn serialization_format() {
+        let trace = frame(3, vec![pane(1, 42, true, false)]);
+        let line = trace.to_json_line().unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(
+            line,
+            "{\"frame\":3,\"window_id\":0,\"timestamp_ms\":1700000000003,\
+             \"panes\":[{\"pane_id\":1,\"cache_key\":42,\"terminal_hash\":42,\
+             \"config_generation\":1,\"shape_generation\":1,\"palette_hash\":7,\
+             \"described\":true,\"skipped\":false,\"commands\":10,\"quads\":100,\
+             \"describe_us\":300,\"execute_us\":50}],\
+             \"chrome\":[{\"name\":\"tab_bar\",\"duration_us\":30},\
+             {\"name\":\"modal\",\"duration_us\":1}],\"total_us\":1006}"
+        );
+        assert_eq!(FrameTrace::from_json_line(&line).unwrap(), trace);
+    }
+
+    #[test]
+    fn read_back_last_frames() {
+        let dir = std::env::temp_dir().join(format!("phaedra-render-diag-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(trace_file_name(1234));
+
+        let mut writer = TraceWriter::create(&path).unwrap();
+        for n in 0..5 {
+            writer
+                .write_frame(&frame(n, vec![pane(1, n, true, false)]))
+                .unwrap();
+        }
+        drop(writer);
+        // A partially written line is ignored
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"frame\":5,")
+            .unwrap();
+
+        let frames = read_last_frames(&path, 3).unwrap();
+        assert_eq!(
+            frames.iter().map(|f| f.frame).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(read_last_frames(&path, 10).unwrap().len(), 5);
+        assert_eq!(latest_trace_file(&dir).unwrap(), path);
+
+        // Older frames come from the rotated file
+        std::fs::rename(&path, rotated_path(&path)).unwrap();
+        let mut writer = TraceWriter::create(&path).unwrap();
+        writer.write_frame(&frame(5, vec![])).unwrap();
+        let frames = read_last_frames(&path, 3).unwrap();
+        assert_eq!(
+            frames.iter().map(|f| f.frame).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summarizer() {
+        let mut palette_change = pane(1, 11, true, false);
+        palette_change.palette_hash = 8;
+        let mut config_change = pane(1, 12, true, false);
+        config_change.config_generation = 2;
+        config_change.palette_hash = 8;
+
+        let frames = vec![
+            frame(1, vec![pane(1, 10, true, false), pane(2, 20, true, false)]),
+            frame(2, vec![pane(1, 10, false, true), pane(2, 21, true, false)]),
+            frame(3, vec![palette_change, pane(2, 21, true, false)]),
+            frame(4, vec![config_change, pane(2, 21, false, false)]),
+        ];
+        let summary = summarize(&frames);
+        assert_eq!(summary.frames, 4);
+        assert_eq!(summary.mean_frame_us, 1005.0);
+        assert_eq!(summary.max_frame_us, 1008);
+
+        let first = &summary.panes[0];
+        assert_eq!(first.pane_id, 1);
+        assert_eq!((first.frames, first.described, first.skipped), (4, 3, 1));
+        assert_eq!(
+            first.reasons,
+            vec![
+                (DescribeReason::FirstSeen, 1),
+                (DescribeReason::Config, 1),
+                (DescribeReason::Palette, 1),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(first.mean_describe_us, 300.0);
+        assert_eq!(first.mean_execute_us, 50.0);
+
+        let second = &summary.panes[1];
+        assert_eq!((second.frames, second.described, second.skipped), (4, 3, 0));
+        assert_eq!(
+            second.reasons,
+            vec![
+                (DescribeReason::FirstSeen, 1),
+                (DescribeReason::Terminal, 1),
+                (DescribeReason::Other, 1),
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        assert_eq!(
+            summary.chrome,
+            vec![
+                SectionSummary {
+                    name: "tab_bar".to_string(),
+                    mean_us: 25.0,
+                    max_us: 40,
+                },
+                SectionSummary {
+                    name: "modal".to_string(),
+                    mean_us: 1.0,
+                    max_us: 1,
+                },
+            ]
+        );
+
+        let text = summary.to_string();
+        assert!(text.starts_with("4 frames, mean 1005us, max 1008us\n"));
+        assert!(text.contains("first-seen=1 config=1 palette=1"));
+        assert!(text.contains("  tab_bar    mean 25us, max 40us"));
+
+        assert_eq!(summarize(&[]).panes, vec![]);
+    }
+}
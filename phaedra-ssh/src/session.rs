@@ -175,6 +175,12 @@ impl Session {
         Ok(exec)
     }
 
+    /// Returns true if `other` refers to the same underlying
+    /// connection as this session
+    pub fn same_session(&self, other: &Session) -> bool {
+        Arc::ptr_eq(&self.tx.pipe, &other.tx.pipe)
+    }
+
     /// Creates a new reference to the sftp channel for filesystem operations
     ///
     /// ### Note
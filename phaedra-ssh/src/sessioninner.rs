@@ -398,8 +398,17 @@ impl SessionInner {
                 .with_context(|| format!("binding to {bind_addr:?}"))?;
         }
 
-        sock.connect(&addr.into())
-            .with_context(|| format!("Connecting to {hostname}:{port} ({addr:?})"))?;
+        let connect_timeout = self
+            .config
+            .get("connecttimeout")
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs);
+        match connect_timeout {
+            Some(timeout) => sock.connect_timeout(&addr.into(), timeout),
+            None => sock.connect(&addr.into()),
+        }
+        .with_context(|| format!("Connecting to {hostname}:{port} ({addr:?})"))?;
         Ok((sock, None))
     }
 
@@ -18,7 +18,7 @@ use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 
-pub use phaedra_escape_parser::hyperlink::Hyperlink;
+pub use phaedra_escape_parser::hyperlink::{Hyperlink, HyperlinkStyle};
 
 /// In addition to handling explicit escape sequences to enable
 /// hyperlinks, we also support defining rules that match text
@@ -59,6 +59,12 @@ pub struct Rule {
     /// Which capture to highlight
     #[dynamic(default)]
     pub highlight: usize,
+
+    /// How to render the links that this rule produces.  When not
+    /// set, they are rendered in the same way as the text around them.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub style: Option<HyperlinkStyle>,
 }
 
 struct RegexWrap(Regex);
@@ -119,9 +125,25 @@ pub struct RuleMatch {
     pub link: Arc<Hyperlink>,
 }
 
+/// Describes a match made by `Rule::test_hyperlinks`, so that a
+/// set of rules can be checked against some sample text.
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct RuleTestMatch {
+    /// The index of the rule that made the match
+    pub rule_index: usize,
+    /// The span (measured in bytes) of the matched text
+    pub start: usize,
+    pub end: usize,
+    /// The matched text
+    pub text: String,
+    /// The URL that the rule's format string expanded to
+    pub url: String,
+}
+
 /// An internal intermediate match result
 #[derive(Debug)]
 struct Match<'t> {
+    rule_index: usize,
     rule: &'t Rule,
     captures: Captures<'t>,
 }
@@ -179,17 +201,21 @@ impl Rule {
             regex: Regex::new(regex)?,
             format: format.to_owned(),
             highlight,
+            style: None,
         })
     }
 
-    /// Given a line of text from the terminal screen, and a set of
-    /// rules, return the set of RuleMatches.
-    pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
+    /// Collect the matches that `rules` make in `line`, longest first.
+    fn matches<'t>(line: &'t str, rules: &'t [Rule]) -> Vec<Match<'t>> {
         let mut matches = Vec::new();
-        for rule in rules.iter() {
+        for (rule_index, rule) in rules.iter().enumerate() {
             for capture_result in rule.regex.captures_iter(line) {
                 if let Ok(captures) = capture_result {
-                    let m = Match { rule, captures };
+                    let m = Match {
+                        rule_index,
+                        rule,
+                        captures,
+                    };
                     if m.highlight().is_some() {
                         matches.push(m);
                     }
@@ -200,12 +226,17 @@ impl Rule {
         // This is to avoid confusion if multiple rules match the
         // same sections of text.
         matches.sort_by(|a, b| b.len().cmp(&a.len()));
-
         matches
+    }
+
+    /// Given a line of text from the terminal screen, and a set of
+    /// rules, return the set of RuleMatches.
+    pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
+        Self::matches(line, rules)
             .into_iter()
             .map(|m| {
                 let url = m.expand();
-                let link = Arc::new(Hyperlink::new_implicit(url));
+                let link = Arc::new(Hyperlink::new_implicit_with_style(url, m.rule.style));
                 RuleMatch {
                     link,
                     range: m.range(),
@@ -213,6 +244,24 @@ impl Rule {
             })
             .collect()
     }
+
+    /// Run `rules` against `line` and describe each of the matches,
+    /// in the order of preference that `match_hyperlinks` uses.
+    pub fn test_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleTestMatch> {
+        Self::matches(line, rules)
+            .into_iter()
+            .map(|m| {
+                let range = m.range();
+                RuleTestMatch {
+                    rule_index: m.rule_index,
+                    start: range.start,
+                    end: range.end,
+                    text: line[range].to_string(),
+                    url: m.expand(),
+                }
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -250,6 +299,54 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_expands_captures() {
+        let rules = vec![
+            Rule::new(r"\b\w+://\S+", "$0").unwrap(),
+            Rule::with_highlight(r"\b(ISSUE|BUG)-(\d+)\b", "https://example.com/$1/$2", 2).unwrap(),
+        ];
+
+        assert_eq!(
+            Rule::test_hyperlinks("see BUG-123 and https://example.com", &rules),
+            vec![
+                RuleTestMatch {
+                    rule_index: 0,
+                    start: 16,
+                    end: 35,
+                    text: "https://example.com".to_string(),
+                    url: "https://example.com".to_string(),
+                },
+                RuleTestMatch {
+                    rule_index: 1,
+                    start: 8,
+                    end: 11,
+                    text: "123".to_string(),
+                    url: "https://example.com/BUG/123".to_string(),
+                },
+            ]
+        );
+        assert!(Rule::test_hyperlinks("nothing to see", &rules).is_empty());
+    }
+
+    #[test]
+    fn style_is_carried_by_link() {
+        let style = HyperlinkStyle {
+            underline: Some(phaedra_cell::Underline::Dashed),
+            hover_color: Some(phaedra_escape_parser::color::RgbColor::new_8bpc(255, 0, 0)),
+            ..Default::default()
+        };
+        let mut rule = Rule::new(r"\bT\d+\b", "https://example.com/$0").unwrap();
+        rule.style = Some(style);
+
+        let matches = Rule::match_hyperlinks("fixes T42", &[rule]);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].link.style(), Some(&style));
+        assert_ne!(
+            *matches[0].link,
+            Hyperlink::new_implicit("https://example.com/T42")
+        );
+    }
+
     #[test]
     fn parse_with_parentheses() {
         fn assert_helper(test_uri: &str, expected_uri: &str, msg: &str) {
@@ -240,6 +240,10 @@ impl Line {
                 );
             }
 
+            if self.is_prompt_start() {
+                lines[0].set_prompt_start(true, seqno);
+            }
+
             lines
         } else {
             vec![self]
@@ -364,6 +368,20 @@ impl Line {
         self.update_last_change_seqno(seqno);
     }
 
+    /// Returns true if the line is the first line of a prompt, as
+    /// marked by OSC 133
+    #[inline]
+    pub fn is_prompt_start(&self) -> bool {
+        self.bits.contains(LineBits::PROMPT_START)
+    }
+
+    /// Set a flag to indicate whether the line is the first line of a
+    /// prompt
+    pub fn set_prompt_start(&mut self, prompt_start: bool, seqno: SequenceNo) {
+        self.bits.set(LineBits::PROMPT_START, prompt_start);
+        self.update_last_change_seqno(seqno);
+    }
+
     /// Set a flag the indicate whether the line should have the bidi
     /// algorithm applied during rendering
     pub fn set_bidi_enabled(&mut self, enabled: bool, seqno: SequenceNo) {
@@ -649,7 +667,7 @@ impl Line {
         let idx = idx.min(my_cells.len());
         let cells = my_cells.split_off(idx);
         Self {
-            bits: self.bits,
+            bits: self.bits - LineBits::PROMPT_START,
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno,
             zones: vec![],
@@ -49,5 +49,10 @@ bitflags! {
         /// Otherwise, the auto-detect direction is used, falling back
         /// to the direction specified by the RTL bit.
         const AUTO_DETECT_DIRECTION = 1<<8;
+
+        /// true if an OSC 133 `A` (or `N`) mark was received while
+        /// the cursor was on this line, making it the first line
+        /// of a prompt
+        const PROMPT_START = 1<<9;
     }
 }
@@ -23,6 +23,25 @@ fn append_line() {
     assert_eq!(line1.len(), 20);
 }
 
+#[test]
+fn prompt_start_stays_on_first_line() {
+    let mut line: Line = "0123456789".into();
+    line.set_prompt_start(true, SEQ_ZERO);
+
+    let wrapped = line.clone().wrap(4, SEQ_ZERO);
+    assert_eq!(
+        wrapped
+            .iter()
+            .map(|line| line.is_prompt_start())
+            .collect::<Vec<_>>(),
+        vec![true, false, false]
+    );
+
+    let tail = line.split_off(5, SEQ_ZERO);
+    assert!(line.is_prompt_start());
+    assert!(!tail.is_prompt_start());
+}
+
 #[test]
 fn hyperlinks() {
     let text = "❤ 😍🤢 http://example.com \u{1f468}\u{1f3fe}\u{200d}\u{1f9b0} http://example.com";
@@ -395,6 +395,7 @@ impl RecordCommand {
             },
             config.launch().default_prog.as_ref(),
             self.cwd.as_ref().or(config.launch().default_cwd.as_ref()),
+            None,
         )?;
 
         let mut child = pair.slave.spawn_command(cmd)?;
@@ -33,6 +33,7 @@ impl ProxyCommand {
         let pdu = Pdu::SetClientId(SetClientId {
             client_id: ClientId::new(),
             is_proxy: true,
+            read_only: false,
         });
         let serial = 1;
         pdu.encode(&mut stream, serial)?;
@@ -0,0 +1,30 @@
+use clap::{Parser, ValueEnum};
+use config::Config;
+
+#[derive(Copy, Clone, Debug, ValueEnum, Default)]
+enum SchemaFormat {
+    /// A JSON Schema, for editors that validate against one
+    #[default]
+    Json,
+    /// `---@class` annotations for lua-language-server
+    Lua,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct ConfigSchemaCommand {
+    /// Which form of the schema to print
+    #[arg(long, default_value = "json")]
+    format: SchemaFormat,
+}
+
+impl ConfigSchemaCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match self.format {
+            SchemaFormat::Json => {
+                println!("{}", serde_json::to_string_pretty(&Config::json_schema())?)
+            }
+            SchemaFormat::Lua => print!("{}", Config::lua_type_definitions()),
+        }
+        Ok(())
+    }
+}
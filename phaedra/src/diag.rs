@@ -0,0 +1,73 @@
+use clap::{Parser, ValueHint};
+use phaedra_render_diagnostics::crash::{latest_report, report_dir};
+use phaedra_render_diagnostics::{latest_trace_file, read_last_frames, summarize};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser, Clone)]
+pub struct DiagCommand {
+    #[command(subcommand)]
+    sub: DiagSubCommand,
+}
+
+#[derive(Debug, Parser, Clone)]
+enum DiagSubCommand {
+    #[command(
+        name = "render",
+        about = "Summarize the render trace recorded while runtime.render_diagnostics is enabled"
+    )]
+    Render(RenderDiagCommand),
+
+    #[command(
+        name = "last-report",
+        about = "Print the path of the most recent crash report, which is written when the GUI panics or loses its GPU"
+    )]
+    LastReport,
+}
+
+#[derive(Debug, Parser, Clone)]
+struct RenderDiagCommand {
+    /// How many of the most recently painted frames to consider
+    #[arg(long, default_value = "100")]
+    last_frames: usize,
+
+    /// The trace file to read.  The default is the most recently
+    /// written trace in the runtime directory.
+    #[arg(long, value_hint=ValueHint::FilePath)]
+    file: Option<PathBuf>,
+
+    /// Print the frames as JSON lines, rather than summarizing them
+    #[arg(long)]
+    raw: bool,
+}
+
+impl DiagCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        match &self.sub {
+            DiagSubCommand::Render(cmd) => cmd.run(),
+            DiagSubCommand::LastReport => {
+                let path = latest_report(&report_dir(&config::CACHE_DIR))?;
+                println!("{}", path.display());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl RenderDiagCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let path = match &self.file {
+            Some(path) => path.clone(),
+            None => latest_trace_file(&config::RUNTIME_DIR)?,
+        };
+        let frames = read_last_frames(&path, self.last_frames)?;
+        if self.raw {
+            for frame in &frames {
+                println!("{}", frame.to_json_line()?);
+            }
+        } else {
+            println!("{}", path.display());
+            print!("{}", summarize(&frames));
+        }
+        Ok(())
+    }
+}
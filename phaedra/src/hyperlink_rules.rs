@@ -0,0 +1,31 @@
+use clap::Parser;
+use config::ConfigHandle;
+use termwiz::hyperlink::Rule;
+
+#[derive(Debug, Parser, Clone)]
+pub struct TestHyperlinkRulesCommand {
+    /// The text to run the configured hyperlink_rules against
+    text: String,
+}
+
+impl TestHyperlinkRulesCommand {
+    pub fn run(&self, config: ConfigHandle) -> anyhow::Result<()> {
+        let rules = &config.terminal_features().hyperlink_rules;
+        let matches = Rule::test_hyperlinks(&self.text, rules);
+        if matches.is_empty() {
+            println!("None of the {} rules matched", rules.len());
+        }
+        // Rules are numbered from 1, as they are in the config file
+        for m in matches {
+            println!(
+                "hyperlink_rules[{}] matched {:?} at bytes {}..{} -> {}",
+                m.rule_index + 1,
+                m.text,
+                m.start,
+                m.end,
+                m.url
+            );
+        }
+        Ok(())
+    }
+}
@@ -19,6 +19,10 @@ use phaedra_gui_subcommands::*;
 
 mod asciicast;
 mod cli;
+mod config_schema;
+mod diag;
+mod hyperlink_rules;
+mod replay_escapes;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 
@@ -50,6 +54,16 @@ pub struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Override specific configuration values with JSON, which is
+    /// merged into the configuration without being evaluated as lua.
+    /// The name may be a dotted path, such as `font_config.font_size`
+    #[arg(
+        long = "config-json",
+        name = "name=json",
+        value_parser=ValueParser::new(name_equals_value),
+        number_of_values = 1)]
+    config_json_override: Vec<(String, String)>,
+
     #[command(subcommand)]
     cmd: Option<SubCommand>,
 }
@@ -114,6 +128,15 @@ enum SubCommand {
     #[command(name = "show-keys", about = "Show key assignments")]
     ShowKeys(ShowKeysCommand),
 
+    #[command(name = "ls-gpus", about = "Display information about GPU adapters")]
+    LsGpus(LsGpusCommand),
+
+    #[command(
+        name = "replay-frames",
+        about = "Replay the render commands recorded while runtime.record_frames is configured"
+    )]
+    ReplayFrames(ReplayFramesCommand),
+
     #[command(name = "cli", about = "Interact with experimental mux server")]
     Cli(cli::CliCommand),
 
@@ -133,6 +156,28 @@ enum SubCommand {
     #[command(name = "replay", about = "Replay an asciicast terminal session")]
     Replay(asciicast::PlayCommand),
 
+    #[command(
+        name = "replay-escapes",
+        about = "Replay a log of the output of a pane, written by TogglePaneEscapeLog, \
+                 and print the resulting screen"
+    )]
+    ReplayEscapes(replay_escapes::ReplayEscapesCommand),
+
+    #[command(name = "diag", about = "Inspect diagnostics recorded by the GUI")]
+    Diag(diag::DiagCommand),
+
+    #[command(
+        name = "config-schema",
+        about = "Print a schema of the configuration for editor completion"
+    )]
+    ConfigSchema(config_schema::ConfigSchemaCommand),
+
+    #[command(
+        name = "test-hyperlink-rules",
+        about = "Show the matches that the configured hyperlink_rules make in some text"
+    )]
+    TestHyperlinkRules(hyperlink_rules::TestHyperlinkRulesCommand),
+
     /// Generate shell completion information
     #[command(name = "shell-completion")]
     ShellCompletion {
@@ -717,6 +762,7 @@ fn init_config(opts: &Opt) -> anyhow::Result<ConfigHandle> {
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
+        &opts.config_json_override,
         opts.skip_config,
     )
     .context("config::common_init")?;
@@ -745,6 +791,8 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::BlockingStart(_)
         | SubCommand::LsFonts(_)
         | SubCommand::ShowKeys(_)
+        | SubCommand::LsGpus(_)
+        | SubCommand::ReplayFrames(_)
         | SubCommand::Ssh(_)
         | SubCommand::Connect(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
@@ -752,6 +800,10 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Cli(cli) => cli::run_cli(&opts, cli),
         SubCommand::Record(cmd) => cmd.run(init_config(&opts)?),
         SubCommand::Replay(cmd) => cmd.run(),
+        SubCommand::ReplayEscapes(cmd) => cmd.run(),
+        SubCommand::Diag(cmd) => cmd.run(),
+        SubCommand::ConfigSchema(cmd) => cmd.run(),
+        SubCommand::TestHyperlinkRules(cmd) => cmd.run(init_config(&opts)?),
         SubCommand::ShellCompletion { shell } => {
             use clap::CommandFactory;
             let mut cmd = Opt::command();
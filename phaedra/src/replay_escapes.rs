@@ -0,0 +1,29 @@
+use clap::{Parser, ValueHint};
+use mux::escape_log::replay_file;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser, Clone)]
+pub struct ReplayEscapesCommand {
+    /// Print the screen as JSON, including the colors and attributes
+    /// of the text, rather than as plain text
+    #[arg(long)]
+    json: bool,
+
+    /// The log to replay
+    #[arg(value_hint=ValueHint::FilePath)]
+    file: PathBuf,
+}
+
+impl ReplayEscapesCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let region = replay_file(&self.file)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&region)?);
+        } else {
+            for line in &region.lines {
+                println!("{}", line.text());
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,11 +2,15 @@
 
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::result::Result;
+use std::sync::{Arc, Mutex, Weak};
 pub use phaedra_cell::color::{AnsiColor, ColorAttribute, RgbColor, SrgbaTuple};
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Palette256(pub [SrgbaTuple; 256]);
 
 #[cfg(feature = "use_serde")]
@@ -43,7 +47,7 @@ impl std::iter::FromIterator<SrgbaTuple> for Palette256 {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub struct ColorPalette {
     pub colors: Palette256,
@@ -69,6 +73,24 @@ impl fmt::Debug for Palette256 {
 }
 
 impl ColorPalette {
+    /// Returns a shared palette equal to this one.  While a palette
+    /// that was interned is alive, interning an equal palette returns
+    /// it again rather than making another copy.
+    pub fn intern(self) -> Arc<ColorPalette> {
+        Self::intern_arc(Arc::new(self))
+    }
+
+    /// Like `intern`, but adopts `palette` as the shared palette if
+    /// there is no equal palette in use
+    pub fn intern_arc(palette: Arc<ColorPalette>) -> Arc<ColorPalette> {
+        PALETTES.lock().unwrap().intern(palette)
+    }
+
+    /// Returns the number of distinct interned palettes that are in use
+    pub fn interned_count() -> usize {
+        PALETTES.lock().unwrap().len()
+    }
+
     pub fn resolve_fg(&self, color: ColorAttribute) -> SrgbaTuple {
         match color {
             ColorAttribute::Default => self.foreground,
@@ -89,6 +111,60 @@ impl ColorPalette {
 
 lazy_static::lazy_static! {
     static ref DEFAULT_PALETTE: ColorPalette = ColorPalette::compute_default();
+    static ref PALETTES: Mutex<PaletteCache> = Mutex::new(PaletteCache::default());
+}
+
+/// The palettes that are in use, keyed by their hash, so that equal
+/// palettes can share a single allocation rather than being copied
+/// for each pane that uses them.  Only weak references are held, so
+/// that a palette is freed once nothing else refers to it.
+#[derive(Default)]
+struct PaletteCache {
+    entries: HashMap<u64, Vec<Weak<ColorPalette>>>,
+    inserts_since_purge: usize,
+}
+
+impl PaletteCache {
+    /// How many palettes may be added before the entries of those that
+    /// have since been freed are removed
+    const PURGE_INTERVAL: usize = 64;
+
+    fn intern(&mut self, palette: Arc<ColorPalette>) -> Arc<ColorPalette> {
+        let mut hasher = DefaultHasher::new();
+        palette.hash(&mut hasher);
+        let bucket = self.entries.entry(hasher.finish()).or_default();
+
+        if let Some(existing) = bucket
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|existing| *existing == palette)
+        {
+            return existing;
+        }
+
+        bucket.push(Arc::downgrade(&palette));
+        self.inserts_since_purge += 1;
+        if self.inserts_since_purge >= Self::PURGE_INTERVAL {
+            self.purge();
+        }
+        palette
+    }
+
+    fn purge(&mut self) {
+        for bucket in self.entries.values_mut() {
+            bucket.retain(|palette| palette.strong_count() > 0);
+        }
+        self.entries.retain(|_, bucket| !bucket.is_empty());
+        self.inserts_since_purge = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.entries
+            .values()
+            .flatten()
+            .filter(|palette| palette.strong_count() > 0)
+            .count()
+    }
 }
 
 impl Default for ColorPalette {
@@ -191,3 +267,42 @@ impl ColorPalette {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn equal_palettes_are_shared() {
+        let mut red = ColorPalette::default();
+        red.background = SrgbaTuple(1., 0., 0., 1.);
+
+        let first = red.clone().intern();
+        let second = red.clone().intern();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let adopted = ColorPalette::intern_arc(Arc::new(red.clone()));
+        assert!(Arc::ptr_eq(&first, &adopted));
+
+        let mut blue = red.clone();
+        blue.background = SrgbaTuple(0., 0., 1., 1.);
+        let blue = blue.intern();
+        assert!(!Arc::ptr_eq(&first, &blue));
+        assert_ne!(*first, *blue);
+    }
+
+    #[test]
+    fn unused_palettes_are_freed() {
+        let mut palette = ColorPalette::default();
+        palette.foreground = SrgbaTuple(0.25, 0.5, 0.75, 1.);
+
+        let interned = palette.clone().intern();
+        let weak = Arc::downgrade(&interned);
+        drop(interned);
+        assert!(weak.upgrade().is_none());
+
+        // Once freed, an equal palette is interned afresh
+        let again = palette.clone().intern();
+        assert_eq!(*again, palette);
+    }
+}
@@ -3,6 +3,7 @@ use downcast_rs::{impl_downcast, Downcast};
 use phaedra_bidi::ParagraphDirectionHint;
 use phaedra_cell::UnicodeVersion;
 use phaedra_surface::{Line, SequenceNo};
+use std::sync::Arc;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NewlineCanon {
@@ -12,6 +13,27 @@ pub enum NewlineCanon {
     CarriageReturnAndLineFeed,
 }
 
+/// What the mouse wheel does while the alternate screen is active and
+/// the application hasn't enabled mouse reporting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlternateBufferWheelBehavior {
+    /// Send `speed` arrow key presses for each wheel tick, which is
+    /// equivalent to xterm's alternateScroll mode
+    SendArrows { speed: u8 },
+    /// Send nothing to the application; the embedding application
+    /// scrolls its viewport instead
+    ScrollHistory,
+    /// Report the wheel to the application as a mouse event, as though
+    /// it had enabled mouse reporting
+    PassThroughMouse,
+}
+
+impl Default for AlternateBufferWheelBehavior {
+    fn default() -> Self {
+        Self::SendArrows { speed: 3 }
+    }
+}
+
 impl NewlineCanon {
     fn target(self) -> Option<&'static str> {
         match self {
@@ -158,7 +180,10 @@ pub trait TerminalConfiguration: Downcast + std::fmt::Debug + Send + Sync {
     /// Various escape sequences can dynamically modify the effective
     /// color palette for a terminal instance at runtime, but this method
     /// defines the initial palette.
-    fn color_palette(&self) -> ColorPalette;
+    /// It is called frequently, so implementations should return a
+    /// palette that they have already interned rather than building
+    /// a new one each time.
+    fn color_palette(&self) -> Arc<ColorPalette>;
 
     fn canonicalize_pasted_newlines(&self) -> NewlineCanon {
         NewlineCanon::default()
@@ -168,6 +193,12 @@ pub trait TerminalConfiguration: Downcast + std::fmt::Debug + Send + Sync {
         3
     }
 
+    fn alternate_buffer_wheel_behavior(&self) -> AlternateBufferWheelBehavior {
+        AlternateBufferWheelBehavior::SendArrows {
+            speed: self.alternate_buffer_wheel_scroll_speed(),
+        }
+    }
+
     fn enq_answerback(&self) -> String {
         "".to_string()
     }
@@ -127,6 +127,50 @@ pub struct SemanticZone {
     pub semantic_type: SemanticType,
 }
 
+/// A command, as delimited by OSC 133 marks: it begins on the line
+/// holding an `A` mark and runs until the next such line.
+/// The prompt, input and output each span from the first to the last
+/// cell of that type within the command.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "dynamic", derive(FromDynamic, ToDynamic))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CommandZone {
+    /// The row holding the `A` mark
+    pub start_y: StableRowIndex,
+    pub prompt: Option<SemanticZone>,
+    pub input: Option<SemanticZone>,
+    pub output: Option<SemanticZone>,
+    /// The status reported by the `D` mark that ended the command
+    pub exit_status: Option<i32>,
+}
+
+impl CommandZone {
+    /// Finds the most recent command that has produced some output.
+    /// While the shell is waiting at a prompt, that is the command
+    /// that ran before it.
+    pub fn last_with_output(commands: &[Self]) -> Option<&Self> {
+        commands.iter().rev().find(|command| command.output.is_some())
+    }
+}
+
+/// Given the rows on which prompts start, in ascending order, returns
+/// the row that is `amount` prompts away from a viewport whose top is
+/// at `position`.  A viewport between two prompts counts as being just
+/// before the later one, so that moving by one in either direction
+/// lands on one of them.
+pub fn prompt_scroll_target(
+    prompts: &[StableRowIndex],
+    position: StableRowIndex,
+    amount: isize,
+) -> Option<StableRowIndex> {
+    let idx = match prompts.binary_search(&position) {
+        Ok(idx) => idx as isize + amount,
+        Err(idx) if amount > 0 => idx as isize + amount - 1,
+        Err(idx) => idx as isize + amount,
+    };
+    prompts.get(idx.max(0) as usize).copied()
+}
+
 pub mod color;
 
 #[cfg(test)]
@@ -71,6 +71,8 @@ pub enum Alert {
     OutputSinceFocusLost,
     /// A change to the progress bar state
     Progress(Progress),
+    /// The color that the tab is tinted with has changed
+    TabColorChanged(Option<crate::color::RgbColor>),
 }
 
 pub trait AlertHandler: Send + Sync {
@@ -11,6 +11,7 @@ use std::io::{BufWriter, Write};
 use std::num::NonZeroUsize;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use terminfo::{Database, Value};
 use termwiz::input::KeyboardEncoding;
 use url::Url;
@@ -35,6 +36,11 @@ mod sixel;
 use crate::terminalstate::image::*;
 use crate::terminalstate::kitty::*;
 
+/// The longest that synchronized output (DECSET 2026) can hold back
+/// rendering, so that an application that never ends its update
+/// cannot freeze the display
+pub const SYNCHRONIZED_OUTPUT_TIMEOUT: Duration = Duration::from_millis(150);
+
 lazy_static::lazy_static! {
     static ref DB: Database = {
         let data = include_bytes!("../../../termwiz/data/wezterm");
@@ -261,6 +267,12 @@ pub struct TerminalState {
 
     clear_semantic_attribute_on_newline: bool,
 
+    /// The row holding the most recent OSC 133 `A` mark
+    last_prompt_start: Option<StableRowIndex>,
+    /// The status reported by OSC 133 `D` for each command, keyed by
+    /// the row holding the `A` mark that began it
+    command_exit_status: HashMap<StableRowIndex, i32>,
+
     /// If true, writing a character inserts a new cell
     insert: bool,
 
@@ -308,6 +320,9 @@ pub struct TerminalState {
     /// designated marker characters.
     bracketed_paste: bool,
 
+    /// When synchronized output (DECSET 2026) began, if it is active
+    synchronized_output: Option<Instant>,
+
     /// Movement events enabled
     any_event_mouse: bool,
     focus_tracking: bool,
@@ -336,8 +351,14 @@ pub struct TerminalState {
     /// The icon title string (OSC 1)
     icon_title: Option<String>,
     progress: Progress,
+    /// The color that the tab containing this terminal is tinted with,
+    /// as set by `OSC 1337 ; SetColors=tab=RRGGBB`
+    tab_color: Option<RgbColor>,
 
-    palette: Option<ColorPalette>,
+    palette: Option<Arc<ColorPalette>>,
+    /// Incremented each time the palette actually changes, other than
+    /// by the configuration being reloaded
+    palette_generation: usize,
 
     pixel_width: usize,
     pixel_height: usize,
@@ -522,6 +543,8 @@ impl TerminalState {
             left_and_right_margin_mode: false,
             wrap_next: false,
             clear_semantic_attribute_on_newline: false,
+            last_prompt_start: None,
+            command_exit_status: HashMap::new(),
             // We default auto wrap to true even though the default for
             // a dec terminal is false, because it is more useful this way.
             dec_auto_wrap: true,
@@ -537,6 +560,7 @@ impl TerminalState {
             color_map,
             application_keypad: false,
             bracketed_paste: false,
+            synchronized_output: None,
             focus_tracking: false,
             mouse_encoding: MouseEncoding::X10,
             keyboard_encoding: KeyboardEncoding::Xterm,
@@ -555,6 +579,7 @@ impl TerminalState {
             title: "phaedra".to_string(),
             icon_title: None,
             palette: None,
+            palette_generation: 0,
             pixel_height: size.pixel_height,
             pixel_width: size.pixel_width,
             dpi: size.dpi,
@@ -581,6 +606,7 @@ impl TerminalState {
             bidi_enabled: None,
             bidi_hint: None,
             progress: Progress::default(),
+            tab_color: None,
         }
     }
 
@@ -598,7 +624,11 @@ impl TerminalState {
     }
 
     pub fn set_config(&mut self, config: Arc<dyn TerminalConfiguration>) {
+        let prior = self.palette();
         self.config = config;
+        if self.palette() != prior {
+            self.palette_generation += 1;
+        }
     }
 
     pub fn get_config(&self) -> Arc<dyn TerminalConfiguration> {
@@ -646,6 +676,10 @@ impl TerminalState {
         self.progress.clone()
     }
 
+    pub fn get_tab_color(&self) -> Option<RgbColor> {
+        self.tab_color
+    }
+
     /// Returns the current working directory associated with the
     /// terminal session.  The working directory can be changed by
     /// the applicaiton using the OSC 7 escape sequence.
@@ -653,28 +687,36 @@ impl TerminalState {
         self.current_dir.as_ref()
     }
 
-    /// Returns a copy of the palette.
+    /// Returns the palette, which is shared rather than copied.
     /// By default we don't keep a copy in the terminal state,
     /// preferring to take the config values from the users
     /// config file and updating to changes live.
     /// However, if they have used dynamic color scheme escape
     /// sequences we'll fork a copy of the palette at that time
     /// so that we can start tracking those changes.
-    pub fn palette(&self) -> ColorPalette {
+    pub fn palette(&self) -> Arc<ColorPalette> {
         self.palette
-            .as_ref()
-            .cloned()
+            .clone()
             .unwrap_or_else(|| self.config.color_palette())
     }
 
+    /// Returns a number that changes each time the palette changes
+    /// through escape sequences or `set_config`.  Changes made by
+    /// reloading the configuration in place are not counted; those
+    /// are tracked by the generation of the configuration.
+    pub fn palette_generation(&self) -> usize {
+        self.palette_generation
+    }
+
     /// Called in response to dynamic color scheme escape sequences.
     /// Will make a copy of the palette from the config file if this
-    /// is the first of these escapes we've seen.
+    /// is the first of these escapes we've seen, or if the palette
+    /// is shared with other terminals.
     pub fn palette_mut(&mut self) -> &mut ColorPalette {
         if self.palette.is_none() {
             self.palette.replace(self.config.color_palette());
         }
-        self.palette.as_mut().unwrap()
+        Arc::make_mut(self.palette.as_mut().unwrap())
     }
 
     /// If the current overridden palette is effectively the same as
@@ -763,6 +805,28 @@ impl TerminalState {
         self.bracketed_paste
     }
 
+    /// Returns true while the application is in the middle of a
+    /// synchronized update (DECSET 2026), during which the hosting GUI
+    /// should keep showing what it rendered before the update began.
+    pub fn is_output_held(&self) -> bool {
+        self.is_output_held_at(Instant::now())
+    }
+
+    /// Returns true if synchronized output is active and will not
+    /// have timed out by `now`
+    pub fn is_output_held_at(&self, now: Instant) -> bool {
+        match self.synchronized_output {
+            Some(began) => now.saturating_duration_since(began) < SYNCHRONIZED_OUTPUT_TIMEOUT,
+            None => false,
+        }
+    }
+
+    /// Returns the time at which a synchronized update that is in
+    /// progress will be considered to have timed out
+    pub fn output_held_until(&self) -> Option<Instant> {
+        self.synchronized_output.map(|began| began + SYNCHRONIZED_OUTPUT_TIMEOUT)
+    }
+
     /// Advise the terminal about a change in its focus state
     pub fn focus_changed(&mut self, focused: bool) {
         if focused == self.focused {
@@ -924,7 +988,18 @@ impl TerminalState {
         }
     }
 
-    fn palette_did_change(&mut self) {
+    /// Called after an escape sequence that may have changed the
+    /// palette override, which was `prior` beforehand.  The override is
+    /// shared with equal palettes, and if it differs from `prior` the
+    /// palette generation is bumped and the screen is redrawn.
+    fn palette_did_change(&mut self, prior: Option<Arc<ColorPalette>>) {
+        if let Some(palette) = self.palette.take() {
+            self.palette.replace(ColorPalette::intern_arc(palette));
+        }
+        if self.palette == prior {
+            return;
+        }
+        self.palette_generation += 1;
         self.make_all_lines_dirty();
         if let Some(handler) = self.alert_handler.as_mut() {
             handler.alert(Alert::PaletteChanged);
@@ -1087,6 +1162,31 @@ impl TerminalState {
         self.new_line(true);
     }
 
+    /// Marks the line holding the cursor as the first line of a prompt,
+    /// in response to OSC 133 `A`, forgetting the status of any command
+    /// that previously began there
+    fn mark_prompt_start(&mut self) {
+        let seqno = self.seqno;
+        let y = self.cursor.y;
+        let screen = self.screen_mut();
+        let line_idx = screen.phys_row(y);
+        screen.line_mut(line_idx).set_prompt_start(true, seqno);
+        let stable_row = screen.phys_to_stable_row_index(line_idx);
+        self.command_exit_status.remove(&stable_row);
+        self.last_prompt_start = Some(stable_row);
+    }
+
+    /// Records the status reported by OSC 133 `D` against the command
+    /// that began with the most recent prompt, forgetting those for
+    /// commands that have been scrolled out of the scrollback
+    fn record_command_status(&mut self, status: i32) {
+        if let Some(prompt_start) = self.last_prompt_start {
+            let first_row = self.screen().phys_to_stable_row_index(0);
+            self.command_exit_status.retain(|&row, _| row >= first_row);
+            self.command_exit_status.insert(prompt_start, status);
+        }
+    }
+
     fn new_line(&mut self, move_to_first_column: bool) {
         let x = if move_to_first_column {
             self.left_and_right_margins.start
@@ -1283,6 +1383,7 @@ impl TerminalState {
 
                 self.g0_charset = CharSet::Ascii;
                 self.g1_charset = CharSet::Ascii;
+                self.synchronized_output.take();
             }
             Device::RequestPrimaryDeviceAttributes => {
                 let mut ident = "\x1b[?65".to_string(); // Vt500
@@ -1573,19 +1674,21 @@ impl TerminalState {
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in phaedra's mux
+                // A nested begin doesn't extend the timeout of the
+                // update that is already in progress
+                if !self.is_output_held() {
+                    self.synchronized_output.replace(Instant::now());
+                }
             }
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in phaedra's mux
+                self.synchronized_output.take();
             }
             Mode::QueryDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in phaedra's mux; if we get here, then it isn't enabled,
-                // so we always report false
-                self.decqrm_response(mode, true, false);
+                self.decqrm_response(mode, true, self.is_output_held());
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SmoothScroll))
@@ -2749,6 +2852,60 @@ impl TerminalState {
         Ok(zones)
     }
 
+    /// Computes the set of `CommandZone`s for the current screen, from
+    /// the lines holding OSC 133 `A` marks.  A marked line that no
+    /// longer holds any prompt, because it was since erased, doesn't
+    /// begin a command.
+    pub fn get_command_zones(&mut self) -> anyhow::Result<Vec<CommandZone>> {
+        let exit_status = &self.command_exit_status;
+        let screen = &mut self.screen;
+        let mut commands: Vec<CommandZone> = vec![];
+
+        let first_stable_row = screen.phys_to_stable_row_index(0);
+        screen.for_each_phys_line_mut(|idx, line| {
+            let stable_row = first_stable_row + idx as StableRowIndex;
+            let prompt_start = line.is_prompt_start();
+            let ranges = line.semantic_zone_ranges();
+
+            if prompt_start
+                && ranges
+                    .iter()
+                    .any(|range| range.semantic_type == SemanticType::Prompt)
+            {
+                commands.push(CommandZone {
+                    start_y: stable_row,
+                    prompt: None,
+                    input: None,
+                    output: None,
+                    exit_status: exit_status.get(&stable_row).copied(),
+                });
+            }
+
+            let command = match commands.last_mut() {
+                Some(command) => command,
+                None => return,
+            };
+            for range in ranges {
+                let zone = match range.semantic_type {
+                    SemanticType::Prompt => &mut command.prompt,
+                    SemanticType::Input => &mut command.input,
+                    SemanticType::Output => &mut command.output,
+                };
+                let zone = zone.get_or_insert(SemanticZone {
+                    start_x: range.range.start as usize,
+                    start_y: stable_row,
+                    end_x: range.range.end as usize,
+                    end_y: stable_row,
+                    semantic_type: range.semantic_type,
+                });
+                zone.end_x = range.range.end as usize;
+                zone.end_y = stable_row;
+            }
+        });
+
+        Ok(commands)
+    }
+
     #[inline]
     pub fn get_reverse_video(&self) -> bool {
         self.reverse_video_mode
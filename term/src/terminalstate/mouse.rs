@@ -1,9 +1,78 @@
+use crate::config::AlternateBufferWheelBehavior;
 use crate::input::*;
 use crate::terminalstate::MouseEncoding;
 use crate::TerminalState;
 use anyhow::bail;
 use std::io::Write;
 
+/// How a mouse wheel event is delivered to the application
+#[derive(Debug, PartialEq, Eq)]
+enum WheelAction {
+    /// Report it as a mouse event
+    Report,
+    /// Press the key the given number of times
+    Keys(KeyCode, u8),
+    /// Send nothing
+    Ignore,
+}
+
+/// Decides how to deliver a wheel event for `button`, given whether the
+/// application has enabled mouse reporting and whether the alternate
+/// screen is active.  As with xterm's alternateScroll mode, only the
+/// vertical wheel is turned into cursor keys; horizontal scrolling only
+/// reaches applications that report the mouse.
+fn wheel_action(
+    button: &MouseButton,
+    reporting: bool,
+    alt_screen: bool,
+    behavior: AlternateBufferWheelBehavior,
+) -> anyhow::Result<WheelAction> {
+    if reporting {
+        return Ok(WheelAction::Report);
+    }
+    if !alt_screen {
+        return Ok(WheelAction::Ignore);
+    }
+    Ok(match behavior {
+        // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
+        AlternateBufferWheelBehavior::SendArrows { speed } => match button {
+            MouseButton::WheelDown(_) => WheelAction::Keys(KeyCode::DownArrow, speed),
+            MouseButton::WheelUp(_) => WheelAction::Keys(KeyCode::UpArrow, speed),
+            MouseButton::WheelLeft(_) | MouseButton::WheelRight(_) => WheelAction::Ignore,
+            _ => bail!("unexpected mouse event"),
+        },
+        AlternateBufferWheelBehavior::ScrollHistory => WheelAction::Ignore,
+        AlternateBufferWheelBehavior::PassThroughMouse => WheelAction::Report,
+    })
+}
+
+/// Returns the button number that is reported for `button`.  The wheel
+/// is reported as buttons 4-7, which are encoded as 64 and up, with
+/// buttons 6 and 7 being scrolled left and right.
+fn report_button_code(button: MouseButton, modifiers: KeyModifiers) -> i8 {
+    let mut code = match button {
+        MouseButton::None => 3,
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        MouseButton::WheelUp(_) => 64,
+        MouseButton::WheelDown(_) => 65,
+        MouseButton::WheelLeft(_) => 66,
+        MouseButton::WheelRight(_) => 67,
+    };
+
+    if modifiers.contains(KeyModifiers::SHIFT) {
+        code += 4;
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        code += 8;
+    }
+    if modifiers.contains(KeyModifiers::CTRL) {
+        code += 16;
+    }
+    code
+}
+
 impl TerminalState {
     /// Encode a coordinate value using X10 encoding or Utf8 encoding.
     /// Out of bounds coords are reported as the 0 byte value.
@@ -49,36 +118,30 @@ impl TerminalState {
                 .unwrap_or(MouseButton::None),
             b => b,
         };
-        let mut code = match button {
-            MouseButton::None => 3,
-            MouseButton::Left => 0,
-            MouseButton::Middle => 1,
-            MouseButton::Right => 2,
-            MouseButton::WheelUp(_) => 64,
-            MouseButton::WheelDown(_) => 65,
-            MouseButton::WheelLeft(_) => 66,
-            MouseButton::WheelRight(_) => 67,
-        };
-
-        if event.modifiers.contains(KeyModifiers::SHIFT) {
-            code += 4;
-        }
-        if event.modifiers.contains(KeyModifiers::ALT) {
-            code += 8;
-        }
-        if event.modifiers.contains(KeyModifiers::CTRL) {
-            code += 16;
-        }
-
-        (code, button)
+        (report_button_code(button, event.modifiers), button)
     }
 
     fn mouse_wheel(&mut self, event: MouseEvent) -> anyhow::Result<()> {
+        let action = wheel_action(
+            &event.button,
+            self.mouse_tracking || self.button_event_mouse || self.any_event_mouse,
+            self.screen.is_alt_screen_active(),
+            self.config.alternate_buffer_wheel_behavior(),
+        )?;
+        match action {
+            WheelAction::Ignore => return Ok(()),
+            WheelAction::Keys(key, count) => {
+                for _ in 0..count {
+                    self.key_down(key, KeyModifiers::default())?;
+                }
+                return Ok(());
+            }
+            WheelAction::Report => {}
+        }
+
         let (button, _button) = self.mouse_report_button_number(&event);
 
-        if self.mouse_encoding == MouseEncoding::SGR
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
+        if self.mouse_encoding == MouseEncoding::SGR {
             log::trace!(
                 "wheel {event:?} ESC [<{};{};{}M",
                 button,
@@ -93,9 +156,7 @@ impl TerminalState {
                 event.y + 1
             )?;
             self.writer.flush()?;
-        } else if self.mouse_encoding == MouseEncoding::SgrPixels
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
+        } else if self.mouse_encoding == MouseEncoding::SgrPixels {
             let height = self.screen.physical_rows as usize;
             let width = self.screen.physical_cols as usize;
             log::trace!(
@@ -116,22 +177,8 @@ impl TerminalState {
                     + 1
             )?;
             self.writer.flush()?;
-        } else if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
+        } else {
             self.encode_x10_or_utf8(event, button)?;
-        } else if self.screen.is_alt_screen_active() {
-            // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
-            for _ in 0..self.config.alternate_buffer_wheel_scroll_speed() {
-                self.key_down(
-                    match event.button {
-                        MouseButton::WheelDown(_) => KeyCode::DownArrow,
-                        MouseButton::WheelUp(_) => KeyCode::UpArrow,
-                        MouseButton::WheelLeft(_) => KeyCode::LeftArrow,
-                        MouseButton::WheelRight(_) => KeyCode::RightArrow,
-                        _ => bail!("unexpected mouse event"),
-                    },
-                    KeyModifiers::default(),
-                )?;
-            }
         }
         Ok(())
     }
@@ -362,3 +409,87 @@ impl TerminalState {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wheel_in_the_alt_screen_follows_the_behavior() {
+        let up = MouseButton::WheelUp(1);
+        let arrows = AlternateBufferWheelBehavior::SendArrows { speed: 2 };
+
+        assert_eq!(
+            wheel_action(&up, false, true, arrows).unwrap(),
+            WheelAction::Keys(KeyCode::UpArrow, 2)
+        );
+        // The horizontal wheel isn't turned into cursor keys
+        assert_eq!(
+            wheel_action(&MouseButton::WheelRight(1), false, true, arrows).unwrap(),
+            WheelAction::Ignore
+        );
+        assert!(wheel_action(&MouseButton::Left, false, true, arrows).is_err());
+        assert_eq!(
+            wheel_action(
+                &up,
+                false,
+                true,
+                AlternateBufferWheelBehavior::ScrollHistory
+            )
+            .unwrap(),
+            WheelAction::Ignore
+        );
+        assert_eq!(
+            wheel_action(
+                &up,
+                false,
+                true,
+                AlternateBufferWheelBehavior::PassThroughMouse
+            )
+            .unwrap(),
+            WheelAction::Report
+        );
+    }
+
+    #[test]
+    fn wheel_reporting_and_primary_screen_ignore_the_behavior() {
+        let up = MouseButton::WheelUp(1);
+        for behavior in [
+            AlternateBufferWheelBehavior::SendArrows { speed: 3 },
+            AlternateBufferWheelBehavior::ScrollHistory,
+            AlternateBufferWheelBehavior::PassThroughMouse,
+        ] {
+            assert_eq!(
+                wheel_action(&up, true, true, behavior).unwrap(),
+                WheelAction::Report
+            );
+            assert_eq!(
+                wheel_action(&up, false, false, behavior).unwrap(),
+                WheelAction::Ignore
+            );
+        }
+    }
+
+    #[test]
+    fn horizontal_wheel_reports_buttons_6_and_7() {
+        let left = MouseButton::WheelLeft(1);
+        let right = MouseButton::WheelRight(3);
+        assert_eq!(report_button_code(left, KeyModifiers::NONE), 66);
+        assert_eq!(report_button_code(right, KeyModifiers::NONE), 67);
+        assert_eq!(report_button_code(left, KeyModifiers::SHIFT), 70);
+        assert_eq!(
+            report_button_code(right, KeyModifiers::CTRL | KeyModifiers::ALT),
+            67 + 8 + 16
+        );
+        for behavior in [
+            AlternateBufferWheelBehavior::SendArrows { speed: 3 },
+            AlternateBufferWheelBehavior::ScrollHistory,
+            AlternateBufferWheelBehavior::PassThroughMouse,
+        ] {
+            assert_eq!(
+                wheel_action(&right, true, false, behavior).unwrap(),
+                WheelAction::Report
+            );
+        }
+    }
+}
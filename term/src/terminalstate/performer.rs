@@ -694,6 +694,7 @@ impl<'a> Performer<'a> {
                 self.dec_ansi_mode = false;
                 self.application_keypad = false;
                 self.bracketed_paste = false;
+                self.synchronized_output.take();
                 self.focus_tracking = false;
                 self.mouse_tracking = false;
                 self.mouse_encoding = MouseEncoding::X10;
@@ -708,7 +709,7 @@ impl<'a> Performer<'a> {
                 self.shift_out = false;
                 self.newline_mode = false;
                 self.tabs = TabStop::new(self.screen().physical_cols, 8);
-                self.palette.take();
+                let prior_palette = self.palette.take();
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as VisibleRowIndex;
                 self.left_and_right_margins = 0..self.screen().physical_cols;
                 self.unicode_version = self.config.unicode_version();
@@ -716,6 +717,7 @@ impl<'a> Performer<'a> {
                 self.suppress_initial_title_change = false;
                 self.accumulating_title.take();
                 self.progress = Progress::default();
+                self.tab_color = None;
 
                 self.screen.full_reset();
                 self.screen.activate_alt_screen(seqno);
@@ -723,7 +725,7 @@ impl<'a> Performer<'a> {
                 self.screen.activate_primary_screen(seqno);
                 self.erase_in_display(EraseInDisplay::EraseScrollback);
                 self.erase_in_display(EraseInDisplay::EraseDisplay);
-                self.palette_did_change();
+                self.palette_did_change(prior_palette);
             }
 
             _ => {
@@ -832,6 +834,14 @@ impl<'a> Performer<'a> {
                         handler.alert(Alert::SetUserVar { name, value });
                     }
                 }
+                ITermProprietary::SetTabColor(color) => {
+                    if color != self.tab_color {
+                        self.tab_color = color;
+                        if let Some(handler) = self.alert_handler.as_mut() {
+                            handler.alert(Alert::TabColorChanged(color));
+                        }
+                    }
+                }
                 ITermProprietary::UnicodeVersion(ITermUnicodeVersionOp::Set(n)) => {
                     self.unicode_version.version = n;
                 }
@@ -867,6 +877,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::FreshLineAndStartPrompt { .. },
             ) => {
                 self.fresh_line();
+                self.mark_prompt_start();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -878,6 +889,7 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfCommandWithFreshLine { .. },
             ) => {
                 self.fresh_line();
+                self.mark_prompt_start();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
@@ -898,8 +910,10 @@ impl<'a> Performer<'a> {
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                self.record_command_status(status);
+            }
 
             OperatingSystemCommand::SystemNotification(message) => {
                 if let Some(handler) = self.alert_handler.as_mut() {
@@ -940,6 +954,7 @@ impl<'a> Performer<'a> {
                 }
             }
             OperatingSystemCommand::ChangeColorNumber(specs) => {
+                let prior_palette = self.palette.clone();
                 log::trace!("ChangeColorNumber: {:?}", specs);
                 for pair in specs {
                     match pair.color {
@@ -960,10 +975,11 @@ impl<'a> Performer<'a> {
                     }
                 }
                 self.implicit_palette_reset_if_same_as_configured();
-                self.palette_did_change();
+                self.palette_did_change(prior_palette);
             }
 
             OperatingSystemCommand::ResetColors(colors) => {
+                let prior_palette = self.palette.clone();
                 log::trace!("ResetColors: {:?}", colors);
                 if colors.is_empty() {
                     // Reset all colors
@@ -981,10 +997,11 @@ impl<'a> Performer<'a> {
                     }
                 }
                 self.implicit_palette_reset_if_same_as_configured();
-                self.palette_did_change();
+                self.palette_did_change(prior_palette);
             }
 
             OperatingSystemCommand::ChangeDynamicColors(first_color, colors) => {
+                let prior_palette = self.palette.clone();
                 log::trace!("ChangeDynamicColors: {:?} {:?}", first_color, colors);
                 use phaedra_escape_parser::osc::DynamicColorNumber;
                 let mut idx: u8 = first_color as u8;
@@ -1036,10 +1053,11 @@ impl<'a> Performer<'a> {
                     idx += 1;
                 }
                 self.implicit_palette_reset_if_same_as_configured();
-                self.palette_did_change();
+                self.palette_did_change(prior_palette);
             }
 
             OperatingSystemCommand::ResetDynamicColor(color) => {
+                let prior_palette = self.palette.clone();
                 log::trace!("ResetDynamicColor: {:?}", color);
                 use phaedra_escape_parser::osc::DynamicColorNumber;
                 let which_color: Option<DynamicColorNumber> = FromPrimitive::from_u8(color as u8);
@@ -1073,7 +1091,7 @@ impl<'a> Performer<'a> {
                     }
                 }
                 self.implicit_palette_reset_if_same_as_configured();
-                self.palette_did_change();
+                self.palette_did_change(prior_palette);
             }
             OperatingSystemCommand::ConEmuProgress(prog) => {
                 use phaedra_escape_parser::osc::Progress as TProg;
@@ -401,3 +401,49 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab"]);
 }
+
+#[test]
+fn test_synchronized_output() {
+    use std::time::Duration;
+
+    let mut term = TestTerm::new(2, 3, 0);
+    assert!(!term.is_output_held());
+
+    term.set_mode("?2026", true);
+    assert!(term.is_output_held());
+    let until = term.output_held_until().unwrap();
+    assert!(term.is_output_held_at(until - Duration::from_millis(1)));
+    assert!(!term.is_output_held_at(until));
+
+    // Output is still applied to the model while held
+    term.print("abc");
+    assert_visible_contents(&term, file!(), line!(), &["abc", ""]);
+
+    // A nested begin doesn't extend the update
+    term.set_mode("?2026", true);
+    assert_eq!(term.output_held_until(), Some(until));
+
+    term.set_mode("?2026", false);
+    assert!(!term.is_output_held());
+    assert_eq!(term.output_held_until(), None);
+
+    // DECSTR ends the update too
+    term.set_mode("?2026", true);
+    term.print("\x1b[!p");
+    assert!(!term.is_output_held());
+}
+
+#[test]
+fn test_synchronized_output_timeout() {
+    let mut term = TestTerm::new(2, 3, 0);
+    term.set_mode("?2026", true);
+    let until = term.output_held_until().unwrap();
+
+    std::thread::sleep(SYNCHRONIZED_OUTPUT_TIMEOUT);
+    assert!(!term.is_output_held());
+
+    // Once timed out, a begin starts a new update
+    term.set_mode("?2026", true);
+    assert!(term.is_output_held());
+    assert!(term.output_held_until().unwrap() > until);
+}
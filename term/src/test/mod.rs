@@ -51,8 +51,8 @@ impl TerminalConfiguration for TestTermConfig {
         self.scrollback
     }
 
-    fn color_palette(&self) -> ColorPalette {
-        ColorPalette::default()
+    fn color_palette(&self) -> Arc<ColorPalette> {
+        ColorPalette::default().intern()
     }
 }
 
@@ -480,6 +480,104 @@ fn test_semantic() {
     );
 }
 
+#[test]
+fn test_command_zones() {
+    fn zone(
+        start_y: StableRowIndex,
+        start_x: usize,
+        end_y: StableRowIndex,
+        end_x: usize,
+        semantic_type: SemanticType,
+    ) -> Option<SemanticZone> {
+        Some(SemanticZone {
+            start_y,
+            start_x,
+            end_y,
+            end_x,
+            semantic_type,
+        })
+    }
+
+    let mut term = TestTerm::new(10, 20, 0);
+    // A command with output
+    term.print("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\ls\r\n");
+    term.print("\x1b]133;C\x1b\\a\r\nb\x1b]133;D;0\x1b\\");
+    // An empty command line
+    term.print("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\\r\n");
+    term.print("\x1b]133;C\x1b\\\x1b]133;D;1\x1b\\");
+    // A failing command, then the prompt that is waiting for input
+    term.print("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\false\r\n");
+    term.print("\x1b]133;C\x1b\\oops\r\n\x1b]133;D;2\x1b\\");
+    term.print("\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\");
+
+    assert_visible_contents(
+        &term,
+        file!(),
+        line!(),
+        &["$ ls", "a", "b", "$ ", "$ false", "oops", "$ ", "", "", ""],
+    );
+
+    let commands = term.get_command_zones().unwrap();
+    assert_eq!(
+        commands,
+        vec![
+            CommandZone {
+                start_y: 0,
+                prompt: zone(0, 0, 0, 1, SemanticType::Prompt),
+                input: zone(0, 2, 0, 3, SemanticType::Input),
+                output: zone(1, 0, 2, 0, SemanticType::Output),
+                exit_status: Some(0),
+            },
+            CommandZone {
+                start_y: 3,
+                prompt: zone(3, 0, 3, 1, SemanticType::Prompt),
+                input: None,
+                output: None,
+                exit_status: Some(1),
+            },
+            CommandZone {
+                start_y: 4,
+                prompt: zone(4, 0, 4, 1, SemanticType::Prompt),
+                input: zone(4, 2, 4, 6, SemanticType::Input),
+                output: zone(5, 0, 5, 3, SemanticType::Output),
+                exit_status: Some(2),
+            },
+            CommandZone {
+                start_y: 6,
+                prompt: zone(6, 0, 6, 1, SemanticType::Prompt),
+                input: None,
+                output: None,
+                exit_status: None,
+            },
+        ]
+    );
+    assert_eq!(
+        CommandZone::last_with_output(&commands).map(|command| command.start_y),
+        Some(4)
+    );
+
+    let prompts: Vec<StableRowIndex> = commands.iter().map(|command| command.start_y).collect();
+    assert_eq!(prompt_scroll_target(&prompts, 2, -1), Some(0));
+    assert_eq!(prompt_scroll_target(&prompts, 2, 1), Some(3));
+    assert_eq!(prompt_scroll_target(&prompts, 3, 1), Some(4));
+    assert_eq!(prompt_scroll_target(&prompts, 3, -1), Some(0));
+    assert_eq!(prompt_scroll_target(&prompts, 0, -1), Some(0));
+    assert_eq!(prompt_scroll_target(&prompts, 6, 1), None);
+
+    // Clearing the screen leaves only the prompt that follows it
+    term.print("\x1b[2J\x1b[H\x1b]133;A\x1b\\$ ");
+    assert_eq!(
+        term.get_command_zones().unwrap(),
+        vec![CommandZone {
+            start_y: 0,
+            prompt: zone(0, 0, 0, 1, SemanticType::Prompt),
+            input: None,
+            output: None,
+            exit_status: None,
+        }]
+    );
+}
+
 #[test]
 fn issue_1161() {
     let mut term = TestTerm::new(1, 5, 0);
@@ -1374,3 +1472,22 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_tab_color() {
+    use crate::color::RgbColor;
+
+    let mut term = TestTerm::new(3, 5, 0);
+    assert_eq!(term.get_tab_color(), None);
+
+    term.print("\x1b]1337;SetColors=tab=ff0000\x1b\\");
+    assert_eq!(term.get_tab_color(), Some(RgbColor::new_8bpc(0xff, 0, 0)));
+
+    term.print("\x1b]1337;SetColors=tab=default\x1b\\");
+    assert_eq!(term.get_tab_color(), None);
+
+    // A full reset restores the normal colors
+    term.print("\x1b]1337;SetColors=tab=00ff00\x1b\\");
+    term.print("\x1bc");
+    assert_eq!(term.get_tab_color(), None);
+}
@@ -1,13 +1,17 @@
 use crate::bitmaps::{BitmapImage, Texture2d, TextureRect};
 use crate::{Point, Rect, Size};
 use anyhow::{ensure, Result as Fallible};
-use guillotiere::{SimpleAtlasAllocator, Size as AtlasSize};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::rc::Rc;
 use thiserror::*;
 
 const PADDING: i32 = 1;
 
+/// New shelves are rounded up to a multiple of this many pixels, so that
+/// sprites whose heights differ slightly can share a shelf
+const SHELF_HEIGHT_GRANULARITY: i32 = 4;
+
 #[derive(Debug, Error)]
 #[error("Texture Size exceeded, need {:?}", size)]
 pub struct OutOfTextureSpace {
@@ -15,13 +19,178 @@ pub struct OutOfTextureSpace {
     pub current_size: usize,
 }
 
+/// A region of the atlas reserved for a sprite, including its padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+/// A horizontal strip of the atlas that holds sprites of similar height
+#[derive(Debug)]
+struct Shelf {
+    y: i32,
+    height: i32,
+    /// Unused horizontal spans as (x, width), ordered by x
+    free: Vec<(i32, i32)>,
+    allocated: usize,
+}
+
+/// Packs sprites into shelves, and unlike a plain shelf packer allows
+/// slots to be returned so that their space can be reused.
+/// Shelves that become empty are removed by `compact`, returning their
+/// rows to the pool so that they can be re-opened at a different height.
+#[derive(Debug)]
+struct ShelfAllocator {
+    side: i32,
+    /// Ordered by y
+    shelves: Vec<Shelf>,
+    used_area: i64,
+}
+
+impl ShelfAllocator {
+    fn new(side: i32) -> Self {
+        Self {
+            side,
+            shelves: vec![],
+            used_area: 0,
+        }
+    }
+
+    fn allocate(&mut self, width: i32, height: i32) -> Option<Slot> {
+        if width > self.side || height > self.side {
+            return None;
+        }
+
+        // Prefer the shortest shelf that fits, but don't waste more
+        // than half of a shelf on a much shorter sprite
+        let mut best: Option<(usize, usize)> = None;
+        for (shelf_idx, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height < height || shelf.height > height * 2 {
+                continue;
+            }
+            if let Some(span_idx) = shelf.free.iter().position(|&(_, w)| w >= width) {
+                if best.map_or(true, |(b, _)| self.shelves[b].height > shelf.height) {
+                    best = Some((shelf_idx, span_idx));
+                }
+            }
+        }
+
+        let (shelf_idx, span_idx) = match best {
+            Some(best) => best,
+            None => (self.open_shelf(height)?, 0),
+        };
+
+        let shelf = &mut self.shelves[shelf_idx];
+        let (x, span_width) = shelf.free[span_idx];
+        if span_width == width {
+            shelf.free.remove(span_idx);
+        } else {
+            shelf.free[span_idx] = (x + width, span_width - width);
+        }
+        shelf.allocated += 1;
+        self.used_area += width as i64 * height as i64;
+
+        Some(Slot {
+            x,
+            y: shelf.y,
+            width,
+            height,
+        })
+    }
+
+    /// Open a new shelf in the first vertical gap that can hold it,
+    /// returning its index
+    fn open_shelf(&mut self, height: i32) -> Option<usize> {
+        let preferred = (height + SHELF_HEIGHT_GRANULARITY - 1) / SHELF_HEIGHT_GRANULARITY
+            * SHELF_HEIGHT_GRANULARITY;
+
+        let mut y = 0;
+        let mut insert_at = None;
+        for (idx, shelf) in self.shelves.iter().enumerate() {
+            if shelf.y - y >= height {
+                insert_at = Some((idx, shelf.y - y));
+                break;
+            }
+            y = shelf.y + shelf.height;
+        }
+        let (idx, gap) = match insert_at {
+            Some(found) => found,
+            None if self.side - y >= height => (self.shelves.len(), self.side - y),
+            None => return None,
+        };
+
+        self.shelves.insert(
+            idx,
+            Shelf {
+                y,
+                height: preferred.min(gap),
+                free: vec![(0, self.side)],
+                allocated: 0,
+            },
+        );
+        Some(idx)
+    }
+
+    fn deallocate(&mut self, slot: Slot) {
+        let shelf = match self.shelves.iter_mut().find(|shelf| shelf.y == slot.y) {
+            Some(shelf) => shelf,
+            None => return,
+        };
+
+        let idx = shelf.free.partition_point(|&(x, _)| x < slot.x);
+        shelf.free.insert(idx, (slot.x, slot.width));
+        // Coalesce with the following span, then the preceding one
+        if idx + 1 < shelf.free.len() && slot.x + slot.width == shelf.free[idx + 1].0 {
+            shelf.free[idx].1 += shelf.free[idx + 1].1;
+            shelf.free.remove(idx + 1);
+        }
+        if idx > 0 && shelf.free[idx - 1].0 + shelf.free[idx - 1].1 == slot.x {
+            shelf.free[idx - 1].1 += shelf.free[idx].1;
+            shelf.free.remove(idx);
+        }
+
+        shelf.allocated -= 1;
+        self.used_area -= slot.width as i64 * slot.height as i64;
+    }
+
+    /// Remove empty shelves so that their space can be reused
+    fn compact(&mut self) {
+        self.shelves.retain(|shelf| shelf.allocated > 0);
+    }
+
+    fn clear(&mut self) {
+        self.shelves.clear();
+        self.used_area = 0;
+    }
+}
+
+struct Allocation {
+    slot: Slot,
+    coords: Rect,
+    /// The frame number at which the sprite was most recently used
+    last_used: u64,
+    /// Pinned sprites are never evicted
+    pinned: bool,
+}
+
 /// Atlases are bitmaps of srgba data that are sized as a power of 2.
-/// We allocate sprites out of the available space, using AtlasAllocator
+/// We allocate sprites out of the available space, using a shelf allocator
 /// to manage the available rectangles.
+/// Each sprite records the frame in which it was last used, so that
+/// space held by stale sprites can be reclaimed without discarding
+/// the whole atlas.
 pub struct Atlas {
     texture: Rc<dyn Texture2d>,
 
-    allocator: SimpleAtlasAllocator,
+    allocator: ShelfAllocator,
+
+    /// Keyed by the origin of the sprite coordinates
+    allocations: HashMap<(isize, isize), Allocation>,
+
+    frame: u64,
 
     /// Dimensions of the texture
     side: usize,
@@ -40,12 +209,13 @@ impl Atlas {
         let rect = Rect::new(Point::new(0, 0), Size::new(iside, iside));
         texture.write(rect, &image);
 
-        let allocator =
-            SimpleAtlasAllocator::new(AtlasSize::new(side.try_into()?, side.try_into()?));
+        let allocator = ShelfAllocator::new(side.try_into()?);
         Ok(Self {
             texture: Rc::clone(texture),
             side,
             allocator,
+            allocations: HashMap::new(),
+            frame: 0,
         })
     }
 
@@ -94,18 +264,22 @@ impl Atlas {
         let reserve_height = reserve_height + padding.unwrap_or(0) as i32 + PADDING * 2;
 
         let start = std::time::Instant::now();
-        let res = if let Some(allocation) = self
-            .allocator
-            .allocate(AtlasSize::new(reserve_width, reserve_height))
-        {
-            let left = allocation.min.x;
-            let top = allocation.min.y;
+        let res = if let Some(slot) = self.allocator.allocate(reserve_width, reserve_height) {
             let rect = Rect::new(
-                Point::new((left + PADDING) as isize, (top + PADDING) as isize),
+                Point::new((slot.x + PADDING) as isize, (slot.y + PADDING) as isize),
                 Size::new(width as isize, height as isize),
             );
 
             self.texture.write(rect, im);
+            self.allocations.insert(
+                (rect.origin.x, rect.origin.y),
+                Allocation {
+                    slot,
+                    coords: rect,
+                    last_used: self.frame,
+                    pinned: false,
+                },
+            );
 
             metrics::histogram!("window.atlas.allocate.success.rate").record(1.);
             Ok(Sprite {
@@ -130,6 +304,88 @@ impl Atlas {
         self.side
     }
 
+    /// Returns true if no sprites are allocated in the atlas
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    /// Advance the frame number used to track sprite usage
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Record that the sprite was used in the current frame
+    pub fn mark_used(&mut self, sprite: &Sprite) {
+        if let Some(allocation) = self
+            .allocations
+            .get_mut(&(sprite.coords.origin.x, sprite.coords.origin.y))
+        {
+            allocation.last_used = self.frame;
+        }
+    }
+
+    /// Prevent the sprite from ever being evicted.  This is used for
+    /// sprites that are held outside of any cache.
+    pub fn pin(&mut self, sprite: &Sprite) {
+        if let Some(allocation) = self
+            .allocations
+            .get_mut(&(sprite.coords.origin.x, sprite.coords.origin.y))
+        {
+            allocation.pinned = true;
+        }
+    }
+
+    /// Returns the fraction of the texture that is occupied by sprites,
+    /// including their padding
+    pub fn utilization(&self) -> f32 {
+        let area = self.side as f64 * self.side as f64;
+        (self.allocator.used_area as f64 / area) as f32
+    }
+
+    /// Release the space held by sprites that have not been used in
+    /// the last `max_age` frames, and compact the shelves so that the
+    /// space can be reused for sprites of any size.
+    /// Returns the coordinates of the evicted sprites; the caller
+    /// must drop any copies of those sprites that it holds, as their
+    /// space will be handed out again.
+    pub fn evict_unused(&mut self, max_age: u64) -> Vec<Rect> {
+        let frame = self.frame;
+        let mut evicted = vec![];
+        self.allocations.retain(|_, allocation| {
+            if allocation.pinned || frame.saturating_sub(allocation.last_used) <= max_age {
+                return true;
+            }
+            evicted.push((allocation.coords, allocation.slot));
+            false
+        });
+
+        let mut rects = Vec::with_capacity(evicted.len());
+        for (coords, slot) in evicted {
+            self.allocator.deallocate(slot);
+
+            // Blank out the slot so that stale pixels cannot bleed into
+            // a neighbor that is later placed next to the padding
+            let slot_rect = Rect::new(
+                Point::new(slot.x as isize, slot.y as isize),
+                Size::new(slot.width as isize, slot.height as isize),
+            );
+            let blank = crate::Image::new(slot.width as usize, slot.height as usize);
+            self.texture.write(slot_rect, &blank);
+
+            rects.push(coords);
+        }
+        self.allocator.compact();
+
+        if !rects.is_empty() {
+            metrics::histogram!("window.atlas.evicted").record(rects.len() as f64);
+        }
+        rects
+    }
+
     /// Zero out the texture, and forget all allocated regions
     pub fn clear(&mut self) {
         let iside = self.side as isize;
@@ -137,6 +393,7 @@ impl Atlas {
         let rect = Rect::new(Point::new(0, 0), Size::new(iside, iside));
         self.texture.write(rect, &image);
         self.allocator.clear();
+        self.allocations.clear();
     }
 }
 
@@ -170,3 +427,125 @@ impl Sprite {
         self.texture.to_texture_coords(self.coords)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bitmaps::ImageTexture;
+    use crate::color::SrgbaPixel;
+
+    /// A 32x32 atlas holds sixteen 6x6 sprites once padding is included
+    fn tiny_atlas() -> (Atlas, Rc<ImageTexture>) {
+        let image = Rc::new(ImageTexture::new(32, 32));
+        let texture: Rc<dyn Texture2d> = image.clone();
+        (Atlas::new(&texture).unwrap(), image)
+    }
+
+    fn is_blank(texture: &ImageTexture, sprite: &Sprite) -> bool {
+        let (x, y) = origin(sprite);
+        *texture.image.borrow().pixel(x as usize, y as usize) == 0
+    }
+
+    fn solid(side: usize) -> crate::Image {
+        let mut image = crate::Image::new(side, side);
+        image.clear(SrgbaPixel::rgba(0xff, 0xff, 0xff, 0xff));
+        image
+    }
+
+    fn origin(sprite: &Sprite) -> (isize, isize) {
+        (sprite.coords.origin.x, sprite.coords.origin.y)
+    }
+
+    #[test]
+    fn recently_used_sprites_survive_eviction() {
+        let (mut atlas, texture) = tiny_atlas();
+        let image = solid(6);
+
+        let sprites: Vec<Sprite> = (0..16).map(|_| atlas.allocate(&image).unwrap()).collect();
+        assert!(atlas.allocate(&image).is_err());
+        assert_eq!(atlas.utilization(), 1.0);
+
+        for _ in 0..3 {
+            atlas.begin_frame();
+        }
+        let (recent, stale) = sprites.split_at(4);
+        for sprite in recent {
+            atlas.mark_used(sprite);
+        }
+
+        let evicted: Vec<(isize, isize)> = atlas
+            .evict_unused(1)
+            .iter()
+            .map(|rect| (rect.origin.x, rect.origin.y))
+            .collect();
+        assert_eq!(evicted.len(), stale.len());
+        for sprite in stale {
+            assert!(evicted.contains(&origin(sprite)));
+        }
+        for sprite in recent {
+            assert!(!evicted.contains(&origin(sprite)));
+        }
+        assert_eq!(atlas.utilization(), 0.25);
+        assert!(stale.iter().all(|sprite| is_blank(&texture, sprite)));
+        assert!(!recent.iter().any(|sprite| is_blank(&texture, sprite)));
+
+        // The reclaimed space is handed out again without disturbing
+        // the surviving sprites
+        let replacements: Vec<Sprite> = (0..12).map(|_| atlas.allocate(&image).unwrap()).collect();
+        assert!(atlas.allocate(&image).is_err());
+        for sprite in &replacements {
+            assert!(!recent.iter().any(|r| origin(r) == origin(sprite)));
+        }
+    }
+
+    #[test]
+    fn pinned_sprites_are_never_evicted() {
+        let (mut atlas, _texture) = tiny_atlas();
+        let image = solid(6);
+        let pinned = atlas.allocate(&image).unwrap();
+        let stale = atlas.allocate(&image).unwrap();
+        atlas.pin(&pinned);
+
+        for _ in 0..10 {
+            atlas.begin_frame();
+        }
+        let evicted = atlas.evict_unused(1);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0], stale.coords);
+    }
+
+    #[test]
+    fn allocation_is_deterministic() {
+        let sizes = [6, 3, 6, 10, 2];
+        let allocate_all = || {
+            let (mut atlas, _texture) = tiny_atlas();
+            assert!(atlas.is_empty());
+            let origins: Vec<(isize, isize)> = sizes
+                .iter()
+                .map(|&size| origin(&atlas.allocate(&solid(size)).unwrap()))
+                .collect();
+            assert!(!atlas.is_empty());
+            origins
+        };
+        assert_eq!(allocate_all(), allocate_all());
+    }
+
+    #[test]
+    fn compaction_reuses_empty_shelves_for_other_sizes() {
+        let (mut atlas, _texture) = tiny_atlas();
+        let small = solid(6);
+        for _ in 0..16 {
+            atlas.allocate(&small).unwrap();
+        }
+
+        let large = solid(30);
+        assert!(atlas.allocate(&large).is_err());
+
+        atlas.begin_frame();
+        assert_eq!(atlas.evict_unused(0).len(), 16);
+        assert_eq!(atlas.utilization(), 0.0);
+
+        let sprite = atlas.allocate(&large).unwrap();
+        assert_eq!(origin(&sprite), (1, 1));
+    }
+}
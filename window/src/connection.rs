@@ -76,6 +76,12 @@ pub trait ConnectionOps {
     /// Perform the system beep/notification sound
     fn beep(&self) {}
 
+    /// Returns true if the user has asked the system to reduce motion
+    /// in the user interface
+    fn prefers_reduced_motion(&self) -> bool {
+        false
+    }
+
     /// Returns information about the screens
     fn screens(&self) -> anyhow::Result<Screens> {
         anyhow::bail!("Unable to query screen information");
@@ -4,7 +4,9 @@ use config::window::WindowLevel;
 use config::{ConfigHandle, Dimension, GeometryOrigin};
 use promise::Future;
 use std::any::Any;
+use std::ops::Range;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use thiserror::Error;
 use url::Url;
 pub mod bitmaps;
@@ -28,6 +30,21 @@ pub fn default_dpi() -> f64 {
     }
 }
 
+static KEYBOARD_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a counter that is advanced whenever the keyboard layout is
+/// switched, so that anything that was derived from the layout can
+/// tell that it needs to be computed again
+pub fn keyboard_generation() -> usize {
+    KEYBOARD_GENERATION.load(Ordering::Relaxed)
+}
+
+/// Records that the keyboard layout was switched.  Called by the
+/// platform layers when the system tells them of a new layout.
+pub(crate) fn bump_keyboard_generation() {
+    KEYBOARD_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
 pub use bitmaps::{BitmapImage, Image};
 pub use connection::*;
 pub use os::*;
@@ -144,9 +161,62 @@ pub enum WindowKeyEvent {
 pub enum DeadKeyStatus {
     /// Not in a dead key processing hold
     None,
-    /// Holding until composition is done; the string is the uncommitted
-    /// composition text to show as a placeholder
-    Composing(String),
+    /// Holding until composition is done; the composition holds the
+    /// uncommitted text to show as a placeholder
+    Composing(Composition),
+}
+
+/// The weight of the underline that marks a clause of composition text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreeditUnderline {
+    /// A clause that is not being converted
+    Thin,
+    /// The clause that the input method is converting
+    Thick,
+}
+
+/// A clause of composition text, as reported by the input method
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PreeditSegment {
+    /// The byte range of the clause within the composition text
+    pub range: Range<usize>,
+    pub underline: PreeditUnderline,
+}
+
+/// The uncommitted text of a dead key or input method composition
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Composition {
+    pub text: String,
+    /// The clauses of the text, in order.  Empty for a dead key,
+    /// or when the input method doesn't divide the text.
+    pub segments: Vec<PreeditSegment>,
+    /// The byte offset of the input method caret within the text,
+    /// if the input method reported one
+    pub caret: Option<usize>,
+}
+
+impl Composition {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            segments: vec![],
+            caret: None,
+        }
+    }
+
+    /// The byte range of the text that the candidate window should be
+    /// placed against: the clause being converted, or else the caret
+    pub fn active_range(&self) -> Range<usize> {
+        if let Some(segment) = self
+            .segments
+            .iter()
+            .find(|segment| segment.underline == PreeditUnderline::Thick)
+        {
+            return segment.range.clone();
+        }
+        let caret = self.caret.unwrap_or(0).min(self.text.len());
+        caret..caret
+    }
 }
 
 #[derive(Debug)]
@@ -193,6 +263,10 @@ pub enum WindowEvent {
 
     AppearanceChanged(Appearance),
 
+    /// Called when the keyboard layout has been switched.
+    /// `keyboard_generation()` has already been incremented.
+    KeyboardLayoutChanged,
+
     Notification(Box<dyn Any + Send + Sync>),
 
     // Called when the files are being dragged into the window
@@ -329,6 +403,22 @@ pub trait WindowOps {
     /// environment.
     fn set_resize_increments(&self, _incr: ResizeIncrement) {}
 
+    /// Asks the desktop environment to draw the user's attention to
+    /// the window, or withdraws that request when `urgent` is false.
+    /// This is the urgency hint on X11, an xdg-activation request on
+    /// Wayland, a dock bounce on macOS and a taskbar flash on Windows.
+    /// Some platforms leave the hint set until it is withdrawn, so it
+    /// should be withdrawn once the window is focused.
+    fn set_urgency_hint(&self, _urgent: bool) {}
+
+    /// Blurs what is behind the transparent parts of the window by
+    /// `radius`, or by the configured `macos_window_background_blur`
+    /// when `radius` is None.  A radius of 0 turns the blur off.
+    /// Only macOS supports this.
+    fn set_background_blur(&self, _radius: Option<i64>) {
+        log::info!("background blur is not supported on this platform");
+    }
+
     fn get_os_parameters(
         &self,
         _config: &ConfigHandle,
@@ -171,6 +171,14 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn prefers_reduced_motion(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let reduce: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+            reduce == YES
+        }
+    }
+
     fn screens(&self) -> anyhow::Result<Screens> {
         let mut by_name = HashMap::new();
         let mut virtual_rect = euclid::rect(0, 0, 0, 0);
@@ -232,13 +240,13 @@ pub fn nsscreen_to_screen_info(screen: *mut Object) -> ScreenInfo {
     let scale = backing_frame.size.width / frame.size.width;
 
     let config = config::configuration();
-    let effective_dpi = if let Some(dpi) = config.font_config().dpi_by_screen.get(&name).copied() {
-        Some(dpi)
-    } else if let Some(dpi) = config.font_config().dpi {
-        Some(dpi)
-    } else {
-        Some(crate::DEFAULT_DPI * scale)
-    };
+    let effective_dpi = Some(config::resolve_monitor_dpi(
+        config.window_config(),
+        config.font_config(),
+        &name,
+        crate::DEFAULT_DPI * scale,
+        crate::DEFAULT_DPI,
+    ));
 
     ScreenInfo {
         name,
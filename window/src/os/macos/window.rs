@@ -8,10 +8,11 @@ use crate::connection::ConnectionOps;
 use crate::os::macos::menu::{MenuItem, RepresentedItem};
 use crate::parameters::{Border, Parameters, TitleBar};
 use crate::{
-    Clipboard, Connection, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent, Modifiers,
-    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, RawKeyEvent, Rect,
-    RequestedWindowGeometry, ResizeIncrement, ResolvedGeometry, ScreenPoint, Size, ULength,
-    WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
+    Clipboard, Composition, Connection, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent,
+    Modifiers, MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point,
+    PreeditSegment, PreeditUnderline, RawKeyEvent, Rect, RequestedWindowGeometry, ResizeIncrement,
+    ResolvedGeometry, ScreenPoint, Size, ULength, WindowDecorations, WindowEvent,
+    WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::anyhow;
 use async_trait::async_trait;
@@ -56,6 +57,8 @@ use phaedra_input_types::{is_ascii_control, IntegratedTitleButtonStyle, Keyboard
 const NSViewLayerContentsPlacementTopLeft: NSInteger = 11;
 #[allow(non_upper_case_globals)]
 const NSViewLayerContentsRedrawDuringViewResize: NSInteger = 2;
+#[allow(non_upper_case_globals)]
+const NSInformationalRequest: NSInteger = 10;
 
 #[link(name = "CoreGraphics", kind = "framework")]
 extern "C" {
@@ -132,10 +135,98 @@ impl NSRange {
     }
 }
 
+/// Convert an offset in UTF-16 code units, as used by NSString,
+/// into a byte offset within `text`
+fn utf16_offset_to_byte(text: &str, offset: usize) -> usize {
+    let mut units = 0;
+    for (idx, c) in text.char_indices() {
+        if units >= offset {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Returns the clauses of the marked text, as ranges of UTF-16 code
+/// units, delimited by runs of its NSMarkedClauseSegment attribute
+unsafe fn marked_text_clauses(astring: id) -> Vec<std::ops::Range<usize>> {
+    let is_astring: bool = msg_send![astring, isKindOfClass: class!(NSAttributedString)];
+    if !is_astring {
+        return vec![];
+    }
+    let len: NSUInteger = msg_send![astring, length];
+    let name = nsstring("NSMarkedClauseSegment");
+    let mut clauses = vec![];
+    let mut idx: NSUInteger = 0;
+    while idx < len {
+        let mut range = NSRange::new(0, 0);
+        let _: id = msg_send![astring,
+            attribute: *name
+            atIndex: idx
+            effectiveRange: NSRangePointer(&mut range)];
+        let end = range.0.location + range.0.length;
+        if end <= idx {
+            break;
+        }
+        clauses.push(range.0.location as usize..end as usize);
+        idx = end;
+    }
+    clauses
+}
+
+/// Build the composition for marked text from its clauses and the
+/// range that the input method selected within it.  The clause that
+/// holds a non-empty selection is the one being converted.
+fn marked_text_composition(
+    text: &str,
+    clauses: &[std::ops::Range<usize>],
+    selected_range: &NSRange,
+) -> Composition {
+    let location = selected_range.0.location as usize;
+    let selected = if selected_range.0.location == NSNotFound as NSUInteger {
+        None
+    } else {
+        Some(location..location + selected_range.0.length as usize)
+    };
+
+    let mut segments: Vec<PreeditSegment> = clauses
+        .iter()
+        .map(|clause| PreeditSegment {
+            range: utf16_offset_to_byte(text, clause.start)..utf16_offset_to_byte(text, clause.end),
+            underline: match &selected {
+                Some(sel) if !sel.is_empty() && clause.contains(&sel.start) => {
+                    PreeditUnderline::Thick
+                }
+                _ => PreeditUnderline::Thin,
+            },
+        })
+        .filter(|segment| !segment.range.is_empty())
+        .collect();
+    if segments.is_empty() && !text.is_empty() {
+        segments.push(PreeditSegment {
+            range: 0..text.len(),
+            underline: PreeditUnderline::Thin,
+        });
+    }
+
+    Composition {
+        text: text.to_string(),
+        segments,
+        caret: selected.map(|sel| utf16_offset_to_byte(text, sel.start)),
+    }
+}
+
 pub(crate) struct WindowInner {
     view: StrongPtr,
     window: StrongPtr,
     config: ConfigHandle,
+    /// The outstanding requestUserAttention, so that it can be
+    /// cancelled when the urgency hint is cleared
+    attention_request: Option<NSInteger>,
+    /// The blur radius set by `set_background_blur`, which replaces
+    /// the configured `macos_window_background_blur`
+    background_blur: Option<i64>,
 }
 
 fn function_key_to_keycode(function_key: char) -> KeyCode {
@@ -263,6 +354,7 @@ impl Window {
                 ime_last_event: None,
                 live_resizing: false,
                 ime_text: String::new(),
+                ime_composition: Composition::default(),
             }));
 
             let window: id = msg_send![get_window_class(), alloc];
@@ -363,6 +455,17 @@ impl Window {
             window.setContentView_(*view);
             window.setDelegate_(*view);
 
+            // Be told when the keyboard layout is switched, so that the
+            // key bindings can be resolved against the new layout
+            let center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+            let () = msg_send![
+                center,
+                addObserver: *view
+                selector: sel!(keyboardSelectionDidChange:)
+                name: *nsstring("NSTextInputContextKeyboardSelectionDidChangeNotification")
+                object: nil
+            ];
+
             view.setWantsLayer(YES);
             let () = msg_send![
                 *view,
@@ -381,9 +484,11 @@ impl Window {
             let width = backing_frame.size.width;
             let height = backing_frame.size.height;
 
-            let dpi = dpi_for_window_screen(*window, &config)
-                .unwrap_or(crate::DEFAULT_DPI * (backing_frame.size.width / frame.size.width))
-                as usize;
+            let dpi = dpi_for_window_screen(
+                *window,
+                &config,
+                backing_frame.size.width / frame.size.width,
+            ) as usize;
 
             let weak_window = window.weak();
             let window_handle = Window {
@@ -395,6 +500,8 @@ impl Window {
                 window,
                 view,
                 config: config.clone(),
+                attention_request: None,
+                background_blur: None,
             }));
             inner.borrow_mut().window.replace(weak_window);
             conn.windows
@@ -607,6 +714,21 @@ impl WindowOps for Window {
         });
     }
 
+    fn set_urgency_hint(&self, urgent: bool) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_urgency_hint(urgent);
+            Ok(())
+        });
+    }
+
+    fn set_background_blur(&self, radius: Option<i64>) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.background_blur = radius;
+            inner.update_window_background_blur();
+            Ok(())
+        });
+    }
+
     fn config_did_change(&self, config: &ConfigHandle) {
         let config = config.clone();
         Connection::with_window_inner(self.id, move |inner| {
@@ -891,7 +1013,8 @@ impl WindowInner {
             CGSSetWindowBackgroundBlurRadius(
                 CGSMainConnectionID(),
                 self.window.windowNumber(),
-                self.config.window_config().macos_window_background_blur,
+                self.background_blur
+                    .unwrap_or(self.config.window_config().macos_window_background_blur),
             );
         }
     }
@@ -980,6 +1103,23 @@ impl WindowInner {
         }
     }
 
+    fn set_urgency_hint(&mut self, urgent: bool) {
+        unsafe {
+            let app = NSApplication::sharedApplication(nil);
+            if urgent {
+                if self.attention_request.is_none() {
+                    // Bounces the dock icon once.  This does nothing
+                    // if the application is already active.
+                    let request: NSInteger =
+                        msg_send![app, requestUserAttention: NSInformationalRequest];
+                    self.attention_request.replace(request);
+                }
+            } else if let Some(request) = self.attention_request.take() {
+                let () = msg_send![app, cancelUserAttentionRequest: request];
+            }
+        }
+    }
+
     fn set_window_level(&mut self, level: WindowLevel) {
         unsafe {
             NSWindow::setLevel_(*self.window, window_level_to_nswindow_level(level));
@@ -1073,8 +1213,10 @@ impl WindowInner {
     }
 
     fn config_did_change(&mut self, config: &ConfigHandle) {
-        let dpi_changed =
-            self.config.font_config().dpi != config.font_config().dpi || self.config.font_config().dpi_by_screen != config.font_config().dpi_by_screen;
+        let dpi_changed = self.config.font_config().dpi != config.font_config().dpi
+            || self.config.font_config().dpi_by_screen != config.font_config().dpi_by_screen
+            || self.config.window_config().dpi_by_monitor != config.window_config().dpi_by_monitor
+            || self.config.window_config().dpi_rounding != config.window_config().dpi_rounding;
 
         self.config = config.clone();
         if let Some(window_view) = WindowView::get_this(unsafe { &**self.view }) {
@@ -1297,6 +1439,8 @@ struct Inner {
     live_resizing: bool,
 
     ime_text: String,
+    /// The clauses and caret of ime_text, as reported by the IME
+    ime_composition: Composition,
 }
 
 #[repr(C)]
@@ -1450,6 +1594,16 @@ impl Keyboard {
 }
 
 impl Inner {
+    /// The composition for ime_text, with the clauses that the IME
+    /// reported for it when it was marked
+    fn composition(&self) -> Composition {
+        if self.ime_composition.text == self.ime_text {
+            self.ime_composition.clone()
+        } else {
+            Composition::new(self.ime_text.clone())
+        }
+    }
+
     /// <https://stackoverflow.com/a/22677690>
     /// <https://stackoverflow.com/a/12548163>
     /// <https://stackoverflow.com/a/8263841>
@@ -1549,15 +1703,26 @@ pub fn superclass(this: &Object) -> &'static Class {
     }
 }
 
-fn dpi_for_window_screen(ns_window: *mut Object, config: &ConfigHandle) -> Option<f64> {
-    if config.font_config().dpi_by_screen.is_empty() {
-        return config.font_config().dpi;
-    }
-
-    let screen = unsafe { msg_send![ns_window, screen] };
-    let info = crate::os::macos::connection::nsscreen_to_screen_info(screen);
-
-    config.font_config().dpi_by_screen.get(&info.name).copied()
+/// Returns the DPI for a window whose backing store is `scale` times
+/// the size of its frame, taking into account the overrides for the
+/// screen that it is on
+fn dpi_for_window_screen(ns_window: *mut Object, config: &ConfigHandle, scale: f64) -> f64 {
+    let name = if config.window_config().dpi_by_monitor.is_empty()
+        && config.font_config().dpi_by_screen.is_empty()
+    {
+        String::new()
+    } else {
+        let screen = unsafe { msg_send![ns_window, screen] };
+        crate::os::macos::connection::nsscreen_to_screen_info(screen).name
+    };
+
+    config::resolve_monitor_dpi(
+        config.window_config(),
+        config.font_config(),
+        &name,
+        crate::DEFAULT_DPI * scale,
+        crate::DEFAULT_DPI,
+    )
 }
 
 #[allow(clippy::identity_op)]
@@ -1636,6 +1801,8 @@ impl WindowView {
     extern "C" fn dealloc(this: &mut Object, _sel: Sel) {
         Self::drop_inner(this);
         unsafe {
+            let center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+            let () = msg_send![center, removeObserver: this as *mut Object];
             let superclass = superclass(this);
             let () = msg_send![super(this, superclass), dealloc];
         }
@@ -1754,6 +1921,8 @@ impl WindowView {
         if let Some(myself) = Self::get_this(this) {
             let mut inner = myself.inner.borrow_mut();
             inner.ime_text = s.to_string();
+            let clauses = unsafe { marked_text_clauses(astring) };
+            inner.ime_composition = marked_text_composition(s, &clauses, &selected_range);
 
             /*
             let key_is_down = inner.key_is_down.take().unwrap_or(true);
@@ -1875,6 +2044,16 @@ impl WindowView {
         }
     }
 
+    extern "C" fn keyboard_selection_did_change(this: &mut Object, _sel: Sel, _notification: id) {
+        crate::bump_keyboard_generation();
+        if let Some(this) = Self::get_this(this) {
+            this.inner
+                .borrow_mut()
+                .events
+                .dispatch(WindowEvent::KeyboardLayoutChanged);
+        }
+    }
+
     extern "C" fn update_tracking_areas(this: &mut Object, _sel: Sel) {
         let frame = unsafe { NSView::frame(this as *mut _) };
 
@@ -2280,7 +2459,7 @@ impl WindowView {
                     Ok(TranslateStatus::Composing(composing)) => {
                         // Next key press in dead key sequence is pending.
                         inner.events.dispatch(WindowEvent::AdviseDeadKeyStatus(
-                            DeadKeyStatus::Composing(composing),
+                            DeadKeyStatus::Composing(Composition::new(composing)),
                         ));
 
                         return;
@@ -2389,7 +2568,7 @@ impl WindowView {
                             // If it didn't generate an event, then a composition
                             // is pending.
                             let status = if inner.ime_last_event.is_none() {
-                                DeadKeyStatus::Composing(inner.ime_text.clone())
+                                DeadKeyStatus::Composing(inner.composition())
                             } else {
                                 DeadKeyStatus::None
                             };
@@ -2419,7 +2598,7 @@ impl WindowView {
                             let status = if inner.ime_text.is_empty() {
                                 DeadKeyStatus::None
                             } else {
-                                DeadKeyStatus::Composing(inner.ime_text.clone())
+                                DeadKeyStatus::Composing(inner.composition())
                             };
                             inner
                                 .events
@@ -2677,15 +2856,15 @@ impl WindowView {
                 _ => WindowState::default(),
             };
 
+            let scale = backing_frame.size.width / frame.size.width;
             let dpi = inner
                 .window
                 .as_ref()
-                .and_then(|window| {
+                .map(|window| {
                     let window = window.load();
-                    dpi_for_window_screen(*window, &inner.config)
+                    dpi_for_window_screen(*window, &inner.config, scale)
                 })
-                .unwrap_or(crate::DEFAULT_DPI * (backing_frame.size.width / frame.size.width))
-                as usize;
+                .unwrap_or(crate::DEFAULT_DPI * scale) as usize;
 
             inner.events.dispatch(WindowEvent::Resized {
                 dimensions: Dimensions {
@@ -3071,6 +3250,11 @@ impl WindowView {
                 Self::view_did_change_effective_appearance as extern "C" fn(&mut Object, Sel),
             );
 
+            cls.add_method(
+                sel!(keyboardSelectionDidChange:),
+                Self::keyboard_selection_did_change as extern "C" fn(&mut Object, Sel, id),
+            );
+
             cls.add_method(
                 sel!(updateTrackingAreas),
                 Self::update_tracking_areas as extern "C" fn(&mut Object, Sel),